@@ -1,7 +1,7 @@
 #!/bin/bash
 set -e
 
-VERSION="1.1.5"  # Change this
+VERSION="1.2.0"  # Change this
 
 # Create temp folder
 rm -rf aur-build
@@ -1,15 +1,19 @@
 #!/bin/bash
 set -e
 
-VERSION="1.1.5"  # Change this
+VERSION="1.2.0"  # Change this
 
 rm -rf build
-mkdir -p build/DEBIAN build/usr/bin
+mkdir -p build/DEBIAN build/usr/bin build/usr/share/man/man1
 
 # Build binary
 cargo build --release
 cp target/release/ppmm build/usr/bin/ppmm
 
+# Man pages, rendered from the CLI definition
+target/release/ppmm man --out-dir build/usr/share/man/man1
+gzip -9n build/usr/share/man/man1/*.1
+
 # Copy control file
 cp packaging/deb/control build/DEBIAN/control
 
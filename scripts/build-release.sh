@@ -1,4 +1,9 @@
 #!/bin/bash
 # Script to build the release version
+# Pass --man to also write man pages to target/release/man
 
 cargo build --release
+
+if [ "$1" = "--man" ]; then
+    target/release/ppmm man --out-dir target/release/man
+fi
@@ -1,7 +1,7 @@
 #!/bin/bash
 set -e
 
-VERSION="1.1.5"  # Change this
+VERSION="1.2.0"  # Change this
 
 cd releases/windows
 zip -r ppmm-windows-x64.zip ppmm-windows-x64.exe
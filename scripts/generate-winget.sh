@@ -1,7 +1,7 @@
 #!/bin/bash
 set -e
 
-VERSION="1.1.5"  # Change this
+VERSION="1.2.0"  # Change this
 SHA256=$(sha256sum releases/windows/ppmm-windows-x64.zip | awk '{print $1}')
 
 cat > packaging/winget/ppmm.yaml <<EOL
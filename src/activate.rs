@@ -0,0 +1,177 @@
+//! The environment a project's venv is active in: what `[scripts]` run
+//! with, and what `ppm env --activate` prints for a shell to `eval`.
+//!
+//! Both come from [`VenvEnv`], so a script and an activated shell always
+//! see the same `VIRTUAL_ENV` and `PATH`.
+
+use crate::context::ProjectContext;
+use crate::paths::normalize;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::path::PathBuf;
+
+/// Where a project's venv and its executables are, as absolute paths
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VenvEnv {
+    /// The directory containing project.toml
+    pub project_root: PathBuf,
+    /// The venv, the value of `VIRTUAL_ENV`
+    pub venv_dir: PathBuf,
+    /// The venv's executable directory, put first on `PATH`
+    pub bin_dir: PathBuf,
+    /// The venv's python interpreter
+    pub python: PathBuf,
+}
+
+impl VenvEnv {
+    /// The environment of the venv of `ctx`, whether it exists yet or not
+    pub fn new(ctx: &ProjectContext) -> VenvEnv {
+        let absolute = |path: PathBuf| {
+            let path = if path.is_absolute() {
+                path
+            } else {
+                std::env::current_dir().unwrap_or_default().join(path)
+            };
+            normalize(&path)
+        };
+        VenvEnv {
+            project_root: absolute(ctx.root.clone()),
+            venv_dir: absolute(ctx.venv_dir()),
+            bin_dir: absolute(ctx.venv_bin_dir()),
+            python: absolute(ctx.venv_python()),
+        }
+    }
+
+    /// Variables to set for the venv to be active, given the current
+    /// `PATH`. `PATH` is left out when the bin directory can't be put on
+    /// it, like on Windows when the path holds a `;`.
+    pub fn vars(&self, current_path: &OsStr) -> Vec<(&'static str, OsString)> {
+        let mut vars = vec![("VIRTUAL_ENV", self.venv_dir.clone().into_os_string())];
+        let mut paths: Vec<PathBuf> = std::env::split_paths(current_path).collect();
+        paths.insert(0, self.bin_dir.clone());
+        if let Ok(path) = std::env::join_paths(paths) {
+            vars.push(("PATH", path));
+        }
+        vars
+    }
+
+    /// Lines that activate the venv in `shell`, keeping the rest of the
+    /// shell's `PATH`
+    pub fn activate_script(&self, shell: ActivateShell) -> String {
+        let venv = shell.quote(&self.venv_dir.to_string_lossy());
+        let bin = shell.quote(&self.bin_dir.to_string_lossy());
+        match shell {
+            ActivateShell::Bash => format!(
+                "export VIRTUAL_ENV={}\nexport PATH={}:\"$PATH\"\n",
+                venv, bin
+            ),
+            ActivateShell::Fish => {
+                format!("set -gx VIRTUAL_ENV {}\nset -gx PATH {} $PATH\n", venv, bin)
+            }
+            ActivateShell::Powershell => format!(
+                "$env:VIRTUAL_ENV = {}\n$env:PATH = {} + [IO.Path]::PathSeparator + $env:PATH\n",
+                venv, bin
+            ),
+        }
+    }
+}
+
+/// Shell `ppm env --activate` prints lines for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActivateShell {
+    /// bash, zsh and other POSIX shells
+    Bash,
+    /// fish
+    Fish,
+    /// PowerShell, on any OS
+    Powershell,
+}
+
+impl ActivateShell {
+    /// The shell called `name`, as on the command line
+    pub fn from_name(name: &str) -> Option<ActivateShell> {
+        match name {
+            "bash" => Some(ActivateShell::Bash),
+            "fish" => Some(ActivateShell::Fish),
+            "powershell" => Some(ActivateShell::Powershell),
+            _ => None,
+        }
+    }
+
+    /// `value` as one single-quoted word of this shell
+    pub fn quote(&self, value: &str) -> String {
+        match self {
+            ActivateShell::Bash => format!("'{}'", value.replace('\'', r"'\''")),
+            ActivateShell::Fish => format!("'{}'", value.replace('\\', r"\\").replace('\'', r"\'")),
+            ActivateShell::Powershell => format!("'{}'", value.replace('\'', "''")),
+        }
+    }
+}
+
+impl fmt::Display for ActivateShell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ActivateShell::Bash => "bash",
+            ActivateShell::Fish => "fish",
+            ActivateShell::Powershell => "powershell",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env() -> VenvEnv {
+        VenvEnv {
+            project_root: PathBuf::from("/home/me/My Project"),
+            venv_dir: PathBuf::from("/home/me/My Project/venv"),
+            bin_dir: PathBuf::from("/home/me/My Project/venv/bin"),
+            python: PathBuf::from("/home/me/My Project/venv/bin/python"),
+        }
+    }
+
+    #[test]
+    fn test_quote_per_shell() {
+        let value = r"it's C:\x";
+        assert_eq!(ActivateShell::Bash.quote(value), r"'it'\''s C:\x'");
+        assert_eq!(ActivateShell::Fish.quote(value), r"'it\'s C:\\x'");
+        assert_eq!(ActivateShell::Powershell.quote(value), r"'it''s C:\x'");
+        assert_eq!(ActivateShell::from_name("zsh"), None);
+    }
+
+    #[test]
+    fn test_activate_scripts() {
+        assert_eq!(
+            env().activate_script(ActivateShell::Bash),
+            "export VIRTUAL_ENV='/home/me/My Project/venv'\n\
+             export PATH='/home/me/My Project/venv/bin':\"$PATH\"\n"
+        );
+        assert_eq!(
+            env().activate_script(ActivateShell::Fish),
+            "set -gx VIRTUAL_ENV '/home/me/My Project/venv'\n\
+             set -gx PATH '/home/me/My Project/venv/bin' $PATH\n"
+        );
+        assert!(
+            env()
+                .activate_script(ActivateShell::Powershell)
+                .starts_with("$env:VIRTUAL_ENV = '/home/me/My Project/venv'\n")
+        );
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_vars_put_the_venv_first() {
+        let vars = env().vars(OsStr::new("/usr/bin:/bin"));
+        assert_eq!(
+            vars,
+            vec![
+                ("VIRTUAL_ENV", OsString::from("/home/me/My Project/venv")),
+                (
+                    "PATH",
+                    OsString::from("/home/me/My Project/venv/bin:/usr/bin:/bin")
+                ),
+            ]
+        );
+    }
+}
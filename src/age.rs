@@ -0,0 +1,204 @@
+//! How old releases are, from the upload days in their release history.
+//!
+//! Days are whole UTC days since the Unix epoch, so a release uploaded
+//! today is 0 days old. `ppm list --older-than` takes a duration like
+//! `180d`, `8w` or `6m`, see [`parse_duration`].
+
+use crate::index::ReleaseEntry;
+use crate::packages::{compare_versions, is_prerelease};
+use std::cmp::Ordering;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Days in a month, for `m` durations and ages
+const MONTH: u64 = 30;
+
+/// Days in a year, for ages
+const YEAR: u64 = 365;
+
+/// Days in a duration like `180d`, `8w` or `6m`, where a month is 30
+/// days
+pub fn parse_duration(text: &str) -> Result<u64, String> {
+    let text = text.trim();
+    let invalid = || {
+        format!(
+            "Invalid duration '{}'; use a number of days, weeks or months like 180d, 8w or 6m",
+            text
+        )
+    };
+    let unit = text.chars().last().ok_or_else(invalid)?;
+    let count: u64 = text[..text.len() - unit.len_utf8()]
+        .parse()
+        .map_err(|_| invalid())?;
+    let days = match unit.to_ascii_lowercase() {
+        'd' => 1,
+        'w' => 7,
+        'm' => MONTH,
+        _ => return Err(invalid()),
+    };
+    count.checked_mul(days).ok_or_else(invalid)
+}
+
+/// Days since the Unix epoch of a day like `2024-05-29`
+pub fn parse_day(day: &str) -> Option<u64> {
+    let mut parts = day.get(..10)?.split('-');
+    let year: u64 = parts.next()?.parse().ok()?;
+    let month: u64 = parts.next()?.parse().ok()?;
+    let day: u64 = parts.next()?.parse().ok()?;
+    if year < 1970 || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    // A civil date to days, after Howard Hinnant's `days_from_civil`
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let yoe = year - era * 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    Some(era * 146_097 + doe - 719_468)
+}
+
+/// Today, in days since the Unix epoch
+pub fn today() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() / 86_400)
+}
+
+/// How many days before `today` the day `uploaded` was
+pub fn age_in_days(uploaded: &str, today: u64) -> Option<u64> {
+    Some(today.saturating_sub(parse_day(uploaded)?))
+}
+
+/// An age in days as `12d`, `5w`, `8m` or `2y 3m`
+pub fn format_age(days: u64) -> String {
+    if days < 14 {
+        format!("{}d", days)
+    } else if days < 2 * MONTH {
+        format!("{}w", days / 7)
+    } else if days < YEAR {
+        format!("{}m", days / MONTH)
+    } else if days % YEAR < MONTH {
+        format!("{}y", days / YEAR)
+    } else {
+        format!("{}y {}m", days / YEAR, days % YEAR / MONTH)
+    }
+}
+
+/// The upload day of `version` in `history`
+pub fn uploaded<'a>(history: &'a [ReleaseEntry], version: &str) -> Option<&'a str> {
+    history
+        .iter()
+        .find(|release| compare_versions(&release.version, version) == Ordering::Equal)?
+        .uploaded
+        .as_deref()
+}
+
+/// The newest release in `history` that isn't yanked or a pre-release,
+/// which is what the index reports as latest
+pub fn latest_release(history: &[ReleaseEntry]) -> Option<&ReleaseEntry> {
+    history
+        .iter()
+        .filter(|release| !release.yanked && !is_prerelease(&release.version))
+        .max_by(|a, b| compare_versions(&a.version, &b.version))
+}
+
+/// Upload days of a package's pinned and latest releases
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReleaseDays {
+    /// Day the pinned version was uploaded
+    pub pinned: Option<String>,
+    /// Day the latest version was uploaded
+    pub latest: Option<String>,
+}
+
+impl ReleaseDays {
+    /// The days of `pinned` and `latest` in `history`
+    pub fn find(history: &[ReleaseEntry], pinned: Option<&str>, latest: Option<&str>) -> Self {
+        let day = |version: Option<&str>| Some(uploaded(history, version?)?.to_string());
+        ReleaseDays {
+            pinned: day(pinned),
+            latest: day(latest),
+        }
+    }
+
+    /// Age of the pinned release on `today`
+    pub fn pinned_age(&self, today: u64) -> Option<u64> {
+        age_in_days(self.pinned.as_deref()?, today)
+    }
+
+    /// Like `pinned 2022-03-01, latest 2024-11-20`, leaving out the latest
+    /// when it's the same day; empty when neither is known
+    pub fn describe(&self) -> String {
+        match (&self.pinned, &self.latest) {
+            (Some(pinned), Some(latest)) if pinned != latest => {
+                format!("pinned {}, latest {}", pinned, latest)
+            }
+            (Some(pinned), _) => format!("pinned {}", pinned),
+            (None, Some(latest)) => format!("latest {}", latest),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::release_entries;
+
+    const RELEASES: &str = include_str!("../tests/fixtures/pypi-releases.json");
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("180d"), Ok(180));
+        assert_eq!(parse_duration("8w"), Ok(56));
+        assert_eq!(parse_duration("6m"), Ok(180));
+        assert_eq!(parse_duration(" 0D "), Ok(0));
+        for invalid in ["", "d", "180", "1y", "-3d", "1.5w", "99999999999999999999m"] {
+            assert!(parse_duration(invalid).is_err(), "{}", invalid);
+        }
+        assert!(parse_duration("6x").unwrap_err().contains("180d, 8w or 6m"));
+    }
+
+    #[test]
+    fn test_parse_day_matches_the_timestamp_format() {
+        assert_eq!(parse_day("1970-01-01"), Some(0));
+        assert_eq!(parse_day("2024-02-29"), Some(19_782));
+        assert_eq!(parse_day("2024-03-01T09:30:00Z"), Some(19_783));
+        for days in [0, 59, 60, 365, 11_016, 19_782, 20_000] {
+            let day = crate::history::format_timestamp(days * 86_400);
+            assert_eq!(parse_day(&day), Some(days), "{}", day);
+        }
+        assert_eq!(parse_day("2024-13-01"), None);
+        assert_eq!(parse_day("yesterday"), None);
+        assert_eq!(age_in_days("2024-05-29", 19_877), Some(5));
+        assert_eq!(age_in_days("2024-05-29", 0), Some(0));
+    }
+
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(0), "0d");
+        assert_eq!(format_age(13), "13d");
+        assert_eq!(format_age(20), "2w");
+        assert_eq!(format_age(200), "6m");
+        assert_eq!(format_age(370), "1y");
+        assert_eq!(format_age(365 * 2 + 95), "2y 3m");
+    }
+
+    #[test]
+    fn test_upload_days_from_the_history() {
+        let history = release_entries(&serde_json::from_str(RELEASES).unwrap());
+        assert_eq!(uploaded(&history, "2.32.0"), Some("2024-05-20"));
+        assert_eq!(uploaded(&history, "9.9.9"), None);
+        // Neither the yanked 2.32.0 nor the 3.0.0b1 pre-release
+        assert_eq!(latest_release(&history).unwrap().version, "2.32.3");
+
+        let days = ReleaseDays::find(&history, Some("2.31.0"), Some("2.32.3"));
+        assert_eq!(days.describe(), "pinned 2023-05-22, latest 2024-05-29");
+        assert_eq!(days.pinned_age(parse_day("2024-05-22").unwrap()), Some(366));
+        let current = ReleaseDays::find(&history, Some("2.32.3"), Some("2.32.3"));
+        assert_eq!(current.describe(), "pinned 2024-05-29");
+        let unknown = ReleaseDays::find(&history, Some("9.9.9"), None);
+        assert_eq!(unknown.describe(), "");
+        assert_eq!(unknown.pinned_age(20_000), None);
+    }
+}
@@ -0,0 +1,330 @@
+//! `[aliases]`: short names for ppm command lines, from project.toml and
+//! the global config.toml.
+//!
+//! ```toml
+//! [aliases]
+//! s = "start"
+//! t = "run test -- -x"
+//! ```
+//!
+//! An alias is expanded where the subcommand goes, before the arguments
+//! are parsed. Its words are split the way a shell would, so quoted
+//! arguments stay whole, and whatever follows the alias on the command
+//! line goes after its expansion. Aliases may use other aliases, up to
+//! [`MAX_DEPTH`] deep, but never themselves. A project's alias overrides
+//! a global one of the same name; neither may take a built-in command's
+//! name.
+
+use crate::error::PpmError;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// How many aliases one command line may go through
+pub const MAX_DEPTH: usize = 8;
+
+/// Where an alias is defined
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// The project's project.toml
+    Project,
+    /// The user's config.toml
+    Global,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Source::Project => "project",
+            Source::Global => "global",
+        })
+    }
+}
+
+/// What an alias stands for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alias {
+    /// The command line it expands to, without `ppm`
+    pub expansion: String,
+    /// Where it's defined
+    pub source: Source,
+}
+
+/// Alias name to alias, ordered by name
+pub type Aliases = BTreeMap<String, Alias>;
+
+/// The effective aliases: `global`'s, overridden by `project`'s
+pub fn merge(global: &BTreeMap<String, String>, project: &BTreeMap<String, String>) -> Aliases {
+    let with_source = |aliases: &BTreeMap<String, String>, source: Source| {
+        aliases
+            .iter()
+            .map(|(name, expansion)| {
+                let alias = Alias {
+                    expansion: expansion.clone(),
+                    source,
+                };
+                (name.clone(), alias)
+            })
+            .collect::<Vec<_>>()
+    };
+    with_source(global, Source::Global)
+        .into_iter()
+        .chain(with_source(project, Source::Project))
+        .collect()
+}
+
+/// The `[aliases]` table of the project.toml at `path`, empty when the
+/// file is missing or isn't valid TOML; the command that reads the whole
+/// file reports that
+pub fn load_project_aliases(path: &Path) -> Result<BTreeMap<String, String>, PpmError> {
+    let Ok(toml_string) = fs::read_to_string(path) else {
+        return Ok(BTreeMap::new());
+    };
+    let Ok(mut table) = toml::from_str::<toml::Table>(&toml_string) else {
+        return Ok(BTreeMap::new());
+    };
+    let Some(aliases) = table.remove("aliases") else {
+        return Ok(BTreeMap::new());
+    };
+    aliases.try_into().map_err(|source| PpmError::ConfigParse {
+        path: path.to_string_lossy().into_owned(),
+        source,
+    })
+}
+
+/// Fail when an alias takes the name of one of `builtins`
+pub fn check_shadowing(aliases: &Aliases, builtins: &[&str]) -> Result<(), PpmError> {
+    match aliases
+        .iter()
+        .find(|(name, _)| builtins.contains(&name.as_str()))
+    {
+        Some((name, alias)) => Err(PpmError::Config(format!(
+            "The {} alias '{}' has the name of a built-in command; rename it",
+            alias.source, name
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Split `line` into words like a POSIX shell: whitespace separates
+/// words, quotes and backslashes keep them together. Nothing else is
+/// special.
+pub fn split_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_default();
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated ' quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_default();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err("unterminated \" quote".to_string()),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated \" quote".to_string()),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some(c) => word.get_or_insert_default().push(c),
+                None => return Err("trailing backslash".to_string()),
+            },
+            c => word.get_or_insert_default().push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// Index of the subcommand in `args`: the first word that isn't a flag
+/// or the value of one of `value_flags`
+fn command_position(args: &[String], value_flags: &[&str]) -> Option<usize> {
+    let mut i = 0;
+    while i < args.len() {
+        let arg = args[i].as_str();
+        if arg == "--" {
+            return None;
+        }
+        if !arg.starts_with('-') {
+            return Some(i);
+        }
+        i += if value_flags.contains(&arg) { 2 } else { 1 };
+    }
+    None
+}
+
+/// `args`, the command line without the program name, with the alias in
+/// the subcommand's place expanded. `builtins` are never expanded and
+/// `value_flags` are the global flags that take a value, like
+/// `--project`.
+pub fn expand(
+    args: &[String],
+    aliases: &Aliases,
+    builtins: &[&str],
+    value_flags: &[&str],
+) -> Result<Vec<String>, PpmError> {
+    let mut args = args.to_vec();
+    let mut chain: Vec<String> = vec![];
+    while let Some(pos) = command_position(&args, value_flags) {
+        let name = args[pos].clone();
+        if builtins.contains(&name.as_str()) {
+            break;
+        }
+        let Some(alias) = aliases.get(&name) else {
+            break;
+        };
+        if chain.contains(&name) {
+            chain.push(name);
+            return Err(PpmError::Config(format!(
+                "Alias cycle: {}",
+                chain.join(" -> ")
+            )));
+        }
+        if chain.len() == MAX_DEPTH {
+            return Err(PpmError::Config(format!(
+                "Alias '{}' goes through more than {} aliases",
+                chain[0], MAX_DEPTH
+            )));
+        }
+        let words = split_words(&alias.expansion)
+            .map_err(|e| PpmError::Config(format!("Alias '{}': {}", name, e)))?;
+        if words.is_empty() {
+            return Err(PpmError::Config(format!("Alias '{}' is empty", name)));
+        }
+        args.splice(pos..=pos, words);
+        chain.push(name);
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUILTINS: &[&str] = &["start", "run", "install", "test"];
+    const VALUE_FLAGS: &[&str] = &["--project", "--progress"];
+
+    fn aliases(entries: &[(&str, &str)]) -> Aliases {
+        let project = entries
+            .iter()
+            .map(|(name, expansion)| (name.to_string(), expansion.to_string()))
+            .collect();
+        merge(&BTreeMap::new(), &project)
+    }
+
+    fn expand_line(line: &str, aliases: &Aliases) -> Result<String, PpmError> {
+        let args = split_words(line).unwrap();
+        expand(&args, aliases, BUILTINS, VALUE_FLAGS).map(|args| args.join("|"))
+    }
+
+    #[test]
+    fn test_split_words() {
+        assert_eq!(
+            split_words(r#"run test -- -k "not slow" 'a b'"#).unwrap(),
+            vec!["run", "test", "--", "-k", "not slow", "a b"]
+        );
+        assert_eq!(
+            split_words(r#"  a\ b "x\"y" '' c  "#).unwrap(),
+            vec!["a b", "x\"y", "", "c"]
+        );
+        assert!(split_words("run 'oops").is_err());
+        assert!(split_words("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_expand_nested_aliases_and_passthrough() {
+        let aliases = aliases(&[
+            ("s", "start"),
+            ("t", "run test -- -k 'not slow'"),
+            ("tt", "t -x"),
+        ]);
+        assert_eq!(
+            expand_line("s -- --port 8000", &aliases).unwrap(),
+            "start|--|--port|8000"
+        );
+        assert_eq!(
+            expand_line("--project api tt -v", &aliases).unwrap(),
+            "--project|api|run|test|--|-k|not slow|-x|-v"
+        );
+        // Built-ins, unknown names and words after the subcommand stay as they are
+        assert_eq!(expand_line("run s", &aliases).unwrap(), "run|s");
+        assert_eq!(expand_line("deploy s", &aliases).unwrap(), "deploy|s");
+        assert_eq!(expand_line("--list", &aliases).unwrap(), "--list");
+    }
+
+    #[test]
+    fn test_expand_rejects_cycles_and_deep_chains() {
+        let cycle = aliases(&[("a", "b"), ("b", "c --flag"), ("c", "a")]);
+        let err = expand_line("a", &cycle).unwrap_err();
+        assert_eq!(err.to_string(), "Alias cycle: a -> b -> c -> a");
+        assert_eq!(err.exit_code(), 2);
+        assert!(expand_line("b", &aliases(&[("b", "b")])).is_err());
+
+        let chain: Vec<(String, String)> = (0..=MAX_DEPTH)
+            .map(|i| (format!("a{}", i), format!("a{}", i + 1)))
+            .collect();
+        let chain: Vec<(&str, &str)> = chain
+            .iter()
+            .map(|(name, expansion)| (name.as_str(), expansion.as_str()))
+            .collect();
+        assert!(expand_line("a0", &aliases(&chain)).is_err());
+        assert!(expand_line("a2", &aliases(&chain)).is_ok());
+        assert!(expand_line("e", &aliases(&[("e", " ")])).is_err());
+    }
+
+    #[test]
+    fn test_merge_and_shadowing() {
+        let global = BTreeMap::from([
+            ("s".to_string(), "start".to_string()),
+            ("i".to_string(), "install".to_string()),
+        ]);
+        let project = BTreeMap::from([("s".to_string(), "start -- --dev".to_string())]);
+        let merged = merge(&global, &project);
+        assert_eq!(merged["s"].source, Source::Project);
+        assert_eq!(merged["s"].expansion, "start -- --dev");
+        assert_eq!(merged["i"].source, Source::Global);
+        assert!(check_shadowing(&merged, BUILTINS).is_ok());
+
+        let shadow = merge(
+            &BTreeMap::new(),
+            &BTreeMap::from([("test".to_string(), "run t".to_string())]),
+        );
+        let err = check_shadowing(&shadow, BUILTINS).unwrap_err();
+        assert!(err.to_string().contains("'test'"));
+    }
+
+    #[test]
+    fn test_load_project_aliases() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("project.toml");
+        assert!(load_project_aliases(&path).unwrap().is_empty());
+        fs::write(
+            &path,
+            "[project]\nname = \"demo\"\n\n[aliases]\ns = \"start\"\n",
+        )
+        .unwrap();
+        assert_eq!(load_project_aliases(&path).unwrap()["s"], "start");
+        fs::write(&path, "[aliases]\ns = 1\n").unwrap();
+        assert!(load_project_aliases(&path).is_err());
+    }
+}
@@ -0,0 +1,202 @@
+//! `ppm build` for projects with a `pyproject.toml`: runs
+//! `python -m build` and reports the distributions it wrote.
+//!
+//! The `build` frontend is installed on demand, by default into a venv of
+//! its own under `.ppm/cache/` so it never ends up among the project's
+//! packages. Artifacts are the files in the output directory that are
+//! new or were rewritten by the run, so older builds lying around in
+//! `dist/` aren't reported again.
+
+use crate::verify::sha256_hex;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// The file that makes a project buildable with `python -m build`
+pub const PYPROJECT_FILE: &str = "pyproject.toml";
+
+/// Where distributions go unless `--out-dir` says otherwise
+pub const DEFAULT_OUT_DIR: &str = "dist";
+
+/// The venv holding `build`, inside the project's `.ppm/cache/`
+pub const BUILD_ENV_DIR: &str = "build-env";
+
+/// Lines of backend output shown when a build fails
+pub const FAILURE_TAIL_LINES: usize = 20;
+
+/// Which distributions to build
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Targets {
+    /// A wheel and an sdist
+    Both,
+    /// Only a wheel
+    Wheel,
+    /// Only an sdist
+    Sdist,
+}
+
+impl Targets {
+    /// From the `--wheel-only` and `--sdist-only` flags
+    pub fn from_flags(wheel_only: bool, sdist_only: bool) -> Targets {
+        match (wheel_only, sdist_only) {
+            (true, false) => Targets::Wheel,
+            (false, true) => Targets::Sdist,
+            _ => Targets::Both,
+        }
+    }
+}
+
+/// Arguments to the venv's python that build `targets` into `out_dir`
+pub fn build_args(targets: Targets, out_dir: &Path) -> Vec<OsString> {
+    let mut args: Vec<OsString> = vec!["-m".into(), "build".into()];
+    if targets != Targets::Sdist {
+        args.push("--wheel".into());
+    }
+    if targets != Targets::Wheel {
+        args.push("--sdist".into());
+    }
+    args.push("--outdir".into());
+    args.push(out_dir.into());
+    args
+}
+
+/// A distribution the build wrote
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Artifact {
+    /// Where the file is
+    pub path: PathBuf,
+    /// Bytes on disk
+    pub size: u64,
+    /// Hex SHA-256 of the contents
+    pub sha256: String,
+}
+
+/// Modification times of the files in a directory before a build
+pub type Snapshot = HashMap<PathBuf, SystemTime>;
+
+/// Files directly inside `dir` with their modification times, empty when
+/// `dir` doesn't exist yet
+pub fn snapshot(dir: &Path) -> Snapshot {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Snapshot::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            if !metadata.is_file() {
+                return None;
+            }
+            Some((entry.path(), metadata.modified().ok()?))
+        })
+        .collect()
+}
+
+/// Files in `dir` that aren't in `before` or changed since, ordered by
+/// name
+pub fn artifacts_since(dir: &Path, before: &Snapshot) -> io::Result<Vec<Artifact>> {
+    let mut paths: Vec<PathBuf> = snapshot(dir)
+        .into_iter()
+        .filter(|(path, modified)| before.get(path) != Some(modified))
+        .map(|(path, _)| path)
+        .collect();
+    paths.sort();
+    paths
+        .into_iter()
+        .map(|path| {
+            let data = fs::read(&path)?;
+            Ok(Artifact {
+                size: data.len() as u64,
+                sha256: sha256_hex(&data),
+                path,
+            })
+        })
+        .collect()
+}
+
+/// The last `count` non-blank lines of `output`
+pub fn tail(output: &str, count: usize) -> Vec<&str> {
+    let lines: Vec<&str> = output
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    lines[lines.len().saturating_sub(count)..].to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_args() {
+        let out = Path::new("dist");
+        let args = |targets| {
+            build_args(targets, out)
+                .into_iter()
+                .map(|arg| arg.into_string().unwrap())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        assert_eq!(
+            args(Targets::from_flags(false, false)),
+            "-m build --wheel --sdist --outdir dist"
+        );
+        assert_eq!(
+            args(Targets::from_flags(true, false)),
+            "-m build --wheel --outdir dist"
+        );
+        assert_eq!(
+            args(Targets::from_flags(false, true)),
+            "-m build --sdist --outdir dist"
+        );
+    }
+
+    #[test]
+    fn test_only_new_and_rewritten_files_are_artifacts() {
+        let dir = tempfile::tempdir().unwrap();
+        let dist = dir.path().join("dist");
+        assert!(snapshot(&dist).is_empty());
+
+        fs::create_dir_all(&dist).unwrap();
+        let old = dist.join("demo-0.1.0.tar.gz");
+        let rebuilt = dist.join("demo-0.2.0-py3-none-any.whl");
+        fs::write(&old, "old").unwrap();
+        fs::write(&rebuilt, "stale").unwrap();
+        let mut before = snapshot(&dist);
+        // Pretend the wheel was written long ago, as a real rebuild would
+        // leave it with a different mtime
+        before.insert(rebuilt.clone(), SystemTime::UNIX_EPOCH);
+
+        fs::write(&rebuilt, "wheel").unwrap();
+        fs::write(dist.join("demo-0.2.0.tar.gz"), "sdist").unwrap();
+        fs::create_dir_all(dist.join("subdir")).unwrap();
+
+        let artifacts = artifacts_since(&dist, &before).unwrap();
+        assert_eq!(
+            artifacts,
+            vec![
+                Artifact {
+                    path: rebuilt,
+                    size: 5,
+                    sha256: sha256_hex(b"wheel"),
+                },
+                Artifact {
+                    path: dist.join("demo-0.2.0.tar.gz"),
+                    size: 5,
+                    sha256: sha256_hex(b"sdist"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tail() {
+        let output = "one\n\ntwo\nthree\n  \nfour\n";
+        assert_eq!(tail(output, 2), vec!["three", "four"]);
+        assert_eq!(tail(output, 10), vec!["one", "two", "three", "four"]);
+        assert!(tail("", 3).is_empty());
+    }
+}
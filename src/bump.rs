@@ -0,0 +1,224 @@
+//! What `ppm bump` changes besides `version` in project.toml: the
+//! `__version__` assignment in the project's source, and a git tag.
+//!
+//! The source is only rewritten where it plainly assigns a string literal
+//! at the top level, like `__version__ = "1.2.0"`; anything computed is
+//! left for the user to update.
+
+use crate::error::PpmError;
+use crate::settings::Project;
+use std::path::{Path, PathBuf};
+
+/// Prefix of the tag `ppm bump --tag` creates, unless `--tag-prefix` says
+/// otherwise
+pub const DEFAULT_TAG_PREFIX: &str = "v";
+
+/// A `__version__` assignment found in a source file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionAssignment {
+    /// Line the assignment is on, counting from 0
+    pub line: usize,
+    /// The version it assigns
+    pub version: String,
+}
+
+/// The first top-level `__version__ = "..."` in `source`. Single and
+/// double quotes and a `: str` annotation are accepted; a trailing
+/// comment is allowed, anything else after the literal isn't.
+pub fn find_assignment(source: &str) -> Option<VersionAssignment> {
+    source.lines().enumerate().find_map(|(line, text)| {
+        let (_, version, _) = split_assignment(text)?;
+        Some(VersionAssignment {
+            line,
+            version: version.to_string(),
+        })
+    })
+}
+
+/// `text` as the part before the version literal's contents, the
+/// contents, and the part after them, when it is a plain assignment
+fn split_assignment(text: &str) -> Option<(&str, &str, &str)> {
+    let rest = text.strip_prefix("__version__")?;
+    let mut rest = rest.trim_start();
+    if let Some(annotated) = rest.strip_prefix(':') {
+        rest = annotated.trim_start().strip_prefix("str")?.trim_start();
+    }
+    let rest = rest.strip_prefix('=')?.trim_start();
+    let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let literal = &rest[1..];
+    let end = literal.find(quote)?;
+    let version = &literal[..end];
+    if version.is_empty() || version.contains(['\\', '"', '\'']) {
+        return None;
+    }
+    let tail = literal[end + 1..].trim();
+    if !(tail.is_empty() || tail.starts_with('#')) {
+        return None;
+    }
+    let start = text.len() - literal.len();
+    Some((&text[..start], version, &text[start + end..]))
+}
+
+/// `source` with its first top-level `__version__` assignment set to
+/// `version`, keeping the quotes, comment and line endings; `None` when
+/// there is no such assignment
+pub fn rewrite_version(source: &str, version: &str) -> Option<String> {
+    let found = find_assignment(source)?;
+    let mut rewritten = String::with_capacity(source.len());
+    for (i, line) in source.split_inclusive('\n').enumerate() {
+        if i == found.line {
+            let (before, _, after) = split_assignment(line.trim_end_matches(['\r', '\n']))?;
+            rewritten.push_str(before);
+            rewritten.push_str(version);
+            rewritten.push_str(after);
+            rewritten.push_str(&line[line.trim_end_matches(['\r', '\n']).len()..]);
+        } else {
+            rewritten.push_str(line);
+        }
+    }
+    Some(rewritten)
+}
+
+/// Files that may hold the project's `__version__`, relative to its root:
+/// the main script, then `<name>/__init__.py` and `src/<name>/__init__.py`
+pub fn version_file_candidates(project: &Project) -> Vec<PathBuf> {
+    let module = project.name.replace(['-', '.'], "_").to_lowercase();
+    vec![
+        PathBuf::from(project.main_script.trim_start_matches("./")),
+        Path::new(&module).join("__init__.py"),
+        Path::new("src").join(&module).join("__init__.py"),
+    ]
+}
+
+/// The file under `root` whose `__version__` `ppm bump --sync-source`
+/// rewrites, with its contents: `version-file` when set, otherwise the
+/// first of [`version_file_candidates`] with an assignment
+pub fn find_version_file(root: &Path, project: &Project) -> Result<(PathBuf, String), PpmError> {
+    if let Some(file) = &project.version_file {
+        let path = root.join(file);
+        let source = std::fs::read_to_string(&path).map_err(|e| {
+            PpmError::Config(format!("Failed to read version-file {}: {}", file, e))
+        })?;
+        if find_assignment(&source).is_none() {
+            return Err(PpmError::Config(format!(
+                "version-file {} has no `__version__ = \"...\"` line",
+                file
+            )));
+        }
+        return Ok((path, source));
+    }
+    let candidates = version_file_candidates(project);
+    for candidate in &candidates {
+        let path = root.join(candidate);
+        if let Ok(source) = std::fs::read_to_string(&path)
+            && find_assignment(&source).is_some()
+        {
+            return Ok((path, source));
+        }
+    }
+    Err(PpmError::Config(format!(
+        "No `__version__ = \"...\"` line in {}; set version-file under [project] to the file that has it",
+        candidates
+            .iter()
+            .map(|candidate| candidate.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rewrite_double_and_single_quotes() {
+        assert_eq!(
+            rewrite_version("__version__ = \"0.1.0\"\n", "0.2.0").as_deref(),
+            Some("__version__ = \"0.2.0\"\n")
+        );
+        assert_eq!(
+            rewrite_version("__version__='0.1.0'  # bumped by ppm\n", "1.0.0").as_deref(),
+            Some("__version__='1.0.0'  # bumped by ppm\n")
+        );
+        assert_eq!(
+            rewrite_version("__version__: str = \"0.1.0\"\r\n", "0.1.1").as_deref(),
+            Some("__version__: str = \"0.1.1\"\r\n")
+        );
+    }
+
+    #[test]
+    fn test_rewrite_only_the_top_level_assignment() {
+        let source = "\
+\"\"\"Demo package.\"\"\"
+
+import os
+
+__version_info__ = (0, 1, 0)
+__version__ = \"0.1.0\"
+__version__ = \"shadowed\"
+
+
+def version():
+    __version__ = \"9.9.9\"
+    return __version__
+";
+        let rewritten = rewrite_version(source, "0.2.0").unwrap();
+        assert_eq!(
+            rewritten,
+            source.replacen("__version__ = \"0.1.0\"", "__version__ = \"0.2.0\"", 1)
+        );
+        assert_eq!(
+            find_assignment(source),
+            Some(VersionAssignment {
+                line: 5,
+                version: "0.1.0".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_computed_versions_are_left_alone() {
+        for source in [
+            "__version__ = get_version()\n",
+            "__version__ = \"0.1\" + suffix\n",
+            "__version__ = f\"{major}.0\"\n",
+            "__version__ = \"0.1.0'\n",
+            "    __version__ = \"0.1.0\"\n",
+            "version = \"0.1.0\"\n",
+        ] {
+            assert_eq!(rewrite_version(source, "0.2.0"), None, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_find_version_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut project = Project::new(
+            "my-lib".to_string(),
+            "0.1.0".to_string(),
+            String::new(),
+            "./main.py".to_string(),
+            None,
+        );
+        std::fs::write(dir.path().join("main.py"), "print('hi')\n").unwrap();
+        assert!(matches!(
+            find_version_file(dir.path(), &project),
+            Err(PpmError::Config(_))
+        ));
+
+        std::fs::create_dir_all(dir.path().join("src/my_lib")).unwrap();
+        std::fs::write(
+            dir.path().join("src/my_lib/__init__.py"),
+            "__version__ = \"0.1.0\"\n",
+        )
+        .unwrap();
+        let (path, _) = find_version_file(dir.path(), &project).unwrap();
+        assert_eq!(path, dir.path().join("src/my_lib/__init__.py"));
+
+        project.version_file = Some("main.py".to_string());
+        assert!(matches!(
+            find_version_file(dir.path(), &project),
+            Err(PpmError::Config(_))
+        ));
+    }
+}
@@ -0,0 +1,673 @@
+//! `ppm bundle`: a project and every distribution it locks in one
+//! `.tar.gz`, to install on a machine without network access.
+//!
+//! An archive holds [`MANIFEST_FILE`], project.toml, ppmm.lock and the
+//! downloaded files under [`WHEELS_DIR`]. The manifest records where the
+//! bundle was made and the sha256 of every file, so installing can refuse
+//! wheels built for another OS, CPU or Python before anything is
+//! installed ([`incompatible`]) and catch a damaged archive while
+//! extracting it ([`extract`]).
+
+use crate::downloads::Platform;
+use crate::error::PpmError;
+use crate::paths;
+use crate::resolver::LOCK_FILE;
+use crate::settings::PROJECT_CONFIG_FILE;
+use crate::verify::sha256_hex;
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Component, Path};
+
+/// The manifest inside an archive
+pub const MANIFEST_FILE: &str = "bundle.json";
+
+/// Directory of the distributions inside an archive
+pub const WHEELS_DIR: &str = "wheels";
+
+/// Where files are downloaded to or extracted, inside the project's
+/// `.ppm/`
+pub const BUNDLE_CACHE: &str = "cache/bundle";
+
+/// Version of the archive layout this ppm reads and writes
+pub const BUNDLE_FORMAT: u32 = 1;
+
+/// A distribution in a bundle
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundledFile {
+    /// Wheel or sdist file name
+    pub filename: String,
+    /// Hex sha256 digest of the file
+    pub sha256: String,
+}
+
+/// What `bundle.json` says about a bundle
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// [`BUNDLE_FORMAT`] of the ppm that made it
+    pub format: u32,
+    /// Version of the ppm that made it
+    pub ppm_version: String,
+    /// Project name
+    pub project: String,
+    /// Project version
+    pub version: String,
+    /// OS it was made on, as in [`std::env::consts::OS`]
+    pub os: String,
+    /// CPU it was made on, as in [`std::env::consts::ARCH`]
+    pub arch: String,
+    /// Python version of the venv it was made from, when known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub python: Option<String>,
+    /// Every distribution, sorted by file name
+    pub files: Vec<BundledFile>,
+}
+
+impl Manifest {
+    /// `linux x86_64 with Python 3.12.1`, or without the Python part
+    pub fn built_for(&self) -> String {
+        match &self.python {
+            Some(python) => format!("{} {} with Python {}", self.os, self.arch, python),
+            None => format!("{} {}", self.os, self.arch),
+        }
+    }
+}
+
+/// The machine a bundle is installed on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Target {
+    /// OS and CPU
+    pub platform: Platform,
+    /// Major and minor version of the interpreter the venv is created
+    /// with; `None` skips the Python checks
+    pub python: Option<(u32, u32)>,
+}
+
+impl Target {
+    /// `macos aarch64 with Python 3.11`, or without the Python part
+    pub fn describe(&self) -> String {
+        match self.python {
+            Some((major, minor)) => format!(
+                "{} {} with Python {}.{}",
+                self.platform.os, self.platform.arch, major, minor
+            ),
+            None => format!("{} {}", self.platform.os, self.platform.arch),
+        }
+    }
+}
+
+/// The major and minor number of a version like `3.12.1`
+pub fn python_version(version: &str) -> Option<(u32, u32)> {
+    let mut parts = version.trim().split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+/// The compatibility tags of a wheel file name, each tag set expanded:
+/// `six-1.16.0-py2.py3-none-any.whl` has the Python tags `py2` and `py3`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WheelTags {
+    /// Python tags like `py3` or `cp312`
+    pub python: Vec<String>,
+    /// ABI tags like `none`, `abi3` or `cp312`
+    pub abi: Vec<String>,
+    /// Platform tags like `any` or `manylinux_2_17_x86_64`
+    pub platform: Vec<String>,
+}
+
+impl WheelTags {
+    /// Tags of the wheel `filename`; `None` when it isn't a wheel
+    pub fn parse(filename: &str) -> Option<WheelTags> {
+        let parts: Vec<&str> = filename.strip_suffix(".whl")?.split('-').collect();
+        // name-version[-build]-python-abi-platform
+        if parts.len() < 5 {
+            return None;
+        }
+        let expand = |tags: &str| tags.split('.').map(str::to_string).collect();
+        let last = parts.len() - 1;
+        Some(WheelTags {
+            python: expand(parts[last - 2]),
+            abi: expand(parts[last - 1]),
+            platform: expand(parts[last]),
+        })
+    }
+
+    /// Whether pip installs the wheel on `target`
+    pub fn installs_on(&self, target: &Target) -> bool {
+        let platform = self
+            .platform
+            .iter()
+            .any(|tag| tag == "any" || target.platform.supports(tag));
+        let python = match target.python {
+            Some(version) => self.python.iter().any(|python| {
+                self.abi
+                    .iter()
+                    .any(|abi| python_tag_matches(python, abi, version))
+            }),
+            None => true,
+        };
+        platform && python
+    }
+}
+
+/// Whether the Python tag `python` with the ABI tag `abi` runs on CPython
+/// `major.minor`: `py3` and `py3X` up to the minor version, `cp3X` only
+/// for that exact version unless the wheel uses the stable `abi3`
+fn python_tag_matches(python: &str, abi: &str, (major, minor): (u32, u32)) -> bool {
+    let digits = python
+        .find(|c: char| c.is_ascii_digit())
+        .unwrap_or(python.len());
+    let (implementation, version) = python.split_at(digits);
+    let mut version = version.chars();
+    if version.next().and_then(|c| c.to_digit(10)) != Some(major) {
+        return false;
+    }
+    let tag_minor: Option<u32> = match version.as_str() {
+        "" => None,
+        rest => match rest.parse() {
+            Ok(minor) => Some(minor),
+            Err(_) => return false,
+        },
+    };
+    match (implementation, tag_minor) {
+        ("py", None) => true,
+        ("py", Some(tag_minor)) => tag_minor <= minor,
+        ("cp", Some(tag_minor)) if abi == "abi3" => tag_minor <= minor,
+        ("cp", Some(tag_minor)) => tag_minor == minor,
+        _ => false,
+    }
+}
+
+/// File names of the wheels in `manifest` that don't install on
+/// `target`. Source distributions are built while installing, so they
+/// never count.
+pub fn incompatible<'a>(manifest: &'a Manifest, target: &Target) -> Vec<&'a str> {
+    manifest
+        .files
+        .iter()
+        .filter(|file| {
+            WheelTags::parse(&file.filename).is_some_and(|tags| !tags.installs_on(target))
+        })
+        .map(|file| file.filename.as_str())
+        .collect()
+}
+
+/// Fail unless every wheel in `manifest` installs on `target`
+pub fn check_compatible(manifest: &Manifest, target: &Target) -> Result<(), PpmError> {
+    let incompatible = incompatible(manifest, target);
+    if incompatible.is_empty() {
+        return Ok(());
+    }
+    Err(PpmError::Other(format!(
+        "The bundle was made on {} and {} of its wheels can't be installed on {}: {}; create the bundle on a machine like this one",
+        manifest.built_for(),
+        incompatible.len(),
+        target.describe(),
+        incompatible.join(", ")
+    )))
+}
+
+/// The files in `dir` with their digests, sorted by name
+pub fn bundled_files(dir: &Path) -> io::Result<Vec<BundledFile>> {
+    let mut files = vec![];
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            files.push(BundledFile {
+                filename: entry.file_name().to_string_lossy().into_owned(),
+                sha256: sha256_hex(&fs::read(entry.path())?),
+            });
+        }
+    }
+    files.sort_by(|a, b| a.filename.cmp(&b.filename));
+    Ok(files)
+}
+
+/// Write the archive `archive` from the project at `root` and the files
+/// of `manifest`, which are in `wheels`
+pub fn write_archive(
+    archive: &Path,
+    manifest: &Manifest,
+    root: &Path,
+    wheels: &Path,
+) -> Result<(), PpmError> {
+    let encoder = GzEncoder::new(File::create(archive)?, Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+    let manifest_json = serde_json::to_vec_pretty(manifest)
+        .map_err(|e| PpmError::Other(format!("Could not write {}: {}", MANIFEST_FILE, e)))?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(manifest_json.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, MANIFEST_FILE, manifest_json.as_slice())?;
+    for name in [PROJECT_CONFIG_FILE, LOCK_FILE] {
+        builder.append_path_with_name(root.join(name), name)?;
+    }
+    for file in &manifest.files {
+        builder.append_path_with_name(
+            wheels.join(&file.filename),
+            format!("{}/{}", WHEELS_DIR, file.filename),
+        )?;
+    }
+    builder.into_inner()?.finish()?;
+    Ok(())
+}
+
+fn open(archive: &Path) -> Result<tar::Archive<GzDecoder<File>>, PpmError> {
+    let file = File::open(archive)
+        .map_err(|e| PpmError::Other(format!("Could not open {}: {}", archive.display(), e)))?;
+    Ok(tar::Archive::new(GzDecoder::new(file)))
+}
+
+fn not_a_bundle(archive: &Path, reason: impl std::fmt::Display) -> PpmError {
+    PpmError::Other(format!(
+        "{} is not a ppm bundle: {}",
+        archive.display(),
+        reason
+    ))
+}
+
+/// The text of the file `name` at the top of the archive `archive`
+pub fn read_text(archive: &Path, name: &str) -> Result<Option<String>, PpmError> {
+    let mut tar = open(archive)?;
+    for entry in tar.entries().map_err(|e| not_a_bundle(archive, e))? {
+        let mut entry = entry.map_err(|e| not_a_bundle(archive, e))?;
+        if entry.path()?.as_ref() == Path::new(name) {
+            let mut text = String::new();
+            entry.read_to_string(&mut text)?;
+            return Ok(Some(text));
+        }
+    }
+    Ok(None)
+}
+
+/// The manifest of the archive `archive`, checked to be in a format this
+/// ppm reads and to list nothing but plain file names, which can't point
+/// out of the directory they are extracted to
+pub fn read_manifest(archive: &Path) -> Result<Manifest, PpmError> {
+    let json = read_text(archive, MANIFEST_FILE)?
+        .ok_or_else(|| not_a_bundle(archive, format!("it has no {}", MANIFEST_FILE)))?;
+    let manifest: Manifest = serde_json::from_str(&json).map_err(|e| not_a_bundle(archive, e))?;
+    if manifest.format > BUNDLE_FORMAT {
+        return Err(PpmError::Other(format!(
+            "{} was made by ppm {}, which writes a newer bundle format; upgrade ppm to install it",
+            archive.display(),
+            manifest.ppm_version
+        )));
+    }
+    if let Some(file) = manifest
+        .files
+        .iter()
+        .find(|file| !is_plain_file_name(&file.filename))
+    {
+        return Err(not_a_bundle(
+            archive,
+            format!(
+                "{} lists '{}', which isn't a plain file name",
+                MANIFEST_FILE, file.filename
+            ),
+        ));
+    }
+    Ok(manifest)
+}
+
+/// Whether `name` is a single file name: no directories, `..`, absolute
+/// path or drive prefix
+fn is_plain_file_name(name: &str) -> bool {
+    !name.contains(['/', '\\'])
+        && matches!(
+            Path::new(name).components().collect::<Vec<_>>().as_slice(),
+            [Component::Normal(part)] if *part == name
+        )
+}
+
+/// Extract the archive `archive`: project.toml and ppmm.lock into `root`,
+/// the files of `manifest` into `wheels`. Each file must be listed in the
+/// manifest with a matching digest and land inside `wheels`; anything else
+/// in the archive is an error rather than being written somewhere.
+pub fn extract(
+    archive: &Path,
+    manifest: &Manifest,
+    root: &Path,
+    wheels: &Path,
+) -> Result<(), PpmError> {
+    fs::create_dir_all(wheels)?;
+    let mut missing: HashSet<&str> = manifest
+        .files
+        .iter()
+        .map(|file| file.filename.as_str())
+        .chain([PROJECT_CONFIG_FILE, LOCK_FILE])
+        .collect();
+    let mut tar = open(archive)?;
+    for entry in tar.entries().map_err(|e| not_a_bundle(archive, e))? {
+        let mut entry = entry.map_err(|e| not_a_bundle(archive, e))?;
+        let name = entry.path()?.to_string_lossy().into_owned();
+        let mut contents = vec![];
+        entry.read_to_end(&mut contents)?;
+        if name == MANIFEST_FILE {
+            continue;
+        }
+        if name == PROJECT_CONFIG_FILE || name == LOCK_FILE {
+            fs::write(root.join(&name), contents)?;
+            missing.remove(name.as_str());
+            continue;
+        }
+        let file = name
+            .strip_prefix(WHEELS_DIR)
+            .and_then(|rest| rest.strip_prefix('/'))
+            .and_then(|filename| manifest.files.iter().find(|file| file.filename == filename))
+            .ok_or_else(|| not_a_bundle(archive, format!("unexpected entry '{}'", name)))?;
+        let target = wheels.join(&file.filename);
+        if !paths::is_within(wheels, &target) {
+            return Err(not_a_bundle(
+                archive,
+                format!("'{}' would be written outside {}", name, wheels.display()),
+            ));
+        }
+        if sha256_hex(&contents) != file.sha256 {
+            return Err(PpmError::Other(format!(
+                "{} in {} doesn't match its digest in {}; the archive is damaged",
+                file.filename,
+                archive.display(),
+                MANIFEST_FILE
+            )));
+        }
+        fs::write(target, contents)?;
+        missing.remove(file.filename.as_str());
+    }
+    let mut missing: Vec<&str> = missing.into_iter().collect();
+    missing.sort_unstable();
+    if !missing.is_empty() {
+        return Err(not_a_bundle(
+            archive,
+            format!("it lacks {}", missing.join(", ")),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINUX: Platform = Platform {
+        os: "linux",
+        arch: "x86_64",
+        musl: false,
+    };
+
+    const MAC_ARM: Platform = Platform {
+        os: "macos",
+        arch: "aarch64",
+        musl: false,
+    };
+
+    fn manifest(files: &[&str]) -> Manifest {
+        Manifest {
+            format: BUNDLE_FORMAT,
+            ppm_version: "1.1.5".to_string(),
+            project: "demo".to_string(),
+            version: "0.1.0".to_string(),
+            os: "linux".to_string(),
+            arch: "x86_64".to_string(),
+            python: Some("3.12.1".to_string()),
+            files: files
+                .iter()
+                .map(|filename| BundledFile {
+                    filename: filename.to_string(),
+                    sha256: sha256_hex(filename.as_bytes()),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_wheel_tags() {
+        let tags = WheelTags::parse("six-1.16.0-py2.py3-none-any.whl").unwrap();
+        assert_eq!(tags.python, ["py2", "py3"]);
+        assert_eq!(tags.abi, ["none"]);
+        assert_eq!(tags.platform, ["any"]);
+        let tags = WheelTags::parse(
+            "numpy-2.1.0-1-cp312-cp312-manylinux_2_17_x86_64.manylinux2014_x86_64.whl",
+        )
+        .unwrap();
+        assert_eq!(tags.python, ["cp312"]);
+        assert_eq!(tags.platform.len(), 2);
+        assert_eq!(WheelTags::parse("six-1.16.0.tar.gz"), None);
+        assert_eq!(WheelTags::parse("broken-py3-none-any.whl"), None);
+    }
+
+    #[test]
+    fn test_tag_compatibility() {
+        let linux_312 = Target {
+            platform: LINUX,
+            python: Some((3, 12)),
+        };
+        let installs = |filename: &str, target: &Target| {
+            WheelTags::parse(filename).unwrap().installs_on(target)
+        };
+        assert!(installs("six-1.16.0-py2.py3-none-any.whl", &linux_312));
+        assert!(installs("attrs-23.2.0-py37-none-any.whl", &linux_312));
+        assert!(installs(
+            "numpy-2.1.0-cp312-cp312-manylinux_2_17_x86_64.whl",
+            &linux_312
+        ));
+        assert!(installs(
+            "cryptography-42.0.5-cp39-abi3-manylinux_2_28_x86_64.whl",
+            &linux_312
+        ));
+        // Another CPython, or a wheel needing a newer one
+        assert!(!installs(
+            "numpy-2.1.0-cp311-cp311-manylinux_2_17_x86_64.whl",
+            &linux_312
+        ));
+        assert!(!installs("new-1.0-py313-none-any.whl", &linux_312));
+        assert!(!installs("legacy-1.0-py2-none-any.whl", &linux_312));
+        assert!(!installs("pypy-1.0-pp310-pypy310_pp73-any.whl", &linux_312));
+        // Another platform
+        let mac = Target {
+            platform: MAC_ARM,
+            python: Some((3, 12)),
+        };
+        assert!(!installs(
+            "numpy-2.1.0-cp312-cp312-manylinux_2_17_x86_64.whl",
+            &mac
+        ));
+        assert!(installs(
+            "numpy-2.1.0-cp312-cp312-macosx_11_0_arm64.whl",
+            &mac
+        ));
+        assert!(installs("six-1.16.0-py2.py3-none-any.whl", &mac));
+        // An unknown Python only checks the platform
+        let unknown = Target {
+            platform: LINUX,
+            python: None,
+        };
+        assert!(installs(
+            "numpy-2.1.0-cp311-cp311-manylinux_2_17_x86_64.whl",
+            &unknown
+        ));
+    }
+
+    #[test]
+    fn test_incompatible_files_are_reported_before_installing() {
+        let manifest = manifest(&[
+            "numpy-2.1.0-cp312-cp312-manylinux_2_17_x86_64.whl",
+            "pyyaml-6.0.2.tar.gz",
+            "six-1.16.0-py2.py3-none-any.whl",
+        ]);
+        let linux = Target {
+            platform: LINUX,
+            python: python_version("3.12.4"),
+        };
+        assert!(check_compatible(&manifest, &linux).is_ok());
+
+        let mac = Target {
+            platform: MAC_ARM,
+            python: Some((3, 12)),
+        };
+        assert_eq!(
+            incompatible(&manifest, &mac),
+            ["numpy-2.1.0-cp312-cp312-manylinux_2_17_x86_64.whl"]
+        );
+        assert_eq!(
+            check_compatible(&manifest, &mac).unwrap_err().to_string(),
+            "The bundle was made on linux x86_64 with Python 3.12.1 and 1 of its wheels can't be installed on macos aarch64 with Python 3.12: numpy-2.1.0-cp312-cp312-manylinux_2_17_x86_64.whl; create the bundle on a machine like this one"
+        );
+    }
+
+    #[test]
+    fn test_manifest_json() {
+        let manifest = manifest(&["six-1.16.0-py2.py3-none-any.whl"]);
+        let json = serde_json::to_value(&manifest).unwrap();
+        assert_eq!(json["format"], 1);
+        assert_eq!(json["project"], "demo");
+        assert_eq!(json["os"], "linux");
+        assert_eq!(json["python"], "3.12.1");
+        assert_eq!(
+            json["files"][0]["filename"],
+            "six-1.16.0-py2.py3-none-any.whl"
+        );
+        assert_eq!(json["files"][0]["sha256"].as_str().unwrap().len(), 64);
+        let parsed: Manifest = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, manifest);
+    }
+
+    #[test]
+    fn test_archive_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("project");
+        let wheels = dir.path().join("wheels");
+        fs::create_dir_all(&project).unwrap();
+        fs::create_dir_all(&wheels).unwrap();
+        fs::write(project.join(PROJECT_CONFIG_FILE), "[project]\n").unwrap();
+        fs::write(project.join(LOCK_FILE), "six==1.16.0\n").unwrap();
+        fs::write(wheels.join("six-1.16.0-py2.py3-none-any.whl"), "wheel").unwrap();
+        let mut manifest = manifest(&[]);
+        manifest.files = bundled_files(&wheels).unwrap();
+        assert_eq!(manifest.files[0].sha256, sha256_hex(b"wheel"));
+
+        let archive = dir.path().join("demo.tar.gz");
+        write_archive(&archive, &manifest, &project, &wheels).unwrap();
+        assert_eq!(read_manifest(&archive).unwrap(), manifest);
+        assert_eq!(
+            read_text(&archive, PROJECT_CONFIG_FILE).unwrap().as_deref(),
+            Some("[project]\n")
+        );
+        assert_eq!(read_text(&archive, "missing.txt").unwrap(), None);
+
+        let target = dir.path().join("target");
+        fs::create_dir_all(&target).unwrap();
+        extract(&archive, &manifest, &target, &target.join("wheels")).unwrap();
+        assert_eq!(
+            fs::read_to_string(target.join(LOCK_FILE)).unwrap(),
+            "six==1.16.0\n"
+        );
+        assert_eq!(
+            fs::read(target.join("wheels/six-1.16.0-py2.py3-none-any.whl")).unwrap(),
+            b"wheel"
+        );
+
+        // A digest that doesn't match is caught
+        let mut tampered = manifest.clone();
+        tampered.files[0].sha256 = sha256_hex(b"other");
+        let err = extract(&archive, &tampered, &target, &target.join("wheels")).unwrap_err();
+        assert!(err.to_string().contains("the archive is damaged"));
+
+        // So are files the manifest doesn't list, and ones it lists that
+        // aren't there
+        let mut listed = manifest.clone();
+        listed.files.clear();
+        let err = extract(&archive, &listed, &target, &target.join("wheels")).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("unexpected entry 'wheels/six-1.16.0")
+        );
+        let mut extra = manifest.clone();
+        extra.files.push(BundledFile {
+            filename: "gone-1.0-py3-none-any.whl".to_string(),
+            sha256: String::new(),
+        });
+        let err = extract(&archive, &extra, &target, &target.join("wheels")).unwrap_err();
+        assert!(
+            err.to_string()
+                .ends_with("it lacks gone-1.0-py3-none-any.whl")
+        );
+
+        // Anything else isn't a bundle
+        fs::write(dir.path().join("plain.tar.gz"), "not gzip").unwrap();
+        assert!(read_manifest(&dir.path().join("plain.tar.gz")).is_err());
+    }
+
+    /// An archive of `manifest` and `entries`, written with raw header
+    /// names, since `tar::Builder` refuses to write `..` itself
+    fn write_raw_archive(archive: &Path, manifest: &Manifest, entries: &[(&str, &[u8])]) {
+        let encoder = GzEncoder::new(File::create(archive).unwrap(), Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        let manifest_json = serde_json::to_vec(manifest).unwrap();
+        let files = [(MANIFEST_FILE, manifest_json.as_slice())];
+        for (name, contents) in files.iter().chain(entries) {
+            let mut header = tar::Header::new_gnu();
+            header.as_gnu_mut().unwrap().name[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append(&header, *contents).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+    }
+
+    #[test]
+    fn test_manifest_cant_write_outside_the_wheels_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("target");
+        let wheels = target.join("wheels");
+        fs::create_dir_all(&wheels).unwrap();
+        let archive = dir.path().join("evil.tar.gz");
+        let mut evil = manifest(&["../evil.whl"]);
+        evil.files[0].sha256 = sha256_hex(b"payload");
+        write_raw_archive(
+            &archive,
+            &evil,
+            &[
+                (PROJECT_CONFIG_FILE, b"[project]\n"),
+                (LOCK_FILE, b""),
+                ("wheels/../evil.whl", b"payload"),
+            ],
+        );
+
+        let err = read_manifest(&archive).unwrap_err();
+        assert!(
+            err.to_string()
+                .ends_with("bundle.json lists '../evil.whl', which isn't a plain file name"),
+            "{}",
+            err
+        );
+        // Even handed the manifest directly, extracting writes nothing
+        // outside the wheels directory
+        let err = extract(&archive, &evil, &target, &wheels).unwrap_err();
+        assert!(
+            err.to_string().contains("would be written outside"),
+            "{}",
+            err
+        );
+        assert!(!target.join("evil.whl").exists());
+
+        for name in [
+            "/tmp/evil.whl",
+            "sub/evil.whl",
+            "sub\\evil.whl",
+            "..",
+            ".",
+            "",
+        ] {
+            assert!(!is_plain_file_name(name), "{}", name);
+        }
+        assert!(is_plain_file_name("six-1.16.0-py2.py3-none-any.whl"));
+    }
+}
@@ -0,0 +1,154 @@
+//! Where to read what changed in a release, for the links `ppm update`
+//! prints under each updated package.
+//!
+//! The link is picked from the `info` table of the package's JSON API
+//! document, see [`changelog_url`].
+
+use serde_json::Value;
+
+/// `project_urls` labels that point at a changelog, compared without
+/// case, spaces or punctuation
+pub const CHANGELOG_LABELS: &[&str] = &["changelog", "changes", "releasenotes"];
+
+/// `project_urls` labels that point at the project's home or repository,
+/// compared like [`CHANGELOG_LABELS`]
+pub const REPOSITORY_LABELS: &[&str] = &[
+    "homepage",
+    "home",
+    "repository",
+    "source",
+    "sourcecode",
+    "code",
+];
+
+/// The best link to `name`'s changelog in `info`, the metadata the JSON
+/// API has for its latest release: a `project_urls` entry labelled
+/// Changelog, Changes or Release notes, else the releases page when the
+/// home page or repository is on GitHub, else the project's page on the
+/// index
+pub fn changelog_url(name: &str, info: &Value) -> String {
+    let project_urls: Vec<(String, &str)> = info["project_urls"]
+        .as_object()
+        .map(|urls| {
+            urls.iter()
+                .filter_map(|(label, url)| Some((normalize_label(label), url.as_str()?)))
+                .filter(|(_, url)| is_web_url(url))
+                .collect()
+        })
+        .unwrap_or_default();
+    let labelled = |labels: &[&str]| {
+        project_urls
+            .iter()
+            .filter(|(label, _)| labels.contains(&label.as_str()))
+            .map(|(_, url)| *url)
+            .collect::<Vec<_>>()
+    };
+
+    if let Some(url) = labelled(CHANGELOG_LABELS).first() {
+        return url.to_string();
+    }
+    let home_page = info["home_page"].as_str().filter(|url| is_web_url(url));
+    if let Some(releases) = home_page
+        .into_iter()
+        .chain(labelled(REPOSITORY_LABELS))
+        .find_map(github_releases)
+    {
+        return releases;
+    }
+    ["project_url", "package_url"]
+        .iter()
+        .filter_map(|key| info[*key].as_str())
+        .find(|url| is_web_url(url))
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("https://pypi.org/project/{}/", name))
+}
+
+/// The releases page of the GitHub repository `url` is in, like
+/// `https://github.com/psf/requests/releases`
+pub fn github_releases(url: &str) -> Option<String> {
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))?;
+    let rest = rest.strip_prefix("www.").unwrap_or(rest);
+    let mut parts = rest.strip_prefix("github.com/")?.split(['/', '#', '?']);
+    let owner = parts.next().filter(|owner| !owner.is_empty())?;
+    let repo = parts.next()?.trim_end_matches(".git");
+    if repo.is_empty() {
+        return None;
+    }
+    Some(format!("https://github.com/{}/{}/releases", owner, repo))
+}
+
+fn normalize_label(label: &str) -> String {
+    label
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+fn is_web_url(url: &str) -> bool {
+    url.starts_with("https://") || url.starts_with("http://")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_changelog_label_wins() {
+        let info = json!({
+            "home_page": "https://github.com/pallets/flask",
+            "project_urls": {
+                "Source": "https://github.com/pallets/flask/",
+                "Release Notes": "https://flask.palletsprojects.com/changes/",
+            },
+        });
+        assert_eq!(
+            changelog_url("flask", &info),
+            "https://flask.palletsprojects.com/changes/"
+        );
+        let info = json!({"project_urls": {"Change-Log": "https://example.com/CHANGES"}});
+        assert_eq!(changelog_url("demo", &info), "https://example.com/CHANGES");
+    }
+
+    #[test]
+    fn test_github_repository_falls_back_to_its_releases() {
+        let info = json!({
+            "home_page": "https://requests.readthedocs.io",
+            "project_urls": {
+                "Documentation": "https://requests.readthedocs.io",
+                "Source": "https://github.com/psf/requests.git",
+            },
+        });
+        assert_eq!(
+            changelog_url("requests", &info),
+            "https://github.com/psf/requests/releases"
+        );
+        let info = json!({"home_page": "https://www.github.com/encode/httpx/tree/master"});
+        assert_eq!(
+            changelog_url("httpx", &info),
+            "https://github.com/encode/httpx/releases"
+        );
+        assert_eq!(github_releases("https://github.com/psf"), None);
+        assert_eq!(github_releases("https://gitlab.com/psf/requests"), None);
+    }
+
+    #[test]
+    fn test_index_page_is_the_last_resort() {
+        let info = json!({
+            "home_page": "UNKNOWN",
+            "project_urls": {"Changelog": "CHANGES.md", "Homepage": "https://example.com"},
+            "package_url": "https://pypi.org/project/Demo-Lib/",
+        });
+        assert_eq!(
+            changelog_url("demo-lib", &info),
+            "https://pypi.org/project/Demo-Lib/"
+        );
+        assert_eq!(
+            changelog_url("demo-lib", &json!({})),
+            "https://pypi.org/project/demo-lib/"
+        );
+    }
+}
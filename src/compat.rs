@@ -0,0 +1,405 @@
+//! `ppm-version` under `[project]`: the ppm releases that read a
+//! project.toml correctly.
+//!
+//! ```toml
+//! [project]
+//! ppm-version = ">=1.2"
+//! ```
+//!
+//! A ppm outside the range refuses the file instead of silently
+//! mis-handling constructs it doesn't know. Commands that add a construct
+//! newer than the range allows raise its lower bound, see [`raise_for`].
+//! Without the field any version goes.
+
+use crate::packages::compare_versions;
+use crate::settings::{Config, PackageSpec, UpdatePolicy};
+use std::cmp::Ordering;
+use std::fmt;
+
+/// Version of the running ppm
+pub const PPM_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// How a [`Clause`] compares
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Ge,
+    Gt,
+    Le,
+    Lt,
+    Eq,
+    Ne,
+}
+
+impl Op {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Op::Ge => ">=",
+            Op::Gt => ">",
+            Op::Le => "<=",
+            Op::Lt => "<",
+            Op::Eq => "==",
+            Op::Ne => "!=",
+        }
+    }
+}
+
+/// One comparison of a requirement, like `>=1.1`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Clause {
+    op: Op,
+    version: String,
+}
+
+/// A `ppm-version` requirement: comparisons separated by commas, all of
+/// which a version must satisfy, like `>=1.1, <2`. A bare version means
+/// that version or newer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionReq {
+    clauses: Vec<Clause>,
+}
+
+impl VersionReq {
+    /// Parse `text`, failing on an unknown operator or a version that
+    /// isn't dotted numbers
+    pub fn parse(text: &str) -> Result<VersionReq, String> {
+        let mut clauses = vec![];
+        for part in text.split(',') {
+            let part = part.trim();
+            let (op, version) = [
+                (">=", Op::Ge),
+                ("<=", Op::Le),
+                ("==", Op::Eq),
+                ("!=", Op::Ne),
+                (">", Op::Gt),
+                ("<", Op::Lt),
+                ("=", Op::Eq),
+            ]
+            .iter()
+            .find_map(|(prefix, op)| part.strip_prefix(prefix).map(|rest| (*op, rest)))
+            .unwrap_or((Op::Ge, part));
+            let version = version.trim();
+            let valid = !version.is_empty()
+                && version.split('.').all(|segment| {
+                    !segment.is_empty() && segment.chars().all(|c| c.is_ascii_digit())
+                });
+            if !valid {
+                return Err(format!("'{}' isn't a version requirement like >=1.1", part));
+            }
+            clauses.push(Clause {
+                op,
+                version: version.to_string(),
+            });
+        }
+        Ok(VersionReq { clauses })
+    }
+
+    /// Whether `version` satisfies every clause
+    pub fn matches(&self, version: &str) -> bool {
+        self.clauses.iter().all(|clause| {
+            let ord = compare_versions(version, &clause.version);
+            match clause.op {
+                Op::Ge => ord != Ordering::Less,
+                Op::Gt => ord == Ordering::Greater,
+                Op::Le => ord != Ordering::Greater,
+                Op::Lt => ord == Ordering::Less,
+                Op::Eq => ord == Ordering::Equal,
+                Op::Ne => ord != Ordering::Equal,
+            }
+        })
+    }
+
+    /// Whether every version the requirement allows is `version` or newer
+    pub fn requires_at_least(&self, version: &str) -> bool {
+        self.clauses.iter().any(|clause| {
+            let ord = compare_versions(&clause.version, version);
+            match clause.op {
+                Op::Ge | Op::Gt | Op::Eq => ord != Ordering::Less,
+                Op::Le | Op::Lt | Op::Ne => false,
+            }
+        })
+    }
+
+    /// The requirement with its lower bounds replaced by `>=version`,
+    /// keeping the upper bounds and exclusions
+    pub fn raised_to(&self, version: &str) -> VersionReq {
+        let mut clauses = vec![Clause {
+            op: Op::Ge,
+            version: version.to_string(),
+        }];
+        clauses.extend(
+            self.clauses
+                .iter()
+                .filter(|clause| matches!(clause.op, Op::Le | Op::Lt | Op::Ne))
+                .cloned(),
+        );
+        VersionReq { clauses }
+    }
+}
+
+impl fmt::Display for VersionReq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let clauses: Vec<String> = self
+            .clauses
+            .iter()
+            .map(|clause| format!("{}{}", clause.op.as_str(), clause.version))
+            .collect();
+        f.write_str(&clauses.join(", "))
+    }
+}
+
+/// A project.toml construct and the first ppm that understands it
+pub struct Feature {
+    /// What it's called in messages
+    pub name: &'static str,
+    /// The first ppm release that handles it
+    pub since: &'static str,
+    used: fn(&Config) -> bool,
+}
+
+impl Feature {
+    /// Whether `config` uses the construct
+    pub fn is_used(&self, config: &Config) -> bool {
+        (self.used)(config)
+    }
+}
+
+/// Constructs older ppm releases would silently mis-handle
+pub const FEATURES: &[Feature] = &[
+    Feature {
+        name: "package markers",
+        since: "1.2.0",
+        used: |config| config.packages.values().any(|spec| spec.marker().is_some()),
+    },
+    Feature {
+        name: "git, path and URL packages",
+        since: "1.2.0",
+        used: |config| config.packages.values().any(|spec| !spec.is_index()),
+    },
+    Feature {
+        name: "update policies",
+        since: "1.2.0",
+        used: |config| {
+            config
+                .packages
+                .values()
+                .any(|spec| spec.update_policy() != UpdatePolicy::Latest)
+        },
+    },
+    Feature {
+        name: "table packages",
+        since: "1.2.0",
+        used: |config| {
+            config
+                .packages
+                .values()
+                .any(|spec| matches!(spec, PackageSpec::Table(_)))
+        },
+    },
+    Feature {
+        name: "index-url",
+        since: "1.2.0",
+        used: |config| config.project.index_url.is_some(),
+    },
+    Feature {
+        name: "constraints",
+        since: "1.2.0",
+        used: |config| config.project.constraints.is_some(),
+    },
+    Feature {
+        name: "python",
+        since: "1.2.0",
+        used: |config| config.project.python.is_some(),
+    },
+    Feature {
+        name: "[workspace]",
+        since: "1.2.0",
+        used: |config| config.workspace.is_some(),
+    },
+    Feature {
+        name: "[aliases]",
+        since: "1.2.0",
+        used: |config| !config.aliases.is_empty(),
+    },
+    Feature {
+        name: "license",
+        since: "1.2.0",
+        used: |config| config.project.license.is_some(),
+    },
+    Feature {
+        name: "version-file",
+        since: "1.2.0",
+        used: |config| config.project.version_file.is_some(),
+    },
+    Feature {
+        name: "[hooks]",
+        since: "1.2.0",
+        used: |config| !config.hooks.is_empty(),
+    },
+    Feature {
+        name: "sync-requirements",
+        since: "1.2.0",
+        used: |config| config.project.sync_requirements,
+    },
+    Feature {
+        name: "use-venv",
+        since: "1.2.0",
+        used: |config| config.project.use_venv.is_some(),
+    },
+    Feature {
+        name: "[requirements]",
+        since: "1.2.0",
+        used: |config| !config.requirements.is_default(),
+    },
+];
+
+/// Fail when `requirement`, the value of `ppm-version`, doesn't admit
+/// the running ppm
+pub fn check(requirement: &str) -> Result<(), String> {
+    let req = VersionReq::parse(requirement).map_err(|e| format!("Invalid ppm-version: {}", e))?;
+    if req.matches(PPM_VERSION) {
+        return Ok(());
+    }
+    Err(format!(
+        "This project needs ppm {} (ppm-version under [project]), but this is ppm {}; \
+         upgrade ppm (`ppm self update`) to use newer project.toml features safely",
+        req, PPM_VERSION
+    ))
+}
+
+/// A `ppm-version` raised for a construct a command added
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Raise {
+    /// The construct that needs it
+    pub feature: &'static str,
+    /// The new value of `ppm-version`
+    pub requirement: String,
+}
+
+impl fmt::Display for Raise {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Set ppm-version = \"{}\" in project.toml: {} need a newer ppm",
+            self.requirement, self.feature
+        )
+    }
+}
+
+/// Raise `ppm-version` in `config` when it uses a construct that `base`,
+/// the config before the command, didn't and that the current range
+/// allows too old a ppm for. An unparsable `ppm-version` is left alone.
+pub fn raise_for(base: &Config, config: &mut Config) -> Option<Raise> {
+    let current = match &config.project.ppm_version {
+        Some(text) => Some(VersionReq::parse(text).ok()?),
+        None => None,
+    };
+    let feature = FEATURES
+        .iter()
+        .filter(|feature| feature.is_used(config) && !feature.is_used(base))
+        .filter(|feature| {
+            current
+                .as_ref()
+                .is_none_or(|req| !req.requires_at_least(feature.since))
+        })
+        .max_by(|a, b| compare_versions(a.since, b.since))?;
+    let raised = match current {
+        Some(req) => req.raised_to(feature.since),
+        None => VersionReq::parse(feature.since).ok()?,
+    };
+    config.project.ppm_version = Some(raised.to_string());
+    Some(Raise {
+        feature: feature.name,
+        requirement: raised.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Project;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_parse_and_match() {
+        let req = VersionReq::parse(">=0.4").unwrap();
+        assert!(req.matches("0.4.0"));
+        assert!(req.matches("1.1.5"));
+        assert!(!req.matches("0.3.9"));
+
+        let range = VersionReq::parse(" >= 1.1, <2 ,!=1.2.0").unwrap();
+        assert_eq!(range.to_string(), ">=1.1, <2, !=1.2.0");
+        assert!(range.matches("1.10.0"));
+        assert!(!range.matches("1.2"));
+        assert!(!range.matches("2.0.0"));
+        assert!(range.matches("1.1.post1"));
+        assert!(!range.matches("1.1rc1"));
+        assert!(!range.matches("2.0.0.post1"));
+        assert_eq!(VersionReq::parse("1.2").unwrap().to_string(), ">=1.2");
+
+        assert!(VersionReq::parse(">=").is_err());
+        assert!(VersionReq::parse("~>1.0").is_err());
+        assert!(VersionReq::parse(">=1.x").is_err());
+    }
+
+    #[test]
+    fn test_check_running_version() {
+        assert!(check(">=0.1").is_ok());
+        assert!(check(&format!("=={}", PPM_VERSION)).is_ok());
+        let err = check(">=999.0").unwrap_err();
+        assert!(err.contains(">=999.0"));
+        assert!(err.contains(PPM_VERSION));
+        assert!(
+            check("soon")
+                .unwrap_err()
+                .starts_with("Invalid ppm-version")
+        );
+
+        // 1.1.5 knew none of these constructs, and the running ppm all
+        for feature in FEATURES {
+            assert_eq!(
+                compare_versions(feature.since, "1.1.5"),
+                Ordering::Greater,
+                "{}",
+                feature.name
+            );
+            assert!(check(&format!(">={}", feature.since)).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_raise_only_for_new_constructs() {
+        let project = Project::new(
+            "demo".to_string(),
+            "0.1.0".to_string(),
+            String::new(),
+            "main.py".to_string(),
+            None,
+        );
+        let base = Config::new(project, HashMap::new(), HashMap::new());
+
+        // Nothing new, nothing raised
+        let mut config = base.clone();
+        config
+            .packages
+            .insert("requests".to_string(), PackageSpec::from("2.31.0"));
+        assert_eq!(raise_for(&base, &mut config), None);
+        assert_eq!(config.project.ppm_version, None);
+
+        // A marker is new; the upper bound stays
+        config.project.ppm_version = Some(">=0.4, <2".to_string());
+        config.packages.insert(
+            "pywin32".to_string(),
+            PackageSpec::from("306; sys_platform == 'win32'"),
+        );
+        let raise = raise_for(&base, &mut config).unwrap();
+        assert_eq!(raise.feature, "package markers");
+        assert_eq!(raise.requirement, ">=1.2.0, <2");
+        assert_eq!(config.project.ppm_version.as_deref(), Some(">=1.2.0, <2"));
+
+        // Already high enough, or already used before the command
+        assert_eq!(raise_for(&base, &mut config), None);
+        let mut again = config.clone();
+        again.project.ppm_version = None;
+        assert_eq!(raise_for(&config, &mut again), None);
+    }
+}
@@ -0,0 +1,152 @@
+use clap::Args;
+use clap_complete::Shell;
+use ppmm::{PpmError, ProjectContext};
+
+const COMPLETIONS_HELP: &str = "\
+Installation:
+    bash:       ppmm completions bash > ~/.local/share/bash-completion/completions/ppmm
+    zsh:        ppmm completions zsh > \"${fpath[1]}/_ppmm\"
+    fish:       ppmm completions fish > ~/.config/fish/completions/ppmm.fish
+    powershell: ppmm completions powershell >> $PROFILE
+
+Script names complete after `ppmm run` and package names after `ppmm rm`,
+read from the project.toml of the current directory.";
+
+const BASH_DYNAMIC: &str = r#"
+_ppmm_dynamic() {
+    local cur="${COMP_WORDS[COMP_CWORD]}"
+    if [[ ${COMP_CWORD} -eq 2 ]]; then
+        case "${COMP_WORDS[1]}" in
+            run)
+                COMPREPLY=( $(compgen -W "$(ppmm _complete scripts 2>/dev/null)" -- "${cur}") )
+                return 0
+                ;;
+            rm)
+                COMPREPLY=( $(compgen -W "$(ppmm _complete packages 2>/dev/null)" -- "${cur}") )
+                return 0
+                ;;
+            examples)
+                COMPREPLY=( $(compgen -W "$(ppmm _complete examples 2>/dev/null)" -- "${cur}") )
+                return 0
+                ;;
+        esac
+    fi
+    _ppmm "$@"
+}
+
+complete -F _ppmm_dynamic -o bashdefault -o default ppmm
+"#;
+
+const ZSH_DYNAMIC: &str = r#"
+_ppmm_dynamic() {
+    if (( CURRENT > 2 )); then
+        case "${words[2]}" in
+            run)
+                compadd -- ${(f)"$(ppmm _complete scripts 2>/dev/null)"}
+                return
+                ;;
+            rm)
+                compadd -- ${(f)"$(ppmm _complete packages 2>/dev/null)"}
+                return
+                ;;
+            examples)
+                compadd -- ${(f)"$(ppmm _complete examples 2>/dev/null)"}
+                return
+                ;;
+        esac
+    fi
+    _ppmm "$@"
+}
+
+_ppmm_dynamic "$@"
+"#;
+
+const FISH_DYNAMIC: &str = r#"
+complete -c ppmm -n "__fish_seen_subcommand_from run" -f -a "(ppmm _complete scripts 2>/dev/null)"
+complete -c ppmm -n "__fish_seen_subcommand_from rm" -f -a "(ppmm _complete packages 2>/dev/null)"
+"#;
+
+#[derive(Args, Debug)]
+#[clap(after_help = COMPLETIONS_HELP)]
+pub struct Completions {
+    /// Shell to generate completions for
+    #[clap(value_parser = ["bash", "zsh", "fish", "powershell"])]
+    pub shell: String,
+}
+
+impl Completions {
+    pub fn print_completions(&self) -> Result<(), PpmError> {
+        let script = generate_script(&self.shell).map_err(PpmError::Other)?;
+        print!("{}", script);
+        Ok(())
+    }
+}
+
+/// Completion script for `shell`, with dynamic script/package lookups
+/// spliced in where the shell supports it
+pub fn generate_script(shell: &str) -> Result<String, String> {
+    let shell: Shell = shell.parse()?;
+    let mut cmd = crate::cli_command();
+    let mut buf: Vec<u8> = vec![];
+    clap_complete::generate(shell, &mut cmd, "ppmm", &mut buf);
+    let script = String::from_utf8_lossy(&buf).into_owned();
+
+    Ok(match shell {
+        Shell::Bash => {
+            let static_complete = "complete -F _ppmm -o bashdefault -o default ppmm\n";
+            script.replace(static_complete, "") + BASH_DYNAMIC
+        }
+        Shell::Zsh => script.replace("\n_ppmm \"$@\"\n", ZSH_DYNAMIC),
+        Shell::Fish => script + FISH_DYNAMIC + &fish_example_topics(),
+        _ => script,
+    })
+}
+
+/// Fish completions for `ppmm examples`, described by the task of each
+/// topic's first example
+fn fish_example_topics() -> String {
+    crate::examples::topics()
+        .iter()
+        .map(|(topic, task)| {
+            format!(
+                "complete -c ppmm -n \"__fish_seen_subcommand_from examples\" -f -a \"{}\" -d \"{}\"\n",
+                topic,
+                task.replace('"', "\\\"")
+            )
+        })
+        .collect()
+}
+
+#[derive(Args, Debug)]
+pub struct CompleteHelper {
+    /// What to list: scripts, packages or example topics
+    #[clap(value_parser = ["scripts", "packages", "examples"])]
+    pub kind: String,
+}
+
+impl CompleteHelper {
+    /// Print candidate names one per line; silent on any error since the
+    /// output is consumed by shell completion functions
+    pub fn print_candidates(&self) {
+        if self.kind == "examples" {
+            for (topic, _) in crate::examples::topics() {
+                println!("{}", topic);
+            }
+            return;
+        }
+        let conf = match ProjectContext::current_dir() {
+            Ok(ctx) => ctx.config,
+            Err(_) => return,
+        };
+
+        let mut names: Vec<&String> = if self.kind == "scripts" {
+            conf.scripts.keys().collect()
+        } else {
+            conf.packages.keys().collect()
+        };
+        names.sort();
+        for name in names {
+            println!("{}", name);
+        }
+    }
+}
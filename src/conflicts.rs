@@ -0,0 +1,234 @@
+//! Summaries of pip's `ResolutionImpossible` errors.
+//!
+//! When no set of versions satisfies every constraint, pip prints the
+//! requirements it was asked to install together and one line per
+//! constraint involved:
+//!
+//! ```text
+//! ERROR: Cannot install flask==2.2.0 and werkzeug==2.1.0 because these package versions have conflicting dependencies.
+//!
+//! The conflict is caused by:
+//!     The user requested werkzeug==2.1.0
+//!     flask 2.2.0 depends on Werkzeug>=2.2.0
+//! ```
+//!
+//! [`parse_resolution_error`] turns that into a [`ResolutionConflict`]
+//! whose `Display` names each contested package once.
+
+use crate::packages::{normalize_pkg_name, requirement_name};
+use std::fmt;
+
+/// One line under "The conflict is caused by:"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConflictCause {
+    /// The distribution and version making the demand, `None` when the
+    /// requirement was passed to pip directly
+    pub required_by: Option<(String, String)>,
+    /// The requirement as pip printed it, e.g. `botocore<1.32.0 and >=1.31.0`
+    pub requirement: String,
+    /// Passed as a constraint rather than a requirement
+    pub constraint: bool,
+}
+
+impl ConflictCause {
+    /// Normalized name of the package the requirement is about
+    pub fn package(&self) -> String {
+        normalize_pkg_name(requirement_name(&self.requirement))
+    }
+}
+
+impl fmt::Display for ConflictCause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.required_by {
+            Some((name, version)) => {
+                write!(f, "{} {} requires {}", name, version, self.requirement)
+            }
+            None if self.constraint => write!(f, "a constraint requires {}", self.requirement),
+            None => write!(f, "project.toml requires {}", self.requirement),
+        }
+    }
+}
+
+/// Requirements pip could not install together, and why
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolutionConflict {
+    /// The requirements named in "Cannot install ...", i.e. the direct
+    /// dependencies that conflict
+    pub requested: Vec<String>,
+    /// Every constraint pip listed, duplicates removed
+    pub causes: Vec<ConflictCause>,
+}
+
+impl ResolutionConflict {
+    /// Packages with competing requirements and the causes for each, in
+    /// the order pip first mentions them. When no package has more than
+    /// one cause, every package is returned.
+    pub fn contested(&self) -> Vec<(String, Vec<&ConflictCause>)> {
+        let mut groups: Vec<(String, Vec<&ConflictCause>)> = vec![];
+        for cause in &self.causes {
+            let package = cause.package();
+            match groups.iter_mut().find(|(name, _)| *name == package) {
+                Some((_, causes)) => causes.push(cause),
+                None => groups.push((package, vec![cause])),
+            }
+        }
+        if groups.iter().any(|(_, causes)| causes.len() > 1) {
+            groups.retain(|(_, causes)| causes.len() > 1);
+        }
+        groups
+    }
+}
+
+impl fmt::Display for ResolutionConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let requested = match self.requested.as_slice() {
+            [] => "The requested packages".to_string(),
+            [only] => only.clone(),
+            [rest @ .., last] => format!("{} and {}", rest.join(", "), last),
+        };
+        write!(f, "{} can't be installed together:", requested)?;
+        for (package, causes) in self.contested() {
+            let causes: Vec<String> = causes.iter().map(|cause| cause.to_string()).collect();
+            write!(f, "\n  {}: {}", package, causes.join("; "))?;
+        }
+        Ok(())
+    }
+}
+
+/// The conflict described by pip's stderr, `None` if the output is not a
+/// `ResolutionImpossible` error
+pub fn parse_resolution_error(stderr: &str) -> Option<ResolutionConflict> {
+    if !stderr.contains("ResolutionImpossible") && !stderr.contains("The conflict is caused by:") {
+        return None;
+    }
+
+    let requested = stderr
+        .lines()
+        .find_map(|line| {
+            let rest = line.trim().strip_prefix("ERROR: Cannot install ")?;
+            let (list, _) = rest.split_once(" because ")?;
+            Some(split_list(list))
+        })
+        .unwrap_or_default();
+
+    let mut causes: Vec<ConflictCause> = vec![];
+    let cause_lines = stderr
+        .lines()
+        .skip_while(|line| line.trim() != "The conflict is caused by:")
+        .skip(1)
+        .map(str::trim)
+        .take_while(|line| !line.is_empty());
+    for line in cause_lines {
+        let cause = if let Some(requirement) = line.strip_prefix("The user requested (constraint) ")
+        {
+            ConflictCause {
+                required_by: None,
+                requirement: requirement.to_string(),
+                constraint: true,
+            }
+        } else if let Some(requirement) = line.strip_prefix("The user requested ") {
+            ConflictCause {
+                required_by: None,
+                requirement: requirement.to_string(),
+                constraint: false,
+            }
+        } else if let Some((by, requirement)) = line.split_once(" depends on ")
+            && let Some((name, version)) = by.split_once(' ')
+        {
+            ConflictCause {
+                required_by: Some((name.to_string(), version.to_string())),
+                requirement: requirement.to_string(),
+                constraint: false,
+            }
+        } else {
+            continue;
+        };
+        if !causes.contains(&cause) {
+            causes.push(cause);
+        }
+    }
+
+    if requested.is_empty() && causes.is_empty() {
+        return None;
+    }
+    Some(ResolutionConflict { requested, causes })
+}
+
+/// `a, b and c` as `[a, b, c]`
+fn split_list(list: &str) -> Vec<String> {
+    let (head, last) = match list.rsplit_once(" and ") {
+        Some((head, last)) => (head, Some(last)),
+        None => (list, None),
+    };
+    head.split(", ")
+        .chain(last)
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FLASK_WERKZEUG: &str = include_str!("../tests/fixtures/conflicts/flask-werkzeug.txt");
+    const BOTO3_AIOBOTOCORE: &str =
+        include_str!("../tests/fixtures/conflicts/boto3-aiobotocore.txt");
+    const THREE_WAY: &str = include_str!("../tests/fixtures/conflicts/pip24-three-way.txt");
+    const NO_MATCHING_VERSION: &str =
+        include_str!("../tests/fixtures/conflicts/no-matching-version.txt");
+
+    #[test]
+    fn test_pinned_dependency_against_dependent() {
+        let conflict = parse_resolution_error(FLASK_WERKZEUG).unwrap();
+        assert_eq!(conflict.requested, vec!["flask==2.2.0", "werkzeug==2.1.0"]);
+        assert_eq!(conflict.causes.len(), 2);
+        assert_eq!(
+            conflict.to_string(),
+            "flask==2.2.0 and werkzeug==2.1.0 can't be installed together:\n  \
+             werkzeug: project.toml requires werkzeug==2.1.0; flask 2.2.0 requires Werkzeug>=2.2.0"
+        );
+    }
+
+    #[test]
+    fn test_transitive_conflict_keeps_only_contested_packages() {
+        let conflict = parse_resolution_error(BOTO3_AIOBOTOCORE).unwrap();
+        assert_eq!(
+            conflict.requested,
+            vec!["aiobotocore==2.7.0", "boto3==1.28.0"]
+        );
+        // The repeated aiobotocore line is listed once
+        assert_eq!(conflict.causes.len(), 3);
+        let contested = conflict.contested();
+        assert_eq!(contested.len(), 1);
+        assert_eq!(contested[0].0, "botocore");
+        assert_eq!(
+            contested[0].1[1].required_by,
+            Some(("aiobotocore".to_string(), "2.7.0".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_three_way_conflict_with_constraint() {
+        let conflict = parse_resolution_error(THREE_WAY).unwrap();
+        assert_eq!(
+            conflict.requested,
+            vec!["pandas==2.2.0", "numpy==1.21.0", "scipy==1.12.0"]
+        );
+        let contested = conflict.contested();
+        assert_eq!(contested.len(), 1);
+        assert_eq!(contested[0].1.len(), 4);
+        assert!(contested[0].1[3].constraint);
+        assert!(
+            conflict
+                .to_string()
+                .ends_with("; a constraint requires numpy<1.22")
+        );
+    }
+
+    #[test]
+    fn test_other_pip_errors_are_not_conflicts() {
+        assert_eq!(parse_resolution_error(NO_MATCHING_VERSION), None);
+        assert_eq!(parse_resolution_error(""), None);
+    }
+}
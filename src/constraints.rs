@@ -0,0 +1,276 @@
+//! pip constraints files: the one `constraints` under `[project]` points
+//! at, which every `pip install` gets as `-c <file>` and `ppm update`
+//! respects, and the one `ppm gen --constraints` writes from ppmm.lock.
+//!
+//! `constraints` is a path relative to the project root or an http(s)
+//! URL. A URL is downloaded into [`CONSTRAINTS_CACHE`] in the project's
+//! `.ppm/` and reused for [`CONSTRAINTS_MAX_AGE`]; a stale copy is used
+//! when it can't be fetched, and only the cached copy with `--offline`.
+//!
+//! Only `name<specifier>` lines constrain `ppm update`. Options such as
+//! nested `-c` files are left to pip, and environment markers aren't
+//! evaluated, so a line with one holds its package back everywhere.
+
+use crate::error::PpmError;
+use crate::index;
+use crate::marker;
+use crate::packages::normalize_pkg_name;
+use crate::requirements::{self, ParseError, RenderOptions};
+use crate::settings::PackageSpec;
+use crate::state::StateDir;
+use reqwest::Client;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+/// The file `ppm gen --constraints` writes, relative to the project root
+pub const CONSTRAINTS_FILE: &str = "constraints.txt";
+
+/// Downloaded constraints files, one per URL, inside the project's
+/// [`StateDir`]
+pub const CONSTRAINTS_CACHE: &str = "cache/constraints";
+
+/// How long a downloaded constraints file is used before it's fetched
+/// again
+pub const CONSTRAINTS_MAX_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// The version clauses a constraints file puts on one package
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Constraint {
+    /// Package name as first written in the file
+    pub name: String,
+    /// Clauses such as `("<", "5.0")` from every line naming the package
+    pub specifier: Vec<(String, String)>,
+}
+
+impl Constraint {
+    /// Whether `version` satisfies every clause
+    pub fn allows(&self, version: &str) -> bool {
+        self.specifier
+            .iter()
+            .all(|(op, spec)| marker::version_matches(version, op, spec))
+    }
+}
+
+/// The constraint as a requirement, like `django>=4.2,<5.0`
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let clauses: Vec<String> = self
+            .specifier
+            .iter()
+            .map(|(op, version)| format!("{}{}", op, version))
+            .collect();
+        write!(f, "{}{}", self.name, clauses.join(","))
+    }
+}
+
+/// A parsed constraints file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Constraints {
+    by_name: HashMap<String, Constraint>,
+}
+
+impl Constraints {
+    /// Parse the contents of a constraints file. Comments, blank lines
+    /// and options are skipped; a line that isn't a requirement fails.
+    pub fn parse(text: &str) -> Result<Constraints, String> {
+        let parsed = requirements::parse_requirements(text).map_err(|e| e.to_string())?;
+        let mut constraints = Constraints::default();
+        for req in parsed.requirements().filter(|req| req.url.is_none()) {
+            constraints
+                .by_name
+                .entry(normalize_pkg_name(&req.name))
+                .or_insert_with(|| Constraint {
+                    name: req.name.clone(),
+                    specifier: vec![],
+                })
+                .specifier
+                .extend(req.specifier.iter().cloned());
+        }
+        Ok(constraints)
+    }
+
+    /// The constraint on `name`, however it's spelled
+    pub fn get(&self, name: &str) -> Option<&Constraint> {
+        self.by_name.get(&normalize_pkg_name(name))
+    }
+
+    /// Whether no package is constrained
+    pub fn is_empty(&self) -> bool {
+        self.by_name.is_empty()
+    }
+}
+
+/// A constraints file pinning every index package in `lock`, the
+/// contents of ppmm.lock, as `name==version`, sorted by name. Hashes,
+/// editable installs and direct references are left out, since pip
+/// doesn't take them as constraints.
+pub fn from_lock(lock: &str) -> Result<String, ParseError> {
+    let parsed = requirements::parse_requirements(lock)?;
+    let pins: BTreeMap<String, PackageSpec> = parsed
+        .requirements()
+        .filter(|req| req.url.is_none())
+        .filter_map(|req| {
+            let spec = PackageSpec::new(req.pinned_version()?, None);
+            Some((req.name.clone(), spec))
+        })
+        .collect();
+    let opts = RenderOptions {
+        header: Some("Generated by `ppm gen --constraints` from ppmm.lock".to_string()),
+        ..RenderOptions::default()
+    };
+    Ok(requirements::render_requirements(&pins, &opts))
+}
+
+/// Whether the `constraints` setting is a URL rather than a path
+pub fn is_url(source: &str) -> bool {
+    source.starts_with("http://") || source.starts_with("https://")
+}
+
+fn cache_file(url: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    url.hash(&mut hasher);
+    format!("{}/{:016x}.txt", CONSTRAINTS_CACHE, hasher.finish())
+}
+
+/// The local copy of the constraints file at `url`, downloaded into
+/// `cache` unless a copy younger than [`CONSTRAINTS_MAX_AGE`] is there.
+/// A stale copy is used when the download fails; `offline` uses any
+/// copy and fails without one.
+pub async fn fetch(
+    client: &Client,
+    url: &str,
+    cache: &StateDir,
+    offline: bool,
+) -> Result<PathBuf, PpmError> {
+    let name = cache_file(url);
+    let path = cache.subpath(&name);
+    let age = std::fs::metadata(&path)
+        .and_then(|meta| meta.modified())
+        .ok()
+        .map(|modified| {
+            SystemTime::now()
+                .duration_since(modified)
+                .unwrap_or_default()
+        });
+    match age {
+        Some(_) if offline => return Ok(path),
+        Some(age) if age < CONSTRAINTS_MAX_AGE => return Ok(path),
+        None if offline => {
+            return Err(PpmError::Network(format!(
+                "The constraints file {} hasn't been downloaded yet; run without --offline once",
+                url
+            )));
+        }
+        _ => {}
+    }
+    match index::fetch_text(client, url).await {
+        Ok(text) => {
+            Constraints::parse(&text).map_err(|e| {
+                PpmError::Config(format!("Invalid constraints file {}: {}", url, e))
+            })?;
+            cache.write(&name, text.as_bytes())?;
+            Ok(path)
+        }
+        Err(_) if age.is_some() => Ok(path),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_merges_lines_per_package() {
+        let constraints = Constraints::parse(
+            "\
+# Pinned by the platform team
+Django>=4.2  # LTS only
+django<5.0
+requests==2.31.0 ; python_version >= \"3.8\"
+urllib3 @ https://example.com/urllib3-2.2.1-py3-none-any.whl
+-c more-constraints.txt
+",
+        )
+        .unwrap();
+        let django = constraints.get("DJANGO").unwrap();
+        assert_eq!(django.to_string(), "Django>=4.2,<5.0");
+        assert!(django.allows("4.2.11"));
+        assert!(!django.allows("5.0.6"));
+        assert!(!django.allows("4.1"));
+        assert!(constraints.get("requests").unwrap().allows("2.31.0"));
+        assert!(!constraints.get("requests").unwrap().allows("2.32.3"));
+        assert_eq!(constraints.get("urllib3"), None);
+
+        let err = Constraints::parse("flask\n>=2.0\n").unwrap_err();
+        assert!(err.starts_with("line 2: "), "{}", err);
+        assert!(Constraints::parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_from_lock_pins_every_package() {
+        let lock = "\
+# Generated by `ppm lock` from project.toml
+requests==2.31.0 \\
+    --hash=sha256:58cd2187c01e70e6e26505bca751777aa9f2ee0b7f4300988b709f44e013003f
+certifi==2024.2.2 \\
+    --hash=sha256:dc383c07b76109f368f6106eee2b593b04a011ea4d55f652c6ca24a754d1cdd1
+-e file:///work/lib
+internal @ git+https://example.com/internal@abc123
+Idna==3.7
+";
+        assert_eq!(
+            from_lock(lock).unwrap(),
+            "\
+# Generated by `ppm gen --constraints` from ppmm.lock
+certifi==2024.2.2
+Idna==3.7
+requests==2.31.0
+"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_uses_the_cached_copy() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = StateDir::project(dir.path());
+        let client = Client::new();
+        let unreachable = "http://127.0.0.1:9/constraints.txt";
+        assert!(matches!(
+            fetch(&client, unreachable, &cache, true).await,
+            Err(PpmError::Network(_))
+        ));
+        assert!(matches!(
+            fetch(&client, unreachable, &cache, false).await,
+            Err(PpmError::Network(_))
+        ));
+
+        // A fresh copy is used without fetching, and so is a stale one
+        // when the fetch fails
+        let written = cache
+            .write(&cache_file(unreachable), b"django<5.0\n")
+            .unwrap();
+        assert_eq!(
+            fetch(&client, unreachable, &cache, false).await.unwrap(),
+            written
+        );
+        let stale = SystemTime::now() - CONSTRAINTS_MAX_AGE * 2;
+        std::fs::File::options()
+            .write(true)
+            .open(&written)
+            .unwrap()
+            .set_modified(stale)
+            .unwrap();
+        assert_eq!(
+            fetch(&client, unreachable, &cache, false).await.unwrap(),
+            written
+        );
+        assert_eq!(
+            fetch(&client, unreachable, &cache, true).await.unwrap(),
+            written
+        );
+    }
+}
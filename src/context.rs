@@ -0,0 +1,850 @@
+//! Everything a command needs to know about the project it operates on.
+
+use crate::changelog;
+use crate::compat;
+use crate::constraints::{self, Constraints};
+use crate::downloads::{self, DistFile, PYPISTATS_API_URL};
+use crate::entry_points::{self, EntryPoint};
+use crate::error::PpmError;
+use crate::index::{self, ReleaseEntry, ReleaseFile, Releases, Throttle};
+use crate::marker::{Marker, MarkerEnv};
+use crate::merge::{self, Conflict};
+use crate::metadata_cache::{self, Counts};
+use crate::paths;
+use crate::pip_config::{Key, NetworkSettings, PipConfig, Setting, Source};
+use crate::prompt::Prompter;
+use crate::runner::{CommandRunner, SystemRunner};
+use crate::settings::{Config, PROJECT_CONFIG_FILE, PackageSpec};
+use crate::state::StateDir;
+use crate::suggest;
+use crate::venv;
+use crate::workspace::{self, Shared};
+use crate::yanked::{self, YankedPin};
+use reqwest::Client;
+use std::cell::OnceCell;
+use std::ffi::OsString;
+use std::future::Future;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::runtime::Runtime;
+
+/// Minimum spacing between concurrent index lookups, about 20 per second
+const LOOKUP_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Request timeouts a phase of many lookups may take in all, see
+/// [`ProjectContext::phase_budget`]
+pub const PHASE_BUDGET_TIMEOUTS: u32 = 4;
+
+/// A loaded project: its root, config and a shared HTTP client
+#[derive(Debug)]
+pub struct ProjectContext {
+    /// Directory containing project.toml
+    pub root: PathBuf,
+    /// Path the config was loaded from and is written back to
+    pub config_path: PathBuf,
+    /// The parsed project.toml
+    pub config: Config,
+    /// Client used for package index lookups
+    pub client: Client,
+    /// Base URL of the package index JSON API
+    pub index_url: String,
+    /// Index URL, timeout and the like, from ppm's settings or pip's
+    pub network: NetworkSettings,
+    /// Non-fatal problems found while loading, for the caller to show
+    pub warnings: Vec<String>,
+    /// Rate limit of concurrent index lookups
+    pub throttle: Throttle,
+    /// Runs python and pip; a [`ScriptedRunner`](crate::runner::ScriptedRunner)
+    /// in tests
+    pub runner: Arc<dyn CommandRunner>,
+    /// Whether [`ProjectContext::python_version`] may run the interpreter
+    /// when pyvenv.cfg doesn't record a version
+    pub probe_python: bool,
+    /// Whether release histories come from the cache only (`--offline`)
+    pub offline: bool,
+    /// Whether scripts run with the system interpreter rather than the
+    /// venv's: `use-venv = false` under `[project]`, or `--system`
+    pub system_python: bool,
+    /// Root of the workspace the project is a member of
+    pub workspace_root: Option<PathBuf>,
+    /// Settings from `[project]`, with unset ones taken from the workspace
+    pub shared: Shared,
+    /// The config as last read from or written to disk
+    base: Config,
+    /// State of the file when `base` was read
+    stamp: Option<FileStamp>,
+    /// Marker environment of the venv interpreter, queried on first use
+    marker_env: OnceCell<MarkerEnv>,
+    /// Version of the venv interpreter, looked up on first use
+    python_version: OnceCell<Option<String>>,
+    /// Runtime driving the async index lookups, created on first use
+    runtime: OnceCell<Runtime>,
+}
+
+/// Modification time and content hash of a file, to notice outside edits
+#[derive(Debug, PartialEq)]
+struct FileStamp {
+    modified: Option<SystemTime>,
+    hash: u64,
+}
+
+impl FileStamp {
+    /// `None` when the file can't be read
+    fn read(path: &Path) -> Option<FileStamp> {
+        let contents = std::fs::read(path).ok()?;
+        let mut hasher = DefaultHasher::new();
+        contents.hash(&mut hasher);
+        Some(FileStamp {
+            modified: std::fs::metadata(path).and_then(|m| m.modified()).ok(),
+            hash: hasher.finish(),
+        })
+    }
+}
+
+impl ProjectContext {
+    /// Load `root/project.toml`. A relative `root` is made absolute so
+    /// paths stay valid for subprocesses started in another directory.
+    ///
+    /// Fails if the config's paths escape the root, see
+    /// [`Config::check_paths`].
+    pub fn load(root: &Path) -> Result<ProjectContext, PpmError> {
+        let root = std::path::absolute(root)?;
+        let config_path = root.join(PROJECT_CONFIG_FILE);
+        let stamp = FileStamp::read(&config_path);
+        let config = match Config::load_from_file(&config_path.to_string_lossy()) {
+            Ok(config) => config,
+            Err(e @ PpmError::ConfigParse { .. }) => {
+                return Err(workspace_only_error(&root).unwrap_or(e));
+            }
+            Err(e) => return Err(e),
+        };
+        config.check_duplicates()?;
+        config.check_markers()?;
+        config.check_scripts()?;
+        let warnings = config.check_paths(&root)?;
+        let workspace = workspace::enclosing_workspace(&root);
+        let shared = Shared::resolve(
+            &config.project,
+            workspace.as_ref().map(|(_, workspace)| workspace),
+        );
+        let network = network_settings(
+            &config,
+            &config_path,
+            &shared,
+            workspace.as_ref().map(|(root, _)| root.as_path()),
+        );
+        let index_url = network
+            .index_url()
+            .and_then(index::json_api_url)
+            .unwrap_or_else(index::pypi_api_url);
+        let system_python = config.project.use_venv == Some(false);
+        Ok(ProjectContext {
+            root,
+            config_path,
+            base: config.clone(),
+            config,
+            client: network_client(&network),
+            index_url,
+            network,
+            warnings,
+            throttle: Throttle::new(LOOKUP_INTERVAL),
+            runner: Arc::new(SystemRunner),
+            probe_python: true,
+            offline: false,
+            system_python,
+            workspace_root: workspace.map(|(root, _)| root),
+            shared,
+            stamp,
+            marker_env: OnceCell::new(),
+            python_version: OnceCell::new(),
+            runtime: OnceCell::new(),
+        })
+    }
+
+    /// Load the project containing the current directory, as the CLI does.
+    /// project.toml is looked up in the current directory and its parents.
+    pub fn current_dir() -> Result<ProjectContext, PpmError> {
+        let cwd = std::env::current_dir()?;
+        match paths::find_project_root(&cwd) {
+            Some(root) => ProjectContext::load(&root),
+            None => Err(PpmError::ConfigNotFound(PROJECT_CONFIG_FILE.to_string())),
+        }
+    }
+
+    /// Virtual environment directory name from the config, `venv` by default
+    pub fn venv_root(&self) -> &str {
+        self.config.project.venv.as_deref().unwrap_or("venv")
+    }
+
+    /// The virtual environment directory, relative venvs resolved against
+    /// the project root
+    pub fn venv_dir(&self) -> PathBuf {
+        paths::get_venv_dir(&self.root, self.venv_root())
+    }
+
+    /// The project's console scripts, see [`entry_points::discover`]
+    pub fn entry_points(&self) -> Vec<EntryPoint> {
+        entry_points::discover(&self.root, &self.venv_dir(), &self.config.project.name)
+    }
+
+    /// The venv's executable directory
+    pub fn venv_bin_dir(&self) -> PathBuf {
+        paths::get_venv_bin_dir(&self.root, self.venv_root())
+    }
+
+    /// The venv's python interpreter
+    pub fn venv_python(&self) -> PathBuf {
+        paths::get_venv_python_path(&self.root, self.venv_root())
+    }
+
+    /// The venv's pip executable
+    pub fn venv_pip(&self) -> PathBuf {
+        paths::get_venv_pip_path(&self.root, self.venv_root())
+    }
+
+    /// The interpreter scripts run with, as a program and the arguments
+    /// before the script's: the venv's, or in
+    /// [`system_python`](Self::system_python) mode the one the venv would
+    /// be created with, see [`venv::interpreter_command`]
+    pub fn python_command(&self) -> Vec<OsString> {
+        if self.system_python {
+            venv::interpreter_command(self.shared.python.as_deref())
+                .into_iter()
+                .map(OsString::from)
+                .collect()
+        } else {
+            vec![self.venv_python().into_os_string()]
+        }
+    }
+
+    /// Fail in [`system_python`](Self::system_python) mode, saying why
+    /// there is no venv to use
+    pub fn check_venv_in_use(&self) -> Result<(), PpmError> {
+        if !self.system_python {
+            return Ok(());
+        }
+        let (reason, undo) = if self.config.project.use_venv == Some(false) {
+            (
+                "use-venv = false under [project]",
+                "remove it to use a venv",
+            )
+        } else {
+            ("--system", "run without it to use the venv")
+        };
+        Err(PpmError::Venv(format!(
+            "This command needs a venv, but ppm is running on the system Python ({}); {}. ppm never installs packages into the system Python",
+            reason, undo
+        )))
+    }
+
+    /// Fail unless the venv is in use, see [`check_venv_in_use`](Self::check_venv_in_use),
+    /// and exists
+    pub fn require_venv(&self) -> Result<(), PpmError> {
+        self.check_venv_in_use()?;
+        if !self.venv_bin_dir().exists() {
+            return Err(PpmError::Venv(
+                "Virtual Environment Not Found; run `ppm install` to create it".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// `main_script` resolved against the project root rather than the
+    /// current directory
+    pub fn main_script_path(&self) -> PathBuf {
+        self.root.join(&self.config.project.main_script)
+    }
+
+    /// Fail unless the root has the pyproject.toml or setup.py that
+    /// `pip install -e .` needs, for projects that install themselves
+    pub fn check_self_installable(&self) -> Result<(), PpmError> {
+        if ["pyproject.toml", "setup.py"]
+            .iter()
+            .any(|file| self.root.join(file).is_file())
+        {
+            return Ok(());
+        }
+        Err(PpmError::Config(format!(
+            "The project installs itself (install_self), but {} has no pyproject.toml or setup.py; add one so pip can install it, or turn install_self off",
+            self.root.display()
+        )))
+    }
+
+    /// Run `future` to completion on the context's runtime. Must not be
+    /// called from async code.
+    pub fn block_on<F: Future>(&self, future: F) -> Result<F::Output, PpmError> {
+        if self.runtime.get().is_none() {
+            let runtime = tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()?;
+            let _ = self.runtime.set(runtime);
+        }
+        match self.runtime.get() {
+            Some(runtime) => Ok(runtime.block_on(future)),
+            None => Err(PpmError::Other("Failed to start the async runtime".to_string())),
+        }
+    }
+
+    /// Latest version of `pkg` on the configured package index
+    pub fn latest_version(&self, pkg: &str) -> Result<String, PpmError> {
+        self.block_on(index::latest_version(&self.client, &self.index_url, pkg))?
+    }
+
+    /// Latest versions of `pkgs`, looked up concurrently, in the order of
+    /// `pkgs`; `on_done` sees each result as it arrives. Fails with a
+    /// [`PpmError::Timeout`] when the lookups take longer than the
+    /// [`phase_budget`](ProjectContext::phase_budget).
+    pub fn latest_versions(
+        &self,
+        pkgs: &[String],
+        on_done: impl FnMut(&str, &Result<String, PpmError>),
+    ) -> Result<Vec<Result<String, PpmError>>, PpmError> {
+        self.block_on(index::latest_versions(
+            &self.client,
+            &self.index_url,
+            pkgs,
+            &self.throttle,
+            Some(self.phase_budget()),
+            on_done,
+        ))?
+    }
+
+    /// Time a phase of many lookups may take in all:
+    /// [`PHASE_BUDGET_TIMEOUTS`] request timeouts, so lookups queued
+    /// behind hung ones can't stall the phase forever
+    pub fn phase_budget(&self) -> Duration {
+        self.network.request_timeout() * PHASE_BUDGET_TIMEOUTS
+    }
+
+    /// Use ppm's command-line flags, like `--timeout`, over the configured
+    /// network settings
+    pub fn apply_network_flags(&mut self, flags: &[(Key, Setting)]) {
+        if flags.is_empty() {
+            return;
+        }
+        self.network = std::mem::take(&mut self.network).with_flags(flags);
+        self.client = network_client(&self.network);
+    }
+
+    /// The latest version and every usable release of `pkg` on the
+    /// configured package index
+    pub fn releases(&self, pkg: &str) -> Result<Releases, PpmError> {
+        self.block_on(index::releases(&self.client, &self.index_url, pkg))?
+    }
+
+    /// Every release of `pkg` on the configured package index, with upload
+    /// days and yanked markers
+    pub fn release_history(&self, pkg: &str) -> Result<Vec<ReleaseEntry>, PpmError> {
+        self.block_on(index::release_history(&self.client, &self.index_url, pkg))?
+    }
+
+    /// Whether lookups go to PyPI, see [`index::pypi_url`]
+    fn on_pypi(&self) -> bool {
+        self.index_url == index::pypi_api_url()
+    }
+
+    /// The cache release histories from PyPI are kept in; none for other
+    /// indexes, whose packages could share names with PyPI's
+    fn release_cache(&self) -> Option<StateDir> {
+        if !self.on_pypi() {
+            return None;
+        }
+        StateDir::global()
+    }
+
+    /// Release histories of `names`, looked up concurrently and cached
+    /// with their upload days; a package that can't be looked up gets its
+    /// cached history, and only the cache is used when
+    /// [`offline`](ProjectContext::offline). `None` when neither has it.
+    /// The lookups count as cache hits and misses for `ppm cache info`.
+    pub fn release_histories(
+        &self,
+        names: &[String],
+    ) -> Result<Vec<Option<Vec<ReleaseEntry>>>, PpmError> {
+        let cache = self.release_cache();
+        let fetched: Vec<Option<Vec<ReleaseEntry>>> = if self.offline {
+            names.iter().map(|_| None).collect()
+        } else {
+            self.block_on(index::release_histories(
+                &self.client,
+                &self.index_url,
+                names,
+                &self.throttle,
+            ))?
+            .into_iter()
+            .map(Result::ok)
+            .collect()
+        };
+        let Some(cache) = cache else {
+            return Ok(fetched);
+        };
+        let max_size = metadata_cache::configured_max_size();
+        let mut counts = Counts::default();
+        let histories = names
+            .iter()
+            .zip(fetched)
+            .map(|(name, history)| {
+                let cached = match history {
+                    Some(history) => {
+                        let _ = yanked::store(&cache, name, &history, max_size);
+                        counts.misses += 1;
+                        return Some(history);
+                    }
+                    None => yanked::load(&cache, name),
+                };
+                match cached {
+                    Some(_) => counts.hits += 1,
+                    None => counts.misses += 1,
+                }
+                cached
+            })
+            .collect();
+        let _ = metadata_cache::record(&cache, counts);
+        Ok(histories)
+    }
+
+    /// Links to the changelogs of `names`, see [`changelog::changelog_url`],
+    /// looked up concurrently; `None` for a package whose metadata can't
+    /// be looked up, and for all of them when
+    /// [`offline`](ProjectContext::offline)
+    pub fn changelog_urls(&self, names: &[String]) -> Result<Vec<Option<String>>, PpmError> {
+        if self.offline {
+            return Ok(names.iter().map(|_| None).collect());
+        }
+        let infos = self.block_on(index::project_infos(
+            &self.client,
+            &self.index_url,
+            names,
+            &self.throttle,
+        ))?;
+        Ok(names
+            .iter()
+            .zip(infos)
+            .map(|(name, info)| Some(changelog::changelog_url(name, &info.ok()?)))
+            .collect())
+    }
+
+    /// The files of `pkg`'s latest release on the configured package
+    /// index, with their sizes; none when
+    /// [`offline`](ProjectContext::offline)
+    pub fn latest_files(&self, pkg: &str) -> Result<Vec<DistFile>, PpmError> {
+        if self.offline {
+            return Ok(vec![]);
+        }
+        self.block_on(index::latest_files(&self.client, &self.index_url, pkg))?
+    }
+
+    /// Last month's downloads of `pkg` from pypistats.org, see
+    /// [`downloads::monthly_downloads`]; `None` for other indexes than
+    /// PyPI and whenever the service has no answer
+    pub fn monthly_downloads(&self, pkg: &str) -> Option<u64> {
+        if !self.on_pypi() {
+            return None;
+        }
+        let cache = StateDir::global();
+        self.block_on(downloads::monthly_downloads(
+            &self.client,
+            PYPISTATS_API_URL,
+            pkg,
+            cache.as_ref(),
+            self.offline,
+        ))
+        .ok()
+        .flatten()
+    }
+
+    /// The configured pins whose release is yanked on the index, going by
+    /// [`release_histories`](ProjectContext::release_histories)
+    pub fn yanked_pins(&self) -> Result<Vec<YankedPin>, PpmError> {
+        let pins = yanked::pins(&self.config);
+        let names: Vec<String> = pins.iter().map(|(name, _)| name.clone()).collect();
+        let histories = self.release_histories(&names)?;
+        Ok(pins
+            .iter()
+            .zip(histories)
+            .filter_map(|((name, version), history)| {
+                yanked::check_pin(name, version, &history?)
+            })
+            .collect())
+    }
+
+    /// Files published for `version` of `pkg` on the configured package index
+    pub fn release_files(&self, pkg: &str, version: &str) -> Result<Vec<ReleaseFile>, PpmError> {
+        self.block_on(index::release_files(
+            &self.client,
+            &self.index_url,
+            pkg,
+            version,
+        ))?
+    }
+
+    /// Close matches on PyPI for `pkg`, a name the index doesn't have;
+    /// none for other indexes, whose packages PyPI's lists don't cover
+    pub fn suggest(&self, pkg: &str) -> Result<Vec<String>, PpmError> {
+        if !self.on_pypi() {
+            return Ok(vec![]);
+        }
+        let cache = StateDir::global();
+        let search_url = suggest::search_url(&index::pypi_url());
+        self.block_on(suggest::suggestions(
+            &self.client,
+            &search_url,
+            pkg,
+            cache.as_ref(),
+        ))
+    }
+
+    /// The constraints file pip is given, downloaded first when
+    /// `constraints` is a URL (see [`constraints::fetch`]); `None` when
+    /// unset
+    pub fn constraints_file(&self) -> Result<Option<PathBuf>, PpmError> {
+        let Some(source) = &self.config.project.constraints else {
+            return Ok(None);
+        };
+        if constraints::is_url(source) {
+            let cache = self.state();
+            return self
+                .block_on(constraints::fetch(
+                    &self.client,
+                    source,
+                    &cache,
+                    self.offline,
+                ))?
+                .map(Some);
+        }
+        let path = self.root.join(source);
+        if !path.is_file() {
+            return Err(PpmError::Config(format!(
+                "The constraints file {} doesn't exist",
+                source
+            )));
+        }
+        Ok(Some(path))
+    }
+
+    /// `-c <file>` for `pip install` when `constraints` is set, otherwise
+    /// nothing
+    pub fn constraint_args(&self) -> Result<Vec<String>, PpmError> {
+        Ok(self
+            .constraints_file()?
+            .map(|path| vec!["-c".to_string(), path.to_string_lossy().into_owned()])
+            .unwrap_or_default())
+    }
+
+    /// The parsed `constraints` file, empty when unset
+    pub fn constraints(&self) -> Result<Constraints, PpmError> {
+        let Some(path) = self.constraints_file()? else {
+            return Ok(Constraints::default());
+        };
+        let text = std::fs::read_to_string(&path)?;
+        Constraints::parse(&text).map_err(|e| {
+            PpmError::Config(format!(
+                "Invalid constraints file {}: {}",
+                self.config.project.constraints.as_deref().unwrap_or_default(),
+                e
+            ))
+        })
+    }
+
+    /// The project's `.ppm/` state directory
+    pub fn state(&self) -> StateDir {
+        StateDir::project(&self.root)
+    }
+
+    /// Version of the venv interpreter, e.g. `3.11.4`, or `None` without a
+    /// venv. It is read from pyvenv.cfg when recorded there and otherwise
+    /// asked from the interpreter (unless `probe_python` is off), once per
+    /// context.
+    pub fn python_version(&self) -> Option<&str> {
+        // Not cached, so a venv created later in the run is picked up
+        if !self.venv_bin_dir().exists() {
+            return None;
+        }
+        self.python_version
+            .get_or_init(|| {
+                venv::cfg_python_version(&self.venv_dir()).or_else(|| {
+                    self.probe_python
+                        .then(|| {
+                            venv::probe_python_version(self.runner.as_ref(), &self.venv_python())
+                        })
+                        .flatten()
+                })
+            })
+            .as_deref()
+    }
+
+    /// Marker environment of the venv interpreter. It is queried once and
+    /// cached, so the venv must exist by the first call.
+    pub fn marker_env(&self) -> Result<&MarkerEnv, PpmError> {
+        if let Some(env) = self.marker_env.get() {
+            return Ok(env);
+        }
+        let env = MarkerEnv::query(self.runner.as_ref(), &self.venv_python())?;
+        Ok(self.marker_env.get_or_init(|| env))
+    }
+
+    /// Whether a configured package applies to the venv: true without a
+    /// marker, otherwise the marker evaluated by the venv interpreter
+    pub fn marker_matches(&self, spec: &PackageSpec) -> Result<bool, PpmError> {
+        match spec.marker() {
+            None => Ok(true),
+            Some(marker) => {
+                let marker = Marker::parse(marker).map_err(PpmError::Config)?;
+                Ok(marker.evaluate(self.marker_env()?))
+            }
+        }
+    }
+
+    /// Write the (possibly modified) config back to where it was loaded from.
+    ///
+    /// If the file was edited since it was loaded, this run's changes are
+    /// merged on top of the edits; keys changed differently on both sides
+    /// are a [`PpmError::ConfigConflict`] and nothing is written.
+    pub fn save(&mut self) -> Result<(), PpmError> {
+        self.save_resolving(None)
+    }
+
+    /// Like [`save`](Self::save), but asks `prompter` which value to keep
+    /// for each conflicting key instead of failing
+    pub fn save_with_prompter(&mut self, prompter: &dyn Prompter) -> Result<(), PpmError> {
+        self.save_resolving(Some(prompter))
+    }
+
+    /// Raise `ppm-version` when the config gained a construct older ppm
+    /// releases don't handle, see [`compat::raise_for`]. Saving does this
+    /// too; call it first to tell the user.
+    pub fn raise_ppm_version(&mut self) -> Option<compat::Raise> {
+        compat::raise_for(&self.base, &mut self.config)
+    }
+
+    fn save_resolving(&mut self, prompter: Option<&dyn Prompter>) -> Result<(), PpmError> {
+        self.raise_ppm_version();
+        let path = self.config_path.to_string_lossy().into_owned();
+        let current = FileStamp::read(&self.config_path);
+        if current.is_some() && current != self.stamp {
+            let theirs = Config::load_from_file(&path)?;
+            let mut unresolved = vec![];
+            let merged = merge::three_way(
+                &self.base.to_table()?,
+                &self.config.to_table()?,
+                &theirs.to_table()?,
+                &mut |conflict| match prompter {
+                    Some(prompter) => prompter.ask_yes_no(&conflict_question(conflict), true),
+                    None => {
+                        unresolved.push(conflict.key.clone());
+                        true
+                    }
+                },
+            );
+            if !unresolved.is_empty() {
+                return Err(PpmError::ConfigConflict {
+                    path,
+                    keys: unresolved,
+                });
+            }
+            self.config = Config::from_table(merged, &path)?;
+        }
+
+        self.config.write_to_file(&path)?;
+        self.base = self.config.clone();
+        self.stamp = FileStamp::read(&self.config_path);
+        Ok(())
+    }
+}
+
+/// The network settings of a project: its own or its workspace's
+/// `index-url`, and pip's settings for the rest
+fn network_settings(
+    config: &Config,
+    config_path: &Path,
+    shared: &Shared,
+    workspace_root: Option<&Path>,
+) -> NetworkSettings {
+    let mut own = Vec::new();
+    if let Some(url) = &shared.index_url {
+        let path = match workspace_root {
+            Some(root) if config.project.index_url.is_none() => root.join(PROJECT_CONFIG_FILE),
+            _ => config_path.to_path_buf(),
+        };
+        own.push((
+            Key::IndexUrl,
+            Setting {
+                value: url.clone(),
+                source: Source::Project(path),
+            },
+        ));
+    }
+    NetworkSettings::resolve(&PipConfig::load(), &own)
+}
+
+/// The client for index lookups, with the configured timeout for
+/// connecting and for each whole request
+fn network_client(network: &NetworkSettings) -> Client {
+    let timeout = network.request_timeout();
+    Client::builder()
+        .connect_timeout(timeout)
+        .timeout(timeout)
+        .build()
+        .unwrap_or_default()
+}
+
+/// The error for a project.toml at `root` that only holds a
+/// `[workspace]` table, which isn't a project to run commands in
+fn workspace_only_error(root: &Path) -> Option<PpmError> {
+    let workspace = workspace::load_workspace(root).ok()??;
+    let members = workspace::members(root, &workspace).unwrap_or_default();
+    Some(PpmError::Config(format!(
+        "{} is a workspace root; pick a member with --project <member> or use --all (members: {})",
+        root.join(PROJECT_CONFIG_FILE).display(),
+        workspace::names(&members)
+    )))
+}
+
+fn conflict_question(conflict: &Conflict) -> String {
+    let describe = |value: &Option<toml::Value>| match value {
+        Some(value) => value.to_string(),
+        None => "removed".to_string(),
+    };
+    format!(
+        "project.toml changed on disk: '{}' is {} there but {} here. Keep {}?",
+        conflict.key,
+        describe(&conflict.theirs),
+        describe(&conflict.ours),
+        describe(&conflict.ours)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prompt::ScriptedPrompter;
+
+    #[test]
+    fn test_load_from_root() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join(PROJECT_CONFIG_FILE),
+            r#"
+[project]
+name = "demo"
+version = "0.1.0"
+description = ""
+main_script = "./main.py"
+venv = ".venv"
+
+[packages]
+
+[scripts]
+"#,
+        )
+        .unwrap();
+
+        let ctx = ProjectContext::load(dir.path()).unwrap();
+        assert_eq!(ctx.root, dir.path());
+        assert_eq!(ctx.config.project.name, "demo");
+        assert_eq!(ctx.venv_root(), ".venv");
+        assert!(ctx.venv_python().starts_with(dir.path().join(".venv")));
+        assert_eq!(ctx.main_script_path(), dir.path().join("main.py"));
+    }
+
+    #[test]
+    fn test_load_rejects_external_venv() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join(PROJECT_CONFIG_FILE),
+            r#"
+[project]
+name = "demo"
+version = "0.1.0"
+description = ""
+main_script = "../main.py"
+venv = "../venv"
+
+[packages]
+
+[scripts]
+"#,
+        )
+        .unwrap();
+        let err = ProjectContext::load(dir.path()).unwrap_err();
+        assert!(err.to_string().contains("'../venv'"));
+        assert_eq!(err.exit_code(), 2);
+    }
+
+    const DEMO: &str = r#"
+[project]
+name = "demo"
+version = "0.1.0"
+description = ""
+main_script = "./main.py"
+
+[packages]
+requests = "2.30.0"
+flask = "3.0.0"
+
+[scripts]
+"#;
+
+    #[test]
+    fn test_save_merges_concurrent_edits() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PROJECT_CONFIG_FILE);
+        std::fs::write(&path, DEMO).unwrap();
+
+        let mut ctx = ProjectContext::load(dir.path()).unwrap();
+        ctx.config
+            .packages
+            .insert("requests".to_string(), "2.31.0".into());
+
+        // Meanwhile someone edits the file in their editor
+        let edited = DEMO
+            .replace("description = \"\"", "description = \"edited\"")
+            .replace("[scripts]", "[scripts]\ntest = \"pytest\"");
+        std::fs::write(&path, edited).unwrap();
+
+        ctx.save().unwrap();
+        let saved = Config::load_from_file(path.to_str().unwrap()).unwrap();
+        assert_eq!(saved.packages["requests"].version(), Some("2.31.0"));
+        assert_eq!(saved.packages["flask"].version(), Some("3.0.0"));
+        assert_eq!(saved.project.description, "edited");
+        assert_eq!(saved.scripts["test"].command(), Some("pytest"));
+        assert_eq!(ctx.config, saved);
+    }
+
+    #[test]
+    fn test_save_reports_conflicting_edits() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PROJECT_CONFIG_FILE);
+        std::fs::write(&path, DEMO).unwrap();
+
+        let mut ctx = ProjectContext::load(dir.path()).unwrap();
+        ctx.config
+            .packages
+            .insert("requests".to_string(), "2.31.0".into());
+        let edited = DEMO.replace("2.30.0", "2.29.0");
+        std::fs::write(&path, &edited).unwrap();
+
+        match ctx.save().unwrap_err() {
+            PpmError::ConfigConflict { keys, .. } => {
+                assert_eq!(keys, vec!["packages.requests".to_string()])
+            }
+            other => panic!("expected ConfigConflict, got {:?}", other),
+        }
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), edited);
+
+        let prompter = ScriptedPrompter::new(&["n"]);
+        ctx.save_with_prompter(&prompter).unwrap();
+        assert!(prompter.transcript().contains("'packages.requests'"));
+        let saved = Config::load_from_file(path.to_str().unwrap()).unwrap();
+        assert_eq!(saved.packages["requests"].version(), Some("2.29.0"));
+    }
+
+    #[test]
+    fn test_load_missing_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = ProjectContext::load(dir.path()).unwrap_err();
+        assert!(matches!(err, PpmError::ConfigNotFound(_)));
+    }
+}
@@ -0,0 +1,195 @@
+//! `ppm test --coverage`: run the tests under coverage.py and read back
+//! what it measured.
+//!
+//! The report comes from `coverage json -o -`, so only the totals and the
+//! per-file summaries coverage.py computed are used; ppm doesn't count
+//! lines itself. `coverage-min` under `[project]` sets the percentage the
+//! total must reach.
+
+use crate::test_runner::Framework;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Package that provides `python -m coverage`; pytest-cov brings it too
+pub const COVERAGE_PACKAGE: &str = "coverage";
+
+/// Where `coverage html` writes the report, relative to the project root
+pub const HTML_DIR: &str = "htmlcov";
+
+/// Line counts of a file, or of the whole run
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct CoverageSummary {
+    /// Statements that can run
+    pub num_statements: u64,
+    /// Statements that didn't run
+    pub missing_lines: u64,
+    /// Share of statements (and branches, when measured) that ran
+    pub percent_covered: f64,
+}
+
+/// One measured file
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileCoverage {
+    /// Path as coverage.py reports it, relative to the project root
+    pub path: String,
+    /// Its line counts
+    pub summary: CoverageSummary,
+}
+
+/// What `coverage json` reported
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoverageReport {
+    /// The whole run
+    pub totals: CoverageSummary,
+    /// Each measured file, ordered by path
+    pub files: Vec<FileCoverage>,
+}
+
+#[derive(Deserialize)]
+struct RawFile {
+    summary: CoverageSummary,
+}
+
+#[derive(Deserialize)]
+struct RawReport {
+    totals: CoverageSummary,
+    #[serde(default)]
+    files: BTreeMap<String, RawFile>,
+}
+
+/// Parse the output of `coverage json -o -`
+pub fn parse_report(json: &str) -> Result<CoverageReport, serde_json::Error> {
+    let raw: RawReport = serde_json::from_str(json)?;
+    Ok(CoverageReport {
+        totals: raw.totals,
+        files: raw
+            .files
+            .into_iter()
+            .map(|(path, file)| FileCoverage {
+                path,
+                summary: file.summary,
+            })
+            .collect(),
+    })
+}
+
+/// Whether `percent` falls short of `minimum`; no minimum is always met
+pub fn below_minimum(percent: f64, minimum: Option<f64>) -> bool {
+    minimum.is_some_and(|minimum| percent < minimum)
+}
+
+/// `percent` as coverage.py prints it, like `87%` or `87.5%`
+pub fn format_percent(percent: f64) -> String {
+    let rounded = (percent * 10.0).round() / 10.0;
+    if rounded.fract() == 0.0 {
+        format!("{:.0}%", rounded)
+    } else {
+        format!("{:.1}%", rounded)
+    }
+}
+
+/// Arguments to the venv's python that run `framework` with `extra` under
+/// coverage, leaving the venv at `venv_dir` unmeasured
+pub fn run_args(framework: Framework, extra: &[String], venv_dir: &Path) -> Vec<String> {
+    let mut args = vec![
+        "-m".to_string(),
+        "coverage".to_string(),
+        "run".to_string(),
+        format!("--omit={}", venv_dir.join("*").display()),
+    ];
+    args.extend(framework.args(extra));
+    args
+}
+
+/// Arguments to the venv's python that print the report as JSON
+pub fn json_args() -> Vec<String> {
+    ["-m", "coverage", "json", "-o", "-"]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect()
+}
+
+/// Arguments to the venv's python that write the HTML report to
+/// [`HTML_DIR`]
+pub fn html_args() -> Vec<String> {
+    ["-m", "coverage", "html", "-d", HTML_DIR]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPORT: &str = r#"{
+        "meta": {"format": 2, "version": "7.5.1", "branch_coverage": false},
+        "files": {
+            "src/app.py": {
+                "executed_lines": [1, 2, 3],
+                "summary": {"covered_lines": 3, "num_statements": 4, "percent_covered": 75.0,
+                            "percent_covered_display": "75", "missing_lines": 1, "excluded_lines": 0},
+                "missing_lines": [7]
+            },
+            "src/__init__.py": {
+                "executed_lines": [],
+                "summary": {"covered_lines": 0, "num_statements": 0, "percent_covered": 100.0,
+                            "percent_covered_display": "100", "missing_lines": 0, "excluded_lines": 0},
+                "missing_lines": []
+            }
+        },
+        "totals": {"covered_lines": 3, "num_statements": 4, "percent_covered": 75.0,
+                   "percent_covered_display": "75", "missing_lines": 1, "excluded_lines": 0}
+    }"#;
+
+    #[test]
+    fn test_parse_report() {
+        let report = parse_report(REPORT).unwrap();
+        assert_eq!(report.totals.percent_covered, 75.0);
+        let paths: Vec<&str> = report.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, vec!["src/__init__.py", "src/app.py"]);
+        assert_eq!(
+            report.files[1].summary,
+            CoverageSummary {
+                num_statements: 4,
+                missing_lines: 1,
+                percent_covered: 75.0,
+            }
+        );
+        assert!(parse_report("No data to report.").is_err());
+    }
+
+    #[test]
+    fn test_minimum_and_formatting() {
+        assert!(below_minimum(74.9, Some(75.0)));
+        assert!(!below_minimum(75.0, Some(75.0)));
+        assert!(!below_minimum(0.0, None));
+        assert_eq!(format_percent(75.0), "75%");
+        assert_eq!(format_percent(87.46), "87.5%");
+        assert_eq!(format_percent(99.99), "100%");
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_coverage_arguments() {
+        assert_eq!(
+            run_args(
+                Framework::Pytest,
+                &["-x".to_string()],
+                Path::new("/project/venv")
+            ),
+            vec![
+                "-m",
+                "coverage",
+                "run",
+                "--omit=/project/venv/*",
+                "-m",
+                "pytest",
+                "-x"
+            ]
+        );
+        assert_eq!(json_args().join(" "), "-m coverage json -o -");
+        assert_eq!(html_args().join(" "), "-m coverage html -d htmlcov");
+    }
+}
@@ -0,0 +1,359 @@
+//! What installing a package downloads and how often others download it,
+//! for `ppm info <package>`.
+//!
+//! The size comes from the files the JSON API lists for the latest
+//! release: [`pick_download`] takes the wheel built for this platform,
+//! else a pure-Python wheel, else the sdist.
+//!
+//! Monthly download counts come from pypistats.org, a third-party service
+//! that only knows PyPI's projects. They're cached in the global cache
+//! directory for [`DOWNLOADS_MAX_AGE`] and are best effort: when the
+//! service can't be reached the count is just left out. `--no-stats`
+//! skips it entirely.
+
+use crate::index;
+use crate::packages::normalize_pkg_name;
+use crate::state::StateDir;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// pypistats.org's API, which has `<name>/recent` under it
+pub const PYPISTATS_API_URL: &str = "https://pypistats.org/api/packages";
+
+/// Cached download counts, inside the global [`StateDir`]
+pub const DOWNLOADS_CACHE: &str = "cache/downloads.json";
+
+/// How long a cached download count is used before it's fetched again
+pub const DOWNLOADS_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A file published for a release, as the JSON API lists it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DistFile {
+    /// Wheel or sdist file name
+    pub filename: String,
+    /// Size in bytes
+    pub size: u64,
+    /// `bdist_wheel` or `sdist`
+    pub packagetype: String,
+}
+
+impl DistFile {
+    /// Whether the file is a wheel
+    pub fn is_wheel(&self) -> bool {
+        self.filename.ends_with(".whl")
+    }
+
+    /// Whether the file is a source distribution
+    pub fn is_sdist(&self) -> bool {
+        self.packagetype == "sdist"
+    }
+
+    /// The platform tags of a wheel, like `manylinux_2_17_x86_64` or
+    /// `any`; none for an sdist
+    pub fn platform_tags(&self) -> Vec<&str> {
+        let Some(stem) = self.filename.strip_suffix(".whl") else {
+            return vec![];
+        };
+        stem.rsplit('-')
+            .next()
+            .map(|tags| tags.split('.').collect())
+            .unwrap_or_default()
+    }
+}
+
+/// The files in a JSON API document's `urls` list, which are those of the
+/// latest release
+pub fn dist_files(urls: &serde_json::Value) -> Vec<DistFile> {
+    let Some(urls) = urls.as_array() else {
+        return vec![];
+    };
+    urls.iter()
+        .filter_map(|file| {
+            Some(DistFile {
+                filename: file["filename"].as_str()?.to_string(),
+                size: file["size"].as_u64()?,
+                packagetype: file["packagetype"].as_str().unwrap_or_default().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// An operating system and CPU that wheels are built for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Platform {
+    /// `linux`, `macos` or `windows`, as in [`std::env::consts::OS`]
+    pub os: &'static str,
+    /// `x86_64`, `aarch64` or `x86`, as in [`std::env::consts::ARCH`]
+    pub arch: &'static str,
+    /// Whether Linux uses musl rather than glibc
+    pub musl: bool,
+}
+
+impl Platform {
+    /// The platform ppm runs on, taken to be the venv's too
+    pub fn current() -> Platform {
+        Platform {
+            os: std::env::consts::OS,
+            arch: std::env::consts::ARCH,
+            musl: cfg!(target_env = "musl"),
+        }
+    }
+
+    /// Whether a wheel with the platform tag `tag` installs here. Pure
+    /// `any` wheels aren't counted, see [`pick_download`].
+    pub fn supports(&self, tag: &str) -> bool {
+        let arches: &[&str] = match self.arch {
+            "x86_64" => &["x86_64", "amd64"],
+            "aarch64" => &["aarch64", "arm64"],
+            "x86" => &["i686", "win32"],
+            _ => &[],
+        };
+        let arch_matches = arches.iter().any(|arch| tag.ends_with(arch))
+            || (self.os == "macos" && tag.ends_with("universal2"))
+            || tag.ends_with(self.arch);
+        let os_matches = match self.os {
+            "linux" if self.musl => tag.starts_with("musllinux"),
+            "linux" => tag.starts_with("manylinux") || tag.starts_with("linux"),
+            "macos" => tag.starts_with("macosx"),
+            "windows" => tag.starts_with("win"),
+            _ => false,
+        };
+        os_matches && arch_matches
+    }
+}
+
+/// The file installing from `files` on `platform` would download: the
+/// first wheel built for it, else a pure-Python wheel, else the sdist
+pub fn pick_download<'a>(files: &'a [DistFile], platform: &Platform) -> Option<&'a DistFile> {
+    let wheels = || files.iter().filter(|file| file.is_wheel());
+    wheels()
+        .find(|file| {
+            file.platform_tags()
+                .iter()
+                .any(|tag| platform.supports(tag))
+        })
+        .or_else(|| wheels().find(|file| file.platform_tags().contains(&"any")))
+        .or_else(|| files.iter().find(|file| file.is_sdist()))
+}
+
+/// A count with thousands separators, like `1,234,567`
+pub fn format_count(count: u64) -> String {
+    let digits = count.to_string();
+    let mut out = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(digit);
+    }
+    out
+}
+
+/// The last month's downloads in a pypistats.org `recent` response
+pub fn parse_recent(json: &str) -> Option<u64> {
+    let value: serde_json::Value = serde_json::from_str(json).ok()?;
+    value["data"]["last_month"].as_u64()
+}
+
+/// A download count as cached, with when it was fetched
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+struct CachedCount {
+    fetched_at: u64,
+    last_month: u64,
+}
+
+fn load_cache(cache: Option<&StateDir>) -> BTreeMap<String, CachedCount> {
+    cache
+        .and_then(|dir| std::fs::read(dir.subpath(DOWNLOADS_CACHE)).ok())
+        .and_then(|contents| serde_json::from_slice(&contents).ok())
+        .unwrap_or_default()
+}
+
+/// Last month's downloads of `name` from the pypistats.org API at
+/// `api_url`, from `cache` while it's fresh and fetched and cached
+/// otherwise. A stale count is used when the fetch fails, and only the
+/// cache with `offline`. `None` when there's no count to be had.
+pub async fn monthly_downloads(
+    client: &Client,
+    api_url: &str,
+    name: &str,
+    cache: Option<&StateDir>,
+    offline: bool,
+) -> Option<u64> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let key = normalize_pkg_name(name);
+    let mut counts = load_cache(cache);
+    let cached = counts.get(&key).copied();
+    match cached {
+        Some(cached) if offline => return Some(cached.last_month),
+        Some(cached) if now.saturating_sub(cached.fetched_at) < DOWNLOADS_MAX_AGE.as_secs() => {
+            return Some(cached.last_month);
+        }
+        None if offline => return None,
+        _ => {}
+    }
+    let url = format!("{}/{}/recent", api_url.trim_end_matches('/'), key);
+    let Some(last_month) = index::fetch_text(client, &url)
+        .await
+        .ok()
+        .and_then(|json| parse_recent(&json))
+    else {
+        return cached.map(|cached| cached.last_month);
+    };
+    if let Some(dir) = cache {
+        counts.insert(
+            key,
+            CachedCount {
+                fetched_at: now,
+                last_month,
+            },
+        );
+        if let Ok(contents) = serde_json::to_vec(&counts) {
+            let _ = dir.write(DOWNLOADS_CACHE, &contents);
+        }
+    }
+    Some(last_month)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RELEASE_FILES: &str = include_str!("../tests/fixtures/release-files.json");
+
+    fn files(package: &str) -> Vec<DistFile> {
+        let fixture: serde_json::Value = serde_json::from_str(RELEASE_FILES).unwrap();
+        dist_files(&fixture[package])
+    }
+
+    fn platform(os: &'static str, arch: &'static str) -> Platform {
+        Platform {
+            os,
+            arch,
+            musl: false,
+        }
+    }
+
+    fn picked(package: &str, platform: &Platform) -> Option<String> {
+        pick_download(&files(package), platform).map(|file| file.filename.clone())
+    }
+
+    #[test]
+    fn test_pick_the_wheel_for_the_platform() {
+        let numpy = files("numpy");
+        // The file without a size is left out
+        assert_eq!(numpy.len(), 8);
+        assert_eq!(
+            numpy[0].platform_tags(),
+            ["manylinux_2_17_x86_64", "manylinux2014_x86_64"]
+        );
+        assert_eq!(
+            picked("numpy", &platform("linux", "x86_64")).unwrap(),
+            "numpy-2.1.0-cp312-cp312-manylinux_2_17_x86_64.manylinux2014_x86_64.whl"
+        );
+        assert_eq!(
+            picked("numpy", &platform("linux", "aarch64")).unwrap(),
+            "numpy-2.1.0-cp312-cp312-manylinux_2_17_aarch64.manylinux2014_aarch64.whl"
+        );
+        let musl = Platform {
+            musl: true,
+            ..platform("linux", "x86_64")
+        };
+        assert_eq!(
+            picked("numpy", &musl).unwrap(),
+            "numpy-2.1.0-cp312-cp312-musllinux_1_1_x86_64.whl"
+        );
+        assert_eq!(
+            picked("numpy", &platform("macos", "aarch64")).unwrap(),
+            "numpy-2.1.0-cp312-cp312-macosx_11_0_arm64.whl"
+        );
+        assert_eq!(
+            picked("numpy", &platform("windows", "x86_64")).unwrap(),
+            "numpy-2.1.0-cp312-cp312-win_amd64.whl"
+        );
+        assert_eq!(
+            picked("numpy", &platform("windows", "x86")).unwrap(),
+            "numpy-2.1.0-cp312-cp312-win32.whl"
+        );
+    }
+
+    #[test]
+    fn test_fall_back_to_a_pure_wheel_then_the_sdist() {
+        // No wheel for this platform, so the sdist
+        assert_eq!(
+            picked("numpy", &platform("freebsd", "x86_64")).unwrap(),
+            "numpy-2.1.0.tar.gz"
+        );
+        // A pure wheel suits every platform
+        for platform in [platform("linux", "x86_64"), platform("freebsd", "riscv64")] {
+            assert_eq!(
+                picked("requests", &platform).unwrap(),
+                "requests-2.32.3-py3-none-any.whl"
+            );
+        }
+        // A universal2 wheel covers both Mac CPUs; other platforms build
+        // from the sdist
+        assert_eq!(
+            picked("pyyaml", &platform("macos", "x86_64")).unwrap(),
+            "PyYAML-6.0.2-cp312-cp312-macosx_10_9_universal2.whl"
+        );
+        let pyyaml = files("pyyaml");
+        let sdist = pick_download(&pyyaml, &platform("linux", "x86_64")).unwrap();
+        assert_eq!(sdist.filename, "pyyaml-6.0.2.tar.gz");
+        assert_eq!(sdist.size, 130_213);
+        assert!(pick_download(&[], &platform("linux", "x86_64")).is_none());
+    }
+
+    #[test]
+    fn test_format_count_and_parse_recent() {
+        assert_eq!(format_count(0), "0");
+        assert_eq!(format_count(999), "999");
+        assert_eq!(format_count(1_000), "1,000");
+        assert_eq!(format_count(123_456_789), "123,456,789");
+        let json = r#"{"data": {"last_day": 1, "last_month": 4217, "last_week": 980},
+            "package": "demo", "type": "recent_downloads"}"#;
+        assert_eq!(parse_recent(json), Some(4217));
+        assert_eq!(parse_recent(r#"{"data": {}}"#), None);
+        assert_eq!(parse_recent("<html>"), None);
+    }
+
+    #[tokio::test]
+    async fn test_download_counts_are_cached() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = StateDir::project(dir.path());
+        let client = Client::new();
+        let unreachable = "http://127.0.0.1:9/api/packages";
+        // Nothing cached and nothing reachable: no count, and no error
+        assert_eq!(
+            monthly_downloads(&client, unreachable, "Demo", Some(&cache), false).await,
+            None
+        );
+
+        // A fresh count is used without fetching; a stale one when the
+        // fetch fails, and any with --offline
+        let stale = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            - DOWNLOADS_MAX_AGE.as_secs() * 2;
+        let counts = format!(
+            r#"{{"demo": {{"fetched_at": {}, "last_month": 42}}}}"#,
+            stale
+        );
+        cache.write(DOWNLOADS_CACHE, counts.as_bytes()).unwrap();
+        for offline in [false, true] {
+            assert_eq!(
+                monthly_downloads(&client, unreachable, "Demo", Some(&cache), offline).await,
+                Some(42)
+            );
+        }
+        assert_eq!(
+            monthly_downloads(&client, unreachable, "other", Some(&cache), true).await,
+            None
+        );
+    }
+}
@@ -0,0 +1,222 @@
+//! The console scripts a project declares for itself, which `ppm run`
+//! runs by name when `[scripts]` has no entry of that name.
+//!
+//! Once the project is installed into its venv, typically editable with
+//! `install_self`, the `[console_scripts]` section of its dist-info
+//! `entry_points.txt` lists them and pip has written an executable for
+//! each into the venv's bin directory. Until then they're read from
+//! `[project.scripts]` in pyproject.toml, and can be listed but not run.
+
+use crate::build::PYPROJECT_FILE;
+use crate::packages::normalize_pkg_name;
+use crate::paths::{self, VENV_BIN_DIR};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where an entry point was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// The installed project's dist-info `entry_points.txt`
+    Installed,
+    /// `[project.scripts]` in pyproject.toml, not installed yet
+    Pyproject,
+}
+
+/// A console script, e.g. `mycli = "myapp.cli:main"`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryPoint {
+    /// Name of the executable
+    pub name: String,
+    /// The function it calls, `module:function`
+    pub target: String,
+    /// Where it was found
+    pub source: Source,
+}
+
+impl EntryPoint {
+    /// The executable pip writes for it into the venv at `venv_dir`
+    pub fn executable(&self, venv_dir: &Path) -> PathBuf {
+        let bin = venv_dir.join(VENV_BIN_DIR);
+        if cfg!(windows) {
+            bin.join(format!("{}.exe", self.name))
+        } else {
+            bin.join(&self.name)
+        }
+    }
+}
+
+/// The `[console_scripts]` entries of an `entry_points.txt`, in file
+/// order
+pub fn parse_entry_points_txt(text: &str) -> Vec<EntryPoint> {
+    let mut section = "";
+    let mut entry_points = vec![];
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = name.trim();
+            continue;
+        }
+        if section != "console_scripts" {
+            continue;
+        }
+        if let Some((name, target)) = line.split_once('=') {
+            entry_points.push(EntryPoint {
+                name: name.trim().to_string(),
+                target: target.trim().to_string(),
+                source: Source::Installed,
+            });
+        }
+    }
+    entry_points
+}
+
+/// The `[project.scripts]` of a pyproject.toml, sorted by name; none
+/// when it doesn't parse
+pub fn parse_pyproject_scripts(text: &str) -> Vec<EntryPoint> {
+    let Ok(table) = toml::from_str::<toml::Table>(text) else {
+        return vec![];
+    };
+    let Some(scripts) = table
+        .get("project")
+        .and_then(|project| project.get("scripts"))
+        .and_then(toml::Value::as_table)
+    else {
+        return vec![];
+    };
+    let mut entry_points: Vec<EntryPoint> = scripts
+        .iter()
+        .filter_map(|(name, target)| {
+            Some(EntryPoint {
+                name: name.clone(),
+                target: target.as_str()?.to_string(),
+                source: Source::Pyproject,
+            })
+        })
+        .collect();
+    entry_points.sort_by(|a, b| a.name.cmp(&b.name));
+    entry_points
+}
+
+/// The distribution name the project installs as: `[project] name` in
+/// pyproject.toml, else `fallback`, the name in project.toml
+pub fn distribution_name(root: &Path, fallback: &str) -> String {
+    fs::read_to_string(root.join(PYPROJECT_FILE))
+        .ok()
+        .and_then(|text| toml::from_str::<toml::Table>(&text).ok())
+        .and_then(|table| {
+            table
+                .get("project")?
+                .get("name")?
+                .as_str()
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| fallback.to_string())
+}
+
+/// The dist-info directory of distribution `name` in the venv at
+/// `venv_dir`
+pub fn find_dist_info(venv_dir: &Path, name: &str) -> Option<PathBuf> {
+    let wanted = normalize_pkg_name(name);
+    for site_packages in paths::site_packages_dirs(venv_dir) {
+        let Ok(entries) = fs::read_dir(&site_packages) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            if let Some((dist, _)) = file_name
+                .strip_suffix(".dist-info")
+                .and_then(|stem| stem.split_once('-'))
+                && normalize_pkg_name(dist) == wanted
+            {
+                return Some(entry.path());
+            }
+        }
+    }
+    None
+}
+
+/// The console scripts of the project at `root` named `name` in
+/// project.toml: from its dist-info in the venv at `venv_dir` when it's
+/// installed there, else from its pyproject.toml
+pub fn discover(root: &Path, venv_dir: &Path, name: &str) -> Vec<EntryPoint> {
+    let dist = distribution_name(root, name);
+    if let Some(dist_info) = find_dist_info(venv_dir, &dist) {
+        return fs::read_to_string(dist_info.join("entry_points.txt"))
+            .map(|text| parse_entry_points_txt(&text))
+            .unwrap_or_default();
+    }
+    fs::read_to_string(root.join(PYPROJECT_FILE))
+        .map(|text| parse_pyproject_scripts(&text))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENTRY_POINTS: &str = include_str!("../tests/fixtures/entry-points/entry_points.txt");
+    const PYPROJECT: &str = include_str!("../tests/fixtures/entry-points/pyproject.toml");
+
+    fn names(entry_points: &[EntryPoint]) -> Vec<&str> {
+        entry_points.iter().map(|ep| ep.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_parse_entry_points_txt() {
+        let entry_points = parse_entry_points_txt(ENTRY_POINTS);
+        assert_eq!(names(&entry_points), ["mycli", "mycli-admin"]);
+        assert_eq!(
+            entry_points[0],
+            EntryPoint {
+                name: "mycli".to_string(),
+                target: "myapp.cli:main".to_string(),
+                source: Source::Installed,
+            }
+        );
+        assert_eq!(entry_points[1].target, "myapp.admin:run [extra]");
+        assert!(parse_entry_points_txt("[gui_scripts]\nmygui = myapp.gui:main\n").is_empty());
+        assert!(parse_entry_points_txt("").is_empty());
+    }
+
+    #[test]
+    fn test_parse_pyproject_scripts() {
+        let entry_points = parse_pyproject_scripts(PYPROJECT);
+        assert_eq!(names(&entry_points), ["mycli", "serve"]);
+        assert_eq!(entry_points[1].target, "myapp.server:serve");
+        assert!(entry_points.iter().all(|ep| ep.source == Source::Pyproject));
+        assert!(parse_pyproject_scripts("[project]\nname = \"x\"\n").is_empty());
+        assert!(parse_pyproject_scripts("[project").is_empty());
+    }
+
+    #[test]
+    fn test_discover_prefers_the_installed_dist_info() {
+        let dir = tempfile::tempdir().unwrap();
+        let venv = dir.path().join("venv");
+        fs::write(dir.path().join(PYPROJECT_FILE), PYPROJECT).unwrap();
+        assert_eq!(distribution_name(dir.path(), "demo"), "my-app");
+
+        // Not installed: pyproject.toml's, which can't run yet
+        let found = discover(dir.path(), &venv, "demo");
+        assert_eq!(names(&found), ["mycli", "serve"]);
+
+        // Installed: the dist-info's, matched by normalized name
+        let dist_info = venv.join("lib/python3.12/site-packages/my_app-0.1.0.dist-info");
+        fs::create_dir_all(&dist_info).unwrap();
+        fs::write(dist_info.join("entry_points.txt"), ENTRY_POINTS).unwrap();
+        assert_eq!(find_dist_info(&venv, "My.App"), Some(dist_info.clone()));
+        let found = discover(dir.path(), &venv, "demo");
+        assert_eq!(names(&found), ["mycli", "mycli-admin"]);
+        assert!(
+            found[0]
+                .executable(&venv)
+                .starts_with(venv.join(VENV_BIN_DIR))
+        );
+
+        // Installed without entry points
+        fs::remove_file(dist_info.join("entry_points.txt")).unwrap();
+        assert!(discover(dir.path(), &venv, "demo").is_empty());
+    }
+}
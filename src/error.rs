@@ -0,0 +1,910 @@
+//! Errors returned by command functions and the exit-code contract.
+//!
+//! | Code | Meaning                                                        |
+//! |------|----------------------------------------------------------------|
+//! | 0    | Success, including an operation the user declined              |
+//! | 1    | Generic failure                                                |
+//! | 2    | project.toml is missing, invalid, or its dependencies conflict |
+//! | 3    | Network failure talking to the package index                   |
+//! | 4    | A subprocess ppm relies on (pip, python, git) failed           |
+//! | 101  | The user's script was killed without an exit status            |
+//! | 130  | Ctrl+C stopped an install before it was done                   |
+//!
+//! When the main script or a project script exits with a non-zero status,
+//! ppm exits with that same status so `ppm start && deploy.sh` behaves like
+//! running the script directly. A command run in every workspace member
+//! with `--all` exits with the status of the first member it failed in,
+//! `ppm check` with the status of its first failed check, and `ppm run`
+//! with a glob with the status of the first script that failed.
+//!
+//! Each variant also has a stable [`ErrorCode`] like `PPM-001`, shown
+//! after the message, included as `code` in JSON output and described at
+//! length by `ppm explain`. Codes are never reused or renumbered.
+
+use crate::conflicts::ResolutionConflict;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Generic failure
+pub const EXIT_GENERIC: i32 = 1;
+/// project.toml missing or invalid
+pub const EXIT_CONFIG: i32 = 2;
+/// Package index unreachable or returned bad data
+pub const EXIT_NETWORK: i32 = 3;
+/// pip, python or git failed
+pub const EXIT_SUBPROCESS: i32 = 4;
+/// A child script was killed without an exit status
+pub const EXIT_CHILD_SIGNALED: i32 = 101;
+/// Ctrl+C stopped an install part-way, see [`crate::interrupt`]
+pub const EXIT_INTERRUPTED: i32 = 130;
+
+/// Every exit status ppm uses itself and what it means, for the man pages'
+/// EXIT STATUS section; a failing script's own status is passed through too
+pub const EXIT_CODES: &[(i32, &str)] = &[
+    (0, "Success, including an operation the user declined"),
+    (EXIT_GENERIC, "Generic failure"),
+    (
+        EXIT_CONFIG,
+        "project.toml is missing, invalid, or its dependencies conflict",
+    ),
+    (EXIT_NETWORK, "Network failure talking to the package index"),
+    (
+        EXIT_SUBPROCESS,
+        "A subprocess ppm relies on (pip, python, git) failed",
+    ),
+    (
+        EXIT_CHILD_SIGNALED,
+        "The user's script was killed without an exit status",
+    ),
+    (
+        EXIT_INTERRUPTED,
+        "Ctrl+C stopped an install before it was done",
+    ),
+];
+
+/// Stable code of a [`PpmError`] category, see [`PpmError::code`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    /// `PPM-001`, [`PpmError::ConfigNotFound`]
+    ConfigNotFound,
+    /// `PPM-002`, [`PpmError::ConfigParse`]
+    ConfigParse,
+    /// `PPM-003`, [`PpmError::ConfigConflict`]
+    ConfigConflict,
+    /// `PPM-004`, [`PpmError::Config`]
+    Config,
+    /// `PPM-005`, [`PpmError::Venv`]
+    Venv,
+    /// `PPM-006`, [`PpmError::Pip`]
+    Pip,
+    /// `PPM-007`, [`PpmError::Network`]
+    Network,
+    /// `PPM-008`, [`PpmError::PackageNotFound`]
+    PackageNotFound,
+    /// `PPM-009`, [`PpmError::Io`]
+    Io,
+    /// `PPM-010`, [`PpmError::Subprocess`]
+    Subprocess,
+    /// `PPM-011`, [`PpmError::ChildExit`]
+    ChildExit,
+    /// `PPM-012`, [`PpmError::MembersFailed`]
+    MembersFailed,
+    /// `PPM-013`, [`PpmError::ChecksFailed`]
+    ChecksFailed,
+    /// `PPM-014`, [`PpmError::DependencyConflict`]
+    DependencyConflict,
+    /// `PPM-015`, [`PpmError::ScriptsFailed`]
+    ScriptsFailed,
+    /// `PPM-016`, [`PpmError::Interrupted`]
+    Interrupted,
+    /// `PPM-017`, [`PpmError::Other`]
+    Other,
+    /// `PPM-018`, [`PpmError::Timeout`]
+    Timeout,
+}
+
+/// What `ppm explain` prints for an [`ErrorCode`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Explanation {
+    /// One line naming the failure
+    pub title: &'static str,
+    /// What happened, in a few sentences
+    pub description: &'static str,
+    /// Usual reasons for it
+    pub causes: &'static [&'static str],
+    /// Commands or edits that usually fix it
+    pub fixes: &'static [&'static str],
+}
+
+impl ErrorCode {
+    /// Every code, in number order
+    pub const ALL: &'static [ErrorCode] = &[
+        ErrorCode::ConfigNotFound,
+        ErrorCode::ConfigParse,
+        ErrorCode::ConfigConflict,
+        ErrorCode::Config,
+        ErrorCode::Venv,
+        ErrorCode::Pip,
+        ErrorCode::Network,
+        ErrorCode::PackageNotFound,
+        ErrorCode::Io,
+        ErrorCode::Subprocess,
+        ErrorCode::ChildExit,
+        ErrorCode::MembersFailed,
+        ErrorCode::ChecksFailed,
+        ErrorCode::DependencyConflict,
+        ErrorCode::ScriptsFailed,
+        ErrorCode::Interrupted,
+        ErrorCode::Other,
+        ErrorCode::Timeout,
+    ];
+
+    /// The code's number, 1 for `PPM-001`
+    pub fn number(self) -> u16 {
+        match self {
+            ErrorCode::ConfigNotFound => 1,
+            ErrorCode::ConfigParse => 2,
+            ErrorCode::ConfigConflict => 3,
+            ErrorCode::Config => 4,
+            ErrorCode::Venv => 5,
+            ErrorCode::Pip => 6,
+            ErrorCode::Network => 7,
+            ErrorCode::PackageNotFound => 8,
+            ErrorCode::Io => 9,
+            ErrorCode::Subprocess => 10,
+            ErrorCode::ChildExit => 11,
+            ErrorCode::MembersFailed => 12,
+            ErrorCode::ChecksFailed => 13,
+            ErrorCode::DependencyConflict => 14,
+            ErrorCode::ScriptsFailed => 15,
+            ErrorCode::Interrupted => 16,
+            ErrorCode::Other => 17,
+            ErrorCode::Timeout => 18,
+        }
+    }
+
+    /// The code written like `PPM-001`, `ppm-1` or `1`; `None` when there
+    /// is no such code
+    pub fn parse(text: &str) -> Option<ErrorCode> {
+        let text = text.trim();
+        let digits = match text.get(..4) {
+            Some(prefix) if prefix.eq_ignore_ascii_case("ppm-") => &text[4..],
+            _ => text,
+        };
+        let number: u16 = digits.parse().ok()?;
+        ErrorCode::ALL
+            .iter()
+            .copied()
+            .find(|code| code.number() == number)
+    }
+
+    /// The longer description `ppm explain` prints
+    pub fn explanation(self) -> Explanation {
+        match self {
+            ErrorCode::ConfigNotFound => Explanation {
+                title: "project.toml not found",
+                description: "The command works on a project, but there is no project.toml in the current directory or any directory above it.",
+                causes: &[
+                    "Running ppm outside the project directory",
+                    "A project that was never initialized with ppm",
+                ],
+                fixes: &[
+                    "cd into the project and run the command again",
+                    "ppm init, to start a project in the current directory",
+                ],
+            },
+            ErrorCode::ConfigParse => Explanation {
+                title: "project.toml doesn't parse",
+                description: "project.toml is not valid TOML, or a key has a value of the wrong type or isn't known. The message gives the line and column.",
+                causes: &[
+                    "A typo or unclosed quote or bracket",
+                    "A misspelled key, e.g. in a [packages] table",
+                    "A key written by a newer ppm",
+                ],
+                fixes: &[
+                    "Fix the line the message points at",
+                    "ppm check-config, to validate the file again",
+                    "ppm self update, when the key comes from a newer ppm",
+                ],
+            },
+            ErrorCode::ConfigConflict => Explanation {
+                title: "project.toml changed on disk during the command",
+                description: "ppm was about to save project.toml, but the file was edited meanwhile and the same keys were changed both ways. Nothing was written.",
+                causes: &[
+                    "Editing project.toml in an editor while ppm ran",
+                    "Two ppm commands changing the project at once",
+                ],
+                fixes: &["Save or discard the edits, then run the command again"],
+            },
+            ErrorCode::Config => Explanation {
+                title: "project.toml can't be used",
+                description: "project.toml parsed, but something in it is invalid: a version, a marker, a path outside the project, a script without a command, or an option that doesn't apply.",
+                causes: &[
+                    "An invalid version or environment marker in [packages]",
+                    "The same package listed twice under different spellings",
+                    "A venv or version-file outside the project",
+                    "ppm-version asking for a newer ppm",
+                ],
+                fixes: &[
+                    "ppm check-config, to list what is wrong",
+                    "ppm check, to run every project check",
+                    "ppm self update, when ppm-version asks for a newer ppm",
+                ],
+            },
+            ErrorCode::Venv => Explanation {
+                title: "Virtual environment problem",
+                description: "The project's virtual environment is missing, broken, or couldn't be created.",
+                causes: &[
+                    "The venv was never created",
+                    "The Python it was made from was removed or upgraded",
+                    "The python in project.toml isn't installed",
+                ],
+                fixes: &[
+                    "ppm install, to create the venv and install the packages",
+                    "Delete the venv directory and run ppm install, to rebuild it",
+                    "ppm env, to see which venv and interpreter ppm uses",
+                ],
+            },
+            ErrorCode::Pip => Explanation {
+                title: "pip failed",
+                description: "pip ran and exited unsuccessfully. Its stderr is part of the message, and in JSON output its exit status too.",
+                causes: &[
+                    "A pinned version that doesn't exist or doesn't support this Python",
+                    "A package that needs a compiler or system library to build",
+                    "The index refusing or failing the download",
+                ],
+                fixes: &[
+                    "Read pip's error in the message",
+                    "ppm update --dry-run, to see which versions are available",
+                    "ppm install again once the cause is fixed",
+                ],
+            },
+            ErrorCode::Network => Explanation {
+                title: "Package index unreachable",
+                description: "ppm couldn't reach the package index, or got an answer it couldn't read, after retrying.",
+                causes: &[
+                    "No network connection, or a proxy blocking the request",
+                    "A wrong index-url in project.toml",
+                    "The index being down or rate limiting",
+                ],
+                fixes: &[
+                    "Check the connection and run the command again",
+                    "Check index-url under [project]",
+                    "--offline, to use cached data where the command allows",
+                ],
+            },
+            ErrorCode::PackageNotFound => Explanation {
+                title: "Package not found",
+                description: "The package index has no package with this name.",
+                causes: &[
+                    "A typo in the package name",
+                    "The import name used instead of the distribution name",
+                    "A package only on a private index",
+                ],
+                fixes: &[
+                    "Use one of the names ppm suggests",
+                    "Set index-url under [project] for a private index",
+                ],
+            },
+            ErrorCode::Io => Explanation {
+                title: "Filesystem error",
+                description: "Reading or writing a file or directory failed.",
+                causes: &[
+                    "Missing permissions on the project or cache directory",
+                    "A full disk",
+                    "A file locked by another program",
+                ],
+                fixes: &[
+                    "Check the permissions and free space of the path in the message",
+                    "ppm cache paths, to see every location ppm writes to",
+                ],
+            },
+            ErrorCode::Subprocess => Explanation {
+                title: "A program couldn't be run",
+                description: "python, git, a shell or another program ppm needed couldn't be started, or failed in a way ppm can't recover from.",
+                causes: &[
+                    "The program isn't installed or isn't on PATH",
+                    "A broken venv",
+                ],
+                fixes: &[
+                    "Install the program the message names",
+                    "ppm check, to check the venv and the tools around it",
+                ],
+            },
+            ErrorCode::ChildExit => Explanation {
+                title: "Script failed",
+                description: "The main script or a project script exited unsuccessfully. ppm exits with the same status, or 101 when the script was killed by a signal.",
+                causes: &["The script's own error, shown in its output above"],
+                fixes: &["Fix the script's error", "ppm start or ppm run again"],
+            },
+            ErrorCode::MembersFailed => Explanation {
+                title: "Failed in some workspace members",
+                description: "A command run with --all failed in the members the message lists. ppm exits with the status of the first failure.",
+                causes: &["Each member's own error, shown above"],
+                fixes: &["ppm --project <member> <command>, to retry one member"],
+            },
+            ErrorCode::ChecksFailed => Explanation {
+                title: "Project checks failed",
+                description: "Some of ppm check's checks failed. ppm exits with the status of the first that did.",
+                causes: &["Each check's own problem, shown in the table above"],
+                fixes: &[
+                    "Fix what each failed check reports",
+                    "ppm check --skip <check>, to leave one out",
+                ],
+            },
+            ErrorCode::DependencyConflict => Explanation {
+                title: "Dependency resolution conflict",
+                description: "pip found no set of versions satisfying every package's requirements. The message lists the contested packages and what asks for each.",
+                causes: &[
+                    "Two pins needing incompatible versions of a shared dependency",
+                    "A constraints file holding a package back",
+                    "A pin that is too old for the others",
+                ],
+                fixes: &[
+                    "Relax or update one of the conflicting pins",
+                    "ppm update <package>, to move a pin to a compatible release",
+                    "ppm rdeps <package>, to see what needs the contested package",
+                ],
+            },
+            ErrorCode::ScriptsFailed => Explanation {
+                title: "Some scripts failed",
+                description: "Some of the scripts a ppm run glob matched failed. ppm exits with the status of the first that did.",
+                causes: &["Each script's own error, shown above"],
+                fixes: &["ppm run <script>, to rerun one script"],
+            },
+            ErrorCode::Interrupted => Explanation {
+                title: "Interrupted",
+                description: "Ctrl+C stopped an install or update before it was done. Running pip processes were allowed to finish.",
+                causes: &["Ctrl+C, or a SIGINT from a supervisor"],
+                fixes: &[
+                    "ppm update --resume, to finish an interrupted update",
+                    "ppm install, to finish an interrupted install",
+                ],
+            },
+            ErrorCode::Other => Explanation {
+                title: "Other failure",
+                description: "A failure without a more specific category; the message says what went wrong.",
+                causes: &["Usually a wrong argument or a missing script or package"],
+                fixes: &["ppm <command> --help, to check the arguments"],
+            },
+            ErrorCode::Timeout => Explanation {
+                title: "Network request timed out",
+                description: "A request to the package index got no answer in time, or a phase of many lookups, like resolving every package for ppm update, ran past its overall budget of four request timeouts. The message lists which packages were looked up and which were still waiting.",
+                causes: &[
+                    "A proxy or firewall that accepts connections but never answers",
+                    "A slow mirror or a very slow connection",
+                    "A timeout set too low in pip's config or with --timeout",
+                ],
+                fixes: &[
+                    "ppm --timeout 120 <command>, to allow more time",
+                    "ppm doctor, to see the index and timeout in use and where they come from",
+                    "--offline, to use cached data where the command allows",
+                ],
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "PPM-{:03}", self.number())
+    }
+}
+
+/// Every failure a ppm command can report
+#[derive(Debug, Error)]
+pub enum PpmError {
+    /// The config file does not exist
+    #[error("Could not find {0}")]
+    ConfigNotFound(String),
+    /// The config file is not valid TOML or does not match the schema
+    #[error("Failed to parse {path}: {source}")]
+    ConfigParse {
+        /// File that failed to parse
+        path: String,
+        /// Underlying TOML error, with line and column
+        #[source]
+        source: toml::de::Error,
+    },
+    /// The config file was edited on disk in a way that conflicts with
+    /// the changes being saved
+    #[error("{path} was changed on disk while ppm was running; conflicting keys: {}", .keys.join(", "))]
+    ConfigConflict {
+        /// File that was being saved
+        path: String,
+        /// Dotted keys changed differently on disk and by ppm
+        keys: Vec<String>,
+    },
+    /// project.toml parsed but its contents can't be used, e.g. a bad version
+    #[error("{0}")]
+    Config(String),
+    /// Virtual environment missing or could not be created
+    #[error("{0}")]
+    Venv(String),
+    /// pip ran but exited unsuccessfully
+    #[error("pip {}: {}", describe_status(.status), .stderr.trim())]
+    Pip {
+        /// pip's stderr
+        stderr: String,
+        /// pip's exit status, `None` when killed by a signal
+        status: Option<i32>,
+    },
+    /// pip found no versions satisfying every dependency's constraints
+    #[error("{0}")]
+    DependencyConflict(ResolutionConflict),
+    /// Package index could not be reached or returned bad data
+    #[error("{0}")]
+    Network(String),
+    /// The package index has no package with this name
+    #[error("Package '{0}' was not found on PyPI")]
+    PackageNotFound(String),
+    /// Filesystem error
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// python, git or a shell could not be spawned
+    #[error("{0}")]
+    Subprocess(String),
+    /// The user's script or main script exited unsuccessfully
+    #[error("'{name}' {}", describe_status(.code))]
+    ChildExit {
+        /// Script name or path
+        name: String,
+        /// Exit status, `None` when killed by a signal
+        code: Option<i32>,
+    },
+    /// A command run with `--all` failed in some workspace members
+    #[error("Failed in {} of {total} workspace members: {}", .failed.len(), .failed.join(", "))]
+    MembersFailed {
+        /// Members it failed in
+        failed: Vec<String>,
+        /// Members it ran in
+        total: usize,
+        /// Exit status of the first failure
+        code: i32,
+    },
+    /// Some of `ppm check`'s checks failed
+    #[error("{} of {total} checks failed: {}", .failed.len(), .failed.join(", "))]
+    ChecksFailed {
+        /// Checks that failed
+        failed: Vec<String>,
+        /// Checks run or skipped
+        total: usize,
+        /// Exit status of the first failure
+        code: i32,
+    },
+    /// Some of the scripts a `ppm run` glob matched failed
+    #[error("{} of {total} scripts failed: {}", .failed.len(), .failed.join(", "))]
+    ScriptsFailed {
+        /// Scripts that failed
+        failed: Vec<String>,
+        /// Scripts run
+        total: usize,
+        /// Exit status of the first failure
+        code: i32,
+    },
+    /// Ctrl+C stopped an install before every package was installed
+    #[error("{0}")]
+    Interrupted(String),
+    /// Anything without a more specific category
+    #[error("{0}")]
+    Other(String),
+    /// A request to the package index, or a phase of many, ran out of time
+    #[error("{}", describe_timeout(.what, .limit, .resolved, .timed_out))]
+    Timeout {
+        /// What ran out of time, like `Looking up 3 packages`
+        what: String,
+        /// The time allowed, when known
+        limit: Option<Duration>,
+        /// Packages looked up before the limit, for a phase
+        resolved: Vec<String>,
+        /// Packages still waiting for the index
+        timed_out: Vec<String>,
+    },
+}
+
+fn describe_timeout(
+    what: &str,
+    limit: &Option<Duration>,
+    resolved: &[String],
+    timed_out: &[String],
+) -> String {
+    let mut out = format!("{} timed out", what);
+    if let Some(limit) = limit {
+        out.push_str(&format!(" after {:?}", limit));
+    }
+    // A single lookup's message already names its package
+    if !resolved.is_empty() || timed_out.len() > 1 {
+        let list = |names: &[String]| match names.is_empty() {
+            true => "none".to_string(),
+            false => names.join(", "),
+        };
+        out.push_str(&format!(
+            "; looked up: {}; still waiting: {}",
+            list(resolved),
+            list(timed_out)
+        ));
+    }
+    out
+}
+
+fn describe_status(status: &Option<i32>) -> String {
+    match status {
+        Some(code) => format!("exited with status {}", code),
+        None => "was terminated by a signal".to_string(),
+    }
+}
+
+impl PpmError {
+    /// A failed pip run, as [`PpmError::DependencyConflict`] when its
+    /// stderr reports a `ResolutionImpossible` error
+    pub fn from_pip(stderr: String, status: Option<i32>) -> PpmError {
+        match crate::conflicts::parse_resolution_error(&stderr) {
+            Some(conflict) => PpmError::DependencyConflict(conflict),
+            None => PpmError::Pip { stderr, status },
+        }
+    }
+
+    /// Process exit status for this error, see the module docs
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            PpmError::ConfigNotFound(_)
+            | PpmError::ConfigParse { .. }
+            | PpmError::ConfigConflict { .. }
+            | PpmError::Config(_)
+            | PpmError::DependencyConflict(_) => EXIT_CONFIG,
+            PpmError::Network(_) | PpmError::Timeout { .. } => EXIT_NETWORK,
+            PpmError::Venv(_) | PpmError::Pip { .. } | PpmError::Subprocess(_) => EXIT_SUBPROCESS,
+            PpmError::ChildExit { code, .. } => match code {
+                Some(code) if *code != 0 => *code,
+                _ => EXIT_CHILD_SIGNALED,
+            },
+            PpmError::MembersFailed { code, .. }
+            | PpmError::ChecksFailed { code, .. }
+            | PpmError::ScriptsFailed { code, .. } => *code,
+            PpmError::Interrupted(_) => EXIT_INTERRUPTED,
+            PpmError::PackageNotFound(_) | PpmError::Io(_) | PpmError::Other(_) => EXIT_GENERIC,
+        }
+    }
+
+    /// Stable code of the error's category, shown after the message
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            PpmError::ConfigNotFound(_) => ErrorCode::ConfigNotFound,
+            PpmError::ConfigParse { .. } => ErrorCode::ConfigParse,
+            PpmError::ConfigConflict { .. } => ErrorCode::ConfigConflict,
+            PpmError::Config(_) => ErrorCode::Config,
+            PpmError::Venv(_) => ErrorCode::Venv,
+            PpmError::Pip { .. } => ErrorCode::Pip,
+            PpmError::DependencyConflict(_) => ErrorCode::DependencyConflict,
+            PpmError::Network(_) => ErrorCode::Network,
+            PpmError::PackageNotFound(_) => ErrorCode::PackageNotFound,
+            PpmError::Io(_) => ErrorCode::Io,
+            PpmError::Subprocess(_) => ErrorCode::Subprocess,
+            PpmError::ChildExit { .. } => ErrorCode::ChildExit,
+            PpmError::MembersFailed { .. } => ErrorCode::MembersFailed,
+            PpmError::ChecksFailed { .. } => ErrorCode::ChecksFailed,
+            PpmError::ScriptsFailed { .. } => ErrorCode::ScriptsFailed,
+            PpmError::Interrupted(_) => ErrorCode::Interrupted,
+            PpmError::Other(_) => ErrorCode::Other,
+            PpmError::Timeout { .. } => ErrorCode::Timeout,
+        }
+    }
+
+    /// The message followed by its code, `Could not find project.toml
+    /// [PPM-001]`, as the CLI prints it
+    pub fn with_code(&self) -> String {
+        format!("{} [{}]", self, self.code())
+    }
+
+    /// Stable identifier used as `error.kind` in JSON output
+    pub fn kind(&self) -> &'static str {
+        match self {
+            PpmError::ConfigNotFound(_) => "config_not_found",
+            PpmError::ConfigParse { .. } => "config_parse",
+            PpmError::ConfigConflict { .. } => "config_conflict",
+            PpmError::Config(_) => "config",
+            PpmError::Venv(_) => "venv",
+            PpmError::Pip { .. } => "pip",
+            PpmError::DependencyConflict(_) => "dependency_conflict",
+            PpmError::Network(_) => "network",
+            PpmError::PackageNotFound(_) => "package_not_found",
+            PpmError::Io(_) => "io",
+            PpmError::Subprocess(_) => "subprocess",
+            PpmError::ChildExit { .. } => "child_exit",
+            PpmError::MembersFailed { .. } => "members_failed",
+            PpmError::ChecksFailed { .. } => "checks_failed",
+            PpmError::ScriptsFailed { .. } => "scripts_failed",
+            PpmError::Interrupted(_) => "interrupted",
+            PpmError::Other(_) => "other",
+            PpmError::Timeout { .. } => "timeout",
+        }
+    }
+
+    /// `{"error": {"kind", "code", "message", "exit_code", ...}}` printed
+    /// on stdout in `--json` mode, with variant-specific fields alongside
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut error = serde_json::json!({
+            "kind": self.kind(),
+            "code": self.code().to_string(),
+            "message": self.to_string(),
+            "exit_code": self.exit_code(),
+        });
+        match self {
+            PpmError::ConfigNotFound(path) | PpmError::ConfigParse { path, .. } => {
+                error["path"] = path.as_str().into();
+            }
+            PpmError::ConfigConflict { path, keys } => {
+                error["path"] = path.as_str().into();
+                error["keys"] = keys.clone().into();
+            }
+            PpmError::Pip { stderr, status } => {
+                error["stderr"] = stderr.as_str().into();
+                error["status"] = (*status).into();
+            }
+            PpmError::DependencyConflict(conflict) => {
+                error["requested"] = conflict.requested.clone().into();
+                error["conflicts"] = conflict
+                    .contested()
+                    .into_iter()
+                    .map(|(package, causes)| {
+                        serde_json::json!({
+                            "package": package,
+                            "causes": causes.iter().map(|c| c.to_string()).collect::<Vec<_>>(),
+                        })
+                    })
+                    .collect::<Vec<_>>()
+                    .into();
+            }
+            PpmError::PackageNotFound(name) => error["package"] = name.as_str().into(),
+            PpmError::ChildExit { name, code } => {
+                error["script"] = name.as_str().into();
+                error["status"] = (*code).into();
+            }
+            PpmError::MembersFailed { failed, .. } => {
+                error["members"] = failed.clone().into();
+            }
+            PpmError::ChecksFailed { failed, .. } => {
+                error["checks"] = failed.clone().into();
+            }
+            PpmError::ScriptsFailed { failed, .. } => {
+                error["scripts"] = failed.clone().into();
+            }
+            PpmError::Timeout {
+                limit,
+                resolved,
+                timed_out,
+                ..
+            } => {
+                error["limit_secs"] = limit.map(|limit| limit.as_secs_f64()).into();
+                error["resolved"] = resolved.clone().into();
+                error["timed_out"] = timed_out.clone().into();
+            }
+            _ => {}
+        }
+        serde_json::json!({ "error": error })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_codes() {
+        assert_eq!(PpmError::Other("x".to_string()).exit_code(), 1);
+        assert_eq!(PpmError::PackageNotFound("x".to_string()).exit_code(), 1);
+        assert_eq!(PpmError::ConfigNotFound("x".to_string()).exit_code(), 2);
+        assert_eq!(PpmError::Network("x".to_string()).exit_code(), 3);
+        assert_eq!(PpmError::Venv("x".to_string()).exit_code(), 4);
+        assert_eq!(PpmError::Interrupted("x".to_string()).exit_code(), 130);
+        let pip = PpmError::Pip {
+            stderr: "boom".to_string(),
+            status: Some(1),
+        };
+        assert_eq!(pip.exit_code(), 4);
+        let members = PpmError::MembersFailed {
+            failed: vec!["services/api".to_string()],
+            total: 2,
+            code: 7,
+        };
+        assert_eq!(members.exit_code(), 7);
+        assert_eq!(
+            members.to_string(),
+            "Failed in 1 of 2 workspace members: services/api"
+        );
+    }
+
+    #[test]
+    fn test_timeouts_are_network_failures() {
+        let request = PpmError::Timeout {
+            what: "Looking up 'requests' on the package index".to_string(),
+            limit: None,
+            resolved: vec![],
+            timed_out: vec!["requests".to_string()],
+        };
+        assert_eq!(request.exit_code(), EXIT_NETWORK);
+        assert_eq!(
+            request.to_string(),
+            "Looking up 'requests' on the package index timed out"
+        );
+
+        let phase = PpmError::Timeout {
+            what: "Looking up 3 packages".to_string(),
+            limit: Some(Duration::from_secs(120)),
+            resolved: vec!["numpy".to_string()],
+            timed_out: vec!["requests".to_string(), "six".to_string()],
+        };
+        assert_eq!(phase.exit_code(), EXIT_NETWORK);
+        assert_eq!(phase.code(), ErrorCode::Timeout);
+        assert_eq!(
+            phase.with_code(),
+            "Looking up 3 packages timed out after 120s; looked up: numpy; still waiting: requests, six [PPM-018]"
+        );
+        let json = phase.to_json();
+        assert_eq!(json["error"]["kind"], "timeout");
+        assert_eq!(json["error"]["limit_secs"], 120.0);
+        assert_eq!(json["error"]["timed_out"][1], "six");
+    }
+
+    #[test]
+    fn test_child_exit_propagates_status() {
+        let err = PpmError::ChildExit {
+            name: "main.py".to_string(),
+            code: Some(7),
+        };
+        assert_eq!(err.exit_code(), 7);
+        assert_eq!(err.to_string(), "'main.py' exited with status 7");
+
+        let killed = PpmError::ChildExit {
+            name: "main.py".to_string(),
+            code: None,
+        };
+        assert_eq!(killed.exit_code(), 101);
+        assert_eq!(killed.to_string(), "'main.py' was terminated by a signal");
+    }
+
+    #[test]
+    fn test_pip_display_trims_stderr() {
+        let err = PpmError::Pip {
+            stderr: "ERROR: No matching distribution\n".to_string(),
+            status: Some(1),
+        };
+        assert_eq!(
+            err.to_string(),
+            "pip exited with status 1: ERROR: No matching distribution"
+        );
+    }
+
+    #[test]
+    fn test_from_pip_recognizes_conflicts() {
+        let stderr = "ERROR: Cannot install a==1.0 and b==1.0 because these package versions have conflicting dependencies.\n\
+                      \nThe conflict is caused by:\n    The user requested c==2.0\n    b 1.0 depends on c<2\n";
+        let err = PpmError::from_pip(stderr.to_string(), Some(1));
+        assert_eq!(err.kind(), "dependency_conflict");
+        assert_eq!(err.exit_code(), 2);
+        assert_eq!(err.to_json()["error"]["conflicts"][0]["package"], "c");
+
+        let err = PpmError::from_pip("ERROR: boom\n".to_string(), Some(1));
+        assert_eq!(err.kind(), "pip");
+    }
+
+    #[test]
+    fn test_to_json_includes_variant_fields() {
+        let json = PpmError::PackageNotFound("nope".to_string()).to_json();
+        assert_eq!(json["error"]["kind"], "package_not_found");
+        assert_eq!(json["error"]["package"], "nope");
+        assert_eq!(json["error"]["exit_code"], 1);
+
+        let parse_err = toml::from_str::<toml::Table>("[project").unwrap_err();
+        let json = PpmError::ConfigParse {
+            path: "project.toml".to_string(),
+            source: parse_err,
+        }
+        .to_json();
+        assert_eq!(json["error"]["kind"], "config_parse");
+        assert_eq!(json["error"]["path"], "project.toml");
+        assert!(
+            json["error"]["message"]
+                .as_str()
+                .unwrap()
+                .starts_with("Failed to parse project.toml")
+        );
+    }
+
+    /// One error of every variant
+    fn every_variant() -> Vec<PpmError> {
+        let text = || "x".to_string();
+        vec![
+            PpmError::ConfigNotFound(text()),
+            PpmError::ConfigParse {
+                path: text(),
+                source: toml::from_str::<toml::Table>("[x").unwrap_err(),
+            },
+            PpmError::ConfigConflict {
+                path: text(),
+                keys: vec![],
+            },
+            PpmError::Config(text()),
+            PpmError::Venv(text()),
+            PpmError::Pip {
+                stderr: text(),
+                status: None,
+            },
+            PpmError::from_pip(
+                "ERROR: Cannot install a because these package versions have conflicting dependencies.\n\
+                 \nThe conflict is caused by:\n    The user requested c==2.0\n"
+                    .to_string(),
+                Some(1),
+            ),
+            PpmError::Network(text()),
+            PpmError::PackageNotFound(text()),
+            PpmError::Io(std::io::Error::other("x")),
+            PpmError::Subprocess(text()),
+            PpmError::ChildExit {
+                name: text(),
+                code: None,
+            },
+            PpmError::MembersFailed {
+                failed: vec![],
+                total: 0,
+                code: 1,
+            },
+            PpmError::ChecksFailed {
+                failed: vec![],
+                total: 0,
+                code: 1,
+            },
+            PpmError::ScriptsFailed {
+                failed: vec![],
+                total: 0,
+                code: 1,
+            },
+            PpmError::Interrupted(text()),
+            PpmError::Other(text()),
+            PpmError::Timeout {
+                what: text(),
+                limit: None,
+                resolved: vec![],
+                timed_out: vec![],
+            },
+        ]
+    }
+
+    #[test]
+    fn test_every_variant_has_an_explained_code() {
+        let codes: Vec<ErrorCode> = every_variant().iter().map(PpmError::code).collect();
+        assert_eq!(codes.len(), ErrorCode::ALL.len());
+        for code in ErrorCode::ALL {
+            assert!(codes.contains(code), "no variant has {}", code);
+            let explanation = code.explanation();
+            assert!(!explanation.title.is_empty());
+            assert!(!explanation.description.is_empty());
+            assert!(!explanation.causes.is_empty());
+            assert!(!explanation.fixes.is_empty());
+        }
+        let numbers: Vec<u16> = ErrorCode::ALL.iter().map(|code| code.number()).collect();
+        assert_eq!(
+            numbers,
+            (1..=ErrorCode::ALL.len() as u16).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_codes_display_and_parse() {
+        assert_eq!(ErrorCode::ConfigNotFound.to_string(), "PPM-001");
+        assert_eq!(ErrorCode::DependencyConflict.to_string(), "PPM-014");
+        assert_eq!(
+            ErrorCode::parse("PPM-014"),
+            Some(ErrorCode::DependencyConflict)
+        );
+        assert_eq!(ErrorCode::parse("ppm-1"), Some(ErrorCode::ConfigNotFound));
+        assert_eq!(ErrorCode::parse(" 004 "), Some(ErrorCode::Config));
+        assert_eq!(ErrorCode::parse("PPM-999"), None);
+        assert_eq!(ErrorCode::parse("PPM-"), None);
+        assert_eq!(ErrorCode::parse("pip"), None);
+
+        let err = PpmError::ConfigNotFound("project.toml".to_string());
+        assert_eq!(err.with_code(), "Could not find project.toml [PPM-001]");
+        assert_eq!(err.to_json()["error"]["code"], "PPM-001");
+    }
+}
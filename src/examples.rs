@@ -0,0 +1,592 @@
+//! Example command lines for common tasks.
+//!
+//! [`EXAMPLES`] is the one list they come from: [`attach`] puts each
+//! command's examples after its `--help`, `ppm examples` prints them as a
+//! gallery, and the completion scripts offer their topics after
+//! `ppmm examples`.
+
+use crate::utils::json_output;
+use clap::{Args, Command};
+use colored::Colorize;
+use ppmm::PpmError;
+
+/// A command line and the task it does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Example {
+    /// The subcommand it runs, with its parent for nested ones, like
+    /// `cache info`
+    pub command: &'static str,
+    /// What it does, in one line
+    pub task: &'static str,
+    /// The command line, as typed in a shell
+    pub line: &'static str,
+}
+
+const fn example(command: &'static str, task: &'static str, line: &'static str) -> Example {
+    Example {
+        command,
+        task,
+        line,
+    }
+}
+
+/// Every example, in the order the gallery shows them
+pub const EXAMPLES: &[Example] = &[
+    example(
+        "new",
+        "Create a project with a git repository and a venv",
+        "ppmm new myapp --git",
+    ),
+    example(
+        "new",
+        "Create a bare project without a venv",
+        "ppmm new scratch --bare --no-venv",
+    ),
+    example(
+        "init",
+        "Set up a project in the current directory without prompts",
+        "ppmm init --yes",
+    ),
+    example(
+        "init",
+        "Choose the Python the venv is created with",
+        "ppmm init --python 3.12 --yes",
+    ),
+    example(
+        "init",
+        "Turn a checkout with a requirements.txt into a project and install it",
+        "ppmm init --yes --from-requirements",
+    ),
+    example(
+        "add",
+        "Add a package at a specific version",
+        "ppmm add requests==2.31.0",
+    ),
+    example(
+        "add",
+        "Add several packages, one with extras",
+        "ppmm add flask \"uvicorn[standard]>=0.30\"",
+    ),
+    example(
+        "add",
+        "Choose a version from the recent releases",
+        "ppmm add django --version-pick",
+    ),
+    example(
+        "rm",
+        "Remove a package and the dependencies nothing else needs",
+        "ppmm rm flask --cascade",
+    ),
+    example(
+        "install",
+        "Install everything project.toml lists",
+        "ppmm install",
+    ),
+    example(
+        "install",
+        "Install a requirements file and record its packages",
+        "ppmm install -r requirements.txt --record",
+    ),
+    example(
+        "install",
+        "Install with four pip processes at once",
+        "ppmm install --jobs 4",
+    ),
+    example(
+        "install",
+        "Install in every member of a workspace",
+        "ppmm --all install",
+    ),
+    example(
+        "start",
+        "Start the main script with arguments",
+        "ppmm start -- --port 8000",
+    ),
+    example(
+        "start",
+        "Wait for a debugger before the script starts",
+        "ppmm start --debug",
+    ),
+    example(
+        "start",
+        "Profile a run and show the ten slowest functions",
+        "ppmm start --profile --profile-top 10",
+    ),
+    example(
+        "start",
+        "Start with the system Python instead of the venv",
+        "ppmm start --system",
+    ),
+    example("run", "Run a script from [scripts]", "ppmm run lint"),
+    example(
+        "run",
+        "Run every script matching a glob",
+        "ppmm run \"test:*\"",
+    ),
+    example(
+        "test",
+        "Run the tests under coverage",
+        "ppmm test --coverage",
+    ),
+    example(
+        "test",
+        "Pass arguments to pytest",
+        "ppmm test -- -k login -x",
+    ),
+    example("fmt", "Format the sources", "ppmm fmt"),
+    example(
+        "lint",
+        "Lint, letting the linter fix what it can",
+        "ppmm lint -- --fix",
+    ),
+    example("update", "Update only one package", "ppmm update requests"),
+    example(
+        "update",
+        "Preview the updates without installing anything",
+        "ppmm update --dry-run",
+    ),
+    example(
+        "update",
+        "Move past the update policies to the newest releases",
+        "ppmm update --latest --pin-style compatible",
+    ),
+    example(
+        "update",
+        "Use a private index",
+        "PIP_INDEX_URL=https://pypi.example.com/simple ppmm update",
+    ),
+    example(
+        "update",
+        "Finish an update stopped with Ctrl+C",
+        "ppmm update --resume",
+    ),
+    example(
+        "list",
+        "See which packages have newer releases",
+        "ppmm list --outdated",
+    ),
+    example(
+        "list",
+        "Find pins older than six months",
+        "ppmm list --outdated --older-than 6m",
+    ),
+    example(
+        "list",
+        "Describe the outdated packages in a pull request",
+        "ppmm list --format markdown",
+    ),
+    example(
+        "list",
+        "Find packages installed by hand that nothing needs",
+        "ppmm list --installed --unmanaged-only",
+    ),
+    example("gen", "Write requirements.txt from the venv", "ppmm gen"),
+    example(
+        "gen",
+        "Write constraints.txt from ppmm.lock",
+        "ppmm gen --constraints",
+    ),
+    example(
+        "gen",
+        "Write every locked package, with what pulls in each dependency",
+        "ppmm gen --from-lock --annotate",
+    ),
+    example(
+        "lock",
+        "Pin the whole dependency tree in ppmm.lock",
+        "ppmm lock",
+    ),
+    example(
+        "sync",
+        "Install what ppmm.lock pins and the venv lacks",
+        "ppmm sync",
+    ),
+    example(
+        "sync",
+        "Fail in CI when the venv differs from the lock",
+        "ppmm sync --check",
+    ),
+    example(
+        "bundle create",
+        "Pack the project for a machine without network",
+        "ppmm bundle create demo.tar.gz",
+    ),
+    example(
+        "bundle install",
+        "Install a bundle without an index",
+        "ppmm bundle install demo.tar.gz --dir demo",
+    ),
+    example(
+        "build",
+        "Build only a wheel, into another directory",
+        "ppmm build --wheel-only --out-dir out",
+    ),
+    example(
+        "publish",
+        "Build and upload, skipping files already on the index",
+        "ppmm publish --build --skip-existing",
+    ),
+    example(
+        "publish",
+        "Check the distributions without uploading",
+        "ppmm publish --dry-run",
+    ),
+    example(
+        "bump",
+        "Bump the minor version, then commit and tag it",
+        "ppmm bump minor --tag",
+    ),
+    example("bump", "Preview a patch bump", "ppmm bump patch --dry-run"),
+    example(
+        "info",
+        "Show the project, all its packages and scripts",
+        "ppmm info --full",
+    ),
+    example("info", "Show a package's releases", "ppmm info requests"),
+    example(
+        "env",
+        "Activate the venv in the current shell",
+        "eval \"$(ppmm env --activate bash)\"",
+    ),
+    example(
+        "venv recreate",
+        "Rebuild the venv after upgrading Python",
+        "ppmm venv recreate",
+    ),
+    example("ide vscode", "Point VS Code at the venv", "ppmm ide vscode"),
+    example(
+        "ide pycharm",
+        "Show how to set the venv interpreter in PyCharm",
+        "ppmm ide pycharm",
+    ),
+    example(
+        "check",
+        "Run the checks a git hook runs",
+        "ppmm check --skip venv",
+    ),
+    example(
+        "check",
+        "Also resolve the packages and fail on yanked releases",
+        "ppmm check --online --deny-yanked",
+    ),
+    example(
+        "check imports",
+        "Find imports missing from project.toml",
+        "ppmm check imports --strict",
+    ),
+    example("check-config", "Validate project.toml", "ppmm check-config"),
+    example(
+        "hooks install",
+        "Install a pre-commit hook running `ppm check`",
+        "ppmm hooks install",
+    ),
+    example(
+        "hooks uninstall",
+        "Remove the git hooks ppm installed",
+        "ppmm hooks uninstall",
+    ),
+    example(
+        "verify",
+        "Check installed files against the digests on PyPI",
+        "ppmm verify",
+    ),
+    example(
+        "stats",
+        "See which packages take the most disk space",
+        "ppmm stats",
+    ),
+    example(
+        "graph",
+        "Draw the dependency graph as Mermaid",
+        "ppmm graph --format mermaid -o deps.md",
+    ),
+    example(
+        "graph",
+        "Show one package and what it depends on",
+        "ppmm graph --package flask",
+    ),
+    example(
+        "rdeps",
+        "Find out why a package is installed",
+        "ppmm rdeps urllib3",
+    ),
+    example(
+        "history",
+        "See which commands changed a package",
+        "ppmm history --package requests",
+    ),
+    example(
+        "cache info",
+        "Show where ppm's caches are and how big",
+        "ppmm cache info",
+    ),
+    example(
+        "cache paths",
+        "List every file ppm reads or writes",
+        "ppmm cache paths",
+    ),
+    example(
+        "cache prune",
+        "Shrink the PyPI metadata cache and drop entries unused for a month",
+        "ppmm cache prune --max-size 10MB --older-than 30d",
+    ),
+    example(
+        "cache pip-info",
+        "See how much disk pip's download and wheel cache takes",
+        "ppmm cache pip-info",
+    ),
+    example(
+        "cache pip-purge",
+        "Empty pip's cache without being asked to confirm",
+        "ppmm cache pip-purge --yes",
+    ),
+    example(
+        "config get",
+        "Show the theme every project uses",
+        "ppmm config --global get theme",
+    ),
+    example(
+        "config set",
+        "Switch to the high-contrast theme",
+        "ppmm config --global set theme high-contrast",
+    ),
+    example("clean", "Remove the project's cached data", "ppmm clean"),
+    example("explain", "Explain an error code", "ppmm explain PPM-014"),
+    example(
+        "examples",
+        "Show the examples about a topic",
+        "ppmm examples index",
+    ),
+    example(
+        "report",
+        "Write a redacted report for a bug",
+        "ppmm report -o report.txt",
+    ),
+    example(
+        "doctor",
+        "See which index and timeout ppm uses, and from where",
+        "ppmm doctor",
+    ),
+    example(
+        "completions",
+        "Install bash completions",
+        "ppmm completions bash > ~/.local/share/bash-completion/completions/ppmm",
+    ),
+    example(
+        "self check-update",
+        "Check for a newer ppmm",
+        "ppmm self check-update",
+    ),
+    example(
+        "self update",
+        "Install a specific ppmm release",
+        "ppmm self update --version v1.2.0",
+    ),
+];
+
+/// The examples of `command` and of its subcommands, so `ppm cache`
+/// shows those of `ppm cache info`
+pub fn for_command(command: &str) -> impl Iterator<Item = &'static Example> + '_ {
+    EXAMPLES.iter().filter(move |example| {
+        example
+            .command
+            .strip_prefix(command)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(' '))
+    })
+}
+
+/// Examples whose command, task or line contains `keyword`, ignoring
+/// case; every example without one
+pub fn matching(keyword: Option<&str>) -> Vec<&'static Example> {
+    let keyword = keyword.map(|keyword| keyword.trim().to_lowercase());
+    EXAMPLES
+        .iter()
+        .filter(|example| match &keyword {
+            Some(keyword) => [example.command, example.task, example.line]
+                .iter()
+                .any(|text| text.to_lowercase().contains(keyword.as_str())),
+            None => true,
+        })
+        .collect()
+}
+
+/// Topics `ppmm examples` completes: the top-level commands with
+/// examples, each with the task of its first one
+pub fn topics() -> Vec<(&'static str, &'static str)> {
+    let mut topics: Vec<(&'static str, &'static str)> = vec![];
+    for example in EXAMPLES {
+        let topic = example.command.split(' ').next().unwrap_or_default();
+        if !topics.iter().any(|(name, _)| *name == topic) {
+            topics.push((topic, example.task));
+        }
+    }
+    topics
+}
+
+/// The `EXAMPLES:` section for after the help of `command`, `None` when
+/// it has no examples
+pub fn help_block(command: &str) -> Option<String> {
+    let lines: Vec<String> = for_command(command)
+        .map(|example| format!("    # {}\n    {}", example.task, example.line))
+        .collect();
+    if lines.is_empty() {
+        return None;
+    }
+    Some(format!("EXAMPLES:\n{}", lines.join("\n\n")))
+}
+
+/// `cmd` with the examples of each of its subcommands, nested ones too,
+/// after their help; `path` is `cmd`'s own command, empty for the root
+pub fn attach(mut cmd: Command<'static>, path: &str) -> Command<'static> {
+    for sub in cmd.get_subcommands_mut() {
+        let command = if path.is_empty() {
+            sub.get_name().to_string()
+        } else {
+            format!("{} {}", path, sub.get_name())
+        };
+        let mut attached = attach(std::mem::take(sub), &command);
+        if let Some(block) = help_block(&command) {
+            let text = match attached.get_after_help() {
+                Some(help) => format!("{}\n\n{}", help, block),
+                None => block,
+            };
+            // clap 3 borrows help text for the command's lifetime
+            attached = attached.after_help(&*Box::leak(text.into_boxed_str()));
+        }
+        *sub = attached;
+    }
+    cmd
+}
+
+#[derive(Args, Debug)]
+pub struct ShowExamples {
+    /// Only show examples mentioning this, like `index` or `update`
+    pub keyword: Option<String>,
+}
+
+impl ShowExamples {
+    pub fn show(&self) -> Result<(), PpmError> {
+        let examples = matching(self.keyword.as_deref());
+        if examples.is_empty() {
+            return Err(PpmError::Other(format!(
+                "No example mentions '{}'; `ppm examples` lists them all",
+                self.keyword.as_deref().unwrap_or_default()
+            )));
+        }
+        if json_output() {
+            let examples: Vec<serde_json::Value> = examples
+                .iter()
+                .map(|example| {
+                    serde_json::json!({
+                        "command": example.command,
+                        "task": example.task,
+                        "line": example.line,
+                    })
+                })
+                .collect();
+            println!("{}", serde_json::json!({ "examples": examples }));
+            return Ok(());
+        }
+        println!();
+        for example in examples {
+            println!("{}", example.task.bold());
+            println!("    {}", example.line.bright_green());
+            println!();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    /// Every visible subcommand of `cmd`, nested ones as `parent child`
+    fn commands(cmd: &Command, path: &str, out: &mut Vec<String>) {
+        for sub in cmd.get_subcommands().filter(|sub| !sub.is_hide_set()) {
+            if sub.get_name() == "help" {
+                continue;
+            }
+            let command = if path.is_empty() {
+                sub.get_name().to_string()
+            } else {
+                format!("{} {}", path, sub.get_name())
+            };
+            commands(sub, &command, out);
+            out.push(command);
+        }
+    }
+
+    #[test]
+    fn test_every_subcommand_has_an_example() {
+        let mut all = vec![];
+        commands(&crate::Cli::command(), "", &mut all);
+        assert!(all.contains(&"cache info".to_string()));
+        let missing: Vec<&String> = all
+            .iter()
+            .filter(|command| for_command(command).next().is_none())
+            .collect();
+        assert!(missing.is_empty(), "no examples for {:?}", missing);
+        // And every example is of a real command
+        for example in EXAMPLES {
+            assert!(
+                all.iter().any(|command| command == example.command),
+                "{:?}",
+                example
+            );
+            assert!(example.line.contains("ppmm "), "{:?}", example);
+        }
+    }
+
+    #[test]
+    fn test_matching() {
+        let private = matching(Some("Private INDEX"));
+        assert_eq!(private.len(), 1);
+        assert_eq!(private[0].command, "update");
+        // The task matches too: `ppm clean` removes cached data
+        assert!(
+            matching(Some("cache"))
+                .iter()
+                .any(|example| example.command == "clean")
+        );
+        assert_eq!(matching(None).len(), EXAMPLES.len());
+        assert!(matching(Some("no such thing")).is_empty());
+    }
+
+    #[test]
+    fn test_attach() {
+        let cmd = attach(crate::Cli::command(), "");
+        let add = cmd.find_subcommand("add").unwrap();
+        let help = add.get_after_help().unwrap();
+        assert!(help.starts_with(
+            "EXAMPLES:\n    # Add a package at a specific version\n    ppmm add requests==2.31.0\n"
+        ));
+        let info = cmd
+            .find_subcommand("cache")
+            .and_then(|cache| cache.find_subcommand("info"))
+            .unwrap();
+        assert!(info.get_after_help().unwrap().contains("ppmm cache info"));
+        assert!(!info.get_after_help().unwrap().contains("ppmm cache paths"));
+        let cache = cmd.find_subcommand("cache").unwrap();
+        assert!(cache.get_after_help().unwrap().contains("ppmm cache paths"));
+        // Help the command already had is kept, examples after it
+        let completions = cmd.find_subcommand("completions").unwrap();
+        let help = completions.get_after_help().unwrap();
+        assert!(help.starts_with("Installation:"));
+        assert!(help.contains("\n\nEXAMPLES:\n"));
+    }
+
+    #[test]
+    fn test_topics() {
+        let topics = topics();
+        assert_eq!(
+            topics[0],
+            ("new", "Create a project with a git repository and a venv")
+        );
+        assert_eq!(
+            topics.iter().filter(|(name, _)| *name == "cache").count(),
+            1
+        );
+    }
+}
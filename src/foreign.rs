@@ -0,0 +1,169 @@
+//! Other tools' manifests saved as project.toml.
+//!
+//! A project.toml that parses as TOML but not as a ppm config is checked
+//! for the tables those tools write, so the error can say what the file
+//! is and what to do with it instead of naming the first field serde
+//! missed. Detection only looks at the parsed document; a file with
+//! none of the telltale tables keeps the parse error.
+
+use std::fmt;
+
+/// A manifest format that isn't ppm's
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Poetry's pyproject.toml, with `[tool.poetry]`
+    Poetry,
+    /// A PEP 621 `[project]` table, with `dependencies`,
+    /// `requires-python` and the like
+    Pep621,
+    /// A pyproject.toml with only a `[build-system]` table
+    BuildSystem,
+}
+
+impl Format {
+    /// The table that gave the format away
+    pub fn telltale(&self) -> &'static str {
+        match self {
+            Format::Poetry => "[tool.poetry]",
+            Format::Pep621 => "[project] with PEP 621 fields like `dependencies`",
+            Format::BuildSystem => "[build-system]",
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Format::Poetry => "a Poetry manifest",
+            Format::Pep621 => "a PEP 621 pyproject.toml",
+            Format::BuildSystem => "a pyproject.toml",
+        })
+    }
+}
+
+/// Keys of a PEP 621 `[project]` table that ppm's `[project]` never has
+const PEP621_KEYS: &[&str] = &[
+    "requires-python",
+    "optional-dependencies",
+    "dynamic",
+    "readme",
+    "authors",
+    "maintainers",
+    "classifiers",
+    "urls",
+];
+
+/// The foreign format `document` is in, `None` when it has nothing
+/// another tool would write
+pub fn detect(document: &toml::Table) -> Option<Format> {
+    if document
+        .get("tool")
+        .and_then(|tool| tool.get("poetry"))
+        .is_some()
+    {
+        return Some(Format::Poetry);
+    }
+    if let Some(project) = document.get("project").and_then(toml::Value::as_table)
+        && (project
+            .get("dependencies")
+            .is_some_and(toml::Value::is_array)
+            || PEP621_KEYS.iter().any(|key| project.contains_key(*key)))
+    {
+        return Some(Format::Pep621);
+    }
+    if document.contains_key("build-system") && !document.contains_key("packages") {
+        return Some(Format::BuildSystem);
+    }
+    None
+}
+
+/// What to tell the user about `path`, a project.toml in `format`
+pub fn describe(path: &str, format: Format) -> String {
+    format!(
+        "{} looks like {} ({}), not a ppm project.toml; rename it to pyproject.toml, run `ppm init` to create a project.toml next to it and `ppm add` its dependencies",
+        path,
+        format,
+        format.telltale()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect_str(text: &str) -> Option<Format> {
+        detect(&toml::from_str(text).unwrap())
+    }
+
+    #[test]
+    fn test_poetry() {
+        let poetry = r#"
+[tool.poetry]
+name = "demo"
+version = "0.1.0"
+
+[tool.poetry.dependencies]
+python = "^3.11"
+requests = "^2.31"
+
+[build-system]
+requires = ["poetry-core"]
+build-backend = "poetry.core.masonry.api"
+"#;
+        assert_eq!(detect_str(poetry), Some(Format::Poetry));
+    }
+
+    #[test]
+    fn test_pep621() {
+        let pep621 = r#"
+[project]
+name = "demo"
+version = "0.1.0"
+dependencies = ["requests>=2.31", "click"]
+
+[build-system]
+requires = ["hatchling"]
+build-backend = "hatchling.build"
+"#;
+        assert_eq!(detect_str(pep621), Some(Format::Pep621));
+        // No dependencies, but a key only PEP 621 has
+        assert_eq!(
+            detect_str("[project]\nname = \"demo\"\nrequires-python = \">=3.10\"\n"),
+            Some(Format::Pep621)
+        );
+    }
+
+    #[test]
+    fn test_build_system_only() {
+        let setuptools = r#"
+[build-system]
+requires = ["setuptools>=61"]
+build-backend = "setuptools.build_meta"
+
+[tool.black]
+line-length = 100
+"#;
+        assert_eq!(detect_str(setuptools), Some(Format::BuildSystem));
+    }
+
+    #[test]
+    fn test_malformed_ppm_file_is_not_foreign() {
+        // ppm's own tables, with a field missing or of the wrong type
+        assert_eq!(detect_str("[project]\nname = \"demo\"\n"), None);
+        assert_eq!(
+            detect_str(
+                "[project]\nname = \"demo\"\nversion = 1\ndescription = \"\"\nmain_script = \"main.py\"\n\n[packages]\nrequests = \"2.31.0\"\n"
+            ),
+            None
+        );
+        assert_eq!(detect_str(""), None);
+    }
+
+    #[test]
+    fn test_message() {
+        assert_eq!(
+            describe("/work/demo/project.toml", Format::Poetry),
+            "/work/demo/project.toml looks like a Poetry manifest ([tool.poetry]), not a ppm project.toml; rename it to pyproject.toml, run `ppm init` to create a project.toml next to it and `ppm add` its dependencies"
+        );
+    }
+}
@@ -0,0 +1,125 @@
+//! Resolving git refs for git packages, via `git ls-remote`, and the
+//! commit and tag `ppm bump --tag` makes in the project's own repository.
+
+use crate::error::PpmError;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// The commit `reference` (a branch or tag) points to in the repository
+/// at `url`
+pub fn resolve_ref(url: &str, reference: &str) -> Result<String, PpmError> {
+    let output = Command::new("git")
+        .args(["ls-remote", url, reference])
+        .output()
+        .map_err(|e| PpmError::Subprocess(format!("Failed to run git: {}", e)))?;
+    if !output.status.success() {
+        return Err(PpmError::Network(format!(
+            "git ls-remote {} failed: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    parse_ls_remote(&String::from_utf8_lossy(&output.stdout), reference)
+        .ok_or_else(|| PpmError::Other(format!("No branch or tag '{}' in {}", reference, url)))
+}
+
+/// The commit for `reference` in `git ls-remote` output. An annotated
+/// tag is listed twice; its peeled `^{}` line names the commit.
+pub fn parse_ls_remote(output: &str, reference: &str) -> Option<String> {
+    let candidates = [
+        format!("refs/tags/{}^{{}}", reference),
+        format!("refs/heads/{}", reference),
+        format!("refs/tags/{}", reference),
+        reference.to_string(),
+    ];
+    let refs: Vec<(&str, &str)> = output
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .collect();
+    candidates.iter().find_map(|wanted| {
+        refs.iter()
+            .find(|(_, name)| name == wanted)
+            .map(|(commit, _)| commit.to_string())
+    })
+}
+
+/// Run git with `args` in `root`, failing when it exits unsuccessfully
+fn git_in(root: &Path, args: &[&str]) -> Result<Output, PpmError> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(root)
+        .output()
+        .map_err(|e| PpmError::Subprocess(format!("Failed to run git: {}", e)))?;
+    if !output.status.success() {
+        return Err(PpmError::Subprocess(format!(
+            "git {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(output)
+}
+
+/// Whether the work tree at `root` has no uncommitted changes to tracked
+/// files; untracked files don't count. Fails outside a git repository.
+pub fn is_clean(root: &Path) -> Result<bool, PpmError> {
+    let output = git_in(root, &["status", "--porcelain", "--untracked-files=no"])?;
+    Ok(output.stdout.iter().all(u8::is_ascii_whitespace))
+}
+
+/// Whether the repository at `root` has a tag named `tag`
+pub fn has_tag(root: &Path, tag: &str) -> Result<bool, PpmError> {
+    let output = git_in(root, &["tag", "--list", tag])?;
+    Ok(!String::from_utf8_lossy(&output.stdout).trim().is_empty())
+}
+
+/// Commit `files` with `message` and put the annotated tag `tag` on the
+/// commit
+pub fn commit_and_tag(
+    root: &Path,
+    files: &[PathBuf],
+    message: &str,
+    tag: &str,
+) -> Result<(), PpmError> {
+    let mut add = vec!["add".to_string(), "--".to_string()];
+    add.extend(files.iter().map(|file| file.to_string_lossy().into_owned()));
+    git_in(root, &add.iter().map(String::as_str).collect::<Vec<_>>())?;
+    git_in(root, &["commit", "-m", message])?;
+    git_in(root, &["tag", "-a", tag, "-m", message])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OUTPUT: &str = "\
+1111111111111111111111111111111111111111\tHEAD
+2222222222222222222222222222222222222222\trefs/heads/main
+3333333333333333333333333333333333333333\trefs/tags/v1.0
+4444444444444444444444444444444444444444\trefs/tags/v1.0^{}
+5555555555555555555555555555555555555555\trefs/tags/v0.9
+";
+
+    #[test]
+    fn test_parse_ls_remote() {
+        assert_eq!(
+            parse_ls_remote(OUTPUT, "main").as_deref(),
+            Some("2222222222222222222222222222222222222222")
+        );
+        // Annotated tags resolve to the commit, not the tag object
+        assert_eq!(
+            parse_ls_remote(OUTPUT, "v1.0").as_deref(),
+            Some("4444444444444444444444444444444444444444")
+        );
+        assert_eq!(
+            parse_ls_remote(OUTPUT, "v0.9").as_deref(),
+            Some("5555555555555555555555555555555555555555")
+        );
+        assert_eq!(
+            parse_ls_remote(OUTPUT, "HEAD").as_deref(),
+            Some("1111111111111111111111111111111111111111")
+        );
+        assert_eq!(parse_ls_remote(OUTPUT, "develop"), None);
+    }
+}
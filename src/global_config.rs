@@ -0,0 +1,329 @@
+//! Settings that apply to every project, from `config.toml` in the user's
+//! ppm config directory.
+//!
+//! The file is optional and every key has a default, so a missing file is
+//! the same as an empty one:
+//!
+//! ```toml
+//! update-check = false
+//! author = "Ada Lovelace"
+//! cache-max-size = "100MB"
+//! theme = "ascii"
+//!
+//! [aliases]
+//! s = "start"
+//! ```
+//!
+//! `ppm config --global set` changes top-level keys with [`set_value`].
+
+use crate::error::PpmError;
+use crate::state::write_atomic;
+use crate::theme::{Theme, ThemeConfig};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Name of the global config file inside [`global_config_dir`]
+pub const GLOBAL_CONFIG_FILE: &str = "config.toml";
+
+/// Overrides the global config directory
+pub const CONFIG_DIR_ENV: &str = "PPM_CONFIG_DIR";
+
+/// Top-level keys [`set_value`] can change
+pub const SETTABLE_KEYS: &[&str] = &["update-check", "author", "cache-max-size", "theme"];
+
+/// The user's settings
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct GlobalConfig {
+    /// Whether to look for newer ppmm releases now and then; on unless
+    /// set to `false`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub update_check: Option<bool>,
+    /// Copyright holder for the LICENSE files `ppm new` and `ppm init`
+    /// write; git's `user.name` when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    /// Budget of the PyPI metadata cache, like `"100MB"`; see
+    /// [`crate::metadata_cache`]
+    #[serde(
+        default,
+        deserialize_with = "crate::metadata_cache::deserialize_size",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub cache_max_size: Option<u64>,
+    /// Colors and symbols of the output; see [`crate::theme`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub theme: Option<ThemeConfig>,
+    /// Aliases for every project; a project's own override these
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, String>,
+}
+
+impl GlobalConfig {
+    /// Where the file is, `None` when there is no config directory
+    pub fn path() -> Option<PathBuf> {
+        global_config_dir().map(|dir| dir.join(GLOBAL_CONFIG_FILE))
+    }
+
+    /// Load the user's config, the defaults when there is none
+    pub fn load() -> Result<GlobalConfig, PpmError> {
+        match GlobalConfig::path() {
+            Some(path) => GlobalConfig::load_from(&path),
+            None => Ok(GlobalConfig::default()),
+        }
+    }
+
+    /// Load the config at `path`, the defaults when the file doesn't exist
+    pub fn load_from(path: &Path) -> Result<GlobalConfig, PpmError> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(GlobalConfig::default());
+            }
+            Err(e) => return Err(e.into()),
+        };
+        toml::from_str(&contents)
+            .map_err(|e| PpmError::Config(format!("Invalid {}: {}", path.display(), e)))
+    }
+
+    /// The configured theme, the default one when there is none
+    pub fn theme(&self) -> Result<Theme, String> {
+        self.theme
+            .as_ref()
+            .map_or_else(|| Ok(Theme::default()), ThemeConfig::resolve)
+    }
+}
+
+/// The value of top-level `key` in the config file at `path`, `None` when
+/// it isn't set
+pub fn get_value(path: &Path, key: &str) -> Result<Option<toml::Value>, PpmError> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let mut table: toml::Table = toml::from_str(&contents)
+        .map_err(|e| PpmError::Config(format!("Invalid {}: {}", path.display(), e)))?;
+    Ok(table.remove(key))
+}
+
+/// Set top-level `key`, one of [`SETTABLE_KEYS`], to `value` in the config
+/// file at `path`, creating it if needed. The rest of the file, comments
+/// included, is left as it is, and nothing is written unless the result
+/// loads.
+pub fn set_value(path: &Path, key: &str, value: &str) -> Result<(), PpmError> {
+    let literal = match key {
+        "update-check" => match value {
+            "true" | "false" => value.to_string(),
+            _ => {
+                return Err(PpmError::Config(format!(
+                    "update-check is true or false, not '{}'",
+                    value
+                )));
+            }
+        },
+        "author" | "cache-max-size" | "theme" => toml::Value::String(value.to_string()).to_string(),
+        _ => {
+            return Err(PpmError::Config(format!(
+                "Unknown config key '{}'; the keys are {}",
+                key,
+                SETTABLE_KEYS.join(", ")
+            )));
+        }
+    };
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let updated = with_top_level_key(&contents, key, &literal).ok_or_else(|| {
+        PpmError::Config(format!(
+            "{} has a [{}] table; edit it there",
+            path.display(),
+            key
+        ))
+    })?;
+    let config: GlobalConfig = toml::from_str(&updated)
+        .map_err(|e| PpmError::Config(format!("Invalid {} for {}: {}", value, key, e)))?;
+    config.theme().map_err(PpmError::Config)?;
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    write_atomic(path, updated.as_bytes())?;
+    Ok(())
+}
+
+/// `contents` with the line `key = literal` replacing the top-level one
+/// for `key`, or added before the first table; `None` when `key` is a table
+fn with_top_level_key(contents: &str, key: &str, literal: &str) -> Option<String> {
+    let line = format!("{} = {}", key, literal);
+    let mut lines: Vec<&str> = contents.lines().collect();
+    let tables = lines
+        .iter()
+        .position(|line| line.trim_start().starts_with('['))
+        .unwrap_or(lines.len());
+    if lines[tables..].iter().any(|line| {
+        let header = line.trim();
+        header == format!("[{}]", key) || header.starts_with(&format!("[{}.", key))
+    }) {
+        return None;
+    }
+    let existing = lines[..tables].iter().position(|line| {
+        line.trim_start()
+            .strip_prefix(key)
+            .is_some_and(|rest| rest.trim_start().starts_with('='))
+    });
+    match existing {
+        Some(i) => lines[i] = &line,
+        None => {
+            // Keeps a blank line between the keys and the first table
+            let at = lines[..tables]
+                .iter()
+                .rposition(|line| !line.trim().is_empty())
+                .map_or(0, |i| i + 1);
+            lines.insert(at, &line);
+        }
+    }
+    let mut out = lines.join("\n");
+    out.push('\n');
+    Some(out)
+}
+
+/// Config directory shared by all projects: `$PPM_CONFIG_DIR` when set,
+/// otherwise `ppm` in the platform config directory (`$XDG_CONFIG_HOME`
+/// or `~/.config` on Linux, `~/Library/Application Support` on macOS,
+/// `%APPDATA%` on Windows)
+pub fn global_config_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os(CONFIG_DIR_ENV).filter(|dir| !dir.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    platform_config_dir().map(|dir| dir.join("ppm"))
+}
+
+#[cfg(target_os = "windows")]
+pub(crate) fn platform_config_dir() -> Option<PathBuf> {
+    std::env::var_os("APPDATA").map(PathBuf::from)
+}
+
+#[cfg(target_os = "macos")]
+pub(crate) fn platform_config_dir() -> Option<PathBuf> {
+    std::env::home_dir().map(|home| home.join("Library").join("Application Support"))
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+pub(crate) fn platform_config_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::home_dir().map(|home| home.join(".config")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_global_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(GLOBAL_CONFIG_FILE);
+        assert_eq!(
+            GlobalConfig::load_from(&path).unwrap(),
+            GlobalConfig::default()
+        );
+
+        std::fs::write(&path, "update-check = false\n").unwrap();
+        assert_eq!(
+            GlobalConfig::load_from(&path).unwrap().update_check,
+            Some(false)
+        );
+
+        std::fs::write(&path, "[aliases]\ni = \"install\"\n").unwrap();
+        assert_eq!(
+            GlobalConfig::load_from(&path).unwrap().aliases["i"],
+            "install"
+        );
+
+        std::fs::write(&path, "cache-max-size = \"10MB\"\n").unwrap();
+        assert_eq!(
+            GlobalConfig::load_from(&path).unwrap().cache_max_size,
+            Some(10 * 1024 * 1024)
+        );
+        std::fs::write(&path, "cache-max-size = 4096\n").unwrap();
+        assert_eq!(
+            GlobalConfig::load_from(&path).unwrap().cache_max_size,
+            Some(4096)
+        );
+        std::fs::write(&path, "cache-max-size = \"lots\"\n").unwrap();
+        assert!(matches!(
+            GlobalConfig::load_from(&path),
+            Err(PpmError::Config(_))
+        ));
+
+        std::fs::write(&path, "theme = \"high-contrast\"\n").unwrap();
+        assert_eq!(
+            GlobalConfig::load_from(&path).unwrap().theme(),
+            Ok(Theme::preset("high-contrast").unwrap())
+        );
+        std::fs::write(&path, "theme = \"neon\"\n").unwrap();
+        assert!(GlobalConfig::load_from(&path).unwrap().theme().is_err());
+
+        std::fs::write(&path, "update-check = \"sometimes\"\n").unwrap();
+        assert!(matches!(
+            GlobalConfig::load_from(&path),
+            Err(PpmError::Config(_))
+        ));
+    }
+
+    #[test]
+    fn test_set_value() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ppm").join(GLOBAL_CONFIG_FILE);
+        set_value(&path, "theme", "high-contrast").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "theme = \"high-contrast\"\n"
+        );
+
+        std::fs::write(
+            &path,
+            "# mine\nupdate-check = false\ntheme = \"ascii\"\n\n[aliases]\ns = \"start\"\n",
+        )
+        .unwrap();
+        set_value(&path, "theme", "default").unwrap();
+        set_value(&path, "author", "Ada \"AL\" Lovelace").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "# mine\nupdate-check = false\ntheme = \"default\"\nauthor = 'Ada \"AL\" Lovelace'\n\n[aliases]\ns = \"start\"\n"
+        );
+        let config = GlobalConfig::load_from(&path).unwrap();
+        assert_eq!(config.author.as_deref(), Some("Ada \"AL\" Lovelace"));
+        assert_eq!(config.aliases["s"], "start");
+
+        // Invalid values leave the file alone
+        let before = std::fs::read_to_string(&path).unwrap();
+        for (key, value) in [
+            ("theme", "neon"),
+            ("update-check", "sometimes"),
+            ("cache-max-size", "lots"),
+            ("colour", "red"),
+        ] {
+            assert!(
+                matches!(set_value(&path, key, value), Err(PpmError::Config(_))),
+                "{} = {}",
+                key,
+                value
+            );
+        }
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), before);
+
+        std::fs::write(&path, "[theme]\npreset = \"ascii\"\n").unwrap();
+        assert!(set_value(&path, "theme", "default").is_err());
+        assert_eq!(
+            get_value(&path, "theme").unwrap().unwrap()["preset"].as_str(),
+            Some("ascii")
+        );
+        assert_eq!(get_value(&path, "author").unwrap(), None);
+    }
+}
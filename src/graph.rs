@@ -0,0 +1,742 @@
+//! The dependency graph of the packages installed in the venv, built from
+//! the `Requires-Dist` lines in their dist-info `METADATA`, its Graphviz
+//! (`ppm graph --format dot`) and Mermaid renderings, and the reverse
+//! walk `ppm rdeps` takes from a package to what depends on it.
+//!
+//! Each time ppm refreshes ppmm.lock the graph is also stored in
+//! [`GRAPH_CACHE`], so `ppm rm` can still tell what needs a package when
+//! the venv is gone.
+//!
+//! Requirements that only apply with an extra are left out, and an edge
+//! is only drawn to a package the venv has; other environment markers
+//! aren't evaluated, since the installed set already reflects them.
+
+use crate::context::ProjectContext;
+use crate::error::PpmError;
+use crate::packages::normalize_pkg_name;
+use crate::paths::site_packages_dirs;
+use crate::settings::Config;
+use crate::state::StateDir;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+/// The last dependency graph read from the venv, inside the project's
+/// [`StateDir`]
+pub const GRAPH_CACHE: &str = "cache/graph.json";
+
+/// Packages `ppm rm --cascade` never removes, since the venv's own
+/// tooling needs them
+pub const KEEP_INSTALLED: &[&str] = &["pip", "setuptools", "wheel"];
+
+/// How `ppm graph` writes the graph
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GraphFormat {
+    /// Graphviz DOT
+    #[default]
+    Dot,
+    /// A Mermaid flowchart
+    Mermaid,
+}
+
+impl FromStr for GraphFormat {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<GraphFormat, String> {
+        match name {
+            "dot" => Ok(GraphFormat::Dot),
+            "mermaid" => Ok(GraphFormat::Mermaid),
+            _ => Err(format!(
+                "Unknown graph format '{}'; use dot or mermaid",
+                name
+            )),
+        }
+    }
+}
+
+impl fmt::Display for GraphFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphFormat::Dot => write!(f, "dot"),
+            GraphFormat::Mermaid => write!(f, "mermaid"),
+        }
+    }
+}
+
+/// One requirement of an installed package
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Dependency {
+    /// Package name as written in the metadata
+    pub name: String,
+    /// Version clauses like `<3,>=1.21.1`, empty for any version
+    pub specifier: String,
+}
+
+/// An installed distribution and what it requires
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstalledPackage {
+    /// Name as the metadata spells it
+    pub name: String,
+    /// Installed version
+    pub version: String,
+    /// Listed in project.toml rather than pulled in by another package
+    pub direct: bool,
+    /// Its `Requires-Dist` requirements, extras-only ones left out
+    pub requires: Vec<Dependency>,
+}
+
+/// The requirement in a `Requires-Dist` value such as
+/// `urllib3 (<3,>=1.21.1) ; python_version >= "3.8"`; `None` when it only
+/// applies with an extra
+pub fn parse_requires_dist(value: &str) -> Option<Dependency> {
+    let (body, marker) = match value.split_once(';') {
+        Some((body, marker)) => (body.trim(), Some(marker)),
+        None => (value.trim(), None),
+    };
+    if marker.is_some_and(|marker| marker.contains("extra")) {
+        return None;
+    }
+    let name_end = body
+        .find(|c: char| !(c.is_ascii_alphanumeric() || "._-".contains(c)))
+        .unwrap_or(body.len());
+    let name = &body[..name_end];
+    if name.is_empty() {
+        return None;
+    }
+    let mut rest = body[name_end..].trim_start();
+    if let Some(after) = rest.strip_prefix('[') {
+        rest = after
+            .split_once(']')
+            .map_or("", |(_, rest)| rest)
+            .trim_start();
+    }
+    let specifier = rest
+        .strip_prefix('(')
+        .and_then(|rest| rest.strip_suffix(')'))
+        .unwrap_or(rest);
+    Some(Dependency {
+        name: name.to_string(),
+        specifier: specifier.split_whitespace().collect(),
+    })
+}
+
+/// Name, version and requirements in the text of a `METADATA` file; only
+/// the header lines before the description are read
+pub fn parse_metadata(metadata: &str) -> (Option<String>, Option<String>, Vec<Dependency>) {
+    let (mut name, mut version, mut requires) = (None, None, vec![]);
+    for line in metadata.lines().take_while(|line| !line.is_empty()) {
+        if let Some(value) = line.strip_prefix("Name:") {
+            name = Some(value.trim().to_string());
+        } else if let Some(value) = line.strip_prefix("Version:") {
+            version = Some(value.trim().to_string());
+        } else if let Some(value) = line.strip_prefix("Requires-Dist:") {
+            requires.extend(parse_requires_dist(value));
+        }
+    }
+    (name, version, requires)
+}
+
+/// Installed packages by normalized name, with the requirements between
+/// them as edges
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DependencyGraph {
+    packages: BTreeMap<String, InstalledPackage>,
+}
+
+impl DependencyGraph {
+    /// A graph of `packages`
+    pub fn new(packages: Vec<InstalledPackage>) -> DependencyGraph {
+        DependencyGraph {
+            packages: packages
+                .into_iter()
+                .map(|package| (normalize_pkg_name(&package.name), package))
+                .collect(),
+        }
+    }
+
+    /// Every distribution installed in `venv_dir`, marking those `config`
+    /// lists as direct
+    pub fn from_venv(venv_dir: &Path, config: &Config) -> DependencyGraph {
+        let mut packages = vec![];
+        for site_packages in site_packages_dirs(venv_dir) {
+            let Ok(entries) = fs::read_dir(&site_packages) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let file_name = entry.file_name().to_string_lossy().into_owned();
+                let Some(stem) = file_name.strip_suffix(".dist-info") else {
+                    continue;
+                };
+                let Some((dist_name, dist_version)) = stem.split_once('-') else {
+                    continue;
+                };
+                let metadata =
+                    fs::read_to_string(entry.path().join("METADATA")).unwrap_or_default();
+                let (name, version, requires) = parse_metadata(&metadata);
+                let name = name.unwrap_or_else(|| dist_name.to_string());
+                packages.push(InstalledPackage {
+                    direct: false,
+                    name,
+                    version: version.unwrap_or_else(|| dist_version.to_string()),
+                    requires,
+                });
+            }
+        }
+        let mut graph = DependencyGraph::new(packages);
+        graph.mark_direct(config);
+        graph
+    }
+
+    /// Mark the packages `config` lists as direct, and only those
+    pub fn mark_direct(&mut self, config: &Config) {
+        let declared: HashSet<String> = config
+            .packages
+            .keys()
+            .map(|name| normalize_pkg_name(name))
+            .collect();
+        for (key, package) in self.packages.iter_mut() {
+            package.direct = declared.contains(key);
+        }
+    }
+
+    /// The package called `name`, however it's spelled
+    pub fn get(&self, name: &str) -> Option<&InstalledPackage> {
+        self.packages.get(&normalize_pkg_name(name))
+    }
+
+    /// Every package, sorted by normalized name
+    pub fn packages(&self) -> impl Iterator<Item = &InstalledPackage> {
+        self.packages.values()
+    }
+
+    /// The installed packages `package` requires, with the specifier of
+    /// each requirement
+    pub fn dependencies<'a>(
+        &'a self,
+        package: &'a InstalledPackage,
+    ) -> impl Iterator<Item = (&'a InstalledPackage, &'a str)> + 'a {
+        package
+            .requires
+            .iter()
+            .filter_map(|dep| Some((self.get(&dep.name)?, dep.specifier.as_str())))
+    }
+
+    /// `(from, to, specifier)` for every requirement between installed
+    /// packages, sorted by `from` then `to`
+    pub fn edges(&self) -> Vec<(&InstalledPackage, &InstalledPackage, &str)> {
+        let mut edges: Vec<_> = self
+            .packages()
+            .flat_map(|from| {
+                self.dependencies(from)
+                    .map(move |(to, spec)| (from, to, spec))
+            })
+            .collect();
+        edges.sort_by_key(|(from, to, _)| {
+            (normalize_pkg_name(&from.name), normalize_pkg_name(&to.name))
+        });
+        edges.dedup_by_key(|(from, to, _)| {
+            (normalize_pkg_name(&from.name), normalize_pkg_name(&to.name))
+        });
+        edges
+    }
+
+    /// The packages that depend on `name`, directly or not, grouped by
+    /// depth: those requiring it first, then those requiring them, and so
+    /// on, each at its shortest distance and sorted by name within a
+    /// depth. `None` when `name` isn't installed.
+    pub fn dependents(&self, name: &str) -> Option<Vec<Vec<&InstalledPackage>>> {
+        let root = self.get(name)?;
+        let mut seen = BTreeSet::from([normalize_pkg_name(&root.name)]);
+        let mut levels: Vec<Vec<&InstalledPackage>> = vec![];
+        let mut frontier = BTreeSet::from([normalize_pkg_name(&root.name)]);
+        while !frontier.is_empty() {
+            let level: Vec<&InstalledPackage> = self
+                .packages
+                .iter()
+                .filter(|(key, _)| !seen.contains(*key))
+                .filter(|(_, package)| {
+                    package
+                        .requires
+                        .iter()
+                        .any(|dep| frontier.contains(&normalize_pkg_name(&dep.name)))
+                })
+                .map(|(_, package)| package)
+                .collect();
+            frontier = level
+                .iter()
+                .map(|package| normalize_pkg_name(&package.name))
+                .collect();
+            seen.extend(frontier.iter().cloned());
+            if !level.is_empty() {
+                levels.push(level);
+            }
+        }
+        Some(levels)
+    }
+
+    /// The direct packages other than those in `removing` that need
+    /// `name`, themselves or through their dependencies, sorted by name
+    pub fn needed_by(&self, name: &str, removing: &[String]) -> Vec<&InstalledPackage> {
+        let removing: HashSet<String> = removing.iter().map(|n| normalize_pkg_name(n)).collect();
+        let mut needed_by: Vec<&InstalledPackage> = self
+            .dependents(name)
+            .unwrap_or_default()
+            .into_iter()
+            .flatten()
+            .filter(|package| {
+                package.direct && !removing.contains(&normalize_pkg_name(&package.name))
+            })
+            .collect();
+        needed_by.sort_by_key(|package| normalize_pkg_name(&package.name));
+        needed_by
+    }
+
+    /// The packages only `removing` needs: what they depend on, directly
+    /// or not, that isn't direct, isn't needed by any direct package that
+    /// stays, and isn't in [`KEEP_INSTALLED`]. Sorted by name.
+    pub fn orphaned_by(&self, removing: &[String]) -> Vec<&InstalledPackage> {
+        let removed: HashSet<String> = removing.iter().map(|n| normalize_pkg_name(n)).collect();
+        let kept = self.closure(
+            self.packages
+                .iter()
+                .filter(|(key, package)| package.direct && !removed.contains(*key))
+                .map(|(_, package)| package),
+        );
+        let dropped = self.closure(removing.iter().filter_map(|name| self.get(name)));
+        dropped
+            .into_iter()
+            .filter(|key| !removed.contains(key) && !kept.contains(key))
+            .filter(|key| !KEEP_INSTALLED.contains(&key.as_str()))
+            .filter_map(|key| self.packages.get(&key))
+            .filter(|package| !package.direct)
+            .collect()
+    }
+
+    /// Normalized names of the installed packages among `roots` and of
+    /// everything they depend on, directly or not
+    pub fn reachable<'a>(&self, roots: impl IntoIterator<Item = &'a str>) -> BTreeSet<String> {
+        self.closure(roots.into_iter().filter_map(|name| self.get(name)))
+    }
+
+    /// Normalized names of `roots` and everything they depend on
+    fn closure<'a>(
+        &'a self,
+        roots: impl Iterator<Item = &'a InstalledPackage>,
+    ) -> BTreeSet<String> {
+        let mut queue: Vec<&InstalledPackage> = roots.collect();
+        let mut seen: BTreeSet<String> = queue
+            .iter()
+            .map(|package| normalize_pkg_name(&package.name))
+            .collect();
+        while let Some(package) = queue.pop() {
+            for (dep, _) in self.dependencies(package) {
+                if seen.insert(normalize_pkg_name(&dep.name)) {
+                    queue.push(dep);
+                }
+            }
+        }
+        seen
+    }
+
+    /// `name` and every package it depends on, directly or not; `None`
+    /// when `name` isn't installed
+    pub fn subgraph(&self, name: &str) -> Option<DependencyGraph> {
+        let root = self.get(name)?;
+        Some(DependencyGraph {
+            packages: self
+                .closure(std::iter::once(root))
+                .into_iter()
+                .filter_map(|key| Some((key.clone(), self.packages.get(&key)?.clone())))
+                .collect(),
+        })
+    }
+
+    /// The graph in `format`
+    pub fn render(&self, format: GraphFormat) -> String {
+        match format {
+            GraphFormat::Dot => self.to_dot(),
+            GraphFormat::Mermaid => self.to_mermaid(),
+        }
+    }
+
+    /// Graphviz DOT, direct packages drawn filled and bold, each edge
+    /// labelled with its version specifier
+    pub fn to_dot(&self) -> String {
+        let mut out =
+            String::from("digraph dependencies {\n    rankdir=LR;\n    node [shape=box];\n");
+        for package in self.packages() {
+            let label = format!(
+                "\"{}\\n{}\"",
+                dot_escape(&package.name),
+                dot_escape(&package.version)
+            );
+            let style = if package.direct {
+                ", style=\"filled,bold\", fillcolor=\"#cde4ff\""
+            } else {
+                ""
+            };
+            out.push_str(&format!(
+                "    {} [label={}{}];\n",
+                dot_string(&normalize_pkg_name(&package.name)),
+                label,
+                style
+            ));
+        }
+        for (from, to, specifier) in self.edges() {
+            let label = if specifier.is_empty() {
+                String::new()
+            } else {
+                format!(" [label={}]", dot_string(specifier))
+            };
+            out.push_str(&format!(
+                "    {} -> {}{};\n",
+                dot_string(&normalize_pkg_name(&from.name)),
+                dot_string(&normalize_pkg_name(&to.name)),
+                label
+            ));
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// A Mermaid flowchart, direct packages in the `direct` class, each
+    /// edge labelled with its version specifier
+    pub fn to_mermaid(&self) -> String {
+        let mut out = String::from("flowchart LR\n");
+        for package in self.packages() {
+            out.push_str(&format!(
+                "    {}[\"{}<br/>{}\"]\n",
+                mermaid_id(&package.name),
+                mermaid_text(&package.name),
+                mermaid_text(&package.version)
+            ));
+        }
+        for (from, to, specifier) in self.edges() {
+            let label = if specifier.is_empty() {
+                String::new()
+            } else {
+                format!("|\"{}\"|", mermaid_text(specifier))
+            };
+            out.push_str(&format!(
+                "    {} -->{} {}\n",
+                mermaid_id(&from.name),
+                label,
+                mermaid_id(&to.name)
+            ));
+        }
+        let direct: Vec<String> = self
+            .packages()
+            .filter(|package| package.direct)
+            .map(|package| mermaid_id(&package.name))
+            .collect();
+        if !direct.is_empty() {
+            out.push_str("    classDef direct fill:#cde4ff,stroke:#1f6feb,stroke-width:2px\n");
+            out.push_str(&format!("    class {} direct\n", direct.join(",")));
+        }
+        out
+    }
+}
+
+/// `text` for inside a quoted DOT string
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// `text` as a quoted DOT string
+fn dot_string(text: &str) -> String {
+    format!("\"{}\"", dot_escape(text))
+}
+
+/// A Mermaid node id for the package `name`: its normalized name with
+/// `-` as `_`, which normalized names never contain, so ids stay unique
+/// and `.`, `-` and keywords like `end` can't break the chart
+fn mermaid_id(name: &str) -> String {
+    format!("pkg_{}", normalize_pkg_name(name).replace('-', "_"))
+}
+
+/// `text` for inside a quoted Mermaid label, with the characters that
+/// would end it or be read as HTML as entity codes
+fn mermaid_text(text: &str) -> String {
+    text.replace('#', "#35;")
+        .replace('"', "#quot;")
+        .replace('<', "#lt;")
+        .replace('>', "#gt;")
+}
+
+/// The dependency graph of the project's venv, stored for later
+pub fn graph(ctx: &ProjectContext) -> Result<DependencyGraph, PpmError> {
+    ctx.check_venv_in_use()?;
+    match current_or_cached(ctx) {
+        Some((graph, false)) => Ok(graph),
+        _ => Err(PpmError::Venv(
+            "Virtual Environment Not Found; run `ppm install` to create it".to_string(),
+        )),
+    }
+}
+
+/// Store `graph` as the project's [`GRAPH_CACHE`]
+pub fn store(state: &StateDir, graph: &DependencyGraph) -> io::Result<()> {
+    let packages: Vec<&InstalledPackage> = graph.packages().collect();
+    let contents = serde_json::to_vec(&packages).map_err(io::Error::other)?;
+    state.write(GRAPH_CACHE, &contents).map(|_| ())
+}
+
+/// The graph last stored in `state`, if any, with the packages `config`
+/// lists marked direct
+pub fn load(state: &StateDir, config: &Config) -> Option<DependencyGraph> {
+    let contents = fs::read(state.subpath(GRAPH_CACHE)).ok()?;
+    let packages: Vec<InstalledPackage> = serde_json::from_slice(&contents).ok()?;
+    let mut graph = DependencyGraph::new(packages);
+    graph.mark_direct(config);
+    Some(graph)
+}
+
+/// The venv's dependency graph, stored for later; the stored one when
+/// there is no venv, with `true` to say so; `None` without either
+pub fn current_or_cached(ctx: &ProjectContext) -> Option<(DependencyGraph, bool)> {
+    let venv_dir = ctx.venv_dir();
+    if !ctx.system_python && venv_dir.is_dir() {
+        let graph = DependencyGraph::from_venv(&venv_dir, &ctx.config);
+        let _ = store(&ctx.state(), &graph);
+        return Some((graph, false));
+    }
+    load(&ctx.state(), &ctx.config).map(|graph| (graph, true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(name: &str, version: &str, direct: bool, requires: &[&str]) -> InstalledPackage {
+        InstalledPackage {
+            name: name.to_string(),
+            version: version.to_string(),
+            direct,
+            requires: requires
+                .iter()
+                .filter_map(|value| parse_requires_dist(value))
+                .collect(),
+        }
+    }
+
+    /// A web app on requests and a `zope.interface` namespace package,
+    /// with a dependency on something that isn't installed
+    fn sample() -> DependencyGraph {
+        DependencyGraph::new(vec![
+            package(
+                "requests",
+                "2.32.3",
+                true,
+                &[
+                    "charset-normalizer (<4,>=2)",
+                    "idna<4,>=2.5",
+                    "urllib3<3,>=1.21.1",
+                    "PySocks!=1.5.7,>=1.5.6; extra == \"socks\"",
+                ],
+            ),
+            package("charset_normalizer", "3.3.2", false, &[]),
+            package("idna", "3.7", false, &[]),
+            package(
+                "urllib3",
+                "2.2.1",
+                false,
+                &["brotli>=1.0.9; extra == 'brotli'"],
+            ),
+            package(
+                "zope.interface",
+                "6.4",
+                true,
+                &["setuptools", "typing-extensions; python_version < \"3.8\""],
+            ),
+            package("setuptools", "70.0.0", false, &[]),
+        ])
+    }
+
+    #[test]
+    fn test_parse_requires_dist() {
+        assert_eq!(
+            parse_requires_dist("urllib3 (<3,>=1.21.1) ; python_version >= \"3.8\""),
+            Some(Dependency {
+                name: "urllib3".to_string(),
+                specifier: "<3,>=1.21.1".to_string(),
+            })
+        );
+        assert_eq!(
+            parse_requires_dist(" requests[socks] >= 2.0, < 3")
+                .unwrap()
+                .specifier,
+            ">=2.0,<3"
+        );
+        assert_eq!(parse_requires_dist("pytest; extra == \"test\""), None);
+        assert_eq!(parse_requires_dist(""), None);
+
+        let (name, version, requires) = parse_metadata(
+            "Metadata-Version: 2.1\nName: Flask\nVersion: 3.0.3\nRequires-Dist: Werkzeug>=3.0.0\nRequires-Dist: asgiref>=3.2 ; extra == \"async\"\n\nRequires-Dist: not-a-header\n",
+        );
+        assert_eq!(
+            (name.as_deref(), version.as_deref()),
+            (Some("Flask"), Some("3.0.3"))
+        );
+        assert_eq!(requires.len(), 1);
+    }
+
+    #[test]
+    fn test_edges_only_reach_installed_packages() {
+        let graph = sample();
+        let edges: Vec<(&str, &str, &str)> = graph
+            .edges()
+            .into_iter()
+            .map(|(from, to, spec)| (from.name.as_str(), to.name.as_str(), spec))
+            .collect();
+        assert_eq!(
+            edges,
+            [
+                ("requests", "charset_normalizer", "<4,>=2"),
+                ("requests", "idna", "<4,>=2.5"),
+                ("requests", "urllib3", "<3,>=1.21.1"),
+                ("zope.interface", "setuptools", ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dependents_by_depth_through_a_diamond() {
+        // app -> web -> http-core -> urllib3, and app -> cli -> http-core
+        let graph = DependencyGraph::new(vec![
+            package("app", "1.0", true, &["web>=2", "cli"]),
+            package("web", "2.1", false, &["http_core"]),
+            package("cli", "0.4", false, &["Http.Core<2"]),
+            package("http-core", "1.2", false, &["urllib3"]),
+            package("urllib3", "2.2.1", false, &[]),
+            package("tool", "0.1", true, &["urllib3; extra == \"net\""]),
+        ]);
+        let names = |levels: Vec<Vec<&InstalledPackage>>| -> Vec<Vec<String>> {
+            levels
+                .into_iter()
+                .map(|level| level.into_iter().map(|p| p.name.clone()).collect())
+                .collect()
+        };
+        assert_eq!(
+            names(graph.dependents("urllib3").unwrap()),
+            [vec!["http-core"], vec!["cli", "web"], vec!["app"]]
+        );
+        assert_eq!(
+            names(graph.dependents("HTTP_core").unwrap()),
+            [vec!["cli", "web"], vec!["app"]]
+        );
+        assert!(graph.dependents("app").unwrap().is_empty());
+        assert!(graph.dependents("tool").unwrap().is_empty());
+        assert_eq!(graph.dependents("flask"), None);
+    }
+
+    #[test]
+    fn test_removal_checks() {
+        // requests-oauthlib needs requests; only requests needs idna, and
+        // both it and twine need urllib3
+        let graph = DependencyGraph::new(vec![
+            package("requests", "2.32.3", true, &["idna<4", "urllib3<3"]),
+            package(
+                "requests-oauthlib",
+                "2.0.0",
+                true,
+                &["oauthlib>=3", "requests>=2"],
+            ),
+            package("oauthlib", "3.2.2", false, &[]),
+            package("twine", "5.1.0", true, &["urllib3>=1.26", "setuptools"]),
+            package("idna", "3.7", false, &[]),
+            package("urllib3", "2.2.1", false, &[]),
+            package("setuptools", "70.0.0", false, &[]),
+        ]);
+        let names = |packages: Vec<&InstalledPackage>| -> Vec<String> {
+            packages.into_iter().map(|p| p.name.clone()).collect()
+        };
+        let removing =
+            |names: &[&str]| -> Vec<String> { names.iter().map(|n| n.to_string()).collect() };
+
+        assert_eq!(
+            names(graph.needed_by("Requests", &removing(&["requests"]))),
+            ["requests-oauthlib"]
+        );
+        assert!(
+            graph
+                .needed_by("requests", &removing(&["requests", "requests_oauthlib"]))
+                .is_empty()
+        );
+        // A dependency of a direct package still counts
+        assert_eq!(
+            names(graph.needed_by("oauthlib", &[])),
+            ["requests-oauthlib"]
+        );
+
+        assert_eq!(
+            names(graph.orphaned_by(&removing(&["requests-oauthlib"]))),
+            ["oauthlib"]
+        );
+        assert_eq!(
+            names(graph.orphaned_by(&removing(&["requests", "requests-oauthlib"]))),
+            ["idna", "oauthlib"]
+        );
+        // setuptools is the venv's own
+        assert!(graph.orphaned_by(&removing(&["twine"])).is_empty());
+    }
+
+    #[test]
+    fn test_store_and_load_mark_direct_from_the_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = StateDir::project(dir.path());
+        let config = |packages: &str| -> Config {
+            toml::from_str(&format!(
+                "[project]\nname = \"demo\"\nversion = \"0.1.0\"\ndescription = \"\"\nmain_script = \"./main.py\"\n\n[packages]\n{}\n[scripts]\n",
+                packages
+            ))
+            .unwrap()
+        };
+        assert_eq!(load(&state, &config("")), None);
+        store(&state, &sample()).unwrap();
+        let loaded = load(&state, &config("idna = \"3.7\"\n")).unwrap();
+        let direct: Vec<&str> = loaded
+            .packages()
+            .filter(|p| p.direct)
+            .map(|p| p.name.as_str())
+            .collect();
+        assert_eq!(direct, ["idna"]);
+        assert_eq!(loaded.edges().len(), sample().edges().len());
+    }
+
+    #[test]
+    fn test_subgraph_of_one_package() {
+        let graph = sample();
+        let sub = graph.subgraph("Zope_Interface").unwrap();
+        let names: Vec<&str> = sub.packages().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["setuptools", "zope.interface"]);
+        assert_eq!(graph.subgraph("urllib3").unwrap().packages().count(), 1);
+        assert_eq!(graph.subgraph("flask"), None);
+    }
+
+    #[test]
+    fn test_dot_golden() {
+        assert_eq!(
+            sample().to_dot(),
+            include_str!("../tests/fixtures/graph/sample.dot")
+        );
+    }
+
+    #[test]
+    fn test_mermaid_golden() {
+        assert_eq!(
+            sample().to_mermaid(),
+            include_str!("../tests/fixtures/graph/sample.mmd")
+        );
+    }
+
+    #[test]
+    fn test_format_names() {
+        assert_eq!("mermaid".parse(), Ok(GraphFormat::Mermaid));
+        assert_eq!(GraphFormat::default().to_string(), "dot");
+        assert!("svg".parse::<GraphFormat>().is_err());
+    }
+}
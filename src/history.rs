@@ -0,0 +1,322 @@
+//! A log of what ppm changed in a project.
+//!
+//! Every command that changes project.toml or the venv appends one JSON
+//! line to `.ppm/history.jsonl`: when it ran, the command line, the
+//! packages whose versions it changed and whether it succeeded. Once the
+//! file passes [`MAX_LOG_BYTES`] it is moved to `history.1.jsonl`,
+//! replacing the previous one, so the log never takes much more than
+//! twice that.
+
+use crate::packages::normalize_pkg_name;
+use crate::settings::Config;
+use crate::state::StateDir;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The log, inside the project's [`StateDir`]
+pub const HISTORY_FILE: &str = "history.jsonl";
+
+/// Where the log is moved when it grows too big
+pub const ROTATED_FILE: &str = "history.1.jsonl";
+
+/// Size at which the log is rotated, a few thousand entries
+pub const MAX_LOG_BYTES: u64 = 256 * 1024;
+
+/// Version of the [`Entry`] format; entries written by a newer ppm are
+/// skipped when reading
+pub const FORMAT_VERSION: u32 = 1;
+
+/// How a logged command ended
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    /// The command succeeded
+    Success,
+    /// The command returned an error, possibly after changing something
+    Failure,
+}
+
+/// A package entry the command added, removed or changed
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    /// Package name as written in project.toml; for a version bump, the
+    /// project's own name
+    pub package: String,
+    /// The version (or source, for git, path and URL packages) before,
+    /// `None` when the package was added
+    pub from: Option<String>,
+    /// The version after, `None` when the package was removed
+    pub to: Option<String>,
+}
+
+/// One line of the log
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Entry {
+    /// Format version, see [`FORMAT_VERSION`]
+    pub version: u32,
+    /// When the command finished, RFC 3339 in UTC
+    pub timestamp: String,
+    /// The command line, without the program name
+    pub command: String,
+    /// What changed in project.toml, ordered by package name
+    pub changes: Vec<Change>,
+    /// Whether the command created the virtual environment
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub venv_created: bool,
+    /// Whether the command succeeded
+    pub outcome: Outcome,
+    /// The error the command failed with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Entry {
+    /// An entry for `command` finishing now, failed when `error` is set
+    pub fn new(command: &str, changes: Vec<Change>, error: Option<String>) -> Entry {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0);
+        Entry {
+            version: FORMAT_VERSION,
+            timestamp: format_timestamp(secs),
+            command: command.to_string(),
+            changes,
+            venv_created: false,
+            outcome: if error.is_some() {
+                Outcome::Failure
+            } else {
+                Outcome::Success
+            },
+            error,
+        }
+    }
+
+    /// Whether the entry changed `package`, compared by normalized name
+    pub fn touches(&self, package: &str) -> bool {
+        let package = normalize_pkg_name(package);
+        self.changes
+            .iter()
+            .any(|change| normalize_pkg_name(&change.package) == package)
+    }
+}
+
+/// Package entries that differ between two versions of project.toml, and
+/// the project version when it changed. Without `before` (a project
+/// that was just created) every package counts as added.
+pub fn diff(before: Option<&Config>, after: &Config) -> Vec<Change> {
+    let mut packages: BTreeMap<String, Change> = BTreeMap::new();
+    for (name, spec) in before.map(|conf| &conf.packages).into_iter().flatten() {
+        packages.insert(
+            normalize_pkg_name(name),
+            Change {
+                package: name.clone(),
+                from: Some(spec.to_string()),
+                to: None,
+            },
+        );
+    }
+    for (name, spec) in &after.packages {
+        let change = packages
+            .entry(normalize_pkg_name(name))
+            .or_insert_with(|| Change {
+                package: name.clone(),
+                from: None,
+                to: None,
+            });
+        change.package = name.clone();
+        change.to = Some(spec.to_string());
+    }
+
+    let mut changes = vec![];
+    if let Some(before) = before
+        && before.project.version != after.project.version
+    {
+        changes.push(Change {
+            package: after.project.name.clone(),
+            from: Some(before.project.version.clone()),
+            to: Some(after.project.version.clone()),
+        });
+    }
+    changes.extend(
+        packages
+            .into_values()
+            .filter(|change| change.from != change.to),
+    );
+    changes
+}
+
+/// Append `entry` to the log in `state`, rotating it first when it is
+/// full
+pub fn append(state: &StateDir, entry: &Entry) -> io::Result<()> {
+    if crate::state::dir_size(&state.subpath(HISTORY_FILE)) >= MAX_LOG_BYTES {
+        state.rename(HISTORY_FILE, ROTATED_FILE)?;
+    }
+    let mut line = serde_json::to_vec(entry)?;
+    line.push(b'\n');
+    state.append(HISTORY_FILE, &line)?;
+    Ok(())
+}
+
+/// Every entry in the log, oldest first. Lines that can't be parsed and
+/// entries in a newer format are skipped.
+pub fn read(state: &StateDir) -> io::Result<Vec<Entry>> {
+    let mut entries = vec![];
+    for name in [ROTATED_FILE, HISTORY_FILE] {
+        let contents = match std::fs::read_to_string(state.subpath(name)) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        entries.extend(
+            contents
+                .lines()
+                .filter_map(|line| serde_json::from_str::<Entry>(line).ok())
+                .filter(|entry| entry.version <= FORMAT_VERSION),
+        );
+    }
+    Ok(entries)
+}
+
+/// `secs` since the Unix epoch as `2024-05-01T09:30:00Z`
+pub fn format_timestamp(secs: u64) -> String {
+    let days = secs / 86_400;
+    let time = secs % 86_400;
+    // Days to a civil date, after Howard Hinnant's `civil_from_days`
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::{PackageSpec, Project};
+    use std::collections::HashMap;
+
+    fn config(version: &str, packages: &[(&str, &str)]) -> Config {
+        Config::new(
+            Project::new(
+                "demo".to_string(),
+                version.to_string(),
+                String::new(),
+                "./main.py".to_string(),
+                None,
+            ),
+            packages
+                .iter()
+                .map(|(name, version)| (name.to_string(), PackageSpec::from(*version)))
+                .collect(),
+            HashMap::new(),
+        )
+    }
+
+    fn change(package: &str, from: Option<&str>, to: Option<&str>) -> Change {
+        Change {
+            package: package.to_string(),
+            from: from.map(String::from),
+            to: to.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_diff_lists_added_removed_and_changed_packages() {
+        let before = config(
+            "0.1.0",
+            &[("numpy", "1.26.0"), ("Flask", "3.0.0"), ("six", "1.16.0")],
+        );
+        let after = config(
+            "0.2.0",
+            &[
+                ("numpy", "2.0.0"),
+                ("flask", "3.0.0"),
+                ("requests", "2.32.0"),
+            ],
+        );
+        assert_eq!(
+            diff(Some(&before), &after),
+            vec![
+                change("demo", Some("0.1.0"), Some("0.2.0")),
+                change("numpy", Some("1.26.0"), Some("2.0.0")),
+                change("requests", None, Some("2.32.0")),
+                change("six", Some("1.16.0"), None),
+            ]
+        );
+        assert_eq!(diff(None, &after).len(), 3);
+        assert!(diff(Some(&after), &after).is_empty());
+    }
+
+    #[test]
+    fn test_entry_format() {
+        let mut entry = Entry::new(
+            "update numpy",
+            vec![change("numpy", Some("1.26.0"), Some("2.0.0"))],
+            None,
+        );
+        entry.timestamp = format_timestamp(1_714_555_800);
+        assert_eq!(
+            serde_json::to_string(&entry).unwrap(),
+            r#"{"version":1,"timestamp":"2024-05-01T09:30:00Z","command":"update numpy","changes":[{"package":"numpy","from":"1.26.0","to":"2.0.0"}],"outcome":"success"}"#
+        );
+        assert!(entry.touches("NumPy"));
+        assert!(!entry.touches("pandas"));
+
+        let failed = Entry::new("add nope", vec![], Some("Network error".to_string()));
+        assert_eq!(failed.outcome, Outcome::Failure);
+        let json = serde_json::to_string(&failed).unwrap();
+        assert_eq!(serde_json::from_str::<Entry>(&json).unwrap(), failed);
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_timestamp(1_735_689_599), "2024-12-31T23:59:59Z");
+    }
+
+    #[test]
+    fn test_log_is_rotated_and_read_back_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = StateDir::project(dir.path());
+        assert!(read(&state).unwrap().is_empty());
+
+        let first = Entry::new(
+            "add numpy",
+            vec![change("numpy", None, Some("2.0.0"))],
+            None,
+        );
+        append(&state, &first).unwrap();
+        // Fill the log past the limit so the next entry starts a new file
+        let filler = vec![b' '; MAX_LOG_BYTES as usize];
+        state.append(HISTORY_FILE, &filler).unwrap();
+        state.append(HISTORY_FILE, b"not json\n").unwrap();
+        let newer =
+            r#"{"version":99,"timestamp":"","command":"","changes":[],"outcome":"success"}"#;
+        state
+            .append(HISTORY_FILE, format!("{}\n", newer).as_bytes())
+            .unwrap();
+        let second = Entry::new("rm numpy", vec![change("numpy", Some("2.0.0"), None)], None);
+        append(&state, &second).unwrap();
+
+        assert!(state.subpath(ROTATED_FILE).is_file());
+        assert!(crate::state::dir_size(&state.subpath(HISTORY_FILE)) < 1024);
+        assert_eq!(read(&state).unwrap(), vec![first, second]);
+    }
+}
@@ -0,0 +1,287 @@
+//! `[hooks]`: git hooks that run ppm commands, installed by
+//! `ppm hooks install`.
+//!
+//! ```toml
+//! [hooks]
+//! pre-commit = "check --skip venv"
+//! pre-push = "test"
+//! ```
+//!
+//! A value naming one of the project's `[scripts]` runs that script with
+//! `ppm run`; anything else is a ppm command line. Without the table only
+//! a pre-commit hook running [`DEFAULT_COMMAND`] is installed.
+//!
+//! Hooks are `sh` scripts, which git also runs through its bundled `sh`
+//! on Windows. Each carries [`MARKER`], so ppm only ever replaces or
+//! removes hooks it wrote.
+
+use crate::error::PpmError;
+use crate::settings::Config;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Comment identifying a hook ppm wrote
+pub const MARKER: &str = "# Installed by `ppm hooks install`";
+
+/// What a hook without a `[hooks]` entry runs
+pub const DEFAULT_COMMAND: &str = "check --skip venv";
+
+/// The hook installed when `[hooks]` is empty
+pub const DEFAULT_HOOK: &str = "pre-commit";
+
+/// Client-side hooks git runs without feeding them input ppm would need
+pub const GIT_HOOKS: &[&str] = &[
+    "pre-commit",
+    "pre-merge-commit",
+    "pre-push",
+    "pre-rebase",
+    "post-checkout",
+    "post-commit",
+    "post-merge",
+];
+
+/// A hook to install: its name and the ppm command line it runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hook {
+    /// Hook name, like `pre-commit`
+    pub name: String,
+    /// Arguments for ppm, like `check --skip venv` or `run lint`
+    pub command: String,
+}
+
+/// The hooks `ppm hooks install` writes: those named in `only`, else
+/// every hook in `[hooks]`, else [`DEFAULT_HOOK`]. Fails on a name git
+/// doesn't run as a client-side hook.
+pub fn planned(config: &Config, only: &[String]) -> Result<Vec<Hook>, PpmError> {
+    let names: Vec<String> = if !only.is_empty() {
+        only.to_vec()
+    } else if !config.hooks.is_empty() {
+        config.hooks.keys().cloned().collect()
+    } else {
+        vec![DEFAULT_HOOK.to_string()]
+    };
+    names
+        .into_iter()
+        .map(|name| {
+            if !GIT_HOOKS.contains(&name.as_str()) {
+                return Err(PpmError::Config(format!(
+                    "'{}' isn't a git hook ppm can install; use one of {}",
+                    name,
+                    GIT_HOOKS.join(", ")
+                )));
+            }
+            let value = config
+                .hooks
+                .get(&name)
+                .map(String::as_str)
+                .unwrap_or(DEFAULT_COMMAND)
+                .trim();
+            let command = if config.scripts.contains_key(value) {
+                format!("run {}", value)
+            } else {
+                value.to_string()
+            };
+            Ok(Hook { name, command })
+        })
+        .collect()
+}
+
+/// The hook script: `cd` into the project, `prefix` from the repository's
+/// top level (empty when they are the same), and run `ppmm` with the
+/// hook's command
+pub fn script(hook: &Hook, prefix: &str) -> String {
+    let mut script = format!("#!/bin/sh\n{}; `ppm hooks uninstall` removes it.\n", MARKER);
+    let prefix = prefix.trim_end_matches('/');
+    if !prefix.is_empty() {
+        script.push_str(&format!(
+            "cd '{}' || exit 1\n",
+            prefix.replace('\'', "'\\''")
+        ));
+    }
+    script.push_str(&format!("exec ppmm {}\n", hook.command));
+    script
+}
+
+/// Whether `contents` is a hook ppm wrote
+pub fn is_ppm_hook(contents: &str) -> bool {
+    contents.contains(MARKER)
+}
+
+/// The repository's hooks directory, honouring `core.hooksPath`, and the
+/// project's path from the top level, for the project at `root`
+pub fn locate(root: &Path) -> Result<(PathBuf, String), PpmError> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--git-path", "hooks", "--show-prefix"])
+        .current_dir(root)
+        .output()
+        .map_err(|e| PpmError::Subprocess(format!("Failed to run git: {}", e)))?;
+    if !output.status.success() {
+        return Err(PpmError::Other(format!(
+            "{} isn't in a git repository; run `git init` first",
+            root.display()
+        )));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut lines = stdout.lines();
+    let hooks_dir = root.join(lines.next().unwrap_or_default().trim());
+    let prefix = lines.next().unwrap_or_default().trim().to_string();
+    Ok((hooks_dir, prefix))
+}
+
+/// Write `contents` as the hook `name` in `hooks_dir`, executable. An
+/// existing hook ppm didn't write is only replaced with `force`. Returns
+/// whether a ppm hook was already there.
+pub fn install(
+    hooks_dir: &Path,
+    name: &str,
+    contents: &str,
+    force: bool,
+) -> Result<bool, PpmError> {
+    let path = hooks_dir.join(name);
+    let existing = fs::read_to_string(&path).ok();
+    let replaced = existing.as_deref().is_some_and(is_ppm_hook);
+    if existing.is_some() && !replaced && !force {
+        return Err(PpmError::Other(format!(
+            "{} exists and wasn't installed by ppm; pass --force to replace it",
+            path.display()
+        )));
+    }
+    fs::create_dir_all(hooks_dir)?;
+    fs::write(&path, contents)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(replaced)
+}
+
+/// Remove the hooks in `hooks_dir` that ppm wrote, returning their names
+/// sorted; other hooks are left alone
+pub fn uninstall(hooks_dir: &Path) -> Result<Vec<String>, PpmError> {
+    let Ok(entries) = fs::read_dir(hooks_dir) else {
+        return Ok(vec![]);
+    };
+    let mut removed = vec![];
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if fs::read_to_string(&path).is_ok_and(|contents| is_ppm_hook(&contents)) {
+            fs::remove_file(&path)?;
+            removed.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+    removed.sort();
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(hooks: &str) -> Config {
+        toml::from_str(&format!(
+            r#"
+[project]
+name = "demo"
+version = "0.1.0"
+description = ""
+main_script = "./main.py"
+
+[packages]
+
+[scripts]
+lint = "ruff check ."
+
+[hooks]
+{}
+"#,
+            hooks
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_planned_hooks() {
+        assert_eq!(
+            planned(&config(""), &[]).unwrap(),
+            [Hook {
+                name: "pre-commit".to_string(),
+                command: "check --skip venv".to_string(),
+            }]
+        );
+        let config = config("pre-commit = \"lint\"\npre-push = \"test -- -x\"");
+        assert_eq!(
+            planned(&config, &[]).unwrap(),
+            [
+                Hook {
+                    name: "pre-commit".to_string(),
+                    command: "run lint".to_string(),
+                },
+                Hook {
+                    name: "pre-push".to_string(),
+                    command: "test -- -x".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            planned(&config, &["post-merge".to_string()]).unwrap()[0].command,
+            DEFAULT_COMMAND
+        );
+        assert!(matches!(
+            planned(&config, &["commit-msg".to_string()]),
+            Err(PpmError::Config(_))
+        ));
+    }
+
+    #[test]
+    fn test_script_changes_into_the_project() {
+        let hook = Hook {
+            name: "pre-commit".to_string(),
+            command: DEFAULT_COMMAND.to_string(),
+        };
+        let script = script(&hook, "");
+        assert!(script.starts_with("#!/bin/sh\n"));
+        assert!(is_ppm_hook(&script));
+        assert!(script.ends_with("\nexec ppmm check --skip venv\n"));
+        assert!(!script.contains("cd "));
+        assert!(super::script(&hook, "apps/it's/").contains("\ncd 'apps/it'\\''s' || exit 1\n"));
+    }
+
+    #[test]
+    fn test_install_refuses_foreign_hooks() {
+        let dir = tempfile::tempdir().unwrap();
+        let hooks_dir = dir.path().join("hooks");
+        assert!(
+            !install(
+                &hooks_dir,
+                "pre-commit",
+                "#!/bin/sh\n# Installed by `ppm hooks install`\n",
+                false
+            )
+            .unwrap()
+        );
+        assert!(
+            install(
+                &hooks_dir,
+                "pre-commit",
+                "#!/bin/sh\n# Installed by `ppm hooks install`\n",
+                false
+            )
+            .unwrap()
+        );
+
+        fs::write(hooks_dir.join("pre-push"), "#!/bin/sh\nmake test\n").unwrap();
+        let err = install(&hooks_dir, "pre-push", MARKER, false).unwrap_err();
+        assert!(err.to_string().contains("pass --force to replace it"));
+        assert_eq!(
+            fs::read_to_string(hooks_dir.join("pre-push")).unwrap(),
+            "#!/bin/sh\nmake test\n"
+        );
+
+        fs::write(hooks_dir.join("post-merge"), "#!/bin/sh\n").unwrap();
+        assert_eq!(uninstall(&hooks_dir).unwrap(), ["pre-commit"]);
+        assert!(hooks_dir.join("pre-push").exists());
+        assert!(hooks_dir.join("post-merge").exists());
+    }
+}
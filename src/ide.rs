@@ -0,0 +1,528 @@
+//! `ppm ide`: point editors at the project's venv.
+//!
+//! VS Code reads `.vscode/settings.json`, which is JSON with comments and
+//! trailing commas allowed. Settings are merged into the file as text: a
+//! key ppm manages has its value replaced in place, a missing one is added
+//! after the last member, and everything else, comments included, is left
+//! exactly as it was.
+
+use serde_json::Value;
+use std::ops::Range;
+
+/// The VS Code workspace settings file, relative to the project root
+pub const VSCODE_SETTINGS_FILE: &str = ".vscode/settings.json";
+
+/// Indentation used when the file doesn't show its own
+const DEFAULT_INDENT: &str = "    ";
+
+/// A setting to merge into a settings file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Setting {
+    /// Top-level key, like `python.defaultInterpreterPath`
+    pub key: String,
+    /// The value ppm wants
+    pub value: Value,
+    /// Whether to replace a value the user already set; otherwise the
+    /// setting is only added when missing
+    pub overwrite: bool,
+}
+
+impl Setting {
+    /// A setting ppm owns and keeps up to date
+    pub fn managed(key: &str, value: Value) -> Setting {
+        Setting {
+            key: key.to_string(),
+            value,
+            overwrite: true,
+        }
+    }
+
+    /// A suggestion that never replaces the user's own choice
+    pub fn suggested(key: &str, value: Value) -> Setting {
+        Setting {
+            key: key.to_string(),
+            value,
+            overwrite: false,
+        }
+    }
+}
+
+/// A setting the merge added or changed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    /// Top-level key
+    pub key: String,
+    /// The value before, `None` when the key was added
+    pub from: Option<Value>,
+    /// The value after
+    pub to: Value,
+}
+
+/// VS Code settings for a project: the venv interpreter, and the test
+/// framework and formatter its `[scripts]` use when they can be told
+pub fn vscode_settings(
+    interpreter: &str,
+    scripts: &[(&str, &str)],
+    detected_framework: Option<crate::test_runner::Framework>,
+) -> Vec<Setting> {
+    use crate::test_runner::Framework;
+
+    let mut settings = vec![Setting::managed(
+        "python.defaultInterpreterPath",
+        Value::from(interpreter),
+    )];
+    let test_script = scripts
+        .iter()
+        .find(|(name, _)| *name == "test")
+        .map(|(_, command)| *command);
+    let framework = match test_script {
+        Some(command) if command.contains("pytest") => Some(Framework::Pytest),
+        Some(command) if command.contains("unittest") => Some(Framework::Unittest),
+        Some(_) => None,
+        None => detected_framework,
+    };
+    if let Some(framework) = framework {
+        let pytest = framework == Framework::Pytest;
+        settings.push(Setting::suggested(
+            "python.testing.pytestEnabled",
+            Value::from(pytest),
+        ));
+        settings.push(Setting::suggested(
+            "python.testing.unittestEnabled",
+            Value::from(!pytest),
+        ));
+    }
+    let formatter = scripts.iter().find_map(|(_, command)| {
+        let words: Vec<&str> = command.split_whitespace().collect();
+        if words.contains(&"black") {
+            Some("ms-python.black-formatter")
+        } else if words.windows(2).any(|pair| pair == ["ruff", "format"]) {
+            Some("charliermarsh.ruff")
+        } else if words.contains(&"autopep8") {
+            Some("ms-python.autopep8")
+        } else {
+            None
+        }
+    });
+    if let Some(formatter) = formatter {
+        settings.push(Setting::suggested(
+            "[python]",
+            serde_json::json!({ "editor.defaultFormatter": formatter }),
+        ));
+    }
+    settings
+}
+
+/// `text` without comments and trailing commas, as plain JSON. Byte
+/// offsets are kept: everything removed becomes spaces.
+pub fn strip_jsonc(text: &str) -> String {
+    let mut out = strip_comments(text).into_bytes();
+    let mut last_comma: Option<usize> = None;
+    let mut i = 0;
+    while i < out.len() {
+        match out[i] {
+            b'"' => {
+                last_comma = None;
+                i = string_end(&out, i).unwrap_or(out.len());
+                continue;
+            }
+            b',' => last_comma = Some(i),
+            b'}' | b']' => {
+                if let Some(comma) = last_comma.take() {
+                    out[comma] = b' ';
+                }
+            }
+            byte if byte.is_ascii_whitespace() => {}
+            _ => last_comma = None,
+        }
+        i += 1;
+    }
+    // Only ASCII bytes outside strings were replaced
+    String::from_utf8(out).unwrap_or_default()
+}
+
+/// `text` with its comments blanked out
+fn strip_comments(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = bytes.to_vec();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => i = string_end(bytes, i).unwrap_or(bytes.len()),
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    out[i] = b' ';
+                    i += 1;
+                }
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                let end = text[i + 2..]
+                    .find("*/")
+                    .map_or(bytes.len(), |at| i + 2 + at + 2);
+                for byte in &mut out[i..end] {
+                    if *byte != b'\n' {
+                        *byte = b' ';
+                    }
+                }
+                i = end;
+            }
+            _ => i += 1,
+        }
+    }
+    // Whole characters were blanked, so the text is still UTF-8
+    String::from_utf8(out).unwrap_or_default()
+}
+
+/// Parse JSON with comments and trailing commas
+pub fn parse_jsonc(text: &str) -> Result<Value, serde_json::Error> {
+    serde_json::from_str(&strip_jsonc(text))
+}
+
+/// Index just past the string starting at `start`
+fn string_end(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'"' => return Some(i + 1),
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// A top-level member of the settings object
+struct Member {
+    key: String,
+    /// Where the value is in the original text
+    value: Range<usize>,
+}
+
+/// The top-level object's members and the position of its closing brace,
+/// found in `plain` (the text with its comments blanked out)
+fn scan_object(plain: &str) -> Result<(Vec<Member>, usize), String> {
+    let bytes = plain.as_bytes();
+    let skip_ws = |mut i: usize| {
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        i
+    };
+    let mut i = skip_ws(0);
+    if bytes.get(i) != Some(&b'{') {
+        return Err("the settings are not a JSON object".to_string());
+    }
+    i += 1;
+    let mut members = vec![];
+    loop {
+        i = skip_ws(i);
+        match bytes.get(i) {
+            Some(b'}') => return Ok((members, i)),
+            Some(b',') => i += 1,
+            Some(b'"') => {
+                let key_end = string_end(bytes, i).ok_or("unterminated string")?;
+                let key: String = serde_json::from_str(&plain[i..key_end])
+                    .map_err(|e| format!("invalid key: {}", e))?;
+                i = skip_ws(key_end);
+                if bytes.get(i) != Some(&b':') {
+                    return Err(format!("expected ':' after \"{}\"", key));
+                }
+                let start = skip_ws(i + 1);
+                let end = value_end(bytes, start).ok_or("unterminated value")?;
+                members.push(Member {
+                    key,
+                    value: start..end,
+                });
+                i = end;
+            }
+            _ => return Err(format!("unexpected character at byte {}", i)),
+        }
+    }
+}
+
+/// Index just past the value starting at `start`
+fn value_end(bytes: &[u8], start: usize) -> Option<usize> {
+    match bytes.get(start)? {
+        b'"' => string_end(bytes, start),
+        b'{' | b'[' => {
+            let mut depth = 0;
+            let mut i = start;
+            while i < bytes.len() {
+                match bytes[i] {
+                    b'"' => {
+                        i = string_end(bytes, i)?;
+                        continue;
+                    }
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Some(i + 1);
+                        }
+                    }
+                    _ => {}
+                }
+                i += 1;
+            }
+            None
+        }
+        _ => {
+            let len = bytes[start..]
+                .iter()
+                .position(|byte| matches!(byte, b',' | b'}' | b']') || byte.is_ascii_whitespace())
+                .unwrap_or(bytes.len() - start);
+            Some(start + len)
+        }
+    }
+}
+
+/// `value` as it is written at `indent`, pretty-printed like the rest of
+/// the file
+fn render(value: &Value, indent: &str) -> String {
+    let pretty = serde_json::to_string_pretty(value).unwrap_or_default();
+    let mut lines = pretty.lines();
+    let mut out = lines.next().unwrap_or_default().to_string();
+    for line in lines {
+        let depth = line.len() - line.trim_start().len();
+        out.push('\n');
+        out.push_str(indent);
+        out.push_str(&indent.repeat(depth / 2));
+        out.push_str(line.trim_start());
+    }
+    out
+}
+
+/// Merge `settings` into the settings file `text` (empty for a missing
+/// file), returning the new text and what changed
+pub fn merge_settings(text: &str, settings: &[Setting]) -> Result<(String, Vec<Change>), String> {
+    let text = if text.trim().is_empty() { "{}\n" } else { text };
+    let current = parse_jsonc(text).map_err(|e| e.to_string())?;
+    let plain = strip_comments(text);
+    let (members, close) = scan_object(&plain)?;
+    let indent = members
+        .first()
+        .and_then(|member| {
+            let line_start = plain[..member.value.start].rfind('\n')? + 1;
+            let line = &plain[line_start..];
+            Some(line[..line.len() - line.trim_start().len()].to_string())
+        })
+        .filter(|indent| !indent.is_empty())
+        .unwrap_or_else(|| DEFAULT_INDENT.to_string());
+
+    let mut changes = vec![];
+    // Edits as (range, replacement), applied back to front
+    let mut edits: Vec<(Range<usize>, String)> = vec![];
+    let mut added = String::new();
+    for setting in settings {
+        let existing = current.get(&setting.key);
+        if existing == Some(&setting.value) || (existing.is_some() && !setting.overwrite) {
+            continue;
+        }
+        changes.push(Change {
+            key: setting.key.clone(),
+            from: existing.cloned(),
+            to: setting.value.clone(),
+        });
+        // The last occurrence wins, as it does for VS Code
+        match members
+            .iter()
+            .rev()
+            .find(|member| member.key == setting.key)
+        {
+            Some(member) => edits.push((member.value.clone(), render(&setting.value, &indent))),
+            None => {
+                if !added.is_empty() {
+                    added.push(',');
+                }
+                added.push_str(&format!(
+                    "\n{}{}: {}",
+                    indent,
+                    Value::from(setting.key.as_str()),
+                    render(&setting.value, &indent)
+                ));
+            }
+        }
+    }
+
+    if !added.is_empty() {
+        match members.last() {
+            Some(last) => {
+                // After the last value and its trailing comma, if any
+                let after = &plain[last.value.end..close];
+                let at = match after.trim_start().strip_prefix(',') {
+                    Some(_) => last.value.end + after.find(',').unwrap_or(0) + 1,
+                    None => {
+                        added.insert(0, ',');
+                        last.value.end
+                    }
+                };
+                edits.push((at..at, added));
+            }
+            None => {
+                let open = plain.find('{').unwrap_or(0);
+                added.push('\n');
+                edits.push((open + 1..close, added));
+            }
+        }
+    }
+
+    let mut merged = text.to_string();
+    edits.sort_by_key(|(range, _)| std::cmp::Reverse(range.start));
+    for (range, replacement) in edits {
+        merged.replace_range(range, &replacement);
+    }
+    if !merged.ends_with('\n') {
+        merged.push('\n');
+    }
+    Ok((merged, changes))
+}
+
+/// An `<jdk>` entry for PyCharm's `options/jdk.table.xml` registering the
+/// interpreter at `python` under `name`
+pub fn pycharm_jdk_entry(name: &str, python: &str, version: Option<&str>) -> String {
+    let escape = |text: &str| {
+        text.replace('&', "&amp;")
+            .replace('"', "&quot;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    };
+    format!(
+        "<jdk version=\"2\">\n  <name value=\"{}\" />\n  <type value=\"Python SDK\" />\n  <version value=\"Python {}\" />\n  <homePath value=\"{}\" />\n</jdk>",
+        escape(name),
+        escape(version.unwrap_or("3")),
+        escape(python)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const INTERPRETER: &str = "python.defaultInterpreterPath";
+
+    #[test]
+    fn test_parse_jsonc() {
+        let text = r#"{
+    // line comment with "quotes"
+    "editor.fontSize": 14, /* block
+    comment */
+    "url": "http://example.com/*not a comment*/",
+    "list": [1, 2,],
+}"#;
+        assert_eq!(
+            parse_jsonc(text).unwrap(),
+            json!({
+                "editor.fontSize": 14,
+                "url": "http://example.com/*not a comment*/",
+                "list": [1, 2],
+            })
+        );
+        assert_eq!(strip_jsonc(text).len(), text.len());
+    }
+
+    #[test]
+    fn test_merge_keeps_comments_and_other_settings() {
+        let text = r#"{
+  // Team settings, keep
+  "editor.rulers": [88],
+  "python.defaultInterpreterPath": "/old/venv/bin/python", // stale
+  "python.testing.pytestEnabled": false,
+  /* trailing notes */
+}
+"#;
+        let settings = vec![
+            Setting::managed(INTERPRETER, json!("/work/demo/venv/bin/python")),
+            Setting::suggested("python.testing.pytestEnabled", json!(true)),
+            Setting::suggested(
+                "[python]",
+                json!({"editor.defaultFormatter": "ms-python.black-formatter"}),
+            ),
+        ];
+        let (merged, changes) = merge_settings(text, &settings).unwrap();
+        assert_eq!(
+            merged,
+            r#"{
+  // Team settings, keep
+  "editor.rulers": [88],
+  "python.defaultInterpreterPath": "/work/demo/venv/bin/python", // stale
+  "python.testing.pytestEnabled": false,
+  "[python]": {
+    "editor.defaultFormatter": "ms-python.black-formatter"
+  }
+  /* trailing notes */
+}
+"#
+        );
+        assert_eq!(
+            changes,
+            vec![
+                Change {
+                    key: INTERPRETER.to_string(),
+                    from: Some(json!("/old/venv/bin/python")),
+                    to: json!("/work/demo/venv/bin/python"),
+                },
+                Change {
+                    key: "[python]".to_string(),
+                    from: None,
+                    to: json!({"editor.defaultFormatter": "ms-python.black-formatter"}),
+                },
+            ]
+        );
+
+        // Merging again changes nothing
+        let (again, changes) = merge_settings(&merged, &settings).unwrap();
+        assert_eq!(again, merged);
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn test_merge_into_empty_and_compact_files() {
+        let settings = vec![Setting::managed(INTERPRETER, json!("/p/venv/bin/python"))];
+        let expected = "{\n    \"python.defaultInterpreterPath\": \"/p/venv/bin/python\"\n}\n";
+        assert_eq!(merge_settings("", &settings).unwrap().0, expected);
+        assert_eq!(merge_settings("{}", &settings).unwrap().0, expected);
+        assert_eq!(
+            merge_settings("{\"a\": 1}", &settings).unwrap().0,
+            "{\"a\": 1,\n    \"python.defaultInterpreterPath\": \"/p/venv/bin/python\"}\n"
+        );
+        assert!(merge_settings("[1, 2]", &settings).is_err());
+        assert!(merge_settings("{\"a\": }", &settings).is_err());
+    }
+
+    #[test]
+    fn test_vscode_settings_follow_scripts() {
+        use crate::test_runner::Framework;
+
+        let settings = vscode_settings(
+            "/p/venv/bin/python",
+            &[("test", "python -m unittest"), ("fmt", "black .")],
+            Some(Framework::Pytest),
+        );
+        let keys: Vec<(&str, &Value)> = settings
+            .iter()
+            .map(|setting| (setting.key.as_str(), &setting.value))
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                (INTERPRETER, &json!("/p/venv/bin/python")),
+                ("python.testing.pytestEnabled", &json!(false)),
+                ("python.testing.unittestEnabled", &json!(true)),
+                (
+                    "[python]",
+                    &json!({"editor.defaultFormatter": "ms-python.black-formatter"})
+                ),
+            ]
+        );
+        assert_eq!(vscode_settings("/p/python", &[], None).len(), 1);
+    }
+
+    #[test]
+    fn test_pycharm_jdk_entry() {
+        let entry = pycharm_jdk_entry("Python 3.12 (a&b)", "/p/venv/bin/python", Some("3.12.1"));
+        assert!(entry.contains("<name value=\"Python 3.12 (a&amp;b)\" />"));
+        assert!(entry.contains("<homePath value=\"/p/venv/bin/python\" />"));
+        assert!(entry.contains("<version value=\"Python 3.12.1\" />"));
+    }
+}
@@ -0,0 +1,520 @@
+//! `ppm check imports`: compare what the code imports with what
+//! project.toml declares.
+//!
+//! The scan is static: `import x` and `from x import y` statements in the
+//! project's `.py` files are read as text, nothing is executed. Import
+//! names are mapped to distributions with the venv's installed metadata
+//! (`top_level.txt`, or `RECORD` when a wheel has none) and then a table
+//! of well-known names that differ from their distribution (`cv2` is
+//! opencv-python).
+
+use crate::context::ProjectContext;
+use crate::error::PpmError;
+use crate::packages::normalize_pkg_name;
+use crate::paths::{normalize, site_packages_dirs};
+use crate::settings::Config;
+use crate::stdlib::{PythonVersion, is_stdlib, parse_python_version};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Python version assumed for the standard-library list without a venv
+pub const DEFAULT_PYTHON: PythonVersion = (3, 12);
+
+/// Import names whose distribution is published under another name
+const ALIASES: &[(&str, &str)] = &[
+    ("Crypto", "pycryptodome"),
+    ("MySQLdb", "mysqlclient"),
+    ("OpenSSL", "pyOpenSSL"),
+    ("PIL", "Pillow"),
+    ("Xlib", "python-xlib"),
+    ("attr", "attrs"),
+    ("bs4", "beautifulsoup4"),
+    ("cv2", "opencv-python"),
+    ("dateutil", "python-dateutil"),
+    ("docx", "python-docx"),
+    ("dotenv", "python-dotenv"),
+    ("fitz", "PyMuPDF"),
+    ("gi", "PyGObject"),
+    ("git", "GitPython"),
+    ("jose", "python-jose"),
+    ("jwt", "PyJWT"),
+    ("kafka", "kafka-python"),
+    ("ldap", "python-ldap"),
+    ("magic", "python-magic"),
+    ("markdown", "Markdown"),
+    ("mpl_toolkits", "matplotlib"),
+    ("multipart", "python-multipart"),
+    ("nacl", "PyNaCl"),
+    ("pkg_resources", "setuptools"),
+    ("pptx", "python-pptx"),
+    ("pythoncom", "pywin32"),
+    ("serial", "pyserial"),
+    ("skimage", "scikit-image"),
+    ("sklearn", "scikit-learn"),
+    ("slugify", "python-slugify"),
+    ("socks", "PySocks"),
+    ("telegram", "python-telegram-bot"),
+    ("usb", "pyusb"),
+    ("websocket", "websocket-client"),
+    ("win32api", "pywin32"),
+    ("win32con", "pywin32"),
+    ("wx", "wxPython"),
+    ("yaml", "PyYAML"),
+    ("zmq", "pyzmq"),
+];
+
+/// An import with no matching entry in `[packages]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingImport {
+    /// Top-level module name as imported
+    pub module: String,
+    /// Distribution that most likely provides it, for `ppm add`
+    pub package: String,
+    /// Files importing it, relative to the project root
+    pub files: Vec<PathBuf>,
+}
+
+/// Result of comparing imports with `[packages]`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    /// Imported but not configured, sorted by module name
+    pub missing: Vec<MissingImport>,
+    /// Configured keys that no scanned file imports, sorted
+    pub unused: Vec<String>,
+}
+
+/// Top-level module names imported by `source`, in order of first use.
+/// Relative imports (`from . import x`) are skipped.
+pub fn parse_imports(source: &str) -> Vec<String> {
+    let mut modules: Vec<String> = vec![];
+    let mut in_string: Option<&str> = None;
+    let mut pending = String::new();
+
+    for raw in source.lines() {
+        let mut line = raw;
+        if let Some(delim) = in_string {
+            match line.find(delim) {
+                Some(end) => {
+                    in_string = None;
+                    line = &line[end + delim.len()..];
+                }
+                None => continue,
+            }
+        }
+        for delim in ["\"\"\"", "'''"] {
+            if line.matches(delim).count() % 2 == 1 {
+                in_string = Some(delim);
+            }
+        }
+
+        // Join backslash continuations into one logical line
+        if let Some(continued) = line.strip_suffix('\\') {
+            pending.push_str(continued);
+            pending.push(' ');
+            continue;
+        }
+        pending.push_str(line);
+        let logical = std::mem::take(&mut pending);
+        let code = logical.split('#').next().unwrap_or_default();
+
+        for statement in code.split(';') {
+            for module in statement_imports(statement.trim()) {
+                if !modules.contains(&module) {
+                    modules.push(module);
+                }
+            }
+        }
+    }
+    modules
+}
+
+fn statement_imports(statement: &str) -> Vec<String> {
+    let top_level = |dotted: &str| {
+        dotted
+            .trim()
+            .split('.')
+            .next()
+            .unwrap_or_default()
+            .to_string()
+    };
+    let valid = |name: &String| {
+        name.chars()
+            .next()
+            .is_some_and(|c| c.is_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+    };
+
+    if let Some(rest) = statement.strip_prefix("import ") {
+        rest.split(',')
+            .map(|part| top_level(part.split(" as ").next().unwrap_or_default()))
+            .filter(valid)
+            .collect()
+    } else if let Some(rest) = statement.strip_prefix("from ") {
+        let module = rest.split_whitespace().next().unwrap_or_default();
+        if module.starts_with('.') || !rest.contains(" import") {
+            return vec![];
+        }
+        Some(top_level(module)).filter(valid).into_iter().collect()
+    } else {
+        vec![]
+    }
+}
+
+/// `.py` files under `root`, sorted. Hidden directories, `__pycache__`
+/// and virtual environments (`venv_dir` and any directory with a
+/// `pyvenv.cfg`) are skipped.
+pub fn find_sources(root: &Path, venv_dir: &Path) -> Vec<PathBuf> {
+    let venv_dir = normalize(venv_dir);
+    let mut sources = vec![];
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                let skip = name.starts_with('.')
+                    || name == "__pycache__"
+                    || normalize(&path) == venv_dir
+                    || path.join("pyvenv.cfg").exists();
+                if !skip {
+                    dirs.push(path);
+                }
+            } else if file_type.is_file() && name.ends_with(".py") {
+                sources.push(path);
+            }
+        }
+    }
+    sources.sort();
+    sources
+}
+
+/// Module names the project provides itself: every source file's stem
+/// and every directory containing sources, relative to `root`
+pub fn local_modules(root: &Path, sources: &[PathBuf]) -> HashSet<String> {
+    let mut local = HashSet::new();
+    for source in sources {
+        let relative = source.strip_prefix(root).unwrap_or(source);
+        if let Some(stem) = relative.file_stem() {
+            local.insert(stem.to_string_lossy().into_owned());
+        }
+        for dir in relative.parent().into_iter().flat_map(Path::components) {
+            local.insert(dir.as_os_str().to_string_lossy().into_owned());
+        }
+    }
+    local
+}
+
+/// Top-level module name to distribution name for everything installed
+/// in the venv
+pub fn installed_modules(venv_dir: &Path) -> HashMap<String, String> {
+    let mut modules = HashMap::new();
+    for site_packages in site_packages_dirs(venv_dir) {
+        let Ok(entries) = fs::read_dir(&site_packages) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let dir = entry.path();
+            let dir_name = entry.file_name().to_string_lossy().into_owned();
+            let Some(stem) = dir_name
+                .strip_suffix(".dist-info")
+                .or_else(|| dir_name.strip_suffix(".egg-info"))
+            else {
+                continue;
+            };
+            let dist = fs::read_to_string(dir.join("METADATA"))
+                .or_else(|_| fs::read_to_string(dir.join("PKG-INFO")))
+                .ok()
+                .and_then(|metadata| {
+                    metadata
+                        .lines()
+                        .find_map(|line| line.strip_prefix("Name:").map(|n| n.trim().to_string()))
+                })
+                .unwrap_or_else(|| stem.split('-').next().unwrap_or(stem).to_string());
+
+            let provided: Vec<String> = match fs::read_to_string(dir.join("top_level.txt")) {
+                Ok(top_level) => top_level
+                    .lines()
+                    .map(|line| line.trim().to_string())
+                    .filter(|line| !line.is_empty())
+                    .collect(),
+                Err(_) => fs::read_to_string(dir.join("RECORD"))
+                    .map(|record| record_modules(&record))
+                    .unwrap_or_default(),
+            };
+            for module in provided {
+                modules.entry(module).or_insert_with(|| dist.clone());
+            }
+        }
+    }
+    modules
+}
+
+/// Top-level modules listed in a dist-info `RECORD`
+fn record_modules(record: &str) -> Vec<String> {
+    let mut modules: Vec<String> = vec![];
+    for line in record.lines() {
+        let path = line.split(',').next().unwrap_or_default();
+        let first = path.split('/').next().unwrap_or_default();
+        let module = if path.contains('/') {
+            first
+        } else {
+            match first.strip_suffix(".py") {
+                Some(module) => module,
+                None => continue,
+            }
+        };
+        let skip = module.is_empty()
+            || module.contains('.')
+            || module == "__pycache__"
+            || module == ".."
+            || module.ends_with(".dist-info")
+            || module.ends_with(".data");
+        if !skip && !modules.iter().any(|m| m == module) {
+            modules.push(module.to_string());
+        }
+    }
+    modules
+}
+
+/// The distribution most likely providing `module`
+fn distribution_for(module: &str, installed: &HashMap<String, String>) -> String {
+    installed
+        .get(module)
+        .map(String::as_str)
+        .or_else(|| {
+            ALIASES
+                .iter()
+                .find(|(name, _)| *name == module)
+                .map(|(_, dist)| *dist)
+        })
+        .unwrap_or(module)
+        .to_string()
+}
+
+/// Compare `imports` (module name to the files importing it) with the
+/// configured packages. Standard-library modules for `python` and the
+/// project's `local` modules are ignored.
+pub fn analyze(
+    imports: &BTreeMap<String, Vec<PathBuf>>,
+    config: &Config,
+    installed: &HashMap<String, String>,
+    local: &HashSet<String>,
+    python: PythonVersion,
+) -> ImportReport {
+    let configured: HashSet<String> = config
+        .packages
+        .iter()
+        .filter(|(_, spec)| !spec.is_self())
+        .map(|(name, _)| normalize_pkg_name(name))
+        .collect();
+
+    let mut used = HashSet::new();
+    let mut missing = vec![];
+    for (module, files) in imports {
+        if is_stdlib(module, python) || local.contains(module) {
+            continue;
+        }
+        let package = distribution_for(module, installed);
+        let found = [normalize_pkg_name(&package), normalize_pkg_name(module)]
+            .into_iter()
+            .find(|name| configured.contains(name));
+        match found {
+            Some(name) => {
+                used.insert(name);
+            }
+            None => missing.push(MissingImport {
+                module: module.clone(),
+                package,
+                files: files.clone(),
+            }),
+        }
+    }
+
+    let mut unused: Vec<String> = config
+        .packages
+        .iter()
+        .filter(|(name, spec)| !spec.is_self() && !used.contains(&normalize_pkg_name(name)))
+        .map(|(name, _)| name.clone())
+        .collect();
+    unused.sort();
+    ImportReport { missing, unused }
+}
+
+/// Scan the project's sources and compare their imports with
+/// `[packages]`, using the venv for installed metadata and the Python
+/// version when it exists
+pub fn check_imports(ctx: &ProjectContext) -> Result<ImportReport, PpmError> {
+    let venv_dir = ctx.venv_dir();
+    let sources = find_sources(&ctx.root, &venv_dir);
+
+    let mut imports: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    for source in &sources {
+        let Ok(text) = fs::read_to_string(source) else {
+            continue;
+        };
+        let relative = source.strip_prefix(&ctx.root).unwrap_or(source);
+        for module in parse_imports(&text) {
+            imports
+                .entry(module)
+                .or_default()
+                .push(relative.to_path_buf());
+        }
+    }
+
+    let mut local = local_modules(&ctx.root, &sources);
+    local.insert(ctx.config.project.name.replace('-', "_"));
+    let python = ctx
+        .python_version()
+        .and_then(parse_python_version)
+        .unwrap_or(DEFAULT_PYTHON);
+    Ok(analyze(
+        &imports,
+        &ctx.config,
+        &installed_modules(&venv_dir),
+        &local,
+        python,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_imports() {
+        let source = r#"
+"""Module docstring mentioning
+import not_a_module
+"""
+import os, sys as system
+import numpy.linalg as la
+from PIL import Image
+from . import sibling
+from .models import User
+from cv2 import (
+    imread,
+)
+import requests; import yaml  # import commented_out
+x = "from nowhere import it"
+import json, \
+    toml
+"#;
+        assert_eq!(
+            parse_imports(source),
+            vec![
+                "os", "sys", "numpy", "PIL", "cv2", "requests", "yaml", "json", "toml"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_analyze() {
+        let config: Config = toml::from_str(
+            r#"
+[project]
+name = "demo"
+version = "0.1.0"
+description = ""
+main_script = "main.py"
+
+[packages]
+Pillow = "10.0.0"
+requests = "2.31.0"
+pytest = "8.0.0"
+
+[scripts]
+"#,
+        )
+        .unwrap();
+        let imports: BTreeMap<String, Vec<PathBuf>> = [
+            ("PIL", "main.py"),
+            ("requests", "main.py"),
+            ("cv2", "video.py"),
+            ("yaml", "main.py"),
+            ("json", "main.py"),
+            ("tomllib", "main.py"),
+            ("helpers", "main.py"),
+        ]
+        .iter()
+        .map(|(module, file)| (module.to_string(), vec![PathBuf::from(file)]))
+        .collect();
+        let installed: HashMap<String, String> =
+            [("yaml".to_string(), "PyYAML".to_string())].into();
+        let local: HashSet<String> = ["helpers".to_string()].into();
+
+        let report = analyze(&imports, &config, &installed, &local, (3, 10));
+        let missing: Vec<(&str, &str)> = report
+            .missing
+            .iter()
+            .map(|m| (m.module.as_str(), m.package.as_str()))
+            .collect();
+        // tomllib isn't in the 3.10 standard library
+        assert_eq!(
+            missing,
+            vec![
+                ("cv2", "opencv-python"),
+                ("tomllib", "tomllib"),
+                ("yaml", "PyYAML")
+            ]
+        );
+        assert_eq!(report.unused, vec!["pytest"]);
+
+        let report = analyze(&imports, &config, &installed, &local, (3, 11));
+        assert_eq!(report.missing.len(), 2);
+    }
+
+    #[test]
+    fn test_sources_and_installed_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("app")).unwrap();
+        fs::write(root.join("main.py"), "import app.util\n").unwrap();
+        fs::write(root.join("app").join("util.py"), "").unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::write(root.join(".git").join("hook.py"), "").unwrap();
+
+        let site = root
+            .join("venv")
+            .join("lib")
+            .join("python3.11")
+            .join("site-packages");
+        fs::create_dir_all(&site).unwrap();
+        fs::write(site.join("six.py"), "").unwrap();
+        let yaml = site.join("PyYAML-6.0.1.dist-info");
+        fs::create_dir_all(&yaml).unwrap();
+        fs::write(
+            yaml.join("METADATA"),
+            "Metadata-Version: 2.1\nName: PyYAML\n",
+        )
+        .unwrap();
+        fs::write(yaml.join("top_level.txt"), "_yaml\nyaml\n").unwrap();
+        let attrs = site.join("attrs-23.1.0.dist-info");
+        fs::create_dir_all(&attrs).unwrap();
+        fs::write(
+            attrs.join("RECORD"),
+            "attr/__init__.py,sha256=x,1\nattrs/__init__.py,sha256=y,1\nattrs-23.1.0.dist-info/RECORD,,\n",
+        )
+        .unwrap();
+
+        let sources = find_sources(root, &root.join("venv"));
+        assert_eq!(
+            sources,
+            vec![root.join("app").join("util.py"), root.join("main.py")]
+        );
+        let local = local_modules(root, &sources);
+        assert!(local.contains("app") && local.contains("main") && local.contains("util"));
+
+        let installed = installed_modules(&root.join("venv"));
+        assert_eq!(installed.get("yaml").map(String::as_str), Some("PyYAML"));
+        assert_eq!(installed.get("attr").map(String::as_str), Some("attrs"));
+        assert_eq!(installed.get("attrs").map(String::as_str), Some("attrs"));
+        assert_eq!(installed.get("six"), None);
+    }
+}
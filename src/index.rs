@@ -0,0 +1,721 @@
+//! Lookups against the package index (PyPI JSON API).
+//!
+//! Lookups are async and share one `reqwest::Client`; the CLI reaches them
+//! through the blocking wrappers on [`ProjectContext`]. Transient failures
+//! (connection errors, 429 and 5xx responses) are retried with backoff; a
+//! request that timed out isn't, as it already took all the time it was
+//! given. [`latest_versions`] runs many lookups at once, at most
+//! [`MAX_CONCURRENT_LOOKUPS`] in flight, spaced out by a [`Throttle`].
+//!
+//! [`ProjectContext`]: crate::ProjectContext
+
+use crate::downloads::{self, DistFile};
+use crate::error::PpmError;
+use futures::stream::{self, StreamExt};
+use reqwest::{Client, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Base URL of the PyPI JSON API
+pub const PYPI_API_URL: &str = "https://pypi.org/pypi";
+
+/// Points ppm at another server than pypi.org in PyPI's place, such as a
+/// local test server; its JSON API is at `<url>/pypi`
+pub const PYPI_URL_ENV: &str = "PPM_PYPI_URL";
+
+/// Address of PyPI: `$PPM_PYPI_URL` when set, otherwise
+/// `https://pypi.org`
+pub fn pypi_url() -> String {
+    match std::env::var(PYPI_URL_ENV) {
+        Ok(url) if !url.trim().is_empty() => url.trim().trim_end_matches('/').to_string(),
+        _ => PYPI_API_URL.trim_end_matches("/pypi").to_string(),
+    }
+}
+
+/// Base URL of PyPI's JSON API, see [`pypi_url`]
+pub fn pypi_api_url() -> String {
+    format!("{}/pypi", pypi_url())
+}
+
+/// Lookups [`latest_versions`] keeps in flight at once
+pub const MAX_CONCURRENT_LOOKUPS: usize = 8;
+
+/// Retries after a transient failure, on top of the first attempt
+pub const RETRIES: u32 = 2;
+
+/// Wait before the first retry, doubled for each one after it
+const RETRY_BACKOFF: Duration = Duration::from_millis(250);
+
+/// Spaces out requests so that no more than one starts per `interval`
+#[derive(Debug)]
+pub struct Throttle {
+    interval: Duration,
+    next: Mutex<Option<Instant>>,
+}
+
+impl Throttle {
+    /// At most one request per `interval`; `Duration::ZERO` disables it
+    pub fn new(interval: Duration) -> Throttle {
+        Throttle {
+            interval,
+            next: Mutex::new(None),
+        }
+    }
+
+    /// Wait for this request's turn
+    pub async fn wait(&self) {
+        let start = {
+            let mut next = self.next.lock().unwrap_or_else(|e| e.into_inner());
+            let now = Instant::now();
+            let start = next.map_or(now, |next| next.max(now));
+            *next = Some(start + self.interval);
+            start
+        };
+        tokio::time::sleep_until(start).await;
+    }
+}
+
+/// Base URL of the JSON API next to the simple index at `simple_url`,
+/// `None` when it can't be told from the URL. Warehouse-style indexes
+/// serve `<base>/simple` and `<base>/pypi`.
+pub fn json_api_url(simple_url: &str) -> Option<String> {
+    simple_url
+        .trim_end_matches('/')
+        .strip_suffix("/simple")
+        .map(|base| format!("{}/pypi", base))
+}
+
+/// GET `url`, retrying transient failures but not timeouts, so a hung
+/// server costs one request timeout rather than one per try. The last
+/// response or error is returned when the retries run out.
+async fn get(client: &Client, url: &str) -> Result<Response, reqwest::Error> {
+    let mut attempt = 0;
+    loop {
+        let result = client.get(url).send().await;
+        let transient = match &result {
+            Ok(resp) => {
+                resp.status() == StatusCode::TOO_MANY_REQUESTS || resp.status().is_server_error()
+            }
+            Err(e) => e.is_connect() && !e.is_timeout(),
+        };
+        if !transient || attempt == RETRIES {
+            return result;
+        }
+        tokio::time::sleep(RETRY_BACKOFF * 2u32.pow(attempt)).await;
+        attempt += 1;
+    }
+}
+
+/// Body of the page at `url` as text, retrying transient failures like
+/// the index lookups do. Any failure, including a non-success status, is
+/// a [`PpmError::Network`]; the request timeout is the client's.
+pub async fn fetch_text(client: &Client, url: &str) -> Result<String, PpmError> {
+    let resp = get(client, url)
+        .await
+        .and_then(|resp| resp.error_for_status())
+        .map_err(|e| PpmError::Network(format!("Failed to fetch {}: {}", url, e)))?;
+    resp.text()
+        .await
+        .map_err(|e| PpmError::Network(format!("Failed to read {}: {}", url, e)))
+}
+
+/// Latest released version of `pkg` according to the index at `index_url`.
+///
+/// A 404 becomes [`PpmError::PackageNotFound`]; any other transport or
+/// response problem is a [`PpmError::Network`].
+pub async fn latest_version(
+    client: &Client,
+    index_url: &str,
+    pkg: &str,
+) -> Result<String, PpmError> {
+    let json = package_json(client, index_url, pkg).await?;
+    latest_in(&json)
+}
+
+/// Versions of a package on the index
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Releases {
+    /// The version the index calls latest
+    pub latest: String,
+    /// Every release with at least one file that isn't yanked
+    pub versions: Vec<String>,
+}
+
+/// The latest version of `pkg` along with all its usable releases, like
+/// [`latest_version`] does for the latest alone
+pub async fn releases(client: &Client, index_url: &str, pkg: &str) -> Result<Releases, PpmError> {
+    let json = package_json(client, index_url, pkg).await?;
+    let latest = latest_in(&json)?;
+    let releases = json["releases"]
+        .as_object()
+        .ok_or_else(|| PpmError::Network("Release list not found in response".to_string()))?;
+    let versions = releases
+        .iter()
+        .filter(|(_, files)| {
+            files.as_array().is_some_and(|files| {
+                files
+                    .iter()
+                    .any(|file| !file["yanked"].as_bool().unwrap_or(false))
+            })
+        })
+        .map(|(version, _)| version.clone())
+        .collect();
+    Ok(Releases { latest, versions })
+}
+
+/// One release of a package as the JSON API lists it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReleaseEntry {
+    /// Version string as published
+    pub version: String,
+    /// Day its first file was uploaded, like `2024-05-29`
+    pub uploaded: Option<String>,
+    /// Whether every file of the release is yanked
+    pub yanked: bool,
+    /// Why the maintainer yanked it, when they said
+    #[serde(default)]
+    pub yanked_reason: Option<String>,
+}
+
+/// Every release in a JSON API document that has files, in the
+/// document's order
+pub fn release_entries(json: &serde_json::Value) -> Vec<ReleaseEntry> {
+    let Some(releases) = json["releases"].as_object() else {
+        return vec![];
+    };
+    releases
+        .iter()
+        .filter_map(|(version, files)| {
+            let files = files.as_array().filter(|files| !files.is_empty())?;
+            let uploaded = files
+                .iter()
+                .filter_map(|file| file["upload_time"].as_str())
+                .min()
+                .map(|time| time.chars().take(10).collect());
+            let yanked = files
+                .iter()
+                .all(|file| file["yanked"].as_bool().unwrap_or(false));
+            let yanked_reason = files
+                .iter()
+                .filter_map(|file| file["yanked_reason"].as_str())
+                .find(|reason| !reason.trim().is_empty())
+                .filter(|_| yanked)
+                .map(|reason| reason.trim().to_string());
+            Some(ReleaseEntry {
+                version: version.clone(),
+                uploaded,
+                yanked,
+                yanked_reason,
+            })
+        })
+        .collect()
+}
+
+/// Every release of `pkg` with its upload day and yanked state, yanked
+/// ones included
+pub async fn release_history(
+    client: &Client,
+    index_url: &str,
+    pkg: &str,
+) -> Result<Vec<ReleaseEntry>, PpmError> {
+    let json = package_json(client, index_url, pkg).await?;
+    if !json["releases"].is_object() {
+        return Err(PpmError::Network(
+            "Release list not found in response".to_string(),
+        ));
+    }
+    Ok(release_entries(&json))
+}
+
+/// The `info` table of `pkg`'s JSON API document: the metadata of its
+/// latest release, such as its home page and `project_urls`
+pub async fn project_info(
+    client: &Client,
+    index_url: &str,
+    pkg: &str,
+) -> Result<serde_json::Value, PpmError> {
+    let mut json = package_json(client, index_url, pkg).await?;
+    match json.get_mut("info").map(serde_json::Value::take) {
+        Some(info) if info.is_object() => Ok(info),
+        _ => Err(PpmError::Network(
+            "Project info not found in response".to_string(),
+        )),
+    }
+}
+
+/// The files of `pkg`'s latest release with their sizes
+pub async fn latest_files(
+    client: &Client,
+    index_url: &str,
+    pkg: &str,
+) -> Result<Vec<DistFile>, PpmError> {
+    let json = package_json(client, index_url, pkg).await?;
+    Ok(downloads::dist_files(&json["urls"]))
+}
+
+/// The version a JSON API document calls latest
+fn latest_in(json: &serde_json::Value) -> Result<String, PpmError> {
+    json["info"]["version"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| PpmError::Network("Version field not found in response".to_string()))
+}
+
+/// The JSON API document for `pkg`
+async fn package_json(
+    client: &Client,
+    index_url: &str,
+    pkg: &str,
+) -> Result<serde_json::Value, PpmError> {
+    let url = format!("{}/{}/json", index_url, pkg);
+    let resp = get(client, &url)
+        .await
+        .map_err(|e| request_error(e, pkg, "Failed to retrieve package version"))?;
+
+    if resp.status() == StatusCode::NOT_FOUND {
+        return Err(PpmError::PackageNotFound(pkg.to_string()));
+    }
+    if !resp.status().is_success() {
+        return Err(PpmError::Network(format!(
+            "Package index returned {} for '{}'",
+            resp.status(),
+            pkg
+        )));
+    }
+
+    resp.json()
+        .await
+        .map_err(|e| request_error(e, pkg, "Failed to parse JSON response"))
+}
+
+/// A failed request for `pkg`: [`PpmError::Timeout`] when it ran out of
+/// time, otherwise a [`PpmError::Network`] saying `context`
+fn request_error(e: reqwest::Error, pkg: &str, context: &str) -> PpmError {
+    if e.is_timeout() {
+        return PpmError::Timeout {
+            what: format!("Looking up '{}' on the package index", pkg),
+            limit: None,
+            resolved: vec![],
+            timed_out: vec![pkg.to_string()],
+        };
+    }
+    PpmError::Network(format!("{}: {}", context, e))
+}
+
+/// [`latest_version`] of each of `pkgs`, looked up concurrently. `on_done`
+/// sees each result as it arrives; the returned results are in the order
+/// of `pkgs`.
+///
+/// With a `budget`, lookups still running when it runs out are cancelled
+/// and the whole phase fails with a [`PpmError::Timeout`] listing the
+/// packages looked up and those still waiting; `on_done` sees the latter
+/// as timed out first.
+pub async fn latest_versions(
+    client: &Client,
+    index_url: &str,
+    pkgs: &[String],
+    throttle: &Throttle,
+    budget: Option<Duration>,
+    mut on_done: impl FnMut(&str, &Result<String, PpmError>),
+) -> Result<Vec<Result<String, PpmError>>, PpmError> {
+    let deadline = budget.map(|budget| Instant::now() + budget);
+    let mut lookups = stream::iter(pkgs.iter().enumerate())
+        .map(|(i, pkg)| async move {
+            throttle.wait().await;
+            (i, latest_version(client, index_url, pkg).await)
+        })
+        .buffer_unordered(MAX_CONCURRENT_LOOKUPS);
+
+    let mut results: Vec<Option<Result<String, PpmError>>> = pkgs.iter().map(|_| None).collect();
+    loop {
+        let next = match deadline {
+            Some(deadline) => match tokio::time::timeout_at(deadline, lookups.next()).await {
+                Ok(next) => next,
+                Err(_) => break,
+            },
+            None => lookups.next().await,
+        };
+        let Some((i, result)) = next else {
+            break;
+        };
+        on_done(&pkgs[i], &result);
+        results[i] = Some(result);
+    }
+    // Cancels the lookups still in flight
+    drop(lookups);
+
+    let (done, waiting): (Vec<_>, Vec<_>) =
+        pkgs.iter().zip(&results).partition(|(_, r)| r.is_some());
+    if waiting.is_empty() {
+        return Ok(results.into_iter().flatten().collect());
+    }
+    let timed_out: Vec<String> = waiting.into_iter().map(|(pkg, _)| pkg.clone()).collect();
+    for pkg in &timed_out {
+        on_done(
+            pkg,
+            &Err(PpmError::Timeout {
+                what: format!("Looking up '{}' on the package index", pkg),
+                limit: budget,
+                resolved: vec![],
+                timed_out: vec![pkg.clone()],
+            }),
+        );
+    }
+    Err(PpmError::Timeout {
+        what: format!(
+            "Looking up {} package{}",
+            pkgs.len(),
+            if pkgs.len() == 1 { "" } else { "s" }
+        ),
+        limit: budget,
+        resolved: done.into_iter().map(|(pkg, _)| pkg.clone()).collect(),
+        timed_out,
+    })
+}
+
+/// [`release_history`] of each of `pkgs`, looked up concurrently like
+/// [`latest_versions`]; the results are in the order of `pkgs`
+pub async fn release_histories(
+    client: &Client,
+    index_url: &str,
+    pkgs: &[String],
+    throttle: &Throttle,
+) -> Vec<Result<Vec<ReleaseEntry>, PpmError>> {
+    let mut lookups = stream::iter(pkgs.iter().enumerate())
+        .map(|(i, pkg)| async move {
+            throttle.wait().await;
+            (i, release_history(client, index_url, pkg).await)
+        })
+        .buffer_unordered(MAX_CONCURRENT_LOOKUPS);
+
+    let mut results: Vec<Option<Result<Vec<ReleaseEntry>, PpmError>>> =
+        pkgs.iter().map(|_| None).collect();
+    while let Some((i, result)) = lookups.next().await {
+        results[i] = Some(result);
+    }
+    results.into_iter().flatten().collect()
+}
+
+/// [`project_info`] of each of `pkgs`, looked up concurrently like
+/// [`latest_versions`]; the results are in the order of `pkgs`
+pub async fn project_infos(
+    client: &Client,
+    index_url: &str,
+    pkgs: &[String],
+    throttle: &Throttle,
+) -> Vec<Result<serde_json::Value, PpmError>> {
+    let mut lookups = stream::iter(pkgs.iter().enumerate())
+        .map(|(i, pkg)| async move {
+            throttle.wait().await;
+            (i, project_info(client, index_url, pkg).await)
+        })
+        .buffer_unordered(MAX_CONCURRENT_LOOKUPS);
+
+    let mut results: Vec<Option<Result<serde_json::Value, PpmError>>> =
+        pkgs.iter().map(|_| None).collect();
+    while let Some((i, result)) = lookups.next().await {
+        results[i] = Some(result);
+    }
+    results.into_iter().flatten().collect()
+}
+
+/// A file published for a release
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReleaseFile {
+    /// Wheel or sdist file name
+    pub filename: String,
+    /// Hex sha256 digest published by the index
+    pub sha256: String,
+}
+
+/// Files published for `version` of `pkg`, empty when the index doesn't
+/// know that package or version
+pub async fn release_files(
+    client: &Client,
+    index_url: &str,
+    pkg: &str,
+    version: &str,
+) -> Result<Vec<ReleaseFile>, PpmError> {
+    let url = format!("{}/{}/{}/json", index_url, pkg, version);
+    let resp = get(client, &url)
+        .await
+        .map_err(|e| request_error(e, pkg, "Failed to retrieve release files"))?;
+
+    if resp.status() == StatusCode::NOT_FOUND {
+        return Ok(vec![]);
+    }
+    if !resp.status().is_success() {
+        return Err(PpmError::Network(format!(
+            "Package index returned {} for '{}' {}",
+            resp.status(),
+            pkg,
+            version
+        )));
+    }
+
+    let json: serde_json::Value = resp
+        .json()
+        .await
+        .map_err(|e| request_error(e, pkg, "Failed to parse JSON response"))?;
+    let urls = json["urls"]
+        .as_array()
+        .ok_or_else(|| PpmError::Network("File list not found in response".to_string()))?;
+    Ok(urls
+        .iter()
+        .filter_map(|file| {
+            Some(ReleaseFile {
+                filename: file["filename"].as_str()?.to_string(),
+                sha256: file["digests"]["sha256"].as_str()?.to_string(),
+            })
+        })
+        .collect())
+}
+
+/// Whether the index at `index_url` has a release `version` of `pkg`
+pub async fn version_exists(
+    client: &Client,
+    index_url: &str,
+    pkg: &str,
+    version: &str,
+) -> Result<bool, PpmError> {
+    let url = format!("{}/{}/{}/json", index_url, pkg, version);
+    let resp = get(client, &url)
+        .await
+        .map_err(|e| request_error(e, pkg, &format!("Failed to look up '{}' {}", pkg, version)))?;
+
+    match resp.status() {
+        StatusCode::NOT_FOUND => Ok(false),
+        status if status.is_success() => Ok(true),
+        status => Err(PpmError::Network(format!(
+            "Package index returned {} for '{}' {}",
+            status, pkg, version
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use httpmock::prelude::*;
+
+    /// Answer every request with `status` and `body` on localhost
+    async fn serve(status: u16, body: &str) -> MockServer {
+        let server = MockServer::start_async().await;
+        server.mock(|when, then| {
+            when.method(GET);
+            then.status(status)
+                .header("content-type", "application/json")
+                .body(body);
+        });
+        server
+    }
+
+    #[tokio::test]
+    async fn test_latest_version() {
+        let server = serve(200, r#"{"info": {"version": "2.31.0"}}"#).await;
+        let url = server.base_url();
+        assert_eq!(
+            latest_version(&Client::new(), &url, "requests")
+                .await
+                .unwrap(),
+            "2.31.0"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_latest_version_not_found() {
+        let server = serve(404, r#"{"message": "Not Found"}"#).await;
+        let url = server.base_url();
+        let err = latest_version(&Client::new(), &url, "nope")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, PpmError::PackageNotFound(ref name) if name == "nope"));
+    }
+
+    #[tokio::test]
+    async fn test_releases_skip_yanked_and_empty() {
+        let server = serve(
+            200,
+            r#"{"info": {"version": "5.0.1"}, "releases": {
+                "4.2.1": [{"yanked": false}],
+                "4.2.2": [{"yanked": true}],
+                "5.0.1": [{"yanked": true}, {"yanked": false}],
+                "5.1a1": []
+            }}"#,
+        )
+        .await;
+        let url = server.base_url();
+        let mut releases = releases(&Client::new(), &url, "django").await.unwrap();
+        releases.versions.sort();
+        assert_eq!(
+            releases,
+            Releases {
+                latest: "5.0.1".to_string(),
+                versions: vec!["4.2.1".to_string(), "5.0.1".to_string()],
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_release_history_keeps_yanked() {
+        let server = serve(
+            200,
+            r#"{"info": {"version": "1.1"}, "releases": {
+                "1.0": [{"upload_time": "2023-01-02T03:04:05", "yanked": true}],
+                "1.1": [{"upload_time": "2023-02-01T00:00:00", "yanked": false}]
+            }}"#,
+        )
+        .await;
+        let url = server.base_url();
+        let mut history = release_history(&Client::new(), &url, "demo").await.unwrap();
+        history.sort_by(|a, b| a.version.cmp(&b.version));
+        assert_eq!(
+            history[0],
+            ReleaseEntry {
+                version: "1.0".to_string(),
+                uploaded: Some("2023-01-02".to_string()),
+                yanked: true,
+                yanked_reason: None,
+            }
+        );
+        assert!(!history[1].yanked);
+    }
+
+    #[tokio::test]
+    async fn test_release_files() {
+        let server = serve(200, r#"{"urls": [{"filename": "six-1.16.0-py2.py3-none-any.whl", "digests": {"sha256": "8abb"}}, {"filename": "six-1.16.0.tar.gz", "digests": {"md5": "x"}}]}"#).await;
+        let url = server.base_url();
+        assert_eq!(
+            release_files(&Client::new(), &url, "six", "1.16.0")
+                .await
+                .unwrap(),
+            vec![ReleaseFile {
+                filename: "six-1.16.0-py2.py3-none-any.whl".to_string(),
+                sha256: "8abb".to_string(),
+            }]
+        );
+
+        let server = serve(404, r#"{"message": "Not Found"}"#).await;
+        let url = server.base_url();
+        assert!(
+            release_files(&Client::new(), &url, "six", "0.0.1")
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_text() {
+        let server = MockServer::start_async().await;
+        server.mock(|when, then| {
+            when.method(GET).path("/page");
+            then.status(200).body("plain body");
+        });
+        let client = Client::new();
+        assert_eq!(
+            fetch_text(&client, &server.url("/page")).await.unwrap(),
+            "plain body"
+        );
+
+        let err = fetch_text(&client, &server.url("/missing"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, PpmError::Network(ref msg) if msg.contains("404")));
+    }
+
+    #[tokio::test]
+    async fn test_version_exists() {
+        let server = MockServer::start_async().await;
+        server.mock(|when, then| {
+            when.method(GET).path("/demo/0.1.0/json");
+            then.status(200).body(r#"{"info": {"version": "0.1.0"}}"#);
+        });
+        server.mock(|when, then| {
+            when.method(GET).path("/demo/0.2.0/json");
+            then.status(404).body(r#"{"message": "Not Found"}"#);
+        });
+        server.mock(|when, then| {
+            when.method(GET).path("/private/1.0/json");
+            then.status(403);
+        });
+        let url = server.base_url();
+        let client = Client::new();
+        assert!(version_exists(&client, &url, "demo", "0.1.0").await.unwrap());
+        assert!(!version_exists(&client, &url, "demo", "0.2.0").await.unwrap());
+        assert!(matches!(
+            version_exists(&client, &url, "private", "1.0").await,
+            Err(PpmError::Network(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_latest_version_network_errors() {
+        let server = serve(200, r#"{"info": {}}"#).await;
+        let url = server.base_url();
+        let err = latest_version(&Client::new(), &url, "requests")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, PpmError::Network(_)));
+
+        // Nothing listens on port 1
+        let err = latest_version(&Client::new(), "http://127.0.0.1:1", "requests")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, PpmError::Network(_)));
+        assert_eq!(err.exit_code(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_server_errors_are_retried() {
+        let server = MockServer::start_async().await;
+        let unavailable = server.mock(|when, then| {
+            when.method(GET);
+            then.status(503);
+        });
+        let err = latest_version(&Client::new(), &server.base_url(), "requests")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, PpmError::Network(ref msg) if msg.contains("503")));
+        unavailable.assert_calls(1 + RETRIES as usize);
+    }
+
+    #[tokio::test]
+    async fn test_latest_versions_keep_package_order() {
+        // The version is the package name's length; "missing" is a 404
+        let server = MockServer::start_async().await;
+        for pkg in ["requests", "six", "numpy"] {
+            server.mock(|when, then| {
+                when.method(GET).path(format!("/{}/json", pkg));
+                then.status(200)
+                    .body(format!(r#"{{"info": {{"version": "{}"}}}}"#, pkg.len()));
+            });
+        }
+        let url = server.base_url();
+        let pkgs: Vec<String> = ["requests", "six", "missing", "numpy"]
+            .iter()
+            .map(|pkg| pkg.to_string())
+            .collect();
+        let mut done = vec![];
+        let results = latest_versions(
+            &Client::new(),
+            &url,
+            &pkgs,
+            &Throttle::new(Duration::from_millis(1)),
+            None,
+            |pkg, _| done.push(pkg.to_string()),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0].as_deref().ok(), Some("8"));
+        assert_eq!(results[1].as_deref().ok(), Some("3"));
+        assert!(matches!(results[2], Err(PpmError::PackageNotFound(_))));
+        assert_eq!(results[3].as_deref().ok(), Some("5"));
+        done.sort();
+        assert_eq!(done, ["missing", "numpy", "requests", "six"]);
+    }
+}
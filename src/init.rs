@@ -0,0 +1,204 @@
+//! The fields `ppm init` and `ppm new` fill in, and how their values are
+//! checked.
+//!
+//! Each field can come from its flag, a typed answer or its default, and
+//! the same check applies whichever it came from, so a script passing
+//! flags gets the errors an interactive user would.
+
+use crate::license;
+use crate::requirement;
+use std::path::{Component, Path};
+
+/// A project.toml field `ppm init` can ask for
+#[derive(Debug, Clone, Copy)]
+pub struct Field {
+    /// The flag that sets it, like `--name`
+    pub flag: &'static str,
+    /// The question asked for it
+    pub question: &'static str,
+    validate: fn(&str) -> Result<(), String>,
+}
+
+impl Field {
+    /// Fail, saying why, when `value` can't be used
+    pub fn check(&self, value: &str) -> Result<(), String> {
+        (self.validate)(value)
+    }
+}
+
+/// `[project] name`
+pub const NAME: Field = Field {
+    flag: "--name",
+    question: "Project name",
+    validate: validate_name,
+};
+
+/// `[project] version`
+pub const VERSION: Field = Field {
+    flag: "--version",
+    question: "Version",
+    validate: validate_version,
+};
+
+/// `[project] description`
+pub const DESCRIPTION: Field = Field {
+    flag: "--description",
+    question: "Description",
+    validate: |_| Ok(()),
+};
+
+/// `[project] main_script`
+pub const MAIN_SCRIPT: Field = Field {
+    flag: "--main-script",
+    question: "Main script",
+    validate: |path| validate_relative_path("the main script", path),
+};
+
+/// `[project] venv`
+pub const VENV: Field = Field {
+    flag: "--venv-name",
+    question: "Virtual environment directory",
+    validate: |path| validate_relative_path("the venv", path),
+};
+
+/// `[project] python`
+pub const PYTHON: Field = Field {
+    flag: "--python",
+    question: "Python interpreter (a command like python3.12, or a version like 3.12)",
+    validate: validate_python,
+};
+
+/// `[project] license`
+pub const LICENSE: Field = Field {
+    flag: "--license",
+    question: "License",
+    validate: license::validate_id,
+};
+
+/// A project name must be a valid PEP 508 name, since it becomes the
+/// distribution's
+pub fn validate_name(name: &str) -> Result<(), String> {
+    requirement::validate_name(name)
+}
+
+/// The project name `ppm init` offers for the directory named `dir`:
+/// lowercased, with spaces, underscores and other punctuation turned into
+/// single `-`s and letters outside ASCII left out. Fails, saying why,
+/// when nothing usable is left or the name would start with a digit.
+pub fn name_from_dir(dir: &str) -> Result<String, String> {
+    let mut name = String::new();
+    for c in dir.to_lowercase().chars() {
+        match c {
+            c if c.is_ascii_alphanumeric() || c == '.' => name.push(c),
+            c if c.is_alphanumeric() => {}
+            _ if name.ends_with('-') => {}
+            _ => name.push('-'),
+        }
+    }
+    let name = name.trim_matches(['-', '.']).replace(".-", "-");
+    if name.is_empty() {
+        return Err(format!(
+            "the directory name '{}' has no letters or digits a project name can use",
+            dir
+        ));
+    }
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        return Err(format!(
+            "'{}', from the directory name, starts with a digit",
+            name
+        ));
+    }
+    validate_name(&name)?;
+    Ok(name)
+}
+
+/// `MAJOR.MINOR.PATCH` with an optional `-prerelease` and `+build`, as
+/// semver and `ppm bump` expect
+pub fn validate_version(version: &str) -> Result<(), String> {
+    let core = version.split(['-', '+']).next().unwrap_or_default();
+    let parts: Vec<&str> = core.split('.').collect();
+    let numeric = |part: &&str| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit());
+    if parts.len() != 3 || !parts.iter().all(numeric) {
+        return Err(format!(
+            "version '{}' isn't a semantic version like 0.1.0",
+            version
+        ));
+    }
+    Ok(())
+}
+
+/// A path inside the project: relative and without `..`
+fn validate_relative_path(what: &str, path: &str) -> Result<(), String> {
+    let inside = Path::new(path)
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+    if path.trim().is_empty() || !inside {
+        return Err(format!(
+            "'{}' must be a relative path inside the project for {}",
+            path, what
+        ));
+    }
+    Ok(())
+}
+
+/// An interpreter command or version: one word
+fn validate_python(python: &str) -> Result<(), String> {
+    if python.is_empty() || python.contains(char::is_whitespace) {
+        return Err(format!(
+            "'{}' isn't an interpreter like python3.12 or 3.12",
+            python
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_fields() {
+        assert!(NAME.check("my-api").is_ok());
+        assert!(NAME.check("my api").is_err());
+        assert!(NAME.check("-api").is_err());
+
+        assert!(VERSION.check("0.1.0").is_ok());
+        assert!(VERSION.check("1.0.0-rc.1+build5").is_ok());
+        assert!(VERSION.check("1.0").is_err());
+        assert!(VERSION.check("v1.0.0").is_err());
+
+        assert!(MAIN_SCRIPT.check("./src/app.py").is_ok());
+        assert!(MAIN_SCRIPT.check("../app.py").is_err());
+        assert!(VENV.check(".venv").is_ok());
+        assert!(VENV.check("/opt/venv").is_err());
+        assert!(VENV.check("").is_err());
+
+        assert!(PYTHON.check("3.12").is_ok());
+        assert!(PYTHON.check("python 3").is_err());
+        assert!(DESCRIPTION.check("").is_ok());
+        assert!(LICENSE.check("apache-2.0").is_ok());
+        assert!(LICENSE.check("WTFPL").is_err());
+    }
+
+    #[test]
+    fn test_name_from_dir() {
+        assert_eq!(name_from_dir("My Project").unwrap(), "my-project");
+        assert_eq!(name_from_dir("data_tools  v2").unwrap(), "data-tools-v2");
+        assert_eq!(name_from_dir("example.com").unwrap(), "example.com");
+        assert_eq!(name_from_dir(".tmpAb12").unwrap(), "tmpab12");
+        assert_eq!(name_from_dir("api. (old)").unwrap(), "api-old");
+        assert_eq!(name_from_dir("Café Über").unwrap(), "caf-ber");
+
+        assert!(
+            name_from_dir("日本語")
+                .unwrap_err()
+                .contains("no letters or digits")
+        );
+        assert!(name_from_dir("...").is_err());
+        assert!(
+            name_from_dir("2024 Report")
+                .unwrap_err()
+                .contains("starts with a digit")
+        );
+    }
+}
@@ -0,0 +1,473 @@
+//! Installing packages with several pip processes at once.
+//!
+//! pip installs one set of requirements per process and resolves them
+//! together. ppm runs up to one pip process per core, at most
+//! [`MAX_DEFAULT_JOBS`], unless told otherwise: exact pins
+//! (`name==version`) are installed concurrently with `--no-deps`, at most
+//! `jobs` at a time, and a final pip run installs their dependencies
+//! together with the remaining specs. Pins can't affect each other's
+//! resolution, so the result is the same as one big install; the
+//! concurrent part only overlaps the downloads and unpacking. One job is a
+//! single `pip install` for everything.
+//!
+//! A parallel install asked to stop (on Ctrl+C, see [`crate::interrupt`])
+//! starts no further pins, lets the running ones finish, and installs
+//! just their dependencies; the tasks it never started are reported as
+//! skipped.
+
+use crate::error::PpmError;
+use crate::interrupt;
+use crate::requirement::Requirement;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+/// Upper bound of [`default_jobs`]
+pub const MAX_DEFAULT_JOBS: usize = 4;
+
+/// Worker count when none is configured, or for `--jobs 0`: one per core,
+/// at most [`MAX_DEFAULT_JOBS`]
+pub fn default_jobs() -> usize {
+    thread::available_parallelism()
+        .map(|cores| cores.get())
+        .unwrap_or(1)
+        .min(MAX_DEFAULT_JOBS)
+}
+
+/// Number of pip processes to run, from `--jobs` or else the `jobs` key of
+/// `[project]`. Unset or 0 means [`default_jobs`]; 1 is a single pip
+/// invocation.
+pub fn resolve_jobs(cli: Option<usize>, configured: Option<usize>) -> usize {
+    match cli.or(configured) {
+        Some(0) | None => default_jobs(),
+        Some(jobs) => jobs,
+    }
+}
+
+/// A package and its `pip install` arguments, e.g. `["requests==2.31.0"]`
+/// or `["-e", "../lib"]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstallTask {
+    /// Package name as configured
+    pub name: String,
+    /// Arguments passed to `pip install`
+    pub args: Vec<String>,
+}
+
+impl InstallTask {
+    /// Build a task from its fields
+    pub fn new(name: &str, args: Vec<String>) -> InstallTask {
+        InstallTask {
+            name: name.to_string(),
+            args,
+        }
+    }
+
+    /// Whether the task is a single `name==version` from the index, which
+    /// can be installed without resolving it against the other tasks
+    pub fn is_exact_pin(&self) -> bool {
+        match self.args.as_slice() {
+            [spec] => Requirement::parse(spec).is_ok_and(|req| {
+                req.url.is_none()
+                    && req.specifier.len() == 1
+                    && req.specifier[0].0 == "=="
+                    && !req.specifier[0].1.contains('*')
+            }),
+            _ => false,
+        }
+    }
+}
+
+/// What happened to the tasks of an install
+#[derive(Debug, Default)]
+pub struct InstallReport {
+    /// `(name, reason)` of every task that failed, in task order
+    pub failed: Vec<(String, String)>,
+    /// The first failure, for the exit status
+    pub error: Option<PpmError>,
+    /// Names of the tasks never started because the install was stopped
+    pub skipped: Vec<String>,
+}
+
+impl InstallReport {
+    /// Report for a single pip run that failed, failing every task
+    pub fn all_failed(tasks: &[InstallTask], error: PpmError) -> InstallReport {
+        let reason = error.to_string();
+        InstallReport {
+            failed: tasks
+                .iter()
+                .map(|task| (task.name.clone(), reason.clone()))
+                .collect(),
+            error: Some(error),
+            skipped: vec![],
+        }
+    }
+
+    /// Whether the task named `name` was installed
+    pub fn succeeded(&self, name: &str) -> bool {
+        !self.failed.iter().any(|(failed, _)| failed == name)
+            && !self.skipped.iter().any(|skipped| skipped == name)
+    }
+
+    fn fail(&mut self, name: &str, error: PpmError) {
+        self.failed.push((name.to_string(), error.to_string()));
+        self.error.get_or_insert(error);
+    }
+}
+
+/// Progress of a parallel install, reported on the calling thread
+#[derive(Debug, PartialEq)]
+pub enum InstallEvent<'a> {
+    /// A worker started installing the package
+    Started(&'a str),
+    /// A line of pip's output, with the package it belongs to; `None` for
+    /// the final dependency run
+    Output(Option<&'a str>, &'a str),
+    /// The package's pip run ended, with the reason when it failed
+    Finished(&'a str, Option<&'a str>),
+}
+
+/// What a [`run_pool`] worker is doing with an item
+#[derive(Debug, PartialEq)]
+pub enum PoolEvent<'a, R> {
+    /// Work on the item started
+    Started,
+    /// The item's work printed a line
+    Line(&'a str),
+    /// The item is done
+    Done(&'a R),
+}
+
+/// Run `work` on each item with at most `jobs` items in flight. `work`
+/// gets a callback for output lines; `report` sees every item's events on
+/// the calling thread as they happen. Results come back in item order.
+pub fn run_pool<T, R, W, F>(items: &[T], jobs: usize, work: W, mut report: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    W: Fn(&T, &dyn Fn(String)) -> R + Sync,
+    F: FnMut(usize, PoolEvent<'_, R>),
+{
+    enum Message<R> {
+        Start(usize),
+        Line(usize, String),
+        Done(usize, R),
+    }
+
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<Option<R>> = items.iter().map(|_| None).collect();
+    thread::scope(|scope| {
+        let (tx, rx) = mpsc::channel();
+        for _ in 0..jobs.clamp(1, items.len().max(1)) {
+            let tx = tx.clone();
+            let (next, work) = (&next, &work);
+            scope.spawn(move || {
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(item) = items.get(index) else { break };
+                    let _ = tx.send(Message::Start(index));
+                    let emit = |line: String| {
+                        let _ = tx.send(Message::Line(index, line));
+                    };
+                    let result = work(item, &emit);
+                    let _ = tx.send(Message::Done(index, result));
+                }
+            });
+        }
+        drop(tx);
+        for message in rx {
+            match message {
+                Message::Start(index) => report(index, PoolEvent::Started),
+                Message::Line(index, line) => report(index, PoolEvent::Line(&line)),
+                Message::Done(index, result) => {
+                    report(index, PoolEvent::Done(&result));
+                    results[index] = Some(result);
+                }
+            }
+        }
+    });
+    results.into_iter().flatten().collect()
+}
+
+/// `pip install <args>` in `cwd`, passing each line of pip's stdout to
+/// `emit` as it is printed
+pub fn pip_install_streaming(
+    pip: &Path,
+    cwd: &Path,
+    args: &[String],
+    emit: &dyn Fn(String),
+) -> Result<(), PpmError> {
+    let mut child = interrupt::shield(&mut Command::new(pip))
+        .arg("install")
+        .args(args)
+        .current_dir(cwd)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| PpmError::Subprocess(format!("Failed to execute pip: {}", e)))?;
+    let _tracked = interrupt::track(&child);
+
+    let stdout = child.stdout.take();
+    let mut stderr = child.stderr.take();
+    // Read stderr on the side so a chatty pip can't fill the pipe and stall
+    let errors = thread::scope(|scope| {
+        let errors = scope.spawn(move || {
+            let mut errors = String::new();
+            if let Some(stderr) = stderr.as_mut() {
+                let _ = stderr.read_to_string(&mut errors);
+            }
+            errors
+        });
+        if let Some(stdout) = stdout {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                emit(line);
+            }
+        }
+        errors.join().unwrap_or_default()
+    });
+
+    let status = child
+        .wait()
+        .map_err(|e| PpmError::Subprocess(format!("Error waiting for pip: {}", e)))?;
+    if !status.success() {
+        return Err(PpmError::from_pip(errors, status.code()));
+    }
+    Ok(())
+}
+
+/// Install `tasks` with up to `jobs` concurrent pip processes, see the
+/// module docs. `options` are given to every pip run, like `-c
+/// constraints.txt`. A failing package doesn't stop the others; packages
+/// whose dependency run fails are reported as failed too. Once `stop`
+/// returns true no more pins are started.
+pub fn install_parallel(
+    pip: &Path,
+    cwd: &Path,
+    options: &[String],
+    tasks: &[InstallTask],
+    jobs: usize,
+    stop: &(dyn Fn() -> bool + Sync),
+    mut report: impl FnMut(InstallEvent<'_>),
+) -> InstallReport {
+    let (pins, rest): (Vec<&InstallTask>, Vec<&InstallTask>) =
+        tasks.iter().partition(|task| task.is_exact_pin());
+
+    let mut install_report = InstallReport::default();
+    let results = run_pool(
+        &pins,
+        jobs,
+        |task, emit| {
+            if stop() {
+                return None;
+            }
+            let mut args = vec!["--no-deps".to_string()];
+            args.extend(options.iter().cloned());
+            args.extend(task.args.iter().cloned());
+            Some(pip_install_streaming(pip, cwd, &args, emit))
+        },
+        |index, event| {
+            let name = pins[index].name.as_str();
+            match event {
+                PoolEvent::Started => report(InstallEvent::Started(name)),
+                PoolEvent::Line(line) => report(InstallEvent::Output(Some(name), line)),
+                PoolEvent::Done(Some(result)) => {
+                    let reason = result.as_ref().err().map(|e| e.to_string());
+                    report(InstallEvent::Finished(name, reason.as_deref()));
+                }
+                PoolEvent::Done(None) => report(InstallEvent::Finished(name, Some("not started"))),
+            }
+        },
+    );
+
+    let mut remaining: Vec<&InstallTask> = vec![];
+    for (task, result) in pins.iter().zip(results) {
+        match result {
+            Some(Ok(())) => remaining.push(task),
+            Some(Err(e)) => install_report.fail(&task.name, e),
+            None => install_report.skipped.push(task.name.clone()),
+        }
+    }
+    // The finished pins still get their dependencies
+    if stop() {
+        install_report
+            .skipped
+            .extend(rest.iter().map(|task| task.name.clone()));
+    } else {
+        remaining.extend(rest);
+    }
+    if remaining.is_empty() {
+        return install_report;
+    }
+
+    let args: Vec<String> = options
+        .iter()
+        .cloned()
+        .chain(remaining.iter().flat_map(|task| task.args.iter().cloned()))
+        .collect();
+    let mut results = run_pool(
+        &[args],
+        1,
+        |args, emit| pip_install_streaming(pip, cwd, args, emit),
+        |_, event| {
+            if let PoolEvent::Line(line) = event {
+                report(InstallEvent::Output(None, line));
+            }
+        },
+    );
+    if let Some(Err(e)) = results.pop() {
+        let reason = e.to_string();
+        for task in &remaining {
+            install_report
+                .failed
+                .push((task.name.clone(), reason.clone()));
+        }
+        install_report.error.get_or_insert(e);
+    }
+    let position = |name: &String| tasks.iter().position(|task| &task.name == name);
+    install_report
+        .failed
+        .sort_by_key(|(name, _)| position(name));
+    install_report.skipped.sort_by_key(position);
+    install_report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_jobs() {
+        assert_eq!(resolve_jobs(None, None), default_jobs());
+        assert_eq!(resolve_jobs(None, Some(1)), 1);
+        assert_eq!(resolve_jobs(None, Some(3)), 3);
+        assert_eq!(resolve_jobs(Some(2), Some(3)), 2);
+        let auto = resolve_jobs(Some(0), None);
+        assert!((1..=MAX_DEFAULT_JOBS).contains(&auto));
+    }
+
+    #[test]
+    fn test_exact_pins() {
+        let task = |args: &[&str]| {
+            InstallTask::new("pkg", args.iter().map(|arg| arg.to_string()).collect())
+        };
+        assert!(task(&["requests==2.31.0"]).is_exact_pin());
+        assert!(task(&["uvloop==0.19.0; sys_platform == 'linux'"]).is_exact_pin());
+        assert!(!task(&["requests>=2.0"]).is_exact_pin());
+        assert!(!task(&["requests==2.*"]).is_exact_pin());
+        assert!(!task(&["lib @ git+https://example.com/lib@abc123"]).is_exact_pin());
+        assert!(!task(&["-e", "../lib"]).is_exact_pin());
+    }
+
+    #[test]
+    fn test_run_pool_keeps_item_order() {
+        let items: Vec<u64> = (0..8).collect();
+        let (mut started, mut lines, mut done) = (0, 0, 0);
+        let results = run_pool(
+            &items,
+            3,
+            |item, emit| {
+                thread::sleep(std::time::Duration::from_millis(8 - item));
+                emit(format!("working on {}", item));
+                item * 10
+            },
+            |_, event| match event {
+                PoolEvent::Started => started += 1,
+                PoolEvent::Line(_) => lines += 1,
+                PoolEvent::Done(_) => done += 1,
+            },
+        );
+        assert_eq!(results, vec![0, 10, 20, 30, 40, 50, 60, 70]);
+        assert_eq!((started, lines, done), (8, 8, 8));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_failed_worker_does_not_stop_the_others() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let pip = dir.path().join("pip");
+        std::fs::write(
+            &pip,
+            "#!/bin/sh\n\
+             echo \"$@\" >> calls.txt\n\
+             case \"$*\" in *broken*) echo 'ERROR: No matching distribution' >&2; exit 1;; esac\n\
+             echo \"Successfully installed $*\"\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&pip, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let tasks = vec![
+            InstallTask::new("requests", vec!["requests==2.31.0".to_string()]),
+            InstallTask::new("broken", vec!["broken==1.0".to_string()]),
+            InstallTask::new("lib", vec!["-e".to_string(), "../lib".to_string()]),
+        ];
+        let mut events = vec![];
+        let options = ["-c".to_string(), "constraints.txt".to_string()];
+        let report = install_parallel(&pip, dir.path(), &options, &tasks, 2, &|| false, |event| {
+            if let InstallEvent::Finished(name, reason) = event {
+                events.push((name.to_string(), reason.is_some()));
+            }
+        });
+
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, "broken");
+        assert!(report.succeeded("requests") && report.succeeded("lib"));
+        assert!(matches!(report.error, Some(PpmError::Pip { .. })));
+        events.sort();
+        assert_eq!(
+            events,
+            vec![
+                ("broken".to_string(), true),
+                ("requests".to_string(), false)
+            ]
+        );
+
+        let calls = std::fs::read_to_string(dir.path().join("calls.txt")).unwrap();
+        let mut calls: Vec<&str> = calls.lines().collect();
+        calls.sort();
+        assert_eq!(
+            calls,
+            vec![
+                "install --no-deps -c constraints.txt broken==1.0",
+                "install --no-deps -c constraints.txt requests==2.31.0",
+                "install -c constraints.txt requests==2.31.0 -e ../lib",
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stopped_install_skips_what_it_has_not_started() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let pip = dir.path().join("pip");
+        std::fs::write(&pip, "#!/bin/sh\necho \"$@\" >> calls.txt\n").unwrap();
+        std::fs::set_permissions(&pip, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let tasks = vec![
+            InstallTask::new("requests", vec!["requests==2.31.0".to_string()]),
+            InstallTask::new("idna", vec!["idna==3.7".to_string()]),
+            InstallTask::new("lib", vec!["-e".to_string(), "../lib".to_string()]),
+            InstallTask::new("certifi", vec!["certifi==2024.2.2".to_string()]),
+        ];
+        // Stop as soon as the first pip run has happened
+        let calls = dir.path().join("calls.txt");
+        let stop = || calls.exists();
+        let report = install_parallel(&pip, dir.path(), &[], &tasks, 1, &stop, |_| {});
+
+        assert!(report.failed.is_empty() && report.error.is_none());
+        assert_eq!(report.skipped, ["idna", "lib", "certifi"]);
+        assert!(report.succeeded("requests"));
+        assert!(!report.succeeded("idna"));
+        assert_eq!(
+            std::fs::read_to_string(&calls).unwrap(),
+            "install --no-deps requests==2.31.0\ninstall requests==2.31.0\n"
+        );
+    }
+}
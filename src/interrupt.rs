@@ -0,0 +1,130 @@
+//! Stopping long installs cleanly on Ctrl+C.
+//!
+//! Once [`handle_ctrl_c`] has run, the first Ctrl+C only sets a flag:
+//! installs stop starting pip processes, let the ones already running
+//! finish, record what got done and exit with [`crate::error::EXIT_INTERRUPTED`].
+//! pip runs in its own process group then (see [`shield`]), so the
+//! terminal's Ctrl+C doesn't kill it half-way through unpacking. A second
+//! Ctrl+C stops the pip processes still running, tracked with [`track`],
+//! and exits right away.
+
+use crate::error::EXIT_INTERRUPTED;
+use std::process::{Child, Command};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static HANDLING: AtomicBool = AtomicBool::new(false);
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Pids of the shielded processes still running, see [`track`]
+static RUNNING: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+/// Catch Ctrl+C from here on, see the module docs. Does nothing when
+/// called again or when the handler can't be installed.
+pub fn handle_ctrl_c() {
+    if HANDLING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let installed = ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            stop_running();
+            std::process::exit(EXIT_INTERRUPTED);
+        }
+        eprintln!(
+            "\nInterrupted: finishing the running pip processes; press Ctrl+C again to stop now"
+        );
+    });
+    if installed.is_err() {
+        HANDLING.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Whether Ctrl+C was pressed since [`handle_ctrl_c`]
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Keep the Ctrl+C ppm handles from reaching `command`'s process, so it
+/// can finish. Without [`handle_ctrl_c`] the child is left alone, to be
+/// stopped together with ppm.
+///
+/// On Unix the process leaves the terminal's foreground group, where
+/// reading from the terminal would stop it, so pip is told not to prompt
+/// (`PIP_NO_INPUT`): credentials for an index have to come from the URL,
+/// netrc or keyring rather than a prompt.
+pub fn shield(command: &mut Command) -> &mut Command {
+    if !HANDLING.load(Ordering::SeqCst) {
+        return command;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0).env("PIP_NO_INPUT", "1");
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+        command.creation_flags(CREATE_NEW_PROCESS_GROUP);
+    }
+    command
+}
+
+/// A shielded process a second Ctrl+C stops, until this is dropped once
+/// it has been waited for
+#[derive(Debug)]
+pub struct Tracked(u32);
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        running().retain(|pid| *pid != self.0);
+    }
+}
+
+/// Have a second Ctrl+C stop `child`, a process started with [`shield`],
+/// with its process group, while the returned guard lives
+pub fn track(child: &Child) -> Tracked {
+    running().push(child.id());
+    Tracked(child.id())
+}
+
+fn running() -> std::sync::MutexGuard<'static, Vec<u32>> {
+    RUNNING.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Stop every tracked process along with what it started
+fn stop_running() {
+    for pid in running().iter() {
+        #[cfg(unix)]
+        // SAFETY: kill only sends a signal; a shielded child leads its
+        // own process group, whose id is its pid
+        unsafe {
+            libc::kill(-(*pid as libc::pid_t), libc::SIGTERM);
+        }
+        #[cfg(windows)]
+        let _ = Command::new("taskkill")
+            .args(["/T", "/F", "/PID", &pid.to_string()])
+            .output();
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::process::{CommandExt, ExitStatusExt};
+
+    #[test]
+    fn test_stop_running_stops_tracked_process_groups() {
+        let mut child = Command::new("sh")
+            .args(["-c", "sleep 30 & wait"])
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        let tracked = track(&child);
+        stop_running();
+        let status = child.wait().unwrap();
+        assert_eq!(status.signal(), Some(libc::SIGTERM));
+        drop(tracked);
+        assert!(!running().contains(&child.id()));
+    }
+}
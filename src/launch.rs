@@ -0,0 +1,241 @@
+//! How `ppm start` runs the main script: directly, or through a module
+//! of the venv's python that runs it for us, like debugpy or cProfile.
+//!
+//! Arguments given after `--` always go to the script itself, after its
+//! path, whatever wraps it, and its exit status is kept.
+
+use std::ffi::OsString;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Port debugpy listens on when `--debug` is given without one
+pub const DEFAULT_DEBUG_PORT: u16 = 5678;
+
+/// Address debugpy listens on; only debuggers on this machine can attach
+pub const DEBUG_HOST: &str = "127.0.0.1";
+
+/// Where `ppm start --profile` writes the raw profile, inside `.ppm/`
+pub const PROFILE_FILE: &str = "profile.out";
+
+/// How many entries the profile summary shows by default
+pub const DEFAULT_PROFILE_TOP: usize = 20;
+
+/// `python -m cProfile` ends with status 0 whatever the script exited
+/// with, so the script runs under a profiler of our own: `argv[1]` is the
+/// output file, the rest the script and its arguments
+const PROFILE_BOOTSTRAP: &str = "\
+import cProfile, os, runpy, sys
+out = sys.argv[1]
+sys.argv = sys.argv[2:]
+sys.path[0] = os.path.dirname(os.path.abspath(sys.argv[0]))
+profile = cProfile.Profile()
+code = 0
+try:
+    profile.runcall(runpy.run_path, sys.argv[0], run_name='__main__')
+except SystemExit as e:
+    code = e.code
+finally:
+    profile.dump_stats(out)
+sys.exit(code)
+";
+
+/// Prints the top `argv[3]` entries of the profile `argv[1]` sorted by
+/// `argv[2]`
+const PSTATS_SUMMARY: &str = "import pstats, sys; pstats.Stats(sys.argv[1]).sort_stats(sys.argv[2]).print_stats(int(sys.argv[3]))";
+
+/// Column the profile summary is sorted by
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProfileSort {
+    /// Time spent in a function and everything it called
+    #[default]
+    Cumulative,
+    /// Time spent in a function itself
+    Tottime,
+}
+
+impl ProfileSort {
+    /// The sort called `name`, as on the command line
+    pub fn from_name(name: &str) -> Option<ProfileSort> {
+        match name {
+            "cumulative" => Some(ProfileSort::Cumulative),
+            "tottime" => Some(ProfileSort::Tottime),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ProfileSort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ProfileSort::Cumulative => "cumulative",
+            ProfileSort::Tottime => "tottime",
+        })
+    }
+}
+
+/// How to run the main script
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Launch {
+    /// `python <script>`
+    Direct,
+    /// `python -m debugpy --listen <host:port> <script>`
+    Debug {
+        /// Port to listen on
+        port: u16,
+        /// Whether the script waits for a debugger before it starts
+        wait: bool,
+    },
+    /// The script under cProfile, writing the profile to `output`
+    Profile {
+        /// Raw profile, for snakeviz and friends
+        output: PathBuf,
+        /// Column the summary is sorted by
+        sort: ProfileSort,
+        /// Entries the summary shows
+        top: usize,
+    },
+}
+
+impl Launch {
+    /// Package the venv needs for this launch, when it's not part of the
+    /// standard library
+    pub fn required_package(&self) -> Option<&'static str> {
+        match self {
+            Launch::Direct | Launch::Profile { .. } => None,
+            Launch::Debug { .. } => Some("debugpy"),
+        }
+    }
+
+    /// Arguments to the venv's python that run `script` with `args`
+    pub fn args(&self, script: &Path, args: &[String]) -> Vec<OsString> {
+        let mut out: Vec<OsString> = match self {
+            Launch::Direct => vec![],
+            Launch::Debug { port, wait } => {
+                let mut out: Vec<OsString> = vec![
+                    "-m".into(),
+                    "debugpy".into(),
+                    "--listen".into(),
+                    format!("{}:{}", DEBUG_HOST, port).into(),
+                ];
+                if *wait {
+                    out.push("--wait-for-client".into());
+                }
+                out
+            }
+            Launch::Profile { output, .. } => {
+                vec!["-c".into(), PROFILE_BOOTSTRAP.into(), output.into()]
+            }
+        };
+        out.push(script.into());
+        out.extend(args.iter().map(OsString::from));
+        out
+    }
+
+    /// What to tell the user before the script starts, `None` when there
+    /// is nothing to say
+    pub fn instructions(&self) -> Option<String> {
+        match self {
+            Launch::Direct | Launch::Profile { .. } => None,
+            Launch::Debug { port, wait } => {
+                let start = if *wait {
+                    "the script starts once a debugger attaches"
+                } else {
+                    "the script is already running"
+                };
+                Some(format!(
+                    "debugpy is listening on {host}:{port}; {start}.\n\
+                     In VS Code, attach with a \"Python Debugger: Remote Attach\" configuration for host {host} and port {port}",
+                    host = DEBUG_HOST,
+                    port = port,
+                    start = start
+                ))
+            }
+        }
+    }
+}
+
+/// Arguments to the venv's python that print the summary of the profile
+/// `launch` wrote, `None` when it doesn't profile
+pub fn profile_summary_args(launch: &Launch) -> Option<Vec<OsString>> {
+    let Launch::Profile { output, sort, top } = launch else {
+        return None;
+    };
+    Some(vec![
+        "-c".into(),
+        PSTATS_SUMMARY.into(),
+        output.into(),
+        sort.to_string().into(),
+        top.to_string().into(),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(args: Vec<OsString>) -> String {
+        args.into_iter()
+            .map(|arg| arg.into_string().unwrap())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn test_launch_arguments() {
+        let script = Path::new("/project/main.py");
+        let args = vec!["--port".to_string(), "8000".to_string()];
+        assert_eq!(
+            words(Launch::Direct.args(script, &args)),
+            "/project/main.py --port 8000"
+        );
+        let debug = Launch::Debug {
+            port: DEFAULT_DEBUG_PORT,
+            wait: true,
+        };
+        assert_eq!(
+            words(debug.args(script, &args)),
+            "-m debugpy --listen 127.0.0.1:5678 --wait-for-client /project/main.py --port 8000"
+        );
+        let no_wait = Launch::Debug {
+            port: 9000,
+            wait: false,
+        };
+        assert_eq!(
+            words(no_wait.args(script, &[])),
+            "-m debugpy --listen 127.0.0.1:9000 /project/main.py"
+        );
+        assert_eq!(no_wait.required_package(), Some("debugpy"));
+        assert!(no_wait.instructions().unwrap().contains("port 9000"));
+        assert_eq!(Launch::Direct.instructions(), None);
+        assert_eq!(profile_summary_args(&debug), None);
+    }
+
+    #[test]
+    fn test_profile_arguments() {
+        let profile = Launch::Profile {
+            output: PathBuf::from("/project/.ppm/profile.out"),
+            sort: ProfileSort::from_name("tottime").unwrap(),
+            top: 5,
+        };
+        let args = profile.args(Path::new("/project/main.py"), &["-v".to_string()]);
+        assert_eq!(args[0], "-c");
+        assert!(
+            args[1]
+                .to_string_lossy()
+                .contains("profile.dump_stats(out)")
+        );
+        assert_eq!(
+            words(args[2..].to_vec()),
+            "/project/.ppm/profile.out /project/main.py -v"
+        );
+        assert_eq!(profile.required_package(), None);
+
+        let summary = profile_summary_args(&profile).unwrap();
+        assert_eq!(summary[1], PSTATS_SUMMARY);
+        assert_eq!(
+            words(summary[2..].to_vec()),
+            "/project/.ppm/profile.out tottime 5"
+        );
+        assert_eq!(ProfileSort::from_name("calls"), None);
+    }
+}
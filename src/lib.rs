@@ -0,0 +1,90 @@
+//! Project-management logic behind the `ppmm` CLI.
+//!
+//! Functions here take a [`ProjectContext`] or a [`Config`] and return
+//! typed results; the binary owns all printing, prompts and progress
+//! display. A minimal embedding:
+//!
+//! ```no_run
+//! use ppmm::{ProjectContext, update::plan_updates};
+//!
+//! let ctx = ProjectContext::load(std::path::Path::new("my-project"))?;
+//! let plan = plan_updates(&ctx, &[], true)?;
+//! for (name, from, to) in plan.updated() {
+//!     println!("{name}: {from} -> {to}");
+//! }
+//! # Ok::<(), ppmm::PpmError>(())
+//! ```
+
+#![warn(missing_docs)]
+
+pub mod activate;
+pub mod age;
+pub mod aliases;
+pub mod build;
+pub mod bump;
+pub mod bundle;
+pub mod changelog;
+pub mod compat;
+pub mod conflicts;
+pub mod constraints;
+pub mod context;
+pub mod coverage;
+pub mod downloads;
+pub mod entry_points;
+pub mod error;
+pub mod foreign;
+pub mod git;
+pub mod graph;
+pub mod global_config;
+pub mod history;
+pub mod hooks;
+pub mod ide;
+pub mod imports;
+pub mod index;
+pub mod init;
+pub mod install;
+pub mod interrupt;
+pub mod launch;
+pub mod license;
+pub mod listing;
+pub mod lock;
+pub mod main_script;
+pub mod markdown;
+pub mod marker;
+pub mod merge;
+pub mod metadata_cache;
+pub mod packages;
+pub mod paths;
+pub mod pip_cache;
+pub mod pip_config;
+pub mod plugins;
+pub mod preflight;
+pub mod presets;
+pub mod prompt;
+pub mod publish;
+pub mod report;
+pub mod requirement;
+pub mod requirements;
+pub mod resolver;
+pub mod resume;
+pub mod runner;
+pub mod scaffold;
+pub mod scripts;
+pub mod self_update;
+pub mod settings;
+pub mod state;
+pub mod stats;
+pub mod stdlib;
+pub mod suggest;
+pub mod test_runner;
+pub mod theme;
+pub mod update;
+pub mod venv;
+pub mod verify;
+pub mod version_pick;
+pub mod workspace;
+pub mod yanked;
+
+pub use context::ProjectContext;
+pub use error::PpmError;
+pub use settings::Config;
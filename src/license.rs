@@ -0,0 +1,147 @@
+//! Licenses `ppm new` and `ppm init` can give a project: the SPDX
+//! identifier under `[project]` and the LICENSE file with its text.
+//!
+//! ```toml
+//! [project]
+//! license = "MIT"
+//! ```
+//!
+//! The texts are embedded in ppm as published. Those with a copyright line
+//! of their own (MIT) have `[year]` and `[fullname]` filled in; the others
+//! keep theirs, which are instructions for source file headers.
+
+use crate::global_config::GlobalConfig;
+use crate::scaffold;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Name of the file the license text is written to, relative to the
+/// project root
+pub const LICENSE_FILE: &str = "LICENSE";
+
+/// The answer for no license
+pub const NO_LICENSE: &str = "none";
+
+/// A license ppm has the text of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct License {
+    /// SPDX identifier, like `MIT`
+    pub id: &'static str,
+    template: &'static str,
+}
+
+/// The licenses ppm can write, in the order they're offered
+pub const LICENSES: &[License] = &[
+    License {
+        id: "MIT",
+        template: include_str!("licenses/MIT.txt"),
+    },
+    License {
+        id: "Apache-2.0",
+        template: include_str!("licenses/Apache-2.0.txt"),
+    },
+    License {
+        id: "GPL-3.0",
+        template: include_str!("licenses/GPL-3.0.txt"),
+    },
+    License {
+        id: "Unlicense",
+        template: include_str!("licenses/Unlicense.txt"),
+    },
+];
+
+impl License {
+    /// The license with the SPDX identifier `id`, ignoring case
+    pub fn find(id: &str) -> Option<&'static License> {
+        LICENSES
+            .iter()
+            .find(|license| license.id.eq_ignore_ascii_case(id))
+    }
+
+    /// Whether the text names the copyright holder, so it needs an author
+    pub fn names_author(&self) -> bool {
+        self.template.contains("[fullname]")
+    }
+
+    /// The text with `year` and `author` filled in
+    pub fn render(&self, year: u64, author: &str) -> String {
+        scaffold::fill(
+            self.template,
+            &[("year", &year.to_string()), ("fullname", author)],
+        )
+    }
+}
+
+/// Fail, saying which are known, unless `id` is a license ppm has or
+/// [`NO_LICENSE`]
+pub fn validate_id(id: &str) -> Result<(), String> {
+    if id.eq_ignore_ascii_case(NO_LICENSE) || License::find(id).is_some() {
+        return Ok(());
+    }
+    let known: Vec<&str> = LICENSES.iter().map(|license| license.id).collect();
+    Err(format!(
+        "'{}' isn't a license ppm has; use one of {} or {}",
+        id,
+        known.join(", "),
+        NO_LICENSE
+    ))
+}
+
+/// The copyright holder: `author` in the global config.toml, else git's
+/// `user.name`
+pub fn author() -> Option<String> {
+    let configured = GlobalConfig::load().ok().and_then(|config| config.author);
+    configured.or_else(git_user_name)
+}
+
+fn git_user_name() -> Option<String> {
+    let output = Command::new("git")
+        .args(["config", "user.name"])
+        .output()
+        .ok()?;
+    let name = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !name.is_empty()).then_some(name)
+}
+
+/// The current year, in UTC
+pub fn current_year() -> u64 {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    crate::history::format_timestamp(secs)[..4]
+        .parse()
+        .unwrap_or(1970)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_fills_in_year_and_author() {
+        let mit = License::find("mit").unwrap();
+        assert!(mit.names_author());
+        let text = mit.render(2026, "Ada Lovelace");
+        assert!(text.starts_with("MIT License\n\nCopyright (c) 2026 Ada Lovelace\n"));
+        assert!(!text.contains("[year]") && !text.contains("[fullname]"));
+
+        // Texts without a copyright line are written as published
+        let apache = License::find("Apache-2.0").unwrap();
+        assert!(!apache.names_author());
+        assert!(
+            apache
+                .render(2026, "Ada")
+                .contains("[yyyy] [name of copyright owner]")
+        );
+        assert!(License::find("unlicense").is_some());
+    }
+
+    #[test]
+    fn test_validate_id() {
+        assert!(validate_id("GPL-3.0").is_ok());
+        assert!(validate_id("None").is_ok());
+        let err = validate_id("BSD-3-Clause").unwrap_err();
+        assert!(err.contains("MIT, Apache-2.0, GPL-3.0, Unlicense or none"));
+        assert!(current_year() >= 2024);
+    }
+}
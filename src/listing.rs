@@ -0,0 +1,482 @@
+//! The packages `ppm list` and `ppm info` show: the configured ones
+//! matching a filter, with what the venv has installed, in display order.
+//! `ppm list --installed` turns it around and shows what is in the venv,
+//! each with the [`Origin`] that explains it.
+//!
+//! The table, JSON and Markdown output are built from the same
+//! [`PackageRow`]s, and with `--outdated` the same [`OutdatedRow`]s, so
+//! they always list the same packages in the same order.
+
+use crate::age::{self, ReleaseDays};
+use crate::graph::DependencyGraph;
+use crate::markdown;
+use crate::packages::{compare_versions, normalize_pkg_name};
+use crate::settings::PackageSpec;
+use crate::workspace::glob_match;
+use crate::yanked::YankedPin;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+/// How many packages `ppm info` shows without `--full` or `--limit`
+pub const DEFAULT_INFO_LIMIT: usize = 10;
+
+/// How the venv's copy of a package compares to project.toml
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum InstallState {
+    /// Not in the venv
+    Missing,
+    /// In the venv at another version than the pinned one
+    Differs,
+    /// In the venv as configured
+    Installed,
+    /// The venv's packages aren't known
+    Unknown,
+}
+
+/// One configured package
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackageRow {
+    /// Name as written in project.toml
+    pub name: String,
+    /// What project.toml asks for
+    pub spec: PackageSpec,
+    /// Version in the venv, `None` when missing or unknown
+    pub installed: Option<String>,
+    /// How that compares to `spec`
+    pub state: InstallState,
+}
+
+/// Order of the rows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortKey {
+    /// By name, ignoring case
+    #[default]
+    Name,
+    /// By pinned version, oldest first; direct references last
+    Version,
+    /// Missing packages first, then version mismatches, then the rest
+    Installed,
+}
+
+impl SortKey {
+    /// The key called `name`, as on the command line
+    pub fn from_name(name: &str) -> Option<SortKey> {
+        match name {
+            "name" => Some(SortKey::Name),
+            "version" => Some(SortKey::Version),
+            "installed" => Some(SortKey::Installed),
+            _ => None,
+        }
+    }
+}
+
+/// Whether the package `name` matches `pattern`: a glob when it has `*`
+/// or `?`, a substring otherwise. Case, `-`, `_` and `.` don't matter.
+pub fn matches_filter(pattern: &str, name: &str) -> bool {
+    let fold = |s: &str| s.to_lowercase().replace(['_', '.'], "-");
+    let (pattern, name) = (fold(pattern), fold(name));
+    if pattern.contains(['*', '?']) {
+        glob_match(&pattern, &name)
+    } else {
+        name.contains(&pattern)
+    }
+}
+
+/// The packages of `packages` matching `filter`, ordered by `sort`.
+/// `installed` maps normalized names to versions, `None` when the venv's
+/// packages aren't known.
+pub fn collect(
+    packages: &HashMap<String, PackageSpec>,
+    installed: Option<&HashMap<String, String>>,
+    filter: Option<&str>,
+    sort: SortKey,
+) -> Vec<PackageRow> {
+    let mut rows: Vec<PackageRow> = packages
+        .iter()
+        .filter(|(name, _)| filter.is_none_or(|pattern| matches_filter(pattern, name)))
+        .map(|(name, spec)| {
+            let version = installed.and_then(|i| i.get(&normalize_pkg_name(name)));
+            let state = match (installed, version) {
+                (None, _) => InstallState::Unknown,
+                (Some(_), None) => InstallState::Missing,
+                (Some(_), Some(v)) if spec.version().is_some_and(|pinned| pinned != v) => {
+                    InstallState::Differs
+                }
+                (Some(_), Some(_)) => InstallState::Installed,
+            };
+            PackageRow {
+                name: name.clone(),
+                spec: spec.clone(),
+                installed: version.cloned(),
+                state,
+            }
+        })
+        .collect();
+
+    let by_version = |a: &PackageRow, b: &PackageRow| match (a.spec.version(), b.spec.version()) {
+        (Some(a), Some(b)) => compare_versions(a, b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    };
+    rows.sort_by(|a, b| {
+        let by_name = a.name.to_lowercase().cmp(&b.name.to_lowercase());
+        match sort {
+            SortKey::Name => by_name,
+            SortKey::Version => by_version(a, b).then(by_name),
+            SortKey::Installed => a.state.cmp(&b.state).then(by_name),
+        }
+    });
+    rows
+}
+
+/// Why a distribution is in the venv
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Origin {
+    /// Listed in project.toml
+    Configured,
+    /// Required by a configured package, directly or not
+    Transitive,
+    /// Neither, like a package installed by hand with pip
+    Unmanaged,
+}
+
+impl Origin {
+    /// The origin's name in `ppm list --installed` output
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Origin::Configured => "configured",
+            Origin::Transitive => "transitive",
+            Origin::Unmanaged => "unmanaged",
+        }
+    }
+}
+
+/// A distribution installed in the venv
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstalledRow {
+    /// Name as pip lists it
+    pub name: String,
+    /// Installed version
+    pub version: String,
+    /// Why it's there
+    pub origin: Origin,
+}
+
+/// Every distribution of `installed`, names and versions as pip lists
+/// them, with its [`Origin`]: configured when `configured` lists it, and
+/// transitive when one of those requires it according to `graph`. Sorted
+/// by name, ignoring case.
+pub fn classify_installed(
+    installed: &[(String, String)],
+    configured: &[String],
+    graph: &DependencyGraph,
+) -> Vec<InstalledRow> {
+    let configured_names: HashSet<String> = configured
+        .iter()
+        .map(|name| normalize_pkg_name(name))
+        .collect();
+    let reachable = graph.reachable(configured.iter().map(String::as_str));
+    let mut rows: Vec<InstalledRow> = installed
+        .iter()
+        .map(|(name, version)| {
+            let key = normalize_pkg_name(name);
+            let origin = if configured_names.contains(&key) {
+                Origin::Configured
+            } else if reachable.contains(&key) {
+                Origin::Transitive
+            } else {
+                Origin::Unmanaged
+            };
+            InstalledRow {
+                name: name.clone(),
+                version: version.clone(),
+                origin,
+            }
+        })
+        .collect();
+    rows.sort_by(|a, b| {
+        a.name
+            .to_lowercase()
+            .cmp(&b.name.to_lowercase())
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    rows
+}
+
+/// Columns of the Markdown tables of `ppm list --outdated` and
+/// `ppm update`
+pub const MARKDOWN_HEADERS: &[&str] = &["Package", "Current", "Latest", "Age", "Changelog"];
+
+/// A configured package with what `ppm list --outdated` found out about
+/// it from the index
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutdatedRow {
+    /// The package and its install state
+    pub package: PackageRow,
+    /// Latest version on the index, `None` when it isn't known or the
+    /// package isn't installed from the index
+    pub latest: Option<String>,
+    /// Upload days of the pinned and latest releases
+    pub released: Option<ReleaseDays>,
+    /// Age in days of the pinned release
+    pub age_days: Option<u64>,
+    /// The pinned release, when it has been yanked
+    pub yanked: Option<YankedPin>,
+    /// Link to the changelog, only looked up for Markdown output
+    pub changelog: Option<String>,
+}
+
+impl OutdatedRow {
+    /// The latest version for display: `-` for packages not from the
+    /// index, `?` when it couldn't be found
+    pub fn latest_text(&self) -> String {
+        match &self.latest {
+            Some(latest) => latest.clone(),
+            None if !self.package.spec.is_index() => "-".to_string(),
+            None => "?".to_string(),
+        }
+    }
+
+    /// The age of the pinned release like `5w`, with the same
+    /// placeholders as [`latest_text`](OutdatedRow::latest_text)
+    pub fn age_text(&self) -> String {
+        match self.age_days {
+            Some(days) => age::format_age(days),
+            None if !self.package.spec.is_index() => "-".to_string(),
+            None => "?".to_string(),
+        }
+    }
+}
+
+/// `rows` as a Markdown table of [`MARKDOWN_HEADERS`]
+pub fn outdated_markdown(rows: &[OutdatedRow]) -> String {
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            vec![
+                row.package.name.clone(),
+                row.package.spec.to_string(),
+                row.latest_text(),
+                row.age_text(),
+                row.changelog
+                    .as_deref()
+                    .map(|url| markdown::link("changelog", url))
+                    .unwrap_or_default(),
+            ]
+        })
+        .collect();
+    markdown::table(MARKDOWN_HEADERS, &cells, &[4])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::PathSource;
+
+    fn packages() -> HashMap<String, PackageSpec> {
+        [
+            ("requests", "2.31.0"),
+            ("Flask", "3.0.0"),
+            ("flask-cors", "4.0.0"),
+            ("numpy", "1.26.4"),
+            ("python_dateutil", "2.9.0"),
+        ]
+        .into_iter()
+        .map(|(name, version)| (name.to_string(), PackageSpec::Version(version.to_string())))
+        .chain([(
+            "mylib".to_string(),
+            PackageSpec::Path(PathSource {
+                path: "../mylib".to_string(),
+                editable: false,
+            }),
+        )])
+        .collect()
+    }
+
+    #[test]
+    fn test_outdated_markdown_matches_golden_file() {
+        let packages = packages();
+        let rows = collect(&packages, None, None, SortKey::Name);
+        let outdated: Vec<OutdatedRow> = rows
+            .into_iter()
+            .map(|package| {
+                let (latest, age_days, changelog) = match package.name.as_str() {
+                    "requests" => (
+                        Some("2.32.3"),
+                        Some(420),
+                        Some("https://github.com/psf/requests/blob/main/HISTORY.md"),
+                    ),
+                    "python_dateutil" => (
+                        Some("2.9.0"),
+                        Some(30),
+                        Some("https://example.com/notes?a|b"),
+                    ),
+                    _ => (None, None, None),
+                };
+                OutdatedRow {
+                    package,
+                    latest: latest.map(str::to_string),
+                    released: None,
+                    age_days,
+                    yanked: None,
+                    changelog: changelog.map(str::to_string),
+                }
+            })
+            .collect();
+        assert_eq!(
+            outdated_markdown(&outdated),
+            include_str!("../tests/fixtures/markdown/outdated.md")
+        );
+    }
+
+    fn names(rows: &[PackageRow]) -> Vec<&str> {
+        rows.iter().map(|row| row.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_filter_by_substring_or_glob() {
+        assert!(matches_filter("flask", "Flask-Cors"));
+        assert!(matches_filter("Python_Date", "python-dateutil"));
+        assert!(matches_filter("fl*k", "Flask"));
+        assert!(!matches_filter("fl*k", "flask-cors"));
+        assert!(matches_filter("n?mpy", "numpy"));
+
+        let packages = packages();
+        let rows = collect(&packages, None, Some("flask"), SortKey::Name);
+        assert_eq!(names(&rows), vec!["Flask", "flask-cors"]);
+        assert!(collect(&packages, None, Some("django*"), SortKey::Name).is_empty());
+    }
+
+    #[test]
+    fn test_sort_by_name_version_and_install_state() {
+        let packages = packages();
+        assert_eq!(
+            names(&collect(&packages, None, None, SortKey::Name)),
+            vec![
+                "Flask",
+                "flask-cors",
+                "mylib",
+                "numpy",
+                "python_dateutil",
+                "requests"
+            ]
+        );
+        assert_eq!(
+            names(&collect(&packages, None, None, SortKey::Version)),
+            vec![
+                "numpy",
+                "python_dateutil",
+                "requests",
+                "Flask",
+                "flask-cors",
+                "mylib"
+            ]
+        );
+
+        let installed: HashMap<String, String> = [
+            ("flask", "3.0.0"),
+            ("requests", "2.30.0"),
+            ("mylib", "0.1.0"),
+            ("numpy", "1.26.4"),
+            ("flask-cors", "4.0.0"),
+        ]
+        .into_iter()
+        .map(|(name, version)| (name.to_string(), version.to_string()))
+        .collect();
+        let rows = collect(&packages, Some(&installed), None, SortKey::Installed);
+        assert_eq!(
+            names(&rows),
+            vec![
+                "python_dateutil",
+                "requests",
+                "Flask",
+                "flask-cors",
+                "mylib",
+                "numpy"
+            ]
+        );
+        assert_eq!(rows[0].state, InstallState::Missing);
+        assert_eq!(rows[1].installed.as_deref(), Some("2.30.0"));
+        assert_eq!(rows[1].state, InstallState::Differs);
+        assert_eq!(rows[4].state, InstallState::Installed);
+    }
+
+    #[test]
+    fn test_classify_installed() {
+        use crate::graph::{InstalledPackage, parse_requires_dist};
+        let package = |name: &str, requires: &[&str]| InstalledPackage {
+            name: name.to_string(),
+            version: "1.0".to_string(),
+            direct: false,
+            requires: requires
+                .iter()
+                .filter_map(|value| parse_requires_dist(value))
+                .collect(),
+        };
+        let graph = DependencyGraph::new(vec![
+            package("Flask", &["Werkzeug>=3.0", "Jinja2>=3.1.2", "click>=8.1.3"]),
+            package("Werkzeug", &["MarkupSafe>=2.1.1"]),
+            package("Jinja2", &["MarkupSafe>=2.0"]),
+            package("MarkupSafe", &[]),
+            package("click", &[]),
+            package("black", &["click>=8.0.0", "pathspec>=0.9.0"]),
+            package("pathspec", &[]),
+            package("pip", &[]),
+            package("zope.interface", &["setuptools"]),
+            package("setuptools", &[]),
+        ]);
+        let installed: Vec<(String, String)> = [
+            "pip",
+            "flask",
+            "Werkzeug",
+            "jinja2",
+            "MarkupSafe",
+            "click",
+            "black",
+            "pathspec",
+            "zope.interface",
+            "setuptools",
+        ]
+        .iter()
+        .map(|name| (name.to_string(), "1.0".to_string()))
+        .collect();
+        // numpy is configured but not installed, so it has no row
+        let configured = vec![
+            "Flask".to_string(),
+            "zope_interface".to_string(),
+            "numpy".to_string(),
+        ];
+
+        let rows = classify_installed(&installed, &configured, &graph);
+        let origins: Vec<(&str, Origin)> = rows
+            .iter()
+            .map(|row| (row.name.as_str(), row.origin))
+            .collect();
+        assert_eq!(
+            origins,
+            vec![
+                ("black", Origin::Unmanaged),
+                ("click", Origin::Transitive),
+                ("flask", Origin::Configured),
+                ("jinja2", Origin::Transitive),
+                ("MarkupSafe", Origin::Transitive),
+                ("pathspec", Origin::Unmanaged),
+                ("pip", Origin::Unmanaged),
+                ("setuptools", Origin::Transitive),
+                ("Werkzeug", Origin::Transitive),
+                ("zope.interface", Origin::Configured),
+            ]
+        );
+        assert_eq!(Origin::Unmanaged.as_str(), "unmanaged");
+
+        // Without a graph nothing is known to be transitive
+        let rows = classify_installed(&installed, &configured, &DependencyGraph::default());
+        assert_eq!(
+            rows.iter()
+                .filter(|row| row.origin == Origin::Transitive)
+                .count(),
+            0
+        );
+    }
+}
@@ -0,0 +1,239 @@
+//! Advisory lock serializing ppm processes that modify the same project.
+//!
+//! The lock is an OS file lock on [`PROJECT_LOCK_FILE`], so it goes away
+//! with the process holding it however that process ends. The holder
+//! writes its pid into the file and empties it again on release, which
+//! lets a waiting process say who it waits for, and lets the next holder
+//! notice a lock left behind by a ppm process that was killed mid-way.
+
+use crate::error::PpmError;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Lock file created in the project root
+pub const PROJECT_LOCK_FILE: &str = ".ppm.lock";
+
+/// Seconds to wait for another ppm process before giving up
+pub const LOCK_TIMEOUT_ENV: &str = "PPM_LOCK_TIMEOUT";
+
+/// How long to wait for the lock when [`LOCK_TIMEOUT_ENV`] isn't set
+pub const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(600);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Exclusive lock on a project, released when dropped, including while
+/// unwinding from a panic
+#[derive(Debug)]
+pub struct ProjectLock {
+    file: File,
+    stale: Option<u32>,
+}
+
+impl ProjectLock {
+    /// Take the lock if no other process holds it, `None` otherwise
+    pub fn try_acquire(root: &Path) -> Result<Option<ProjectLock>, PpmError> {
+        let file = open(root)?;
+        match file.try_lock() {
+            Ok(()) => ProjectLock::claim(root, file).map(Some),
+            Err(TryLockError::WouldBlock) => Ok(None),
+            Err(TryLockError::Error(e)) => Err(lock_error(root, e)),
+        }
+    }
+
+    /// Take the lock, waiting for any other holder to release it
+    pub fn acquire(root: &Path) -> Result<ProjectLock, PpmError> {
+        let file = open(root)?;
+        file.lock().map_err(|e| lock_error(root, e))?;
+        ProjectLock::claim(root, file)
+    }
+
+    /// Take the lock, waiting at most `timeout` for another holder to
+    /// release it. `waiting` is called once, with the holder's pid when
+    /// the lock file names one, if the lock is taken.
+    pub fn acquire_timeout(
+        root: &Path,
+        timeout: Duration,
+        waiting: impl FnOnce(Option<u32>),
+    ) -> Result<ProjectLock, PpmError> {
+        if let Some(lock) = ProjectLock::try_acquire(root)? {
+            return Ok(lock);
+        }
+        waiting(holder(root));
+        let start = Instant::now();
+        loop {
+            if let Some(lock) = ProjectLock::try_acquire(root)? {
+                return Ok(lock);
+            }
+            if start.elapsed() >= timeout {
+                let who = match holder(root) {
+                    Some(pid) => format!("ppm process (pid {})", pid),
+                    None => "ppm process".to_string(),
+                };
+                return Err(PpmError::Other(format!(
+                    "Gave up after {}s waiting for another {} to release {}; set {} to wait longer",
+                    timeout.as_secs(),
+                    who,
+                    root.join(PROJECT_LOCK_FILE).display(),
+                    LOCK_TIMEOUT_ENV
+                )));
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Pid of a ppm process that held the lock before this one and
+    /// exited without releasing it
+    pub fn stale_pid(&self) -> Option<u32> {
+        self.stale
+    }
+
+    /// Record this process as the holder, noting a pid left behind by a
+    /// holder that is no longer running
+    fn claim(root: &Path, mut file: File) -> Result<ProjectLock, PpmError> {
+        let mut previous = String::new();
+        let _ = file.read_to_string(&mut previous);
+        let stale =
+            parse_pid(&previous).filter(|pid| *pid != std::process::id() && !process_alive(*pid));
+        file.set_len(0)
+            .and_then(|()| file.seek(SeekFrom::Start(0)))
+            .and_then(|_| write!(file, "{}", std::process::id()))
+            .and_then(|()| file.flush())
+            .map_err(|e| lock_error(root, e))?;
+        Ok(ProjectLock { file, stale })
+    }
+}
+
+impl Drop for ProjectLock {
+    fn drop(&mut self) {
+        // An empty file tells the next holder this one finished cleanly
+        let _ = self.file.set_len(0);
+    }
+}
+
+/// How long to wait for the lock: [`LOCK_TIMEOUT_ENV`] seconds, else
+/// [`DEFAULT_LOCK_TIMEOUT`]
+pub fn lock_timeout(var: Option<String>) -> Duration {
+    var.and_then(|secs| secs.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_LOCK_TIMEOUT)
+}
+
+/// Pid written into the lock file by its current or last holder
+pub fn holder(root: &Path) -> Option<u32> {
+    parse_pid(&std::fs::read_to_string(root.join(PROJECT_LOCK_FILE)).ok()?)
+}
+
+/// Whether a process with `pid` is running. Where that can't be told,
+/// every process counts as running.
+pub fn process_alive(pid: u32) -> bool {
+    if cfg!(target_os = "linux") {
+        return Path::new("/proc").join(pid.to_string()).exists();
+    }
+    true
+}
+
+fn parse_pid(contents: &str) -> Option<u32> {
+    contents.trim().parse().ok().filter(|pid| *pid != 0)
+}
+
+fn open(root: &Path) -> Result<File, PpmError> {
+    let path = root.join(PROJECT_LOCK_FILE);
+    OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .read(true)
+        .write(true)
+        .open(&path)
+        .map_err(|e| lock_error(root, e))
+}
+
+fn lock_error(root: &Path, e: std::io::Error) -> PpmError {
+    std::io::Error::new(
+        e.kind(),
+        format!(
+            "Failed to lock {}: {}",
+            root.join(PROJECT_LOCK_FILE).display(),
+            e
+        ),
+    )
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_is_exclusive_until_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let held = ProjectLock::try_acquire(dir.path()).unwrap();
+        assert!(held.is_some());
+        assert!(ProjectLock::try_acquire(dir.path()).unwrap().is_none());
+        assert_eq!(holder(dir.path()), Some(std::process::id()));
+
+        drop(held);
+        assert_eq!(holder(dir.path()), None);
+        assert!(ProjectLock::try_acquire(dir.path()).unwrap().is_some());
+        assert!(dir.path().join(PROJECT_LOCK_FILE).exists());
+    }
+
+    #[test]
+    fn test_waiting_names_the_holder_and_times_out() {
+        let dir = tempfile::tempdir().unwrap();
+        let held = ProjectLock::try_acquire(dir.path()).unwrap().unwrap();
+        let mut waited_for = None;
+        let err = ProjectLock::acquire_timeout(dir.path(), Duration::ZERO, |pid| waited_for = pid)
+            .unwrap_err();
+        assert_eq!(waited_for, Some(std::process::id()));
+        assert!(err.to_string().starts_with(&format!(
+            "Gave up after 0s waiting for another ppm process (pid {})",
+            std::process::id()
+        )));
+
+        let root = dir.path().to_path_buf();
+        let waiter = std::thread::spawn(move || {
+            ProjectLock::acquire_timeout(&root, Duration::from_secs(30), |_| {}).map(|_| ())
+        });
+        std::thread::sleep(Duration::from_millis(200));
+        assert!(!waiter.is_finished());
+        drop(held);
+        waiter.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_lock_is_released_on_panic() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_path_buf();
+        let panicked = std::thread::spawn(move || {
+            let _lock = ProjectLock::try_acquire(&root).unwrap().unwrap();
+            panic!("interrupted");
+        })
+        .join();
+        assert!(panicked.is_err());
+        let lock = ProjectLock::try_acquire(dir.path()).unwrap().unwrap();
+        assert_eq!(lock.stale_pid(), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_pid_of_a_dead_holder_is_stale() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let dead = child.id();
+        child.wait().unwrap();
+        std::fs::write(dir.path().join(PROJECT_LOCK_FILE), dead.to_string()).unwrap();
+
+        let lock = ProjectLock::try_acquire(dir.path()).unwrap().unwrap();
+        assert_eq!(lock.stale_pid(), Some(dead));
+        assert_eq!(holder(dir.path()), Some(std::process::id()));
+    }
+
+    #[test]
+    fn test_lock_timeout_from_the_environment() {
+        assert_eq!(lock_timeout(None), DEFAULT_LOCK_TIMEOUT);
+        assert_eq!(lock_timeout(Some("5".to_string())), Duration::from_secs(5));
+        assert_eq!(lock_timeout(Some("soon".to_string())), DEFAULT_LOCK_TIMEOUT);
+    }
+}
@@ -1,10 +1,19 @@
+mod completions;
+mod examples;
+mod man;
 mod ppm_functions;
+mod progress;
 mod project_managers;
-mod settings;
+mod table;
 mod utils;
 
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches, Parser};
+use is_terminal::IsTerminal;
+use ppmm::global_config::GlobalConfig;
+use ppmm::pip_config;
+use progress::ProgressMode;
 use project_managers::Action;
+use std::time::Duration;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 const ABOUT: &str = env!("CARGO_PKG_DESCRIPTION");
@@ -15,25 +24,211 @@ const AUTHOR: &str = env!("CARGO_PKG_AUTHORS");
 #[clap(author=AUTHOR, version=VERSION, about=ABOUT, long_about = None)]
 struct Cli {
     #[clap(subcommand)]
-    command: Action,
+    command: Option<Action>,
+    /// List built-in commands and installed ppm-<name> plugins
+    #[clap(long = "list", takes_value = false)]
+    list: bool,
+    /// List the aliases from project.toml and the global config.toml
+    #[clap(long = "list-aliases", takes_value = false)]
+    list_aliases: bool,
+    /// Hide progress bars and spinners
+    #[clap(short = 'q', long = "quiet", global = true, takes_value = false)]
+    quiet: bool,
+    /// Print machine-readable JSON where supported; messages go to stderr
+    #[clap(long = "json", global = true, takes_value = false)]
+    json: bool,
+    /// Progress reporting: bars on a terminal, or JSON events on stderr
+    #[clap(long = "progress", global = true, default_value = "auto", value_parser = ["auto", "json"])]
+    progress: String,
+    /// Skip network requests the command doesn't need, like the update check;
+    /// yanked-release checks use cached release data
+    #[clap(long = "offline", global = true, takes_value = false)]
+    offline: bool,
+    /// Seconds a request to the package index may take; a phase of many
+    /// lookups gets four times as long in all. pip's timeout setting, or 30,
+    /// when left out
+    #[clap(long = "timeout", global = true, value_name = "SECONDS", value_parser = pip_config::parse_timeout)]
+    timeout: Option<Duration>,
+    /// Regenerate the requirements file after commands that change the
+    /// packages, as if `sync-requirements = true` were set
+    #[clap(long = "sync-requirements", global = true, takes_value = false)]
+    sync_requirements: bool,
+    /// Run the command in this workspace member, by path or directory name
+    #[clap(long = "project", global = true, value_name = "MEMBER")]
+    project: Option<String>,
+    /// Run the command in every workspace member, one after another
+    #[clap(
+        long = "all",
+        global = true,
+        takes_value = false,
+        conflicts_with = "project"
+    )]
+    all: bool,
+    /// Read the venv's Python version only from pyvenv.cfg (for tests)
+    #[clap(long = "no-probe", global = true, hide = true, takes_value = false)]
+    no_probe: bool,
+}
+
+/// The CLI definition with each command's examples after its help, see
+/// [`examples::attach`]
+fn cli_command() -> clap::Command<'static> {
+    examples::attach(Cli::command(), "")
 }
 
 fn main() {
-    let cli = Cli::parse();
+    // First, so every message is styled; a broken config.toml is reported
+    // by the commands that read it
+    if let Ok(config) = GlobalConfig::load() {
+        match config.theme() {
+            Ok(theme) => utils::set_theme(theme),
+            Err(e) => utils::wprint(format!("Ignoring the theme in the global config: {}", e)),
+        }
+    }
+    let (args, aliases) = match ppm_functions::expand_aliases(&Cli::command()) {
+        Ok(expanded) => expanded,
+        Err(e) => {
+            utils::eprint(e.with_code());
+            std::process::exit(e.exit_code());
+        }
+    };
+    let matches = cli_command().get_matches_from(args);
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    utils::set_json_output(cli.json);
+    utils::set_no_probe(cli.no_probe);
+    utils::set_offline(cli.offline);
+    utils::set_timeout(cli.timeout);
+    utils::set_sync_requirements(cli.sync_requirements);
+    progress::init(if cli.progress == "json" {
+        ProgressMode::Json
+    } else if !cli.quiet && !cli.json && std::io::stdout().is_terminal() {
+        ProgressMode::Bars
+    } else {
+        ProgressMode::Hidden
+    });
+
+    let command = match &cli.command {
+        Some(command) => command,
+        None if cli.list => {
+            ppm_functions::list_commands(&cli_command());
+            return;
+        }
+        None if cli.list_aliases => {
+            ppm_functions::list_aliases(&aliases);
+            return;
+        }
+        None => {
+            // Fails the way a missing subcommand always has
+            Cli::command()
+                .subcommand_required(true)
+                .arg_required_else_help(true)
+                .get_matches();
+            return;
+        }
+    };
+
+    let prompter = utils::TerminalPrompter;
+    // `clean --all` predates workspaces and keeps its meaning
+    let all_members = cli.all && !matches!(command, Action::Clean(_));
+    let result = if cli.project.is_some() || all_members {
+        if command.in_workspace() {
+            ppm_functions::run_in_members(cli.project.as_deref(), || execute(command, &prompter))
+        } else {
+            Err(ppmm::PpmError::Other(
+                "--project and --all only apply to commands that work on a project".to_string(),
+            ))
+        }
+    } else {
+        execute(command, &prompter)
+    };
+    let quiet_after = matches!(
+        command,
+        Action::SelfManage(_)
+            | Action::Completions(_)
+            | Action::Complete(_)
+            | Action::Man(_)
+            | Action::External(_)
+    );
+    if result.is_ok() && !quiet_after {
+        ppm_functions::notify_update(cli.offline);
+    }
+
+    if let Err(e) = result {
+        utils::eprint(e.with_code());
+        if utils::json_output() {
+            println!("{}", e.to_json());
+        }
+        std::process::exit(e.exit_code());
+    }
+}
 
-    match &cli.command {
-        Action::New(project) => project.create_project(false),
-        Action::Init(project) => project.create_project(true),
-        Action::Add(add_proj) => add_proj.add_package(),
+/// Run `command` in the project around the current directory, recording
+/// it in the project's history when it changes the project
+fn execute(command: &Action, prompter: &utils::TerminalPrompter) -> Result<(), ppmm::PpmError> {
+    let history = command
+        .changed_project_root()
+        .map(utils::HistoryRecorder::start);
+    let result = match command {
+        Action::New(project) => project.create_project(false, prompter),
+        Action::Init(project) => project.create_project(true, prompter),
+        Action::Add(add_proj) => add_proj.add_package(prompter),
         Action::Rm(rp) => rp.remove_package(),
-        Action::Run(run) => run.run_script(),
-        Action::Install(installer) => installer.install_packages(),
+        Action::Run(run) => run.run_script(prompter),
+        Action::Install(installer) => installer.install_packages(prompter),
         Action::Build(builder) => builder.build_project(),
+        Action::Publish(publisher) => publisher.publish(),
         Action::Bump(bumper) => bumper.bump_version(),
-        Action::Info => ppm_functions::show_project_info(),
-        Action::Gen => ppm_functions::gen_requirements(),
-        Action::Start => ppm_functions::start_project(),
-        Action::Update(update) => update.update_package(),
-        Action::List => ppm_functions::list_packages(),
+        Action::Info(info) => info.show_info(),
+        Action::Env(env) => env.show_env(),
+        Action::Venv(venv) => venv.run(prompter),
+        Action::Gen(generate) => generate.run(),
+        Action::Start(start) => start.start_project(prompter),
+        Action::Test(tests) => tests.run_tests(prompter),
+        Action::Fmt(fmt) => fmt.run(ppmm::presets::Task::Fmt, prompter),
+        Action::Lint(lint) => lint.run(ppmm::presets::Task::Lint, prompter),
+        Action::Ide(command) => command.run(),
+        Action::Update(update) => update.update_package(prompter),
+        Action::List(list) => list.list_packages(),
+        Action::Lock => ppm_functions::lock_packages(),
+        Action::Sync(sync) => sync.sync_packages(),
+        Action::Bundle(bundle) => bundle.run(),
+        Action::Check(check) => check.check_project(),
+        Action::Hooks(hooks) => hooks.run(),
+        Action::Verify => ppm_functions::verify_packages(),
+        Action::Stats => ppm_functions::show_stats(),
+        Action::Graph(graph) => graph.run(),
+        Action::Rdeps(rdeps) => rdeps.run(),
+        Action::Cache(cache) => cache.run(prompter),
+        Action::Config(config) => config.run(),
+        Action::Clean(clean) => clean.clean_project(),
+        Action::History(show) => show.show_history(),
+        Action::CheckConfig => ppm_functions::check_config(),
+        Action::Explain(explain) => explain.run(),
+        Action::Examples(examples) => examples.show(),
+        Action::Report(report) => report.run(),
+        Action::Doctor => ppm_functions::doctor(),
+        Action::SelfManage(command) => command.run(),
+        Action::Completions(completions) => completions.print_completions(),
+        Action::Complete(helper) => {
+            helper.print_candidates();
+            Ok(())
+        }
+        Action::Man(pages) => pages.write(),
+        Action::External(args) => {
+            ppm_functions::run_plugin(args).unwrap_or_else(|| {
+                // No plugin either: the usual unknown subcommand error
+                Cli::command()
+                    .allow_external_subcommands(false)
+                    .get_matches();
+                Ok(())
+            })
+        }
+    };
+    let result = match result {
+        Ok(()) if command.changes_packages() => ppm_functions::sync_requirements(),
+        result => result,
+    };
+    if let Some(history) = history {
+        history.finish(&result);
     }
+    result
 }
@@ -0,0 +1,203 @@
+//! Finding the file `ppm start` should run when `main_script` doesn't
+//! point at one.
+//!
+//! Candidates come from the usual layouts, best first: `main.py`,
+//! `app.py`, `src/main.py` (what `ppm new` creates), `__main__.py` of a
+//! package under `src/` (the one named after the project first), and a
+//! lone top-level .py file that isn't tooling like setup.py.
+
+use crate::error::PpmError;
+use crate::prompt::Prompter;
+use std::fs;
+use std::path::Path;
+
+/// Top-level files that are never the entry point
+const NOT_ENTRY_POINTS: &[&str] = &["setup.py", "conftest.py", "noxfile.py", "fabfile.py"];
+
+/// `name` as a Python package directory: lowercase, with `-` and `.` as
+/// `_`
+fn package_name(name: &str) -> String {
+    name.to_lowercase().replace(['-', '.'], "_")
+}
+
+/// Whether the top-level `file` holds tests or tooling
+fn is_tooling(file: &str) -> bool {
+    NOT_ENTRY_POINTS.contains(&file) || file.starts_with("test_") || file.ends_with("_test.py")
+}
+
+/// Main script candidates among `files`, relative paths with `/`
+/// separators, best first. `project_name` ranks its own package's
+/// `__main__.py` above others.
+pub fn rank_candidates(files: &[&str], project_name: &str) -> Vec<String> {
+    let own_main = format!("src/{}/__main__.py", package_name(project_name));
+    let mut ranked: Vec<(u8, &str)> = files
+        .iter()
+        .filter_map(|&file| {
+            let rank = match file {
+                "main.py" => 0,
+                "app.py" => 1,
+                "src/main.py" => 2,
+                _ if file == own_main => 3,
+                _ if file.starts_with("src/")
+                    && file.ends_with("/__main__.py")
+                    && file.matches('/').count() == 2 =>
+                {
+                    4
+                }
+                _ => return None,
+            };
+            Some((rank, file))
+        })
+        .collect();
+    ranked.sort();
+
+    // A lone script at the top is the entry point of a one-file project
+    let top_level: Vec<&str> = files
+        .iter()
+        .copied()
+        .filter(|file| !file.contains('/') && file.ends_with(".py") && !is_tooling(file))
+        .collect();
+    if let [file] = top_level.as_slice()
+        && !ranked.iter().any(|(_, ranked)| ranked == file)
+    {
+        ranked.push((5, file));
+    }
+    ranked
+        .into_iter()
+        .map(|(_, file)| file.to_string())
+        .collect()
+}
+
+/// Names of the .py files in `dir`, or of its subdirectories when
+/// `dirs` is set
+fn entries(dir: &Path, dirs: bool) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return vec![];
+    };
+    entries
+        .flatten()
+        .filter(|entry| {
+            let path = entry.path();
+            if dirs {
+                path.is_dir()
+            } else {
+                path.is_file() && path.extension().is_some_and(|ext| ext == "py")
+            }
+        })
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .collect()
+}
+
+/// Main script candidates in the project at `root`, best first
+pub fn find_candidates(root: &Path, project_name: &str) -> Vec<String> {
+    let mut files = entries(root, false);
+    let src = root.join("src");
+    files.extend(
+        entries(&src, false)
+            .into_iter()
+            .map(|file| format!("src/{}", file)),
+    );
+    for package in entries(&src, true) {
+        if src.join(&package).join("__main__.py").is_file() {
+            files.push(format!("src/{}/__main__.py", package));
+        }
+    }
+    files.sort();
+    let files: Vec<&str> = files.iter().map(String::as_str).collect();
+    rank_candidates(&files, project_name)
+}
+
+/// The candidate to use: the only one, or the one picked with `prompter`
+/// when there are several. `None` when there are no candidates; an error
+/// when nobody picks one, so a non-interactive run never guesses.
+pub fn choose(candidates: &[String], prompter: &dyn Prompter) -> Result<Option<String>, PpmError> {
+    match candidates {
+        [] => Ok(None),
+        [only] => Ok(Some(only.clone())),
+        _ => {
+            let options: Vec<&str> = candidates.iter().map(String::as_str).collect();
+            match prompter.select("Which file should `ppm start` run?", &options) {
+                Some(i) => Ok(Some(candidates[i].clone())),
+                None => Err(PpmError::Config(format!(
+                    "Found several possible main scripts: {}; set main_script in project.toml to one of them",
+                    candidates.join(", ")
+                ))),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prompt::ScriptedPrompter;
+
+    #[test]
+    fn test_rank_candidates() {
+        assert_eq!(
+            rank_candidates(&["app.py", "main.py", "setup.py", "utils.py"], "demo"),
+            vec!["main.py", "app.py"]
+        );
+        assert_eq!(
+            rank_candidates(
+                &[
+                    "src/other/__main__.py",
+                    "src/my_tool/__main__.py",
+                    "src/my_tool/cli/__main__.py",
+                    "src/main.py",
+                ],
+                "My-Tool"
+            ),
+            vec![
+                "src/main.py",
+                "src/my_tool/__main__.py",
+                "src/other/__main__.py"
+            ]
+        );
+        // A lone script counts, tooling and tests don't
+        assert_eq!(
+            rank_candidates(&["setup.py", "test_cli.py", "cli.py"], "demo"),
+            vec!["cli.py"]
+        );
+        assert!(rank_candidates(&["cli.py", "helpers.py"], "demo").is_empty());
+    }
+
+    #[test]
+    fn test_find_candidates_in_tree() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        assert!(find_candidates(root, "demo").is_empty());
+
+        fs::create_dir_all(root.join("src/demo")).unwrap();
+        fs::write(root.join("src/demo/__main__.py"), "").unwrap();
+        fs::write(root.join("src/demo/__init__.py"), "").unwrap();
+        fs::write(root.join("setup.py"), "").unwrap();
+        assert_eq!(find_candidates(root, "demo"), vec!["src/demo/__main__.py"]);
+
+        fs::write(root.join("app.py"), "").unwrap();
+        fs::create_dir_all(root.join("main.py")).unwrap();
+        assert_eq!(
+            find_candidates(root, "demo"),
+            vec!["app.py", "src/demo/__main__.py"]
+        );
+    }
+
+    #[test]
+    fn test_choose_asks_only_between_several() {
+        let none = ScriptedPrompter::new(&[]);
+        assert_eq!(choose(&[], &none).unwrap(), None);
+        let one = vec!["app.py".to_string()];
+        assert_eq!(choose(&one, &none).unwrap().as_deref(), Some("app.py"));
+        assert_eq!(none.transcript(), "");
+
+        let several = vec!["main.py".to_string(), "app.py".to_string()];
+        let prompter = ScriptedPrompter::new(&["2"]);
+        assert_eq!(
+            choose(&several, &prompter).unwrap().as_deref(),
+            Some("app.py")
+        );
+        let err = choose(&several, &none).unwrap_err();
+        assert_eq!(err.exit_code(), 2);
+        assert!(err.to_string().contains("main.py, app.py"));
+    }
+}
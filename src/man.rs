@@ -0,0 +1,225 @@
+//! Roff man pages for ppmm and each of its subcommands.
+//!
+//! The pages come from the same CLI definition as `--help`, rendered with
+//! `clap_mangen`, plus an EXAMPLES section from [`crate::examples`] and an
+//! EXIT STATUS table from [`ppmm::error::EXIT_CODES`]. Packagers write them
+//! with the hidden `ppmm man --out-dir <dir>`.
+
+use crate::examples::{self, Example};
+use crate::utils::iprint;
+use clap::{Args, Command, CommandFactory};
+use clap_mangen::Man;
+use clap_mangen::roff::{Roff, bold, roman};
+use ppmm::PpmError;
+use ppmm::error::{EXIT_CODES, ErrorCode};
+use std::path::{Path, PathBuf};
+
+#[derive(Args, Debug)]
+pub struct ManPages {
+    /// Directory to write ppmm.1 and a ppmm-<command>.1 per subcommand to
+    #[clap(long = "out-dir", value_name = "DIR")]
+    pub out_dir: PathBuf,
+}
+
+impl ManPages {
+    pub fn write(&self) -> Result<(), PpmError> {
+        let written = write_pages(crate::Cli::command(), &self.out_dir)?;
+        iprint(format!(
+            "Wrote {} man pages to {}",
+            written.len(),
+            self.out_dir.display()
+        ));
+        Ok(())
+    }
+}
+
+/// Write a page for `cmd` and for every visible subcommand, nested ones
+/// too, to `out_dir`; returns the files written
+pub fn write_pages(cmd: Command<'static>, out_dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(out_dir)?;
+    let mut cmd = without_help_subcommand(cmd);
+    // Copies global options like --json into each subcommand's page
+    cmd.build();
+    let mut pages = vec![];
+    collect(&cmd, "", &mut pages);
+    let mut written = vec![];
+    for (path, page) in pages {
+        let file = out_dir.join(format!("{}.1", page_name(&path)));
+        std::fs::write(&file, render_page(page, &path)?)?;
+        written.push(file);
+    }
+    Ok(written)
+}
+
+/// `ppmm` for the top-level command, `ppmm-cache-info` for `cache info`
+fn page_name(path: &str) -> String {
+    std::iter::once("ppmm")
+        .chain(path.split_whitespace())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// `help` would get a page and a SUBCOMMANDS entry but means nothing in one
+fn without_help_subcommand(mut cmd: Command<'static>) -> Command<'static> {
+    for sub in cmd.get_subcommands_mut() {
+        *sub = without_help_subcommand(std::mem::take(sub));
+    }
+    cmd.disable_help_subcommand(true)
+}
+
+/// `cmd` and its visible subcommands with their paths, like `cache info`
+fn collect(cmd: &Command<'static>, path: &str, out: &mut Vec<(String, Command<'static>)>) {
+    out.push((path.to_string(), cmd.clone()));
+    for sub in cmd.get_subcommands().filter(|sub| !sub.is_hide_set()) {
+        let sub_path = if path.is_empty() {
+            sub.get_name().to_string()
+        } else {
+            format!("{} {}", path, sub.get_name())
+        };
+        collect(sub, &sub_path, out);
+    }
+}
+
+/// The page for the command at `path`, in the section order `clap_mangen`
+/// uses, with EXAMPLES and EXIT STATUS before VERSION and AUTHORS
+fn render_page(cmd: Command<'static>, path: &str) -> std::io::Result<Vec<u8>> {
+    let name = page_name(path);
+    // The page's name is also what SUBCOMMANDS refers to other pages by
+    let cmd = cmd.name(&name);
+    let has_options = cmd.get_arguments().any(|arg| !arg.is_hide_set());
+    let has_subcommands = cmd.get_subcommands().any(|sub| !sub.is_hide_set());
+    let has_version = cmd.get_version().is_some();
+    let has_author = cmd.get_author().is_some();
+    let man = Man::new(cmd)
+        .title(name.to_uppercase())
+        .source(format!("ppmm {}", crate::VERSION))
+        .manual("ppmm Manual");
+
+    let mut out = vec![];
+    man.render_title(&mut out)?;
+    man.render_name_section(&mut out)?;
+    man.render_synopsis_section(&mut out)?;
+    man.render_description_section(&mut out)?;
+    if has_options {
+        man.render_options_section(&mut out)?;
+    }
+    if has_subcommands {
+        man.render_subcommands_section(&mut out)?;
+    }
+    let shown: Vec<&Example> = if path.is_empty() {
+        examples::matching(None)
+    } else {
+        examples::for_command(path).collect()
+    };
+    examples_section(&shown).to_writer(&mut out)?;
+    exit_status_section(path.is_empty()).to_writer(&mut out)?;
+    if has_version {
+        man.render_version_section(&mut out)?;
+    }
+    if has_author {
+        man.render_authors_section(&mut out)?;
+    }
+    Ok(out)
+}
+
+fn examples_section(shown: &[&Example]) -> Roff {
+    let mut roff = Roff::new();
+    roff.control("SH", ["EXAMPLES"]);
+    for example in shown {
+        roff.control("PP", [])
+            .text([roman(example.task)])
+            .control("RS", ["4"])
+            .text([bold(example.line)])
+            .control("RE", []);
+    }
+    roff
+}
+
+/// The exit-code table, and on the top-level page the PPM-xxx error codes
+fn exit_status_section(with_error_codes: bool) -> Roff {
+    let mut roff = Roff::new();
+    roff.control("SH", ["EXIT STATUS"]);
+    for (code, meaning) in EXIT_CODES {
+        roff.control("TP", [])
+            .text([bold(code.to_string())])
+            .text([roman(*meaning)]);
+    }
+    roff.control("PP", []).text([roman(
+        "When a script ppmm runs exits with a non-zero status, ppmm exits with that same status.",
+    )]);
+    if with_error_codes {
+        roff.control("SH", ["ERROR CODES"]);
+        roff.text([roman(
+            "Errors end with a stable code, which ppmm explain describes at length:",
+        )]);
+        for code in ErrorCode::ALL {
+            roff.control("TP", [])
+                .text([bold(code.to_string())])
+                .text([roman(code.explanation().title)]);
+        }
+    }
+    roff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let written = write_pages(crate::Cli::command(), dir.path()).unwrap();
+        let names: Vec<String> = written
+            .iter()
+            .map(|file| file.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        for name in [
+            "ppmm.1",
+            "ppmm-add.1",
+            "ppmm-cache-info.1",
+            "ppmm-self-update.1",
+        ] {
+            assert!(names.iter().any(|written| written == name), "{}", name);
+        }
+        // One page per visible subcommand and one for ppmm itself
+        let mut visible = vec![];
+        collect(
+            &without_help_subcommand(crate::Cli::command()),
+            "",
+            &mut visible,
+        );
+        assert_eq!(written.len(), visible.len());
+        // Hidden commands and clap's help subcommand get no page
+        assert!(!names.iter().any(|name| name.contains("_complete")));
+        assert!(!names.iter().any(|name| name.contains("help")));
+
+        for file in &written {
+            let page = std::fs::read_to_string(file).unwrap();
+            for header in [
+                ".SH NAME",
+                ".SH SYNOPSIS",
+                ".SH DESCRIPTION",
+                ".SH EXAMPLES",
+                ".SH \"EXIT STATUS\"",
+            ] {
+                assert!(page.contains(header), "{} in {}", header, file.display());
+            }
+            let title = file.file_stem().unwrap().to_string_lossy().to_uppercase();
+            assert!(
+                page.contains(&format!(".TH {} 1", title)),
+                "{}",
+                file.display()
+            );
+        }
+
+        let add = std::fs::read_to_string(dir.path().join("ppmm-add.1")).unwrap();
+        assert!(add.contains("ppmm add requests"));
+        assert!(add.contains("Network failure talking to the package index"));
+        let cache = std::fs::read_to_string(dir.path().join("ppmm-cache.1")).unwrap();
+        assert!(cache.contains("ppmm\\-cache\\-info(1)"));
+        let top = std::fs::read_to_string(dir.path().join("ppmm.1")).unwrap();
+        assert!(top.contains(".SH \"ERROR CODES\""));
+        assert!(top.contains("PPM\\-017"));
+        assert!(!add.contains("ERROR CODES"));
+    }
+}
@@ -0,0 +1,130 @@
+//! GitHub-flavored Markdown tables, for `--format markdown` output that
+//! can be pasted into an issue or piped into `gh pr create --body-file -`.
+//!
+//! Callers build the cells from the same rows their plain table and JSON
+//! output use; this module only escapes and lays them out.
+
+/// `text` made safe for a table cell: pipes are escaped, backslashes
+/// doubled so they can't escape the pipe after them, and line breaks
+/// become spaces since a cell can't span lines
+pub fn escape_cell(text: &str) -> String {
+    text.split(['\r', '\n'])
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace('\\', "\\\\")
+        .replace('|', "\\|")
+}
+
+/// A link labelled `label` to `url`, with the characters that would end
+/// the link or the cell percent-encoded
+pub fn link(label: &str, url: &str) -> String {
+    let mut encoded = String::with_capacity(url.len());
+    for c in url.trim().chars() {
+        match c {
+            ' ' => encoded.push_str("%20"),
+            '(' => encoded.push_str("%28"),
+            ')' => encoded.push_str("%29"),
+            '|' => encoded.push_str("%7C"),
+            '<' => encoded.push_str("%3C"),
+            '>' => encoded.push_str("%3E"),
+            _ => encoded.push(c),
+        }
+    }
+    format!(
+        "[{}]({})",
+        escape_cell(label).replace(['[', ']'], ""),
+        encoded
+    )
+}
+
+/// A table with `headers` and `rows`, columns padded to line up in the
+/// source. Cells are escaped with [`escape_cell`] except those of the
+/// columns in `raw`, which already hold Markdown like a [`link`].
+pub fn table(headers: &[&str], rows: &[Vec<String>], raw: &[usize]) -> String {
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            row.iter()
+                .enumerate()
+                .map(|(column, cell)| {
+                    if raw.contains(&column) {
+                        cell.clone()
+                    } else {
+                        escape_cell(cell)
+                    }
+                })
+                .collect()
+        })
+        .collect();
+    let headers: Vec<String> = headers.iter().map(|header| escape_cell(header)).collect();
+    let widths: Vec<usize> = (0..headers.len())
+        .map(|column| {
+            cells
+                .iter()
+                .filter_map(|row| row.get(column))
+                .chain([&headers[column]])
+                .map(|cell| cell.chars().count())
+                .max()
+                .unwrap_or(0)
+                .max(3)
+        })
+        .collect();
+    let line = |row: &[String]| {
+        let padded: Vec<String> = widths
+            .iter()
+            .enumerate()
+            .map(|(column, width)| {
+                let cell = row.get(column).map(String::as_str).unwrap_or_default();
+                format!("{:<width$}", cell, width = width)
+            })
+            .collect();
+        format!("| {} |\n", padded.join(" | "))
+    };
+
+    let mut out = line(&headers);
+    let rule: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+    out.push_str(&line(&rule));
+    for row in &cells {
+        out.push_str(&line(row));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_cell() {
+        assert_eq!(escape_cell("plain"), "plain");
+        assert_eq!(escape_cell("a|b"), "a\\|b");
+        assert_eq!(escape_cell("back\\|slash"), "back\\\\\\|slash");
+        assert_eq!(escape_cell("two\r\nlines\n"), "two lines");
+    }
+
+    #[test]
+    fn test_link() {
+        assert_eq!(
+            link("changelog", "https://example.com/a b/(x)|y"),
+            "[changelog](https://example.com/a%20b/%28x%29%7Cy)"
+        );
+        assert_eq!(link("[v|2]", "https://x.org"), "[v\\|2](https://x.org)");
+    }
+
+    #[test]
+    fn test_table() {
+        let rows = vec![
+            vec!["requests".to_string(), "[c](https://x.org)".to_string()],
+            vec!["a|b".to_string(), String::new()],
+        ];
+        assert_eq!(
+            table(&["Package", "Link"], &rows, &[1]),
+            "| Package  | Link               |\n\
+             | -------- | ------------------ |\n\
+             | requests | [c](https://x.org) |\n\
+             | a\\|b     |                    |\n"
+        );
+        assert_eq!(table(&["A"], &[], &[]), "| A   |\n| --- |\n");
+    }
+}
@@ -0,0 +1,515 @@
+//! PEP 508 environment markers such as `sys_platform == "win32"`, and a
+//! small evaluator for them.
+//!
+//! Markers are parsed into a [`Marker`] tree and evaluated against a
+//! [`MarkerEnv`], normally queried once from the project's interpreter.
+
+use crate::error::PpmError;
+use crate::packages::compare_versions;
+use crate::runner::{self, CommandRunner, RunOptions};
+use serde::Deserialize;
+use std::cmp::Ordering;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Prints the marker environment as JSON, following the definitions in
+/// PEP 508
+const ENV_SCRIPT: &str = r#"
+import json, os, platform, sys
+impl = sys.implementation
+version = "{0.major}.{0.minor}.{0.micro}".format(impl.version)
+if impl.version.releaselevel != "final":
+    version += impl.version.releaselevel[0] + str(impl.version.serial)
+print(json.dumps({
+    "os_name": os.name,
+    "sys_platform": sys.platform,
+    "platform_machine": platform.machine(),
+    "platform_python_implementation": platform.python_implementation(),
+    "platform_release": platform.release(),
+    "platform_system": platform.system(),
+    "platform_version": platform.version(),
+    "python_version": ".".join(platform.python_version_tuple()[:2]),
+    "python_full_version": platform.python_version(),
+    "implementation_name": impl.name,
+    "implementation_version": version,
+}))
+"#;
+
+/// Values of the marker variables for one interpreter
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct MarkerEnv {
+    /// `os.name`, e.g. `posix`
+    pub os_name: String,
+    /// `sys.platform`, e.g. `linux` or `win32`
+    pub sys_platform: String,
+    /// `platform.machine()`, e.g. `x86_64`
+    pub platform_machine: String,
+    /// `platform.python_implementation()`, e.g. `CPython`
+    pub platform_python_implementation: String,
+    /// `platform.release()`
+    pub platform_release: String,
+    /// `platform.system()`, e.g. `Linux`
+    pub platform_system: String,
+    /// `platform.version()`
+    pub platform_version: String,
+    /// Major and minor version, e.g. `3.12`
+    pub python_version: String,
+    /// Full version, e.g. `3.12.1`
+    pub python_full_version: String,
+    /// `sys.implementation.name`, e.g. `cpython`
+    pub implementation_name: String,
+    /// Version of the implementation
+    pub implementation_version: String,
+}
+
+impl MarkerEnv {
+    /// Ask `python` for its marker environment
+    pub fn query(runner: &dyn CommandRunner, python: &Path) -> Result<MarkerEnv, PpmError> {
+        let output = runner
+            .run(
+                python.as_os_str(),
+                &runner::args(["-c", ENV_SCRIPT]),
+                &RunOptions::default(),
+            )
+            .map_err(|e| PpmError::Venv(format!("Failed to run {}: {}", python.display(), e)))?;
+        if !output.success() {
+            return Err(PpmError::Venv(format!(
+                "Failed to read the marker environment from {}: {}",
+                python.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        serde_json::from_slice(&output.stdout).map_err(|e| {
+            PpmError::Venv(format!(
+                "Unexpected marker environment from {}: {}",
+                python.display(),
+                e
+            ))
+        })
+    }
+
+    /// Value of the variable `name`; `extra` is always empty since
+    /// configured packages are not installed as someone's extra
+    fn get(&self, name: &str) -> Option<&str> {
+        let value = match name {
+            "os_name" => &self.os_name,
+            "sys_platform" => &self.sys_platform,
+            "platform_machine" => &self.platform_machine,
+            "platform_python_implementation" => &self.platform_python_implementation,
+            "platform_release" => &self.platform_release,
+            "platform_system" => &self.platform_system,
+            "platform_version" => &self.platform_version,
+            "python_version" => &self.python_version,
+            "python_full_version" => &self.python_full_version,
+            "implementation_name" => &self.implementation_name,
+            "implementation_version" => &self.implementation_version,
+            "extra" => return Some(""),
+            _ => return None,
+        };
+        Some(value)
+    }
+}
+
+/// Marker variables that hold versions and compare as such
+const VERSION_VARIABLES: [&str; 3] = [
+    "python_version",
+    "python_full_version",
+    "implementation_version",
+];
+
+/// Comparison operators, longest first so `===` wins over `==`
+const OPERATORS: [&str; 8] = ["===", "~=", "==", "!=", "<=", ">=", "<", ">"];
+
+/// A parsed marker expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Marker {
+    /// Both sides hold
+    And(Box<Marker>, Box<Marker>),
+    /// Either side holds
+    Or(Box<Marker>, Box<Marker>),
+    /// `left op right`, e.g. `python_version >= "3.8"`
+    Compare {
+        /// Left operand
+        left: Operand,
+        /// One of `<`, `<=`, `==`, `!=`, `>=`, `>`, `~=`, `===`, `in`, `not in`
+        op: String,
+        /// Right operand
+        right: Operand,
+    },
+}
+
+/// One side of a comparison
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    /// A marker variable such as `sys_platform`
+    Variable(String),
+    /// A quoted string
+    Literal(String),
+}
+
+impl Marker {
+    /// Parse a marker expression
+    pub fn parse(marker: &str) -> Result<Marker, String> {
+        let tokens = tokenize(marker)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let parsed = parser.or()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(parsed),
+            Some(token) => Err(format!("unexpected {} in marker", token)),
+        }
+    }
+
+    /// Whether the marker holds in `env`
+    pub fn evaluate(&self, env: &MarkerEnv) -> bool {
+        match self {
+            Marker::And(a, b) => a.evaluate(env) && b.evaluate(env),
+            Marker::Or(a, b) => a.evaluate(env) || b.evaluate(env),
+            Marker::Compare { left, op, right } => {
+                let version = [left, right]
+                    .iter()
+                    .any(|o| matches!(o, Operand::Variable(v) if VERSION_VARIABLES.contains(&v.as_str())));
+                compare(left.value(env), op, right.value(env), version)
+            }
+        }
+    }
+}
+
+impl FromStr for Marker {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Marker, String> {
+        Marker::parse(s)
+    }
+}
+
+impl Operand {
+    fn value<'a>(&'a self, env: &'a MarkerEnv) -> &'a str {
+        match self {
+            // Variables are checked while parsing
+            Operand::Variable(name) => env.get(name).unwrap_or_default(),
+            Operand::Literal(value) => value,
+        }
+    }
+}
+
+/// Parse `marker` and evaluate it in `env`
+pub fn evaluate(marker: &str, env: &MarkerEnv) -> Result<bool, String> {
+    Ok(Marker::parse(marker)?.evaluate(env))
+}
+
+/// Whether `version` satisfies the clause `op spec`, like `>= 2.0` or
+/// `== 2.*`
+pub fn version_matches(version: &str, op: &str, spec: &str) -> bool {
+    compare(version, op, spec, true)
+}
+
+fn compare(left: &str, op: &str, right: &str, version: bool) -> bool {
+    match op {
+        "in" => right.contains(left),
+        "not in" => !right.contains(left),
+        "===" => left == right,
+        "~=" => version && compatible(left, right),
+        "==" | "!=" if version && right.ends_with(".*") => {
+            let prefix = &right[..right.len() - 2];
+            let matches = left == prefix || left.starts_with(&format!("{}.", prefix));
+            matches == (op == "==")
+        }
+        _ => {
+            let ord = if version {
+                compare_versions(left, right)
+            } else {
+                left.cmp(right)
+            };
+            match op {
+                "==" => ord == Ordering::Equal,
+                "!=" => ord != Ordering::Equal,
+                "<" => ord == Ordering::Less,
+                "<=" => ord != Ordering::Greater,
+                ">" => ord == Ordering::Greater,
+                ">=" => ord != Ordering::Less,
+                _ => false,
+            }
+        }
+    }
+}
+
+/// `version ~= spec`: at least `spec`, and the same release series, so
+/// `3.9.2 ~= 3.9.1` but not `3.10 ~= 3.9.1`
+fn compatible(version: &str, spec: &str) -> bool {
+    let segments: Vec<&str> = spec.split('.').collect();
+    if segments.len() < 2 {
+        return false;
+    }
+    let series = segments[..segments.len() - 1].join(".");
+    let in_series = version
+        .split('.')
+        .zip(series.split('.'))
+        .all(|(v, s)| compare_versions(v, s) == Ordering::Equal)
+        && version.split('.').count() >= segments.len() - 1;
+    in_series && compare_versions(version, spec) != Ordering::Less
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Op(String),
+    Variable(String),
+    Literal(String),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Open => write!(f, "'('"),
+            Token::Close => write!(f, "')'"),
+            Token::And => write!(f, "'and'"),
+            Token::Or => write!(f, "'or'"),
+            Token::Op(op) => write!(f, "'{}'", op),
+            Token::Variable(name) => write!(f, "'{}'", name),
+            Token::Literal(value) => write!(f, "\"{}\"", value),
+        }
+    }
+}
+
+fn tokenize(marker: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut rest = marker.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let len = if c == '(' || c == ')' {
+            tokens.push(if c == '(' { Token::Open } else { Token::Close });
+            1
+        } else if c == '"' || c == '\'' {
+            let end = rest[1..]
+                .find(c)
+                .ok_or_else(|| "unterminated string in marker".to_string())?;
+            tokens.push(Token::Literal(rest[1..end + 1].to_string()));
+            end + 2
+        } else if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push(Token::Op(op.to_string()));
+            op.len()
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let word = identifier(rest);
+            let mut len = word.len();
+            match word {
+                "and" => tokens.push(Token::And),
+                "or" => tokens.push(Token::Or),
+                "in" => tokens.push(Token::Op("in".to_string())),
+                "not" => {
+                    let after = rest[len..].trim_start();
+                    if identifier(after) != "in" {
+                        return Err("expected 'in' after 'not' in marker".to_string());
+                    }
+                    tokens.push(Token::Op("not in".to_string()));
+                    len = rest.len() - after.len() + 2;
+                }
+                _ if MarkerEnv::default().get(word).is_none() => {
+                    return Err(format!("unknown marker variable '{}'", word));
+                }
+                _ => tokens.push(Token::Variable(word.to_string())),
+            }
+            len
+        } else {
+            return Err(format!("unexpected '{}' in marker", c));
+        };
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+/// The leading run of letters, digits and underscores
+fn identifier(s: &str) -> &str {
+    let end = s
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(s.len());
+    &s[..end]
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.tokens.get(self.pos) == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn or(&mut self) -> Result<Marker, String> {
+        let mut marker = self.and()?;
+        while self.eat(&Token::Or) {
+            marker = Marker::Or(Box::new(marker), Box::new(self.and()?));
+        }
+        Ok(marker)
+    }
+
+    fn and(&mut self) -> Result<Marker, String> {
+        let mut marker = self.expr()?;
+        while self.eat(&Token::And) {
+            marker = Marker::And(Box::new(marker), Box::new(self.expr()?));
+        }
+        Ok(marker)
+    }
+
+    fn expr(&mut self) -> Result<Marker, String> {
+        if self.eat(&Token::Open) {
+            let marker = self.or()?;
+            if !self.eat(&Token::Close) {
+                return Err("unbalanced '(' in marker".to_string());
+            }
+            return Ok(marker);
+        }
+        let left = self.operand()?;
+        let op = match self.next() {
+            Some(Token::Op(op)) => op,
+            Some(token) => return Err(format!("expected an operator, found {}", token)),
+            None => return Err("expected an operator at end of marker".to_string()),
+        };
+        let right = self.operand()?;
+        if matches!((&left, &right), (Operand::Literal(_), Operand::Literal(_))) {
+            return Err("a marker comparison needs a variable on one side".to_string());
+        }
+        Ok(Marker::Compare { left, op, right })
+    }
+
+    fn operand(&mut self) -> Result<Operand, String> {
+        match self.next() {
+            Some(Token::Variable(name)) => Ok(Operand::Variable(name)),
+            Some(Token::Literal(value)) => Ok(Operand::Literal(value)),
+            Some(token) => Err(format!(
+                "expected a variable or quoted string, found {}",
+                token
+            )),
+            None => Err("marker ends early".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(sys_platform: &str, python_full_version: &str, machine: &str) -> MarkerEnv {
+        MarkerEnv {
+            os_name: if sys_platform == "win32" {
+                "nt"
+            } else {
+                "posix"
+            }
+            .to_string(),
+            sys_platform: sys_platform.to_string(),
+            platform_machine: machine.to_string(),
+            python_version: python_full_version
+                .splitn(3, '.')
+                .take(2)
+                .collect::<Vec<_>>()
+                .join("."),
+            python_full_version: python_full_version.to_string(),
+            ..MarkerEnv::default()
+        }
+    }
+
+    #[test]
+    fn test_platform_markers() {
+        let linux = env("linux", "3.12.1", "x86_64");
+        let windows = env("win32", "3.11.4", "AMD64");
+        for (marker, on_linux, on_windows) in [
+            ("sys_platform == 'win32'", false, true),
+            ("sys_platform == \"linux\"", true, false),
+            ("sys_platform != 'win32'", true, false),
+            ("'win' in sys_platform", false, true),
+            ("'win' not in sys_platform", true, false),
+            (
+                "platform_machine == 'x86_64' or platform_machine == 'AMD64'",
+                true,
+                true,
+            ),
+            (
+                "os_name == 'posix' and platform_machine == 'aarch64'",
+                false,
+                false,
+            ),
+            ("extra == 'socks'", false, false),
+        ] {
+            assert_eq!(evaluate(marker, &linux), Ok(on_linux), "{}", marker);
+            assert_eq!(evaluate(marker, &windows), Ok(on_windows), "{}", marker);
+        }
+    }
+
+    #[test]
+    fn test_version_markers() {
+        let py312 = env("linux", "3.12.1", "x86_64");
+        for (marker, expected) in [
+            ("python_version >= '3.8'", true),
+            ("python_version < '3.12'", false),
+            ("python_version > '3.9'", true),
+            ("'3.10' <= python_version", true),
+            ("python_version == '3.12'", true),
+            ("python_full_version == '3.12.*'", true),
+            ("python_full_version != '3.11.*'", true),
+            ("python_full_version ~= '3.12.0'", true),
+            ("python_full_version ~= '3.11.0'", false),
+            ("python_version ~= '3.10'", true),
+            ("python_version === '3.12'", true),
+        ] {
+            assert_eq!(evaluate(marker, &py312), Ok(expected), "{}", marker);
+        }
+    }
+
+    #[test]
+    fn test_precedence_and_parentheses() {
+        let linux = env("linux", "3.12.1", "x86_64");
+        // `and` binds tighter than `or`
+        assert_eq!(
+            evaluate(
+                "sys_platform == 'linux' or sys_platform == 'win32' and python_version < '3'",
+                &linux
+            ),
+            Ok(true)
+        );
+        assert_eq!(
+            evaluate(
+                "(sys_platform == 'linux' or sys_platform == 'win32') and python_version < '3'",
+                &linux
+            ),
+            Ok(false)
+        );
+    }
+
+    #[test]
+    fn test_invalid_markers() {
+        for marker in [
+            "",
+            "sys_platform",
+            "sys_platform ==",
+            "sys_platform = 'linux'",
+            "sys_platform == 'linux",
+            "(sys_platform == 'linux'",
+            "sys_platform == 'linux')",
+            "'a' == 'b'",
+            "python_versoin >= '3.8'",
+            "sys_platform not 'linux'",
+            "sys_platform == 'linux' and",
+        ] {
+            assert!(
+                Marker::parse(marker).is_err(),
+                "{:?} should be rejected",
+                marker
+            );
+        }
+    }
+}
@@ -0,0 +1,139 @@
+//! Three-way merge of TOML tables, used to save project.toml on top of
+//! edits made on disk since it was loaded.
+
+use toml::{Table, Value};
+
+/// A key changed differently on disk and in memory since the common base
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    /// Dotted key, e.g. `packages.requests`
+    pub key: String,
+    /// In-memory value, `None` if removed
+    pub ours: Option<Value>,
+    /// On-disk value, `None` if removed
+    pub theirs: Option<Value>,
+}
+
+/// Merge `ours` and `theirs`, both derived from `base`.
+///
+/// A key changed on only one side takes that side's value; nested tables
+/// are merged key by key. For a key changed differently on both sides,
+/// `keep_ours` decides which value wins.
+pub fn three_way(
+    base: &Table,
+    ours: &Table,
+    theirs: &Table,
+    keep_ours: &mut dyn FnMut(&Conflict) -> bool,
+) -> Table {
+    merge_tables("", base, ours, theirs, keep_ours)
+}
+
+fn merge_tables(
+    prefix: &str,
+    base: &Table,
+    ours: &Table,
+    theirs: &Table,
+    keep_ours: &mut dyn FnMut(&Conflict) -> bool,
+) -> Table {
+    let mut keys: Vec<&String> = base
+        .keys()
+        .chain(ours.keys())
+        .chain(theirs.keys())
+        .collect();
+    keys.sort();
+    keys.dedup();
+
+    let mut merged = Table::new();
+    for key in keys {
+        let (b, o, t) = (base.get(key), ours.get(key), theirs.get(key));
+        let value = if o == b {
+            t.cloned()
+        } else if t == b || o == t {
+            o.cloned()
+        } else if let (Some(Value::Table(o)), Some(Value::Table(t))) = (o, t) {
+            let empty = Table::new();
+            let b = match b {
+                Some(Value::Table(b)) => b,
+                _ => &empty,
+            };
+            let path = format!("{}{}.", prefix, key);
+            Some(Value::Table(merge_tables(&path, b, o, t, keep_ours)))
+        } else {
+            let conflict = Conflict {
+                key: format!("{}{}", prefix, key),
+                ours: o.cloned(),
+                theirs: t.cloned(),
+            };
+            if keep_ours(&conflict) {
+                conflict.ours
+            } else {
+                conflict.theirs
+            }
+        };
+        if let Some(value) = value {
+            merged.insert(key.clone(), value);
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(s: &str) -> Table {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_non_overlapping_changes_combine() {
+        let base = table("[packages]\nrequests = \"2.30.0\"\nflask = \"3.0.0\"\n[scripts]\n");
+        let ours = table("[packages]\nrequests = \"2.31.0\"\nflask = \"3.0.0\"\n[scripts]\n");
+        let theirs = table("[packages]\nrequests = \"2.30.0\"\n[scripts]\ntest = \"pytest\"\n");
+
+        let mut conflicts = vec![];
+        let merged = three_way(&base, &ours, &theirs, &mut |c| {
+            conflicts.push(c.clone());
+            true
+        });
+        assert!(conflicts.is_empty());
+        assert_eq!(
+            merged,
+            table("[packages]\nrequests = \"2.31.0\"\n[scripts]\ntest = \"pytest\"\n")
+        );
+    }
+
+    #[test]
+    fn test_conflicting_changes_are_reported() {
+        let base = table("[packages]\nrequests = \"2.30.0\"\n");
+        let ours = table("[packages]\nrequests = \"2.31.0\"\n");
+        let theirs = table("[packages]\nrequests = \"2.29.0\"\n");
+
+        let mut seen = vec![];
+        let merged = three_way(&base, &ours, &theirs, &mut |c| {
+            seen.push(c.key.clone());
+            false
+        });
+        assert_eq!(seen, vec!["packages.requests".to_string()]);
+        assert_eq!(merged, theirs);
+
+        let merged = three_way(&base, &ours, &theirs, &mut |_| true);
+        assert_eq!(merged, ours);
+    }
+
+    #[test]
+    fn test_removal_against_edit_conflicts() {
+        let base = table("[packages]\nrequests = \"2.30.0\"\n");
+        let ours = table("[packages]\n");
+        let theirs = table("[packages]\nrequests = \"2.29.0\"\n");
+
+        let mut seen = None;
+        three_way(&base, &ours, &theirs, &mut |c| {
+            seen = Some(c.clone());
+            true
+        });
+        let conflict = seen.unwrap();
+        assert_eq!(conflict.ours, None);
+        assert_eq!(conflict.theirs, Some(Value::String("2.29.0".to_string())));
+    }
+}
@@ -0,0 +1,409 @@
+//! A size budget for the PyPI metadata cache.
+//!
+//! Release histories are cached one file per package under
+//! [`RELEASES_CACHE`] in the global cache directory, which every project
+//! shares, so without a limit it grows with each package ppm ever looks
+//! up. Storing an entry evicts the least recently used ones until the
+//! directory fits its budget, [`DEFAULT_MAX_SIZE`] unless `cache-max-size`
+//! is set in the global config. An entry was last used when its file was
+//! last modified: it is rewritten when fetched again and [`touch`]ed when
+//! read. Entries used at the same time go in file name order, so eviction
+//! is deterministic. `ppm cache prune` evicts on demand, and
+//! [`STATS_FILE`] keeps the hit and miss counts `ppm cache info` shows.
+
+use crate::global_config::GlobalConfig;
+use crate::state::StateDir;
+use crate::yanked::RELEASES_CACHE;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Budget of the metadata cache when the global config doesn't set one
+pub const DEFAULT_MAX_SIZE: u64 = 50 * 1024 * 1024;
+
+/// Hit and miss counts, see [`Stats`], inside the global [`StateDir`]
+pub const STATS_FILE: &str = "cache/pypi-stats.json";
+
+/// A cached release history
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// The history's file
+    pub path: PathBuf,
+    /// Size in bytes
+    pub size: u64,
+    /// When it was last written or read
+    pub used: SystemTime,
+}
+
+/// What a prune or an eviction removed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Pruned {
+    /// Entries removed
+    pub removed: usize,
+    /// Bytes freed
+    pub freed: u64,
+}
+
+impl Pruned {
+    fn add(&mut self, other: Pruned) {
+        self.removed += other.removed;
+        self.freed += other.freed;
+    }
+}
+
+/// Lookups of release histories: a hit was answered from the cache, a
+/// miss had to ask the index or went unanswered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Counts {
+    /// Lookups the cache answered
+    pub hits: u64,
+    /// Lookups it didn't
+    pub misses: u64,
+}
+
+/// Hit and miss counts of the last run that looked anything up, and of
+/// every run since the cache was created
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stats {
+    /// The last run's
+    #[serde(default)]
+    pub last_run: Counts,
+    /// Every run's together
+    #[serde(default)]
+    pub total: Counts,
+}
+
+/// The budget from the global config, [`DEFAULT_MAX_SIZE`] when unset
+pub fn configured_max_size() -> u64 {
+    GlobalConfig::load()
+        .ok()
+        .and_then(|config| config.cache_max_size)
+        .unwrap_or(DEFAULT_MAX_SIZE)
+}
+
+/// The cached release histories in `cache`, least recently used first
+pub fn entries(cache: &StateDir) -> Vec<Entry> {
+    let Ok(dir) = std::fs::read_dir(cache.subpath(RELEASES_CACHE)) else {
+        return vec![];
+    };
+    let mut entries: Vec<Entry> = dir
+        .flatten()
+        // Leaves the temporary files of writes in progress alone
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            if !metadata.is_file() {
+                return None;
+            }
+            Some(Entry {
+                path: entry.path(),
+                size: metadata.len(),
+                used: metadata.modified().ok()?,
+            })
+        })
+        .collect();
+    entries.sort_by(|a, b| a.used.cmp(&b.used).then_with(|| a.path.cmp(&b.path)));
+    entries
+}
+
+/// Remove the least recently used entries until the rest take at most
+/// `max_size` bytes; `keep`, the entry just written, is never removed
+pub fn evict(cache: &StateDir, max_size: u64, keep: Option<&Path>) -> io::Result<Pruned> {
+    let entries = entries(cache);
+    let mut total: u64 = entries.iter().map(|entry| entry.size).sum();
+    let mut pruned = Pruned::default();
+    for entry in entries {
+        if total <= max_size {
+            break;
+        }
+        if Some(entry.path.as_path()) == keep {
+            continue;
+        }
+        pruned.add(remove(&entry)?);
+        total -= entry.size;
+    }
+    Ok(pruned)
+}
+
+/// `ppm cache prune`: remove the entries not used for `older_than`, then
+/// evict down to `max_size`
+pub fn prune(
+    cache: &StateDir,
+    max_size: u64,
+    older_than: Option<Duration>,
+    now: SystemTime,
+) -> io::Result<Pruned> {
+    let mut pruned = Pruned::default();
+    if let Some(age) = older_than {
+        let cutoff = now.checked_sub(age).unwrap_or(UNIX_EPOCH);
+        for entry in entries(cache).iter().filter(|entry| entry.used < cutoff) {
+            pruned.add(remove(entry)?);
+        }
+    }
+    pruned.add(evict(cache, max_size, None)?);
+    Ok(pruned)
+}
+
+fn remove(entry: &Entry) -> io::Result<Pruned> {
+    match std::fs::remove_file(&entry.path) {
+        Ok(()) => Ok(Pruned {
+            removed: 1,
+            freed: entry.size,
+        }),
+        // Another ppm got to it first
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Pruned::default()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Mark the entry at `path` as used now, so eviction keeps it longer
+pub fn touch(path: &Path) -> io::Result<()> {
+    std::fs::File::options()
+        .write(true)
+        .open(path)?
+        .set_modified(SystemTime::now())
+}
+
+/// The counts stored in `cache`, zero when there are none
+pub fn load_stats(cache: &StateDir) -> Stats {
+    std::fs::read(cache.subpath(STATS_FILE))
+        .ok()
+        .and_then(|contents| serde_json::from_slice(&contents).ok())
+        .unwrap_or_default()
+}
+
+/// Store `run` as the last run's counts and add it to the totals; runs
+/// that looked nothing up leave the counts alone
+pub fn record(cache: &StateDir, run: Counts) -> io::Result<()> {
+    if run == Counts::default() {
+        return Ok(());
+    }
+    let mut stats = load_stats(cache);
+    stats.last_run = run;
+    stats.total.hits += run.hits;
+    stats.total.misses += run.misses;
+    let contents = serde_json::to_vec(&stats).map_err(io::Error::other)?;
+    cache.write(STATS_FILE, &contents).map(|_| ())
+}
+
+/// Bytes in a size like `50MB`, `512K` or `1.5G`; units are powers of
+/// 1024, as `ppm cache info` shows them, and a bare number is bytes
+pub fn parse_size(text: &str) -> Result<u64, String> {
+    let trimmed = text.trim();
+    let invalid = || {
+        format!(
+            "Invalid size '{}'; use a number of bytes or a size like 500K, 50MB or 1G",
+            trimmed
+        )
+    };
+    let split = trimmed
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split);
+    let number: f64 = number.trim().parse().map_err(|_| invalid())?;
+    let unit = unit.to_ascii_lowercase();
+    let unit = unit
+        .strip_suffix("ib")
+        .or_else(|| unit.strip_suffix('b'))
+        .unwrap_or(&unit);
+    let multiplier: u64 = match unit {
+        "" => 1,
+        "k" => 1024,
+        "m" => 1024 * 1024,
+        "g" => 1024 * 1024 * 1024,
+        "t" => 1024 * 1024 * 1024 * 1024,
+        _ => return Err(invalid()),
+    };
+    if !number.is_finite() || number < 0.0 {
+        return Err(invalid());
+    }
+    Ok((number * multiplier as f64) as u64)
+}
+
+/// Reads `cache-max-size` from the global config as a size like `"50MB"`,
+/// see [`parse_size`], or a number of bytes
+pub fn deserialize_size<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<u64>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Size {
+        Bytes(u64),
+        Text(String),
+    }
+    match Option::<Size>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(Size::Bytes(bytes)) => Ok(Some(bytes)),
+        Some(Size::Text(text)) => parse_size(&text)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    /// An entry of `size` bytes for `name`, last used `days_ago` before `now`
+    fn fabricate(
+        cache: &StateDir,
+        name: &str,
+        size: usize,
+        now: SystemTime,
+        days_ago: u32,
+    ) -> PathBuf {
+        let path = cache
+            .write(
+                &format!("{}/{}.json", RELEASES_CACHE, name),
+                &vec![b' '; size],
+            )
+            .unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(now - DAY * days_ago)
+            .unwrap();
+        path
+    }
+
+    fn names(cache: &StateDir) -> Vec<String> {
+        entries(cache)
+            .iter()
+            .map(|entry| {
+                entry
+                    .path
+                    .file_stem()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_entries_are_least_recently_used_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = StateDir::project(dir.path());
+        assert!(entries(&cache).is_empty());
+        let now = SystemTime::now();
+        fabricate(&cache, "flask", 10, now, 1);
+        fabricate(&cache, "django", 10, now, 3);
+        // Used at the same time as django: name order breaks the tie
+        fabricate(&cache, "attrs", 10, now, 3);
+        // A write in progress isn't an entry
+        cache
+            .write(&format!("{}/.numpy.json.42.tmp", RELEASES_CACHE), b"{}")
+            .unwrap();
+        cache.write(STATS_FILE, b"{}").unwrap();
+        assert_eq!(names(&cache), ["attrs", "django", "flask"]);
+    }
+
+    #[test]
+    fn test_evict_removes_the_oldest_until_under_budget() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = StateDir::project(dir.path());
+        let now = SystemTime::now();
+        fabricate(&cache, "a", 100, now, 5);
+        fabricate(&cache, "b", 100, now, 4);
+        fabricate(&cache, "c", 100, now, 3);
+        fabricate(&cache, "d", 100, now, 2);
+
+        assert_eq!(evict(&cache, 400, None).unwrap(), Pruned::default());
+        assert_eq!(
+            evict(&cache, 250, None).unwrap(),
+            Pruned {
+                removed: 2,
+                freed: 200
+            }
+        );
+        assert_eq!(names(&cache), ["c", "d"]);
+    }
+
+    #[test]
+    fn test_evict_never_removes_the_entry_just_written() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = StateDir::project(dir.path());
+        let now = SystemTime::now();
+        fabricate(&cache, "recent", 100, now, 1);
+        // Written now, but with a clock that says it's the oldest
+        let written = fabricate(&cache, "written", 300, now, 10);
+
+        let pruned = evict(&cache, 200, Some(&written)).unwrap();
+        assert_eq!(pruned.removed, 1);
+        assert_eq!(names(&cache), ["written"]);
+        // Alone over the budget, it still stays
+        assert_eq!(evict(&cache, 0, Some(&written)).unwrap().removed, 0);
+        assert!(written.exists());
+    }
+
+    #[test]
+    fn test_touch_keeps_an_entry_longer() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = StateDir::project(dir.path());
+        let now = SystemTime::now();
+        let read = fabricate(&cache, "read", 100, now, 9);
+        fabricate(&cache, "unread", 100, now, 2);
+        touch(&read).unwrap();
+        evict(&cache, 100, None).unwrap();
+        assert_eq!(names(&cache), ["read"]);
+    }
+
+    #[test]
+    fn test_prune_older_than_then_to_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = StateDir::project(dir.path());
+        let now = SystemTime::now();
+        fabricate(&cache, "old", 10, now, 40);
+        fabricate(&cache, "older", 10, now, 90);
+        fabricate(&cache, "new", 10, now, 1);
+        fabricate(&cache, "newer", 10, now, 0);
+
+        let pruned = prune(&cache, DEFAULT_MAX_SIZE, Some(DAY * 30), now).unwrap();
+        assert_eq!(
+            pruned,
+            Pruned {
+                removed: 2,
+                freed: 20
+            }
+        );
+        assert_eq!(names(&cache), ["new", "newer"]);
+        assert_eq!(prune(&cache, 10, None, now).unwrap().removed, 1);
+        assert_eq!(names(&cache), ["newer"]);
+        assert_eq!(prune(&cache, 0, None, now).unwrap().freed, 10);
+        assert!(entries(&cache).is_empty());
+    }
+
+    #[test]
+    fn test_record_stats() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = StateDir::project(dir.path());
+        assert_eq!(load_stats(&cache), Stats::default());
+        record(&cache, Counts { hits: 2, misses: 1 }).unwrap();
+        record(&cache, Counts::default()).unwrap();
+        record(&cache, Counts { hits: 0, misses: 3 }).unwrap();
+        assert_eq!(
+            load_stats(&cache),
+            Stats {
+                last_run: Counts { hits: 0, misses: 3 },
+                total: Counts { hits: 2, misses: 4 },
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1024"), Ok(1024));
+        assert_eq!(parse_size("512K"), Ok(512 * 1024));
+        assert_eq!(parse_size("50MB"), Ok(50 * 1024 * 1024));
+        assert_eq!(parse_size("50 MiB"), Ok(50 * 1024 * 1024));
+        assert_eq!(parse_size("1.5g"), Ok(1536 * 1024 * 1024));
+        assert!(parse_size("").is_err());
+        assert!(parse_size("50 parsecs").is_err());
+        assert!(parse_size("-1M").is_err());
+        assert!(parse_size("MB").is_err());
+    }
+}
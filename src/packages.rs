@@ -0,0 +1,435 @@
+//! Package names: PEP 503 normalization and duplicate detection.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+
+/// PEP 503 normalized name, used to compare config keys with pip's spelling
+pub fn normalize_pkg_name(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut prev_sep = false;
+    for c in name.trim().chars() {
+        if c == '-' || c == '_' || c == '.' {
+            if !prev_sep {
+                out.push('-');
+            }
+            prev_sep = true;
+        } else {
+            out.extend(c.to_lowercase());
+            prev_sep = false;
+        }
+    }
+    out
+}
+
+/// The distribution name at the start of a requirement such as
+/// `Flask>=2.0` or `requests[socks]==2.31.0`
+pub fn requirement_name(spec: &str) -> &str {
+    let spec = spec.trim();
+    let end = spec
+        .find(|c: char| "<>=!~;[@ ".contains(c))
+        .unwrap_or(spec.len());
+    &spec[..end]
+}
+
+/// Compare versions the way PEP 440 orders them, so `2.10.0 > 2.9.1` and
+/// `1.0.dev1 < 1.0a1 < 1.0rc1 < 1.0 < 1.0.post1 < 1.0.1`. Versions that
+/// aren't PEP 440 are compared numerically segment by segment, with
+/// non-numeric suffixes as text.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    match (Pep440::parse(a), Pep440::parse(b)) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        _ => compare_dotted(a, b),
+    }
+}
+
+/// A version as PEP 440 sorts it; the field order is the sort order
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Pep440 {
+    epoch: u64,
+    /// Release segments without trailing zeros, so `3.0 == 3.0.0`
+    release: Vec<u64>,
+    /// Phase and number: a development release of the plain release
+    /// (`1.0.dev1`) first, then alpha, beta and rc, then no pre-release
+    pre: (u8, u64),
+    /// `None` sorts before any post-release
+    post: Option<u64>,
+    /// A development release sorts before the version it leads up to
+    dev: (u8, u64),
+    local: String,
+}
+
+impl Pep440 {
+    fn parse(version: &str) -> Option<Pep440> {
+        let version = version.trim().to_lowercase();
+        let version = version.strip_prefix('v').unwrap_or(&version);
+        let (public, local) = version.split_once('+').unwrap_or((version, ""));
+        let (epoch, public) = match public.split_once('!') {
+            Some((epoch, rest)) => (epoch.parse().ok()?, rest),
+            None => (0, public),
+        };
+
+        let release_end = public
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(public.len());
+        let release_text = public[..release_end].trim_end_matches('.');
+        let mut release = release_text
+            .split('.')
+            .map(|segment| segment.parse::<u64>().ok())
+            .collect::<Option<Vec<u64>>>()?;
+        while release.len() > 1 && release.last() == Some(&0) {
+            release.pop();
+        }
+
+        let (mut pre, mut post, mut dev) = (None, None, None);
+        let mut rest = &public[release_text.len()..];
+        while !rest.is_empty() {
+            let separated = rest.starts_with(['.', '-', '_']);
+            let trimmed = rest.trim_start_matches(['.', '-', '_']);
+            let letters = trimmed
+                .find(|c: char| !c.is_ascii_alphabetic())
+                .unwrap_or(trimmed.len());
+            let (word, after) = trimmed.split_at(letters);
+            let after = after.trim_start_matches(['.', '-', '_']);
+            let digits = after
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(after.len());
+            let number = after[..digits].parse::<u64>().unwrap_or(0);
+            let duplicate = match word {
+                "a" | "alpha" => pre.replace((1, number)).is_some(),
+                "b" | "beta" => pre.replace((2, number)).is_some(),
+                "c" | "rc" | "pre" | "preview" => pre.replace((3, number)).is_some(),
+                "post" | "rev" | "r" => post.replace(number).is_some(),
+                "dev" => dev.replace(number).is_some(),
+                // `1.0-1` is the post-release `1.0.post1`
+                "" if separated && digits > 0 => post.replace(number).is_some(),
+                _ => return None,
+            };
+            if duplicate {
+                return None;
+            }
+            rest = &after[digits..];
+        }
+
+        let pre = match pre {
+            Some(pre) => pre,
+            None if post.is_none() && dev.is_some() => (0, 0),
+            None => (4, 0),
+        };
+        Some(Pep440 {
+            epoch,
+            release,
+            pre,
+            post,
+            dev: dev.map_or((1, 0), |number| (0, number)),
+            local: local.to_string(),
+        })
+    }
+}
+/// Dotted versions numerically segment by segment, non-numeric suffixes
+/// as text
+fn compare_dotted(a: &str, b: &str) -> Ordering {
+    let mut left = a.trim().split('.');
+    let mut right = b.trim().split('.');
+    loop {
+        match (left.next(), right.next()) {
+            (None, None) => return Ordering::Equal,
+            (l, r) => {
+                let ord = compare_segment(l.unwrap_or("0"), r.unwrap_or("0"));
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+            }
+        }
+    }
+}
+
+/// Whether `version` is a pre- or development release such as `2.0rc1`
+/// or `1.5.dev2`; post releases like `1.0.post1` are final
+pub fn is_prerelease(version: &str) -> bool {
+    let version = version.trim().to_lowercase();
+    let public = version.split('+').next().unwrap_or_default();
+    let release = public.split("post").next().unwrap_or_default();
+    release.chars().any(|c| c.is_ascii_alphabetic())
+}
+
+/// The first `count` numeric segments of `version`, missing ones as 0,
+/// e.g. `[4, 2]` for `4.2rc1` and a count of 2
+pub fn release_prefix(version: &str, count: usize) -> Vec<u64> {
+    let mut segments = version.trim().split('.').map(|segment| {
+        let digits = segment
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(segment.len());
+        segment[..digits].parse::<u64>().unwrap_or(0)
+    });
+    (0..count).map(|_| segments.next().unwrap_or(0)).collect()
+}
+
+fn compare_segment(a: &str, b: &str) -> Ordering {
+    let split = |s: &str| {
+        let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        (
+            s[..digits].parse::<u64>().unwrap_or(0),
+            s[digits..].to_string(),
+        )
+    };
+    let (a_num, a_rest) = split(a);
+    let (b_num, b_rest) = split(b);
+    a_num
+        .cmp(&b_num)
+        .then_with(|| match (a_rest.is_empty(), b_rest.is_empty()) {
+            // A pre-release suffix sorts before the plain release
+            (true, false) => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            _ => a_rest.cmp(&b_rest),
+        })
+}
+
+/// Several spellings of one package, e.g. `Flask` and `flask`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicatePackage {
+    /// Normalized name shared by every entry
+    pub canonical: String,
+    /// Each spelling with its version, in input order
+    pub entries: Vec<(String, Option<String>)>,
+}
+
+impl fmt::Display for DuplicatePackage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let listed: Vec<String> = self
+            .entries
+            .iter()
+            .map(|(name, version)| match version {
+                Some(version) => format!("{}=={}", name, version),
+                None => name.clone(),
+            })
+            .collect();
+        write!(
+            f,
+            "'{}' is listed as {}",
+            self.canonical,
+            listed.join(" and ")
+        )
+    }
+}
+
+/// Config keys that normalize to the same package name, sorted by
+/// canonical name
+pub fn find_duplicates(packages: &HashMap<String, String>) -> Vec<DuplicatePackage> {
+    let mut by_name: HashMap<String, Vec<(String, Option<String>)>> = HashMap::new();
+    for (name, version) in packages {
+        by_name
+            .entry(normalize_pkg_name(name))
+            .or_default()
+            .push((name.clone(), Some(version.clone())));
+    }
+
+    let mut duplicates: Vec<DuplicatePackage> = by_name
+        .into_iter()
+        .filter(|(_, entries)| entries.len() > 1)
+        .map(|(canonical, mut entries)| {
+            entries.sort();
+            DuplicatePackage { canonical, entries }
+        })
+        .collect();
+    duplicates.sort_by(|a, b| a.canonical.cmp(&b.canonical));
+    duplicates
+}
+
+/// How to pick between entries for the same package with different versions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Prefer {
+    /// Keep the highest version; an unpinned entry counts as newest
+    Newer,
+    /// Keep the entry that appears first
+    First,
+}
+
+/// Indices of `entries` to keep so each package appears once.
+///
+/// Entries with the same version are collapsed to the first one. Entries
+/// with different versions are resolved by `prefer`, or returned as
+/// conflicts when it is `None`. Kept indices are in input order.
+pub fn resolve_duplicates(
+    entries: &[(String, Option<String>)],
+    prefer: Option<Prefer>,
+) -> Result<Vec<usize>, Vec<DuplicatePackage>> {
+    let mut order: Vec<String> = vec![];
+    let mut groups: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, (name, _)) in entries.iter().enumerate() {
+        let canonical = normalize_pkg_name(requirement_name(name));
+        if !groups.contains_key(&canonical) {
+            order.push(canonical.clone());
+        }
+        groups.entry(canonical).or_default().push(i);
+    }
+
+    let mut kept = vec![];
+    let mut conflicts = vec![];
+    for canonical in order {
+        let group = &groups[&canonical];
+        let first = group[0];
+        let agree = group.iter().all(|&i| entries[i].1 == entries[first].1);
+        let winner = match prefer {
+            _ if agree => first,
+            Some(Prefer::First) => first,
+            Some(Prefer::Newer) => *group
+                .iter()
+                .rev()
+                .max_by(|&&a, &&b| match (&entries[a].1, &entries[b].1) {
+                    (None, None) => Ordering::Equal,
+                    (None, Some(_)) => Ordering::Greater,
+                    (Some(_), None) => Ordering::Less,
+                    (Some(a), Some(b)) => compare_versions(a, b),
+                })
+                .unwrap_or(&first),
+            None => {
+                conflicts.push(DuplicatePackage {
+                    canonical,
+                    entries: group.iter().map(|&i| entries[i].clone()).collect(),
+                });
+                continue;
+            }
+        };
+        kept.push(winner);
+    }
+
+    if conflicts.is_empty() {
+        kept.sort();
+        Ok(kept)
+    } else {
+        Err(conflicts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, version: Option<&str>) -> (String, Option<String>) {
+        (name.to_string(), version.map(|v| v.to_string()))
+    }
+
+    #[test]
+    fn test_normalize_pkg_name() {
+        assert_eq!(normalize_pkg_name("Flask"), "flask");
+        assert_eq!(normalize_pkg_name("typing_extensions"), "typing-extensions");
+        assert_eq!(normalize_pkg_name("zope.interface"), "zope-interface");
+        assert_eq!(normalize_pkg_name("My__Weird-.Name"), "my-weird-name");
+    }
+
+    #[test]
+    fn test_requirement_name() {
+        assert_eq!(requirement_name("Flask>=2.0"), "Flask");
+        assert_eq!(requirement_name("requests[socks]==2.31.0"), "requests");
+        assert_eq!(requirement_name("  numpy "), "numpy");
+    }
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(compare_versions("2.10.0", "2.9.1"), Ordering::Greater);
+        assert_eq!(compare_versions("3.0", "3.0.0"), Ordering::Equal);
+        assert_eq!(compare_versions("1.0rc1", "1.0"), Ordering::Less);
+        assert_eq!(compare_versions("1.0.post1", "1.0"), Ordering::Greater);
+
+        // PEP 440 order, each version before the next
+        let ordered = [
+            "1.0.dev1",
+            "1.0a1",
+            "1.0a2.dev1",
+            "1.0a2",
+            "1.0b1",
+            "1.0rc1",
+            "1.0",
+            "1.0+local",
+            "1.0.post1.dev1",
+            "1.0.post1",
+            "1.0-2",
+            "1.0.1",
+            "1!0.1",
+        ];
+        for pair in ordered.windows(2) {
+            assert_eq!(
+                compare_versions(pair[0], pair[1]),
+                Ordering::Less,
+                "{} < {}",
+                pair[0],
+                pair[1]
+            );
+            assert_eq!(compare_versions(pair[1], pair[0]), Ordering::Greater);
+        }
+        assert_eq!(compare_versions("v1.0.0-RC.1", "1.0rc1"), Ordering::Equal);
+        // Not PEP 440: segment by segment
+        assert_eq!(compare_versions("2024.1-custom", "2024.2"), Ordering::Less);
+    }
+
+    #[test]
+    fn test_prereleases_and_release_prefix() {
+        assert!(is_prerelease("2.0rc1"));
+        assert!(is_prerelease("1.5.dev2"));
+        assert!(!is_prerelease("1.0.post1"));
+        assert!(!is_prerelease("1.0+local"));
+        assert_eq!(release_prefix("4.2rc1", 2), vec![4, 2]);
+        assert_eq!(release_prefix("5", 2), vec![5, 0]);
+    }
+
+    #[test]
+    fn test_find_duplicates_case_and_separators() {
+        let packages: HashMap<String, String> = [
+            ("Flask", "2.0"),
+            ("flask", "3.0"),
+            ("typing_extensions", "4.9.0"),
+            ("Typing.Extensions", "4.9.0"),
+            ("requests", "2.31.0"),
+        ]
+        .iter()
+        .map(|(n, v)| (n.to_string(), v.to_string()))
+        .collect();
+
+        let duplicates = find_duplicates(&packages);
+        assert_eq!(duplicates.len(), 2);
+        assert_eq!(duplicates[0].canonical, "flask");
+        assert_eq!(
+            duplicates[0].entries,
+            vec![entry("Flask", Some("2.0")), entry("flask", Some("3.0"))]
+        );
+        assert_eq!(
+            duplicates[0].to_string(),
+            "'flask' is listed as Flask==2.0 and flask==3.0"
+        );
+        assert_eq!(duplicates[1].canonical, "typing-extensions");
+    }
+
+    #[test]
+    fn test_resolve_duplicates() {
+        let entries = vec![
+            entry("Flask", Some("2.0")),
+            entry("requests", Some("2.31.0")),
+            entry("flask", Some("3.0")),
+            entry("Requests", Some("2.31.0")),
+        ];
+
+        // Same version collapses silently, different versions conflict
+        let conflicts = resolve_duplicates(&entries, None).unwrap_err();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].canonical, "flask");
+
+        assert_eq!(
+            resolve_duplicates(&entries, Some(Prefer::First)).unwrap(),
+            vec![0, 1]
+        );
+        assert_eq!(
+            resolve_duplicates(&entries, Some(Prefer::Newer)).unwrap(),
+            vec![1, 2]
+        );
+    }
+
+    #[test]
+    fn test_resolve_prefers_unpinned_as_newer() {
+        let entries = vec![entry("black", Some("23.1.0")), entry("Black", None)];
+        assert_eq!(
+            resolve_duplicates(&entries, Some(Prefer::Newer)).unwrap(),
+            vec![1]
+        );
+    }
+}
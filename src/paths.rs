@@ -0,0 +1,344 @@
+//! Locations inside a project, always built from the project root.
+//!
+//! Nothing here depends on the current directory: a relative `venv` from
+//! project.toml is joined onto the root, an absolute one is used as-is.
+
+use crate::settings::PROJECT_CONFIG_FILE;
+use std::path::{Component, Path, PathBuf};
+
+#[cfg(target_os = "windows")]
+const PYTHON_EXE: &str = "python.exe";
+#[cfg(not(target_os = "windows"))]
+const PYTHON_EXE: &str = "python";
+
+#[cfg(target_os = "windows")]
+const PIP_EXE: &str = "pip.exe";
+#[cfg(not(target_os = "windows"))]
+const PIP_EXE: &str = "pip";
+
+/// Directory holding a venv's executables (`Scripts` on Windows)
+#[cfg(target_os = "windows")]
+pub const VENV_BIN_DIR: &str = "Scripts";
+/// Directory holding a venv's executables (`Scripts` on Windows)
+#[cfg(not(target_os = "windows"))]
+pub const VENV_BIN_DIR: &str = "bin";
+
+/// The virtual environment directory for `venv_root` as written in
+/// project.toml
+pub fn get_venv_dir(project_root: &Path, venv_root: &str) -> PathBuf {
+    project_root.join(venv_root)
+}
+
+/// The venv's executable directory, prepended to `PATH` for scripts
+pub fn get_venv_bin_dir(project_root: &Path, venv_root: &str) -> PathBuf {
+    get_venv_dir(project_root, venv_root).join(VENV_BIN_DIR)
+}
+
+/// The venv's python interpreter
+pub fn get_venv_python_path(project_root: &Path, venv_root: &str) -> PathBuf {
+    get_venv_bin_dir(project_root, venv_root).join(PYTHON_EXE)
+}
+
+/// The venv's pip executable
+pub fn get_venv_pip_path(project_root: &Path, venv_root: &str) -> PathBuf {
+    get_venv_bin_dir(project_root, venv_root).join(PIP_EXE)
+}
+
+/// The venv's existing site-packages directories: `Lib/site-packages` on
+/// Windows, `lib/pythonX.Y/site-packages` (and `lib64`) elsewhere
+pub fn site_packages_dirs(venv_dir: &Path) -> Vec<PathBuf> {
+    let mut dirs = vec![venv_dir.join("Lib").join("site-packages")];
+    for lib in ["lib", "lib64"] {
+        if let Ok(entries) = std::fs::read_dir(venv_dir.join(lib)) {
+            for entry in entries.flatten() {
+                if entry.file_name().to_string_lossy().starts_with("python") {
+                    dirs.push(entry.path().join("site-packages"));
+                }
+            }
+        }
+    }
+    dirs.retain(|dir| dir.is_dir());
+    dirs
+}
+
+/// Nearest directory at or above `start` containing project.toml
+pub fn find_project_root(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .find(|dir| dir.join(PROJECT_CONFIG_FILE).is_file())
+        .map(Path::to_path_buf)
+}
+
+/// Resolve `.` and `..` components without touching the filesystem
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !out.pop() {
+                    out.push("..");
+                }
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// Whether `path`, relative to `root` or absolute, stays inside `root`.
+///
+/// `..` components are resolved first; then the deepest part of the path
+/// that exists is canonicalized so a symlink pointing out of the project
+/// is caught too.
+pub fn is_within(root: &Path, path: &Path) -> bool {
+    let Ok(root) = std::path::absolute(root) else {
+        return false;
+    };
+    let root = normalize(&root);
+    let candidate = normalize(&root.join(path));
+    if !candidate.starts_with(&root) {
+        return false;
+    }
+
+    let Ok(real_root) = root.canonicalize() else {
+        return true;
+    };
+    match candidate.ancestors().find(|dir| dir.exists()) {
+        Some(existing) => match existing.canonicalize() {
+            Ok(real) => real.starts_with(&real_root),
+            Err(_) => true,
+        },
+        None => true,
+    }
+}
+
+/// `path` as shown in messages: on Windows with backslashes throughout
+/// and without the `\\?\` prefix of [`extended_length`]; unchanged
+/// elsewhere, where a backslash is an ordinary file name character
+pub fn display(path: &Path) -> String {
+    display_for(&path.display().to_string(), cfg!(target_os = "windows"))
+}
+
+fn display_for(text: &str, windows: bool) -> String {
+    if !windows {
+        return text.to_string();
+    }
+    let text = match text.strip_prefix(r"\\?\UNC\") {
+        Some(share) => format!(r"\\{}", share),
+        None => text.strip_prefix(r"\\?\").unwrap_or(text).to_string(),
+    };
+    text.replace('/', "\\")
+}
+
+/// `path` made absolute and, on Windows, given the `\\?\` prefix that
+/// lifts the 260-character `MAX_PATH` limit, for operations that walk
+/// deep trees such as removing a venv. Unchanged elsewhere.
+pub fn extended_length(path: &Path) -> PathBuf {
+    #[cfg(target_os = "windows")]
+    {
+        // `absolute` resolves `.`, `..` and `/` on Windows, which verbatim
+        // paths no longer do
+        match std::path::absolute(path) {
+            Ok(absolute) => PathBuf::from(verbatim(&absolute.display().to_string())),
+            Err(_) => path.to_path_buf(),
+        }
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        path.to_path_buf()
+    }
+}
+
+/// The verbatim form of an absolute, normalized Windows path:
+/// `C:\a` becomes `\\?\C:\a` and `\\server\share` becomes
+/// `\\?\UNC\server\share`
+#[cfg(any(target_os = "windows", test))]
+fn verbatim(absolute: &str) -> String {
+    if absolute.starts_with(r"\\?\") {
+        absolute.to_string()
+    } else if let Some(share) = absolute.strip_prefix(r"\\") {
+        format!(r"\\?\UNC\{}", share)
+    } else {
+        format!(r"\\?\{}", absolute)
+    }
+}
+
+/// Remove the directory `path` and everything in it, through
+/// [`extended_length`] so deep trees on Windows don't fail past
+/// `MAX_PATH`
+pub fn remove_dir_all(path: &Path) -> std::io::Result<()> {
+    std::fs::remove_dir_all(extended_length(path))
+}
+
+/// Whether `path` is a file this user may run
+#[cfg(unix)]
+pub fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+/// Whether `path` is a file this user may run
+#[cfg(not(unix))]
+pub fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_venv_paths() {
+        let root = Path::new("project");
+
+        #[cfg(target_os = "windows")]
+        {
+            assert_eq!(
+                get_venv_python_path(root, "test_venv"),
+                Path::new(r"project\test_venv\Scripts\python.exe")
+            );
+            assert_eq!(
+                get_venv_pip_path(root, "test_venv"),
+                Path::new(r"project\test_venv\Scripts\pip.exe")
+            );
+            assert_eq!(
+                get_venv_bin_dir(root, "test_venv"),
+                Path::new(r"project\test_venv\Scripts")
+            );
+        }
+
+        #[cfg(not(target_os = "windows"))]
+        {
+            assert_eq!(
+                get_venv_python_path(root, "test_venv"),
+                Path::new("project/test_venv/bin/python")
+            );
+            assert_eq!(
+                get_venv_pip_path(root, "test_venv"),
+                Path::new("project/test_venv/bin/pip")
+            );
+            assert_eq!(
+                get_venv_bin_dir(root, "test_venv"),
+                Path::new("project/test_venv/bin")
+            );
+        }
+    }
+
+    #[test]
+    fn test_absolute_venv_ignores_root() {
+        let venv = std::env::temp_dir().join("shared-venv");
+        let venv_root = venv.to_str().unwrap();
+        assert_eq!(get_venv_dir(Path::new("project"), venv_root), venv);
+        assert_eq!(
+            get_venv_pip_path(Path::new("project"), venv_root),
+            venv.join(VENV_BIN_DIR).join(PIP_EXE)
+        );
+    }
+
+    #[test]
+    fn test_root_with_spaces() {
+        let root = Path::new("My Projects").join("demo app");
+        let python = get_venv_python_path(&root, "venv");
+        assert!(python.starts_with(&root));
+        assert_eq!(python.components().count(), root.components().count() + 3);
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize(Path::new("./a/b/../c")), Path::new("a/c"));
+        assert_eq!(normalize(Path::new("a/../../b")), Path::new("../b"));
+    }
+
+    #[test]
+    fn test_is_within_rejects_parent_dirs_and_absolute_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        assert!(is_within(root, Path::new("venv")));
+        assert!(is_within(root, Path::new("./envs/../.venv")));
+        assert!(is_within(root, &root.join("venv")));
+        assert!(!is_within(root, Path::new("../other")));
+        assert!(!is_within(root, Path::new("venv/../../other")));
+        assert!(!is_within(root, Path::new("/usr")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_is_within_follows_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        std::os::unix::fs::symlink(outside.path(), dir.path().join("link")).unwrap();
+        assert!(!is_within(dir.path(), Path::new("link")));
+        assert!(!is_within(dir.path(), Path::new("link/venv")));
+
+        std::fs::create_dir(dir.path().join("real")).unwrap();
+        std::os::unix::fs::symlink(dir.path().join("real"), dir.path().join("alias")).unwrap();
+        assert!(is_within(dir.path(), Path::new("alias/venv")));
+    }
+
+    #[test]
+    fn test_find_project_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("src").join("pkg name");
+        std::fs::create_dir_all(&nested).unwrap();
+        assert_eq!(find_project_root(&nested), None);
+
+        std::fs::write(dir.path().join(PROJECT_CONFIG_FILE), "").unwrap();
+        assert_eq!(find_project_root(&nested), Some(dir.path().to_path_buf()));
+        assert_eq!(
+            find_project_root(dir.path()),
+            Some(dir.path().to_path_buf())
+        );
+    }
+
+    #[test]
+    fn test_display_normalizes_windows_separators() {
+        assert_eq!(
+            display_for(r"C:\Users\First Last/project/venv", true),
+            r"C:\Users\First Last\project\venv"
+        );
+        assert_eq!(display_for(r"\\?\C:\deep\venv", true), r"C:\deep\venv");
+        assert_eq!(
+            display_for(r"\\?\UNC\server\share\venv", true),
+            r"\\server\share\venv"
+        );
+        assert_eq!(display_for(r"odd\name/venv", false), r"odd\name/venv");
+    }
+
+    #[test]
+    fn test_verbatim() {
+        assert_eq!(verbatim(r"C:\Users\First Last"), r"\\?\C:\Users\First Last");
+        assert_eq!(verbatim(r"\\server\share\x"), r"\\?\UNC\server\share\x");
+        assert_eq!(verbatim(r"\\?\C:\x"), r"\\?\C:\x");
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn test_extended_length_on_windows() {
+        let path = extended_length(Path::new(r"C:\Users\First Last\project/venv"));
+        assert_eq!(path, Path::new(r"\\?\C:\Users\First Last\project\venv"));
+        assert_eq!(display(&path), r"C:\Users\First Last\project\venv");
+        assert_eq!(
+            get_venv_python_path(Path::new(r"C:\Users\First Last\app"), "venv"),
+            Path::new(r"C:\Users\First Last\app\venv\Scripts\python.exe")
+        );
+    }
+
+    #[test]
+    fn test_remove_dir_all_under_spaces() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("First Last").join("my project");
+        let mut deep = get_venv_dir(&root, "venv");
+        for _ in 0..20 {
+            deep.push("nested directory name");
+        }
+        std::fs::create_dir_all(&deep).unwrap();
+        std::fs::write(deep.join("module file.py"), "").unwrap();
+
+        remove_dir_all(&get_venv_dir(&root, "venv")).unwrap();
+        assert!(!get_venv_dir(&root, "venv").exists());
+        assert!(root.is_dir());
+    }
+}
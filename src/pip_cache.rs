@@ -0,0 +1,361 @@
+//! pip's HTTP and wheel cache, which every venv's pip shares and which
+//! nothing ever shrinks.
+//!
+//! The venv's pip is asked where the cache is (`pip cache dir`) and how
+//! big it is (`pip cache info`). The wording of `cache info` changed
+//! between pip releases, so [`parse_info`] goes by keywords and skips
+//! lines it doesn't know. A pip older than 20.1 has no `cache` command;
+//! the directory then comes from pip's config or the platform default,
+//! and ppm measures it itself.
+
+use crate::context::ProjectContext;
+use crate::error::PpmError;
+use crate::pip_config::{Key, PipConfig};
+use crate::runner::{self, RunOptions, RunOutput};
+use crate::state::dir_size;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Subdirectories holding cached index pages and downloads, `http-v2`
+/// since pip 23.3
+const HTTP_DIRS: &[&str] = &["http", "http-v2"];
+
+/// Subdirectory holding wheels pip built locally
+const WHEELS_DIR: &str = "wheels";
+
+/// Sizes and counts of the cache; `None` for what wasn't reported
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Usage {
+    /// Bytes of cached index pages and downloads
+    pub http_size: Option<u64>,
+    /// Number of cached index pages and downloads
+    pub http_files: Option<u64>,
+    /// Bytes of locally built wheels
+    pub wheels_size: Option<u64>,
+    /// Number of locally built wheels
+    pub wheels: Option<u64>,
+}
+
+impl Usage {
+    /// Bytes of everything cached, `None` when no size is known
+    pub fn total(&self) -> Option<u64> {
+        match (self.http_size, self.wheels_size) {
+            (None, None) => None,
+            (http, wheels) => Some(http.unwrap_or(0) + wheels.unwrap_or(0)),
+        }
+    }
+}
+
+/// pip's cache as found for a project
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PipCache {
+    /// The cache directory
+    pub dir: PathBuf,
+    /// What it holds
+    pub usage: Usage,
+    /// Whether ppm measured the directory itself because the venv's pip
+    /// couldn't report on it
+    pub measured: bool,
+}
+
+impl PipCache {
+    /// Bytes of everything cached
+    pub fn total(&self) -> u64 {
+        self.usage.total().unwrap_or(0)
+    }
+}
+
+/// Parse the output of `pip cache info`. Sizes pip doesn't qualify, as
+/// in pip 20.1, are of the wheels; locations are skipped since
+/// `pip cache dir` names the directory.
+pub fn parse_info(text: &str) -> Usage {
+    let mut usage = Usage::default();
+    for line in text.lines() {
+        let Some((key, value)) = line.split_once(": ") else {
+            continue;
+        };
+        // `(pip v23.3+)` and the like qualify a location, never a size
+        let key = key
+            .split('(')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase();
+        let value = value.trim();
+        let is_http = key.contains("http") || key.contains("index page");
+        if key.starts_with("number of") {
+            let count = value.parse().ok();
+            if is_http {
+                usage.http_files = count;
+            } else if key.contains("wheel") {
+                usage.wheels = count;
+            }
+        } else if key == "size" || key.ends_with(" size") {
+            let size = parse_size(value);
+            if is_http {
+                usage.http_size = size;
+            } else {
+                usage.wheels_size = size;
+            }
+        }
+    }
+    usage
+}
+
+/// Bytes in a size as pip prints it, like `303.5 MB`, `12 kB` or
+/// `0 bytes`; binary units like `MiB` are read too
+pub fn parse_size(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let number: f64 = text[..split].parse().ok()?;
+    let unit: u64 = match text[split..].trim().to_lowercase().as_str() {
+        "" | "b" | "byte" | "bytes" => 1,
+        "kb" => 1000,
+        "mb" => 1000 * 1000,
+        "gb" => 1000 * 1000 * 1000,
+        "tb" => 1000 * 1000 * 1000 * 1000,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        "tib" => 1 << 40,
+        _ => return None,
+    };
+    Some((number * unit as f64).round() as u64)
+}
+
+/// Number of files `pip cache purge` says it removed, from its
+/// `Files removed: N` line
+pub fn parse_purged(text: &str) -> Option<u64> {
+    text.lines().find_map(|line| {
+        let count = line.trim().strip_prefix("Files removed:")?;
+        count.split_whitespace().next()?.parse().ok()
+    })
+}
+
+/// Whether pip failed because it has no `cache` command
+pub fn is_unknown_command(stderr: &str) -> bool {
+    stderr.contains("unknown command")
+}
+
+/// Measure `dir` the way `pip cache info` reports it
+pub fn measure(dir: &Path) -> Usage {
+    let http: Vec<PathBuf> = HTTP_DIRS.iter().map(|name| dir.join(name)).collect();
+    let wheels = dir.join(WHEELS_DIR);
+    Usage {
+        http_size: Some(http.iter().map(|dir| dir_size(dir)).sum()),
+        http_files: Some(http.iter().map(|dir| count_files(dir, &|_| true)).sum()),
+        wheels_size: Some(dir_size(&wheels)),
+        wheels: Some(count_files(&wheels, &|path| {
+            path.extension().is_some_and(|ext| ext == "whl")
+        })),
+    }
+}
+
+fn count_files(path: &Path, keep: &dyn Fn(&Path) -> bool) -> u64 {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return u64::from(keep(path));
+    }
+    fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| count_files(&entry.path(), keep))
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+/// The cache directory pip uses without asking it: `cache-dir` from its
+/// config, else the platform default
+pub fn configured_dir(pip: &PipConfig) -> Option<PathBuf> {
+    if let Some(setting) = pip.get(Key::CacheDir) {
+        return Some(match setting.value.strip_prefix("~/") {
+            Some(rest) => std::env::home_dir()?.join(rest),
+            None => PathBuf::from(setting.value),
+        });
+    }
+    default_dir()
+}
+
+#[cfg(target_os = "windows")]
+fn default_dir() -> Option<PathBuf> {
+    crate::state::platform_cache_dir().map(|dir| dir.join("pip").join("Cache"))
+}
+
+#[cfg(not(target_os = "windows"))]
+fn default_dir() -> Option<PathBuf> {
+    crate::state::platform_cache_dir().map(|dir| dir.join("pip"))
+}
+
+fn run_pip(ctx: &ProjectContext, args: &[&str]) -> Option<RunOutput> {
+    ctx.runner
+        .run(
+            ctx.venv_pip().as_os_str(),
+            &runner::args(args),
+            &RunOptions::in_dir(&ctx.root),
+        )
+        .ok()
+}
+
+/// Where the project's pip caches and what it holds, as its pip reports
+/// it, or measured by ppm when that pip is too old or won't run
+pub fn inspect(ctx: &ProjectContext) -> Result<PipCache, PpmError> {
+    let dir = run_pip(ctx, &["cache", "dir"]);
+    if let Some(output) = &dir
+        && !output.success()
+        && output.stderr_lossy().contains("cache is disabled")
+    {
+        return Err(PpmError::Config(
+            "pip's cache is disabled (no-cache-dir is set), so there is nothing cached".to_string(),
+        ));
+    }
+    let dir = dir
+        .filter(RunOutput::success)
+        .map(|output| PathBuf::from(output.stdout_lossy().trim()))
+        .filter(|dir| !dir.as_os_str().is_empty());
+    let Some(dir) = dir else {
+        let dir = configured_dir(&PipConfig::load()).ok_or_else(|| {
+            PpmError::Other("Could not tell where pip's cache is; set PIP_CACHE_DIR".to_string())
+        })?;
+        return Ok(PipCache {
+            usage: measure(&dir),
+            dir,
+            measured: true,
+        });
+    };
+    let reported = run_pip(ctx, &["cache", "info"])
+        .filter(RunOutput::success)
+        .map(|output| parse_info(&output.stdout_lossy()))
+        .filter(|usage| usage.total().is_some());
+    Ok(match reported {
+        Some(usage) => PipCache {
+            dir,
+            usage,
+            measured: false,
+        },
+        None => PipCache {
+            usage: measure(&dir),
+            dir,
+            measured: true,
+        },
+    })
+}
+
+/// Empty the cache with `pip cache purge`; the number of files removed
+/// when pip says
+pub fn purge(ctx: &ProjectContext) -> Result<Option<u64>, PpmError> {
+    let output = ctx
+        .runner
+        .run(
+            ctx.venv_pip().as_os_str(),
+            &runner::args(["cache", "purge"]),
+            &RunOptions::in_dir(&ctx.root),
+        )
+        .map_err(|e| PpmError::Subprocess(format!("Failed to execute pip: {}", e)))?;
+    if !output.success() {
+        let stderr = output.stderr_lossy();
+        if is_unknown_command(&stderr) {
+            return Err(PpmError::Venv(
+                "The venv's pip is older than 20.1 and can't purge its cache; upgrade pip in the venv first"
+                    .to_string(),
+            ));
+        }
+        return Err(PpmError::from_pip(stderr, output.code));
+    }
+    Ok(parse_purged(&output.stdout_lossy()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_info_across_pip_versions() {
+        assert_eq!(
+            parse_info(include_str!("../tests/fixtures/pip-cache/info-20.1.txt")),
+            Usage {
+                http_size: None,
+                http_files: None,
+                wheels_size: Some(12_000),
+                wheels: Some(3),
+            }
+        );
+        assert_eq!(
+            parse_info(include_str!("../tests/fixtures/pip-cache/info-21.0.txt")),
+            Usage {
+                http_size: Some(303_500_000),
+                http_files: Some(1019),
+                wheels_size: Some(12_000),
+                wheels: Some(3),
+            }
+        );
+        let usage = parse_info(include_str!("../tests/fixtures/pip-cache/info-24.0.txt"));
+        assert_eq!(
+            usage,
+            Usage {
+                http_size: Some(2_100_000_000),
+                http_files: Some(8431),
+                wheels_size: Some(41_600_000),
+                wheels: Some(27),
+            }
+        );
+        assert_eq!(usage.total(), Some(2_141_600_000));
+
+        let unknown = parse_info(include_str!(
+            "../tests/fixtures/pip-cache/unknown-command.txt"
+        ));
+        assert_eq!(unknown, Usage::default());
+        assert_eq!(unknown.total(), None);
+        assert!(is_unknown_command(include_str!(
+            "../tests/fixtures/pip-cache/unknown-command.txt"
+        )));
+    }
+
+    #[test]
+    fn test_parse_size_and_purged() {
+        assert_eq!(parse_size("0 bytes"), Some(0));
+        assert_eq!(parse_size("512 bytes"), Some(512));
+        assert_eq!(parse_size("1.5 kB"), Some(1500));
+        assert_eq!(parse_size("2 MiB"), Some(2 * 1024 * 1024));
+        assert_eq!(parse_size("lots"), None);
+        assert_eq!(parse_size("3 parsecs"), None);
+
+        assert_eq!(parse_purged("Files removed: 1046\n"), Some(1046));
+        assert_eq!(
+            parse_purged("WARNING: something\nFiles removed: 12 (3.4 MB)\n"),
+            Some(12)
+        );
+        assert_eq!(parse_purged("Nothing here"), None);
+    }
+
+    #[test]
+    fn test_measure() {
+        let dir = tempfile::tempdir().unwrap();
+        let http = dir.path().join("http").join("a").join("b");
+        let http_v2 = dir.path().join("http-v2").join("c");
+        let wheels = dir.path().join("wheels").join("ab").join("cd");
+        for path in [&http, &http_v2, &wheels] {
+            fs::create_dir_all(path).unwrap();
+        }
+        fs::write(http.join("page"), vec![b'x'; 100]).unwrap();
+        fs::write(http_v2.join("page.body"), vec![b'x'; 50]).unwrap();
+        fs::write(wheels.join("demo-1.0-py3-none-any.whl"), vec![b'x'; 30]).unwrap();
+        fs::write(wheels.join("origin.json"), vec![b'x'; 5]).unwrap();
+
+        assert_eq!(
+            measure(dir.path()),
+            Usage {
+                http_size: Some(150),
+                http_files: Some(2),
+                wheels_size: Some(35),
+                wheels: Some(1),
+            }
+        );
+        assert_eq!(measure(&dir.path().join("missing")).total(), Some(0));
+    }
+}
@@ -0,0 +1,549 @@
+//! pip's own configuration: its config files and `PIP_*` environment
+//! variables.
+//!
+//! Machines set up for an internal mirror usually say so in
+//! `/etc/pip.conf` or `PIP_INDEX_URL`, which every pip run honors. ppm's
+//! own index lookups read the same settings so they see the index pip
+//! installs from. A setting ppm has its own value for wins:
+//!
+//! 1. a ppm flag, like `--timeout`
+//! 2. project.toml, or the workspace's
+//! 3. pip's environment variable, like `PIP_INDEX_URL`
+//! 4. pip's config files, the later ones in [`config_files`] first
+//!
+//! Files are read the way pip reads them for `pip install`: `[install]`
+//! overrides `[global]`. The venv's own pip.conf isn't read, since ppm
+//! writes it from project.toml's `index-url`.
+
+use crate::global_config;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Names a config file pip reads after all the others; set to the null
+/// device, no config file is read at all
+pub const CONFIG_FILE_ENV: &str = "PIP_CONFIG_FILE";
+
+/// Timeout of a request to the package index when nothing sets one
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Sections of a config file that apply to `pip install`, the later
+/// overriding the earlier
+const SECTIONS: &[&str] = &["global", "install"];
+
+/// A network setting ppm shares with pip
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Key {
+    /// Simple index packages come from
+    IndexUrl,
+    /// More indexes pip looks in, separated by whitespace
+    ExtraIndexUrl,
+    /// pip's HTTP and wheel cache
+    CacheDir,
+    /// Seconds a connection may stall
+    Timeout,
+}
+
+impl Key {
+    /// Every key, in the order `ppm doctor` shows them
+    pub const ALL: [Key; 4] = [
+        Key::IndexUrl,
+        Key::ExtraIndexUrl,
+        Key::CacheDir,
+        Key::Timeout,
+    ];
+
+    /// Name of the option in a config file, like `index-url`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Key::IndexUrl => "index-url",
+            Key::ExtraIndexUrl => "extra-index-url",
+            Key::CacheDir => "cache-dir",
+            Key::Timeout => "timeout",
+        }
+    }
+
+    /// pip's environment variable for it, like `PIP_INDEX_URL`
+    pub fn env_var(&self) -> &'static str {
+        match self {
+            Key::IndexUrl => "PIP_INDEX_URL",
+            Key::ExtraIndexUrl => "PIP_EXTRA_INDEX_URL",
+            Key::CacheDir => "PIP_CACHE_DIR",
+            Key::Timeout => "PIP_TIMEOUT",
+        }
+    }
+}
+
+/// Where the value of a setting came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// A ppm command-line flag, like `--timeout`
+    Flag(String),
+    /// A project.toml, the project's or its workspace's
+    Project(PathBuf),
+    /// One of pip's environment variables
+    Env(String),
+    /// One of pip's config files
+    File(PathBuf),
+}
+
+impl Source {
+    /// The kind of source, for JSON output: `flag`, `project`, `pip-env`
+    /// or `pip-config`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Source::Flag(_) => "flag",
+            Source::Project(_) => "project",
+            Source::Env(_) => "pip-env",
+            Source::File(_) => "pip-config",
+        }
+    }
+
+    /// The flag, path or variable name, without the `$` of [`Display`](fmt::Display)
+    pub fn origin(&self) -> String {
+        match self {
+            Source::Flag(flag) => flag.clone(),
+            Source::Project(path) | Source::File(path) => path.display().to_string(),
+            Source::Env(var) => var.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Flag(flag) => f.write_str(flag),
+            Source::Project(path) | Source::File(path) => write!(f, "{}", path.display()),
+            Source::Env(var) => write!(f, "${}", var),
+        }
+    }
+}
+
+/// A setting's value with where it came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Setting {
+    /// The value as written
+    pub value: String,
+    /// Where it was found
+    pub source: Source,
+}
+
+/// Options of an INI file by section, names normalized the way pip
+/// does: lowercase, `-` for `_`, without leading `--`
+pub type Sections = BTreeMap<String, BTreeMap<String, String>>;
+
+/// Parse the INI text of a pip config file. Indented lines continue the
+/// value above them, and lines that are neither a section, an option nor
+/// a comment are skipped rather than failing, since pip reports those
+/// itself.
+pub fn parse(text: &str) -> Sections {
+    let mut sections = Sections::new();
+    let mut section: Option<String> = None;
+    let mut option: Option<String> = None;
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with(['#', ';']) {
+            continue;
+        }
+        if line.starts_with([' ', '\t'])
+            && let (Some(section), Some(option)) = (&section, &option)
+        {
+            let value = sections
+                .entry(section.clone())
+                .or_default()
+                .entry(option.clone())
+                .or_default();
+            if !value.is_empty() {
+                value.push('\n');
+            }
+            value.push_str(trimmed);
+            continue;
+        }
+        if let Some(name) = trimmed
+            .strip_prefix('[')
+            .and_then(|rest| rest.strip_suffix(']'))
+        {
+            section = Some(name.trim().to_string());
+            option = None;
+            continue;
+        }
+        let Some(split) = trimmed.find(['=', ':']) else {
+            option = None;
+            continue;
+        };
+        let Some(current) = &section else {
+            continue;
+        };
+        let name = normalize(&trimmed[..split]);
+        sections
+            .entry(current.clone())
+            .or_default()
+            .insert(name.clone(), trimmed[split + 1..].trim().to_string());
+        option = Some(name);
+    }
+    sections
+}
+
+/// An option name as pip compares them
+fn normalize(name: &str) -> String {
+    name.trim()
+        .trim_start_matches("--")
+        .to_lowercase()
+        .replace('_', "-")
+}
+
+/// pip's config files in the order pip reads them, each overriding the
+/// ones before it: site-wide, then the user's, then `$PIP_CONFIG_FILE`.
+/// `var` looks up environment variables. Files that don't exist are
+/// listed too.
+pub fn config_files(var: impl Fn(&str) -> Option<String>) -> Vec<PathBuf> {
+    let var = |name: &str| var(name).filter(|value| !value.is_empty());
+    let env_file = var(CONFIG_FILE_ENV);
+    if env_file.as_deref().is_some_and(is_null_device) {
+        return Vec::new();
+    }
+    let mut files = site_config_files(&var);
+    if cfg!(not(target_os = "windows"))
+        && let Some(home) = std::env::home_dir()
+    {
+        files.push(home.join(".pip").join("pip.conf"));
+    }
+    if let Some(dir) = global_config::platform_config_dir() {
+        files.push(dir.join("pip").join(file_name()));
+    }
+    files.extend(env_file.map(PathBuf::from));
+    files
+}
+
+/// `pip.ini` on Windows, `pip.conf` elsewhere
+fn file_name() -> &'static str {
+    if cfg!(target_os = "windows") {
+        "pip.ini"
+    } else {
+        "pip.conf"
+    }
+}
+
+fn is_null_device(path: &str) -> bool {
+    path == "/dev/null" || path.eq_ignore_ascii_case("nul")
+}
+
+#[cfg(target_os = "windows")]
+fn site_config_files(var: &impl Fn(&str) -> Option<String>) -> Vec<PathBuf> {
+    var("ALLUSERSPROFILE")
+        .map(|dir| PathBuf::from(dir).join("pip").join("pip.ini"))
+        .into_iter()
+        .collect()
+}
+
+#[cfg(target_os = "macos")]
+fn site_config_files(_var: &impl Fn(&str) -> Option<String>) -> Vec<PathBuf> {
+    vec![
+        PathBuf::from("/Library/Application Support/pip/pip.conf"),
+        PathBuf::from("/etc/pip.conf"),
+    ]
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn site_config_files(var: &impl Fn(&str) -> Option<String>) -> Vec<PathBuf> {
+    let dirs = var("XDG_CONFIG_DIRS").unwrap_or_else(|| "/etc/xdg".to_string());
+    let mut files: Vec<PathBuf> = dirs
+        .split(':')
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| PathBuf::from(dir).join("pip").join("pip.conf"))
+        .collect();
+    files.push(PathBuf::from("/etc/pip.conf"));
+    files
+}
+
+/// What pip is configured with: its config files and environment
+/// variables
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PipConfig {
+    /// Every config file pip would read, `None` for those that don't
+    /// exist or can't be read
+    pub files: Vec<(PathBuf, Option<Sections>)>,
+    /// The `PIP_*` variables of the [`Key`]s that are set
+    pub env: BTreeMap<Key, String>,
+}
+
+impl PipConfig {
+    /// Read the config files and environment of this process
+    pub fn load() -> PipConfig {
+        let files = config_files(|name| std::env::var(name).ok())
+            .into_iter()
+            .map(|path| {
+                let sections = std::fs::read_to_string(&path).ok().map(|text| parse(&text));
+                (path, sections)
+            })
+            .collect();
+        PipConfig::with_env(files, |name| std::env::var(name).ok())
+    }
+
+    /// `files` with the variables `var` looks up
+    pub fn with_env(
+        files: Vec<(PathBuf, Option<Sections>)>,
+        var: impl Fn(&str) -> Option<String>,
+    ) -> PipConfig {
+        let env = Key::ALL
+            .into_iter()
+            .filter_map(|key| {
+                let value = var(key.env_var())?;
+                let value = value.trim();
+                (!value.is_empty()).then(|| (key, value.to_string()))
+            })
+            .collect();
+        PipConfig { files, env }
+    }
+
+    /// The value pip would use for `key`, with its source
+    pub fn get(&self, key: Key) -> Option<Setting> {
+        if let Some(value) = self.env.get(&key) {
+            return Some(Setting {
+                value: value.clone(),
+                source: Source::Env(key.env_var().to_string()),
+            });
+        }
+        self.files.iter().rev().find_map(|(path, sections)| {
+            let sections = sections.as_ref()?;
+            let value = SECTIONS
+                .iter()
+                .rev()
+                .find_map(|section| sections.get(*section)?.get(key.name()))?;
+            Some(Setting {
+                value: value.clone(),
+                source: Source::File(path.clone()),
+            })
+        })
+    }
+}
+
+/// ppm's network settings, each taken from the first place that sets it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NetworkSettings {
+    /// Each [`Key`] that is set anywhere
+    pub settings: BTreeMap<Key, Setting>,
+}
+
+impl NetworkSettings {
+    /// Resolve every key: `ppm` holds ppm's own values, flags before
+    /// config, and the first one for a key wins; keys it doesn't have
+    /// come from `pip`
+    pub fn resolve(pip: &PipConfig, ppm: &[(Key, Setting)]) -> NetworkSettings {
+        let settings = Key::ALL
+            .into_iter()
+            .filter_map(|key| {
+                let own = ppm
+                    .iter()
+                    .find(|(own, _)| *own == key)
+                    .map(|(_, setting)| setting.clone());
+                Some((key, own.or_else(|| pip.get(key))?))
+            })
+            .collect();
+        NetworkSettings { settings }
+    }
+
+    /// The setting for `key`, `None` when nothing sets it
+    pub fn get(&self, key: Key) -> Option<&Setting> {
+        self.settings.get(&key)
+    }
+
+    /// The simple index URL, `None` for PyPI
+    pub fn index_url(&self) -> Option<&str> {
+        self.get(Key::IndexUrl)
+            .map(|setting| setting.value.as_str())
+    }
+
+    /// The extra index URLs, in order
+    pub fn extra_index_urls(&self) -> Vec<&str> {
+        self.get(Key::ExtraIndexUrl)
+            .map(|setting| setting.value.split_whitespace().collect())
+            .unwrap_or_default()
+    }
+
+    /// The timeout for HTTP requests, `None` when unset or not a positive
+    /// number of seconds
+    pub fn timeout(&self) -> Option<Duration> {
+        parse_timeout(&self.get(Key::Timeout)?.value).ok()
+    }
+
+    /// The timeout ppm's own requests use: [`NetworkSettings::timeout`],
+    /// or [`DEFAULT_TIMEOUT`]
+    pub fn request_timeout(&self) -> Duration {
+        self.timeout().unwrap_or(DEFAULT_TIMEOUT)
+    }
+
+    /// These settings with `flags`, ppm's command-line flags, winning over
+    /// whatever set the same keys before
+    pub fn with_flags(mut self, flags: &[(Key, Setting)]) -> NetworkSettings {
+        for (key, setting) in flags {
+            self.settings.insert(*key, setting.clone());
+        }
+        self
+    }
+}
+
+/// A timeout in seconds like `30` or `2.5`, which must be positive
+pub fn parse_timeout(text: &str) -> Result<Duration, String> {
+    let seconds: f64 = text
+        .trim()
+        .parse()
+        .map_err(|_| format!("'{}' is not a number of seconds", text))?;
+    if !seconds.is_finite() || seconds <= 0.0 {
+        return Err(format!(
+            "The timeout must be more than 0 seconds, not {}",
+            text
+        ));
+    }
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SITE: &str = include_str!("../tests/fixtures/pip-config/site.conf");
+    const USER: &str = include_str!("../tests/fixtures/pip-config/user.conf");
+
+    fn fixtures() -> Vec<(PathBuf, Option<Sections>)> {
+        vec![
+            (PathBuf::from("/etc/pip.conf"), Some(parse(SITE))),
+            (PathBuf::from("/home/me/.pip/pip.conf"), None),
+            (
+                PathBuf::from("/home/me/.config/pip/pip.conf"),
+                Some(parse(USER)),
+            ),
+        ]
+    }
+
+    fn file(path: &str) -> Source {
+        Source::File(PathBuf::from(path))
+    }
+
+    #[test]
+    fn test_parse() {
+        let sections = parse(SITE);
+        assert_eq!(
+            sections["global"]["index-url"],
+            "https://mirror.example.com/simple"
+        );
+        // Continuation lines, `:` separators and `_` in names
+        assert_eq!(
+            sections["global"]["extra-index-url"],
+            "https://a.example.com/simple\nhttps://b.example.com/simple"
+        );
+        assert_eq!(sections["global"]["cache-dir"], "/var/cache/pip");
+        assert_eq!(sections["install"]["timeout"], "60");
+        assert!(!sections["global"].contains_key("this is not an option"));
+
+        assert_eq!(parse("index-url = x\n"), Sections::new());
+        assert_eq!(
+            parse("[global]\n--Index_URL=x\n")["global"]["index-url"],
+            "x"
+        );
+    }
+
+    #[test]
+    fn test_later_files_and_install_section_win() {
+        let pip = PipConfig::with_env(fixtures(), |_| None);
+        // user.conf overrides the index, /etc/pip.conf's [install] the timeout
+        assert_eq!(
+            pip.get(Key::IndexUrl),
+            Some(Setting {
+                value: "https://user.example.com/simple".to_string(),
+                source: file("/home/me/.config/pip/pip.conf"),
+            })
+        );
+        assert_eq!(pip.get(Key::Timeout).unwrap().value, "60");
+        assert_eq!(
+            pip.get(Key::CacheDir).unwrap().source,
+            file("/etc/pip.conf")
+        );
+    }
+
+    #[test]
+    fn test_env_beats_files() {
+        let pip = PipConfig::with_env(fixtures(), |name| match name {
+            "PIP_INDEX_URL" => Some("https://env.example.com/simple".to_string()),
+            "PIP_TIMEOUT" => Some("  ".to_string()),
+            _ => None,
+        });
+        let index = pip.get(Key::IndexUrl).unwrap();
+        assert_eq!(index.value, "https://env.example.com/simple");
+        assert_eq!(index.source.to_string(), "$PIP_INDEX_URL");
+        // A blank variable doesn't count
+        assert_eq!(pip.get(Key::Timeout).unwrap().source, file("/etc/pip.conf"));
+    }
+
+    #[test]
+    fn test_ppm_settings_beat_pip() {
+        let pip = PipConfig::with_env(fixtures(), |name| {
+            (name == "PIP_TIMEOUT").then(|| "5".to_string())
+        });
+        let project = Setting {
+            value: "https://project.example.com/simple".to_string(),
+            source: Source::Project(PathBuf::from("/work/demo/project.toml")),
+        };
+        let flag = Setting {
+            value: "2.5".to_string(),
+            source: Source::Flag("--timeout".to_string()),
+        };
+        let network = NetworkSettings::resolve(
+            &pip,
+            &[
+                (Key::Timeout, flag.clone()),
+                (Key::IndexUrl, project.clone()),
+                (
+                    Key::Timeout,
+                    Setting {
+                        value: "1".to_string(),
+                        source: Source::Project(PathBuf::from("project.toml")),
+                    },
+                ),
+            ],
+        );
+        assert_eq!(network.get(Key::IndexUrl), Some(&project));
+        assert_eq!(network.get(Key::Timeout), Some(&flag));
+        assert_eq!(network.timeout(), Some(Duration::from_millis(2500)));
+        assert_eq!(
+            network.extra_index_urls(),
+            [
+                "https://a.example.com/simple",
+                "https://b.example.com/simple"
+            ]
+        );
+
+        let network = NetworkSettings::resolve(&PipConfig::default(), &[]);
+        assert_eq!(network, NetworkSettings::default());
+        assert_eq!(network.index_url(), None);
+        assert_eq!(network.timeout(), None);
+        assert_eq!(network.request_timeout(), DEFAULT_TIMEOUT);
+
+        let network = NetworkSettings::resolve(&pip, &[(Key::IndexUrl, project.clone())])
+            .with_flags(&[(Key::Timeout, flag.clone())]);
+        assert_eq!(network.get(Key::Timeout), Some(&flag));
+        assert_eq!(network.get(Key::IndexUrl), Some(&project));
+        assert_eq!(network.request_timeout(), Duration::from_millis(2500));
+    }
+
+    #[test]
+    fn test_parse_timeout() {
+        assert_eq!(parse_timeout("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_timeout(" 0.5 "), Ok(Duration::from_millis(500)));
+        for bad in ["0", "-1", "soon", "inf", "NaN"] {
+            assert!(parse_timeout(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_config_file_env() {
+        let files =
+            config_files(|name| (name == CONFIG_FILE_ENV).then(|| "/opt/ci/pip.conf".to_string()));
+        assert_eq!(files.last(), Some(&PathBuf::from("/opt/ci/pip.conf")));
+        assert!(files.len() > 1);
+
+        let none = config_files(|name| (name == CONFIG_FILE_ENV).then(|| "/dev/null".to_string()));
+        assert!(none.is_empty());
+    }
+}
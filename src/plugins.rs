@@ -0,0 +1,127 @@
+//! External subcommands: `ppm deploy` runs an executable named
+//! `ppm-deploy` found on `PATH`, like cargo and git do.
+//!
+//! Only exact names in absolute `PATH` entries count; relative entries,
+//! the empty one included, would search the current directory and are
+//! skipped. The plugin gets the rest of the command line as its
+//! arguments and the project's locations in [`PROJECT_ROOT_ENV`],
+//! [`VENV_ENV`] and [`CONFIG_ENV`] when run inside a project.
+
+use crate::paths;
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::path::PathBuf;
+
+/// Prefix of plugin executables
+pub const PLUGIN_PREFIX: &str = "ppm-";
+
+/// The project root, for plugins run inside a project
+pub const PROJECT_ROOT_ENV: &str = "PPM_PROJECT_ROOT";
+
+/// The project's virtual environment directory
+pub const VENV_ENV: &str = "PPM_VENV";
+
+/// The project's project.toml
+pub const CONFIG_ENV: &str = "PPM_CONFIG";
+
+/// Absolute directories in the `PATH` value `path_var`, in order
+fn search_dirs(path_var: &OsStr) -> Vec<PathBuf> {
+    std::env::split_paths(path_var)
+        .filter(|dir| dir.is_absolute())
+        .collect()
+}
+
+/// File names a plugin called `name` may have on this platform
+fn file_names(name: &str) -> Vec<String> {
+    let base = format!("{}{}", PLUGIN_PREFIX, name);
+    if cfg!(target_os = "windows") {
+        [".exe", ".cmd", ".bat"]
+            .iter()
+            .map(|ext| format!("{}{}", base, ext))
+            .collect()
+    } else {
+        vec![base]
+    }
+}
+
+/// The executable for plugin `name`, first match in `path_var` order
+pub fn find_plugin(name: &str, path_var: &OsStr) -> Option<PathBuf> {
+    if name.is_empty() || name.contains(['/', '\\']) {
+        return None;
+    }
+    let names = file_names(name);
+    search_dirs(path_var).into_iter().find_map(|dir| {
+        names
+            .iter()
+            .map(|file_name| dir.join(file_name))
+            .find(|path| paths::is_executable(path))
+    })
+}
+
+/// Every plugin in `path_var` by name, each with the executable that
+/// [`find_plugin`] would run
+pub fn discover_plugins(path_var: &OsStr) -> BTreeMap<String, PathBuf> {
+    let mut plugins = BTreeMap::new();
+    for dir in search_dirs(path_var) {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            let Some(name) = file_name.strip_prefix(PLUGIN_PREFIX) else {
+                continue;
+            };
+            let name = if cfg!(target_os = "windows") {
+                match name.rsplit_once('.') {
+                    Some((stem, ext)) if ["exe", "cmd", "bat"].contains(&ext) => stem,
+                    _ => continue,
+                }
+            } else {
+                name
+            };
+            if name.is_empty() || plugins.contains_key(name) {
+                continue;
+            }
+            if let Some(path) = find_plugin(name, path_var) {
+                plugins.insert(name.to_string(), path);
+            }
+        }
+    }
+    plugins
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::Path;
+
+    fn write_script(dir: &Path, name: &str, mode: u32) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, "#!/bin/sh\nexit 0\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_plugins_are_found_on_path_only() {
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+        let deploy = write_script(first.path(), "ppm-deploy", 0o755);
+        write_script(second.path(), "ppm-deploy", 0o755);
+        let lint = write_script(second.path(), "ppm-lint", 0o755);
+        write_script(second.path(), "ppm-notes", 0o644);
+        write_script(second.path(), "deploy", 0o755);
+
+        let path_var =
+            std::env::join_paths([first.path(), Path::new("relative"), second.path()]).unwrap();
+        assert_eq!(find_plugin("deploy", &path_var), Some(deploy.clone()));
+        assert_eq!(find_plugin("notes", &path_var), None);
+        assert_eq!(find_plugin("../ppm-deploy", &path_var), None);
+        assert_eq!(
+            discover_plugins(&path_var),
+            BTreeMap::from([("deploy".to_string(), deploy), ("lint".to_string(), lint)])
+        );
+    }
+}
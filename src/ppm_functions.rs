@@ -1,39 +1,106 @@
-use crate::settings::Config;
+use crate::progress::{Event, Phase, Progress, Status};
+use crate::table::{Cell, Style, Table};
 use crate::utils::*;
 use colored::*;
-pub(crate) use std::path::Path;
+use ppmm::PpmError;
+use ppmm::error::ErrorCode;
+use ppmm::activate::{ActivateShell, VenvEnv};
+use ppmm::aliases::{self, Aliases};
+use ppmm::ProjectContext;
+use ppmm::packages::{compare_versions, normalize_pkg_name};
+use ppmm::prompt::Prompter;
+use ppmm::requirements::{self, RenderOptions, diff_requirements, generate_requirements};
+use ppmm::resolver::{self, Drift, ResolvedPackage, find_drift, render_lock};
+use ppmm::scripts::{self, Script};
+use ppmm::settings::{PROJECT_CONFIG_FILE, PackageSpec, UpdatePolicy};
+use ppmm::state::{self, StateDir, format_size};
+use ppmm::stats;
+use ppmm::test_runner::{self, Framework};
+use ppmm::age::{self, ReleaseDays};
+use ppmm::build;
+use ppmm::bundle::{self, BUNDLE_CACHE, BUNDLE_FORMAT, Manifest, Target};
+use ppmm::constraints::{self, CONSTRAINTS_FILE};
+use ppmm::coverage;
+use ppmm::downloads::{self, Platform};
+use ppmm::entry_points;
+use ppmm::index::{self, ReleaseEntry};
+use ppmm::paths;
+use ppmm::pip_cache;
+use ppmm::pip_config::{Key, NetworkSettings, PipConfig};
+use ppmm::plugins;
+use ppmm::preflight::{self, CheckResult, Preflight};
+use ppmm::presets::{self, Task, Tool};
+use ppmm::publish;
+use ppmm::report::{self, Report};
+use ppmm::global_config::{self, GlobalConfig};
+use ppmm::self_update::{self, CheckState};
+use ppmm::git;
+use ppmm::graph::{self, DependencyGraph, GraphFormat};
+use ppmm::history::{self, Outcome};
+use ppmm::hooks;
+use ppmm::ide;
+use ppmm::imports;
+use ppmm::install::{self, InstallTask};
+use ppmm::resume::{self, PendingUpdate};
+use ppmm::runner::{self, RunOptions};
+use ppmm::launch::{self, Launch};
+use ppmm::listing::{self, InstallState, Origin, OutdatedRow, PackageRow, SortKey};
+use ppmm::main_script;
+use ppmm::metadata_cache;
+use ppmm::update::{
+    UpdateOptions, UpdateSummary, constrain_updates_with, lookup_latest, plan_git_refresh_with,
+    plan_policy_updates_with, plan_updates_with, update_targets,
+};
+use ppmm::venv::{self, check_interpreter};
+use ppmm::verify::{self, VerifyStatus};
+use ppmm::workspace;
+use ppmm::yanked::{self, YankedPin};
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsString;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-pub fn show_project_info() {
-    let config_file = get_project_config_file();
-    if !Path::new(config_file).exists() {
-        eprint(format!("Could not find {}", config_file));
-        return;
-    }
-    let conf = match Config::load_from_file(config_file) {
-        Ok(conf) => conf,
-        Err(e) => {
-            eprint(e.to_string());
-            return;
-        }
-    };
+/// `ppm info`: the project's metadata, scripts and packages, showing at
+/// most `limit` packages when set
+pub fn show_project_info(limit: Option<usize>) -> Result<(), PpmError> {
+    let ctx = load_project()?;
+    let conf = &ctx.config;
     println!();
 
-    let venv_root = conf.project.venv.as_deref().unwrap_or("venv");
-
-    match Command::new(get_venv_python_path(venv_root))
-        .arg("--version")
-        .output()
-    {
-        Ok(output) if output.status.success() => {
-            let version = String::from_utf8_lossy(&output.stdout);
-            if let Some((name, ver)) = version.trim().split_once(' ') {
-                println!("{}: {}", name.bold().bright_purple(), ver.bold().red());
-            }
-        }
-        Ok(_) | Err(_) => {
+    let venv_exists = check_venv_dir_exists(&ctx);
+    let environment = match (venv_exists, ctx.python_version()) {
+        _ if ctx.system_python => "system Python (use-venv = false)".bold().to_string(),
+        (false, _) => "no venv".bright_red().bold().to_string(),
+        (true, Some(version)) => format!(
+            "venv '{}', Python {}",
+            ctx.venv_root(),
+            version.bold().red()
+        ),
+        (true, None) => format!("venv '{}'", ctx.venv_root()),
+    };
+    println!("{}: {}", "Environment".bold().bright_purple(), environment);
+    // One `pip list` tells which packages below are installed, and at what
+    let installed = if ctx.system_python {
+        None
+    } else if !venv_exists {
+        wprint(format!(
+            "Could not find '{}' directory, the Python version and installed packages are unavailable; `ppm install` creates it",
+            ctx.venv_root()
+        ));
+        None
+    } else {
+        if ctx.python_version().is_none() {
             wprint("Failed to get Python version".to_string());
         }
+        match get_installed_packages(&ctx) {
+            Ok(installed) => Some(installed),
+            Err(e) => {
+                wprint(e);
+                None
+            }
+        }
     };
 
     println!(
@@ -51,6 +118,13 @@ pub fn show_project_info() {
         "Description".green().bold(),
         conf.project.description.bright_white().bold()
     );
+    if let Some(license) = &conf.project.license {
+        println!(
+            "{}: {}",
+            "License".green().bold(),
+            license.bright_white().bold()
+        );
+    }
 
     println!();
     let count = conf.scripts.len();
@@ -63,8 +137,66 @@ pub fn show_project_info() {
             "Scripts".to_owned()
         }
     );
-    for (name, cmd) in conf.scripts.iter() {
-        println!("{}: {}", name.bright_yellow().bold(), cmd.green().bold());
+    let os = std::env::consts::OS;
+    for (name, script) in conf.scripts.iter() {
+        let Some(command) = script.command() else {
+            println!(
+                "{}: {}",
+                name.bright_yellow().bold(),
+                format!("(no command for {})", os).red()
+            );
+            continue;
+        };
+        let mut notes = vec![];
+        if script.is_os_variant(os) {
+            notes.push(format!("{} variant", os));
+        }
+        let requires = script.requires();
+        if !requires.is_empty() {
+            notes.push(format!("requires {}", requires.join(", ")));
+        }
+        if notes.is_empty() {
+            println!(
+                "{}: {}",
+                name.bright_yellow().bold(),
+                command.green().bold()
+            );
+        } else {
+            println!(
+                "{}: {} {}",
+                name.bright_yellow().bold(),
+                command.green().bold(),
+                format!("({})", notes.join("; ")).dimmed()
+            );
+        }
+    }
+    let entry_points = ctx.entry_points();
+    if !entry_points.is_empty() {
+        println!();
+        println!(
+            "-- {} {} --",
+            entry_points.len().to_string().green().bold(),
+            if entry_points.len() == 1 {
+                "Entry point"
+            } else {
+                "Entry points"
+            }
+        );
+    }
+    for entry_point in &entry_points {
+        let mut notes = vec!["entry point".to_string()];
+        if entry_point.source == entry_points::Source::Pyproject {
+            notes.push("not installed".to_string());
+        }
+        if conf.scripts.contains_key(&entry_point.name) {
+            notes.push("shadowed by [scripts]".to_string());
+        }
+        println!(
+            "{}: {} {}",
+            entry_point.name.bright_yellow().bold(),
+            entry_point.target.green().bold(),
+            format!("({})", notes.join("; ")).dimmed()
+        );
     }
 
     println!();
@@ -78,262 +210,4496 @@ pub fn show_project_info() {
             "Packages".to_owned()
         }
     );
-    for (name, version) in conf.packages.iter().take(10) {
+    let rows = listing::collect(&conf.packages, installed.as_ref(), None, SortKey::Name);
+    let shown = limit.unwrap_or(rows.len());
+    let theme = theme();
+    for row in rows.iter().take(shown) {
+        let spec = &row.spec;
+        let marker = match spec.marker() {
+            Some(marker) => format!(" ; {}", marker).dimmed().to_string(),
+            None => String::new(),
+        };
+        let status = match (row.state, &row.installed) {
+            (InstallState::Unknown, _) => String::new(),
+            (InstallState::Missing, _) => {
+                let missing = format!("{} not installed", theme.cross);
+                format!(" {}", theme.error(&missing))
+            }
+            (InstallState::Differs, Some(version)) => {
+                format!(" {}", format!("installed {}", version).yellow())
+            }
+            _ => format!(" {}", theme.info(&theme.check)),
+        };
         println!(
-            "{}=={}",
-            name.bright_yellow().bold(),
-            version.bright_red().bold()
+            "{}{}{}{}{}",
+            row.name.bright_yellow().bold(),
+            if spec.is_index() { "==" } else { " @ " },
+            spec.to_string().bright_red().bold(),
+            marker,
+            status
         );
     }
-    if conf.packages.len() > 10 {
-        println!("... and {} more", conf.packages.len() - 10);
+    if rows.len() > shown {
+        println!(
+            "... and {} more; {}",
+            rows.len() - shown,
+            "see them all with `ppm info --full`".dimmed()
+        );
     }
     println!();
+    Ok(())
 }
 
-pub fn gen_requirements() {
-    let config_file = get_project_config_file();
-    if !Path::new(config_file).exists() {
-        eprint(format!("Could not find {}", config_file));
-        return;
+/// `ppm info <package>`: the package's releases, newest first, with the
+/// day each was uploaded and its age; at most `limit` when set
+pub fn show_package_info(name: &str, limit: Option<usize>, stats: bool) -> Result<(), PpmError> {
+    let ctx = load_project()?;
+    let configured = ctx
+        .config
+        .packages
+        .iter()
+        .find(|(configured, _)| normalize_pkg_name(configured) == normalize_pkg_name(name))
+        .map(|(_, spec)| spec);
+    let Some(history) = ctx.release_histories(&[name.to_string()])?.pop().flatten() else {
+        return Err(PpmError::Network(format!(
+            "Could not look up the releases of {}{}",
+            name,
+            if ctx.offline {
+                "; none are cached for --offline"
+            } else {
+                ""
+            }
+        )));
+    };
+    let pinned = configured
+        .filter(|spec| spec.is_index())
+        .and_then(|spec| spec.version());
+    let latest = age::latest_release(&history).map(|release| release.version.as_str());
+    let mut releases: Vec<&ReleaseEntry> = history.iter().collect();
+    releases.sort_by(|a, b| compare_versions(&b.version, &a.version));
+    let today = age::today();
+    let age_of = |release: &ReleaseEntry| {
+        release
+            .uploaded
+            .as_deref()
+            .and_then(|day| age::age_in_days(day, today))
+    };
+    // Neither the size nor the download count is worth failing over
+    let files = ctx.latest_files(name).unwrap_or_default();
+    let download = downloads::pick_download(&files, &Platform::current());
+    let monthly = if stats {
+        ctx.monthly_downloads(name)
+    } else {
+        None
+    };
+
+    if json_output() {
+        let releases: Vec<serde_json::Value> = releases
+            .iter()
+            .map(|release| {
+                serde_json::json!({
+                    "version": release.version,
+                    "uploaded": release.uploaded,
+                    "age_days": age_of(release),
+                    "yanked": release.yanked,
+                })
+            })
+            .collect();
+        println!(
+            "{}",
+            serde_json::json!({
+                "name": name,
+                "configured": configured.map(PackageSpec::to_string),
+                "latest": latest,
+                "download": download,
+                "downloads_last_month": monthly,
+                "releases": releases,
+            })
+        );
+        return Ok(());
     }
 
-    let conf = match Config::load_from_file(config_file) {
-        Ok(conf) => conf,
-        Err(e) => {
-            eprint(e.to_string());
-            return;
+    println!();
+    println!("{}: {}", "Package".green().bold(), name.bright_cyan().bold());
+    if let Some(spec) = configured {
+        println!(
+            "{}: {}",
+            "Configured".green().bold(),
+            spec.to_string().bright_red().bold()
+        );
+    }
+    if let Some(file) = download {
+        let (label, note) = if file.is_wheel() {
+            ("Wheel size", file.filename.clone())
+        } else {
+            ("Sdist size", format!("{}; no wheel for this platform", file.filename))
+        };
+        println!(
+            "{}: {} {}",
+            label.green().bold(),
+            format_size(file.size),
+            format!("({})", note).dimmed()
+        );
+    }
+    if let Some(monthly) = monthly {
+        println!(
+            "{}: {} {}",
+            "Downloads/month".green().bold(),
+            downloads::format_count(monthly),
+            "(pypistats.org)".dimmed()
+        );
+    }
+    println!();
+    let shown = limit.unwrap_or(releases.len()).min(releases.len());
+    let mut table = Table::new(&["Version", "Uploaded", "Age", "Note"]);
+    for release in &releases[..shown] {
+        let is = |version: Option<&str>| {
+            version.is_some_and(|version| compare_versions(version, &release.version).is_eq())
+        };
+        let note = if release.yanked {
+            Cell::new("yanked", Style::Error)
+        } else if is(pinned) {
+            Cell::new("pinned", Style::Warn)
+        } else if is(latest) {
+            Cell::new("latest", Style::Info)
+        } else {
+            Cell::plain("")
+        };
+        table.add_row(vec![
+            Cell::new(release.version.clone(), Style::Bold),
+            Cell::plain(release.uploaded.clone().unwrap_or_else(|| "?".to_string())),
+            Cell::new(
+                age_of(release).map_or("?".to_string(), age::format_age),
+                Style::Dim,
+            ),
+            note,
+        ]);
+    }
+    table.print();
+    if releases.len() > shown {
+        println!(
+            "... and {} more; {}",
+            releases.len() - shown,
+            format!("see them all with `ppm info {} --full`", name).dimmed()
+        );
+    }
+    println!();
+    Ok(())
+}
+
+/// Write the requirements file from project.toml, or with `from_lock`
+/// every package ppmm.lock pins, `# via` annotated with `annotate`
+pub fn gen_requirements(
+    from_lock: bool,
+    annotate: bool,
+    reproducible: bool,
+) -> Result<(), PpmError> {
+    let ctx = load_project()?;
+    let req_file = ctx.config.requirements_file();
+    let contents = if from_lock {
+        let graph = if annotate {
+            let (graph, _) = graph::current_or_cached(&ctx).ok_or_else(|| {
+                PpmError::Venv(
+                    "--annotate needs the dependency graph; run `ppm install` first".to_string(),
+                )
+            })?;
+            Some(graph)
+        } else {
+            None
+        };
+        locked_requirements(&ctx, graph.as_ref(), reproducible)?
+    } else {
+        generated_requirements(&ctx, reproducible)
+    };
+    write_requirements(&ctx, &contents)?;
+    iprint(format!("Generated {}", req_file));
+    Ok(())
+}
+
+/// The header comment of a generated requirements file, with the time
+/// unless `reproducible`
+fn generated_header(ctx: &ProjectContext, source: &str, reproducible: bool) -> String {
+    let timestamp = (!reproducible).then(|| {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0);
+        history::format_timestamp(secs)
+    });
+    requirements::generated_header(&ctx.config, source, timestamp.as_deref())
+}
+
+/// The requirements file `ppm gen` writes from project.toml
+fn generated_requirements(ctx: &ProjectContext, reproducible: bool) -> String {
+    let opts = RenderOptions {
+        header: Some(generated_header(ctx, PROJECT_CONFIG_FILE, reproducible)),
+        ..RenderOptions::default()
+    };
+    generate_requirements(&ctx.config, &opts)
+}
+
+/// The requirements file `ppm gen --from-lock` writes, annotated from
+/// `graph` when given
+fn locked_requirements(
+    ctx: &ProjectContext,
+    graph: Option<&DependencyGraph>,
+    reproducible: bool,
+) -> Result<String, PpmError> {
+    let lock_file = get_lock_file();
+    let lock = read_lock_file(ctx, ctx.config.requirements_file())?;
+    let header = generated_header(ctx, lock_file, reproducible);
+    requirements::render_locked(&ctx.config, &lock, graph, Some(header))
+        .map_err(|e| PpmError::Other(format!("Could not read {}: {}", lock_file, e)))
+}
+
+fn write_requirements(ctx: &ProjectContext, contents: &str) -> Result<(), PpmError> {
+    let req_file = ctx.config.requirements_file();
+    std::fs::write(ctx.root.join(req_file), contents)
+        .map_err(|e| PpmError::Other(format!("Could not write {}: {}", req_file, e)))
+}
+
+/// Regenerate the requirements file after a command changed the packages,
+/// when the project sets `sync-requirements` or `--sync-requirements` was
+/// given. A file generated from ppmm.lock is regenerated from it, keeping
+/// its annotations when the dependency graph is at hand; an up-to-date
+/// file is left alone.
+pub fn sync_requirements() -> Result<(), PpmError> {
+    let ctx = load_project()?;
+    if !ctx.config.project.sync_requirements && !sync_requirements_forced() {
+        return Ok(());
+    }
+    let req_file = ctx.config.requirements_file();
+    let current = std::fs::read_to_string(ctx.root.join(req_file)).ok();
+    let generated = match &current {
+        Some(current) if requirements::generated_from(current) == Some(get_lock_file()) => {
+            let graph = current
+                .contains("# via ")
+                .then(|| graph::current_or_cached(&ctx))
+                .flatten()
+                .map(|(graph, _)| graph);
+            locked_requirements(&ctx, graph.as_ref(), false)?
         }
+        _ => generated_requirements(&ctx, false),
     };
+    if current.is_some_and(|current| diff_requirements(&current, &generated).is_empty()) {
+        return Ok(());
+    }
+    write_requirements(&ctx, &generated)?;
+    iprint(format!("Updated {}", req_file));
+    Ok(())
+}
+
+/// The contents of ppmm.lock, which `target` is generated from
+fn read_lock_file(ctx: &ProjectContext, target: &str) -> Result<String, PpmError> {
+    let lock_file = get_lock_file();
+    std::fs::read_to_string(ctx.root.join(lock_file)).map_err(|_| {
+        PpmError::Other(format!(
+            "No {} to generate {} from; run `ppm lock` first",
+            lock_file, target
+        ))
+    })
+}
 
-    let mut reqs = String::new();
-    for (name, version) in conf.packages.iter() {
-        reqs.push_str(&format!("{}=={}\n", name, version));
+/// Write constraints.txt from ppmm.lock
+pub fn gen_constraints() -> Result<(), PpmError> {
+    let ctx = load_project()?;
+    let lock_file = get_lock_file();
+    let lock = read_lock_file(&ctx, CONSTRAINTS_FILE)?;
+    let pins = constraints::from_lock(&lock)
+        .map_err(|e| PpmError::Other(format!("Could not read {}: {}", lock_file, e)))?;
+    std::fs::write(ctx.root.join(CONSTRAINTS_FILE), pins)
+        .map_err(|e| PpmError::Other(format!("Could not write {}: {}", CONSTRAINTS_FILE, e)))?;
+    iprint(format!("Generated {} from {}", CONSTRAINTS_FILE, lock_file));
+    Ok(())
+}
+
+pub fn check_config() -> Result<(), PpmError> {
+    let ctx = load_project()?;
+    if json_output() {
+        println!(
+            "{}",
+            serde_json::json!({
+                "valid": true,
+                "path": ctx.config_path.to_string_lossy(),
+                "warnings": ctx.warnings,
+            })
+        );
+    } else {
+        iprint(format!("{} is valid", paths::display(&ctx.config_path)));
     }
+    Ok(())
+}
 
-    let req_file = get_requirements_file();
-    match std::fs::write(req_file, reqs) {
-        Ok(_) => iprint(format!("Generated {}", req_file)),
-        Err(e) => eprint(format!("Could not write {}: {}", req_file, e)),
+/// The full dependency set of project.toml, resolved by the venv's pip
+fn resolve_packages(ctx: &ProjectContext) -> Result<Vec<ResolvedPackage>, PpmError> {
+    ctx.require_venv()?;
+    let spinner = Progress::spinner(Phase::Resolve, "Resolving dependencies with pip...");
+    match resolver::resolve(ctx) {
+        Ok(resolved) => {
+            spinner.finish();
+            Ok(resolved)
+        }
+        Err(e) => {
+            spinner.abandon(&e.to_string());
+            Err(e)
+        }
     }
 }
 
-pub fn start_project() {
-    let config_file = get_project_config_file();
-    if !Path::new(config_file).exists() {
-        eprint(format!("Could not find {}", config_file));
-        return;
+pub fn lock_packages() -> Result<(), PpmError> {
+    let ctx = load_project()?;
+    let _lock = lock_project(&ctx)?;
+
+    let resolved = resolve_packages(&ctx)?;
+    let lock_file = get_lock_file();
+    std::fs::write(ctx.root.join(lock_file), render_lock(&resolved))
+        .map_err(|e| PpmError::Other(format!("Could not write {}: {}", lock_file, e)))?;
+    let requested = resolved.iter().filter(|pkg| pkg.requested).count();
+    iprint(format!(
+        "Locked {} packages ({} configured, {} dependencies) to {}",
+        resolved.len(),
+        requested,
+        resolved.len() - requested,
+        lock_file
+    ));
+    Ok(())
+}
+
+/// Write project.toml, ppmm.lock and every distribution the lock pins,
+/// downloaded by the venv's pip, into the archive `output`
+pub fn create_bundle(output: &Path) -> Result<(), PpmError> {
+    let ctx = load_project()?;
+    let lock_file = get_lock_file();
+    if !ctx.root.join(lock_file).is_file() {
+        return Err(PpmError::Other(format!(
+            "No {} to bundle; run `ppm lock` first",
+            lock_file
+        )));
+    }
+    ctx.check_venv_in_use()?;
+    if !check_venv_dir_exists(&ctx) {
+        return Err(PpmError::Venv(format!(
+            "No virtual environment at {}; run `ppm install` to create it",
+            ctx.venv_root()
+        )));
     }
 
-    let conf = match Config::load_from_file(config_file) {
-        Ok(conf) => conf,
+    let state = ctx.state();
+    state.remove(BUNDLE_CACHE)?;
+    let wheels = state.ensure_dir(BUNDLE_CACHE)?;
+    let spinner = Progress::spinner(Phase::Resolve, "Downloading locked distributions...");
+    let mut args = runner::args(["download", "--no-deps", "--dest"]);
+    args.push(wheels.clone().into());
+    args.extend(runner::args(["-r", lock_file]));
+    let download = match ctx.runner.run(
+        ctx.venv_pip().as_os_str(),
+        &args,
+        &RunOptions::in_dir(&ctx.root),
+    ) {
+        Ok(download) => download,
         Err(e) => {
-            eprint(e.to_string());
-            return;
+            let msg = format!("Failed to execute pip: {}", e);
+            spinner.abandon(&msg);
+            return Err(PpmError::Subprocess(msg));
         }
     };
+    if !download.success() {
+        let err = PpmError::from_pip(download.stderr_lossy(), download.code);
+        spinner.abandon(&err.to_string());
+        return Err(err);
+    }
+    spinner.finish();
 
-    let venv_root = conf.project.venv.as_deref().unwrap_or("venv");
-
-    if !Path::new(&conf.project.main_script).exists() {
-        eprint(format!(
-            "Main script '{}' not found",
-            conf.project.main_script
+    let files = bundle::bundled_files(&wheels)?;
+    for file in files.iter().filter(|file| !file.filename.ends_with(".whl")) {
+        wprint(format!(
+            "{} has no wheel; it is built from source when the bundle is installed",
+            file.filename
         ));
-        return;
     }
+    let manifest = Manifest {
+        format: BUNDLE_FORMAT,
+        ppm_version: env!("CARGO_PKG_VERSION").to_string(),
+        project: ctx.config.project.name.clone(),
+        version: ctx.config.project.version.clone(),
+        os: std::env::consts::OS.to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+        python: ctx.python_version().map(str::to_string),
+        files,
+    };
+    let written = bundle::write_archive(output, &manifest, &ctx.root, &wheels);
+    state.remove(BUNDLE_CACHE)?;
+    written?;
+    iprint(format!(
+        "Bundled {} {} with {} distributions into {} (made on {})",
+        manifest.project,
+        manifest.version,
+        manifest.files.len(),
+        output.display(),
+        manifest.built_for()
+    ));
+    Ok(())
+}
 
-    let mut child = match Command::new(get_venv_python_path(venv_root))
-        .arg(&conf.project.main_script)
-        .spawn()
-    {
-        Ok(child) => child,
-        Err(e) => {
-            eprint("Failed to start main file".to_owned());
-            eprint(e.to_string());
-            return;
-        }
+/// Unpack the bundle `archive` into `dir`, the current directory by
+/// default, then create the venv and install the bundled files into it
+/// without contacting an index. Wheels that can't be installed here fail
+/// the command before anything is written.
+pub fn install_bundle(archive: &Path, dir: Option<&Path>, force: bool) -> Result<(), PpmError> {
+    let manifest = bundle::read_manifest(archive)?;
+    let root = match dir {
+        Some(dir) => dir.to_path_buf(),
+        None => std::env::current_dir()?,
     };
+    if root.join(PROJECT_CONFIG_FILE).exists() && !force {
+        return Err(PpmError::Other(format!(
+            "{} already has a {}; pass --force to replace it with the bundle's",
+            paths::display(&root),
+            PROJECT_CONFIG_FILE
+        )));
+    }
+    let config_text = bundle::read_text(archive, PROJECT_CONFIG_FILE)?.unwrap_or_default();
+    let config: ppmm::settings::Config =
+        toml::from_str(&config_text).map_err(|source| PpmError::ConfigParse {
+            path: format!("{} in {}", PROJECT_CONFIG_FILE, archive.display()),
+            source,
+        })?;
+    let python = venv::interpreter_command(config.project.python.as_deref());
+    let target = Target {
+        platform: Platform::current(),
+        python: venv::probe_interpreter_version(&runner::SystemRunner, &python)
+            .as_deref()
+            .and_then(bundle::python_version),
+    };
+    bundle::check_compatible(&manifest, &target)?;
 
-    match child.wait() {
-        Ok(status) => {
-            if !status.success() {
-                wprint(format!("Process exited with status: {}", status));
-            }
-        }
+    std::fs::create_dir_all(&root)?;
+    let wheels = StateDir::project(&root).ensure_dir(BUNDLE_CACHE)?;
+    bundle::extract(archive, &manifest, &root, &wheels)?;
+    let mut ctx = ProjectContext::load(&root)?;
+    apply_global_flags(&mut ctx);
+    ctx.check_venv_in_use()?;
+    if !check_venv_dir_exists(&ctx) {
+        setup_project_venv(
+            ctx.runner.as_ref(),
+            &ctx.root,
+            ctx.venv_root(),
+            ctx.shared.python.as_deref(),
+        )?;
+    }
+
+    iprint(format!(
+        "Installing {} distributions from the bundle...",
+        manifest.files.len()
+    ));
+    let spinner = Progress::spinner(Phase::Install, "pip install --no-index");
+    let mut args = runner::args(["install", "--no-index", "--find-links"]);
+    args.push(wheels.clone().into());
+    args.extend(runner::args(["-r", get_lock_file()]));
+    let options = RunOptions {
+        shield: true,
+        ..RunOptions::in_dir(&ctx.root)
+    };
+    let installed = ctx.runner.run(ctx.venv_pip().as_os_str(), &args, &options);
+    StateDir::project(&root).remove(BUNDLE_CACHE)?;
+    let installed = match installed {
+        Ok(installed) => installed,
         Err(e) => {
-            eprint(format!("Error waiting for process: {}", e));
+            let msg = format!("Failed to execute pip: {}", e);
+            spinner.abandon(&msg);
+            return Err(PpmError::Subprocess(msg));
         }
+    };
+    if !installed.success() {
+        let err = PpmError::from_pip(installed.stderr_lossy(), installed.code);
+        spinner.abandon(&err.to_string());
+        return Err(err);
     }
+    spinner.finish();
+    iprint(format!(
+        "Installed {} {} into {}",
+        manifest.project,
+        manifest.version,
+        paths::display(&ctx.venv_dir())
+    ));
+    Ok(())
 }
 
-pub fn update_packages(pkg_names: &[String]) {
-    let config_file = get_project_config_file();
-    if !Path::new(config_file).exists() {
-        eprint(format!("Could not find {}", config_file));
-        return;
+/// Run `ppm check`'s checks but those in `skip`, one line each
+pub fn run_checks(online: bool, deny_yanked: bool, skip: &[String]) -> Result<(), PpmError> {
+    let cwd = std::env::current_dir()?;
+    let root = paths::find_project_root(&cwd)
+        .ok_or_else(|| PpmError::ConfigNotFound(PROJECT_CONFIG_FILE.to_string()))?;
+    let project = ProjectContext::load(&root).ok().map(|mut ctx| {
+        apply_global_flags(&mut ctx);
+        ctx
+    });
+    let results = preflight::run(&Preflight::new(root, project, online, deny_yanked), skip)?;
+    if json_output() {
+        let checks: Vec<serde_json::Value> = results.iter().map(CheckResult::to_json).collect();
+        println!("{}", serde_json::json!({ "checks": checks }));
+    } else {
+        let width = results.iter().map(|result| result.name.len()).max().unwrap_or(0);
+        for result in &results {
+            let status = match result.outcome {
+                preflight::Outcome::Pass(_) => "pass".green(),
+                preflight::Outcome::Warn(_) => "warn".yellow(),
+                preflight::Outcome::Skip(_) => "skip".dimmed(),
+                preflight::Outcome::Fail(_) => "fail".red(),
+            };
+            println!(
+                "  {}  {:<width$}  {}",
+                status,
+                result.name,
+                result.outcome.detail(),
+                width = width
+            );
+        }
     }
+    preflight::verdict(&results)
+}
 
-    let mut conf = match Config::load_from_file(config_file) {
-        Ok(conf) => conf,
-        Err(e) => {
-            eprint(e.to_string());
-            return;
+pub fn install_hooks(only: &[String], force: bool) -> Result<(), PpmError> {
+    let ctx = load_project()?;
+    let planned = hooks::planned(&ctx.config, only)?;
+    let (hooks_dir, prefix) = hooks::locate(&ctx.root)?;
+    let mut installed = vec![];
+    for hook in &planned {
+        let script = hooks::script(hook, &prefix);
+        let replaced = hooks::install(&hooks_dir, &hook.name, &script, force)?;
+        if !json_output() {
+            iprint(format!(
+                "{} the {} hook: ppmm {}",
+                if replaced { "Updated" } else { "Installed" },
+                hook.name,
+                hook.command
+            ));
         }
-    };
+        installed.push(serde_json::json!({
+            "name": hook.name,
+            "command": hook.command,
+            "path": hooks_dir.join(&hook.name).to_string_lossy(),
+        }));
+    }
+    if json_output() {
+        println!("{}", serde_json::json!({ "installed": installed }));
+    }
+    Ok(())
+}
 
-    if conf.packages.is_empty() {
-        eprint("No packages to update".to_owned());
-        return;
+pub fn uninstall_hooks() -> Result<(), PpmError> {
+    let ctx = load_project()?;
+    let (hooks_dir, _) = hooks::locate(&ctx.root)?;
+    let removed = hooks::uninstall(&hooks_dir)?;
+    if json_output() {
+        println!("{}", serde_json::json!({ "removed": removed }));
+    } else if removed.is_empty() {
+        iprint("No hooks installed by ppm".to_string());
+    } else {
+        iprint(format!("Removed the {} hook(s)", removed.join(", ")));
+    }
+    Ok(())
+}
+
+/// Warn about each pin on a yanked release; with `deny` any of them
+/// fails the command
+fn warn_yanked(yanked: &[YankedPin], deny: bool) -> Result<(), PpmError> {
+    for pin in yanked {
+        wprint(pin.to_string());
     }
+    if deny && !yanked.is_empty() {
+        return Err(PpmError::Other(format!(
+            "{} pinned version(s) have been yanked (--deny-yanked)",
+            yanked.len()
+        )));
+    }
+    Ok(())
+}
 
-    let venv_root = conf.project.venv.clone().unwrap_or_else(|| "venv".to_string());
+pub fn check_imports(strict: bool) -> Result<(), PpmError> {
+    let ctx = load_project()?;
+    let report = imports::check_imports(&ctx)?;
 
-    if !check_venv_dir_exists(&venv_root) {
-        wprint(format!("Could not find '{}' directory", venv_root));
-        if ask_if_create_venv() {
-            if let Err(e) = setup_venv(format!("./{}", venv_root)) {
-                eprint(format!("Failed to setup venv: {}", e));
-                return;
+    if json_output() {
+        let missing: Vec<serde_json::Value> = report
+            .missing
+            .iter()
+            .map(|m| {
+                serde_json::json!({
+                    "module": m.module,
+                    "package": m.package,
+                    "files": m.files.iter().map(|f| f.to_string_lossy()).collect::<Vec<_>>(),
+                })
+            })
+            .collect();
+        println!(
+            "{}",
+            serde_json::json!({ "missing": missing, "unused": report.unused })
+        );
+    } else if report.missing.is_empty() && report.unused.is_empty() {
+        iprint("Every import is declared in project.toml and every package is imported".to_string());
+    } else {
+        if !report.missing.is_empty() {
+            println!("{}", "Imported but missing from project.toml:".bold());
+            for m in &report.missing {
+                let mut files: Vec<String> = m
+                    .files
+                    .iter()
+                    .take(3)
+                    .map(|f| paths::display(f))
+                    .collect();
+                if m.files.len() > 3 {
+                    files.push(format!("and {} more", m.files.len() - 3));
+                }
+                let package = if m.package == m.module {
+                    String::new()
+                } else {
+                    format!(" ({})", m.package)
+                };
+                println!(
+                    "  {}{} {}",
+                    m.module.red(),
+                    package,
+                    format!("in {}", files.join(", ")).dimmed()
+                );
             }
-        } else {
-            wprint("Update Cancelled".to_owned());
-            return;
+        }
+        if !report.unused.is_empty() {
+            println!("{}", "Configured but never imported:".bold());
+            for name in &report.unused {
+                println!("  {}", name.yellow());
+            }
+        }
+        if !report.missing.is_empty() {
+            let mut packages: Vec<&str> = report.missing.iter().map(|m| m.package.as_str()).collect();
+            packages.dedup();
+            iprint(format!("Add them with: ppm add {}", packages.join(" ")));
         }
     }
 
-    let mut updates: Vec<(String, String)> = vec![];
-    let mut failed_packages: Vec<String> = vec![];
+    if strict && !report.missing.is_empty() {
+        return Err(PpmError::Other(format!(
+            "{} imported package(s) missing from project.toml",
+            report.missing.len()
+        )));
+    }
+    Ok(())
+}
 
-    // Filter packages if specific ones are requested
-    let packages_to_check: Vec<String> = if pkg_names.is_empty() {
-        conf.packages.keys().cloned().collect()
-    } else {
-        let mut valid_names = vec![];
-        for name in pkg_names {
-            if conf.packages.contains_key(name) {
-                valid_names.push(name.clone());
-            } else {
-                wprint(format!("Package '{}' not found in project.toml", name));
-            }
+pub fn verify_packages() -> Result<(), PpmError> {
+    let ctx = load_project()?;
+    ctx.require_venv()?;
+
+    let index_count = ctx.config.packages.values().filter(|spec| spec.is_index()).count();
+    let progress = Progress::bar(Phase::Resolve, index_count, "Checking PyPI");
+    let results = verify::verify_with(&ctx.config, &ctx.venv_dir(), |name, version| {
+        progress.start_item(name);
+        let files = ctx.release_files(name, version);
+        match &files {
+            Ok(_) => progress.finish_item(),
+            Err(e) => progress.fail_item(format!("Could not fetch files of {}: {}", name, e)),
         }
-        valid_names
-    };
+        files
+    });
+    progress.finish();
+    let results = results?;
 
-    if packages_to_check.is_empty() {
-        if !pkg_names.is_empty() {
-             eprint("No valid packages specified to update".to_owned());
-        } else {
-             eprint("No packages to update".to_owned());
+    if json_output() {
+        let packages: Vec<serde_json::Value> = results
+            .iter()
+            .map(|result| {
+                serde_json::json!({
+                    "name": result.name,
+                    "version": result.version,
+                    "status": result.status.label(),
+                    "detail": result.status.detail(),
+                })
+            })
+            .collect();
+        println!("{}", serde_json::json!({ "packages": packages }));
+    } else {
+        let mut table = Table::new(&["Package", "Installed", "Status", "Detail"]);
+        for result in &results {
+            let style = match result.status {
+                VerifyStatus::Ok(_) => Style::Info,
+                VerifyStatus::Mismatch(_) => Style::Error,
+                VerifyStatus::Unknown(_) => Style::Warn,
+                VerifyStatus::Unverifiable(_) => Style::Dim,
+            };
+            table.add_row(vec![
+                Cell::new(result.name.clone(), Style::Bold),
+                match &result.version {
+                    Some(version) => Cell::plain(version.clone()),
+                    None => Cell::new("-", Style::Dim),
+                },
+                Cell::new(result.status.label(), style),
+                Cell::new(result.status.detail(), Style::Dim),
+            ]);
         }
-        return;
+        println!();
+        table.print();
+        println!();
     }
 
-    for name in packages_to_check {
-        match get_pkg_version(&name) {
-            Ok(latest_ver) => updates.push((name.clone(), latest_ver)),
-            Err(e) => {
-                eprint(format!("Could not find latest version of {}: {}", name, e));
-                failed_packages.push(name.clone());
-            }
+    let failed = results.iter().filter(|r| r.status.is_failure()).count();
+    if failed > 0 {
+        return Err(PpmError::Other(format!(
+            "{} package(s) failed verification",
+            failed
+        )));
+    }
+    Ok(())
+}
+
+/// `ppm graph`: the venv's dependency graph in `format`, restricted to
+/// `package` and what it depends on when given, on stdout or in `output`
+pub fn show_graph(
+    format: GraphFormat,
+    output: Option<&Path>,
+    package: Option<&str>,
+) -> Result<(), PpmError> {
+    let ctx = load_project()?;
+    let mut graph = graph::graph(&ctx)?;
+    if let Some(name) = package {
+        graph = graph.subgraph(name).ok_or_else(|| {
+            PpmError::Other(format!("Package '{}' isn't installed in the venv", name))
+        })?;
+    }
+    let rendered = graph.render(format);
+    match output {
+        Some(path) => {
+            std::fs::write(path, rendered).map_err(|e| {
+                PpmError::Other(format!("Could not write {}: {}", paths::display(path), e))
+            })?;
+            iprint(format!(
+                "Wrote the dependency graph of {} packages to {}",
+                graph.packages().count(),
+                paths::display(path)
+            ));
         }
+        None => print!("{}", rendered),
     }
+    Ok(())
+}
 
-    if updates.is_empty() {
-        eprint("No packages to update".to_owned());
-        return;
+/// `ppm rdeps <package>`: everything in the venv that depends on
+/// `name`, by depth, marking the packages project.toml lists
+pub fn show_rdeps(name: &str) -> Result<(), PpmError> {
+    let ctx = load_project()?;
+    let graph = graph::graph(&ctx)?;
+    let package = graph.get(name).ok_or_else(|| {
+        PpmError::Other(format!("Package '{}' isn't installed in the venv", name))
+    })?;
+    let levels = graph.dependents(name).unwrap_or_default();
+    let prune = levels.is_empty() && !package.direct;
+
+    if json_output() {
+        let dependents: Vec<serde_json::Value> = levels
+            .iter()
+            .enumerate()
+            .flat_map(|(depth, level)| {
+                level.iter().map(move |dependent| {
+                    serde_json::json!({
+                        "name": dependent.name,
+                        "version": dependent.version,
+                        "depth": depth + 1,
+                        "direct": dependent.direct,
+                    })
+                })
+            })
+            .collect();
+        println!(
+            "{}",
+            serde_json::json!({
+                "name": package.name,
+                "version": package.version,
+                "direct": package.direct,
+                "dependents": dependents,
+                "prune_candidate": prune,
+            })
+        );
+        return Ok(());
+    }
+
+    if levels.is_empty() {
+        iprint(format!("Nothing in the venv depends on {}", package.name));
+        if prune {
+            wprint(format!(
+                "{} isn't in project.toml either; it's a candidate for pruning from the venv",
+                package.name
+            ));
+        }
+        return Ok(());
     }
+    println!();
+    println!(
+        "{} {} {}",
+        "Dependents of".bold(),
+        package.name.bright_cyan().bold(),
+        package.version.dimmed()
+    );
+    for (depth, level) in levels.iter().enumerate() {
+        let title = if depth == 0 {
+            "Depth 1 (require it directly)".to_string()
+        } else {
+            format!("Depth {}", depth + 1)
+        };
+        println!();
+        println!("{} ({}):", title.green().bold(), level.len());
+        for dependent in level {
+            let marker = if dependent.direct {
+                " (in project.toml)".yellow().to_string()
+            } else {
+                String::new()
+            };
+            println!(
+                "  {} {}{}",
+                dependent.name,
+                dependent.version.dimmed(),
+                marker
+            );
+        }
+    }
+    println!();
+    Ok(())
+}
+
+/// How many of the largest packages the stats summary singles out
+const TOP_OFFENDERS: usize = 3;
 
-    let mut updated_packages: Vec<(String, String)> = vec![];
+pub fn show_stats() -> Result<(), PpmError> {
+    let ctx = load_project()?;
+    let stats = stats::stats(&ctx)?;
+    // pip's cache lives outside the venv but grows with every install
+    let pip_cache = pip_cache::inspect(&ctx).ok();
 
-    let mut packages_to_install: Vec<String> = vec![];
-    for (name, ver) in updates.iter() {
-        packages_to_install.push(format!("{}=={}", name, ver));
+    if json_output() {
+        let packages: Vec<serde_json::Value> = stats
+            .packages
+            .iter()
+            .map(|package| {
+                serde_json::json!({
+                    "name": package.name,
+                    "version": package.version,
+                    "size": package.size,
+                    "direct": package.direct,
+                })
+            })
+            .collect();
+        let pip_cache =
+            pip_cache.map(|cache| serde_json::json!({ "dir": cache.dir, "size": cache.total() }));
+        println!(
+            "{}",
+            serde_json::json!({
+                "total": stats.total,
+                "packages": packages,
+                "pip_cache": pip_cache,
+            })
+        );
+        return Ok(());
     }
 
-    // Batched pip install for better performance
-    match install_packages_batch(&packages_to_install, &venv_root) {
-        Ok(_) => {
-            for (name, ver) in updates {
-                updated_packages.push((name.clone(), ver.clone()));
+    let mut table = Table::new(&["Package", "Version", "Size", "Share", "Type"]);
+    for package in &stats.packages {
+        table.add_row(vec![
+            Cell::new(
+                package.name.clone(),
+                if package.direct { Style::Bold } else { Style::Plain },
+            ),
+            Cell::plain(package.version.clone()),
+            Cell::plain(format_size(package.size)),
+            Cell::plain(format!(
+                "{:.1}%",
+                stats::share(package.size, stats.total) * 100.0
+            )),
+            if package.direct {
+                Cell::new("direct", Style::Info)
+            } else {
+                Cell::new("transitive", Style::Dim)
+            },
+        ]);
+    }
+    println!();
+    table.print();
+    println!();
+    println!(
+        "{}: {}",
+        "Total venv size".bold(),
+        format_size(stats.total)
+    );
+    let top = stats.packages.len().min(TOP_OFFENDERS);
+    if top > 0 {
+        let names: Vec<&str> = stats.packages[..top]
+            .iter()
+            .map(|package| package.name.as_str())
+            .collect();
+        println!(
+            "Largest {}: {} ({:.1}% of the venv)",
+            top,
+            names.join(", "),
+            stats.top_share(top) * 100.0
+        );
+    }
+    if let Some(cache) = pip_cache {
+        println!(
+            "{}: {} in {}",
+            "pip cache".bold(),
+            format_size(cache.total()),
+            paths::display(&cache.dir)
+        );
+        println!(
+            "{}: {}",
+            "Total on disk".bold(),
+            format_size(stats.total + cache.total())
+        );
+    }
+    Ok(())
+}
 
-                iprint(format!("Updated {}", name));
-            }
+/// `ppm build` for a project with a pyproject.toml: `python -m build`
+/// into `out_dir` (`dist/` by default), then a table of what it wrote
+pub fn build_distributions(
+    ctx: &ProjectContext,
+    targets: build::Targets,
+    out_dir: Option<&Path>,
+    in_venv: bool,
+) -> Result<(), PpmError> {
+    let python = if in_venv {
+        ctx.require_venv()?;
+        ctx.venv_python()
+    } else {
+        let cache = ctx.state().ensure_dir(state::CACHE)?;
+        let python = paths::get_venv_python_path(&cache, build::BUILD_ENV_DIR);
+        let build_env = cache.join(build::BUILD_ENV_DIR);
+        if !python.exists() {
+            setup_venv(
+                ctx.runner.as_ref(),
+                &build_env,
+                ctx.shared.python.as_deref(),
+            )?;
         }
+        venv::write_pip_config(&build_env, ctx.shared.index_url.as_deref())?;
+        python
+    };
+    ensure_python_package(&python, "build")?;
+
+    let out_dir = ctx
+        .root
+        .join(out_dir.unwrap_or(Path::new(build::DEFAULT_OUT_DIR)));
+    let before = build::snapshot(&out_dir);
+    iprint(format!("Building project: {}", ctx.config.project.name));
+    let spinner = Progress::spinner(Phase::Script, "Running python -m build...");
+    let output = match Command::new(&python)
+        .args(build::build_args(targets, &out_dir))
+        .current_dir(&ctx.root)
+        .output()
+    {
+        Ok(output) => output,
         Err(e) => {
-            eprint(format!("Failed to update packages: {}", e));
+            let msg = format!("Failed to run python -m build: {}", e);
+            spinner.abandon(&msg);
+            return Err(PpmError::Subprocess(msg));
         }
+    };
+    if !output.status.success() {
+        spinner.abandon("Build failed");
+        let combined = format!(
+            "{}\n{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let lines = build::tail(&combined, build::FAILURE_TAIL_LINES);
+        if !lines.is_empty() {
+            print_human(format!("Last {} lines of build output:", lines.len()).dimmed());
+            for line in lines {
+                print_human(format!("  {}", line));
+            }
+        }
+        return Err(PpmError::Subprocess(format!(
+            "python -m build failed{}",
+            output
+                .status
+                .code()
+                .map(|code| format!(" with status {}", code))
+                .unwrap_or_default()
+        )));
     }
+    spinner.finish();
 
-    for (name, ver) in updated_packages {
-        conf.packages.insert(name, ver);
-    }
-
-    if let Err(e) = conf.write_to_file(config_file) {
-        eprint(format!("Failed to update config file: {}", e));
+    let artifacts = build::artifacts_since(&out_dir, &before)?;
+    let display_path = |path: &Path| {
+        paths::display(path.strip_prefix(&ctx.root).unwrap_or(path))
+    };
+    if json_output() {
+        let artifacts: Vec<serde_json::Value> = artifacts
+            .iter()
+            .map(|artifact| {
+                serde_json::json!({
+                    "path": display_path(&artifact.path),
+                    "size": artifact.size,
+                    "sha256": artifact.sha256,
+                })
+            })
+            .collect();
+        println!("{}", serde_json::json!({ "artifacts": artifacts }));
+        return Ok(());
     }
 
-    if !failed_packages.is_empty() {
+    if artifacts.is_empty() {
         wprint(format!(
-            "Failed to update {} package(s): {}",
-            failed_packages.len(),
-            failed_packages.join(", ")
+            "The build wrote nothing to {}",
+            display_path(&out_dir)
         ));
+        return Ok(());
     }
-
-    if let Err(e) = generate_lock_file(&venv_root) {
-        eprint(format!("Failed to generate lock file: {}", e));
+    let mut table = Table::new(&["Artifact", "Size", "SHA-256"]);
+    for artifact in &artifacts {
+        table.add_row(vec![
+            Cell::new(display_path(&artifact.path), Style::Bold),
+            Cell::plain(format_size(artifact.size)),
+            Cell::new(artifact.sha256.clone(), Style::Dim),
+        ]);
     }
+    println!();
+    table.print();
+    println!();
+    iprint("Build completed successfully".to_string());
+    Ok(())
 }
 
-pub fn list_packages() {
-    let config_file = get_project_config_file();
-    if !Path::new(config_file).exists() {
-        eprint(format!("Could not find {}", config_file));
-        return;
+/// Install `package` with `python`'s pip unless it can already be
+/// imported
+fn ensure_python_package(python: &Path, package: &str) -> Result<(), PpmError> {
+    let installed = Command::new(python)
+        .args(["-c", &format!("import {}", package)])
+        .output()
+        .map(|output| output.status.success())
+        .unwrap_or(false);
+    if installed {
+        return Ok(());
+    }
+    let spinner = Progress::spinner(Phase::Install, &format!("Installing {}...", package));
+    let output = match Command::new(python)
+        .args(["-m", "pip", "install", "--quiet", package])
+        .output()
+    {
+        Ok(output) => output,
+        Err(e) => {
+            let msg = format!("Failed to execute pip: {}", e);
+            spinner.abandon(&msg);
+            return Err(PpmError::Subprocess(msg));
+        }
+    };
+    if !output.status.success() {
+        spinner.abandon(&format!("Failed to install {}", package));
+        return Err(PpmError::from_pip(
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+            output.status.code(),
+        ));
     }
+    spinner.finish();
+    Ok(())
+}
 
-    let conf = match Config::load_from_file(config_file) {
-        Ok(conf) => conf,
+/// Refuse to publish over a release of the project's version that is
+/// already on the index behind `repository_url`; with `skip_existing`
+/// only warn, as twine will skip the files that are there
+pub fn check_unpublished(
+    ctx: &ProjectContext,
+    repository_url: &str,
+    skip_existing: bool,
+) -> Result<(), PpmError> {
+    let project = &ctx.config.project;
+    let Some(api_url) = publish::json_api_url(repository_url) else {
+        wprint(format!(
+            "Can't tell whether {} {} is already on {}; publishing without checking",
+            project.name,
+            project.version,
+            publish::redact_url(repository_url)
+        ));
+        return Ok(());
+    };
+    let spinner = Progress::spinner(
+        Phase::Resolve,
+        &format!(
+            "Checking {} {} on the index...",
+            project.name, project.version
+        ),
+    );
+    let exists = match ctx
+        .block_on(index::version_exists(
+            &ctx.client,
+            &api_url,
+            &project.name,
+            &project.version,
+        ))
+        .and_then(|result| result)
+    {
+        Ok(exists) => exists,
         Err(e) => {
-            eprint(e.to_string());
-            return;
+            spinner.abandon("Failed to check the index");
+            return Err(e);
         }
     };
+    spinner.finish();
+    if !exists {
+        return Ok(());
+    }
+    if skip_existing {
+        wprint(format!(
+            "{} {} is already published; files already uploaded will be skipped",
+            project.name, project.version
+        ));
+        return Ok(());
+    }
+    Err(PpmError::Other(format!(
+        "{} {} is already published on {}; bump the version with `ppm bump` or pass --skip-existing",
+        project.name,
+        project.version,
+        publish::redact_url(repository_url)
+    )))
+}
 
-    let count = conf.packages.len();
-    
-    if count == 0 {
-        wprint("No packages configured".to_string());
-        return;
+/// `ppm publish`: `twine check` the distributions of the project's
+/// version in `dist_dir`, then upload them to `repository_url` unless
+/// `dry_run`
+pub fn publish_distributions(
+    ctx: &ProjectContext,
+    dist_dir: &Path,
+    repository_url: &str,
+    dry_run: bool,
+    skip_existing: bool,
+) -> Result<(), PpmError> {
+    let project = &ctx.config.project;
+    let files = publish::dist_files(dist_dir, &project.name, &project.version);
+    if files.is_empty() {
+        return Err(PpmError::Other(format!(
+            "No distributions of {} {} in {}; run `ppm build` first or pass --build",
+            project.name,
+            project.version,
+            paths::display(dist_dir)
+        )));
     }
+    ctx.require_venv()?;
+    let python = ctx.venv_python();
+    ensure_python_package(&python, "twine")?;
 
-    println!(
-        "\nConfigured packages ({}):",
-        count.to_string().green().bold()
-    );
-    
-    for (name, version) in conf.packages.iter() {
+    run_twine(ctx, &python, &publish::check_args(&files), &[])?;
+    if !dry_run {
+        let token = std::env::var(publish::TOKEN_ENV).ok();
+        run_twine(
+            ctx,
+            &python,
+            &publish::upload_args(repository_url, skip_existing, &files),
+            &publish::credentials_env(token.as_deref()),
+        )?;
+    }
+
+    let display_path = |path: &Path| {
+        paths::display(path.strip_prefix(&ctx.root).unwrap_or(path))
+    };
+    if json_output() {
+        let files: Vec<String> = files.iter().map(|path| display_path(path)).collect();
         println!(
-            "{}=={}",
-            name.green().bold(),
-            version.bright_black()
+            "{}",
+            serde_json::json!({
+                "repository_url": publish::redact_url(repository_url),
+                "files": files,
+                "dry_run": dry_run,
+            })
         );
+    } else if dry_run {
+        iprint(format!(
+            "Dry run: {} distribution(s) passed twine check, nothing uploaded",
+            files.len()
+        ));
+    } else {
+        iprint(format!(
+            "Published {} {} to {}",
+            project.name,
+            project.version,
+            publish::redact_url(repository_url)
+        ));
     }
-    
-    println!();
+    Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    // Most functions here have side effects (printing, filesystem, shelling out).
-    // They are better tested via integration tests (CLI tests).
-    // We strictly follow the request to add the module.
-    
-    #[test]
-    fn test_placeholder() {
-        assert!(true);
+/// Run `python` with twine `args` and `env`, its output going where ours
+/// does. Only the redacted command line is ever shown.
+fn run_twine(
+    ctx: &ProjectContext,
+    python: &Path,
+    args: &[std::ffi::OsString],
+    env: &[(&str, String)],
+) -> Result<(), PpmError> {
+    let command = publish::display_command("python", args);
+    iprint(format!("Running {}", command));
+    let mut cmd = Command::new(python);
+    cmd.args(args)
+        .envs(env.iter().map(|(key, value)| (key, value)))
+        .current_dir(&ctx.root);
+    if json_output() {
+        cmd.stdout(std::io::stderr());
     }
+    let status = cmd
+        .status()
+        .map_err(|e| PpmError::Subprocess(format!("Failed to run twine: {}", e)))?;
+    if !status.success() {
+        return Err(PpmError::Subprocess(format!(
+            "`{}` failed{}",
+            command,
+            status
+                .code()
+                .map(|code| format!(" with status {}", code))
+                .unwrap_or_default()
+        )));
+    }
+    Ok(())
 }
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Mention a newer ppmm release found by an earlier check, and start the
+/// next check in the background when one is due. Never waits for the
+/// network and never fails the command.
+pub fn notify_update(offline: bool) {
+    // The notice is for people; scripts and pipes don't get one
+    if json_output() || !std::io::stderr().is_terminal() {
+        return;
+    }
+    let config = GlobalConfig::load().unwrap_or_default();
+    if self_update::disabled_reason(offline, &config, |name| std::env::var(name).ok()).is_some() {
+        return;
+    }
+    let Some(dir) = StateDir::global() else {
+        return;
+    };
+    let state = CheckState::load(&dir);
+    if let Some(latest) = state.as_ref().and_then(|state| state.newer_than(VERSION)) {
+        eprintln!(
+            "{}",
+            format!(
+                "ppmm {} is available (you have {}); run `cargo install ppmm` to upgrade",
+                latest, VERSION
+            )
+            .dimmed()
+        );
+    }
+
+    let now = self_update::now();
+    if state.as_ref().is_none_or(|state| state.is_due(now)) {
+        // Claim the slot first so commands run meanwhile don't start more checks
+        let claimed = CheckState {
+            checked_at: now,
+            latest: state.and_then(|state| state.latest),
+        };
+        if claimed.save(&dir).is_ok()
+            && let Ok(exe) = std::env::current_exe()
+        {
+            let _ = Command::new(exe)
+                .args(["self", "check-update", "--background"])
+                .stdin(std::process::Stdio::null())
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::null())
+                .spawn();
+        }
+    }
+}
+
+pub fn check_update(background: bool) -> Result<(), PpmError> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    let latest = runtime.block_on(self_update::latest_release(self_update::CRATES_IO_API_URL));
+    let saved = StateDir::global().map(|dir| {
+        // A failed check keeps what the last successful one found
+        let previous = CheckState::load(&dir).and_then(|state| state.latest);
+        CheckState {
+            checked_at: self_update::now(),
+            latest: latest.as_ref().ok().cloned().or(previous),
+        }
+        .save(&dir)
+    });
+    if background {
+        return Ok(());
+    }
+
+    iprint(format!("Installed version: {}", VERSION));
+    let latest = latest?;
+    if self_update::is_newer(&latest, VERSION) {
+        iprint(format!(
+            "ppmm {} is available; run `cargo install ppmm` to upgrade",
+            latest.bright_green()
+        ));
+    } else {
+        iprint(format!("ppmm is up to date (latest release: {})", latest));
+    }
+    if let Some(Err(e)) = saved {
+        wprint(format!("Could not record the result: {}", e));
+    }
+    let config = GlobalConfig::load()?;
+    if let Some(reason) = self_update::disabled_reason(false, &config, |name| std::env::var(name).ok()) {
+        iprint(format!("Automatic checks are off: {}", reason));
+    }
+    Ok(())
+}
+
+pub fn self_update(version: Option<&str>, dry_run: bool) -> Result<(), PpmError> {
+    let exe = std::env::current_exe()?;
+    let exe = exe.canonicalize().unwrap_or(exe);
+    if let Some(hint) = self_update::managed_install(&exe) {
+        return Err(PpmError::Other(format!(
+            "Not replacing {}: {}",
+            paths::display(&exe),
+            hint
+        )));
+    }
+    let (os, arch) = (std::env::consts::OS, std::env::consts::ARCH);
+    let asset = self_update::asset_name(os, arch).ok_or_else(|| {
+        PpmError::Other(format!(
+            "No prebuilt ppmm binary is published for {}/{}; run `cargo install ppmm` instead",
+            os, arch
+        ))
+    })?;
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    let spinner = Progress::spinner(Phase::Resolve, "Looking up the release");
+    let release = runtime.block_on(self_update::fetch_release(
+        self_update::GITHUB_RELEASES_API_URL,
+        version,
+    ));
+    spinner.finish();
+    let release = release?;
+    if version.is_none() && !self_update::is_newer(release.version(), VERSION) {
+        iprint(format!("ppmm {} is the latest release", VERSION));
+        return Ok(());
+    }
+    let missing = |name: &str| {
+        PpmError::Other(format!("Release {} has no {} asset", release.tag, name))
+    };
+    let binary_url = release.assets.get(asset).ok_or_else(|| missing(asset))?;
+    let shasums_url = release
+        .assets
+        .get(self_update::SHASUMS_FILE)
+        .ok_or_else(|| missing(self_update::SHASUMS_FILE))?;
+
+    if dry_run {
+        iprint(format!(
+            "Would replace ppmm {} at {} with {} ({})",
+            VERSION,
+            paths::display(&exe),
+            release.tag,
+            binary_url
+        ));
+        return Ok(());
+    }
+
+    let spinner = Progress::spinner(Phase::Install, &format!("Downloading {}", release.tag));
+    let downloaded = runtime.block_on(async {
+        let shasums = self_update::download(shasums_url).await?;
+        let binary = self_update::download(binary_url).await?;
+        Ok::<_, PpmError>((shasums, binary))
+    });
+    spinner.finish();
+    let (shasums, binary) = downloaded?;
+    let expected = self_update::parse_shasums(&String::from_utf8_lossy(&shasums))
+        .remove(asset)
+        .ok_or_else(|| {
+            PpmError::Other(format!(
+                "{} of {} has no checksum for {}",
+                self_update::SHASUMS_FILE,
+                release.tag,
+                asset
+            ))
+        })?;
+    let actual = verify::sha256_hex(&binary);
+    if actual != expected {
+        return Err(PpmError::Other(format!(
+            "Checksum mismatch for {}: expected {}, got {}; nothing was changed",
+            asset, expected, actual
+        )));
+    }
+
+    self_update::replace_exe(&exe, &binary).map_err(|e| {
+        PpmError::Other(format!("Failed to replace {}: {}", paths::display(&exe), e))
+    })?;
+    iprint(format!(
+        "Updated ppmm: {} {} {}",
+        VERSION.bright_cyan(),
+        theme().arrow,
+        release.version().bright_green()
+    ));
+    Ok(())
+}
+
+pub fn cache_info() -> Result<(), PpmError> {
+    // Outside a project there is only the global cache to report
+    let project = match ProjectContext::current_dir() {
+        Ok(ctx) => Some(ctx.state()),
+        Err(PpmError::ConfigNotFound(_)) => None,
+        Err(e) => return Err(e),
+    };
+    let global = StateDir::global();
+    let locations = [("Project", project), ("Global", global.clone())];
+    let max_size = metadata_cache::configured_max_size();
+    let metadata = global.as_ref().map(|dir| {
+        let entries = metadata_cache::entries(dir);
+        let size: u64 = entries.iter().map(|entry| entry.size).sum();
+        (dir, entries.len(), size, metadata_cache::load_stats(dir))
+    });
+
+    if json_output() {
+        let mut info = serde_json::Map::new();
+        for (label, dir) in &locations {
+            let value = match dir {
+                Some(dir) => serde_json::json!({
+                    "path": dir.path(),
+                    "exists": dir.exists(),
+                    "size": dir.size(),
+                }),
+                None => serde_json::Value::Null,
+            };
+            info.insert(label.to_lowercase(), value);
+        }
+        let value = match &metadata {
+            Some((dir, entries, size, stats)) => serde_json::json!({
+                "path": dir.subpath(yanked::RELEASES_CACHE),
+                "entries": entries,
+                "size": size,
+                "max_size": max_size,
+                "last_run": stats.last_run,
+                "total": stats.total,
+            }),
+            None => serde_json::Value::Null,
+        };
+        info.insert("metadata".to_string(), value);
+        println!("{}", serde_json::Value::Object(info));
+        return Ok(());
+    }
+
+    let mut table = Table::new(&["Cache", "Path", "Size"]);
+    for (label, dir) in &locations {
+        let Some(dir) = dir else {
+            continue;
+        };
+        table.add_row(vec![
+            Cell::new(*label, Style::Bold),
+            Cell::plain(paths::display(dir.path())),
+            if dir.exists() {
+                Cell::plain(format_size(dir.size()))
+            } else {
+                Cell::new("not created", Style::Dim)
+            },
+        ]);
+    }
+    if let Some((dir, entries, size, _)) = &metadata {
+        table.add_row(vec![
+            Cell::new("PyPI metadata", Style::Bold),
+            Cell::plain(paths::display(&dir.subpath(yanked::RELEASES_CACHE))),
+            Cell::plain(format!(
+                "{} of {}, {} {}",
+                format_size(*size),
+                format_size(max_size),
+                entries,
+                if *entries == 1 { "entry" } else { "entries" }
+            )),
+        ]);
+    }
+    println!();
+    table.print();
+    println!();
+    let stats = metadata.map(|(.., stats)| stats);
+    if let Some(stats) = stats.filter(|stats| stats.total != metadata_cache::Counts::default()) {
+        let counts = |counts: metadata_cache::Counts| {
+            format!(
+                "{} {}, {} {}",
+                counts.hits,
+                if counts.hits == 1 { "hit" } else { "hits" },
+                counts.misses,
+                if counts.misses == 1 { "miss" } else { "misses" }
+            )
+        };
+        println!(
+            "Metadata lookups: {} in the last run; {} in total",
+            counts(stats.last_run),
+            counts(stats.total)
+        );
+        println!();
+    }
+    Ok(())
+}
+
+/// `ppm cache prune`: remove cached release histories not used for
+/// `older_than` days, then the least recently used ones beyond `max_size`
+/// bytes, the configured budget when unset
+pub fn cache_prune(max_size: Option<u64>, older_than: Option<u64>) -> Result<(), PpmError> {
+    let Some(global) = StateDir::global() else {
+        return Err(PpmError::Other(
+            "There is no global cache directory; set PPM_CACHE_DIR".to_string(),
+        ));
+    };
+    let max_size = max_size.unwrap_or_else(metadata_cache::configured_max_size);
+    let older_than = older_than.map(|days| Duration::from_secs(days * 24 * 60 * 60));
+    let pruned = metadata_cache::prune(&global, max_size, older_than, SystemTime::now())?;
+    if json_output() {
+        println!(
+            "{}",
+            serde_json::json!({"removed": pruned.removed, "freed": pruned.freed})
+        );
+        return Ok(());
+    }
+    if pruned.removed == 0 {
+        iprint("Nothing to prune in the metadata cache".to_string());
+    } else {
+        let noun = if pruned.removed == 1 {
+            "history"
+        } else {
+            "histories"
+        };
+        iprint(format!(
+            "Removed {} cached release {} ({})",
+            pruned.removed,
+            noun,
+            format_size(pruned.freed)
+        ));
+    }
+    Ok(())
+}
+
+/// `ppm cache pip-info`: where the venv's pip caches downloads and
+/// built wheels, and how much space they take
+pub fn pip_cache_info() -> Result<(), PpmError> {
+    let ctx = load_project()?;
+    ctx.require_venv()?;
+    let cache = pip_cache::inspect(&ctx)?;
+    if json_output() {
+        println!(
+            "{}",
+            serde_json::json!({
+                "dir": cache.dir,
+                "http_size": cache.usage.http_size,
+                "http_files": cache.usage.http_files,
+                "wheels_size": cache.usage.wheels_size,
+                "wheels": cache.usage.wheels,
+                "total": cache.total(),
+                "measured": cache.measured,
+            })
+        );
+        return Ok(());
+    }
+
+    let size = |size: Option<u64>| match size {
+        Some(size) => Cell::plain(format_size(size)),
+        None => Cell::new("unknown", Style::Dim),
+    };
+    let count = |count: Option<u64>| match count {
+        Some(count) => Cell::plain(count.to_string()),
+        None => Cell::new("unknown", Style::Dim),
+    };
+    let mut table = Table::new(&["Cached", "Size", "Files"]);
+    table.add_row(vec![
+        Cell::new("Index pages and downloads", Style::Bold),
+        size(cache.usage.http_size),
+        count(cache.usage.http_files),
+    ]);
+    table.add_row(vec![
+        Cell::new("Locally built wheels", Style::Bold),
+        size(cache.usage.wheels_size),
+        count(cache.usage.wheels),
+    ]);
+    println!();
+    table.print();
+    println!();
+    println!(
+        "{}: {} in {}",
+        "Total".bold(),
+        format_size(cache.total()),
+        paths::display(&cache.dir)
+    );
+    if cache.measured {
+        println!("The venv's pip has no `pip cache` command, so ppm measured the directory itself");
+    }
+    println!();
+    Ok(())
+}
+
+/// `ppm cache pip-purge`
+pub fn pip_cache_purge(yes: bool, prompter: &dyn Prompter) -> Result<(), PpmError> {
+    let ctx = load_project()?;
+    purge_pip_cache(&ctx, yes, prompter)
+}
+
+/// Empty pip's cache with `pip cache purge` once the user agrees to
+/// losing its size, or right away with `yes`
+fn purge_pip_cache(
+    ctx: &ProjectContext,
+    yes: bool,
+    prompter: &dyn Prompter,
+) -> Result<(), PpmError> {
+    ctx.require_venv()?;
+    let before = pip_cache::inspect(ctx)?;
+    if before.total() == 0 {
+        iprint(format!(
+            "pip's cache in {} is already empty",
+            paths::display(&before.dir)
+        ));
+        return Ok(());
+    }
+    if !yes
+        && !prompter.ask_yes_no(
+            &format!(
+                "Remove {} of cached downloads and wheels from {}?",
+                format_size(before.total()),
+                paths::display(&before.dir)
+            ),
+            false,
+        )
+    {
+        wprint("Purge Cancelled".to_owned());
+        return Ok(());
+    }
+    let removed = pip_cache::purge(ctx)?;
+    let after = pip_cache::inspect(ctx)
+        .map(|cache| cache.total())
+        .unwrap_or(0);
+    let freed = before.total().saturating_sub(after);
+    if json_output() {
+        println!(
+            "{}",
+            serde_json::json!({"removed": removed, "freed": freed})
+        );
+        return Ok(());
+    }
+    match removed {
+        Some(removed) => iprint(format!(
+            "Removed {} {} from pip's cache ({} freed)",
+            removed,
+            if removed == 1 { "file" } else { "files" },
+            format_size(freed)
+        )),
+        None => iprint(format!("Purged pip's cache ({} freed)", format_size(freed))),
+    }
+    Ok(())
+}
+
+/// `ppm config --global get`: the value as written, strings unquoted
+pub fn config_get(key: &str) -> Result<(), PpmError> {
+    let value = match GlobalConfig::path() {
+        Some(path) => global_config::get_value(&path, key)?,
+        None => None,
+    };
+    if json_output() {
+        println!("{}", serde_json::json!({"key": key, "value": value}));
+        return Ok(());
+    }
+    match value {
+        Some(toml::Value::String(value)) => println!("{}", value),
+        Some(value) => println!("{}", value),
+        None => {}
+    }
+    Ok(())
+}
+
+/// `ppm config --global set`
+pub fn config_set(key: &str, value: &str) -> Result<(), PpmError> {
+    let Some(path) = GlobalConfig::path() else {
+        return Err(PpmError::Other(
+            "There is no config directory; set PPM_CONFIG_DIR".to_string(),
+        ));
+    };
+    global_config::set_value(&path, key, value)?;
+    iprint(format!("Set {} = {} in {}", key, value, paths::display(&path)));
+    Ok(())
+}
+
+/// `ppm cache paths`: every file and directory ppm reads or writes, the
+/// project's only inside a project
+pub fn cache_paths() -> Result<(), PpmError> {
+    let mut locations: Vec<(&str, Option<PathBuf>)> = Vec::new();
+    match ProjectContext::current_dir() {
+        Ok(ctx) => {
+            let state = ctx.state();
+            locations.extend([
+                ("project_root", Some(ctx.root.clone())),
+                ("project_config", Some(ctx.config_path.clone())),
+                ("venv", Some(ctx.venv_dir())),
+                ("project_lock", Some(ctx.root.join(ppmm::lock::PROJECT_LOCK_FILE))),
+                ("project_state", Some(state.path().to_path_buf())),
+                ("history", Some(state.subpath(history::HISTORY_FILE))),
+                ("project_cache", Some(state.subpath(state::CACHE))),
+            ]);
+        }
+        Err(PpmError::ConfigNotFound(_)) => {}
+        Err(e) => return Err(e),
+    }
+    locations.push(("global_config", GlobalConfig::path()));
+    locations.push((
+        "global_cache",
+        StateDir::global().map(|dir| dir.path().to_path_buf()),
+    ));
+
+    if json_output() {
+        let info: serde_json::Map<String, serde_json::Value> = locations
+            .iter()
+            .map(|(key, path)| (key.to_string(), serde_json::json!(path)))
+            .collect();
+        println!("{}", serde_json::Value::Object(info));
+        return Ok(());
+    }
+
+    let mut table = Table::new(&["Location", "Path"]);
+    for (key, path) in &locations {
+        table.add_row(vec![
+            Cell::new(key.replace('_', " "), Style::Bold),
+            match path {
+                Some(path) => Cell::plain(paths::display(path)),
+                None => Cell::new("unavailable", Style::Dim),
+            },
+        ]);
+    }
+    println!();
+    table.print();
+    println!();
+    Ok(())
+}
+
+/// One view of the venv's interpreter in `ppm doctor`: its version and
+/// path or command
+struct InterpreterRow {
+    label: &'static str,
+    version: Option<String>,
+    interpreter: Option<String>,
+}
+
+/// The interpreter the venv was created with as ppm recorded it, as its
+/// pyvenv.cfg says and the one `ppm venv recreate` would use now, with
+/// how the venv's base interpreter changed
+fn interpreter_rows(ctx: &ProjectContext) -> (Vec<InterpreterRow>, Option<venv::Drift>) {
+    let record = venv::VenvRecord::load(&ctx.state());
+    let cfg = venv::PyvenvCfg::read(&ctx.venv_dir());
+    let command = venv::interpreter_command(ctx.shared.python.as_deref());
+    let rows = vec![
+        InterpreterRow {
+            label: "created with",
+            version: record
+                .as_ref()
+                .and_then(|record| record.python_version.clone()),
+            interpreter: record.map(|record| paths::display(&record.base_python)),
+        },
+        InterpreterRow {
+            label: "pyvenv.cfg",
+            version: cfg.as_ref().and_then(|cfg| cfg.version.clone()),
+            interpreter: cfg
+                .and_then(|cfg| cfg.executable.or(cfg.home))
+                .map(|path| paths::display(&path)),
+        },
+        InterpreterRow {
+            label: "system",
+            version: venv::probe_interpreter_version(ctx.runner.as_ref(), &command),
+            interpreter: Some(command.join(" ")),
+        },
+    ];
+    let drift = match venv::check_drift(&ctx.state(), &ctx.venv_dir(), &ctx.venv_python(), |base| {
+        venv::probe_python_version(ctx.runner.as_ref(), base)
+    }) {
+        venv::InterpreterCheck::Drifted(drift) => Some(drift),
+        _ => None,
+    };
+    (rows, drift)
+}
+
+/// `ppm doctor`: the network settings ppm uses and where each came from,
+/// ppm's own settings or pip's, with the pip config files it looked at,
+/// and inside a project the interpreter of its venv
+pub fn doctor() -> Result<(), PpmError> {
+    let pip = PipConfig::load();
+    let ctx = match ProjectContext::current_dir() {
+        Ok(ctx) => Some(ctx),
+        Err(PpmError::ConfigNotFound(_)) => None,
+        Err(e) => return Err(e),
+    };
+    let (network, lookup_url) = match &ctx {
+        Some(ctx) => (ctx.network.clone(), ctx.index_url.clone()),
+        None => {
+            let network = NetworkSettings::resolve(&pip, &[]).with_flags(&network_flags());
+            let lookup_url = network
+                .index_url()
+                .and_then(index::json_api_url)
+                .unwrap_or_else(index::pypi_api_url);
+            (network, lookup_url)
+        }
+    };
+    let python = ctx.as_ref().map(interpreter_rows);
+
+    if json_output() {
+        let settings: serde_json::Map<String, serde_json::Value> = Key::ALL
+            .iter()
+            .map(|key| {
+                let value = match network.get(*key) {
+                    Some(setting) => serde_json::json!({
+                        "value": setting.value,
+                        "source": setting.source.kind(),
+                        "origin": setting.source.origin(),
+                    }),
+                    None => serde_json::Value::Null,
+                };
+                (key.name().replace('-', "_"), value)
+            })
+            .collect();
+        let files: Vec<serde_json::Value> = pip
+            .files
+            .iter()
+            .map(|(path, sections)| serde_json::json!({"path": path, "exists": sections.is_some()}))
+            .collect();
+        let python = python.map(|(rows, drift)| {
+            let mut info: serde_json::Map<String, serde_json::Value> = rows
+                .iter()
+                .map(|row| {
+                    let value = match (&row.version, &row.interpreter) {
+                        (None, None) => serde_json::Value::Null,
+                        (version, interpreter) => {
+                            serde_json::json!({"version": version, "interpreter": interpreter})
+                        }
+                    };
+                    (row.label.replace([' ', '.'], "_"), value)
+                })
+                .collect();
+            info.insert(
+                "drift".to_string(),
+                serde_json::json!(drift.map(|drift| drift.to_string())),
+            );
+            info
+        });
+        println!(
+            "{}",
+            serde_json::json!({
+                "network": settings,
+                "lookup_url": lookup_url,
+                "pip_config_files": files,
+                "python": python,
+            })
+        );
+        return Ok(());
+    }
+
+    let mut table = Table::new(&["Setting", "Value", "Source"]);
+    for key in Key::ALL {
+        let (value, source) = match network.get(key) {
+            Some(setting) => {
+                // Extra index URLs can be on several lines
+                let words: Vec<&str> = setting.value.split_whitespace().collect();
+                (
+                    Cell::plain(words.join(" ")),
+                    Cell::plain(setting.source.to_string()),
+                )
+            }
+            None if key == Key::IndexUrl => (
+                Cell::plain(format!("{}/simple", index::pypi_url())),
+                Cell::new("default", Style::Dim),
+            ),
+            None => (Cell::new("-", Style::Dim), Cell::new("not set", Style::Dim)),
+        };
+        table.add_row(vec![Cell::new(key.name(), Style::Bold), value, source]);
+    }
+    println!();
+    table.print();
+    println!();
+    println!("Package lookups: {}", lookup_url);
+    if let Some(index_url) = network.index_url()
+        && index::json_api_url(index_url).is_none()
+    {
+        wprint(format!(
+            "{} doesn't end in /simple, so ppm can't find its JSON API and looks packages up on PyPI",
+            index_url
+        ));
+    }
+    println!();
+    println!("pip config files, later ones first:");
+    for (path, sections) in pip.files.iter().rev() {
+        if sections.is_some() {
+            println!("  {}", paths::display(path));
+        } else {
+            let missing = format!("{} (missing)", paths::display(path));
+            println!("  {}", missing.dimmed());
+        }
+    }
+
+    if let Some((rows, drift)) = python {
+        let mut table = Table::new(&["Python", "Version", "Interpreter"]);
+        for row in rows {
+            let cell = |value: Option<String>| match value {
+                Some(value) => Cell::plain(value),
+                None => Cell::new("-", Style::Dim),
+            };
+            table.add_row(vec![
+                Cell::new(row.label, Style::Bold),
+                cell(row.version),
+                cell(row.interpreter),
+            ]);
+        }
+        println!();
+        table.print();
+        println!();
+        if let Some(drift) = drift {
+            wprint(format!("{}; run `ppm venv recreate`", drift));
+        }
+    }
+    Ok(())
+}
+
+/// `ppm report`: environment details for a bug report as Markdown, on
+/// stdout or written to `output`; secrets are redacted and nothing is
+/// sent anywhere
+pub fn write_report(output: Option<&Path>) -> Result<(), PpmError> {
+    let mut report = Report {
+        ppm_version: ppmm::compat::PPM_VERSION.to_string(),
+        os: std::env::consts::OS.to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+        ..Report::default()
+    };
+    let root = paths::find_project_root(&std::env::current_dir()?);
+    if let Some(root) = &root {
+        report.project_root = Some(paths::display(root));
+        report.config = std::fs::read_to_string(root.join(PROJECT_CONFIG_FILE))
+            .ok()
+            .map(|contents| report::sanitize_config(&contents));
+    }
+    // An unusable project.toml still gets reported, just without a venv
+    if let Some(mut ctx) = root.and_then(|root| ProjectContext::load(&root).ok()) {
+        apply_global_flags(&mut ctx);
+        let venv = ctx.venv_dir();
+        report.venv = Some(paths::display(&venv));
+        report.venv_exists = venv.is_dir();
+        report.python_version = ctx.python_version().map(str::to_string);
+        report.pip_version = ctx
+            .runner
+            .run(
+                ctx.venv_pip().as_os_str(),
+                &[OsString::from("--version")],
+                &RunOptions::in_dir(&ctx.root),
+            )
+            .ok()
+            .filter(|output| output.success())
+            .and_then(|output| {
+                resolver::parse_pip_version(&output.stdout_lossy()).map(str::to_string)
+            });
+        let entries = history::read(&ctx.state()).unwrap_or_default();
+        report.history = entries[entries.len().saturating_sub(report::HISTORY_LINES)..]
+            .iter()
+            .map(report::history_line)
+            .collect();
+    }
+
+    let markdown = report.to_markdown();
+    match output {
+        Some(path) => {
+            std::fs::write(path, markdown).map_err(|e| {
+                PpmError::Other(format!("Could not write {}: {}", paths::display(path), e))
+            })?;
+            iprint(format!(
+                "Wrote the report to {}; check it before sharing, nothing was sent anywhere",
+                paths::display(path)
+            ));
+        }
+        None => print!("{}", markdown),
+    }
+    Ok(())
+}
+
+/// `ppm explain`: the description, causes and usual fixes of error
+/// `code`, or a list of every code
+pub fn explain_error(code: Option<&str>) -> Result<(), PpmError> {
+    let Some(code) = code else {
+        if json_output() {
+            let codes: Vec<serde_json::Value> = ErrorCode::ALL
+                .iter()
+                .map(|code| {
+                    serde_json::json!({
+                        "code": code.to_string(),
+                        "title": code.explanation().title,
+                    })
+                })
+                .collect();
+            println!("{}", serde_json::json!({ "codes": codes }));
+            return Ok(());
+        }
+        let mut table = Table::new(&["Code", "Error"]);
+        for code in ErrorCode::ALL {
+            table.add_row(vec![
+                Cell::new(code.to_string(), Style::Bold),
+                Cell::plain(code.explanation().title),
+            ]);
+        }
+        println!();
+        table.print();
+        println!();
+        return Ok(());
+    };
+    let code = ErrorCode::parse(code).ok_or_else(|| {
+        PpmError::Other(format!(
+            "Unknown error code '{}'; codes run from {} to {}, `ppm explain` lists them",
+            code,
+            ErrorCode::ALL[0],
+            ErrorCode::ALL[ErrorCode::ALL.len() - 1]
+        ))
+    })?;
+    let explanation = code.explanation();
+    if json_output() {
+        println!(
+            "{}",
+            serde_json::json!({
+                "code": code.to_string(),
+                "title": explanation.title,
+                "description": explanation.description,
+                "causes": explanation.causes,
+                "fixes": explanation.fixes,
+            })
+        );
+        return Ok(());
+    }
+    println!();
+    println!(
+        "{} {}",
+        code.to_string().bright_red().bold(),
+        explanation.title.bold()
+    );
+    println!();
+    println!("{}", explanation.description);
+    println!();
+    println!("{}", "Common causes:".green().bold());
+    for cause in explanation.causes {
+        println!("  - {}", cause);
+    }
+    println!();
+    println!("{}", "Usually fixed by:".green().bold());
+    for fix in explanation.fixes {
+        println!("  - {}", fix);
+    }
+    println!();
+    Ok(())
+}
+
+/// `ppm env`: where the venv and its interpreter are, or with `activate`
+/// the lines that activate the venv in that shell
+pub fn show_env(activate: Option<ActivateShell>) -> Result<(), PpmError> {
+    if let Some(shell) = activate {
+        // Only the lines go to stdout, for `eval`; no warnings among them
+        let ctx = ProjectContext::current_dir()?;
+        ctx.check_venv_in_use()?;
+        print!("{}", VenvEnv::new(&ctx).activate_script(shell));
+        return Ok(());
+    }
+    let ctx = load_project()?;
+    let env = VenvEnv::new(&ctx);
+    let exists = check_venv_dir_exists(&ctx);
+
+    if json_output() {
+        println!(
+            "{}",
+            serde_json::json!({
+                "project_root": env.project_root,
+                "venv": env.venv_dir,
+                "python": env.python,
+                "bin_dir": env.bin_dir,
+                "venv_exists": exists,
+            })
+        );
+        return Ok(());
+    }
+
+    let mut table = Table::new(&["Location", "Path"]);
+    for (label, path) in [
+        ("Project root", &env.project_root),
+        ("Venv", &env.venv_dir),
+        ("Interpreter", &env.python),
+        ("Bin dir", &env.bin_dir),
+    ] {
+        table.add_row(vec![
+            Cell::new(label, Style::Bold),
+            Cell::plain(paths::display(path)),
+        ]);
+    }
+    println!();
+    table.print();
+    println!();
+    if !exists {
+        wprint("The venv doesn't exist yet; `ppm install` creates it".to_string());
+    }
+    Ok(())
+}
+
+pub fn clean_project(all: bool) -> Result<(), PpmError> {
+    let ctx = load_project()?;
+    let _lock = lock_project(&ctx)?;
+    let state = ctx.state();
+    let freed = if all {
+        state.clear()?
+    } else {
+        state.remove(state::CACHE)?
+    };
+    let what = if all {
+        format!("{}/", state::STATE_DIR)
+    } else {
+        format!("{}/{}/", state::STATE_DIR, state::CACHE)
+    };
+    iprint(format!("Removed {} ({})", what, format_size(freed)));
+    Ok(())
+}
+
+pub fn show_history(package: Option<&str>, limit: usize) -> Result<(), PpmError> {
+    let ctx = load_project()?;
+    let mut entries = history::read(&ctx.state())?;
+    if let Some(package) = package {
+        entries.retain(|entry| entry.touches(package));
+    }
+    let entries = &entries[entries.len().saturating_sub(limit)..];
+
+    if json_output() {
+        println!("{}", serde_json::to_string(entries).unwrap_or_default());
+        return Ok(());
+    }
+    if entries.is_empty() {
+        iprint("No history recorded yet".to_string());
+        return Ok(());
+    }
+
+    let mut table = Table::new(&["When", "Command", "Changes", "Outcome"]);
+    for entry in entries {
+        let mut changes: Vec<String> = entry
+            .changes
+            .iter()
+            .filter(|change| {
+                package.is_none_or(|package| {
+                    normalize_pkg_name(&change.package) == normalize_pkg_name(package)
+                })
+            })
+            .map(|change| match (&change.from, &change.to) {
+                (Some(from), Some(to)) => {
+                    format!("{} {} {} {}", change.package, from, theme().arrow, to)
+                }
+                (None, Some(to)) => format!("+{} {}", change.package, to),
+                (Some(from), None) => format!("-{} {}", change.package, from),
+                (None, None) => change.package.clone(),
+            })
+            .collect();
+        if entry.venv_created {
+            changes.push("created venv".to_string());
+        }
+        table.add_row(vec![
+            Cell::new(entry.timestamp.replacen('T', " ", 1), Style::Dim),
+            Cell::new(format!("ppm {}", entry.command), Style::Bold),
+            Cell::plain(changes.join(", ")),
+            match entry.outcome {
+                Outcome::Success => Cell::new("ok", Style::Info),
+                Outcome::Failure => Cell::new("failed", Style::Error),
+            },
+        ]);
+    }
+    println!();
+    table.print();
+    println!();
+    Ok(())
+}
+
+pub fn sync_packages(check: bool) -> Result<(), PpmError> {
+    let ctx = load_project()?;
+    let _lock = if check {
+        None
+    } else {
+        Some(lock_project(&ctx)?)
+    };
+
+    let resolved = resolve_packages(&ctx)?;
+    let installed = get_installed_packages(&ctx).map_err(PpmError::Venv)?;
+    let drift = find_drift(&resolved, &installed);
+    if drift.is_empty() {
+        iprint("The venv matches project.toml".to_string());
+        return Ok(());
+    }
+
+    for change in &drift {
+        match change {
+            Drift::Missing { name, version } => {
+                println!("  {} {}=={}", "missing".red(), name, version)
+            }
+            Drift::Mismatch {
+                name,
+                installed,
+                resolved,
+            } => println!(
+                "  {} {} {} -> {}",
+                "changed".yellow(),
+                name,
+                installed.red(),
+                resolved.green()
+            ),
+        }
+    }
+    if check {
+        return Err(PpmError::Other(format!(
+            "{} package(s) out of sync with project.toml; run `ppm sync` to install them",
+            drift.len()
+        )));
+    }
+
+    let args: Vec<Vec<String>> = drift
+        .iter()
+        .filter_map(|change| {
+            let name = match change {
+                Drift::Missing { name, .. } | Drift::Mismatch { name, .. } => name,
+            };
+            resolved.iter().find(|pkg| &pkg.name == name)
+        })
+        .map(|pkg| match &pkg.source {
+            Some(source) if pkg.editable => vec!["-e".to_string(), source.clone()],
+            _ => vec![pkg.requirement()],
+        })
+        .collect();
+    pip_install(&args, &ctx)?;
+    iprint(format!("Synced {} packages", args.len()));
+    Ok(())
+}
+
+/// Resolve and sanity-check `main_script` before anything is spawned
+fn main_script_to_run(ctx: &ProjectContext) -> Result<PathBuf, PpmError> {
+    let main_script = &ctx.config.project.main_script;
+    if main_script.trim().is_empty() {
+        return Err(PpmError::Config(
+            "main_script is empty; set main_script in project.toml to the file `ppm start` should run"
+                .to_string(),
+        ));
+    }
+
+    let script_path = ctx.main_script_path();
+    if script_path.is_dir() {
+        return Err(PpmError::Config(format!(
+            "Main script '{}' is a directory; main_script must point to a file",
+            main_script
+        )));
+    }
+    if !script_path.exists() {
+        return Err(PpmError::Config(format!(
+            "Main script '{}' not found",
+            main_script
+        )));
+    }
+    if script_path.extension().is_none_or(|ext| ext != "py") {
+        wprint(format!(
+            "Main script '{}' is not a .py file, running it with python anyway",
+            main_script
+        ));
+    }
+    Ok(script_path)
+}
+
+/// Point a missing or empty `main_script` at the project's entry point
+/// and save it, asking which one when there are several
+fn fill_missing_main_script(
+    ctx: &mut ProjectContext,
+    prompter: &dyn Prompter,
+) -> Result<(), PpmError> {
+    let configured = ctx.config.project.main_script.clone();
+    if !configured.trim().is_empty() && ctx.main_script_path().exists() {
+        return Ok(());
+    }
+    let candidates = main_script::find_candidates(&ctx.root, &ctx.config.project.name);
+    let Some(found) = main_script::choose(&candidates, prompter)? else {
+        return Ok(());
+    };
+    wprint(format!(
+        "Main script '{}' not found, using '{}' and saving it to project.toml",
+        configured, found
+    ));
+    ctx.config.project.main_script = format!("./{}", found);
+    save_project(ctx, None)
+}
+
+/// Print the slowest functions of the profile `launch` wrote, and where
+/// the raw profile is
+fn print_profile_summary(ctx: &ProjectContext, python: &[OsString], launch: &Launch) {
+    let (Some(summary_args), Launch::Profile { output, .. }) =
+        (launch::profile_summary_args(launch), launch)
+    else {
+        return;
+    };
+    let options = RunOptions {
+        inherit_stdio: true,
+        ..RunOptions::in_dir(&ctx.root)
+    };
+    let mut args = python[1..].to_vec();
+    args.extend(summary_args);
+    let summarized = ctx
+        .runner
+        .run(&python[0], &args, &options)
+        .is_ok_and(|status| status.success());
+    if !summarized {
+        wprint("Could not summarize the profile".to_string());
+    }
+    let output = ctx.root.join(output);
+    iprint(format!(
+        "Raw profile saved to '{}'; explore it with `snakeviz {}`",
+        paths::display(&output),
+        paths::display(&output)
+    ));
+}
+
+/// Make sure the venv has the tool `package`, offering to install it
+fn ensure_venv_package(
+    ctx: &ProjectContext,
+    package: &str,
+    prompter: &dyn Prompter,
+) -> Result<(), PpmError> {
+    if verify::inspect_installed(&ctx.venv_dir(), package).is_some() {
+        return Ok(());
+    }
+    let question = format!("{} isn't installed in the venv. Install it now?", package);
+    if !prompter.ask_yes_no(&question, true) {
+        return Err(PpmError::Venv(format!(
+            "{} is needed for this; install it in the venv first",
+            package
+        )));
+    }
+    pip_install(&[vec![package.to_string()]], ctx)
+}
+
+pub fn start_project(
+    launch: Launch,
+    args: &[String],
+    system: bool,
+    prompter: &dyn Prompter,
+) -> Result<(), PpmError> {
+    let mut ctx = load_project()?;
+    ctx.system_python |= system;
+    start(&mut ctx, launch, args, prompter)
+}
+
+/// Run the main script of `ctx` with the venv interpreter, or the system
+/// one in [`system_python`](ProjectContext::system_python) mode, as
+/// `launch` says, passing it `args`
+fn start(
+    ctx: &mut ProjectContext,
+    launch: Launch,
+    args: &[String],
+    prompter: &dyn Prompter,
+) -> Result<(), PpmError> {
+    fill_missing_main_script(ctx, prompter)?;
+    let script_path = main_script_to_run(ctx)?;
+    if !ctx.system_python {
+        if !ensure_venv(ctx, prompter)? {
+            wprint("Start Cancelled".to_owned());
+            return Ok(());
+        }
+        if let Err(problem) = check_interpreter(&ctx.venv_dir(), &ctx.venv_python()) {
+            return Err(PpmError::Venv(format!(
+                "The virtual environment at '{}' is missing or broken: {}. Remove it and run `ppm install` to recreate it",
+                paths::display(&ctx.venv_dir()),
+                problem
+            )));
+        }
+        if let Some(package) = launch.required_package() {
+            ensure_venv_package(ctx, package, prompter)?;
+        }
+    }
+    if let Some(instructions) = launch.instructions() {
+        iprint(instructions);
+    }
+    if let Launch::Profile { .. } = launch {
+        // Also clears the last profile, so it's never summarized as this one
+        ctx.state().write(launch::PROFILE_FILE, b"")?;
+    }
+
+    let script = ctx.config.project.main_script.as_str();
+    Event::new(Phase::Script, Status::Start).script(script).emit();
+    let options = RunOptions {
+        inherit_stdio: true,
+        ..RunOptions::in_dir(&ctx.root)
+    };
+    let python = ctx.python_command();
+    let mut python_args = python[1..].to_vec();
+    python_args.extend(launch.args(&script_path, args));
+    let status = match ctx.runner.run(&python[0], &python_args, &options) {
+        Ok(status) => status,
+        Err(e) => {
+            Event::new(Phase::Script, Status::Fail)
+                .script(script)
+                .message(&e.to_string())
+                .emit();
+            return Err(PpmError::Subprocess(format!(
+                "Failed to start main file: {}",
+                e
+            )));
+        }
+    };
+
+    Event::new(Phase::Script, Status::Finish)
+        .script(script)
+        .exit_code(status.code)
+        .emit();
+    print_profile_summary(ctx, &python, &launch);
+    if !status.success() {
+        return Err(PpmError::ChildExit {
+            name: script.to_string(),
+            code: status.code,
+        });
+    }
+    Ok(())
+}
+
+pub fn update_packages(
+    pkg_names: &[String],
+    options: UpdateOptions,
+    prompter: &dyn Prompter,
+) -> Result<(), PpmError> {
+    if options.markdown {
+        reserve_stdout();
+    }
+    let mut ctx = load_project()?;
+    let _lock = if options.dry_run {
+        None
+    } else {
+        Some(lock_project(&ctx)?)
+    };
+    if options.resume {
+        return resume_update(&mut ctx, options, prompter);
+    }
+    if let Ok(Some(pending)) = PendingUpdate::load(&ctx.state()) {
+        wprint(format!(
+            "An interrupted update still has {} package(s) to install; `ppm update --resume` finishes it",
+            pending.packages.len()
+        ));
+    }
+    update_project(&mut ctx, pkg_names, options, prompter)
+}
+
+/// Finish the update Ctrl+C stopped: install the packages it left in
+/// `.ppm/pending-update.json`, resolving them again when the plan is
+/// older than [`resume::PENDING_MAX_AGE`]
+fn resume_update(
+    ctx: &mut ProjectContext,
+    options: UpdateOptions,
+    prompter: &dyn Prompter,
+) -> Result<(), PpmError> {
+    let state = ctx.state();
+    let Some(pending) = PendingUpdate::load(&state)? else {
+        return Err(PpmError::Other(
+            "No interrupted update to resume; run `ppm update` instead".to_string(),
+        ));
+    };
+    let names = pending.names();
+    if pending.is_stale(resume::now()) {
+        iprint(format!(
+            "The interrupted update was planned over an hour ago; looking up {} again",
+            names.join(", ")
+        ));
+        PendingUpdate::clear(&state)?;
+        return update_project(ctx, &names, options, prompter);
+    }
+
+    let (summary, changed) = pending.summary(&ctx.config);
+    for name in &changed {
+        wprint(format!(
+            "Leaving out '{}': project.toml changed it since the update stopped",
+            name
+        ));
+    }
+    if summary.packages.is_empty() {
+        PendingUpdate::clear(&state)?;
+        iprint("Nothing left to resume".to_string());
+        return Ok(());
+    }
+    let plan = UpdateSummary {
+        dry_run: true,
+        packages: summary.packages.clone(),
+        changelogs: HashMap::new(),
+        ages: HashMap::new(),
+        mode: summary.mode,
+        rewrites: summary.rewrites.clone(),
+    };
+    print_human(format!("\n{}", plan.render().trim_end()));
+    if !options.yes
+        && !prompter.ask_yes_no(
+            &format!(
+                "Resume the interrupted update of {} package(s)?",
+                summary.packages.len()
+            ),
+            true,
+        )
+    {
+        wprint("Update Cancelled".to_owned());
+        return Ok(());
+    }
+    if !ensure_venv(ctx, prompter)? {
+        wprint("Update Cancelled".to_owned());
+        return Ok(());
+    }
+    PendingUpdate::clear(&state)?;
+    install_update(ctx, summary, options, prompter)
+}
+
+fn update_project(
+    ctx: &mut ProjectContext,
+    pkg_names: &[String],
+    options: UpdateOptions,
+    prompter: &dyn Prompter,
+) -> Result<(), PpmError> {
+    let UpdateOptions {
+        dry_run,
+        refresh_git,
+        yes,
+        ..
+    } = options;
+    if ctx.config.packages.is_empty() {
+        wprint("No packages to update".to_owned());
+        return Ok(());
+    }
+
+    if !dry_run && !ensure_venv(ctx, prompter)? {
+        wprint("Update Cancelled".to_owned());
+        return Ok(());
+    }
+
+    let constraints = ctx.constraints()?;
+    let mut selected = update_targets(&ctx.config, pkg_names)?;
+    for name in &selected.unknown {
+        wprint(format!("Package '{}' not found in project.toml", name));
+    }
+
+    // Markers need the venv interpreter; a dry run without one plans everything
+    if !dry_run || check_venv_dir_exists(ctx) {
+        let mut targets = vec![];
+        for name in selected.targets {
+            if matches_environment(ctx, &name, &ctx.config.packages[&name])? {
+                targets.push(name);
+            }
+        }
+        selected.targets = targets;
+    }
+
+    for name in &selected.targets {
+        let reason = match &ctx.config.packages[name] {
+            PackageSpec::Git(_) if !refresh_git => "git package, pass --refresh-git to update it",
+            PackageSpec::Git(git) if git.tracked_ref().is_none() => {
+                "git package without a branch or tag to follow"
+            }
+            PackageSpec::Path(_) | PackageSpec::Url(_) => "not installed from the package index",
+            _ => continue,
+        };
+        iprint(format!("Skipping '{}': {}", name, reason));
+    }
+
+    // With --latest, packages are planned as if their policy let them
+    // move anywhere
+    let planning = options.mode.planning_config(&ctx.config);
+
+    // Git, path and URL packages have no index version to look up, and
+    // packages with an update policy are looked up further down
+    let index_targets: Vec<String> = selected
+        .targets
+        .iter()
+        .filter(|name| {
+            let spec = &planning.packages[*name];
+            spec.version().is_some() && spec.update_policy() == UpdatePolicy::Latest
+        })
+        .cloned()
+        .collect();
+    let progress = Progress::bar(Phase::Resolve, index_targets.len(), "Resolving");
+    for name in &index_targets {
+        progress.start_concurrent_item(name);
+    }
+    let latest = lookup_latest(ctx, &planning, &index_targets, |name, result| {
+        let reason = result.as_ref().err().map(|e| e.to_string());
+        progress.finish_concurrent_item(name, reason.as_deref());
+    });
+    let mut latest = match latest {
+        Ok(latest) => latest,
+        Err(e) => {
+            progress.abandon(&e.to_string());
+            return Err(e);
+        }
+    };
+    progress.finish();
+    let mut summary = plan_updates_with(&planning, &selected.targets, dry_run, |name| {
+        latest.remove(name).unwrap_or_else(|| ctx.latest_version(name))
+    });
+    plan_policy_updates_with(&planning, &selected.targets, &mut summary, |name| {
+        ctx.releases(name)
+    });
+    constrain_updates_with(&planning, &mut summary, &constraints, |name| {
+        ctx.releases(name)
+    });
+    summary.mode = options.mode;
+    summary.plan_rewrites(&ctx.config);
+    if refresh_git {
+        plan_git_refresh_with(&ctx.config, &selected.targets, &mut summary, |git, reference| {
+            git::resolve_ref(&git.git, reference)
+        });
+    }
+
+    // Links are best-effort; a package whose metadata can't be fetched
+    // just goes without one
+    let changelog_targets = summary.updated_index_packages(&ctx.config);
+    if !changelog_targets.is_empty() {
+        let urls = ctx.changelog_urls(&changelog_targets)?;
+        summary.set_changelogs(&changelog_targets, urls);
+    }
+
+    // Everything is resolved; show the plan before anything changes
+    let updates = summary.updated().count();
+    if !dry_run && updates > 0 {
+        let plan = UpdateSummary {
+            dry_run: true,
+            packages: summary.packages.clone(),
+            changelogs: summary.changelogs.clone(),
+            ages: HashMap::new(),
+            mode: summary.mode,
+            rewrites: summary.rewrites.clone(),
+        };
+        print_human(format!("\n{}", plan.render().trim_end()));
+        if !yes && !prompter.ask_yes_no(&format!("Apply these {} updates?", updates), true) {
+            wprint("Update Cancelled".to_owned());
+            return Ok(());
+        }
+    }
+
+    install_update(ctx, summary, options, prompter)
+}
+
+/// Install and record the planned `summary`, then report it. Packages
+/// Ctrl+C kept from being installed are saved for `ppm update --resume`.
+fn install_update(
+    ctx: &mut ProjectContext,
+    mut summary: UpdateSummary,
+    options: UpdateOptions,
+    prompter: &dyn Prompter,
+) -> Result<(), PpmError> {
+    let UpdateOptions {
+        dry_run,
+        jobs,
+        force_reinstall,
+        deny_yanked,
+        ..
+    } = options;
+
+    // pip only runs for packages that would change, unless asked to
+    // reinstall them all
+    let installed = if dry_run || force_reinstall {
+        None
+    } else {
+        get_installed_packages(ctx).ok()
+    };
+    let packages_to_install =
+        summary.install_specs(&ctx.config, installed.as_ref(), force_reinstall);
+
+    let mut install_error = None;
+    let mut pending = None;
+    if !dry_run && !packages_to_install.is_empty() {
+        match parse_requirements(&packages_to_install) {
+            Ok(reqs) => {
+                // pip leaves a package it already has alone unless told
+                // to reinstall it
+                let tasks: Vec<InstallTask> = reqs
+                    .iter()
+                    .map(|req| {
+                        let mut args = vec![req.to_string()];
+                        if force_reinstall {
+                            args.insert(0, "--force-reinstall".to_string());
+                        }
+                        InstallTask::new(&req.name, args)
+                    })
+                    .collect();
+                let jobs = install::resolve_jobs(jobs, ctx.config.project.jobs);
+                let report = install_tasks(&tasks, ctx, jobs);
+                for (name, reason) in &report.failed {
+                    summary.fail_update(name, reason);
+                }
+                if !report.skipped.is_empty() {
+                    pending = Some(PendingUpdate::take_from(
+                        &mut summary,
+                        &report.skipped,
+                        resume::now(),
+                    ));
+                }
+                // Only the packages that made it into the venv are recorded
+                if report.failed.len() + report.skipped.len() < tasks.len() {
+                    summary.apply(&mut ctx.config);
+                    save_project(ctx, Some(prompter))?;
+
+                    if let Err(e) = generate_lock_file(ctx) {
+                        eprint(format!("Failed to generate lock file: {}", e));
+                    }
+                }
+                install_error = report.error;
+            }
+            Err(e) => {
+                summary.fail_updates(&e.to_string());
+                install_error = Some(e);
+            }
+        }
+    }
+
+    if options.markdown {
+        let names = summary.updated_index_packages(&ctx.config);
+        let histories = ctx.release_histories(&names)?;
+        let today = age::today();
+        for (name, history) in names.iter().zip(histories) {
+            let from = summary
+                .updated()
+                .find(|(updated, _, _)| updated == name)
+                .map(|(_, from, _)| from);
+            if let Some(days) = history
+                .and_then(|history| ReleaseDays::find(&history, from, None).pinned_age(today))
+            {
+                summary.ages.insert(name.clone(), days);
+            }
+        }
+        print!("{}", summary.to_markdown());
+    } else if json_output() {
+        println!("{}", summary.to_json());
+    } else {
+        println!();
+        print!("{}", summary.render());
+    }
+
+    // Pins the update leaves in place can still be on yanked releases
+    let moving: Vec<&str> = summary.updated().map(|(name, _, _)| name).collect();
+    let yanked: Vec<YankedPin> = ctx
+        .yanked_pins()?
+        .into_iter()
+        .filter(|pin| !dry_run || !moving.contains(&pin.name.as_str()))
+        .collect();
+    let denied = warn_yanked(&yanked, deny_yanked);
+
+    let failed = summary.failed().count();
+    if let Some(pending) = pending {
+        pending.store(&ctx.state())?;
+        let left = pending.names();
+        Err(PpmError::Interrupted(format!(
+            "Interrupted with {} package(s) left to update: {}; run `ppm update --resume` to finish",
+            left.len(),
+            left.join(", ")
+        )))
+    } else if let Some(e) = install_error {
+        Err(e)
+    } else if failed > 0 {
+        Err(PpmError::Network(format!(
+            "Could not resolve {} package(s)",
+            failed
+        )))
+    } else {
+        denied
+    }
+}
+
+pub fn list_packages(
+    filter: Option<&str>,
+    outdated: bool,
+    sort: SortKey,
+    deny_yanked: bool,
+    older_than: Option<u64>,
+    markdown: bool,
+) -> Result<(), PpmError> {
+    if markdown {
+        reserve_stdout();
+    }
+    let outdated = outdated || markdown;
+    let ctx = load_project()?;
+    let conf = &ctx.config;
+
+    let count = conf.packages.len();
+
+    if count == 0 {
+        wprint("No packages configured".to_string());
+        return Ok(());
+    }
+
+    let installed = if check_venv_dir_exists(&ctx) {
+        match get_installed_packages(&ctx) {
+            Ok(installed) => Some(installed),
+            Err(e) => {
+                wprint(e);
+                None
+            }
+        }
+    } else {
+        wprint(format!(
+            "Could not find '{}' directory, installed versions are unavailable",
+            ctx.venv_root()
+        ));
+        None
+    };
+
+    let mut rows = listing::collect(&conf.packages, installed.as_ref(), filter, sort);
+    if rows.is_empty() {
+        if json_output() {
+            println!("{}", serde_json::json!({ "packages": [] }));
+        }
+        wprint(format!(
+            "No packages match '{}'",
+            filter.unwrap_or_default()
+        ));
+        return Ok(());
+    }
+
+    let mut latest: HashMap<String, String> = HashMap::new();
+    let mut histories: HashMap<String, Vec<ReleaseEntry>> = HashMap::new();
+    if outdated {
+        let index: Vec<String> = rows
+            .iter()
+            .filter(|row| row.spec.is_index())
+            .map(|row| row.name.clone())
+            .collect();
+        // Offline, the latest versions come from the cached histories below
+        if !ctx.offline {
+            let progress = Progress::bar(Phase::Resolve, index.len(), "Checking PyPI");
+            for name in &index {
+                progress.start_concurrent_item(name);
+            }
+            let results = ctx.latest_versions(&index, |name, result| {
+                let reason = result.as_ref().err().map(|e| e.to_string());
+                progress.finish_concurrent_item(name, reason.as_deref());
+            });
+            let results = match results {
+                Ok(results) => results,
+                Err(e) => {
+                    progress.abandon(&e.to_string());
+                    return Err(e);
+                }
+            };
+            progress.finish();
+            for (name, result) in index.iter().zip(results) {
+                match result {
+                    Ok(ver) => {
+                        latest.insert(name.clone(), ver);
+                    }
+                    Err(e) => eprint(format!("Could not find latest version of {}: {}", name, e)),
+                }
+            }
+        }
+        let found = ctx.release_histories(&index)?;
+        for (name, history) in index.into_iter().zip(found) {
+            if let Some(history) = history {
+                histories.insert(name, history);
+            }
+        }
+        for (name, history) in &histories {
+            if let Some(release) = age::latest_release(history)
+                && !latest.contains_key(name)
+            {
+                latest.insert(name.clone(), release.version.clone());
+            }
+        }
+    }
+    let yanked: Vec<YankedPin> = rows
+        .iter()
+        .filter(|row| row.spec.is_index())
+        .filter_map(|row| {
+            yanked::check_pin(&row.name, row.spec.version()?, histories.get(&row.name)?)
+        })
+        .collect();
+    let released: HashMap<&str, ReleaseDays> = histories
+        .iter()
+        .filter_map(|(name, history)| {
+            let spec = conf.packages.get(name)?;
+            let days = ReleaseDays::find(
+                history,
+                spec.version(),
+                latest.get(name).map(String::as_str),
+            );
+            Some((name.as_str(), days))
+        })
+        .collect();
+    let today = age::today();
+    let pinned_age = |name: &str| released.get(name)?.pinned_age(today);
+
+    if let Some(days) = older_than {
+        rows.retain(|row| pinned_age(&row.name).is_some_and(|age| age >= days));
+        if rows.is_empty() {
+            if json_output() {
+                println!("{}", serde_json::json!({ "packages": [] }));
+            }
+            wprint(format!("No pinned release is older than {} days", days));
+            return warn_yanked(&yanked, deny_yanked);
+        }
+    }
+
+    let mut details: Vec<OutdatedRow> = rows
+        .iter()
+        .filter(|_| outdated)
+        .map(|row| OutdatedRow {
+            package: row.clone(),
+            latest: latest.get(&row.name).cloned(),
+            released: released.get(row.name.as_str()).cloned(),
+            age_days: pinned_age(&row.name),
+            yanked: yanked.iter().find(|pin| pin.name == row.name).cloned(),
+            changelog: None,
+        })
+        .collect();
+
+    if markdown {
+        // Links are best-effort, as for `ppm update`
+        let behind: Vec<String> = details
+            .iter()
+            .filter(|row| {
+                row.package.spec.is_index()
+                    && row
+                        .latest
+                        .as_deref()
+                        .is_some_and(|l| row.package.spec.version() != Some(l))
+            })
+            .map(|row| row.package.name.clone())
+            .collect();
+        if !behind.is_empty() {
+            let urls = ctx.changelog_urls(&behind)?;
+            for (name, url) in behind.iter().zip(urls) {
+                if let Some(row) = details.iter_mut().find(|row| &row.package.name == name) {
+                    row.changelog = url;
+                }
+            }
+        }
+        print!("{}", listing::outdated_markdown(&details));
+        return warn_yanked(&yanked, deny_yanked);
+    }
+
+    if json_output() {
+        let packages: Vec<serde_json::Value> = if outdated {
+            details.iter().map(outdated_json).collect()
+        } else {
+            rows.iter().map(|row| package_json(row, None)).collect()
+        };
+        println!("{}", serde_json::json!({ "packages": packages }));
+        return warn_yanked(&yanked, deny_yanked);
+    }
+
+    let mut headers = vec!["Package", "Configured", "Installed"];
+    if outdated {
+        headers.extend(["Latest", "Policy", "Age", "Released"]);
+    }
+    let mut table = Table::new(&headers);
+    for (i, row) in rows.iter().enumerate() {
+        let spec = &row.spec;
+        let version = spec.version();
+        let inst_cell = match (row.state, &row.installed) {
+            (InstallState::Unknown, _) => Cell::new("-", Style::Dim),
+            (InstallState::Missing, _) => Cell::new("not installed", Style::Error),
+            (InstallState::Differs, Some(v)) => Cell::new(v.clone(), Style::Warn),
+            (_, v) => Cell::plain(v.clone().unwrap_or_default()),
+        };
+        let mut cells = vec![
+            Cell::new(row.name.clone(), Style::Bold),
+            Cell::plain(spec.to_string()).with_note(spec.marker().map(|m| format!("; {}", m))),
+            inst_cell,
+        ];
+        if let Some(detail) = details.get(i) {
+            cells.push(match &detail.latest {
+                Some(l) if version != Some(l.as_str()) => Cell::new(l.clone(), Style::Info),
+                _ => Cell::new(detail.latest_text(), Style::Dim),
+            });
+            cells.push(match spec.update_policy() {
+                _ if !spec.is_index() => Cell::new("-", Style::Dim),
+                UpdatePolicy::Latest => Cell::new("latest", Style::Dim),
+                policy => Cell::new(policy.to_string(), Style::Warn),
+            });
+            cells.push(match detail.age_days {
+                Some(_) => Cell::plain(detail.age_text()),
+                None => Cell::new(detail.age_text(), Style::Dim),
+            });
+            cells.push(Cell::new(
+                detail
+                    .released
+                    .as_ref()
+                    .map(ReleaseDays::describe)
+                    .unwrap_or_default(),
+                Style::Dim,
+            ));
+        }
+        table.add_row(cells);
+    }
+
+    let shown = if rows.len() == count {
+        count.to_string().green().bold().to_string()
+    } else {
+        format!("{} of {}", rows.len().to_string().green().bold(), count)
+    };
+    println!("\nConfigured packages ({}):\n", shown);
+    table.print();
+    println!();
+    warn_yanked(&yanked, deny_yanked)
+}
+
+/// `ppm list --installed`: every distribution in the venv with why it's
+/// there, only the unmanaged ones with `unmanaged_only`
+pub fn list_installed(filter: Option<&str>, unmanaged_only: bool) -> Result<(), PpmError> {
+    let ctx = load_project()?;
+    ctx.require_venv()?;
+    let installed = get_installed_distributions(&ctx).map_err(PpmError::Venv)?;
+    let configured: Vec<String> = ctx.config.packages.keys().cloned().collect();
+    let graph = graph::graph(&ctx)?;
+    let mut rows = listing::classify_installed(&installed, &configured, &graph);
+    let count = rows.len();
+    rows.retain(|row| filter.is_none_or(|pattern| listing::matches_filter(pattern, &row.name)));
+    if unmanaged_only {
+        // The venv's own tooling is never something to clean up
+        rows.retain(|row| {
+            row.origin == Origin::Unmanaged
+                && !graph::KEEP_INSTALLED.contains(&normalize_pkg_name(&row.name).as_str())
+        });
+    }
+
+    if json_output() {
+        let packages: Vec<serde_json::Value> = rows
+            .iter()
+            .map(|row| {
+                serde_json::json!({
+                    "name": row.name,
+                    "version": row.version,
+                    "origin": row.origin.as_str(),
+                })
+            })
+            .collect();
+        println!("{}", serde_json::json!({ "packages": packages }));
+        return Ok(());
+    }
+    if rows.is_empty() {
+        match filter {
+            Some(pattern) => wprint(format!("No installed packages match '{}'", pattern)),
+            None => iprint("Nothing in the venv is unmanaged".to_string()),
+        }
+        return Ok(());
+    }
+
+    let mut table = Table::new(&["Package", "Version", "Origin"]);
+    for row in &rows {
+        let style = match row.origin {
+            Origin::Configured => Style::Info,
+            Origin::Transitive => Style::Dim,
+            Origin::Unmanaged => Style::Warn,
+        };
+        table.add_row(vec![
+            Cell::new(row.name.clone(), Style::Bold),
+            Cell::plain(row.version.clone()),
+            Cell::new(row.origin.as_str(), style),
+        ]);
+    }
+    let shown = if rows.len() == count {
+        count.to_string().green().bold().to_string()
+    } else {
+        format!("{} of {}", rows.len().to_string().green().bold(), count)
+    };
+    println!("\nInstalled packages ({}):\n", shown);
+    table.print();
+    println!();
+    Ok(())
+}
+
+/// A row of `ppm list --json`; `latest` is `Some` with `--outdated`, and
+/// holds the version found on the index, if any
+fn package_json(row: &PackageRow, latest: Option<Option<&String>>) -> serde_json::Value {
+    let state = match row.state {
+        InstallState::Missing => "missing",
+        InstallState::Differs => "differs",
+        InstallState::Installed => "installed",
+        InstallState::Unknown => "unknown",
+    };
+    let mut value = serde_json::json!({
+        "name": row.name,
+        "configured": row.spec.to_string(),
+        "marker": row.spec.marker(),
+        "installed": row.installed,
+        "state": state,
+    });
+    if let Some(latest) = latest {
+        value["latest"] = serde_json::json!(latest);
+        value["policy"] = serde_json::json!(row.spec.update_policy().to_string());
+    }
+    value
+}
+
+/// A row of `ppm list --outdated --json`
+fn outdated_json(row: &OutdatedRow) -> serde_json::Value {
+    let mut value = package_json(&row.package, Some(row.latest.as_ref()));
+    value["released"] = serde_json::json!({
+        "pinned": row.released.as_ref().and_then(|days| days.pinned.as_deref()),
+        "latest": row.released.as_ref().and_then(|days| days.latest.as_deref()),
+    });
+    value["age_days"] = serde_json::json!(row.age_days);
+    value["yanked"] = row
+        .yanked
+        .as_ref()
+        .map_or(serde_json::Value::Null, YankedPin::to_json);
+    value
+}
+
+/// `ppm ide vscode`: merge the venv interpreter, and unless
+/// `interpreter_only` test and formatter settings, into
+/// .vscode/settings.json; with `print` only show what would change
+pub fn configure_vscode(print: bool, interpreter_only: bool) -> Result<(), PpmError> {
+    let ctx = load_project()?;
+    let python = ctx.venv_python();
+    if !python.exists() {
+        wprint("The venv doesn't exist yet; run `ppm install` to create it".to_string());
+    }
+    let interpreter = python.display().to_string();
+    let mut scripts: Vec<(&str, &str)> = ctx
+        .config
+        .scripts
+        .iter()
+        .filter_map(|(name, script)| Some((name.as_str(), script.command()?)))
+        .collect();
+    scripts.sort();
+    let mut settings = ide::vscode_settings(
+        &interpreter,
+        &scripts,
+        test_runner::detect(&ctx.root, &ctx.venv_dir()),
+    );
+    if interpreter_only {
+        settings.truncate(1);
+    }
+
+    let path = ctx.root.join(ide::VSCODE_SETTINGS_FILE);
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let (merged, changes) = ide::merge_settings(&text, &settings).map_err(|e| {
+        PpmError::Config(format!("Can't update {}: {}", ide::VSCODE_SETTINGS_FILE, e))
+    })?;
+    let write = !print && !changes.is_empty();
+    if write {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        state::write_atomic(&path, merged.as_bytes())?;
+    }
+
+    if json_output() {
+        let changes: Vec<serde_json::Value> = changes
+            .iter()
+            .map(|change| {
+                serde_json::json!({ "key": change.key, "from": change.from, "to": change.to })
+            })
+            .collect();
+        println!(
+            "{}",
+            serde_json::json!({
+                "path": ide::VSCODE_SETTINGS_FILE,
+                "changes": changes,
+                "written": write,
+            })
+        );
+        return Ok(());
+    }
+    if changes.is_empty() {
+        iprint(format!("{} is up to date", ide::VSCODE_SETTINGS_FILE));
+        return Ok(());
+    }
+    for change in &changes {
+        match &change.from {
+            Some(from) => println!(
+                "  {} {}: {} {} {}",
+                "~".yellow().bold(),
+                change.key,
+                from,
+                theme().arrow,
+                change.to
+            ),
+            None => println!("  {} {}: {}", "+".green().bold(), change.key, change.to),
+        }
+    }
+    if print {
+        iprint(format!(
+            "Would update {}; run without --print to write it",
+            ide::VSCODE_SETTINGS_FILE
+        ));
+    } else {
+        iprint(format!("Updated {}", ide::VSCODE_SETTINGS_FILE));
+    }
+    Ok(())
+}
+
+/// `ppm ide pycharm`: PyCharm keeps interpreters outside the project, so
+/// show the interpreter and a `jdk.table.xml` entry for it
+pub fn show_pycharm_setup() -> Result<(), PpmError> {
+    let ctx = load_project()?;
+    let python = ctx.venv_python().display().to_string();
+    let version = ctx.python_version().map(str::to_string);
+    let short_version = version
+        .as_deref()
+        .map(|version| version.split('.').take(2).collect::<Vec<_>>().join("."))
+        .unwrap_or_else(|| "3".to_string());
+    let name = format!("Python {} ({})", short_version, ctx.config.project.name);
+    let entry = ide::pycharm_jdk_entry(&name, &python, version.as_deref());
+
+    if json_output() {
+        println!(
+            "{}",
+            serde_json::json!({ "interpreter": python, "name": name, "jdk_table_entry": entry })
+        );
+        return Ok(());
+    }
+    println!("{}: {}", "Interpreter".bold(), python);
+    println!();
+    println!("In PyCharm: Settings > Project > Python Interpreter > Add Interpreter >");
+    println!("Add Local Interpreter > Virtualenv Environment > Existing, and pick the path above.");
+    println!();
+    println!(
+        "Or, with PyCharm closed, add this inside <application><component name=\"ProjectJdkTable\">"
+    );
+    println!("of options/jdk.table.xml in the PyCharm config directory:");
+    println!();
+    println!("{}", entry.dimmed());
+    Ok(())
+}
+
+/// `--project <member>` and `--all`: call `run` from the root of the
+/// named member of the workspace around the current directory, or from
+/// each member's root in turn followed by a summary
+pub fn run_in_members(
+    project: Option<&str>,
+    mut run: impl FnMut() -> Result<(), PpmError>,
+) -> Result<(), PpmError> {
+    let cwd = std::env::current_dir()?;
+    let Some((root, workspace)) = workspace::find_workspace_root(&cwd) else {
+        return Err(PpmError::Config(format!(
+            "Not in a workspace: no {} with a [workspace] table here or in a parent directory",
+            get_project_config_file()
+        )));
+    };
+    let members = workspace::members(&root, &workspace)?;
+    if let Some(name) = project {
+        let member = workspace::select(&members, name)?;
+        std::env::set_current_dir(&member.root)?;
+        return run();
+    }
+    if members.is_empty() {
+        return Err(PpmError::Config(format!(
+            "The workspace at {} has no members; check members under [workspace]",
+            paths::display(&root)
+        )));
+    }
+
+    let mut outcomes = vec![];
+    for member in &members {
+        print_human(format!("{} {}", "==>".bright_blue().bold(), member.name.bold()));
+        std::env::set_current_dir(&member.root)?;
+        let code = match run() {
+            Ok(()) => None,
+            Err(e) => {
+                eprint(e.with_code());
+                if json_output() {
+                    println!("{}", e.to_json());
+                }
+                Some(e.exit_code())
+            }
+        };
+        outcomes.push((member, code));
+    }
+    std::env::set_current_dir(&cwd)?;
+
+    if json_output() {
+        let members: Vec<serde_json::Value> = outcomes
+            .iter()
+            .map(|(member, code)| {
+                serde_json::json!({
+                    "name": member.name,
+                    "path": member.root,
+                    "ok": code.is_none(),
+                    "exit_code": code.unwrap_or(0),
+                })
+            })
+            .collect();
+        println!("{}", serde_json::json!({ "members": members }));
+    } else {
+        println!();
+        let mut table = Table::new(&["Member", "Result"]);
+        for (member, code) in &outcomes {
+            table.add_row(vec![
+                Cell::new(member.name.clone(), Style::Bold),
+                match code {
+                    None => Cell::new("ok", Style::Info),
+                    Some(code) => Cell::new(format!("failed (exit {})", code), Style::Error),
+                },
+            ]);
+        }
+        table.print();
+    }
+
+    let failed: Vec<(String, i32)> = outcomes
+        .iter()
+        .filter_map(|(member, code)| code.map(|code| (member.name.clone(), code)))
+        .collect();
+    match failed.first() {
+        None => Ok(()),
+        Some((_, code)) => Err(PpmError::MembersFailed {
+            code: *code,
+            failed: failed.iter().map(|(name, _)| name.clone()).collect(),
+            total: outcomes.len(),
+        }),
+    }
+}
+
+/// Make sure the venv has the packages `script` requires, offering to
+/// install those it lacks; fails listing them when they stay missing
+fn check_script_requirements(
+    ctx: &ProjectContext,
+    script: &Script,
+    prompter: &dyn Prompter,
+) -> Result<(), PpmError> {
+    if script.requires.is_empty() {
+        return Ok(());
+    }
+    // Nothing to check or install into on the system Python
+    if ctx.system_python {
+        wprint(format!(
+            "Not checking that the system Python has {}, which script '{}' requires",
+            script.requires.join(", "),
+            script.name
+        ));
+        return Ok(());
+    }
+    let installed = preflight::installed_versions(&ctx.venv_dir());
+    let missing = scripts::missing_requirements(&script.requires, &installed);
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let listed = missing.join(", ");
+    if !check_venv_dir_exists(ctx) {
+        return Err(PpmError::Venv(format!(
+            "Script '{}' needs {}, but the venv doesn't exist yet; run `ppm install` first",
+            script.name, listed
+        )));
+    }
+    if !prompter.is_interactive() {
+        return Err(PpmError::Other(format!(
+            "Script '{}' needs packages the venv doesn't have: {}; run it from a terminal to install them",
+            script.name, listed
+        )));
+    }
+    let question = format!(
+        "Script '{}' needs {}, which the venv doesn't have. Install them now?",
+        script.name, listed
+    );
+    if !prompter.ask_yes_no(&question, true) {
+        return Err(PpmError::Other(format!(
+            "Not running '{}' without {}",
+            script.name, listed
+        )));
+    }
+    let args: Vec<Vec<String>> = missing.into_iter().map(|requirement| vec![requirement]).collect();
+    pip_install(&args, ctx)
+}
+
+/// Run `script` in the project root once the packages it requires are
+/// installed, failing with its exit status
+pub fn run_script(
+    ctx: &ProjectContext,
+    script: &Script,
+    prompter: &dyn Prompter,
+) -> Result<(), PpmError> {
+    check_script_requirements(ctx, script, prompter)?;
+    let mut cmd = script_command(ctx, script)?;
+
+    let name = script.name.as_str();
+    Event::new(Phase::Script, Status::Start).script(name).emit();
+    let status = cmd.spawn().and_then(|mut child| child.wait()).map_err(|e| {
+        Event::new(Phase::Script, Status::Fail)
+            .script(name)
+            .message(&e.to_string())
+            .emit();
+        PpmError::Subprocess(format!("Failed to run script '{}': {}", name, e))
+    })?;
+    Event::new(Phase::Script, Status::Finish)
+        .script(name)
+        .exit_code(status.code())
+        .emit();
+
+    if !status.success() {
+        return Err(PpmError::ChildExit {
+            name: name.to_string(),
+            code: status.code(),
+        });
+    }
+    Ok(())
+}
+
+/// Run each of `scripts` in turn, carrying on past failures, then show
+/// how each went; fails with the status of the first that failed
+pub fn run_scripts(
+    ctx: &ProjectContext,
+    scripts: &[Script],
+    prompter: &dyn Prompter,
+) -> Result<(), PpmError> {
+    let mut outcomes = vec![];
+    for script in scripts {
+        print_human(format!("{} {}", "==>".bright_blue().bold(), script.name.bold()));
+        let code = match run_script(ctx, script, prompter) {
+            Ok(()) => None,
+            Err(e) => {
+                eprint(e.with_code());
+                Some(e.exit_code())
+            }
+        };
+        outcomes.push((script, code));
+    }
+
+    if json_output() {
+        let scripts: Vec<serde_json::Value> = outcomes
+            .iter()
+            .map(|(script, code)| {
+                serde_json::json!({
+                    "name": script.name,
+                    "ok": code.is_none(),
+                    "exit_code": code.unwrap_or(0),
+                })
+            })
+            .collect();
+        println!("{}", serde_json::json!({ "scripts": scripts }));
+    } else {
+        println!();
+        let mut table = Table::new(&["Script", "Result"]);
+        for (script, code) in &outcomes {
+            table.add_row(vec![
+                Cell::new(script.name.clone(), Style::Bold),
+                match code {
+                    None => Cell::new("ok", Style::Info),
+                    Some(code) => Cell::new(format!("failed (exit {})", code), Style::Error),
+                },
+            ]);
+        }
+        table.print();
+    }
+
+    let failed: Vec<(String, i32)> = outcomes
+        .iter()
+        .filter_map(|(script, code)| code.map(|code| (script.name.clone(), code)))
+        .collect();
+    match failed.first() {
+        None => Ok(()),
+        Some((_, code)) => Err(PpmError::ScriptsFailed {
+            code: *code,
+            failed: failed.iter().map(|(name, _)| name.clone()).collect(),
+            total: outcomes.len(),
+        }),
+    }
+}
+
+/// Run the `ppm-<name>` plugin for `args` (the unknown subcommand and
+/// what followed it), `None` when there is no such plugin on PATH
+pub fn run_plugin(args: &[String]) -> Option<Result<(), PpmError>> {
+    let (name, rest) = args.split_first()?;
+    let path_var = std::env::var_os("PATH").unwrap_or_default();
+    let plugin = plugins::find_plugin(name, &path_var)?;
+
+    let mut cmd = Command::new(&plugin);
+    cmd.args(rest);
+    let root = std::env::current_dir()
+        .ok()
+        .and_then(|cwd| paths::find_project_root(&cwd));
+    if let Some(root) = root {
+        cmd.env(plugins::PROJECT_ROOT_ENV, &root)
+            .env(plugins::CONFIG_ENV, root.join(get_project_config_file()));
+        // An unreadable project.toml is the plugin's business, not ours
+        if let Ok(ctx) = ProjectContext::load(&root) {
+            cmd.env(plugins::VENV_ENV, ctx.venv_dir());
+        }
+    }
+    Some(exec_plugin(cmd, name))
+}
+
+/// Replace this process with the plugin
+#[cfg(unix)]
+fn exec_plugin(mut cmd: Command, name: &str) -> Result<(), PpmError> {
+    use std::os::unix::process::CommandExt;
+
+    let e = cmd.exec();
+    Err(PpmError::Subprocess(format!(
+        "Failed to run plugin '{}{}': {}",
+        plugins::PLUGIN_PREFIX,
+        name,
+        e
+    )))
+}
+
+/// Run the plugin and exit with its status
+#[cfg(not(unix))]
+fn exec_plugin(mut cmd: Command, name: &str) -> Result<(), PpmError> {
+    let plugin = format!("{}{}", plugins::PLUGIN_PREFIX, name);
+    let status = cmd
+        .status()
+        .map_err(|e| PpmError::Subprocess(format!("Failed to run plugin '{}': {}", plugin, e)))?;
+    if !status.success() {
+        return Err(PpmError::ChildExit {
+            name: plugin,
+            code: status.code(),
+        });
+    }
+    Ok(())
+}
+
+/// The aliases in effect here: the global config's, overridden by those
+/// of the project around the current directory
+fn load_aliases() -> Result<Aliases, PpmError> {
+    let global = GlobalConfig::load()?.aliases;
+    let project = match std::env::current_dir()
+        .ok()
+        .and_then(|cwd| paths::find_project_root(&cwd))
+    {
+        Some(root) => aliases::load_project_aliases(&root.join(PROJECT_CONFIG_FILE))?,
+        None => BTreeMap::new(),
+    };
+    Ok(aliases::merge(&global, &project))
+}
+
+/// The process's arguments with an alias in the subcommand's place
+/// expanded, and the aliases in effect
+pub fn expand_aliases(cli: &clap::Command) -> Result<(Vec<OsString>, Aliases), PpmError> {
+    let mut args: Vec<OsString> = std::env::args_os().collect();
+    let aliases = load_aliases()?;
+    if aliases.is_empty() {
+        return Ok((args, aliases));
+    }
+    let mut builtins: Vec<&str> = vec!["help"];
+    for command in cli.get_subcommands() {
+        builtins.push(command.get_name());
+        builtins.extend(command.get_all_aliases());
+    }
+    aliases::check_shadowing(&aliases, &builtins)?;
+
+    let value_flags: Vec<String> = cli
+        .get_arguments()
+        .filter(|arg| arg.is_takes_value_set())
+        .filter_map(|arg| arg.get_long())
+        .map(|long| format!("--{}", long))
+        .collect();
+    let value_flags: Vec<&str> = value_flags.iter().map(String::as_str).collect();
+    // Arguments that aren't UTF-8 can't be matched against aliases anyway
+    let Some(words) = args[1..]
+        .iter()
+        .map(|arg| arg.to_str().map(str::to_string))
+        .collect::<Option<Vec<String>>>()
+    else {
+        return Ok((args, aliases));
+    };
+    let expanded = aliases::expand(&words, &aliases, &builtins, &value_flags)?;
+    args.truncate(1);
+    args.extend(expanded.into_iter().map(OsString::from));
+    Ok((args, aliases))
+}
+
+/// `ppm --list-aliases`: the aliases in effect and where each comes from
+pub fn list_aliases(aliases: &Aliases) {
+    if json_output() {
+        let aliases: Vec<serde_json::Value> = aliases
+            .iter()
+            .map(|(name, alias)| {
+                serde_json::json!({
+                    "name": name,
+                    "expansion": alias.expansion,
+                    "source": alias.source.to_string(),
+                })
+            })
+            .collect();
+        println!("{}", serde_json::json!({ "aliases": aliases }));
+        return;
+    }
+    if aliases.is_empty() {
+        iprint(
+            "No aliases; add an [aliases] table to project.toml or to the global config.toml"
+                .to_string(),
+        );
+        return;
+    }
+    let mut table = Table::new(&["Alias", "Runs", "From"]);
+    for (name, alias) in aliases {
+        table.add_row(vec![
+            Cell::new(name.clone(), Style::Bold),
+            Cell::plain(format!("ppm {}", alias.expansion)),
+            Cell::new(alias.source.to_string(), Style::Dim),
+        ]);
+    }
+    table.print();
+}
+
+/// `ppm --list`: the built-in subcommands of `cli` and the plugins on
+/// PATH that don't clash with them
+pub fn list_commands(cli: &clap::Command) {
+    let builtins: Vec<(&str, &str)> = cli
+        .get_subcommands()
+        .filter(|command| !command.is_hide_set())
+        .map(|command| (command.get_name(), command.get_about().unwrap_or_default()))
+        .collect();
+    let path_var = std::env::var_os("PATH").unwrap_or_default();
+    let plugins: Vec<(String, PathBuf)> = plugins::discover_plugins(&path_var)
+        .into_iter()
+        .filter(|(name, _)| !builtins.iter().any(|(builtin, _)| builtin == name))
+        .collect();
+
+    if json_output() {
+        let commands: Vec<serde_json::Value> = builtins
+            .iter()
+            .map(|(name, about)| serde_json::json!({ "name": name, "about": about }))
+            .collect();
+        let plugins: Vec<serde_json::Value> = plugins
+            .iter()
+            .map(|(name, path)| serde_json::json!({ "name": name, "path": path }))
+            .collect();
+        println!(
+            "{}",
+            serde_json::json!({ "commands": commands, "plugins": plugins })
+        );
+        return;
+    }
+
+    let width = builtins
+        .iter()
+        .map(|(name, _)| name.len())
+        .chain(plugins.iter().map(|(name, _)| name.len()))
+        .max()
+        .unwrap_or(0);
+    println!("{}", "Built-in commands:".bold());
+    for (name, about) in &builtins {
+        println!("    {:width$}    {}", name, about, width = width);
+    }
+    println!();
+    println!("{}", "Plugins:".bold());
+    if plugins.is_empty() {
+        println!(
+            "    {}",
+            format!("none; put a {}<name> executable on PATH", plugins::PLUGIN_PREFIX).dimmed()
+        );
+    }
+    for (name, path) in &plugins {
+        println!(
+            "    {:width$}    {}",
+            name,
+            paths::display(path).dimmed(),
+            width = width
+        );
+    }
+}
+
+/// Output of a test run kept for finding its summary, enough for any
+/// framework's closing lines
+const TEST_OUTPUT_TAIL: usize = 64 * 1024;
+
+/// Print what the tests covered, with the HTML report too when `html` is
+/// set; an error when the total is below `coverage-min`
+fn report_coverage(ctx: &ProjectContext, html: bool) -> Result<(), PpmError> {
+    let python = ctx.venv_python();
+    let output = Command::new(&python)
+        .args(coverage::json_args())
+        .current_dir(&ctx.root)
+        .output()
+        .map_err(|e| PpmError::Subprocess(format!("Failed to run coverage: {}", e)))?;
+    if !output.status.success() {
+        return Err(PpmError::Subprocess(format!(
+            "coverage json failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    let report = coverage::parse_report(&String::from_utf8_lossy(&output.stdout))
+        .map_err(|e| PpmError::Other(format!("Could not read the coverage report: {}", e)))?;
+
+    let minimum = ctx.config.project.coverage_min;
+    let mut table = Table::new(&["File", "Stmts", "Miss", "Cover"]);
+    for file in &report.files {
+        let summary = &file.summary;
+        let style = if coverage::below_minimum(summary.percent_covered, minimum) {
+            Style::Warn
+        } else {
+            Style::Plain
+        };
+        table.add_row(vec![
+            Cell::plain(file.path.clone()),
+            Cell::plain(summary.num_statements.to_string()),
+            Cell::plain(summary.missing_lines.to_string()),
+            Cell::new(coverage::format_percent(summary.percent_covered), style),
+        ]);
+    }
+    print_human("");
+    print_human(table.render().trim_end());
+
+    let total = coverage::format_percent(report.totals.percent_covered);
+    let below = coverage::below_minimum(report.totals.percent_covered, minimum);
+    let total = if below {
+        total.bright_red().bold()
+    } else {
+        total.bright_green().bold()
+    };
+    print_human(format!("{} {}", "Total coverage:".bold(), total));
+
+    if html {
+        let written = Command::new(&python)
+            .args(coverage::html_args())
+            .current_dir(&ctx.root)
+            .status()
+            .is_ok_and(|status| status.success());
+        if written {
+            let index = ctx.root.join(coverage::HTML_DIR).join("index.html");
+            iprint(format!(
+                "HTML coverage report written to '{}'",
+                paths::display(&index)
+            ));
+        } else {
+            wprint("Could not write the HTML coverage report".to_string());
+        }
+    }
+
+    match minimum {
+        Some(minimum) if below => Err(PpmError::Other(format!(
+            "Coverage {} is below coverage-min {}",
+            coverage::format_percent(report.totals.percent_covered),
+            coverage::format_percent(minimum)
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// `ppm test`: the `test` script when project.toml defines one, otherwise
+/// the detected framework in the venv, with `args` passed on. Ends with a
+/// one-line verdict from the run's summary.
+pub fn run_tests(
+    prompter: &dyn Prompter,
+    args: &[String],
+    with_coverage: bool,
+    coverage_html: bool,
+) -> Result<(), PpmError> {
+    let ctx = load_project()?;
+
+    let mut cmd = if let Some(script) = ctx.config.scripts.get("test") {
+        if with_coverage {
+            return Err(PpmError::Config(
+                "--coverage runs pytest or unittest under coverage and can't wrap the `test` script in [scripts]"
+                    .to_string(),
+            ));
+        }
+        let command = script.local_command("test")?;
+        let script = if args.is_empty() {
+            command.to_string()
+        } else {
+            format!("{} {}", command, test_runner::quote_args(args))
+        };
+        shell_command(&ctx, &script)?
+    } else {
+        if !ensure_venv(&ctx, prompter)? {
+            wprint("Test Cancelled".to_owned());
+            return Ok(());
+        }
+        let venv_dir = ctx.venv_dir();
+        let Some(framework) = test_runner::detect(&ctx.root, &venv_dir) else {
+            wprint("Add pytest with `ppm add pytest`, or a `test` entry to [scripts]".to_string());
+            return Err(PpmError::Other("No test framework detected".to_string()));
+        };
+        if framework == Framework::Pytest
+            && verify::inspect_installed(&venv_dir, "pytest").is_none()
+        {
+            return Err(PpmError::Other(
+                "The project configures pytest but it isn't installed in the venv; run `ppm add pytest`"
+                    .to_string(),
+            ));
+        }
+        let mut extra = args.to_vec();
+        // The output goes through a pipe, which pytest would print plain
+        if framework == Framework::Pytest
+            && std::io::stdout().is_terminal()
+            && !json_output()
+            && !args.iter().any(|arg| arg.starts_with("--color"))
+        {
+            extra.insert(0, "--color=yes".to_string());
+        }
+        let framework_args = if with_coverage {
+            ensure_venv_package(&ctx, coverage::COVERAGE_PACKAGE, prompter)?;
+            coverage::run_args(framework, &extra, &venv_dir)
+        } else {
+            framework.args(&extra)
+        };
+        iprint(format!("Running tests with {}", framework));
+        let mut cmd = Command::new(ctx.venv_python());
+        cmd.args(framework_args).current_dir(&ctx.root);
+        cmd
+    };
+
+    Event::new(Phase::Script, Status::Start).script("test").emit();
+    let (status, output) = match run_teed(&mut cmd) {
+        Ok(result) => result,
+        Err(e) => {
+            Event::new(Phase::Script, Status::Fail)
+                .script("test")
+                .message(&e.to_string())
+                .emit();
+            return Err(PpmError::Subprocess(format!("Failed to run tests: {}", e)));
+        }
+    };
+    Event::new(Phase::Script, Status::Finish)
+        .script("test")
+        .exit_code(status.code())
+        .emit();
+
+    if let Some(summary) = test_runner::parse_summary(&output) {
+        let verdict = if summary.success() && status.success() {
+            "Tests passed:".bright_green().bold()
+        } else {
+            "Tests failed:".bright_red().bold()
+        };
+        print_human(format!("{} {}", verdict, summary));
+    }
+    // Coverage is reported for failing runs too, but their exit status wins
+    let coverage = if with_coverage {
+        report_coverage(&ctx, coverage_html)
+    } else {
+        Ok(())
+    };
+    if !status.success() {
+        return Err(PpmError::ChildExit {
+            name: "test".to_string(),
+            code: status.code(),
+        });
+    }
+    coverage
+}
+
+/// `ppm fmt` and `ppm lint`: the script of the same name in [scripts],
+/// or ruff (black, flake8) over the project's sources
+pub fn run_preset(task: Task, args: &[String], prompter: &dyn Prompter) -> Result<(), PpmError> {
+    let ctx = load_project()?;
+    let name = task.to_string();
+
+    let mut cmd = if let Some(script) = ctx.config.scripts.get(&name) {
+        let command = script.local_command(&name)?;
+        let script = if args.is_empty() {
+            command.to_string()
+        } else {
+            format!("{} {}", command, test_runner::quote_args(args))
+        };
+        shell_command(&ctx, &script)?
+    } else {
+        if !ensure_venv(&ctx, prompter)? {
+            wprint(match task {
+                Task::Fmt => "Format Cancelled".to_owned(),
+                Task::Lint => "Lint Cancelled".to_owned(),
+            });
+            return Ok(());
+        }
+        let venv_dir = ctx.venv_dir();
+        let tool = match presets::pick_tool(task, |package| {
+            verify::inspect_installed(&venv_dir, package).is_some()
+        }) {
+            Some(tool) => tool,
+            None => {
+                ensure_venv_package(&ctx, Tool::Ruff.package(), prompter)?;
+                Tool::Ruff
+            }
+        };
+        let paths = presets::source_paths(&ctx.root, &venv_dir);
+        if paths.is_empty() {
+            wprint(format!("No Python sources to {}", name));
+            return Ok(());
+        }
+        iprint(format!("Running {} with {}", name, tool));
+        let mut cmd = Command::new(ctx.venv_python());
+        cmd.args(tool.args(task, &paths, args))
+            .current_dir(&ctx.root);
+        cmd
+    };
+
+    Event::new(Phase::Script, Status::Start)
+        .script(&name)
+        .emit();
+    let status = match cmd.status() {
+        Ok(status) => status,
+        Err(e) => {
+            Event::new(Phase::Script, Status::Fail)
+                .script(&name)
+                .message(&e.to_string())
+                .emit();
+            return Err(PpmError::Subprocess(format!(
+                "Failed to run {}: {}",
+                name, e
+            )));
+        }
+    };
+    Event::new(Phase::Script, Status::Finish)
+        .script(&name)
+        .exit_code(status.code())
+        .emit();
+    if !status.success() {
+        return Err(PpmError::ChildExit {
+            name,
+            code: status.code(),
+        });
+    }
+    Ok(())
+}
+
+/// Run `cmd`, copying its stdout and stderr through as they arrive while
+/// keeping the last [`TEST_OUTPUT_TAIL`] bytes of both
+fn run_teed(cmd: &mut Command) -> std::io::Result<(std::process::ExitStatus, String)> {
+    use std::io::{Read, Write};
+    use std::sync::{Arc, Mutex};
+
+    fn copy(mut from: impl Read, mut to: impl Write, tail: &Mutex<Vec<u8>>) -> std::io::Result<()> {
+        let mut buf = [0u8; 8192];
+        loop {
+            let n = from.read(&mut buf)?;
+            if n == 0 {
+                return Ok(());
+            }
+            to.write_all(&buf[..n])?;
+            to.flush()?;
+            let mut tail = tail.lock().unwrap_or_else(|e| e.into_inner());
+            tail.extend_from_slice(&buf[..n]);
+            let excess = tail.len().saturating_sub(TEST_OUTPUT_TAIL);
+            tail.drain(..excess);
+        }
+    }
+
+    let mut child = cmd
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()?;
+    let tail = Arc::new(Mutex::new(Vec::new()));
+    let stderr = child.stderr.take();
+    let stderr_tail = Arc::clone(&tail);
+    let stderr_thread = std::thread::spawn(move || match stderr {
+        Some(stderr) => copy(stderr, std::io::stderr(), &stderr_tail),
+        None => Ok(()),
+    });
+    if let Some(stdout) = child.stdout.take() {
+        // In JSON mode stdout is reserved for ppm's own output
+        if json_output() {
+            copy(stdout, std::io::stderr(), &tail)?;
+        } else {
+            copy(stdout, std::io::stdout(), &tail)?;
+        }
+    }
+    let _ = stderr_thread.join();
+    let status = child.wait()?;
+    let output =
+        String::from_utf8_lossy(&tail.lock().unwrap_or_else(|e| e.into_inner())).into_owned();
+    Ok((status, output))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use httpmock::prelude::*;
+    use ppmm::prompt::ScriptedPrompter;
+    use ppmm::runner::{RunOutput, ScriptedRunner};
+    use ppmm::update::UpdateMode;
+    use std::sync::Arc;
+
+    /// Project in a temp dir whose venv does not exist
+    fn project_without_venv(venv: &str) -> (tempfile::TempDir, ProjectContext) {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("project.toml"),
+            format!(
+                r#"
+[project]
+name = "demo"
+version = "0.1.0"
+description = ""
+main_script = "./main.py"
+venv = "{}"
+
+[packages]
+requests = "2.30.0"
+
+[scripts]
+"#,
+                venv
+            ),
+        )
+        .unwrap();
+        let ctx = ProjectContext::load(dir.path()).unwrap();
+        (dir, ctx)
+    }
+
+    /// Swap the context's runner for a scripted one, returned for setting
+    /// up answers and checking calls
+    fn scripted_runner(ctx: &mut ProjectContext) -> Arc<ScriptedRunner> {
+        let runner = Arc::new(ScriptedRunner::new());
+        ctx.runner = runner.clone();
+        runner
+    }
+
+    /// A package index on localhost answering every request with `body`,
+    /// served under `index.url("/pypi")`
+    fn serve_index(body: &'static str) -> MockServer {
+        let index = MockServer::start();
+        index.mock(|when, then| {
+            when.method(GET);
+            then.status(200).body(body);
+        });
+        index
+    }
+
+    #[test]
+    fn test_main_script_preflight() {
+        let (dir, mut ctx) = project_without_venv("venv");
+
+        ctx.config.project.main_script = "  ".to_string();
+        let err = main_script_to_run(&ctx).unwrap_err();
+        assert!(err.to_string().contains("set main_script in project.toml"));
+
+        std::fs::create_dir(dir.path().join("app")).unwrap();
+        ctx.config.project.main_script = "app".to_string();
+        let err = main_script_to_run(&ctx).unwrap_err();
+        assert!(err.to_string().contains("is a directory"));
+
+        ctx.config.project.main_script = "missing.py".to_string();
+        let err = main_script_to_run(&ctx).unwrap_err();
+        assert!(err.to_string().contains("'missing.py' not found"));
+
+        std::fs::write(dir.path().join("run"), "#!/usr/bin/env python\n").unwrap();
+        ctx.config.project.main_script = "run".to_string();
+        assert_eq!(main_script_to_run(&ctx).unwrap(), dir.path().join("run"));
+    }
+
+    #[test]
+    fn test_missing_main_script_is_filled_in() {
+        let (dir, mut ctx) = project_without_venv("venv");
+        let none = ScriptedPrompter::new(&[]);
+        fill_missing_main_script(&mut ctx, &none).unwrap();
+        assert_eq!(ctx.config.project.main_script, "./main.py");
+
+        std::fs::write(dir.path().join("app.py"), "").unwrap();
+        fill_missing_main_script(&mut ctx, &none).unwrap();
+        assert_eq!(ctx.config.project.main_script, "./app.py");
+        let saved = ProjectContext::load(dir.path()).unwrap();
+        assert_eq!(saved.config.project.main_script, "./app.py");
+
+        // Several candidates need an answer
+        std::fs::remove_file(dir.path().join("app.py")).unwrap();
+        std::fs::create_dir_all(dir.path().join("src/demo")).unwrap();
+        std::fs::write(dir.path().join("src/demo/__main__.py"), "").unwrap();
+        std::fs::write(dir.path().join("src/main.py"), "").unwrap();
+        assert!(fill_missing_main_script(&mut ctx, &none).is_err());
+        let prompter = ScriptedPrompter::new(&["2"]);
+        fill_missing_main_script(&mut ctx, &prompter).unwrap();
+        assert_eq!(ctx.config.project.main_script, "./src/demo/__main__.py");
+    }
+
+    #[test]
+    fn test_update_declining_venv_cancels() {
+        let (dir, mut ctx) = project_without_venv("ppmm-test-missing-venv");
+        let before = std::fs::read_to_string(dir.path().join("project.toml")).unwrap();
+        let prompter = ScriptedPrompter::new(&["n"]);
+
+        update_project(&mut ctx, &[], UpdateOptions::default(), &prompter).unwrap();
+
+        assert!(prompter.transcript().contains("create a virtual environment"));
+        let after = std::fs::read_to_string(dir.path().join("project.toml")).unwrap();
+        assert_eq!(before, after);
+    }
+
+    #[test]
+    fn test_update_eof_at_venv_prompt_cancels() {
+        let (_dir, mut ctx) = project_without_venv("ppmm-test-missing-venv");
+        let prompter = ScriptedPrompter::new(&[]);
+        assert!(update_project(&mut ctx, &[], UpdateOptions::default(), &prompter).is_ok());
+    }
+
+    #[test]
+    fn test_update_declining_confirmation_changes_nothing() {
+        // The index says requests 2.31.0 is out
+        let (dir, mut ctx) = project_without_venv("venv");
+        let index = serve_index(r#"{"info": {"version": "2.31.0"}}"#);
+        ctx.index_url = index.url("/pypi");
+        let runner = scripted_runner(&mut ctx);
+        std::fs::create_dir_all(ctx.venv_bin_dir()).unwrap();
+        let before = std::fs::read_to_string(dir.path().join("project.toml")).unwrap();
+        let prompter = ScriptedPrompter::new(&["n"]);
+
+        update_project(&mut ctx, &[], UpdateOptions::default(), &prompter).unwrap();
+
+        assert!(prompter.transcript().contains("Apply these 1 updates?"));
+        let after = std::fs::read_to_string(dir.path().join("project.toml")).unwrap();
+        assert_eq!(before, after);
+        assert_eq!(ctx.config.packages["requests"].version(), Some("2.30.0"));
+        let venv: Vec<_> = std::fs::read_dir(ctx.venv_dir()).unwrap().collect();
+        assert_eq!(venv.len(), 1);
+        assert!(runner.calls().is_empty());
+    }
+
+    #[test]
+    fn test_pip_cache_purge_asks_first() {
+        let (dir, mut ctx) = project_without_venv("venv");
+        let runner = scripted_runner(&mut ctx);
+        let cache = dir.path().join("pip-cache");
+        runner
+            .respond(
+                "pip cache dir",
+                RunOutput::ok(&format!("{}\n", cache.display())),
+            )
+            .respond(
+                "pip cache info",
+                RunOutput::ok(include_str!("../tests/fixtures/pip-cache/info-24.0.txt")),
+            )
+            .respond("pip cache purge", RunOutput::ok("Files removed: 8458\n"));
+        std::fs::create_dir_all(ctx.venv_bin_dir()).unwrap();
+
+        let prompter = ScriptedPrompter::new(&["n"]);
+        purge_pip_cache(&ctx, false, &prompter).unwrap();
+        assert!(
+            prompter
+                .transcript()
+                .contains("Remove 2.0 GiB of cached downloads")
+        );
+        assert_eq!(
+            runner.command_lines(),
+            vec!["pip cache dir", "pip cache info"]
+        );
+
+        let prompter = ScriptedPrompter::new(&["y"]);
+        purge_pip_cache(&ctx, false, &prompter).unwrap();
+        assert!(
+            runner
+                .command_lines()
+                .contains(&"pip cache purge".to_string())
+        );
+
+        // pip before 20.1 has no cache command
+        let runner = scripted_runner(&mut ctx);
+        runner.respond(
+            "pip cache",
+            RunOutput::failed(
+                1,
+                include_str!("../tests/fixtures/pip-cache/unknown-command.txt"),
+            ),
+        );
+        let err = pip_cache::purge(&ctx).unwrap_err();
+        assert!(matches!(err, PpmError::Venv(_)), "{}", err);
+    }
+
+    #[test]
+    fn test_update_installs_and_records_the_new_version() {
+        let (dir, mut ctx) = project_without_venv("venv");
+        let index = serve_index(r#"{"info": {"version": "2.31.0"}}"#);
+        ctx.index_url = index.url("/pypi");
+        let runner = scripted_runner(&mut ctx);
+        runner
+            .respond(
+                "pip list",
+                RunOutput::ok(r#"[{"name": "requests", "version": "2.30.0"}]"#),
+            )
+            .respond(
+                "pip install",
+                RunOutput::ok("Successfully installed requests-2.31.0"),
+            )
+            .respond("pip freeze", RunOutput::ok("requests==2.31.0\n"));
+        std::fs::create_dir_all(ctx.venv_bin_dir()).unwrap();
+        let prompter = ScriptedPrompter::new(&["y"]);
+
+        update_project(&mut ctx, &[], UpdateOptions::default(), &prompter).unwrap();
+
+        assert_eq!(
+            runner.command_lines(),
+            vec![
+                "pip list --format=json",
+                "pip install requests==2.31.0",
+                "pip freeze"
+            ]
+        );
+        assert_eq!(runner.calls()[1].cwd.as_deref(), Some(dir.path()));
+        let saved = ProjectContext::load(dir.path()).unwrap();
+        assert_eq!(saved.config.packages["requests"].version(), Some("2.31.0"));
+        let lock = std::fs::read_to_string(dir.path().join(get_lock_file())).unwrap();
+        assert_eq!(lock, "requests==2.31.0\n");
+    }
+
+    #[test]
+    fn test_update_force_reinstall_passes_the_flag_to_pip() {
+        // requests is already at the newest version
+        let (_dir, mut ctx) = project_without_venv("venv");
+        let index = serve_index(r#"{"info": {"version": "2.30.0"}}"#);
+        ctx.index_url = index.url("/pypi");
+        let runner = scripted_runner(&mut ctx);
+        runner
+            .respond(
+                "pip install",
+                RunOutput::ok("Successfully installed requests-2.30.0"),
+            )
+            .respond("pip freeze", RunOutput::ok("requests==2.30.0\n"));
+        std::fs::create_dir_all(ctx.venv_bin_dir()).unwrap();
+        let options = UpdateOptions {
+            force_reinstall: true,
+            ..UpdateOptions::default()
+        };
+
+        update_project(&mut ctx, &[], options, &ScriptedPrompter::new(&[])).unwrap();
+
+        assert_eq!(
+            runner.command_lines(),
+            vec![
+                "pip install --force-reinstall requests==2.30.0",
+                "pip freeze"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_update_failing_pip_keeps_the_old_version() {
+        let (dir, mut ctx) = project_without_venv("venv");
+        let index = serve_index(r#"{"info": {"version": "2.31.0"}}"#);
+        ctx.index_url = index.url("/pypi");
+        let runner = scripted_runner(&mut ctx);
+        runner.respond("pip list", RunOutput::ok("[]")).respond(
+            "pip install",
+            RunOutput::failed(
+                1,
+                "ERROR: No matching distribution found for requests==2.31.0",
+            ),
+        );
+        std::fs::create_dir_all(ctx.venv_bin_dir()).unwrap();
+        let options = UpdateOptions {
+            yes: true,
+            ..UpdateOptions::default()
+        };
+        let none = ScriptedPrompter::new(&[]);
+
+        let err = update_project(&mut ctx, &[], options, &none).unwrap_err();
+        assert!(err.to_string().contains("No matching distribution"));
+        assert!(!runner.command_lines().contains(&"pip freeze".to_string()));
+        let saved = ProjectContext::load(dir.path()).unwrap();
+        assert_eq!(saved.config.packages["requests"].version(), Some("2.30.0"));
+    }
+
+    /// Project with a main script and a venv whose python is a stand-in
+    fn project_with_venv() -> (tempfile::TempDir, ProjectContext, Arc<ScriptedRunner>) {
+        let (dir, mut ctx) = project_without_venv("venv");
+        std::fs::write(dir.path().join("main.py"), "print('hi')\n").unwrap();
+        std::fs::create_dir_all(ctx.venv_bin_dir()).unwrap();
+        std::fs::write(ctx.venv_python(), "").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let executable = std::fs::Permissions::from_mode(0o755);
+            std::fs::set_permissions(ctx.venv_python(), executable).unwrap();
+        }
+        let runner = scripted_runner(&mut ctx);
+        (dir, ctx, runner)
+    }
+
+    #[test]
+    fn test_start_runs_main_script_with_venv_python() {
+        let (dir, mut ctx, runner) = project_with_venv();
+        runner.respond("python", RunOutput::ok(""));
+        let none = ScriptedPrompter::new(&[]);
+
+        start(
+            &mut ctx,
+            Launch::Direct,
+            &["--port".to_string(), "80".to_string()],
+            &none,
+        )
+        .unwrap();
+
+        let calls = runner.calls();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].program, ctx.venv_python());
+        assert_eq!(calls[0].args[0], ctx.main_script_path().into_os_string());
+        assert_eq!(calls[0].args[1..], ["--port", "80"]);
+        assert_eq!(calls[0].cwd.as_deref(), Some(dir.path()));
+    }
+
+    #[test]
+    fn test_start_reports_the_script_exit_code() {
+        let (_dir, mut ctx, runner) = project_with_venv();
+        runner.respond("python", RunOutput::failed(3, ""));
+        let none = ScriptedPrompter::new(&[]);
+
+        match start(&mut ctx, Launch::Direct, &[], &none).unwrap_err() {
+            PpmError::ChildExit { name, code } => {
+                assert_eq!(name, "./main.py");
+                assert_eq!(code, Some(3));
+            }
+            other => panic!("expected ChildExit, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_start_offers_to_create_the_venv() {
+        let (dir, mut ctx) = project_without_venv("venv");
+        std::fs::write(dir.path().join("main.py"), "").unwrap();
+        let runner = scripted_runner(&mut ctx);
+        runner.respond(
+            "python -m venv",
+            RunOutput::failed(1, "No module named venv"),
+        );
+        let prompter = ScriptedPrompter::new(&["y"]);
+
+        let err = start(&mut ctx, Launch::Direct, &[], &prompter).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            PpmError::Venv("Virtual environment creation failed: No module named venv".to_string())
+                .to_string()
+        );
+        let venv = ctx.venv_dir().into_os_string();
+        assert_eq!(
+            runner.calls()[0].args,
+            [OsString::from("-m"), "venv".into(), venv]
+        );
+    }
+
+    #[test]
+    fn test_start_in_system_mode_skips_the_venv() {
+        let (dir, mut ctx) = project_without_venv("venv");
+        std::fs::write(dir.path().join("main.py"), "").unwrap();
+        ctx.system_python = true;
+        ctx.shared.python = Some("python3.12".to_string());
+        let runner = scripted_runner(&mut ctx);
+        runner.respond("python3", RunOutput::ok(""));
+        let none = ScriptedPrompter::new(&[]);
+
+        start(&mut ctx, Launch::Direct, &["-v".to_string()], &none).unwrap();
+
+        let calls = runner.calls();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].program, Path::new("python3.12"));
+        assert_eq!(
+            calls[0].args,
+            [ctx.main_script_path().into_os_string(), "-v".into()]
+        );
+        assert_eq!(none.transcript(), "");
+        assert!(!ctx.venv_dir().exists());
+    }
+
+    #[test]
+    fn test_resume_asks_before_installing_the_pending_plan() {
+        let (dir, mut ctx) = project_without_venv("venv");
+        let options = UpdateOptions {
+            resume: true,
+            ..UpdateOptions::default()
+        };
+        let none = ScriptedPrompter::new(&[]);
+        let err = resume_update(&mut ctx, options, &none).unwrap_err();
+        assert!(err.to_string().starts_with("No interrupted update to resume"));
+
+        let pending = PendingUpdate {
+            created_at: resume::now(),
+            packages: vec![resume::PendingPackage {
+                name: "requests".to_string(),
+                from: "2.30.0".to_string(),
+                to: "2.32.3".to_string(),
+            }],
+            mode: UpdateMode::Policy,
+            rewrites: vec![],
+        };
+        pending.store(&ctx.state()).unwrap();
+        let declined = ScriptedPrompter::new(&["n"]);
+        resume_update(&mut ctx, options, &declined).unwrap();
+        assert!(
+            declined
+                .transcript()
+                .contains("Resume the interrupted update of 1 package(s)?")
+        );
+        assert_eq!(PendingUpdate::load(&ctx.state()).unwrap(), Some(pending));
+        let config = std::fs::read_to_string(dir.path().join("project.toml")).unwrap();
+        assert!(config.contains("requests = \"2.30.0\""));
+
+        // Edited since the update stopped: nothing is left to resume
+        ctx.config.packages.insert("requests".to_string(), "2.31.0".into());
+        resume_update(&mut ctx, options, &none).unwrap();
+        assert_eq!(PendingUpdate::load(&ctx.state()).unwrap(), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_missing_script_requirements_are_offered() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("project.toml"),
+            include_str!("../tests/fixtures/scripts-project.toml"),
+        )
+        .unwrap();
+        let site_packages = dir.path().join("venv/lib/python3.12/site-packages");
+        std::fs::create_dir_all(site_packages.join("mkdocs-1.6.0.dist-info")).unwrap();
+        // pip "installs" by adding a dist-info for each argument
+        let bin = dir.path().join("venv/bin");
+        std::fs::create_dir_all(&bin).unwrap();
+        let pip = bin.join("pip");
+        std::fs::write(
+            &pip,
+            format!(
+                "#!/bin/sh
+shift
+for req in \"$@\"; do mkdir -p '{}'/mkdocs_material-9.5.0.dist-info; done
+",
+                site_packages.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&pip, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let ctx = ProjectContext::load(dir.path()).unwrap();
+        let docs = scripts::select(&ctx.config, &scripts::Target::Name("docs".to_string()))
+            .unwrap()
+            .remove(0);
+
+        let declined = ScriptedPrompter::new(&["n"]);
+        let err = run_script(&ctx, &docs, &declined).unwrap_err();
+        assert_eq!(err.to_string(), "Not running 'docs' without mkdocs-material>=9");
+        assert!(
+            declined
+                .transcript()
+                .contains("Script 'docs' needs mkdocs-material>=9, which the venv doesn't have")
+        );
+        assert!(!site_packages.join("mkdocs_material-9.5.0.dist-info").exists());
+
+        let accepted = ScriptedPrompter::new(&["y"]);
+        run_script(&ctx, &docs, &accepted).unwrap();
+        assert!(site_packages.join("mkdocs_material-9.5.0.dist-info").exists());
+        // Now nothing is missing, so nothing is asked
+        let none = ScriptedPrompter::new(&[]);
+        run_script(&ctx, &docs, &none).unwrap();
+        assert_eq!(none.transcript(), "");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_in_system_mode_leaves_the_environment_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("project.toml"),
+            include_str!("../tests/fixtures/scripts-project.toml"),
+        )
+        .unwrap();
+        let mut ctx = ProjectContext::load(dir.path()).unwrap();
+        let docs = scripts::select(&ctx.config, &scripts::Target::Name("docs".to_string()))
+            .unwrap()
+            .remove(0);
+        let env_names = |ctx: &ProjectContext| -> Vec<String> {
+            let cmd = script_command(ctx, &docs).unwrap();
+            cmd.get_envs()
+                .map(|(name, _)| name.to_string_lossy().into_owned())
+                .collect()
+        };
+        assert!(env_names(&ctx).contains(&"VIRTUAL_ENV".to_string()));
+
+        ctx.system_python = true;
+        assert!(env_names(&ctx).is_empty());
+        // docs requires packages, but there is no venv to look in or
+        // install them into
+        let none = ScriptedPrompter::new(&[]);
+        run_script(&ctx, &docs, &none).unwrap();
+        assert_eq!(none.transcript(), "");
+    }
+
+    #[test]
+    fn test_update_accepting_venv_creates_it() {
+        // The venv's parent is a regular file so creation must fail; an
+        // error from setup_venv proves the accepted branch ran
+        let (dir, mut ctx) = project_without_venv("blocker/venv");
+        std::fs::write(dir.path().join("blocker"), "").unwrap();
+        let prompter = ScriptedPrompter::new(&["garbage", "yes"]);
+
+        let err = update_project(&mut ctx, &[], UpdateOptions::default(), &prompter).unwrap_err();
+        assert!(matches!(err, PpmError::Venv(_)));
+        assert!(prompter.transcript().contains("Invalid option"));
+    }
+}
+
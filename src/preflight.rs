@@ -0,0 +1,650 @@
+//! `ppm check`: cheap validations chained for a pre-commit hook.
+//!
+//! Each entry of [`CHECKS`] is a named function from the [`Preflight`]
+//! state to an [`Outcome`]. They run in order and only read files, except
+//! the checks that need `--online`: yanked pins come from the cached
+//! release histories unless it is given, and the pip resolution (the
+//! slowest) only runs with it.
+
+use crate::context::ProjectContext;
+use crate::error::PpmError;
+use crate::packages::{compare_versions, normalize_pkg_name};
+use crate::paths;
+use crate::requirement::Requirement;
+use crate::requirements::{self, RenderOptions, diff_requirements, generate_requirements};
+use crate::resolver::{self, LOCK_FILE};
+use crate::settings::{Config, PROJECT_CONFIG_FILE, PackageSpec};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What the checks look at
+pub struct Preflight {
+    /// Directory holding project.toml
+    pub root: PathBuf,
+    /// The loaded project; `None` when project.toml doesn't load, which
+    /// skips the checks that need it
+    pub project: Option<ProjectContext>,
+    /// Allow network access
+    pub online: bool,
+    /// Fail on yanked pins instead of warning
+    pub deny_yanked: bool,
+}
+
+impl Preflight {
+    /// The checks' view of the project at `root`. Without `online` the
+    /// project is switched to [`offline`](ProjectContext::offline).
+    pub fn new(
+        root: PathBuf,
+        project: Option<ProjectContext>,
+        online: bool,
+        deny_yanked: bool,
+    ) -> Preflight {
+        let project = project.map(|mut ctx| {
+            ctx.offline |= !online;
+            ctx
+        });
+        Preflight {
+            root,
+            project,
+            online,
+            deny_yanked,
+        }
+    }
+}
+
+/// Result of one check
+#[derive(Debug)]
+pub enum Outcome {
+    /// Nothing wrong
+    Pass(String),
+    /// Worth a look, but not a failure
+    Warn(String),
+    /// Not run
+    Skip(String),
+    /// Failed; the error decides the exit code
+    Fail(PpmError),
+}
+
+impl Outcome {
+    /// `pass`, `warn`, `skip` or `fail`
+    pub fn status(&self) -> &'static str {
+        match self {
+            Outcome::Pass(_) => "pass",
+            Outcome::Warn(_) => "warn",
+            Outcome::Skip(_) => "skip",
+            Outcome::Fail(_) => "fail",
+        }
+    }
+
+    /// What was found, in one line where possible
+    pub fn detail(&self) -> String {
+        match self {
+            Outcome::Pass(detail) | Outcome::Warn(detail) | Outcome::Skip(detail) => detail.clone(),
+            Outcome::Fail(e) => e.to_string(),
+        }
+    }
+}
+
+/// A named check
+pub struct Check {
+    /// Name shown in the output and accepted by `--skip`
+    pub name: &'static str,
+    /// The check itself
+    pub run: fn(&Preflight) -> Outcome,
+}
+
+/// Every check `ppm check` runs, in order
+pub const CHECKS: &[Check] = &[
+    Check {
+        name: "config",
+        run: check_config,
+    },
+    Check {
+        name: "duplicates",
+        run: check_duplicates,
+    },
+    Check {
+        name: "lock",
+        run: check_lock,
+    },
+    Check {
+        name: "requirements",
+        run: check_requirements,
+    },
+    Check {
+        name: "venv",
+        run: check_venv,
+    },
+    Check {
+        name: "yanked",
+        run: check_yanked,
+    },
+    Check {
+        name: "resolve",
+        run: check_resolve,
+    },
+];
+
+/// A check's name with its outcome
+#[derive(Debug)]
+pub struct CheckResult {
+    /// Name of the check
+    pub name: &'static str,
+    /// What it found
+    pub outcome: Outcome,
+}
+
+impl CheckResult {
+    /// `{"name", "status", "detail"}` for JSON output
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut result = serde_json::json!({
+            "name": self.name,
+            "status": self.outcome.status(),
+            "detail": self.outcome.detail(),
+        });
+        if let Outcome::Fail(e) = &self.outcome {
+            result["exit_code"] = e.exit_code().into();
+        }
+        result
+    }
+}
+
+/// Run every check but those named in `skip`; an unknown name there is an
+/// error
+pub fn run(preflight: &Preflight, skip: &[String]) -> Result<Vec<CheckResult>, PpmError> {
+    if let Some(unknown) = skip
+        .iter()
+        .find(|name| !CHECKS.iter().any(|check| check.name == name.as_str()))
+    {
+        let names: Vec<&str> = CHECKS.iter().map(|check| check.name).collect();
+        return Err(PpmError::Other(format!(
+            "No check named '{}' to skip; the checks are {}",
+            unknown,
+            names.join(", ")
+        )));
+    }
+    Ok(CHECKS
+        .iter()
+        .map(|check| CheckResult {
+            name: check.name,
+            outcome: if skip.iter().any(|name| name == check.name) {
+                Outcome::Skip("skipped with --skip".to_string())
+            } else {
+                (check.run)(preflight)
+            },
+        })
+        .collect())
+}
+
+/// `Ok` unless a check failed; then [`PpmError::ChecksFailed`] with the
+/// exit code of the first failure
+pub fn verdict(results: &[CheckResult]) -> Result<(), PpmError> {
+    let failed: Vec<&CheckResult> = results
+        .iter()
+        .filter(|result| matches!(result.outcome, Outcome::Fail(_)))
+        .collect();
+    match failed.first() {
+        None => Ok(()),
+        Some(first) => Err(PpmError::ChecksFailed {
+            failed: failed
+                .iter()
+                .map(|result| result.name.to_string())
+                .collect(),
+            total: results.len(),
+            code: match &first.outcome {
+                Outcome::Fail(e) => e.exit_code(),
+                _ => unreachable!(),
+            },
+        }),
+    }
+}
+
+fn load_config(preflight: &Preflight) -> Result<Config, PpmError> {
+    Config::load_from_file(&preflight.root.join(PROJECT_CONFIG_FILE).to_string_lossy())
+}
+
+fn project(preflight: &Preflight) -> Result<&ProjectContext, Outcome> {
+    preflight
+        .project
+        .as_ref()
+        .ok_or_else(|| Outcome::Skip(format!("needs a {} that loads", PROJECT_CONFIG_FILE)))
+}
+
+/// project.toml parses, its markers and scripts are valid and its paths
+/// stay in the project
+pub fn check_config(preflight: &Preflight) -> Outcome {
+    let config = match load_config(preflight) {
+        Ok(config) => config,
+        Err(e) => return Outcome::Fail(e),
+    };
+    if let Err(e) = config.check_markers().and_then(|()| config.check_scripts()) {
+        return Outcome::Fail(e);
+    }
+    match config.check_paths(&preflight.root) {
+        Ok(warnings) if warnings.is_empty() => {
+            Outcome::Pass(format!("{} is valid", PROJECT_CONFIG_FILE))
+        }
+        Ok(warnings) => Outcome::Warn(warnings.join("; ")),
+        Err(e) => Outcome::Fail(e),
+    }
+}
+
+/// No package is listed twice under different spellings
+pub fn check_duplicates(preflight: &Preflight) -> Outcome {
+    let Ok(config) = load_config(preflight) else {
+        return Outcome::Skip(format!("needs a {} that parses", PROJECT_CONFIG_FILE));
+    };
+    match config.check_duplicates() {
+        Ok(()) => Outcome::Pass(format!(
+            "{} packages, each listed once",
+            config.packages.len()
+        )),
+        Err(e) => Outcome::Fail(e),
+    }
+}
+
+/// ppmm.lock, when there is one, pins every configured package at its
+/// configured version
+pub fn check_lock(preflight: &Preflight) -> Outcome {
+    let ctx = match project(preflight) {
+        Ok(ctx) => ctx,
+        Err(outcome) => return outcome,
+    };
+    let Ok(lock) = fs::read_to_string(ctx.root.join(LOCK_FILE)) else {
+        return Outcome::Skip(format!("no {}", LOCK_FILE));
+    };
+    let problems = lock_drift(&ctx.config, &lock);
+    if problems.is_empty() {
+        return Outcome::Pass(format!("{} matches {}", LOCK_FILE, PROJECT_CONFIG_FILE));
+    }
+    Outcome::Fail(PpmError::Other(format!(
+        "{} is out of date: {}; run `ppm lock`",
+        LOCK_FILE,
+        problems.join("; ")
+    )))
+}
+
+/// The requirements file is what `ppm gen` would write, when the project
+/// sets `sync-requirements`; for a file generated from ppmm.lock, what
+/// `ppm gen --from-lock` would
+pub fn check_requirements(preflight: &Preflight) -> Outcome {
+    let ctx = match project(preflight) {
+        Ok(ctx) => ctx,
+        Err(outcome) => return outcome,
+    };
+    if !ctx.config.project.sync_requirements {
+        return Outcome::Skip("sync-requirements is off".to_string());
+    }
+    let req_file = ctx.config.requirements_file();
+    let current = fs::read_to_string(ctx.root.join(req_file)).unwrap_or_default();
+    let from_lock = requirements::generated_from(&current) == Some(LOCK_FILE);
+    let (source, generated, command) = if from_lock {
+        let Ok(lock) = fs::read_to_string(ctx.root.join(LOCK_FILE)) else {
+            return Outcome::Fail(PpmError::Other(format!(
+                "{} was generated from {}, which is missing; run `ppm lock`",
+                req_file, LOCK_FILE
+            )));
+        };
+        let generated = match requirements::render_locked(&ctx.config, &lock, None, None) {
+            Ok(generated) => generated,
+            Err(e) => {
+                return Outcome::Fail(PpmError::Other(format!(
+                    "Could not read {}: {}",
+                    LOCK_FILE, e
+                )));
+            }
+        };
+        (LOCK_FILE, generated, "ppm gen --from-lock")
+    } else {
+        let generated = generate_requirements(&ctx.config, &RenderOptions::default());
+        (PROJECT_CONFIG_FILE, generated, "ppm gen")
+    };
+    let changes = diff_requirements(&current, &generated);
+    if changes.is_empty() {
+        return Outcome::Pass(format!("{} matches {}", req_file, source));
+    }
+    Outcome::Fail(PpmError::Other(format!(
+        "{} is out of date: {}; run `{}`",
+        req_file,
+        changes.join("; "),
+        command
+    )))
+}
+
+/// The venv exists and has every configured package that applies to it,
+/// index packages at their pinned versions
+pub fn check_venv(preflight: &Preflight) -> Outcome {
+    let ctx = match project(preflight) {
+        Ok(ctx) => ctx,
+        Err(outcome) => return outcome,
+    };
+    if ctx.system_python {
+        return Outcome::Skip("use-venv = false; scripts run on the system Python".to_string());
+    }
+    if !ctx.venv_bin_dir().exists() {
+        return Outcome::Fail(PpmError::Venv(format!(
+            "No virtual environment at {}; run `ppm install` to create it",
+            ctx.venv_root()
+        )));
+    }
+    let installed = installed_versions(&ctx.venv_dir());
+    // A marker that can't be evaluated doesn't count against the venv
+    let problems = venv_drift(&ctx.config, &installed, |spec| {
+        ctx.marker_matches(spec).unwrap_or(false)
+    });
+    if problems.is_empty() {
+        return Outcome::Pass(format!("{} has every configured package", ctx.venv_root()));
+    }
+    Outcome::Fail(PpmError::Other(format!(
+        "{} out of sync with {}: {}; run `ppm sync`",
+        ctx.venv_root(),
+        PROJECT_CONFIG_FILE,
+        problems.join("; ")
+    )))
+}
+
+/// No pin is on a release the index has yanked, going by the cached
+/// release histories unless `online`
+pub fn check_yanked(preflight: &Preflight) -> Outcome {
+    let ctx = match project(preflight) {
+        Ok(ctx) => ctx,
+        Err(outcome) => return outcome,
+    };
+    let yanked = match ctx.yanked_pins() {
+        Ok(yanked) => yanked,
+        Err(e) => return Outcome::Fail(e),
+    };
+    if yanked.is_empty() {
+        return Outcome::Pass("no pinned release is yanked".to_string());
+    }
+    let listed: Vec<String> = yanked.iter().map(|pin| pin.to_string()).collect();
+    if preflight.deny_yanked {
+        Outcome::Fail(PpmError::Other(listed.join("; ")))
+    } else {
+        Outcome::Warn(listed.join("; "))
+    }
+}
+
+/// pip resolves every configured package together; needs `online`
+pub fn check_resolve(preflight: &Preflight) -> Outcome {
+    if !preflight.online {
+        return Outcome::Skip("needs --online".to_string());
+    }
+    let ctx = match project(preflight) {
+        Ok(ctx) => ctx,
+        Err(outcome) => return outcome,
+    };
+    if !ctx.venv_bin_dir().exists() {
+        return Outcome::Skip("needs the venv's pip".to_string());
+    }
+    match resolver::resolve(ctx) {
+        Ok(resolved) => Outcome::Pass(format!(
+            "no dependency conflicts: {} packages resolve together",
+            resolved.len()
+        )),
+        Err(e) => Outcome::Fail(e),
+    }
+}
+
+/// Configured index packages without a marker that `lock` doesn't pin at
+/// their version, one description each, sorted by name. Packages with a
+/// marker are left out, since the lock only has the ones that applied
+/// where it was made.
+pub fn lock_drift(config: &Config, lock: &str) -> Vec<String> {
+    let locked: HashMap<String, Option<String>> = lock
+        .lines()
+        .map(|line| line.trim().trim_end_matches('\\').trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with('-'))
+        .filter_map(|line| Requirement::parse(line).ok())
+        .map(|req| {
+            let version = req.pinned_version().map(str::to_string);
+            (normalize_pkg_name(&req.name), version)
+        })
+        .collect();
+    let mut names: Vec<&String> = config.packages.keys().collect();
+    names.sort();
+    let mut problems = vec![];
+    for name in names {
+        let spec = &config.packages[name];
+        let Some(version) = spec.version().filter(|_| spec.marker().is_none()) else {
+            continue;
+        };
+        match locked.get(&normalize_pkg_name(name)) {
+            None => problems.push(format!("{} isn't locked", name)),
+            Some(Some(locked)) if compare_versions(locked, version) == Ordering::Equal => {}
+            Some(locked) => problems.push(format!(
+                "{} is locked at {}, {} pins {}",
+                name,
+                locked.as_deref().unwrap_or("an unpinned version"),
+                PROJECT_CONFIG_FILE,
+                version
+            )),
+        }
+    }
+    problems
+}
+
+/// Configured packages for which `applies` holds that aren't in
+/// `installed`, or are at another version than pinned, one description
+/// each, sorted by name. The project itself isn't looked for.
+pub fn venv_drift(
+    config: &Config,
+    installed: &HashMap<String, String>,
+    applies: impl Fn(&PackageSpec) -> bool,
+) -> Vec<String> {
+    let mut names: Vec<&String> = config.packages.keys().collect();
+    names.sort();
+    let mut problems = vec![];
+    for name in names {
+        let spec = &config.packages[name];
+        if spec.is_self() || !applies(spec) {
+            continue;
+        }
+        match (installed.get(&normalize_pkg_name(name)), spec.version()) {
+            (None, _) => problems.push(format!("{} isn't installed", name)),
+            (Some(found), Some(version)) if compare_versions(found, version) != Ordering::Equal => {
+                problems.push(format!(
+                    "{} {} is installed, {} pins {}",
+                    name, found, PROJECT_CONFIG_FILE, version
+                ))
+            }
+            _ => {}
+        }
+    }
+    problems
+}
+
+/// Distributions in the venv's site-packages, keyed by normalized name,
+/// read from their dist-info directories without starting pip
+pub fn installed_versions(venv_dir: &Path) -> HashMap<String, String> {
+    let mut installed = HashMap::new();
+    for site_packages in paths::site_packages_dirs(venv_dir) {
+        let Ok(entries) = fs::read_dir(&site_packages) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            if let Some((name, version)) = file_name
+                .strip_suffix(".dist-info")
+                .and_then(|stem| stem.split_once('-'))
+            {
+                installed.insert(normalize_pkg_name(name), version.to_string());
+            }
+        }
+    }
+    installed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROJECT: &str = r#"
+[project]
+name = "demo"
+version = "0.1.0"
+description = ""
+main_script = "./main.py"
+
+[packages]
+requests = "2.31.0"
+Flask = "3.0.0"
+pywin32 = "306; sys_platform == 'win32'"
+
+[scripts]
+"#;
+
+    fn preflight(dir: &Path) -> Preflight {
+        Preflight::new(
+            dir.to_path_buf(),
+            ProjectContext::load(dir).ok(),
+            false,
+            false,
+        )
+    }
+
+    fn write_dist(dir: &Path, name: &str, version: &str) {
+        let site_packages = dir.join("venv/lib/python3.12/site-packages");
+        fs::create_dir_all(site_packages.join(format!("{}-{}.dist-info", name, version))).unwrap();
+    }
+
+    #[test]
+    fn test_lock_drift() {
+        let config: Config = toml::from_str(PROJECT).unwrap();
+        let lock = "\
+# Generated by `ppm lock` from project.toml
+flask==3.0.0 \\
+    --hash=sha256:abc
+requests==2.30.0
+";
+        assert_eq!(
+            lock_drift(&config, lock),
+            ["requests is locked at 2.30.0, project.toml pins 2.31.0"]
+        );
+        assert_eq!(
+            lock_drift(&config, "Flask==3.0\n"),
+            ["requests isn't locked"]
+        );
+        assert!(lock_drift(&config, "flask==3.0.0\nrequests==2.31.0\n").is_empty());
+    }
+
+    #[test]
+    fn test_venv_drift() {
+        let config: Config = toml::from_str(PROJECT).unwrap();
+        let installed: HashMap<String, String> =
+            HashMap::from([("flask".to_string(), "2.3.3".to_string())]);
+        assert_eq!(
+            venv_drift(&config, &installed, |spec| spec.marker().is_none()),
+            [
+                "Flask 2.3.3 is installed, project.toml pins 3.0.0",
+                "requests isn't installed",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_installed_versions_from_dist_info() {
+        let dir = tempfile::tempdir().unwrap();
+        write_dist(dir.path(), "Flask", "3.0.0");
+        write_dist(dir.path(), "typing_extensions", "4.12.2");
+        let installed = installed_versions(&dir.path().join("venv"));
+        assert_eq!(installed["flask"], "3.0.0");
+        assert_eq!(installed["typing-extensions"], "4.12.2");
+    }
+
+    #[test]
+    fn test_run_each_check() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(PROJECT_CONFIG_FILE), PROJECT).unwrap();
+        let results = run(&preflight(dir.path()), &[]).unwrap();
+        let statuses: Vec<(&str, &str)> = results
+            .iter()
+            .map(|result| (result.name, result.outcome.status()))
+            .collect();
+        assert_eq!(
+            statuses,
+            [
+                ("config", "pass"),
+                ("duplicates", "pass"),
+                ("lock", "skip"),
+                ("requirements", "skip"),
+                ("venv", "fail"),
+                ("yanked", "pass"),
+                ("resolve", "skip"),
+            ]
+        );
+        let err = verdict(&results).unwrap_err();
+        assert_eq!(err.exit_code(), 4);
+        assert_eq!(err.to_string(), "1 of 7 checks failed: venv");
+
+        fs::create_dir_all(dir.path().join("venv/bin")).unwrap();
+        write_dist(dir.path(), "flask", "3.0.0");
+        write_dist(dir.path(), "requests", "2.31.0");
+        assert!(matches!(
+            check_venv(&preflight(dir.path())),
+            Outcome::Pass(_)
+        ));
+        fs::write(dir.path().join(LOCK_FILE), "flask==3.0.0\n").unwrap();
+        assert!(matches!(
+            check_lock(&preflight(dir.path())),
+            Outcome::Fail(PpmError::Other(_))
+        ));
+    }
+
+    #[test]
+    fn test_requirements_check_shows_the_diff() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join(PROJECT_CONFIG_FILE),
+            PROJECT.replace("[packages]", "sync-requirements = true\n\n[packages]"),
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("requirements.txt"),
+            "Flask==3.0.0\npywin32==306 ; sys_platform == 'win32'\nrequests==2.30.0\n",
+        )
+        .unwrap();
+        match check_requirements(&preflight(dir.path())) {
+            Outcome::Fail(e) => assert_eq!(
+                e.to_string(),
+                "requirements.txt is out of date: -requests==2.30.0; +requests==2.31.0; run `ppm gen`"
+            ),
+            outcome => panic!("{:?}", outcome),
+        }
+
+        fs::write(
+            dir.path().join("requirements.txt"),
+            "Flask==3.0.0\r\npywin32==306 ; sys_platform == 'win32'\r\nrequests==2.31.0",
+        )
+        .unwrap();
+        assert!(matches!(
+            check_requirements(&preflight(dir.path())),
+            Outcome::Pass(_)
+        ));
+    }
+
+    #[test]
+    fn test_skip_and_unknown_names() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join(PROJECT_CONFIG_FILE),
+            PROJECT.replace("Flask", "flask = \"3.0.0\"\nFlask"),
+        )
+        .unwrap();
+        let preflight = preflight(dir.path());
+        assert!(preflight.project.is_none());
+        let results = run(&preflight, &["venv".to_string()]).unwrap();
+        assert_eq!(results[1].outcome.status(), "fail");
+        assert_eq!(results[4].outcome.detail(), "skipped with --skip");
+        assert_eq!(results[2].outcome.status(), "skip");
+        assert_eq!(verdict(&results).unwrap_err().exit_code(), 2);
+
+        let err = run(&preflight, &["nope".to_string()]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "No check named 'nope' to skip; the checks are config, duplicates, lock, requirements, venv, yanked, resolve"
+        );
+    }
+}
@@ -0,0 +1,194 @@
+//! `ppm fmt` and `ppm lint`: run the formatter or linter the venv has
+//! over the project's sources.
+//!
+//! ruff is preferred for both; black formats and flake8 lints when ruff
+//! isn't installed but they are. A `fmt` or `lint` entry in `[scripts]`
+//! always wins over these presets.
+
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What the preset does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Task {
+    /// Rewrite the sources in a consistent style
+    Fmt,
+    /// Report problems in the sources
+    Lint,
+}
+
+impl Task {
+    /// Tools that can do the task, most preferred first
+    pub fn tools(&self) -> &'static [Tool] {
+        match self {
+            Task::Fmt => &[Tool::Ruff, Tool::Black],
+            Task::Lint => &[Tool::Ruff, Tool::Flake8],
+        }
+    }
+}
+
+impl fmt::Display for Task {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Task::Fmt => "fmt",
+            Task::Lint => "lint",
+        })
+    }
+}
+
+/// A formatter or linter run as a module of the venv's python
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    /// `ruff format` and `ruff check`
+    Ruff,
+    /// `black`, formatting only
+    Black,
+    /// `flake8`, linting only
+    Flake8,
+}
+
+impl Tool {
+    /// Package that provides the tool, also its module name
+    pub fn package(&self) -> &'static str {
+        match self {
+            Tool::Ruff => "ruff",
+            Tool::Black => "black",
+            Tool::Flake8 => "flake8",
+        }
+    }
+
+    /// Arguments to the venv's python that run the tool for `task` over
+    /// `paths`, followed by `extra` from the command line
+    pub fn args(&self, task: Task, paths: &[PathBuf], extra: &[String]) -> Vec<OsString> {
+        let mut args: Vec<OsString> = vec!["-m".into(), self.package().into()];
+        if *self == Tool::Ruff {
+            args.push(if task == Task::Fmt { "format" } else { "check" }.into());
+        }
+        args.extend(extra.iter().map(OsString::from));
+        args.extend(paths.iter().map(OsString::from));
+        args
+    }
+}
+
+impl fmt::Display for Tool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.package())
+    }
+}
+
+/// The tool to run for `task`, the first of [`Task::tools`] that
+/// `installed` says the venv has
+pub fn pick_tool(task: Task, installed: impl Fn(&str) -> bool) -> Option<Tool> {
+    task.tools()
+        .iter()
+        .copied()
+        .find(|tool| installed(tool.package()))
+}
+
+/// Top-level names that never hold the project's own sources
+const NOT_SOURCES: &[&str] = &["build", "dist", "htmlcov", "node_modules", "__pycache__"];
+
+/// What the tools run over: the .py files and directories at the top of
+/// the project at `root`, except the venv at `venv_dir`, hidden entries
+/// and build output, relative to `root` and ordered by name
+pub fn source_paths(root: &Path, venv_dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(root) else {
+        return vec![];
+    };
+    // `venv` may be configured as `./venv` or as an absolute path
+    let venv_dir = venv_dir.canonicalize().unwrap_or(venv_dir.to_path_buf());
+    let mut paths: Vec<PathBuf> = entries
+        .flatten()
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let path = entry.path();
+            if name.starts_with('.') || NOT_SOURCES.contains(&name.as_str()) {
+                return false;
+            }
+            if path.is_dir() {
+                path.canonicalize().is_ok_and(|dir| dir != venv_dir)
+            } else {
+                name.ends_with(".py") || name.ends_with(".pyi")
+            }
+        })
+        .map(|entry| PathBuf::from(entry.file_name()))
+        .collect();
+    paths.sort();
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pick_tool_prefers_ruff() {
+        let only = |packages: &'static [&'static str]| move |name: &str| packages.contains(&name);
+        assert_eq!(
+            pick_tool(Task::Fmt, only(&["black", "ruff"])),
+            Some(Tool::Ruff)
+        );
+        assert_eq!(
+            pick_tool(Task::Fmt, only(&["black", "flake8"])),
+            Some(Tool::Black)
+        );
+        assert_eq!(
+            pick_tool(Task::Lint, only(&["black", "flake8"])),
+            Some(Tool::Flake8)
+        );
+        assert_eq!(pick_tool(Task::Lint, only(&["black"])), None);
+        assert_eq!(pick_tool(Task::Fmt, only(&[])), None);
+    }
+
+    #[test]
+    fn test_tool_arguments() {
+        let paths = vec![PathBuf::from("app.py"), PathBuf::from("src")];
+        let words = |args: Vec<OsString>| {
+            args.into_iter()
+                .map(|arg| arg.into_string().unwrap())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        assert_eq!(
+            words(Tool::Ruff.args(Task::Fmt, &paths, &["--check".to_string()])),
+            "-m ruff format --check app.py src"
+        );
+        assert_eq!(
+            words(Tool::Ruff.args(Task::Lint, &paths, &[])),
+            "-m ruff check app.py src"
+        );
+        assert_eq!(
+            words(Tool::Flake8.args(Task::Lint, &paths, &[])),
+            "-m flake8 app.py src"
+        );
+    }
+
+    #[test]
+    fn test_source_paths_skip_the_venv() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for sub in [".env", ".git", "build", "env", "src", "tests"] {
+            fs::create_dir_all(root.join(sub)).unwrap();
+        }
+        for file in ["app.py", "stubs.pyi", "project.toml", "README.md"] {
+            fs::write(root.join(file), "").unwrap();
+        }
+        let names = |paths: Vec<PathBuf>| {
+            paths
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
+        };
+        // A venv named `env` is skipped, and `venv` isn't special
+        assert_eq!(
+            names(source_paths(root, &root.join("env"))),
+            vec!["app.py", "src", "stubs.pyi", "tests"]
+        );
+        assert_eq!(
+            names(source_paths(root, &root.join("src/../venv"))),
+            vec!["app.py", "env", "src", "stubs.pyi", "tests"]
+        );
+    }
+}
@@ -0,0 +1,339 @@
+use crate::utils::{eprint, print_human, theme};
+use colored::Color;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressState, ProgressStyle};
+use serde::Serialize;
+use std::{
+    cell::RefCell,
+    sync::atomic::{AtomicU8, Ordering},
+    time::Duration,
+};
+
+/// Time after which bars show how long they have been up
+const LONG_WAIT: Duration = Duration::from_secs(3);
+
+/// Bumped whenever the shape of [`Event`] changes incompatibly
+pub const EVENT_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressMode {
+    /// No progress output at all (`--quiet`, `--json`, non-TTY stdout)
+    Hidden,
+    /// Spinners and bars drawn on stdout
+    Bars,
+    /// Newline-delimited JSON events on stderr (`--progress json`)
+    Json,
+}
+
+static MODE: AtomicU8 = AtomicU8::new(0);
+
+/// Select how progress is reported for the rest of the process.
+/// Called once from `main` after the global flags are parsed.
+pub fn init(mode: ProgressMode) {
+    MODE.store(mode as u8, Ordering::Relaxed);
+}
+
+pub fn mode() -> ProgressMode {
+    match MODE.load(Ordering::Relaxed) {
+        1 => ProgressMode::Bars,
+        2 => ProgressMode::Json,
+        _ => ProgressMode::Hidden,
+    }
+}
+
+/// Phase of work an event belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Phase {
+    Resolve,
+    Install,
+    Venv,
+    Script,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Start,
+    Finish,
+    Fail,
+}
+
+/// One line of the `--progress json` stream
+#[derive(Debug, Serialize)]
+pub struct Event<'a> {
+    pub v: u32,
+    pub event: Phase,
+    pub status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub package: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub script: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+}
+
+impl<'a> Event<'a> {
+    pub fn new(event: Phase, status: Status) -> Event<'a> {
+        Event {
+            v: EVENT_SCHEMA_VERSION,
+            event,
+            status,
+            package: None,
+            script: None,
+            message: None,
+            exit_code: None,
+        }
+    }
+
+    pub fn package(mut self, package: &'a str) -> Event<'a> {
+        self.package = Some(package);
+        self
+    }
+
+    pub fn script(mut self, script: &'a str) -> Event<'a> {
+        self.script = Some(script);
+        self
+    }
+
+    pub fn message(mut self, message: &'a str) -> Event<'a> {
+        self.message = Some(message);
+        self
+    }
+
+    pub fn exit_code(mut self, code: Option<i32>) -> Event<'a> {
+        self.exit_code = code;
+        self
+    }
+
+    /// Write the event to stderr when `--progress json` is active
+    pub fn emit(&self) {
+        if mode() != ProgressMode::Json {
+            return;
+        }
+        if let Ok(line) = serde_json::to_string(self) {
+            eprintln!("{}", line);
+        }
+    }
+}
+
+/// A spinner or `n/total` bar. When bars are disabled the underlying bar is
+/// hidden, so command code can report events unconditionally.
+pub struct Progress {
+    bar: ProgressBar,
+    phase: Phase,
+    item: RefCell<Option<String>>,
+}
+
+impl Progress {
+    /// Spinner for a single operation of unknown length (pip, venv creation)
+    pub fn spinner(phase: Phase, msg: &str) -> Progress {
+        let bar = Self::new_bar(None);
+        bar.set_style(themed(
+            ProgressStyle::with_template(&format!("{{spinner{}}} {{msg}}{{waited}}", accent()))
+                .unwrap_or_else(|_| ProgressStyle::default_spinner()),
+        ));
+        bar.set_message(msg.to_string());
+        bar.enable_steady_tick(Duration::from_millis(100));
+        Event::new(phase, Status::Start).message(msg).emit();
+        Progress {
+            bar,
+            phase,
+            item: RefCell::new(None),
+        }
+    }
+
+    /// Bar for a loop over `total` items, e.g. resolving package versions
+    pub fn bar(phase: Phase, total: usize, prefix: &str) -> Progress {
+        let bar = Self::new_bar(Some(total as u64));
+        let accent = accent();
+        bar.set_style(themed(
+            ProgressStyle::with_template(&format!(
+                "{{spinner{}}} {{prefix}} [{{bar:25{}}}] {{pos}}/{{len}} {{msg}}{{waited}}",
+                accent, accent
+            ))
+            .unwrap_or_else(|_| ProgressStyle::default_bar())
+            .progress_chars("=> "),
+        ));
+        bar.set_prefix(prefix.to_string());
+        bar.enable_steady_tick(Duration::from_millis(100));
+        Progress {
+            bar,
+            phase,
+            item: RefCell::new(None),
+        }
+    }
+
+    fn new_bar(len: Option<u64>) -> ProgressBar {
+        let bar = match len {
+            Some(len) => ProgressBar::new(len),
+            None => ProgressBar::new_spinner(),
+        };
+        bar.set_draw_target(if mode() == ProgressMode::Bars {
+            ProgressDrawTarget::stdout()
+        } else {
+            ProgressDrawTarget::hidden()
+        });
+        bar
+    }
+
+    /// An item of the loop has started; shown as the bar's status line
+    pub fn start_item(&self, name: &str) {
+        self.bar.set_message(name.to_string());
+        Event::new(self.phase, Status::Start).package(name).emit();
+        *self.item.borrow_mut() = Some(name.to_string());
+    }
+
+    /// An item finished successfully; its status line collapses into the count
+    pub fn finish_item(&self) {
+        if let Some(name) = self.item.borrow_mut().take() {
+            Event::new(self.phase, Status::Finish).package(&name).emit();
+        }
+        self.bar.set_message("");
+        self.bar.inc(1);
+    }
+
+    /// An item failed; the reason is kept above the bar instead of collapsing
+    pub fn fail_item(&self, msg: String) {
+        if let Some(name) = self.item.borrow_mut().take() {
+            Event::new(self.phase, Status::Fail)
+                .package(&name)
+                .message(&msg)
+                .emit();
+        }
+        self.bar.suspend(|| eprint(msg));
+        self.bar.inc(1);
+    }
+
+    /// An item of a concurrent loop has started; unlike
+    /// [`Progress::start_item`] several can be in flight at once
+    pub fn start_concurrent_item(&self, name: &str) {
+        Event::new(self.phase, Status::Start).package(name).emit();
+    }
+
+    /// A concurrent item ended, failing with `reason` if there is one. The
+    /// caller reports the failure.
+    pub fn finish_concurrent_item(&self, name: &str, reason: Option<&str>) {
+        let event = match reason {
+            Some(reason) => Event::new(self.phase, Status::Fail).message(reason),
+            None => Event::new(self.phase, Status::Finish),
+        };
+        event.package(name).emit();
+        self.bar.inc(1);
+    }
+
+    /// Print a line of output above the bar
+    pub fn println(&self, text: String) {
+        self.bar.suspend(|| print_human(text));
+    }
+
+    /// Remove the bar from the terminal so regular output can follow
+    pub fn finish(self) {
+        if self.bar.length().is_none() {
+            Event::new(self.phase, Status::Finish).emit();
+        }
+        self.bar.finish_and_clear();
+    }
+
+    /// Clear a spinner whose operation failed; the caller reports the error
+    pub fn abandon(self, reason: &str) {
+        Event::new(self.phase, Status::Fail).message(reason).emit();
+        self.bar.finish_and_clear();
+    }
+}
+
+/// The theme's accent color as a template style, like `.green` or
+/// `.magenta.bright`; empty for colors templates can't name
+fn accent() -> String {
+    let (name, bright) = match theme().accent {
+        Color::Black => ("black", false),
+        Color::Red => ("red", false),
+        Color::Green => ("green", false),
+        Color::Yellow => ("yellow", false),
+        Color::Blue => ("blue", false),
+        Color::Magenta => ("magenta", false),
+        Color::Cyan => ("cyan", false),
+        Color::White => ("white", false),
+        Color::BrightBlack => ("black", true),
+        Color::BrightRed => ("red", true),
+        Color::BrightGreen => ("green", true),
+        Color::BrightYellow => ("yellow", true),
+        Color::BrightBlue => ("blue", true),
+        Color::BrightMagenta => ("magenta", true),
+        Color::BrightCyan => ("cyan", true),
+        Color::BrightWhite => ("white", true),
+        _ => return String::new(),
+    };
+    format!(":.{}{}", name, if bright { ".bright" } else { "" })
+}
+
+/// `style` with the default braille spinner swapped for ASCII when the
+/// theme keeps to ASCII, and the `{waited}` key
+fn themed(style: ProgressStyle) -> ProgressStyle {
+    let style = style.with_key("waited", waited);
+    if theme().is_ascii() {
+        style.tick_chars("-\\|/ ")
+    } else {
+        style
+    }
+}
+
+/// How long the bar has been up, once that's long enough to suggest a
+/// slow network rather than a slow machine: ` (12s)`
+fn waited(state: &ProgressState, w: &mut dyn std::fmt::Write) {
+    let elapsed = state.elapsed();
+    if elapsed >= LONG_WAIT {
+        let _ = write!(w, " ({}s)", elapsed.as_secs());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accent_follows_theme() {
+        // Nothing in the tests sets a theme, so this is the default one
+        assert_eq!(accent(), ":.green");
+        assert!(ProgressStyle::with_template(&format!("{{spinner{}}} {{msg}}", accent())).is_ok());
+    }
+
+    #[test]
+    fn test_bar_counts_items_when_hidden() {
+        init(ProgressMode::Hidden);
+        let bar = Progress::bar(Phase::Resolve, 3, "Resolving");
+        bar.start_item("requests");
+        bar.finish_item();
+        bar.start_item("numpy");
+        bar.fail_item("Could not find latest version of numpy".to_string());
+        assert_eq!(bar.bar.position(), 2);
+        assert!(bar.bar.is_hidden());
+        bar.finish();
+    }
+
+    #[test]
+    fn test_spinner_hidden_when_disabled() {
+        init(ProgressMode::Hidden);
+        let spinner = Progress::spinner(Phase::Install, "Installing 2 packages...");
+        assert!(spinner.bar.is_hidden());
+        spinner.finish();
+    }
+
+    #[test]
+    fn test_event_serialization() {
+        let event = Event::new(Phase::Resolve, Status::Start).package("requests");
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"v":1,"event":"resolve","status":"start","package":"requests"}"#
+        );
+
+        let event = Event::new(Phase::Script, Status::Finish)
+            .script("test")
+            .exit_code(Some(3));
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"v":1,"event":"script","status":"finish","script":"test","exit_code":3}"#
+        );
+    }
+}
@@ -1,8 +1,43 @@
-use crate::settings::*;
+use crate::completions::{CompleteHelper, Completions};
+use crate::examples::ShowExamples;
+use crate::man::ManPages;
+use crate::progress::{Event, Phase, Progress, Status};
 use crate::utils::*;
 use clap::{Args, Subcommand};
 use colored::Colorize;
-use std::{collections::HashMap, fs, path::Path, process::Command, time::Instant};
+use ppmm::activate::ActivateShell;
+use ppmm::age;
+use ppmm::bump;
+use ppmm::git;
+use ppmm::graph::{self, DependencyGraph};
+use ppmm::init;
+use ppmm::install::{self, InstallTask};
+use ppmm::launch::{self, Launch, ProfileSort};
+use ppmm::license::{self, LICENSE_FILE, LICENSES, License, NO_LICENSE};
+use ppmm::listing::{self, SortKey};
+use ppmm::main_script;
+use ppmm::metadata_cache;
+use ppmm::packages::{Prefer, normalize_pkg_name, resolve_duplicates};
+use ppmm::paths::normalize;
+use ppmm::presets::Task;
+use ppmm::prompt::{MAX_ATTEMPTS, Prompter};
+use ppmm::requirement::Requirement;
+use ppmm::requirements::{self, IndexOption, RequirementsFile};
+use ppmm::runner::{self, RunOptions, SystemRunner};
+use ppmm::scaffold::{self, README_FILE, SMOKE_TEST_FILE, TEST_SCRIPT};
+use ppmm::scripts::{self, Target};
+use ppmm::settings::{Config, PackageSpec, Project};
+use ppmm::state::STATE_DIR;
+use ppmm::update::{PinStyle, UpdateMode, UpdateOptions};
+use ppmm::version_pick;
+use ppmm::{PpmError, ProjectContext};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    time::Instant,
+};
 
 const STARTER_SOURCE_PY: &str = "
 def main():
@@ -28,36 +63,149 @@ pub enum Action {
     /// Install packages from project.toml or provided requirements.txt
     Install(Installer),
     /// Run main script defined in project.toml
-    Start,
+    Start(StartProject),
+    /// Run the project's tests with pytest or unittest, or its `test` script
+    Test(RunTests),
+    /// Format the sources with ruff or black, or run the `fmt` script
+    Fmt(RunPreset),
+    /// Lint the sources with ruff or flake8, or run the `lint` script
+    Lint(RunPreset),
+    /// Point an editor at the project's venv
+    Ide(IdeCommand),
     /// Generate requirements.txt file
-    Gen,
+    Gen(GenRequirements),
     /// Show the project.toml file
-    Info,
+    Info(ShowInfo),
+    /// Show the project's venv paths, or shell lines that activate it
+    Env(ShowEnv),
+    /// Manage the project's virtual environment
+    Venv(VenvProject),
     /// Update all or specific packages to their latest versions
     Update(UpdatePackage),
     /// Build the project
     Build(BuildProject),
+    /// Upload the built distributions to PyPI or another index with twine
+    Publish(PublishProject),
     /// Bump project version (major, minor, patch)
     Bump(BumpVersion),
     /// List packages declared in project.toml
-    List,
+    List(ListPackages),
+    /// Resolve every dependency with pip and pin the result in ppmm.lock
+    Lock,
+    /// Install resolved dependencies the venv is missing or has at another version
+    Sync(SyncPackages),
+    /// Pack the project and its locked packages into one archive, or install such a bundle offline
+    Bundle(BundleProject),
+    /// Run quick checks on the project, e.g. from a git hook
+    Check(CheckProject),
+    /// Install or remove git hooks that run ppm commands
+    Hooks(HooksProject),
+    /// Check installed packages against the digests published on PyPI
+    Verify,
+    /// Show how much disk space each installed package takes
+    Stats,
+    /// Print the installed packages' dependency graph as Graphviz DOT or Mermaid
+    Graph(GraphProject),
+    /// List what in the venv depends on a package, directly or transitively
+    Rdeps(ReverseDeps),
+    /// Inspect ppm's caches
+    Cache(CacheProject),
+    /// Read or change ppm's settings
+    Config(ConfigProject),
+    /// Remove the project's cached data from .ppm/
+    Clean(CleanProject),
+    /// Show what ppm commands changed in this project
+    History(ShowHistory),
+    /// Validate project.toml without running anything
+    #[clap(name = "check-config")]
+    CheckConfig,
+    /// Describe an error code like PPM-014: causes and usual fixes
+    Explain(ExplainError),
+    /// Show example command lines for common tasks, optionally only those mentioning a keyword
+    Examples(ShowExamples),
+    /// Print environment details for a bug report, with secrets redacted
+    Report(ReportProject),
+    /// Show the network settings ppm uses and whether they come from ppm or pip
+    Doctor,
+    /// Manage ppm itself
+    #[clap(name = "self")]
+    SelfManage(SelfCommand),
+    /// Print a shell completion script (bash, zsh, fish, powershell)
+    Completions(Completions),
+    /// Print script or package names for shell completion
+    #[clap(name = "_complete", hide = true)]
+    Complete(CompleteHelper),
+    /// Write roff man pages for ppm and each subcommand, for packagers
+    #[clap(name = "man", hide = true)]
+    Man(ManPages),
+    /// A `ppm-<name>` plugin on PATH, with its arguments
+    #[clap(external_subcommand)]
+    External(Vec<String>),
+}
+
+impl Action {
+    /// Whether the command works on an existing project, so `--project`
+    /// and `--all` can pick workspace members for it
+    pub fn in_workspace(&self) -> bool {
+        if let Action::Bundle(bundle) = self {
+            return bundle.in_project();
+        }
+        !matches!(
+            self,
+            Action::New(_)
+                | Action::Explain(_)
+                | Action::Examples(_)
+                | Action::Config(_)
+                | Action::SelfManage(_)
+                | Action::Completions(_)
+                | Action::Complete(_)
+                | Action::Man(_)
+                | Action::External(_)
+        )
+    }
+
+    /// Root of the project the command may change, so its changes can be
+    /// logged; `None` for commands that only read
+    pub fn changed_project_root(&self) -> Option<PathBuf> {
+        let cwd = std::env::current_dir().ok()?;
+        match self {
+            Action::New(project) => return project.given_name().map(|name| cwd.join(name)),
+            Action::Init(_) => return Some(cwd),
+            Action::Add(_) | Action::Rm(_) | Action::Install(_) | Action::Venv(_) => {}
+            Action::Bump(bump) if !bump.dry_run => {}
+            Action::Update(update) if !update.dry_run => {}
+            Action::Sync(sync) if !sync.check => {}
+            _ => return None,
+        }
+        ppmm::paths::find_project_root(&cwd)
+    }
+
+    /// Whether the command can change `[packages]`, so a synced
+    /// requirements file has to be regenerated after it
+    pub fn changes_packages(&self) -> bool {
+        match self {
+            Action::Add(_) | Action::Rm(_) | Action::Install(_) => true,
+            Action::Update(update) => !update.dry_run,
+            _ => false,
+        }
+    }
 }
 
 pub struct ProjectCreator {
-    project: ProjectConf,
+    project: NewProject,
     is_init: bool,
 }
 
 impl ProjectCreator {
-    fn new(project: ProjectConf, is_init: bool) -> ProjectCreator {
+    fn new(project: NewProject, is_init: bool) -> ProjectCreator {
         ProjectCreator { project, is_init }
     }
 
-    fn get_path_with(&self, path: &str) -> String {
+    fn get_path_with(&self, path: &str) -> PathBuf {
         if self.is_init {
-            path.to_string()
+            PathBuf::from(path)
         } else {
-            format!("{}/{}", self.project.name, path)
+            Path::new(&self.project.name).join(path)
         }
     }
 
@@ -78,93 +226,206 @@ impl ProjectCreator {
             .map_err(|e| format!("Failed to initialize git: {}", e))?;
 
         let gitignore_path = self.get_path_with(".gitignore");
-        fs::write(&gitignore_path, "/build\n/venv\n")
+        fs::write(&gitignore_path, "/build\n/venv\n/.ppm.lock\n/.ppm/\n")
             .map_err(|e| format!("Failed to create .gitignore: {}", e))?;
 
         Ok(())
     }
 
-    fn create_boilerplate_files(&self) -> Result<(), String> {
-        let proj_dest = self.get_path_with("src");
-        let main_file_path = format!("{}/main.py", proj_dest);
+    fn create_boilerplate_file(&self, path: &str) -> Result<(), String> {
+        let main_file_path = self.get_path_with(path);
+        if let Some(parent) = main_file_path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create directory: {}", e))?;
+        }
         fs::write(&main_file_path, STARTER_SOURCE_PY)
-            .map_err(|e| format!("Failed to create main.py: {}", e))?;
+            .map_err(|e| format!("Failed to create {}: {}", path, e))?;
         Ok(())
     }
 
-    fn save_config(&self) -> Result<(), String> {
-        let mut conf = Config::new(
-            Project::new(
-                self.project.name.clone(),
-                self.project.version.clone(),
-                self.project.description.clone(),
-                if self.is_init {
-                    "./main.py"
-                } else {
-                    "./src/main.py"
-                }
-                .to_string(),
-                self.project.venv.clone(),
-            ),
-            HashMap::new(),
-            HashMap::new(),
+    /// The `--main-script` given, created when missing, or else the
+    /// existing entry point, or the starter file created when there is
+    /// none. `--yes` takes the likeliest of several entry points.
+    fn main_script(&self, prompter: &dyn Prompter) -> Result<String, PpmError> {
+        if let Some(path) = &self.project.main_script {
+            let path = format!("./{}", path.trim_start_matches("./"));
+            if !self.get_path_with(&path).exists() {
+                self.create_boilerplate_file(&path)
+                    .map_err(PpmError::Other)?;
+            }
+            return Ok(path);
+        }
+        let candidates = main_script::find_candidates(&self.get_path_with("."), &self.project.name);
+        let found = if self.project.yes {
+            candidates.first().cloned()
+        } else {
+            main_script::choose(&candidates, prompter)?
+        };
+        if let Some(found) = found {
+            iprint(format!("Using '{}' as the main script", found));
+            return Ok(format!("./{}", found));
+        }
+        self.create_boilerplate_file("src/main.py")
+            .map_err(PpmError::Other)?;
+        Ok("./src/main.py".to_string())
+    }
+
+    /// Write the text of the chosen license to LICENSE, keeping an
+    /// existing file unless `--force` is given
+    fn write_license(&self) -> Result<(), PpmError> {
+        let Some(license) = self.project.license.as_deref().and_then(License::find) else {
+            return Ok(());
+        };
+        let path = self.get_path_with(LICENSE_FILE);
+        if path.exists() && !self.project.force {
+            wprint(format!(
+                "{} already exists, keeping it; --force replaces it with the {} text",
+                LICENSE_FILE, license.id
+            ));
+            return Ok(());
+        }
+        let author = license::author();
+        if author.is_none() && license.names_author() {
+            wprint(format!(
+                "No author found for {}: set author in the global config.toml or git's user.name, then fill in [fullname]",
+                LICENSE_FILE
+            ));
+        }
+        let text = license.render(
+            license::current_year(),
+            author.as_deref().unwrap_or("[fullname]"),
+        );
+        fs::write(&path, text)
+            .map_err(|e| PpmError::Other(format!("Failed to create {}: {}", LICENSE_FILE, e)))
+    }
+
+    /// Write the README and smoke test asked for, keeping files that
+    /// already exist
+    fn write_starter_files(&self, main_script: &str) -> Result<(), PpmError> {
+        let mut files = vec![];
+        if self.project.readme {
+            let text = scaffold::readme(&self.project.name, &self.project.description, main_script);
+            files.push((README_FILE, text));
+        }
+        if self.project.tests {
+            files.push((SMOKE_TEST_FILE, scaffold::smoke_test(main_script)));
+        }
+        for (file, text) in files {
+            let path = self.get_path_with(file);
+            if path.exists() {
+                wprint(format!("{} already exists, keeping it", file));
+                continue;
+            }
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)
+                    .map_err(|e| PpmError::Other(format!("Failed to create directory: {}", e)))?;
+            }
+            fs::write(&path, text)
+                .map_err(|e| PpmError::Other(format!("Failed to create {}: {}", file, e)))?;
+        }
+        Ok(())
+    }
+
+    fn save_config(&self, main_script: String) -> Result<(), PpmError> {
+        let mut project = Project::new(
+            self.project.name.clone(),
+            self.project.version.clone(),
+            self.project.description.clone(),
+            main_script,
+            self.project.venv.clone(),
         );
+        project.python = self.project.python.clone();
+        project.license = self.project.license.clone();
+        let mut conf = Config::new(project, HashMap::new(), HashMap::new());
         conf.scripts.insert(
             "upgrade-pip".to_string(),
-            "python -m pip install --upgrade pip".to_string(),
+            "python -m pip install --upgrade pip".into(),
         );
+        if self.project.tests {
+            conf.scripts
+                .insert("test".to_string(), TEST_SCRIPT.into());
+        }
 
         let config_path = self.get_path_with(get_project_config_file());
-        conf.write_to_file(&config_path)
-            .map_err(|e| format!("Failed to write config: {}", e))?;
+        conf.write_to_file(&config_path.to_string_lossy())
+    }
+
+    /// Write the answers into the `[project]` table of `config`, keeping
+    /// the rest; the main script is only changed when given or unset
+    fn apply_metadata(&self, config: &mut Config, main_script: Option<String>) {
+        let project = &mut config.project;
+        project.name = self.project.name.clone();
+        project.version = self.project.version.clone();
+        project.description = self.project.description.clone();
+        project.venv = self.project.venv.clone();
+        project.python = self.project.python.clone();
+        project.license = self.project.license.clone();
+        if let Some(main_script) = main_script {
+            project.main_script = main_script;
+        }
+    }
+
+    /// Update the metadata of the existing project `config` from the
+    /// answers, creating the venv when it's missing
+    fn reconfigure(&self, mut config: Config, prompter: &dyn Prompter) -> Result<(), PpmError> {
+        let main_script =
+            if self.project.main_script.is_some() || config.project.main_script.trim().is_empty() {
+                Some(self.main_script(prompter)?)
+            } else {
+                None
+            };
+        let license_changed = self.project.license != config.project.license;
+        self.apply_metadata(&mut config, main_script);
+        if license_changed || self.project.force || !self.get_path_with(LICENSE_FILE).exists() {
+            self.write_license()?;
+        }
+
+        let venv_path = self.get_path_with(self.project.venv.as_deref().unwrap_or("venv"));
+        if !self.project.no_venv && !venv_path.exists() {
+            setup_project_venv(
+                &SystemRunner,
+                &self.get_path_with("."),
+                self.project.venv.as_deref().unwrap_or("venv"),
+                self.project.python.as_deref(),
+            )?;
+        }
+        config.write_to_file(get_project_config_file())?;
+        iprint(format!(
+            "Reconfigured {}; its packages and scripts are unchanged",
+            get_project_config_file()
+        ));
         Ok(())
     }
 
-    pub fn create_project(&self) {
+    pub fn create_project(&self, prompter: &dyn Prompter) -> Result<(), PpmError> {
         let start = Instant::now();
-        let proj_dest = self.get_path_with("src");
 
         if project_exists(&self.project.name, self.is_init) {
-            eprint(format!(
+            return Err(PpmError::Other(format!(
                 "Project With Name '{}' Already Exists",
                 &self.project.name
-            ));
-            return;
-        }
-
-        if let Err(e) = fs::create_dir_all(&proj_dest) {
-            eprint(format!("Failed to create directory: {}", e));
-            return;
-        }
-
-        if let Err(e) = self.create_boilerplate_files() {
-            eprint(e);
-            return;
+            )));
         }
 
-        if let Err(e) = self.create_git() {
-            eprint(e);
-            return;
-        }
+        // Not `<name>/.`, which create_dir_all can't create
+        fs::create_dir_all(self.get_path_with(""))
+            .map_err(|e| PpmError::Other(format!("Failed to create directory: {}", e)))?;
+        let main_script = self.main_script(prompter)?;
+        self.write_license()?;
+        self.write_starter_files(&main_script)?;
+        self.create_git().map_err(PpmError::Subprocess)?;
 
         if !self.project.no_venv {
-            let venv_path = self
-                .project
-                .venv
-                .clone()
-                .unwrap_or_else(|| "venv".to_string());
-            if let Err(e) = setup_venv(self.get_path_with(&venv_path)) {
-                eprint(format!("Failed to setup venv: {}", e));
-                return;
-            }
+            setup_project_venv(
+                &SystemRunner,
+                &self.get_path_with("."),
+                self.project.venv.as_deref().unwrap_or("venv"),
+                self.project.python.as_deref(),
+            )?;
         } else {
             wprint("Virtual environment is disabled, some commands might not work".to_string());
         }
 
-        if let Err(e) = self.save_config() {
-            eprint(e);
-            return;
-        }
+        self.save_config(main_script)?;
 
         let elapsed = start.elapsed();
         iprint(format!("{} in {}s", "Completed".green(), elapsed.as_secs()));
@@ -173,34 +434,352 @@ impl ProjectCreator {
             println!("  cd {}", self.project.name.blue());
         }
         println!("  {} start\n", "ppm".red());
+        Ok(())
     }
 }
 
 #[derive(Args, Debug, Clone)]
 pub struct ProjectConf {
     /// Set Project Name
-    name: String,
-    /// Set Project Version
-    #[clap(short = 'v', long = "version", default_value = "0.1.0")]
-    version: String,
+    name: Option<String>,
+    /// Set Project Name, like the positional argument
+    #[clap(long = "name", value_name = "NAME", conflicts_with = "name")]
+    name_flag: Option<String>,
+    /// Set Project Version [default: 0.1.0]
+    #[clap(short = 'v', long = "version")]
+    version: Option<String>,
     /// Set Project Description
-    #[clap(short = 'd', long = "description", default_value = "")]
-    description: String,
+    #[clap(short = 'd', long = "description")]
+    description: Option<String>,
+    /// Set the script `ppm start` runs, created when missing, instead of detecting one
+    #[clap(long = "main-script", value_name = "PATH")]
+    main_script: Option<String>,
     /// Set Virtual Environment Name
-    #[clap(long = "venv")]
+    #[clap(long = "venv-name", alias = "venv", value_name = "DIR")]
     venv: Option<String>,
+    /// Set the interpreter the venv is created with, like python3.12 or 3.12
+    #[clap(long = "python", value_name = "PYTHON")]
+    python: Option<String>,
+    /// Set the license and write its LICENSE file: MIT, Apache-2.0, GPL-3.0, Unlicense or none
+    #[clap(long = "license", value_name = "SPDX-ID")]
+    license: Option<String>,
+    /// Overwrite an existing LICENSE file
+    #[clap(long = "force", takes_value = false)]
+    force: bool,
+    /// Write a README.md with a getting-started section; on for `new` unless --bare
+    #[clap(long = "with-readme", takes_value = false)]
+    with_readme: bool,
+    /// Write tests/test_smoke.py and a `test` script; on for `new` unless --bare
+    #[clap(long = "with-tests", takes_value = false)]
+    with_tests: bool,
+    /// Only write project.toml and the main script
+    #[clap(long = "bare", takes_value = false)]
+    bare: bool,
     /// Enable Git
     #[clap(short = 'g', long = "git", takes_value = false)]
     git: bool,
     /// Don't Create Virtual Environment
     #[clap(short = 'e', long = "no-venv", takes_value = false)]
     no_venv: bool,
+    /// Accept the defaults for anything not given as a flag instead of asking
+    #[clap(short = 'y', long = "yes", takes_value = false)]
+    yes: bool,
+    /// Record the packages of a requirements file in project.toml and install
+    /// them [default: requirements.txt]
+    #[clap(
+        long = "from-requirements",
+        value_name = "FILE",
+        min_values = 0,
+        max_values = 1,
+        default_missing_value = requirements::REQUIREMENTS_FILE
+    )]
+    from_requirements: Option<String>,
+}
+
+/// What a project is created with, once flags, answers and defaults are
+/// combined
+#[derive(Debug, Clone)]
+struct NewProject {
+    name: String,
+    version: String,
+    description: String,
+    main_script: Option<String>,
+    venv: Option<String>,
+    python: Option<String>,
+    license: Option<String>,
+    readme: bool,
+    tests: bool,
+    git: bool,
+    no_venv: bool,
+    yes: bool,
+    force: bool,
+}
+
+/// The value of `field`: `given` from its flag, else the answer to its
+/// question when `ask`, else `default`. Flag values, answers and defaults
+/// are checked alike; an invalid answer is asked again.
+fn field_value(
+    field: &init::Field,
+    given: Option<&str>,
+    default: Option<&str>,
+    ask: bool,
+    prompter: &dyn Prompter,
+) -> Result<Option<String>, PpmError> {
+    if let Some(value) = given {
+        field
+            .check(value)
+            .map_err(|e| PpmError::Config(format!("Invalid {}: {}", field.flag, e)))?;
+        return Ok(Some(value.to_string()));
+    }
+    if !ask {
+        if let Some(value) = default {
+            field
+                .check(value)
+                .map_err(|e| PpmError::Config(format!("{}; set it with {}", e, field.flag)))?;
+        }
+        return Ok(default.map(str::to_string));
+    }
+    for _ in 0..MAX_ATTEMPTS {
+        let Some(answer) = prompter.ask_string(field.question, default) else {
+            return Ok(None);
+        };
+        match field.check(&answer) {
+            Ok(()) => return Ok(Some(answer)),
+            Err(e) => wprint(e),
+        }
+    }
+    Err(PpmError::Config(format!(
+        "No valid answer for '{}'; set it with {}",
+        field.question, field.flag
+    )))
 }
 
 impl ProjectConf {
-    pub fn create_project(&self, is_init: bool) {
-        let proj_creator = ProjectCreator::new(self.clone(), is_init);
-        proj_creator.create_project();
+    /// The name given on the command line, if any
+    pub fn given_name(&self) -> Option<&str> {
+        self.name.as_deref().or(self.name_flag.as_deref())
+    }
+
+    pub fn create_project(&self, is_init: bool, prompter: &dyn Prompter) -> Result<(), PpmError> {
+        if let Some(file) = &self.from_requirements {
+            return self.init_from_requirements(is_init, file, prompter);
+        }
+        let existing = if is_init && project_exists(&String::new(), true) {
+            wprint(format!(
+                "{} already exists in this directory",
+                get_project_config_file()
+            ));
+            let question = "Reconfigure its [project] metadata, keeping its packages and scripts?";
+            if self.yes || !prompter.ask_yes_no(question, false) {
+                iprint(format!(
+                    "Init cancelled, {} is unchanged",
+                    get_project_config_file()
+                ));
+                return Ok(());
+            }
+            Some(Config::load_from_file(get_project_config_file())?)
+        } else {
+            None
+        };
+        let project = self.answers(is_init, existing.as_ref().map(|c| &c.project), prompter)?;
+        let proj_creator = ProjectCreator::new(project, is_init);
+        match existing {
+            Some(config) => proj_creator.reconfigure(config, prompter),
+            None => proj_creator.create_project(prompter),
+        }
+    }
+
+    /// `init --from-requirements`: create the project, record the packages
+    /// of `file` in it and install them. project.toml is saved before pip
+    /// runs, so a failed install leaves the packages for `ppm install`.
+    fn init_from_requirements(
+        &self,
+        is_init: bool,
+        file: &str,
+        prompter: &dyn Prompter,
+    ) -> Result<(), PpmError> {
+        if !is_init {
+            return Err(PpmError::Config(
+                "--from-requirements only works with `ppm init`, in the directory with the requirements file"
+                    .to_string(),
+            ));
+        }
+        if project_exists(&String::new(), true) {
+            return Err(PpmError::Config(format!(
+                "{} already exists; add the packages of {} to it with `ppm install -r {} --record`",
+                get_project_config_file(),
+                file,
+                file
+            )));
+        }
+        if !Path::new(file).is_file() {
+            return Err(PpmError::Config(format!(
+                "Requirements file {} not found; pass the file as --from-requirements <FILE>",
+                file
+            )));
+        }
+        // Read before anything is written, so a bad file leaves no project
+        let (reqs, index) = read_requirement_files(
+            &[file.to_string()],
+            None,
+            &format!("keep one of them in {}", file),
+        )?;
+
+        let project = self.answers(is_init, None, prompter)?;
+        ProjectCreator::new(project, is_init).create_project(prompter)?;
+
+        let mut ctx = load_project()?;
+        let _lock = lock_project(&ctx)?;
+        if let Some(index) = &index {
+            adopt_index(&mut ctx, index, prompter);
+        }
+        let (recorded, unresolved) = record_requirements(&mut ctx, &reqs, "Importing");
+        save_project(&mut ctx, Some(prompter))?;
+        iprint(format!(
+            "Recorded {} package(s) from {} in {}",
+            recorded.len(),
+            file,
+            get_project_config_file()
+        ));
+        if unresolved > 0 {
+            return Err(PpmError::Network(format!(
+                "Could not resolve {} package(s) from {}; add them with `ppm add`, then run `ppm install`",
+                unresolved, file
+            )));
+        }
+        if self.no_venv {
+            wprint("No venv to install into; `ppm install` installs the packages".to_string());
+            return Ok(());
+        }
+        Installer::default()
+            .install_into(&mut ctx, prompter)
+            .inspect_err(|_| {
+                wprint(format!(
+                    "{} keeps the packages of {}; fix the failure and retry with `ppm install`",
+                    get_project_config_file(),
+                    file
+                ))
+            })
+    }
+
+    /// The flags, with `init` asking for the fields they leave out unless
+    /// `--yes` is given. The defaults come from `existing` when `init`
+    /// reconfigures a project, and the name from the directory's otherwise.
+    fn answers(
+        &self,
+        is_init: bool,
+        existing: Option<&Project>,
+        prompter: &dyn Prompter,
+    ) -> Result<NewProject, PpmError> {
+        let ask = is_init && !self.yes;
+        let mut name_default = None;
+        if is_init && self.given_name().is_none() {
+            let derived = match existing {
+                Some(project) => Ok(project.name.clone()),
+                None => std::env::current_dir()
+                    .ok()
+                    .and_then(|dir| {
+                        dir.file_name()
+                            .map(|name| name.to_string_lossy().into_owned())
+                    })
+                    .ok_or_else(|| "the current directory has no name".to_string())
+                    .and_then(|dir| init::name_from_dir(&dir)),
+            };
+            match derived {
+                Ok(name) => name_default = Some(name),
+                Err(why) if self.yes => {
+                    return Err(PpmError::Config(format!(
+                        "Can't name the project after its directory: {}; set it with --name",
+                        why
+                    )));
+                }
+                Err(why) => wprint(format!(
+                    "Can't name the project after its directory: {}",
+                    why
+                )),
+            }
+        }
+        let name = field_value(
+            &init::NAME,
+            self.given_name(),
+            name_default.as_deref(),
+            !self.yes,
+            prompter,
+        )?
+        .ok_or_else(|| {
+            PpmError::Config("A project name is needed: `ppm new <NAME>` or --name".to_string())
+        })?;
+        let version_default = existing.map_or("0.1.0", |project| project.version.as_str());
+        let version = field_value(
+            &init::VERSION,
+            self.version.as_deref(),
+            Some(version_default),
+            ask,
+            prompter,
+        )?
+        .unwrap_or_else(|| version_default.to_string());
+        let description = field_value(
+            &init::DESCRIPTION,
+            self.description.as_deref(),
+            existing
+                .map(|project| project.description.as_str())
+                .filter(|description| !description.is_empty()),
+            ask,
+            prompter,
+        )?
+        .unwrap_or_default();
+        if let Some(main_script) = &self.main_script {
+            field_value(&init::MAIN_SCRIPT, Some(main_script), None, false, prompter)?;
+        }
+        let venv = field_value(
+            &init::VENV,
+            self.venv.as_deref(),
+            Some(
+                existing
+                    .and_then(|project| project.venv.as_deref())
+                    .unwrap_or("venv"),
+            ),
+            ask && !self.no_venv,
+            prompter,
+        )?
+        .filter(|venv| self.venv.is_some() || venv != "venv");
+        let python = field_value(
+            &init::PYTHON,
+            self.python.as_deref(),
+            existing.and_then(|project| project.python.as_deref()),
+            ask && !self.no_venv,
+            prompter,
+        )?;
+        let license = match &self.license {
+            Some(id) => field_value(&init::LICENSE, Some(id), None, false, prompter)?,
+            None if ask => {
+                let mut options: Vec<&str> = LICENSES.iter().map(|license| license.id).collect();
+                options.push(NO_LICENSE);
+                prompter
+                    .select(init::LICENSE.question, &options)
+                    .map(|i| options[i].to_string())
+                    .or_else(|| existing.and_then(|project| project.license.clone()))
+            }
+            None => existing.and_then(|project| project.license.clone()),
+        };
+        // Stored by its canonical SPDX identifier
+        let license =
+            license.and_then(|id| License::find(&id).map(|license| license.id.to_string()));
+        Ok(NewProject {
+            name,
+            version,
+            description,
+            main_script: self.main_script.clone(),
+            venv,
+            python,
+            license,
+            readme: self.with_readme || (!is_init && !self.bare),
+            tests: self.with_tests || (!is_init && !self.bare),
+            git: self.git,
+            no_venv: self.no_venv,
+            yes: self.yes,
+            force: self.force,
+        })
     }
 }
 
@@ -208,65 +787,178 @@ impl ProjectConf {
 pub struct AddPackage {
     /// List of packages to add
     pub pkg_names: Vec<String>,
+    /// Fail on a package the index doesn't have instead of offering close matches
+    #[clap(long = "no-suggest", takes_value = false)]
+    pub no_suggest: bool,
+    /// Choose each unpinned package's version from its recent releases
+    #[clap(long = "version-pick", takes_value = false)]
+    pub version_pick: bool,
+    /// Offer pre-releases too with --version-pick
+    #[clap(long = "pre", takes_value = false, requires = "version-pick")]
+    pub pre: bool,
 }
 
 impl AddPackage {
-    pub fn add_package(&self) {
-        let config_file = get_project_config_file();
-        if !Path::new(config_file).exists() {
-            eprint(format!("Could not find {}", config_file));
-            return;
+    /// A close match for `name`, which the index doesn't have, picked by
+    /// the user; `None` when there is none or none is picked
+    fn pick_suggestion(
+        &self,
+        ctx: &ProjectContext,
+        name: &str,
+        prompter: &dyn Prompter,
+    ) -> Result<Option<String>, PpmError> {
+        let suggestions = ctx.suggest(name)?;
+        if suggestions.is_empty() {
+            return Ok(None);
         }
+        wprint(format!(
+            "Package '{}' was not found; did you mean {}?",
+            name,
+            suggestions.join(", ")
+        ));
+        let mut options: Vec<&str> = suggestions.iter().map(String::as_str).collect();
+        options.push("None of these");
+        Ok(prompter
+            .select(
+                &format!("Add which package instead of '{}'?", name),
+                &options,
+            )
+            .and_then(|i| suggestions.get(i).cloned()))
+    }
 
-        let mut conf = match Config::load_from_file(config_file) {
-            Ok(conf) => conf,
-            Err(e) => {
-                eprint(e.to_string());
-                return;
-            }
+    /// A release of `name` picked by the user from the newest ones;
+    /// `None` when none is picked
+    fn pick_version(
+        &self,
+        ctx: &ProjectContext,
+        name: &str,
+        prompter: &dyn Prompter,
+    ) -> Result<Option<String>, PpmError> {
+        let history = ctx.release_history(name)?;
+        let releases = version_pick::pickable(&history, self.pre);
+        if releases.is_empty() {
+            return Err(PpmError::Other(format!(
+                "'{}' has no releases to pick from{}",
+                name,
+                if self.pre {
+                    ""
+                } else {
+                    "; --pre includes pre-releases"
+                }
+            )));
+        }
+        let lines = version_pick::describe(&releases);
+        let options: Vec<&str> = lines.iter().map(String::as_str).collect();
+        let Some(i) = prompter.select(&format!("Which version of '{}'?", name), &options) else {
+            return Ok(None);
         };
+        let release = releases[i];
+        if release.yanked {
+            wprint(format!(
+                "{} {} was yanked from the index; pip installs it only because it is pinned",
+                name, release.version
+            ));
+        }
+        Ok(Some(release.version.clone()))
+    }
 
-        let venv_root = conf.project.venv.as_deref().unwrap_or("venv");
+    pub fn add_package(&self, prompter: &dyn Prompter) -> Result<(), PpmError> {
+        if self.version_pick && !prompter.is_interactive() {
+            return Err(PpmError::Other(
+                "--version-pick needs a terminal to choose in; pass an explicit 'pkg==version' instead"
+                    .to_string(),
+            ));
+        }
+        let mut ctx = load_project()?;
+        ctx.check_venv_in_use()?;
+        let _lock = lock_project(&ctx)?;
 
         if self.pkg_names.is_empty() {
             wprint("No packages specified".to_string());
-            return;
-        }
-
-        match install_packages_batch(&self.pkg_names, venv_root) {
-            Ok(_) => {
-                for pkg_name in self.pkg_names.iter() {
-                    let (vname, ver) = parse_version(pkg_name);
-                    let version = match ver {
-                        Some(v) => v,
-                        None => match get_pkg_version(&vname) {
-                            Ok(v) => v,
-                            Err(e) => {
-                                eprint(format!("Failed to get version for '{}': {}", vname, e));
-                                continue;
-                            }
-                        },
-                    };
-
-                    conf.packages.insert(vname.clone(), version);
-                    iprint(format!("Package '{}' added successfully", &vname));
-                }
+            return Ok(());
+        }
 
-                match conf.write_to_file(config_file) {
-                    Ok(_) => {
-                        if let Err(e) = generate_lock_file(venv_root) {
-                            eprint(format!("Failed to generate lock file: {}", e));
-                        }
-                    }
-                    Err(e) => {
-                        eprint(e.to_string());
+        let mut reqs = parse_requirements(&self.pkg_names)?;
+        // Look up unpinned packages before pip runs, so that a name the
+        // index doesn't have can be swapped for a close match
+        let mut latest = HashMap::new();
+        for req in reqs.iter_mut() {
+            if req.url.is_some() || req.pinned_version().is_some() {
+                continue;
+            }
+            let mut found = ctx.latest_version(&req.name);
+            if matches!(found, Err(PpmError::PackageNotFound(_)))
+                && !self.no_suggest
+                && let Some(name) = self.pick_suggestion(&ctx, &req.name, prompter)?
+            {
+                req.name = name;
+                found = ctx.latest_version(&req.name);
+            }
+            if let Err(PpmError::PackageNotFound(name)) = found {
+                return Err(PpmError::PackageNotFound(name));
+            }
+            if self.version_pick {
+                match self.pick_version(&ctx, &req.name, prompter)? {
+                    Some(version) => req.specifier = vec![("==".to_string(), version)],
+                    None => {
+                        wprint("Add Cancelled".to_owned());
+                        return Ok(());
                     }
                 }
+                continue;
             }
-            Err(e) => {
-                eprint(e);
-            }
+            latest.insert(req.name.clone(), found);
+        }
+        install_packages_batch(&reqs, &ctx)?;
+
+        let progress = Progress::bar(Phase::Resolve, reqs.len(), "Recording");
+        let mut added = vec![];
+        let mut unresolved = 0;
+        for req in &reqs {
+            let vname = req.name.clone();
+            progress.start_item(&vname);
+            let marker = req.marker.as_deref();
+            let spec = match (&req.url, req.pinned_version()) {
+                // Direct references have no index version to look up
+                (Some(url), _) => PackageSpec::direct(url, marker),
+                (None, Some(v)) => PackageSpec::new(v, marker),
+                (None, None) => match latest
+                    .remove(&vname)
+                    .unwrap_or_else(|| ctx.latest_version(&vname))
+                {
+                    Ok(v) => PackageSpec::new(&v, marker),
+                    Err(e) => {
+                        progress.fail_item(format!(
+                            "Failed to get version for '{}': {}",
+                            vname, e
+                        ));
+                        unresolved += 1;
+                        continue;
+                    }
+                },
+            };
+
+            replace_package(&mut ctx.config, req, spec);
+            added.push(vname);
+            progress.finish_item();
+        }
+        progress.finish();
+        for vname in added {
+            iprint(format!("Package '{}' added successfully", &vname));
+        }
+
+        save_project(&mut ctx, None)?;
+        if let Err(e) = generate_lock_file(&ctx) {
+            eprint(format!("Failed to generate lock file: {}", e));
+        }
+
+        if unresolved > 0 {
+            return Err(PpmError::Network(format!(
+                "Could not resolve {} package(s)",
+                unresolved
+            )));
         }
+        Ok(())
     }
 }
 
@@ -274,16 +966,26 @@ impl AddPackage {
 pub struct RemovePackage {
     /// List of packages to remove
     pub pkg_names: Vec<String>,
+    /// Remove packages that other configured packages need
+    #[clap(long = "force", takes_value = false)]
+    pub force: bool,
+    /// Also uninstall the dependencies nothing else needs anymore
+    #[clap(long = "cascade", takes_value = false)]
+    pub cascade: bool,
 }
 
 impl RemovePackage {
-    fn uninstall_package(&self, pkg: &str, venv_root: &str) -> Result<(), String> {
-        if !check_venv_dir_exists(venv_root) {
-            return Err("Virtual Environment Not Found".to_string());
+    fn uninstall_package(&self, pkg: &str, ctx: &ProjectContext) -> Result<(), String> {
+        if !check_venv_dir_exists(ctx) {
+            iprint(format!(
+                "No virtual environment; removing {} from project.toml only",
+                pkg
+            ));
+            return Ok(());
         }
 
         iprint(format!("Uninstalling {}", pkg));
-        let output = Command::new(get_venv_pip_path(venv_root))
+        let output = Command::new(ctx.venv_pip())
             .arg("uninstall")
             .arg("-y")
             .arg(pkg)
@@ -301,369 +1003,910 @@ impl RemovePackage {
         Ok(())
     }
 
-    pub fn remove_package(&self) {
-        let config_file = get_project_config_file();
-        if !Path::new(config_file).exists() {
-            eprint(format!("Could not find {}", config_file));
-            return;
+    /// The configured packages besides those being removed that need
+    /// `pkg`, according to `graph`; `None` when `pkg` may go
+    fn blocking_dependents(&self, pkg: &str, graph: &DependencyGraph) -> Option<String> {
+        let needed_by: Vec<&str> = graph
+            .needed_by(pkg, &self.pkg_names)
+            .into_iter()
+            .map(|package| package.name.as_str())
+            .collect();
+        if needed_by.is_empty() {
+            None
+        } else {
+            Some(needed_by.join(", "))
         }
+    }
 
-        let mut conf = match Config::load_from_file(config_file) {
-            Ok(conf) => conf,
-            Err(e) => {
-                eprint(e.to_string());
-                return;
-            }
-        };
+    pub fn remove_package(&self) -> Result<(), PpmError> {
+        let mut ctx = load_project()?;
+        let _lock = lock_project(&ctx)?;
 
-        let venv_root = conf.project.venv.clone().unwrap_or_else(|| "venv".to_string());
+        let graph = graph::current_or_cached(&ctx);
+        match &graph {
+            Some((_, true)) => wprint(
+                "No virtual environment; checking what needs the packages against the dependency metadata cached in .ppm/".to_string(),
+            ),
+            None => wprint(
+                "No virtual environment and no cached dependency metadata; can't check whether other packages need the ones removed".to_string(),
+            ),
+            Some(_) => {}
+        }
 
+        let mut missing = 0;
+        let mut failed = 0;
+        let mut blocked = 0;
+        let mut removed = vec![];
         for pkg_name in self.pkg_names.iter() {
-            if !conf.packages.contains_key(pkg_name) {
+            if !ctx.config.packages.contains_key(pkg_name) {
                 eprint(format!("Package '{}' does not exist", pkg_name));
+                missing += 1;
                 continue;
             }
 
-            match self.uninstall_package(pkg_name, &venv_root) {
+            let needed_by = graph
+                .as_ref()
+                .and_then(|(graph, _)| self.blocking_dependents(pkg_name, graph));
+            if let Some(needed_by) = needed_by {
+                if !self.force {
+                    eprint(format!(
+                        "Not removing '{}': {} still need it; pass --force to remove it anyway",
+                        pkg_name, needed_by
+                    ));
+                    blocked += 1;
+                    continue;
+                }
+                wprint(format!(
+                    "Removing '{}' although {} still need it",
+                    pkg_name, needed_by
+                ));
+            }
+
+            match self.uninstall_package(pkg_name, &ctx) {
                 Ok(_) => {
-                    conf.packages.remove(pkg_name);
-                    match conf.write_to_file(config_file) {
-                        Ok(_) => {
-                            iprint(format!("Package '{}' removed successfully", pkg_name));
-                            if let Err(e) = generate_lock_file(&venv_root) {
-                                eprint(format!("Failed to generate lock file: {}", e));
-                            }
-                        }
-                        Err(e) => {
-                            eprint(e.to_string());
-                            continue;
-                        }
-                    }
+                    ctx.config.packages.remove(pkg_name);
+                    save_project(&mut ctx, None)?;
+                    iprint(format!("Package '{}' removed successfully", pkg_name));
+                    removed.push(pkg_name.clone());
                 }
                 Err(e) => {
                     eprint(format!("Failed to remove '{}': {}", pkg_name, e));
+                    failed += 1;
+                }
+            }
+        }
+
+        if self.cascade && !removed.is_empty() && check_venv_dir_exists(&ctx) {
+            let orphans: Vec<String> = graph
+                .as_ref()
+                .map(|(graph, _)| {
+                    graph
+                        .orphaned_by(&removed)
+                        .into_iter()
+                        .map(|package| package.name.clone())
+                        .collect()
+                })
+                .unwrap_or_default();
+            if orphans.is_empty() {
+                iprint("No dependencies were left orphaned".to_string());
+            } else {
+                iprint(format!(
+                    "Removing {} orphaned dependencies: {}",
+                    orphans.len(),
+                    orphans.join(", ")
+                ));
+            }
+            for orphan in &orphans {
+                if let Err(e) = self.uninstall_package(orphan, &ctx) {
+                    eprint(format!("Failed to remove '{}': {}", orphan, e));
+                    failed += 1;
                 }
             }
         }
+
+        if !removed.is_empty()
+            && check_venv_dir_exists(&ctx)
+            && let Err(e) = generate_lock_file(&ctx)
+        {
+            eprint(format!("Failed to generate lock file: {}", e));
+        }
+
+        if failed > 0 {
+            Err(PpmError::Subprocess(format!(
+                "Failed to remove {} package(s)",
+                failed
+            )))
+        } else if blocked > 0 {
+            Err(PpmError::Other(format!(
+                "{} package(s) are still needed by other configured packages",
+                blocked
+            )))
+        } else if missing > 0 {
+            Err(PpmError::Other(format!(
+                "{} package(s) not found in project.toml",
+                missing
+            )))
+        } else {
+            Ok(())
+        }
     }
 }
 
 #[derive(Args, Debug)]
 pub struct RunScript {
-    /// Script Name
-    pub script_name: String,
+    /// Script name, or a glob like `test:*` running every script it matches; the project's default-script when left out
+    pub script_name: Option<String>,
+    /// Run every script whose name matches this glob, one after another in name order
+    #[clap(long = "filter", value_name = "PATTERN", conflicts_with = "script-name")]
+    pub filter: Option<String>,
+    /// Run the commands in a shell, even those of scripts with `shell = false`
+    #[clap(long = "shell", takes_value = false)]
+    pub shell: bool,
+    /// Run without the venv on PATH, as `use-venv = false` does
+    #[clap(long = "system", takes_value = false)]
+    pub system: bool,
 }
 
 impl RunScript {
-    pub fn run_script(&self) {
-        let config_file = get_project_config_file();
-        if !Path::new(config_file).exists() {
-            eprint(format!("Could not find {}", config_file));
-            return;
+    pub fn run_script(&self, prompter: &dyn Prompter) -> Result<(), PpmError> {
+        let mut ctx = load_project()?;
+        ctx.system_python |= self.system;
+        let target = Target::new(self.script_name.as_deref(), self.filter.as_deref());
+        // The project's entry points live in the venv
+        if let Target::Name(name) = &target
+            && !ctx.system_python
+        {
+            let entry_points = ctx.entry_points();
+            if ctx.config.scripts.contains_key(name) {
+                if entry_points.iter().any(|ep| &ep.name == name) {
+                    wprint(format!(
+                        "'{}' is both in [scripts] and an entry point of the project; running the script",
+                        name
+                    ));
+                }
+            } else if let Some(script) =
+                scripts::entry_point_script(&entry_points, name, &ctx.venv_dir())?
+            {
+                return crate::ppm_functions::run_script(&ctx, &script, prompter);
+            }
         }
-
-        let conf = match Config::load_from_file(config_file) {
-            Ok(conf) => conf,
-            Err(e) => {
-                eprint(e.to_string());
-                return;
+        let mut scripts = scripts::select(&ctx.config, &target)?;
+        if self.shell {
+            for script in &mut scripts {
+                script.shell = true;
             }
-        };
+        }
+        match target {
+            Target::Pattern(_) => crate::ppm_functions::run_scripts(&ctx, &scripts, prompter),
+            _ => crate::ppm_functions::run_script(&ctx, &scripts[0], prompter),
+        }
+    }
+}
 
-        let cmd_str = match conf.scripts.get(&self.script_name) {
-            Some(cmd) => cmd,
-            None => {
-                eprint(format!(
-                    "Script with name '{}' does not exist",
-                    self.script_name
-                ));
-                return;
-            }
-        };
+#[derive(Args, Debug, Default)]
+pub struct Installer {
+    /// Install a requirements file into the venv with pip, leaving
+    /// project.toml alone; may be given more than once
+    #[clap(
+        short = 'r',
+        long = "requirement",
+        alias = "requirements",
+        value_name = "FILE",
+        multiple_occurrences = true
+    )]
+    pub requirements: Vec<String>,
+    /// Also record the requirements' packages in project.toml
+    #[clap(long = "record", takes_value = false, requires = "requirements")]
+    pub record: bool,
+    /// When requirements list a package twice, keep the higher version
+    #[clap(
+        long = "prefer-newer",
+        takes_value = false,
+        conflicts_with = "prefer-first",
+        requires = "record"
+    )]
+    pub prefer_newer: bool,
+    /// When requirements list a package twice, keep the first entry
+    #[clap(long = "prefer-first", takes_value = false, requires = "record")]
+    pub prefer_first: bool,
+    /// Install the project itself in editable mode (`-e .`) and set
+    /// install_self in project.toml
+    #[clap(short = 'e', long = "editable", conflicts_with = "requirements")]
+    pub editable: Option<String>,
+    /// Run up to N pip processes at once (default and 0: one per core, at most 4; 1: a single pip run)
+    #[clap(short = 'j', long = "jobs", value_name = "N")]
+    pub jobs: Option<usize>,
+}
 
-        let venv_root = conf.project.venv.as_deref().unwrap_or("venv");
+#[derive(Args, Debug)]
+pub struct VenvProject {
+    #[clap(subcommand)]
+    pub command: VenvCommand,
+}
 
-        let mut cmd = if cfg!(target_os = "windows") {
-            let mut c = Command::new("cmd");
-            c.arg("/C");
-            c
-        } else if cfg!(target_os = "linux") || cfg!(target_os = "macos") {
-            let mut c = Command::new("sh");
-            c.arg("-c");
-            c
-        } else {
-            eprint("Unsupported OS".to_owned());
-            return;
-        };
+#[derive(Subcommand, Debug)]
+pub enum VenvCommand {
+    /// Delete the venv, create it again with the configured Python and reinstall the packages
+    Recreate,
+}
 
-        let current_path = std::env::var_os("PATH").unwrap_or_default();
-        let mut paths = std::env::split_paths(&current_path).collect::<Vec<_>>();
-        paths.insert(0, std::path::PathBuf::from(get_venv_bin_dir(venv_root)));
-        if let Ok(new_path) = std::env::join_paths(paths) {
-            cmd.env("PATH", new_path);
+impl VenvProject {
+    pub fn run(&self, prompter: &dyn Prompter) -> Result<(), PpmError> {
+        match self.command {
+            VenvCommand::Recreate => recreate_venv(prompter),
         }
-        cmd.arg(cmd_str);
+    }
+}
 
-        match cmd.spawn() {
-            Ok(mut child) => {
-                if let Err(e) = child.wait() {
-                    eprint(format!("Error waiting for script: {}", e));
-                }
-            }
-            Err(e) => {
-                eprint(e.to_string());
-            }
+/// `ppm venv recreate`: replace the venv with a new one made by the
+/// interpreter project.toml names, then install the packages into it
+fn recreate_venv(prompter: &dyn Prompter) -> Result<(), PpmError> {
+    let mut ctx = load_project()?;
+    ctx.check_venv_in_use()?;
+    let _lock = lock_project(&ctx)?;
+    let venv_dir = ctx.venv_dir();
+    if venv_dir.exists() {
+        // Never remove something that isn't a venv, like a `venv = "."`
+        if normalize(&venv_dir) == normalize(&ctx.root) || !venv_dir.join("pyvenv.cfg").is_file() {
+            return Err(PpmError::Venv(format!(
+                "'{}' has no pyvenv.cfg and doesn't look like a virtual environment; remove it yourself to recreate it",
+                ppmm::paths::display(&venv_dir)
+            )));
         }
+        iprint(format!("Removing {}", ppmm::paths::display(&venv_dir)));
+        fs::remove_dir_all(&venv_dir)?;
     }
+    setup_project_venv(
+        ctx.runner.as_ref(),
+        &ctx.root,
+        ctx.venv_root(),
+        ctx.shared.python.as_deref(),
+    )?;
+    Installer::default().install_into(&mut ctx, prompter)
 }
 
-#[derive(Args, Debug)]
-pub struct Installer {
-    /// Install from requirements
-    #[clap(short = 'r', long = "requirements", default_value = "")]
-    pub requirements: String,
+/// Offer the `--index-url` of a requirements file as the project's
+/// `index-url`; declining keeps installing from the configured index
+fn adopt_index(ctx: &mut ProjectContext, index: &IndexOption, prompter: &dyn Prompter) {
+    if ctx.shared.index_url.as_deref() == Some(index.url.as_str()) {
+        return;
+    }
+    let question = format!(
+        "{} installs from {}; use it as index-url in project.toml?",
+        index.origin, index.url
+    );
+    if !prompter.ask_yes_no(&question, false) {
+        wprint(format!(
+            "Installing from {} instead of {}",
+            ctx.shared.index_url.as_deref().unwrap_or("PyPI"),
+            index.url
+        ));
+        return;
+    }
+    ctx.config.project.index_url = Some(index.url.clone());
+    ctx.shared.index_url = Some(index.url.clone());
+    if let Some(api) = ppmm::index::json_api_url(&index.url) {
+        ctx.index_url = api;
+    }
 }
 
-impl Installer {
-    fn install_from_req(&self) {
-        let config_file = get_project_config_file();
-        if !Path::new(config_file).exists() {
-            eprint(format!("Could not find {}", config_file));
-            return;
-        }
+/// The requirements of the files at `paths`, one per package as `prefer`
+/// decides, and the first `--index-url` among them. Lines project.toml
+/// can't hold are reported and left out; conflicting entries fail with
+/// `hint` on how to choose.
+fn read_requirement_files(
+    paths: &[String],
+    prefer: Option<Prefer>,
+    hint: &str,
+) -> Result<(Vec<Requirement>, Option<IndexOption>), PpmError> {
+    let mut req_file = RequirementsFile::default();
+    for path in paths {
+        let parsed = requirements::parse_file(Path::new(path), |path| fs::read_to_string(path))?;
+        req_file.requirements.extend(parsed.requirements);
+        req_file.index_url = req_file.index_url.or(parsed.index_url);
+        req_file.extra_index_urls.extend(parsed.extra_index_urls);
+        req_file.manual.extend(parsed.manual);
+        req_file.ignored.extend(parsed.ignored);
+    }
+    for line in &req_file.manual {
+        wprint(format!("Skipped {}", line));
+    }
+    for line in &req_file.ignored {
+        wprint(format!("Ignored {}", line));
+    }
+    for extra in &req_file.extra_index_urls {
+        wprint(format!(
+            "Ignored --extra-index-url {} ({}): project.toml has a single index-url",
+            extra.url, extra.origin
+        ));
+    }
 
-        let mut conf = match Config::load_from_file(config_file) {
-            Ok(conf) => conf,
-            Err(e) => {
-                eprint(e.to_string());
-                return;
-            }
+    let parsed: Vec<Requirement> = req_file
+        .requirements
+        .into_iter()
+        .map(|line| line.requirement)
+        .collect();
+    let entries: Vec<(String, Option<String>)> = parsed
+        .iter()
+        .map(|req| (req.name.clone(), req.pinned_version().map(String::from)))
+        .collect();
+    let kept = resolve_duplicates(&entries, prefer).map_err(|conflicts| {
+        let listed: Vec<String> = conflicts.iter().map(|c| c.to_string()).collect();
+        PpmError::Other(format!(
+            "Conflicting entries in {}: {}; {}",
+            paths.join(", "),
+            listed.join("; "),
+            hint
+        ))
+    })?;
+    let reqs = kept.into_iter().map(|i| parsed[i].clone()).collect();
+    Ok((reqs, req_file.index_url))
+}
+
+/// Write `reqs` into the `[packages]` of `ctx`, looking up the latest
+/// version of the unpinned ones; returns the names recorded and how many
+/// couldn't be looked up
+fn record_requirements(
+    ctx: &mut ProjectContext,
+    reqs: &[Requirement],
+    label: &str,
+) -> (Vec<String>, usize) {
+    let progress = Progress::bar(Phase::Resolve, reqs.len(), label);
+    let mut recorded = vec![];
+    let mut unresolved = 0;
+    for req in reqs {
+        let vname = req.name.clone();
+        progress.start_item(&vname);
+        let marker = req.marker.as_deref();
+        let spec = match (&req.url, req.pinned_version()) {
+            // Direct references have no index version to look up
+            (Some(url), _) => PackageSpec::direct(url, marker),
+            (None, Some(v)) => PackageSpec::new(v, marker),
+            (None, None) => match ctx.latest_version(&vname) {
+                Ok(v) => PackageSpec::new(&v, marker),
+                Err(e) => {
+                    progress.fail_item(format!("Failed to get version for '{}': {}", vname, e));
+                    unresolved += 1;
+                    continue;
+                }
+            },
         };
 
-        let venv_root = conf.project.venv.clone().unwrap_or_else(|| "venv".to_string());
+        replace_package(&mut ctx.config, req, spec);
+        recorded.push(vname);
+        progress.finish_item();
+    }
+    progress.finish();
+    (recorded, unresolved)
+}
 
-        if !check_venv_dir_exists(&venv_root) {
-            wprint(format!("Could not find '{}' directory", venv_root));
-            if ask_if_create_venv() {
-                if let Err(e) = setup_venv(format!("./{}", venv_root)) {
-                    eprint(format!("Failed to setup venv: {}", e));
-                    return;
-                }
-            } else {
-                wprint("Installation Cancelled".to_owned());
-                return;
+impl Installer {
+    /// `pip install -r` each requirements file into the venv, with pip's
+    /// output shown as it runs, without touching project.toml
+    fn install_req_files(&self, prompter: &dyn Prompter) -> Result<(), PpmError> {
+        for path in &self.requirements {
+            if !Path::new(path).is_file() {
+                return Err(PpmError::Other(format!(
+                    "Requirements file {} not found",
+                    path
+                )));
             }
         }
+        let ctx = load_project()?;
+        if !ensure_venv(&ctx, prompter)? {
+            wprint("Installation Cancelled".to_owned());
+            return Ok(());
+        }
 
-        let req_file = match fs::read_to_string(&self.requirements) {
-            Ok(f) => f,
-            Err(e) => {
-                eprint(format!("Failed to read {}: {}", self.requirements, e));
-                return;
-            }
+        let mut args = vec!["-m".to_string(), "pip".to_string(), "install".to_string()];
+        for path in &self.requirements {
+            // pip runs in the project root, which may not be the current directory
+            args.push("-r".to_string());
+            args.push(std::path::absolute(path)?.to_string_lossy().into_owned());
+        }
+        args.extend(ctx.constraint_args()?);
+        iprint(format!(
+            "Installing {} into the venv; project.toml is left as it is",
+            self.requirements.join(", ")
+        ));
+        let options = RunOptions {
+            inherit_stdio: true,
+            ..RunOptions::in_dir(&ctx.root)
         };
+        let status = ctx
+            .runner
+            .run(
+                ctx.venv_python().as_os_str(),
+                &runner::args(&args),
+                &options,
+            )
+            .map_err(|e| PpmError::Subprocess(format!("Failed to execute pip: {}", e)))?;
+        if !status.success() {
+            return Err(PpmError::ChildExit {
+                name: "pip install -r".to_string(),
+                code: status.code,
+            });
+        }
+        iprint("Installed; pass --record to add the packages to project.toml".to_string());
+        Ok(())
+    }
 
-        let pkg_names: Vec<&str> = req_file
-            .lines()
-            .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
-            .collect();
+    fn install_from_req(&self, prompter: &dyn Prompter) -> Result<(), PpmError> {
+        let mut ctx = load_project()?;
+        let _lock = lock_project(&ctx)?;
+
+        let prefer = if self.prefer_newer {
+            Some(Prefer::Newer)
+        } else if self.prefer_first {
+            Some(Prefer::First)
+        } else {
+            None
+        };
+        let (reqs, index) = read_requirement_files(
+            &self.requirements,
+            prefer,
+            "pass --prefer-newer or --prefer-first to choose",
+        )?;
+        if let Some(index) = &index {
+            adopt_index(&mut ctx, index, prompter);
+        }
+
+        if !ensure_venv(&ctx, prompter)? {
+            wprint("Installation Cancelled".to_owned());
+            return Ok(());
+        }
 
-        if pkg_names.is_empty() {
+        if reqs.is_empty() {
             wprint("No packages found in requirements file".to_owned());
-            return;
-        }
-
-        let pkg_names_string: Vec<String> = pkg_names.iter().map(|&s| s.to_string()).collect();
-
-        match install_packages_batch(&pkg_names_string, &venv_root) {
-            Ok(_) => {
-                for pkg_name in pkg_names {
-                    let (vname, ver) = parse_version(pkg_name);
-                    let version = match ver {
-                        Some(v) => v,
-                        None => match get_pkg_version(&vname) {
-                            Ok(v) => v,
-                            Err(e) => {
-                                eprint(format!("Failed to get version for '{}': {}", vname, e));
-                                continue;
-                            }
-                        },
-                    };
-
-                    conf.packages.insert(vname.clone(), version);
-                    iprint(format!("Package '{}' installed successfully", &vname));
-                }
-                
-                match conf.write_to_file(config_file) {
-                    Ok(_) => {},
-                    Err(e) => eprint(e.to_string()),
-                }
-            }
-            Err(e) => {
-                eprint(e);
-            }
+            return Ok(());
+        }
+
+        install_packages_batch(&reqs, &ctx)?;
+
+        let (installed, unresolved) = record_requirements(&mut ctx, &reqs, "Recording");
+        for vname in installed {
+            iprint(format!("Package '{}' installed successfully", &vname));
         }
 
-        if let Err(e) = generate_lock_file(&venv_root) {
+        save_project(&mut ctx, Some(prompter))?;
+
+        if let Err(e) = generate_lock_file(&ctx) {
             eprint(format!("Failed to generate lock file: {}", e));
         }
+
+        if unresolved > 0 {
+            return Err(PpmError::Network(format!(
+                "Could not resolve {} package(s)",
+                unresolved
+            )));
+        }
+        Ok(())
     }
 
-    pub fn install_packages(&self) {
+    pub fn install_packages(&self, prompter: &dyn Prompter) -> Result<(), PpmError> {
+        if self.record {
+            return self.install_from_req(prompter);
+        }
         if !self.requirements.is_empty() {
-            self.install_from_req();
-            return;
+            return self.install_req_files(prompter);
         }
 
-        let config_file = get_project_config_file();
-        if !Path::new(config_file).exists() {
-            eprint(format!("Could not find {}", config_file));
-            return;
-        }
+        let mut ctx = load_project()?;
+        let _lock = lock_project(&ctx)?;
+        self.install_into(&mut ctx, prompter)
+    }
 
-        let conf = match Config::load_from_file(config_file) {
-            Ok(conf) => conf,
-            Err(e) => {
-                eprint(e.to_string());
-                return;
+    /// Install the packages of the loaded project `ctx`, from ppmm.lock
+    /// when there is one
+    fn install_into(
+        &self,
+        ctx: &mut ProjectContext,
+        prompter: &dyn Prompter,
+    ) -> Result<(), PpmError> {
+        let mark_self = match &self.editable {
+            Some(path) => {
+                if normalize(&std::path::absolute(path)?) != normalize(&ctx.root) {
+                    return Err(PpmError::Other(format!(
+                        "Only the project itself can be installed with -e; add '{}' to [packages] as {{ path = \"...\", editable = true }} instead",
+                        path
+                    )));
+                }
+                !ctx.config.installs_self()
             }
+            None => false,
         };
+        let installs_self = mark_self || ctx.config.installs_self();
+        if installs_self {
+            ctx.check_self_installable()?;
+        }
 
-        if conf.packages.is_empty() {
+        let conf = &ctx.config;
+        let has_packages = conf.packages.values().any(|spec| !spec.is_self());
+        if !has_packages && !installs_self {
             wprint("No packages to install".to_owned());
-            return;
+            return Ok(());
         }
 
-        let venv_root = conf.project.venv.as_deref().unwrap_or("venv");
-
-        if !check_venv_dir_exists(venv_root) {
-            wprint(format!("Could not find '{}' directory", venv_root));
-            if ask_if_create_venv() {
-                if let Err(e) = setup_venv(format!("./{}", venv_root)) {
-                    eprint(format!("Failed to setup venv: {}", e));
-                    return;
-                }
-            } else {
-                wprint("Installation Cancelled".to_owned());
-                return;
-            }
+        if !ensure_venv(ctx, prompter)? {
+            wprint("Installation Cancelled".to_owned());
+            return Ok(());
         }
 
-        if Path::new("ppmm.lock").exists() {
+        let lock_file = ctx.root.join(get_lock_file());
+        let mut from_lock = false;
+        if lock_file.exists() {
             iprint("Found ppmm.lock, installing from lock file...".to_string());
-            let output = Command::new(get_venv_pip_path(venv_root))
-                .arg("install")
-                .arg("-r")
-                .arg("ppmm.lock")
-                .output();
+            let mut args = runner::args(["install", "-r"]);
+            args.push(lock_file.clone().into());
+            args.extend(runner::args(ctx.constraint_args()?));
+            let output = ctx
+                .runner
+                .run(ctx.venv_pip().as_os_str(), &args, &RunOptions::default());
 
             match output {
                 Ok(out) => {
-                    if !out.status.success() {
+                    if !out.success() {
                         eprint(format!(
                             "Failed to install from lock file: {}",
-                            String::from_utf8_lossy(&out.stderr)
+                            out.stderr_lossy()
                         ));
                     } else {
-                        println!("{}", String::from_utf8_lossy(&out.stdout));
+                        println!("{}", out.stdout_lossy());
                         iprint("Installed from ppmm.lock successfully".to_string());
-                        return;
+                        from_lock = true;
                     }
                 }
                 Err(e) => {
-                    eprint(format!("Failed to execute pip: {}", e));
-                    return;
+                    return Err(PpmError::Subprocess(format!("Failed to execute pip: {}", e)));
                 }
             }
         }
 
-        let mut packages_to_install: Vec<String> = vec![];
-        for (name, version) in conf.packages.iter() {
-            packages_to_install.push(format!("{}=={}", name, version));
-        }
+        if !from_lock {
+            let mut names: Vec<&String> = conf
+                .packages
+                .iter()
+                .filter(|(_, spec)| !spec.is_self())
+                .map(|(name, _)| name)
+                .collect();
+            names.sort();
+            let mut applicable = vec![];
+            for name in names {
+                if matches_environment(ctx, name, &conf.packages[name])? {
+                    applicable.push(name);
+                }
+            }
+            if applicable.is_empty() && has_packages {
+                iprint("No packages apply to this environment".to_owned());
+            }
 
-        // Batched pip install for better performance
-        match install_packages_batch(&packages_to_install, venv_root) {
-            Ok(_) => {
-                for (name, _) in conf.packages.iter() {
-                     iprint(format!("Package '{}' installed", name));
+            let tasks: Vec<InstallTask> = applicable
+                .iter()
+                .map(|name| InstallTask::new(name, conf.packages[*name].pip_args(name)))
+                .collect();
+
+            let jobs = install::resolve_jobs(self.jobs, conf.project.jobs);
+            let report = install_tasks(&tasks, ctx, jobs);
+            for name in applicable {
+                if report.succeeded(name) {
+                    iprint(format!("Package '{}' installed", name));
                 }
             }
-            Err(e) => eprint(format!("Failed to install packages: {}", e)),
+            if !report.skipped.is_empty() {
+                return Err(PpmError::Interrupted(format!(
+                    "Interrupted before installing {}; run `ppm install` again to finish",
+                    report.skipped.join(", ")
+                )));
+            }
+            if let Some(e) = report.error {
+                // A single pip run fails as a whole; its error says it all
+                if jobs > 1 {
+                    for (name, reason) in &report.failed {
+                        eprint(format!("Failed to install '{}': {}", name, reason));
+                    }
+                }
+                return Err(e);
+            }
         }
 
-        if let Err(e) = generate_lock_file(venv_root) {
+        // After the dependencies, so pip doesn't resolve them on its own
+        if installs_self {
+            install_self(ctx)?;
+        }
+        if mark_self {
+            ctx.config.project.install_self = true;
+            save_project(ctx, None)?;
+        }
+
+        if !from_lock && let Err(e) = generate_lock_file(ctx) {
             eprint(format!("Failed to generate lock file: {}", e));
         }
+        Ok(())
     }
 }
 
 #[derive(Args, Debug)]
-pub struct BuildProject;
+pub struct StartProject {
+    /// Run under debugpy, listening on PORT (5678 when omitted)
+    #[clap(long = "debug", value_name = "PORT")]
+    pub debug: Option<Option<u16>>,
+    /// Like --debug, but start the script without waiting for a debugger
+    #[clap(long = "debug-no-wait", takes_value = false)]
+    pub debug_no_wait: bool,
+    /// Profile the run with cProfile and print the slowest functions
+    #[clap(
+        long = "profile",
+        takes_value = false,
+        conflicts_with_all = &["debug", "debug-no-wait"]
+    )]
+    pub profile: bool,
+    /// Sort the profile summary by time including callees or by own time
+    #[clap(
+        long = "profile-sort",
+        default_value = "cumulative",
+        value_parser = ["cumulative", "tottime"],
+        requires = "profile"
+    )]
+    pub profile_sort: String,
+    /// Number of functions in the profile summary
+    #[clap(
+        long = "profile-top",
+        value_name = "N",
+        default_value_t = launch::DEFAULT_PROFILE_TOP,
+        requires = "profile"
+    )]
+    pub profile_top: usize,
+    /// Run with the system Python instead of the venv, as `use-venv = false` does
+    #[clap(long = "system", takes_value = false)]
+    pub system: bool,
+    /// Arguments for the main script, after `--`
+    #[clap(last = true)]
+    pub args: Vec<String>,
+}
 
-impl BuildProject {
-    pub fn build_project(&self) {
-        let config_file = get_project_config_file();
-        if !Path::new(config_file).exists() {
-            eprint(format!("Could not find {}", config_file));
-            return;
+impl StartProject {
+    /// How the main script should be run
+    pub fn launch(&self) -> Launch {
+        if self.profile {
+            return Launch::Profile {
+                // Relative to the project root, where the script runs
+                output: Path::new(STATE_DIR).join(launch::PROFILE_FILE),
+                sort: ProfileSort::from_name(&self.profile_sort).unwrap_or_default(),
+                top: self.profile_top,
+            };
         }
+        if self.debug.is_none() && !self.debug_no_wait {
+            return Launch::Direct;
+        }
+        Launch::Debug {
+            port: self.debug.flatten().unwrap_or(launch::DEFAULT_DEBUG_PORT),
+            wait: !self.debug_no_wait,
+        }
+    }
+
+    pub fn start_project(&self, prompter: &dyn Prompter) -> Result<(), PpmError> {
+        crate::ppm_functions::start_project(self.launch(), &self.args, self.system, prompter)
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct RunTests {
+    /// Run the tests under coverage.py and report what they covered
+    #[clap(long = "coverage", takes_value = false)]
+    pub coverage: bool,
+    /// Like --coverage, and also write an HTML report to htmlcov/
+    #[clap(long = "coverage-html", takes_value = false)]
+    pub coverage_html: bool,
+    /// Arguments for the test runner, after `--`
+    #[clap(last = true)]
+    pub args: Vec<String>,
+}
+
+impl RunTests {
+    pub fn run_tests(&self, prompter: &dyn Prompter) -> Result<(), PpmError> {
+        crate::ppm_functions::run_tests(
+            prompter,
+            &self.args,
+            self.coverage || self.coverage_html,
+            self.coverage_html,
+        )
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct RunPreset {
+    /// Arguments for the formatter or linter, after `--`
+    #[clap(last = true)]
+    pub args: Vec<String>,
+}
+
+impl RunPreset {
+    pub fn run(&self, task: Task, prompter: &dyn Prompter) -> Result<(), PpmError> {
+        crate::ppm_functions::run_preset(task, &self.args, prompter)
+    }
+}
 
-        let conf = match Config::load_from_file(config_file) {
-            Ok(conf) => conf,
-            Err(e) => {
-                eprint(e.to_string());
-                return;
+#[derive(Args, Debug)]
+pub struct ShowEnv {
+    /// Print lines that activate the venv, for `eval "$(ppm env --activate bash)"`
+    #[clap(long = "activate", value_name = "SHELL", value_parser = ["bash", "fish", "powershell"])]
+    pub activate: Option<String>,
+}
+
+impl ShowEnv {
+    pub fn show_env(&self) -> Result<(), PpmError> {
+        crate::ppm_functions::show_env(self.activate.as_deref().and_then(ActivateShell::from_name))
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct IdeCommand {
+    #[clap(subcommand)]
+    pub command: IdeAction,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum IdeAction {
+    /// Set the venv interpreter in .vscode/settings.json, keeping everything else
+    Vscode(VscodeConfig),
+    /// Show how to add the venv interpreter to PyCharm
+    Pycharm,
+}
+
+#[derive(Args, Debug)]
+pub struct VscodeConfig {
+    /// Only show what would change
+    #[clap(long)]
+    pub print: bool,
+    /// Only set the interpreter, not the test and formatter settings
+    #[clap(long)]
+    pub interpreter_only: bool,
+}
+
+impl IdeCommand {
+    pub fn run(&self) -> Result<(), PpmError> {
+        match &self.command {
+            IdeAction::Vscode(config) => {
+                crate::ppm_functions::configure_vscode(config.print, config.interpreter_only)
             }
-        };
+            IdeAction::Pycharm => crate::ppm_functions::show_pycharm_setup(),
+        }
+    }
+}
 
-        // Check if build script exists
+#[derive(Args, Debug)]
+pub struct BuildProject {
+    /// Only build a wheel (pyproject.toml projects)
+    #[clap(long, conflicts_with = "sdist-only")]
+    pub wheel_only: bool,
+    /// Only build an sdist (pyproject.toml projects)
+    #[clap(long)]
+    pub sdist_only: bool,
+    /// Directory for the built distributions (default: dist)
+    #[clap(long, value_name = "DIR")]
+    pub out_dir: Option<PathBuf>,
+    /// Install `build` into the project's venv instead of a separate
+    /// build environment
+    #[clap(long)]
+    pub in_venv: bool,
+}
+
+impl BuildProject {
+    /// Whether any option for `python -m build` was given
+    fn packaging_flags(&self) -> bool {
+        self.wheel_only || self.sdist_only || self.out_dir.is_some() || self.in_venv
+    }
+
+    pub fn build_project(&self) -> Result<(), PpmError> {
+        let ctx = load_project()?;
+        let conf = &ctx.config;
+
+        // A build script takes precedence over building a pyproject.toml
         let build_script = match conf.scripts.get("build") {
+            Some(_) if self.packaging_flags() => {
+                return Err(PpmError::Config(
+                    "--wheel-only, --sdist-only, --out-dir and --in-venv only apply without a 'build' script in project.toml".to_string(),
+                ));
+            }
             Some(script) => script,
+            None if ctx.root.join(ppmm::build::PYPROJECT_FILE).is_file() => {
+                return crate::ppm_functions::build_distributions(
+                    &ctx,
+                    ppmm::build::Targets::from_flags(self.wheel_only, self.sdist_only),
+                    self.out_dir.as_deref(),
+                    self.in_venv,
+                );
+            }
             None => {
-                wprint("No 'build' script defined in project.toml".to_string());
-                wprint("Add a [scripts] section with 'build = \"your build command\"'".to_string());
-                return;
+                wprint("Add a [scripts] section with 'build = \"your build command\"', or a pyproject.toml".to_string());
+                return Err(PpmError::Config(
+                    "No 'build' script defined in project.toml".to_string(),
+                ));
             }
         };
 
         iprint(format!("Building project: {}", conf.project.name));
 
-        let venv_root = conf.project.venv.as_deref().unwrap_or("venv");
-
-        let mut cmd = if cfg!(target_os = "windows") {
-            let mut c = Command::new("cmd");
-            c.arg("/C");
-            c
-        } else if cfg!(target_os = "linux") || cfg!(target_os = "macos") {
-            let mut c = Command::new("sh");
-            c.arg("-c");
-            c
-        } else {
-            eprint("Unsupported OS".to_owned());
-            return;
-        };
-
-        let current_path = std::env::var_os("PATH").unwrap_or_default();
-        let mut paths = std::env::split_paths(&current_path).collect::<Vec<_>>();
-        paths.insert(0, std::path::PathBuf::from(get_venv_bin_dir(venv_root)));
-        if let Ok(new_path) = std::env::join_paths(paths) {
-            cmd.env("PATH", new_path);
+        let mut cmd = shell_command(&ctx, build_script.local_command("build")?)?;
+
+        Event::new(Phase::Script, Status::Start).script("build").emit();
+        let status = cmd.spawn().and_then(|mut child| child.wait()).map_err(|e| {
+            Event::new(Phase::Script, Status::Fail)
+                .script("build")
+                .message(&e.to_string())
+                .emit();
+            PpmError::Subprocess(format!("Failed to execute build script: {}", e))
+        })?;
+        Event::new(Phase::Script, Status::Finish)
+            .script("build")
+            .exit_code(status.code())
+            .emit();
+
+        if !status.success() {
+            return Err(PpmError::ChildExit {
+                name: "build".to_string(),
+                code: status.code(),
+            });
         }
-        cmd.arg(build_script);
+        iprint("Build completed successfully".to_string());
+        Ok(())
+    }
+}
 
-        match cmd.spawn() {
-            Ok(mut child) => {
-                if let Err(e) = child.wait() {
-                    eprint(format!("Error waiting for build script: {}", e));
-                    return;
-                }
-                iprint("Build completed successfully".to_string());
-            }
-            Err(e) => {
-                eprint(format!("Failed to execute build script: {}", e));
+#[derive(Args, Debug)]
+pub struct PublishProject {
+    /// Run `ppm build` first
+    #[clap(long)]
+    pub build: bool,
+    /// Directory holding the distributions (default: dist)
+    #[clap(long, value_name = "DIR")]
+    pub dist_dir: Option<PathBuf>,
+    /// Upload URL of the index (default: $TWINE_REPOSITORY_URL, then PyPI)
+    #[clap(long, value_name = "URL")]
+    pub repository_url: Option<String>,
+    /// Only run `twine check` on the distributions
+    #[clap(long)]
+    pub dry_run: bool,
+    /// Skip files that are already on the index instead of refusing
+    #[clap(long)]
+    pub skip_existing: bool,
+}
+
+impl PublishProject {
+    pub fn publish(&self) -> Result<(), PpmError> {
+        let ctx = load_project()?;
+        let repository_url = self
+            .repository_url
+            .clone()
+            .or_else(|| {
+                std::env::var(ppmm::publish::REPOSITORY_URL_ENV)
+                    .ok()
+                    .filter(|url| !url.is_empty())
+            })
+            .unwrap_or_else(|| ppmm::publish::PYPI_UPLOAD_URL.to_string());
+
+        crate::ppm_functions::check_unpublished(&ctx, &repository_url, self.skip_existing)?;
+        if self.build {
+            BuildProject {
+                wheel_only: false,
+                sdist_only: false,
+                out_dir: self.dist_dir.clone(),
+                in_venv: false,
             }
+            .build_project()?;
         }
+        let dist_dir = ctx.root.join(
+            self.dist_dir
+                .as_deref()
+                .unwrap_or(Path::new(ppmm::build::DEFAULT_OUT_DIR)),
+        );
+        crate::ppm_functions::publish_distributions(
+            &ctx,
+            &dist_dir,
+            &repository_url,
+            self.dry_run,
+            self.skip_existing,
+        )
     }
 }
 
@@ -672,47 +1915,120 @@ pub struct BumpVersion {
     /// Version bump type: major, minor, or patch
     #[clap(value_parser = ["major", "minor", "patch"])]
     pub bump_type: String,
+    /// Also rewrite `__version__` in the main module or version-file
+    #[clap(long = "sync-source", takes_value = false)]
+    pub sync_source: bool,
+    /// Commit the bump and tag it `v<version>`; the work tree must be clean
+    #[clap(long = "tag", takes_value = false)]
+    pub tag: bool,
+    /// Prefix of the tag created with --tag
+    #[clap(
+        long = "tag-prefix",
+        value_name = "PREFIX",
+        default_value = bump::DEFAULT_TAG_PREFIX,
+        requires = "tag"
+    )]
+    pub tag_prefix: String,
+    /// Show what would change without changing anything
+    #[clap(long = "dry-run", takes_value = false)]
+    pub dry_run: bool,
 }
 
 impl BumpVersion {
-    pub fn bump_version(&self) {
-        let config_file = get_project_config_file();
-        if !Path::new(config_file).exists() {
-            eprint(format!("Could not find {}", config_file));
-            return;
-        }
-
-        let mut conf = match Config::load_from_file(config_file) {
-            Ok(conf) => conf,
-            Err(e) => {
-                eprint(e.to_string());
-                return;
+    pub fn bump_version(&self) -> Result<(), PpmError> {
+        let mut ctx = load_project()?;
+        let _lock = lock_project(&ctx)?;
+
+        let current_version = ctx.config.project.version.clone();
+        let new_version = bump_semantic_version(&current_version, &self.bump_type)
+            .map_err(|e| PpmError::Config(format!("Failed to bump version: {}", e)))?;
+
+        // Everything that could fail is checked before any file changes
+        let tag = format!("{}{}", self.tag_prefix, new_version);
+        if self.tag {
+            if !git::is_clean(&ctx.root)? {
+                return Err(PpmError::Other(
+                    "The working tree has uncommitted changes; commit or stash them before `ppm bump --tag`"
+                        .to_string(),
+                ));
             }
-        };
-
-        let current_version = conf.project.version.clone();
-        let new_version = match bump_semantic_version(&current_version, &self.bump_type) {
-            Ok(v) => v,
-            Err(e) => {
-                eprint(format!("Failed to bump version: {}", e));
-                return;
+            if git::has_tag(&ctx.root, &tag)? {
+                return Err(PpmError::Other(format!("Tag '{}' already exists", tag)));
             }
+        }
+        let source = if self.sync_source {
+            let (path, source) = bump::find_version_file(&ctx.root, &ctx.config.project)?;
+            let old = bump::find_assignment(&source).map(|found| found.version);
+            let rewritten = bump::rewrite_version(&source, &new_version).unwrap_or(source);
+            Some((path, old.unwrap_or_default(), rewritten))
+        } else {
+            None
         };
 
-        conf.project.version = new_version.clone();
-
-        match conf.write_to_file(config_file) {
-            Ok(_) => {
+        let root = ctx.root.clone();
+        let source_name = |path: &Path| {
+            normalize(path.strip_prefix(&root).unwrap_or(path))
+                .display()
+                .to_string()
+        };
+        if self.dry_run {
+            iprint(format!(
+                "Would bump version: {} {} {}",
+                current_version.bright_cyan(),
+                theme().arrow,
+                new_version.bright_green()
+            ));
+            if let Some((path, old, _)) = &source {
                 iprint(format!(
-                    "Version bumped: {} → {}",
-                    current_version.bright_cyan(),
+                    "Would set __version__ in {}: {} {} {}",
+                    source_name(path),
+                    old.bright_cyan(),
+                    theme().arrow,
                     new_version.bright_green()
                 ));
             }
-            Err(e) => {
-                eprint(format!("Failed to update project.toml: {}", e));
+            if self.tag {
+                iprint(format!(
+                    "Would commit the bump and tag it {}",
+                    tag.bright_green()
+                ));
             }
+            return Ok(());
         }
+
+        ctx.config.project.version = new_version.clone();
+        save_project(&mut ctx, None)?;
+        iprint(format!(
+            "Version bumped: {} {} {}",
+            current_version.bright_cyan(),
+            theme().arrow,
+            new_version.bright_green()
+        ));
+        let mut changed = vec![ctx.config_path.clone()];
+        if let Some((path, old, rewritten)) = source {
+            fs::write(&path, rewritten)?;
+            iprint(format!(
+                "Set __version__ in {}: {} {} {}",
+                source_name(&path),
+                old.bright_cyan(),
+                theme().arrow,
+                new_version.bright_green()
+            ));
+            changed.push(path);
+        }
+        if self.tag {
+            git::commit_and_tag(
+                &ctx.root,
+                &changed,
+                &format!("Bump version to {}", new_version),
+                &tag,
+            )?;
+            iprint(format!(
+                "Committed the bump and tagged it {}",
+                tag.bright_green()
+            ));
+        }
+        Ok(())
     }
 }
 
@@ -720,14 +2036,578 @@ impl BumpVersion {
 pub struct UpdatePackage {
     /// List of packages to update
     pub pkg_names: Vec<String>,
+    /// Show what would be updated without installing anything
+    #[clap(long = "dry-run", takes_value = false)]
+    pub dry_run: bool,
+    /// Re-resolve the branch or tag of git packages to its latest commit
+    #[clap(long = "refresh-git", takes_value = false)]
+    pub refresh_git: bool,
+    /// Run up to N pip processes at once (default and 0: one per core, at most 4; 1: a single pip run)
+    #[clap(short = 'j', long = "jobs", value_name = "N")]
+    pub jobs: Option<usize>,
+    /// Apply the updates without asking for confirmation
+    #[clap(short = 'y', long = "yes", takes_value = false)]
+    pub yes: bool,
+    /// Also reinstall packages the venv already has at their version, with pip's --force-reinstall
+    #[clap(long = "force-reinstall", takes_value = false)]
+    pub force_reinstall: bool,
+    /// Exit non-zero when a pin left in place is on a yanked release
+    #[clap(long = "deny-yanked", takes_value = false)]
+    pub deny_yanked: bool,
+    /// Install the packages an update stopped with Ctrl+C didn't get to
+    #[clap(
+        long = "resume",
+        takes_value = false,
+        conflicts_with_all = &["pkg-names", "dry-run", "refresh-git", "latest"]
+    )]
+    pub resume: bool,
+    /// Move packages to their newest release even past a `patch` or `minor` update policy
+    #[clap(long = "latest", takes_value = false)]
+    pub latest: bool,
+    /// With --latest, also move packages whose update policy is `pin`
+    #[clap(long = "force", takes_value = false, requires = "latest")]
+    pub force: bool,
+    /// With --latest, how packages moved past their policy are written back: exact pins the new version, compatible keeps the policy
+    #[clap(long = "pin-style", value_parser = ["exact", "compatible"], requires = "latest")]
+    pub pin_style: Option<String>,
+    /// Summary format; markdown prints a table of the updated packages to paste into a pull request
+    #[clap(long = "format", default_value = "text", value_parser = ["text", "markdown"])]
+    pub format: String,
 }
 
 impl UpdatePackage {
-    pub fn update_package(&self) {
-        crate::ppm_functions::update_packages(self.pkg_names.as_slice());
+    fn mode(&self) -> UpdateMode {
+        if !self.latest {
+            return UpdateMode::Policy;
+        }
+        UpdateMode::Latest {
+            force: self.force,
+            style: match self.pin_style.as_deref() {
+                Some("exact") => PinStyle::Exact,
+                _ => PinStyle::Compatible,
+            },
+        }
+    }
+
+    pub fn update_package(&self, prompter: &dyn Prompter) -> Result<(), PpmError> {
+        crate::ppm_functions::update_packages(
+            self.pkg_names.as_slice(),
+            UpdateOptions {
+                dry_run: self.dry_run,
+                refresh_git: self.refresh_git,
+                jobs: self.jobs,
+                yes: self.yes,
+                force_reinstall: self.force_reinstall,
+                deny_yanked: self.deny_yanked,
+                resume: self.resume,
+                markdown: self.format == "markdown",
+                mode: self.mode(),
+            },
+            prompter,
+        )
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct ListPackages {
+    /// Only list packages whose name contains this, or matches it as a glob like `django-*`
+    pub filter: Option<String>,
+    /// Also show the latest version available on PyPI
+    #[clap(long = "outdated", takes_value = false)]
+    pub outdated: bool,
+    /// Sort rows by package name, configured version or install state
+    #[clap(long = "sort", default_value = "name", value_parser = ["name", "version", "installed"])]
+    pub sort: String,
+    /// Exit non-zero when a pinned version is yanked (with --outdated)
+    #[clap(long = "deny-yanked", takes_value = false, requires = "outdated")]
+    pub deny_yanked: bool,
+    /// Only list packages whose pinned release is older than this, like 180d, 8w or 6m (with --outdated)
+    #[clap(
+        long = "older-than",
+        value_name = "DURATION",
+        value_parser = age::parse_duration,
+        requires = "outdated"
+    )]
+    pub older_than: Option<u64>,
+    /// Output format; markdown prints a table with changelog links to paste into a pull request and implies --outdated
+    #[clap(long = "format", default_value = "table", value_parser = ["table", "markdown"])]
+    pub format: String,
+    /// List every distribution in the venv instead, as configured, transitive or unmanaged
+    #[clap(
+        long = "installed",
+        takes_value = false,
+        conflicts_with_all = &["outdated", "sort", "format"]
+    )]
+    pub installed: bool,
+    /// Only list what neither project.toml nor its packages need (with --installed)
+    #[clap(long = "unmanaged-only", takes_value = false, requires = "installed")]
+    pub unmanaged_only: bool,
+}
+
+impl ListPackages {
+    pub fn list_packages(&self) -> Result<(), PpmError> {
+        if self.installed {
+            return crate::ppm_functions::list_installed(
+                self.filter.as_deref(),
+                self.unmanaged_only,
+            );
+        }
+        crate::ppm_functions::list_packages(
+            self.filter.as_deref(),
+            self.outdated,
+            SortKey::from_name(&self.sort).unwrap_or_default(),
+            self.deny_yanked,
+            self.older_than,
+            self.format == "markdown",
+        )
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct ShowInfo {
+    /// Show this package's releases with their upload days instead of the project
+    pub package: Option<String>,
+    /// Show every package instead of the first 10
+    #[clap(long = "full", takes_value = false, conflicts_with = "limit")]
+    pub full: bool,
+    /// Show at most this many packages
+    #[clap(long = "limit", value_name = "N")]
+    pub limit: Option<usize>,
+    /// Don't look up the package's monthly downloads on pypistats.org
+    #[clap(long = "no-stats", takes_value = false, requires = "package")]
+    pub no_stats: bool,
+}
+
+impl ShowInfo {
+    pub fn show_info(&self) -> Result<(), PpmError> {
+        let limit = if self.full {
+            None
+        } else {
+            Some(self.limit.unwrap_or(listing::DEFAULT_INFO_LIMIT))
+        };
+        match &self.package {
+            Some(package) => {
+                crate::ppm_functions::show_package_info(package, limit, !self.no_stats)
+            }
+            None => crate::ppm_functions::show_project_info(limit),
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct GenRequirements {
+    /// Write constraints.txt pinning every package in ppmm.lock, dependencies included
+    #[clap(long = "constraints", takes_value = false)]
+    pub constraints: bool,
+    /// Write every package ppmm.lock pins, direct and transitive ones in separate sections
+    #[clap(
+        long = "from-lock",
+        takes_value = false,
+        conflicts_with = "constraints"
+    )]
+    pub from_lock: bool,
+    /// End each transitive line with `# via` and the packages requiring it
+    #[clap(long = "annotate", takes_value = false, requires = "from-lock")]
+    pub annotate: bool,
+    /// Leave the time out of the header, so regenerating an unchanged project changes nothing
+    #[clap(long = "reproducible", takes_value = false)]
+    pub reproducible: bool,
+}
+
+impl GenRequirements {
+    pub fn run(&self) -> Result<(), PpmError> {
+        if self.constraints {
+            return crate::ppm_functions::gen_constraints();
+        }
+        crate::ppm_functions::gen_requirements(self.from_lock, self.annotate, self.reproducible)
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct CheckProject {
+    #[clap(subcommand)]
+    pub command: Option<CheckCommand>,
+    /// Fail when a pinned version is yanked from the index
+    #[clap(long = "deny-yanked", takes_value = false)]
+    pub deny_yanked: bool,
+    /// Look up yanked releases on the index and resolve the packages with
+    /// pip, instead of only using cached data
+    #[clap(long = "online", takes_value = false)]
+    pub online: bool,
+    /// Checks not to run: config, duplicates, lock, requirements, venv, yanked, resolve
+    #[clap(
+        long = "skip",
+        value_name = "CHECK",
+        multiple_occurrences = true,
+        use_value_delimiter = true
+    )]
+    pub skip: Vec<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CheckCommand {
+    /// Find imports missing from project.toml and packages that are never imported
+    Imports(CheckImports),
+}
+
+#[derive(Args, Debug)]
+pub struct CheckImports {
+    /// Exit non-zero when an import is missing from project.toml
+    #[clap(long = "strict", takes_value = false)]
+    pub strict: bool,
+}
+
+impl CheckProject {
+    pub fn check_project(&self) -> Result<(), PpmError> {
+        match &self.command {
+            None => crate::ppm_functions::run_checks(self.online, self.deny_yanked, &self.skip),
+            Some(CheckCommand::Imports(imports)) => {
+                crate::ppm_functions::check_imports(imports.strict)
+            }
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct HooksProject {
+    #[clap(subcommand)]
+    pub command: HooksCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum HooksCommand {
+    /// Write the git hooks from [hooks] in project.toml, or a pre-commit
+    /// hook running `ppm check --skip venv`
+    Install(InstallHooks),
+    /// Remove the git hooks ppm installed
+    Uninstall,
+}
+
+#[derive(Args, Debug)]
+pub struct InstallHooks {
+    /// Hooks to install, like pre-push; those in [hooks] by default
+    pub hooks: Vec<String>,
+    /// Replace existing hooks ppm didn't install
+    #[clap(long = "force", takes_value = false)]
+    pub force: bool,
+}
+
+impl HooksProject {
+    pub fn run(&self) -> Result<(), PpmError> {
+        match &self.command {
+            HooksCommand::Install(install) => {
+                crate::ppm_functions::install_hooks(&install.hooks, install.force)
+            }
+            HooksCommand::Uninstall => crate::ppm_functions::uninstall_hooks(),
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct BundleProject {
+    #[clap(subcommand)]
+    pub command: BundleCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum BundleCommand {
+    /// Write project.toml, ppmm.lock and a download of every locked package
+    /// into a .tar.gz
+    Create(CreateBundle),
+    /// Unpack a bundle and install it into a new venv without an index
+    Install(InstallBundle),
+}
+
+#[derive(Args, Debug)]
+pub struct CreateBundle {
+    /// The archive to write, like demo.tar.gz
+    pub output: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct InstallBundle {
+    /// The archive `ppm bundle create` wrote
+    pub archive: PathBuf,
+    /// Directory to unpack the project into (default: the current one)
+    #[clap(long = "dir", value_name = "DIR")]
+    pub dir: Option<PathBuf>,
+    /// Replace a project.toml that is already there
+    #[clap(long = "force", takes_value = false)]
+    pub force: bool,
+}
+
+impl BundleProject {
+    /// Whether the command works on the project around the current
+    /// directory; `install` makes one instead
+    pub fn in_project(&self) -> bool {
+        matches!(self.command, BundleCommand::Create(_))
+    }
+
+    pub fn run(&self) -> Result<(), PpmError> {
+        match &self.command {
+            BundleCommand::Create(create) => crate::ppm_functions::create_bundle(&create.output),
+            BundleCommand::Install(install) => crate::ppm_functions::install_bundle(
+                &install.archive,
+                install.dir.as_deref(),
+                install.force,
+            ),
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct GraphProject {
+    /// Output format
+    #[clap(long = "format", default_value = "dot", value_parser = ["dot", "mermaid"])]
+    pub format: String,
+    /// Write the graph to this file instead of stdout
+    #[clap(long = "output", short = 'o', value_name = "FILE")]
+    pub output: Option<PathBuf>,
+    /// Only show this package and what it depends on
+    #[clap(long = "package", value_name = "NAME")]
+    pub package: Option<String>,
+}
+
+impl GraphProject {
+    pub fn run(&self) -> Result<(), PpmError> {
+        crate::ppm_functions::show_graph(
+            self.format.parse().unwrap_or_default(),
+            self.output.as_deref(),
+            self.package.as_deref(),
+        )
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct ReverseDeps {
+    /// The installed package to find the dependents of
+    pub package: String,
+}
+
+impl ReverseDeps {
+    pub fn run(&self) -> Result<(), PpmError> {
+        crate::ppm_functions::show_rdeps(&self.package)
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct CacheProject {
+    #[clap(subcommand)]
+    pub command: CacheCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CacheCommand {
+    /// Show where the project and global caches are and how big they are
+    Info,
+    /// Show every file and directory ppm reads or writes
+    Paths,
+    /// Remove cached PyPI metadata that is old or beyond the size budget
+    Prune(PruneCache),
+    /// Show where the venv's pip caches downloads and built wheels, and their size
+    PipInfo,
+    /// Empty pip's download and wheel cache with `pip cache purge`
+    PipPurge(PurgePipCache),
+}
+
+#[derive(Args, Debug)]
+pub struct PruneCache {
+    /// Shrink the metadata cache to this size, like 10MB; the configured cache-max-size when left out
+    #[clap(long = "max-size", value_name = "SIZE", value_parser = metadata_cache::parse_size)]
+    pub max_size: Option<u64>,
+    /// Remove entries not used for this long, like 30d, 8w or 6m
+    #[clap(long = "older-than", value_name = "DURATION", value_parser = age::parse_duration)]
+    pub older_than: Option<u64>,
+}
+
+#[derive(Args, Debug)]
+pub struct PurgePipCache {
+    /// Purge without asking for confirmation
+    #[clap(short = 'y', long = "yes", takes_value = false)]
+    pub yes: bool,
+}
+
+impl CacheProject {
+    pub fn run(&self, prompter: &dyn Prompter) -> Result<(), PpmError> {
+        match &self.command {
+            CacheCommand::Info => crate::ppm_functions::cache_info(),
+            CacheCommand::Paths => crate::ppm_functions::cache_paths(),
+            CacheCommand::Prune(prune) => {
+                crate::ppm_functions::cache_prune(prune.max_size, prune.older_than)
+            }
+            CacheCommand::PipInfo => crate::ppm_functions::pip_cache_info(),
+            CacheCommand::PipPurge(purge) => {
+                crate::ppm_functions::pip_cache_purge(purge.yes, prompter)
+            }
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct ConfigProject {
+    /// Use the global config.toml shared by every project
+    #[clap(long = "global", takes_value = false)]
+    pub global: bool,
+    #[clap(subcommand)]
+    pub command: ConfigCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Print a setting's value, nothing when it isn't set
+    Get(GetConfig),
+    /// Change a setting, keeping the rest of the file as it is
+    Set(SetConfig),
+}
+
+#[derive(Args, Debug)]
+pub struct GetConfig {
+    #[clap(value_parser = ["update-check", "author", "cache-max-size", "theme"])]
+    pub key: String,
+}
+
+#[derive(Args, Debug)]
+pub struct SetConfig {
+    #[clap(value_parser = ["update-check", "author", "cache-max-size", "theme"])]
+    pub key: String,
+    /// The new value, like high-contrast for theme
+    pub value: String,
+}
+
+impl ConfigProject {
+    pub fn run(&self) -> Result<(), PpmError> {
+        if !self.global {
+            return Err(PpmError::Other(
+                "Project settings live in project.toml; pass --global to use the global config.toml"
+                    .to_string(),
+            ));
+        }
+        match &self.command {
+            ConfigCommand::Get(get) => crate::ppm_functions::config_get(&get.key),
+            ConfigCommand::Set(set) => crate::ppm_functions::config_set(&set.key, &set.value),
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct ReportProject {
+    /// Write the report to this file instead of stdout
+    #[clap(long = "output", short = 'o', value_name = "FILE")]
+    pub output: Option<PathBuf>,
+}
+
+impl ReportProject {
+    pub fn run(&self) -> Result<(), PpmError> {
+        crate::ppm_functions::write_report(self.output.as_deref())
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct ExplainError {
+    /// Error code shown after an error message, e.g. PPM-014; every code when left out
+    pub code: Option<String>,
+}
+
+impl ExplainError {
+    pub fn run(&self) -> Result<(), PpmError> {
+        crate::ppm_functions::explain_error(self.code.as_deref())
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct CleanProject {
+    /// Remove the whole .ppm/ directory, logs and backups included
+    #[clap(long = "all", takes_value = false)]
+    pub all: bool,
+}
+
+impl CleanProject {
+    pub fn clean_project(&self) -> Result<(), PpmError> {
+        crate::ppm_functions::clean_project(self.all)
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct ShowHistory {
+    /// Only show commands that changed this package
+    #[clap(long = "package", value_name = "NAME")]
+    pub package: Option<String>,
+    /// Show at most this many of the most recent entries
+    #[clap(short = 'n', long = "limit", default_value = "20")]
+    pub limit: usize,
+}
+
+impl ShowHistory {
+    pub fn show_history(&self) -> Result<(), PpmError> {
+        crate::ppm_functions::show_history(self.package.as_deref(), self.limit)
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct SelfCommand {
+    #[clap(subcommand)]
+    pub command: SelfAction,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SelfAction {
+    /// Check whether a newer ppmm release is available
+    CheckUpdate(CheckUpdate),
+    /// Replace this ppmm binary with a release from GitHub
+    Update(SelfUpdate),
+}
+
+#[derive(Args, Debug)]
+pub struct SelfUpdate {
+    /// Install this release tag instead of the latest, e.g. v1.2.0
+    #[clap(long = "version", value_name = "TAG")]
+    pub version: Option<String>,
+    /// Only show what would be installed
+    #[clap(long = "dry-run", takes_value = false)]
+    pub dry_run: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct CheckUpdate {
+    /// Only record the result for later runs, printing nothing
+    #[clap(long = "background", hide = true, takes_value = false)]
+    pub background: bool,
+}
+
+impl SelfCommand {
+    pub fn run(&self) -> Result<(), PpmError> {
+        match &self.command {
+            SelfAction::CheckUpdate(check) => crate::ppm_functions::check_update(check.background),
+            SelfAction::Update(update) => {
+                crate::ppm_functions::self_update(update.version.as_deref(), update.dry_run)
+            }
+        }
     }
 }
 
+#[derive(Args, Debug)]
+pub struct SyncPackages {
+    /// Only report differences, exiting non-zero if there are any
+    #[clap(long = "check", takes_value = false)]
+    pub check: bool,
+}
+
+impl SyncPackages {
+    pub fn sync_packages(&self) -> Result<(), PpmError> {
+        crate::ppm_functions::sync_packages(self.check)
+    }
+}
+
+/// Record `req` as `spec`, dropping any other spelling of the same
+/// package so project.toml never lists it twice
+fn replace_package(conf: &mut Config, req: &Requirement, spec: PackageSpec) {
+    let name = req.name.as_str();
+    let canonical = normalize_pkg_name(name);
+    conf.packages
+        .retain(|key, _| key == name || normalize_pkg_name(key) != canonical);
+    conf.packages.insert(name.to_string(), spec);
+}
+
 /// Bump semantic version (major.minor.patch)
 fn bump_semantic_version(version: &str, bump_type: &str) -> Result<String, String> {
     // Remove alpha/beta suffixes
@@ -765,3 +2645,131 @@ fn bump_semantic_version(version: &str, bump_type: &str) -> Result<String, Strin
 
     Ok(new_version)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use ppmm::prompt::ScriptedPrompter;
+    use ppmm::runner::{RunOutput, ScriptedRunner};
+    use std::sync::Arc;
+
+    #[derive(Parser)]
+    struct InitCli {
+        #[clap(flatten)]
+        conf: ProjectConf,
+    }
+
+    fn init_conf(args: &[&str]) -> ProjectConf {
+        InitCli::parse_from(std::iter::once("init").chain(args.iter().copied())).conf
+    }
+
+    #[test]
+    fn test_init_asks_only_for_fields_without_flags() {
+        let prompter = ScriptedPrompter::new(&["api", "1.0", "2.0.0", "An API", ""]);
+        let project = init_conf(&["--python", "3.12"])
+            .answers(true, None, &prompter)
+            .unwrap();
+        assert_eq!(project.name, "api");
+        assert_eq!(project.version, "2.0.0");
+        assert_eq!(project.description, "An API");
+        assert_eq!(project.venv, None);
+        assert_eq!(project.python.as_deref(), Some("3.12"));
+        let transcript = prompter.transcript();
+        assert_eq!(transcript.matches("Version").count(), 2);
+        assert!(!transcript.contains("Python interpreter"));
+
+        // --yes takes the defaults without asking
+        let none = ScriptedPrompter::new(&[]);
+        let project = init_conf(&["--yes", "--name", "api"])
+            .answers(true, None, &none)
+            .unwrap();
+        assert_eq!(project.version, "0.1.0");
+        assert!(none.transcript().is_empty());
+    }
+
+    #[test]
+    fn test_reconfigure_keeps_packages_and_defaults_to_existing_metadata() {
+        let mut existing = Project::new(
+            "api".to_string(),
+            "1.4.0".to_string(),
+            "The API".to_string(),
+            "./server.py".to_string(),
+            Some(".venv".to_string()),
+        );
+        existing.python = Some("3.11".to_string());
+        let mut config = Config::new(
+            existing.clone(),
+            HashMap::from([("requests".to_string(), PackageSpec::from("2.31.0"))]),
+            HashMap::from([("test".to_string(), "pytest".into())]),
+        );
+
+        // Enter keeps each field; only the version changes
+        let prompter = ScriptedPrompter::new(&["", "1.5.0", "", "", ""]);
+        let project = init_conf(&[])
+            .answers(true, Some(&existing), &prompter)
+            .unwrap();
+        assert_eq!(project.name, "api");
+        assert_eq!(project.venv.as_deref(), Some(".venv"));
+        assert_eq!(project.python.as_deref(), Some("3.11"));
+
+        ProjectCreator::new(project, true).apply_metadata(&mut config, None);
+        assert_eq!(config.project.version, "1.5.0");
+        assert_eq!(config.project.description, "The API");
+        assert_eq!(config.project.main_script, "./server.py");
+        assert_eq!(config.packages["requests"], PackageSpec::from("2.31.0"));
+        assert_eq!(config.scripts["test"].command(), Some("pytest"));
+    }
+
+    fn installer() -> Installer {
+        Installer {
+            requirements: vec![],
+            record: false,
+            prefer_newer: false,
+            prefer_first: false,
+            editable: None,
+            jobs: None,
+        }
+    }
+
+    #[test]
+    fn test_install_runs_pip_then_writes_the_lock_file() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("project.toml"),
+            "[project]\nname = \"demo\"\nversion = \"0.1.0\"\ndescription = \"\"\nmain_script = \"./main.py\"\n\n[packages]\nrequests = \"2.31.0\"\nflask = \"3.0.0\"\n\n[scripts]\n",
+        )
+        .unwrap();
+        let mut ctx = ProjectContext::load(dir.path()).unwrap();
+        std::fs::create_dir_all(ctx.venv_bin_dir()).unwrap();
+        let runner = Arc::new(ScriptedRunner::new());
+        runner.respond("pip install", RunOutput::ok("")).respond(
+            "pip freeze",
+            RunOutput::ok("flask==3.0.0\nrequests==2.31.0\n"),
+        );
+        ctx.runner = runner.clone();
+        let none = ScriptedPrompter::new(&[]);
+
+        installer().install_into(&mut ctx, &none).unwrap();
+        assert_eq!(
+            runner.command_lines(),
+            vec!["pip install flask==3.0.0 requests==2.31.0", "pip freeze"]
+        );
+        let lock_file = dir.path().join(get_lock_file());
+        assert!(
+            fs::read_to_string(&lock_file)
+                .unwrap()
+                .contains("requests==2.31.0")
+        );
+
+        // With a lock file, pip installs from it and the lock is kept
+        let runner = Arc::new(ScriptedRunner::new());
+        runner.respond("pip install -r", RunOutput::ok(""));
+        ctx.runner = runner.clone();
+        installer().install_into(&mut ctx, &none).unwrap();
+        assert_eq!(
+            runner.command_lines(),
+            vec![format!("pip install -r {}", lock_file.display())]
+        );
+    }
+}
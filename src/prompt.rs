@@ -0,0 +1,219 @@
+//! User prompts behind a trait so command flows can be driven by tests.
+//!
+//! Invalid answers are retried at most [`MAX_ATTEMPTS`] times. End of input
+//! or a read error always means "no" / no answer, never a panic.
+
+use std::cell::RefCell;
+use std::io::{BufRead, Cursor, Write};
+
+/// How many invalid answers are tolerated before giving up
+pub const MAX_ATTEMPTS: usize = 3;
+
+/// Source of answers to interactive questions
+pub trait Prompter {
+    /// Ask a yes/no question. An empty answer picks `default`; end of input
+    /// or too many invalid answers count as "no".
+    fn ask_yes_no(&self, question: &str, default: bool) -> bool;
+
+    /// Ask for free text. An empty answer picks `default`; end of input
+    /// gives `None`.
+    fn ask_string(&self, question: &str, default: Option<&str>) -> Option<String>;
+
+    /// Ask to pick one of `options`, returning its index, or `None` on end
+    /// of input or too many invalid answers.
+    fn select(&self, question: &str, options: &[&str]) -> Option<usize>;
+
+    /// Whether questions reach someone who can answer them. Without one
+    /// every question takes its default.
+    fn is_interactive(&self) -> bool {
+        true
+    }
+}
+
+/// [`Prompter`] reading answers line by line from `R` and writing the
+/// questions to `W`
+pub struct LinePrompter<R, W> {
+    input: RefCell<R>,
+    output: RefCell<W>,
+}
+
+impl<R: BufRead, W: Write> LinePrompter<R, W> {
+    /// Prompter over the given reader and writer
+    pub fn new(input: R, output: W) -> LinePrompter<R, W> {
+        LinePrompter {
+            input: RefCell::new(input),
+            output: RefCell::new(output),
+        }
+    }
+
+    /// Give back the writer, e.g. to inspect what was asked
+    pub fn into_output(self) -> W {
+        self.output.into_inner()
+    }
+
+    fn say(&self, text: &str) {
+        let mut output = self.output.borrow_mut();
+        let _ = output.write_all(text.as_bytes());
+        let _ = output.flush();
+    }
+
+    /// Print `prompt` and read one line; `None` on end of input or error
+    fn read_answer(&self, prompt: &str) -> Option<String> {
+        self.say(prompt);
+        let mut line = String::new();
+        match self.input.borrow_mut().read_line(&mut line) {
+            Ok(0) | Err(_) => {
+                self.say("\n");
+                None
+            }
+            Ok(_) => Some(line.trim().to_string()),
+        }
+    }
+}
+
+impl<R: BufRead, W: Write> Prompter for LinePrompter<R, W> {
+    fn ask_yes_no(&self, question: &str, default: bool) -> bool {
+        let hint = if default { "Y/n" } else { "y/N" };
+        for _ in 0..MAX_ATTEMPTS {
+            let answer = match self.read_answer(&format!("{} ({}): ", question, hint)) {
+                Some(answer) => answer,
+                None => return false,
+            };
+            match answer.to_lowercase().as_str() {
+                "" => return default,
+                "y" | "yes" => return true,
+                "n" | "no" => return false,
+                _ => self.say("Invalid option, please type 'y' or 'n'\n"),
+            }
+        }
+        false
+    }
+
+    fn ask_string(&self, question: &str, default: Option<&str>) -> Option<String> {
+        let prompt = match default {
+            Some(default) => format!("{} [{}]: ", question, default),
+            None => format!("{}: ", question),
+        };
+        let answer = self.read_answer(&prompt)?;
+        if answer.is_empty() {
+            default.map(|d| d.to_string())
+        } else {
+            Some(answer)
+        }
+    }
+
+    fn select(&self, question: &str, options: &[&str]) -> Option<usize> {
+        if options.is_empty() {
+            return None;
+        }
+        let mut menu = format!("{}\n", question);
+        for (i, option) in options.iter().enumerate() {
+            menu.push_str(&format!("  {}) {}\n", i + 1, option));
+        }
+        self.say(&menu);
+
+        for _ in 0..MAX_ATTEMPTS {
+            let answer = self.read_answer(&format!("Choose [1-{}]: ", options.len()))?;
+            match answer.parse::<usize>() {
+                Ok(n) if (1..=options.len()).contains(&n) => return Some(n - 1),
+                _ => self.say(&format!(
+                    "Invalid option, please type a number from 1 to {}\n",
+                    options.len()
+                )),
+            }
+        }
+        None
+    }
+}
+
+/// Prompter answering from a fixed script, for tests. Everything asked is
+/// kept in a transcript.
+pub struct ScriptedPrompter {
+    inner: LinePrompter<Cursor<Vec<u8>>, Vec<u8>>,
+}
+
+impl ScriptedPrompter {
+    /// Answer with `answers` in order, then behave as if input ended
+    pub fn new(answers: &[&str]) -> ScriptedPrompter {
+        let script: String = answers.iter().map(|a| format!("{}\n", a)).collect();
+        ScriptedPrompter {
+            inner: LinePrompter::new(Cursor::new(script.into_bytes()), vec![]),
+        }
+    }
+
+    /// Questions and messages written so far
+    pub fn transcript(&self) -> String {
+        String::from_utf8_lossy(&self.inner.output.borrow()).into_owned()
+    }
+}
+
+impl Prompter for ScriptedPrompter {
+    fn ask_yes_no(&self, question: &str, default: bool) -> bool {
+        self.inner.ask_yes_no(question, default)
+    }
+
+    fn ask_string(&self, question: &str, default: Option<&str>) -> Option<String> {
+        self.inner.ask_string(question, default)
+    }
+
+    fn select(&self, question: &str, options: &[&str]) -> Option<usize> {
+        self.inner.select(question, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_yes_no_answers() {
+        let prompter = ScriptedPrompter::new(&["y", "NO", "", ""]);
+        assert!(prompter.ask_yes_no("Continue?", false));
+        assert!(!prompter.ask_yes_no("Continue?", true));
+        assert!(prompter.ask_yes_no("Continue?", true));
+        assert!(!prompter.ask_yes_no("Continue?", false));
+        assert!(prompter.transcript().contains("Continue? (Y/n): "));
+    }
+
+    #[test]
+    fn test_yes_no_retries_then_gives_up() {
+        let prompter = ScriptedPrompter::new(&["maybe", "y"]);
+        assert!(prompter.ask_yes_no("Continue?", false));
+
+        let garbage = vec!["what"; MAX_ATTEMPTS + 5];
+        let prompter = ScriptedPrompter::new(&garbage);
+        assert!(!prompter.ask_yes_no("Continue?", true));
+        assert_eq!(
+            prompter.transcript().matches("Invalid option").count(),
+            MAX_ATTEMPTS
+        );
+    }
+
+    #[test]
+    fn test_eof_means_no() {
+        let prompter = ScriptedPrompter::new(&[]);
+        assert!(!prompter.ask_yes_no("Continue?", true));
+        assert_eq!(prompter.ask_string("Name", Some("demo")), None);
+        assert_eq!(prompter.select("Pick", &["a", "b"]), None);
+    }
+
+    #[test]
+    fn test_ask_string_default() {
+        let prompter = ScriptedPrompter::new(&["", "custom"]);
+        assert_eq!(
+            prompter.ask_string("Name", Some("demo")),
+            Some("demo".to_string())
+        );
+        assert_eq!(
+            prompter.ask_string("Name", Some("demo")),
+            Some("custom".to_string())
+        );
+    }
+
+    #[test]
+    fn test_select() {
+        let prompter = ScriptedPrompter::new(&["0", "3", "2"]);
+        assert_eq!(prompter.select("Shell", &["bash", "zsh"]), Some(1));
+        assert!(prompter.transcript().contains("  2) zsh"));
+    }
+}
@@ -0,0 +1,310 @@
+//! PEP 508 requirement specifiers such as `requests[socks] >= 2.0 ; python_version < "3.12"`.
+//!
+//! Specs end up on pip's command line, so anything that could be read as
+//! an option (a leading `-`) or smuggle extra lines is rejected outright.
+
+use crate::marker::Marker;
+use std::fmt;
+use std::str::FromStr;
+
+/// Version comparison operators, longest first so `===` wins over `==`
+const OPERATORS: [&str; 8] = ["===", "~=", "==", "!=", "<=", ">=", "<", ">"];
+
+/// A parsed requirement
+#[derive(Debug, Clone, PartialEq)]
+pub struct Requirement {
+    /// Distribution name as written
+    pub name: String,
+    /// Extras inside `[...]`
+    pub extras: Vec<String>,
+    /// Version clauses such as `(">=", "2.0")`, empty for any version
+    pub specifier: Vec<(String, String)>,
+    /// Direct reference after `@`
+    pub url: Option<String>,
+    /// Environment marker after `;`
+    pub marker: Option<String>,
+}
+
+impl Requirement {
+    /// Parse and validate one requirement
+    pub fn parse(spec: &str) -> Result<Requirement, String> {
+        let invalid = |reason: &str| format!("Invalid requirement '{}': {}", spec.trim(), reason);
+
+        if spec.chars().any(|c| c.is_control()) {
+            return Err(invalid("control characters and newlines are not allowed"));
+        }
+        let trimmed = spec.trim();
+        if trimmed.is_empty() {
+            return Err("Package name cannot be empty".to_string());
+        }
+        if trimmed.starts_with('-') {
+            return Err(invalid("must not start with '-'"));
+        }
+
+        let (body, marker) = match trimmed.split_once(';') {
+            Some((body, marker)) => {
+                let marker = marker.trim();
+                if marker.is_empty() {
+                    return Err(invalid("empty environment marker after ';'"));
+                }
+                Marker::parse(marker).map_err(|e| invalid(&e))?;
+                (body.trim_end(), Some(marker.to_string()))
+            }
+            None => (trimmed, None),
+        };
+
+        let name_end = body
+            .find(|c: char| !(c.is_ascii_alphanumeric() || "._-".contains(c)))
+            .unwrap_or(body.len());
+        let name = &body[..name_end];
+        validate_name(name).map_err(|e| invalid(&e))?;
+        let mut rest = body[name_end..].trim_start();
+
+        let mut extras = vec![];
+        if let Some(after) = rest.strip_prefix('[') {
+            let close = after
+                .find(']')
+                .ok_or_else(|| invalid("unclosed '[' in extras"))?;
+            for extra in after[..close].split(',').map(str::trim) {
+                if extra.is_empty() && after[..close].trim().is_empty() {
+                    break;
+                }
+                validate_name(extra).map_err(|_| invalid(&format!("bad extra '{}'", extra)))?;
+                extras.push(extra.to_string());
+            }
+            rest = after[close + 1..].trim_start();
+        }
+
+        let mut url = None;
+        let mut specifier = vec![];
+        if let Some(after) = rest.strip_prefix('@') {
+            let reference = after.trim();
+            if !(reference.contains("://") || reference.starts_with("file:"))
+                || reference.contains(char::is_whitespace)
+            {
+                return Err(invalid("'@' must be followed by a URL"));
+            }
+            url = Some(reference.to_string());
+        } else if !rest.is_empty() {
+            let clauses = match rest.strip_prefix('(') {
+                Some(inner) => inner
+                    .strip_suffix(')')
+                    .ok_or_else(|| invalid("unclosed '(' in version specifier"))?,
+                None => rest,
+            };
+            for clause in clauses.split(',') {
+                specifier.push(parse_clause(clause.trim()).map_err(|e| invalid(&e))?);
+            }
+        }
+
+        Ok(Requirement {
+            name: name.to_string(),
+            extras,
+            specifier,
+            url,
+            marker,
+        })
+    }
+
+    /// The version if the spec pins exactly one, e.g. `2.31.0` for
+    /// `requests==2.31.0`
+    pub fn pinned_version(&self) -> Option<&str> {
+        match self.specifier.as_slice() {
+            [(op, version)] if (op == "==" || op == "===") && !version.contains('*') => {
+                Some(version)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Requirement {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Requirement, String> {
+        Requirement::parse(s)
+    }
+}
+
+/// Normalized spelling, safe to hand to pip as a single argument
+impl fmt::Display for Requirement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if !self.extras.is_empty() {
+            write!(f, "[{}]", self.extras.join(","))?;
+        }
+        if let Some(url) = &self.url {
+            write!(f, " @ {}", url)?;
+        }
+        let clauses: Vec<String> = self
+            .specifier
+            .iter()
+            .map(|(op, version)| format!("{}{}", op, version))
+            .collect();
+        write!(f, "{}", clauses.join(","))?;
+        if let Some(marker) = &self.marker {
+            // A space is required before ';' after a URL
+            write!(f, " ; {}", marker)?;
+        }
+        Ok(())
+    }
+}
+
+/// PEP 508 names: ASCII letters and digits, with `.`, `_` and `-` allowed
+/// only between them
+pub(crate) fn validate_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("missing package name".to_string());
+    }
+    let first = name.chars().next().unwrap();
+    let last = name.chars().last().unwrap();
+    if !first.is_ascii_alphanumeric() || !last.is_ascii_alphanumeric() {
+        return Err(format!(
+            "name '{}' must start and end with a letter or digit",
+            name
+        ));
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "._-".contains(c))
+    {
+        return Err(format!("name '{}' contains invalid characters", name));
+    }
+    Ok(())
+}
+
+fn parse_clause(clause: &str) -> Result<(String, String), String> {
+    let op = OPERATORS
+        .iter()
+        .find(|op| clause.starts_with(**op))
+        .ok_or_else(|| format!("expected a version operator in '{}'", clause))?;
+    let version = clause[op.len()..].trim();
+    if version.is_empty() {
+        return Err(format!("missing version after '{}'", op));
+    }
+    let allowed = |c: char| c.is_ascii_alphanumeric() || "._*+!-".contains(c);
+    if *op != "===" && !version.chars().all(allowed) {
+        return Err(format!("invalid version '{}'", version));
+    }
+    if version.contains(char::is_whitespace) {
+        return Err(format!("invalid version '{}'", version));
+    }
+    Ok((op.to_string(), version.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_names() {
+        for name in [
+            "requests",
+            "my-package",
+            "my_package",
+            "package123",
+            "zope.interface",
+        ] {
+            let req = Requirement::parse(name).unwrap();
+            assert_eq!(req.name, name);
+            assert!(req.specifier.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_pinned_version() {
+        let req = Requirement::parse("requests==2.26.0").unwrap();
+        assert_eq!(req.name, "requests");
+        assert_eq!(req.pinned_version(), Some("2.26.0"));
+        assert_eq!(Requirement::parse("numpy").unwrap().pinned_version(), None);
+        assert_eq!(
+            Requirement::parse("numpy>=1.0").unwrap().pinned_version(),
+            None
+        );
+        assert_eq!(
+            Requirement::parse("numpy==1.*").unwrap().pinned_version(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_pep508_examples() {
+        let valid = [
+            ("requests >= 2.0", "requests>=2.0"),
+            (
+                "requests[security,socks]>=2.8.1,==2.8.*",
+                "requests[security,socks]>=2.8.1,==2.8.*",
+            ),
+            ("requests [ socks ] (>=2.8.1)", "requests[socks]>=2.8.1"),
+            ("name<=1", "name<=1"),
+            ("name~=1.4.2", "name~=1.4.2"),
+            ("name===1.0-local", "name===1.0-local"),
+            (
+                "name; python_version < \"3.12\"",
+                "name ; python_version < \"3.12\"",
+            ),
+            (
+                "pip @ https://github.com/pypa/pip/archive/22.0.zip",
+                "pip @ https://github.com/pypa/pip/archive/22.0.zip",
+            ),
+            (
+                "name @ file:///tmp/name-1.0.tar.gz ; os_name == 'posix'",
+                "name @ file:///tmp/name-1.0.tar.gz ; os_name == 'posix'",
+            ),
+            ("A.B-C_D[]", "A.B-C_D"),
+        ];
+        for (spec, normalized) in valid {
+            let req =
+                Requirement::parse(spec).unwrap_or_else(|e| panic!("{} should parse: {}", spec, e));
+            assert_eq!(req.to_string(), normalized);
+            assert_eq!(Requirement::parse(&req.to_string()).unwrap(), req);
+        }
+
+        let invalid = [
+            "",
+            "pkg with spaces",
+            "pkg/slash",
+            "-r requirements.txt",
+            "--index-url=http://evil",
+            "requests\n--extra-index-url http://evil",
+            "requests\0",
+            "_private",
+            "name-",
+            "name[extra",
+            "name[bad extra]",
+            "name >=",
+            "name 2.0",
+            "name (>=1.0",
+            "name>=1.0 2",
+            "name @ not-a-url",
+            "name; ",
+            "name; python_version < \"3.12",
+            "name; (os_name == 'nt'",
+            "naïve",
+        ];
+        for spec in invalid {
+            assert!(
+                Requirement::parse(spec).is_err(),
+                "{:?} should be rejected",
+                spec
+            );
+        }
+    }
+
+    #[test]
+    fn test_extras_and_marker_parts() {
+        let req: Requirement = "Django[argon2, bcrypt] (>=4.2, <5) ; python_version >= \"3.10\""
+            .parse()
+            .unwrap();
+        assert_eq!(req.name, "Django");
+        assert_eq!(req.extras, vec!["argon2", "bcrypt"]);
+        assert_eq!(
+            req.specifier,
+            vec![
+                (">=".to_string(), "4.2".to_string()),
+                ("<".to_string(), "5".to_string())
+            ]
+        );
+        assert_eq!(req.marker.as_deref(), Some("python_version >= \"3.10\""));
+    }
+}
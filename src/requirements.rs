@@ -0,0 +1,1086 @@
+//! requirements.txt generation, and reading requirements files for
+//! `ppm install -r`.
+//!
+//! [`render_requirements`] and [`parse_requirements`] do the formatting
+//! and parsing on strings alone; `ppm gen`, the `sync-requirements` check
+//! and constraints.txt are written with the first, and every file ppm
+//! reads goes through the second.
+//!
+//! [`parse_file`] follows `-r` includes relative to the including file,
+//! joins backslash continuations and drops comments and per-requirement
+//! options like `--hash`. Index options are collected with the file they
+//! came from, for the caller to offer as the project's `index-url`.
+//! Editable installs and bare URL, VCS or path lines have no name to put
+//! under `[packages]` and are reported for manual handling instead. Files
+//! are read through a callback, so the parsing itself never touches the
+//! filesystem.
+
+use crate::error::PpmError;
+use crate::graph::DependencyGraph;
+use crate::packages::{compare_versions, normalize_pkg_name};
+use crate::paths;
+use crate::requirement::Requirement;
+use crate::settings::{Config, PROJECT_CONFIG_FILE, PackageSpec};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The file `ppm gen` writes unless `[requirements] output` says otherwise
+pub const REQUIREMENTS_FILE: &str = "requirements.txt";
+
+/// `name==version` lines for every configured package, see
+/// [`render_requirements`]. The project itself isn't a requirement of
+/// its own, so a path package pointing at `.` is left out.
+pub fn generate_requirements(config: &Config, opts: &RenderOptions) -> String {
+    let packages: BTreeMap<String, PackageSpec> = config
+        .packages
+        .iter()
+        .filter(|(_, spec)| !spec.is_self())
+        .map(|(name, spec)| (name.clone(), spec.clone()))
+        .collect();
+    render_requirements(&packages, opts)
+}
+
+/// The header comment of a requirements file `ppm gen` writes from
+/// `source`, like `Generated by ppm from project.toml for demo 0.1.0 at
+/// 2024-05-01T09:30:00Z`; without `timestamp` regenerating an unchanged
+/// project gives the same file
+pub fn generated_header(config: &Config, source: &str, timestamp: Option<&str>) -> String {
+    let mut header = format!(
+        "Generated by ppm from {} for {} {}",
+        source, config.project.name, config.project.version
+    );
+    if let Some(timestamp) = timestamp {
+        header.push_str(&format!(" at {}", timestamp));
+    }
+    header
+}
+
+/// The source named in the header [`generated_header`] wrote, like
+/// `ppmm.lock` for a file generated with `ppm gen --from-lock`; `None`
+/// for a file without such a header
+pub fn generated_from(contents: &str) -> Option<&str> {
+    let header = contents.lines().next()?;
+    let (source, _) = header
+        .strip_prefix("# Generated by ppm from ")?
+        .split_once(" for ")?;
+    Some(source)
+}
+
+/// Every package `lock`, the contents of ppmm.lock, pins, in two
+/// sections: those `config` lists, then the transitive ones pulled in by
+/// them. With `graph`, each transitive line ends in `# via` and the
+/// packages that require it, like pip-compile's annotations. Editable
+/// installs, the project itself among them, are left out.
+pub fn render_locked(
+    config: &Config,
+    lock: &str,
+    graph: Option<&DependencyGraph>,
+    header: Option<String>,
+) -> Result<String, ParseError> {
+    let direct_names: HashSet<String> = config
+        .packages
+        .keys()
+        .map(|name| normalize_pkg_name(name))
+        .collect();
+    let (mut direct, mut transitive) = (BTreeMap::new(), BTreeMap::new());
+    for req in parse_requirements(lock)?.requirements() {
+        let marker = req.marker.as_deref();
+        let spec = match (&req.url, req.pinned_version()) {
+            (Some(url), _) => PackageSpec::direct(url, marker),
+            (None, Some(version)) => PackageSpec::new(version, marker),
+            (None, None) => continue,
+        };
+        let section = if direct_names.contains(&normalize_pkg_name(&req.name)) {
+            &mut direct
+        } else {
+            &mut transitive
+        };
+        section.insert(req.name.clone(), spec);
+    }
+
+    let mut via = HashMap::new();
+    for name in transitive.keys() {
+        let parents = graph
+            .and_then(|graph| graph.dependents(name))
+            .and_then(|levels| levels.into_iter().next())
+            .unwrap_or_default();
+        if !parents.is_empty() {
+            let names: Vec<&str> = parents.iter().map(|parent| parent.name.as_str()).collect();
+            via.insert(
+                normalize_pkg_name(name),
+                format!("via {}", names.join(", ")),
+            );
+        }
+    }
+
+    let mut sections = vec![];
+    if let Some(header) = header {
+        sections.push(format!("# {}\n", header));
+    }
+    if !direct.is_empty() {
+        sections.push(render_requirements(
+            &direct,
+            &RenderOptions {
+                header: Some(format!("Direct dependencies, from {}", PROJECT_CONFIG_FILE)),
+                ..RenderOptions::default()
+            },
+        ));
+    }
+    if !transitive.is_empty() {
+        sections.push(render_requirements(
+            &transitive,
+            &RenderOptions {
+                header: Some("Transitive dependencies".to_string()),
+                comments: via,
+                ..RenderOptions::default()
+            },
+        ));
+    }
+    Ok(sections.join("\n"))
+}
+
+/// What [`render_requirements`] adds to the requirement lines
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RenderOptions {
+    /// A comment written as the first line, without its `# `
+    pub header: Option<String>,
+    /// `algorithm:digest` hashes by normalized package name, written as
+    /// `--hash` options. pip checks every line's hashes or none, so they
+    /// are only written when every line has at least one.
+    pub hashes: HashMap<String, Vec<String>>,
+    /// Comments ending the lines of some packages, by normalized name
+    pub comments: HashMap<String, String>,
+}
+
+/// One line per package, sorted by normalized name so the output is
+/// stable across runs. Markers are kept as written, e.g.
+/// `pywin32==306 ; sys_platform == 'win32'`, and git, path and URL
+/// packages use their requirement syntax (see [`PackageSpec::requirement`]).
+///
+/// Keys naming the same package (`Flask` and `flask`) produce one line,
+/// with the highest version.
+pub fn render_requirements(
+    packages: &BTreeMap<String, PackageSpec>,
+    opts: &RenderOptions,
+) -> String {
+    let mut by_name: BTreeMap<String, (&String, &PackageSpec)> = BTreeMap::new();
+    for (name, spec) in packages {
+        by_name
+            .entry(normalize_pkg_name(name))
+            .and_modify(|kept| {
+                let newer = compare_versions(
+                    spec.version().unwrap_or_default(),
+                    kept.1.version().unwrap_or_default(),
+                )
+                .then_with(|| kept.0.cmp(name));
+                if newer.is_gt() {
+                    *kept = (name, spec);
+                }
+            })
+            .or_insert((name, spec));
+    }
+
+    let hashed = !by_name.is_empty()
+        && by_name.keys().all(|name| {
+            opts.hashes
+                .get(name)
+                .is_some_and(|hashes| !hashes.is_empty())
+        });
+    let mut out = String::new();
+    if let Some(header) = &opts.header {
+        out.push_str(&format!("# {}\n", header));
+    }
+    for (normalized, (name, spec)) in &by_name {
+        out.push_str(&spec.requirement(name));
+        if hashed {
+            for hash in &opts.hashes[normalized] {
+                out.push_str(&format!(" \\\n    --hash={}", hash));
+            }
+        }
+        if let Some(comment) = opts.comments.get(normalized) {
+            out.push_str(&format!("  # {}", comment));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// How `current`, the requirements file on disk, differs from
+/// `generated`: the lines to remove as `-line` and to add as `+line`, in
+/// file order. Line endings, blank lines and comments are ignored, so a
+/// file saved with CRLF, without a final newline or with another header
+/// is still up to date.
+pub fn diff_requirements(current: &str, generated: &str) -> Vec<String> {
+    let lines = |text: &str| -> Vec<String> {
+        text.lines()
+            .map(|line| split_comment(line).0.trim_end().to_string())
+            .filter(|line| !line.is_empty())
+            .collect()
+    };
+    let (old, new) = (lines(current), lines(generated));
+
+    // Longest common subsequence, filled from the end so the walk below
+    // goes forward
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut changes = vec![];
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && common[i + 1][j] >= common[i][j + 1]) {
+            changes.push(format!("-{}", old[i]));
+            i += 1;
+        } else {
+            changes.push(format!("+{}", new[j]));
+            j += 1;
+        }
+    }
+    changes
+}
+
+/// Where a line of a requirements file is
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Origin {
+    /// The file, as given or joined onto the including file's directory
+    pub file: PathBuf,
+    /// Line number, counting from 1; a continued line's first line
+    pub line: usize,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file.display(), self.line)
+    }
+}
+
+/// A requirement line
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequirementLine {
+    /// The requirement, marker included
+    pub requirement: Requirement,
+    /// Where it is
+    pub origin: Origin,
+}
+
+/// An index option, like `--index-url https://...`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexOption {
+    /// The index URL
+    pub url: String,
+    /// Where it is
+    pub origin: Origin,
+}
+
+/// A line that isn't imported, and why
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedLine {
+    /// The line, continuations joined
+    pub text: String,
+    /// Where it is
+    pub origin: Origin,
+    /// Why it isn't imported
+    pub reason: String,
+}
+
+impl fmt::Display for SkippedLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}): {}", self.text, self.origin, self.reason)
+    }
+}
+
+/// A requirements file with its includes
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RequirementsFile {
+    /// Requirement lines in file order, includes expanded in place
+    pub requirements: Vec<RequirementLine>,
+    /// The first `--index-url`
+    pub index_url: Option<IndexOption>,
+    /// Every `--extra-index-url`
+    pub extra_index_urls: Vec<IndexOption>,
+    /// Editable installs and URL, VCS or path lines without a name, for
+    /// the user to add by hand
+    pub manual: Vec<SkippedLine>,
+    /// Options ppm doesn't carry over, such as `--find-links`, constraints
+    /// files and a second, different `--index-url`
+    pub ignored: Vec<SkippedLine>,
+}
+
+/// Parse the requirements file at `path` and the files it includes, each
+/// read with `load`. Fails on an invalid requirement, a file that can't
+/// be read, or includes that form a cycle.
+pub fn parse_file<F>(path: &Path, mut load: F) -> Result<RequirementsFile, PpmError>
+where
+    F: FnMut(&Path) -> io::Result<String>,
+{
+    let mut parsed = RequirementsFile::default();
+    let mut stack = vec![];
+    parse_into(
+        &paths::normalize(path),
+        None,
+        &mut load,
+        &mut stack,
+        &mut parsed,
+    )?;
+    Ok(parsed)
+}
+
+fn parse_into<F>(
+    path: &Path,
+    included_from: Option<&Origin>,
+    load: &mut F,
+    stack: &mut Vec<PathBuf>,
+    parsed: &mut RequirementsFile,
+) -> Result<(), PpmError>
+where
+    F: FnMut(&Path) -> io::Result<String>,
+{
+    if stack.iter().any(|open| open == path) {
+        let chain: Vec<String> = stack
+            .iter()
+            .chain([&path.to_path_buf()])
+            .map(|file| file.display().to_string())
+            .collect();
+        return Err(PpmError::Other(format!(
+            "Requirements files include each other: {}",
+            chain.join(" -> ")
+        )));
+    }
+    let contents = load(path).map_err(|e| {
+        PpmError::Other(match included_from {
+            Some(origin) => format!(
+                "Failed to read {} (included at {}): {}",
+                path.display(),
+                origin,
+                e
+            ),
+            None => format!("Failed to read {}: {}", path.display(), e),
+        })
+    })?;
+    let origin = |line| Origin {
+        file: path.to_path_buf(),
+        line,
+    };
+    let lines = parse_requirements(&contents)
+        .map_err(|e| PpmError::Other(format!("{}: {}", origin(e.line), e.message)))?;
+    stack.push(path.to_path_buf());
+    for line in lines.lines {
+        let origin = origin(line.line);
+        match line.entry {
+            Entry::Requirement { requirement, .. } => parsed.requirements.push(RequirementLine {
+                requirement,
+                origin,
+            }),
+            Entry::Location(text) => parsed.manual.push(SkippedLine {
+                text,
+                origin,
+                reason: "no package name; add it under [packages] as a git, path or URL package"
+                    .to_string(),
+            }),
+            Entry::Option(text) => parse_option(&text, origin, load, stack, parsed)?,
+            Entry::Comment => {}
+        }
+    }
+    stack.pop();
+    Ok(())
+}
+
+/// What a line of a requirements file holds
+#[derive(Debug, Clone, PartialEq)]
+pub enum Entry {
+    /// A requirement and the `--hash` options after it; other
+    /// per-requirement options are dropped
+    Requirement {
+        /// The requirement, marker included
+        requirement: Requirement,
+        /// `algorithm:digest` values of its `--hash` options
+        hashes: Vec<String>,
+    },
+    /// A pip option, like `-r base.txt` or `--index-url https://...`
+    Option(String),
+    /// A bare URL, VCS reference or path, which has no package name
+    Location(String),
+    /// Nothing but a comment
+    Comment,
+}
+
+/// A line of a requirements file, continuations joined
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedLine {
+    /// Line number, counting from 1; a continued line's first line
+    pub line: usize,
+    /// What it holds
+    pub entry: Entry,
+    /// Its `#` comment, without the `#` and surrounding whitespace
+    pub comment: Option<String>,
+}
+
+/// The lines of one requirements file, blank ones left out
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParsedRequirements {
+    /// Lines in file order
+    pub lines: Vec<ParsedLine>,
+}
+
+impl ParsedRequirements {
+    /// The requirements, in file order
+    pub fn requirements(&self) -> impl Iterator<Item = &Requirement> {
+        self.lines.iter().filter_map(|line| match &line.entry {
+            Entry::Requirement { requirement, .. } => Some(requirement),
+            _ => None,
+        })
+    }
+}
+
+/// A line that is neither a requirement, an option, a location nor a
+/// comment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// Line number, counting from 1
+    pub line: usize,
+    /// What is wrong with it
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parse the contents of one requirements file, without following
+/// includes: backslash continuations are joined and each line is split
+/// into what it holds and its comment.
+pub fn parse_requirements(input: &str) -> Result<ParsedRequirements, ParseError> {
+    let mut parsed = ParsedRequirements::default();
+    for (line, text) in logical_lines(input) {
+        let (text, comment) = split_comment(&text);
+        let text = text.trim();
+        let comment = comment
+            .map(|comment| comment.trim().to_string())
+            .filter(|comment| !comment.is_empty());
+        let entry = if text.is_empty() {
+            if comment.is_none() {
+                continue;
+            }
+            Entry::Comment
+        } else if text.starts_with('-') {
+            Entry::Option(text.to_string())
+        } else {
+            // Per-requirement options such as --hash come after the requirement
+            let (requirement, options) = text.split_at(text.find(" --").unwrap_or(text.len()));
+            match Requirement::parse(requirement.trim()) {
+                Ok(requirement) => Entry::Requirement {
+                    requirement,
+                    hashes: hash_options(options),
+                },
+                Err(_) if is_location(requirement.trim()) => Entry::Location(text.to_string()),
+                Err(message) => return Err(ParseError { line, message }),
+            }
+        };
+        parsed.lines.push(ParsedLine {
+            line,
+            entry,
+            comment,
+        });
+    }
+    Ok(parsed)
+}
+
+/// The values of `--hash=...` and `--hash ...` among `options`
+fn hash_options(options: &str) -> Vec<String> {
+    let mut hashes = vec![];
+    let mut words = options.split_whitespace();
+    while let Some(word) = words.next() {
+        if let Some(hash) = word.strip_prefix("--hash=") {
+            hashes.push(hash.to_string());
+        } else if word == "--hash" {
+            hashes.extend(words.next().map(str::to_string));
+        }
+    }
+    hashes
+}
+
+/// Lines with backslash continuations joined, with the number of each
+/// one's first line
+fn logical_lines(contents: &str) -> Vec<(usize, String)> {
+    let mut lines = vec![];
+    let mut pending: Option<(usize, String)> = None;
+    for (i, raw) in contents.lines().enumerate() {
+        let (start, mut text) = pending.take().unwrap_or((i + 1, String::new()));
+        match raw.strip_suffix('\\') {
+            Some(continued) => {
+                text.push_str(continued);
+                pending = Some((start, text));
+            }
+            None => {
+                text.push_str(raw);
+                lines.push((start, text));
+            }
+        }
+    }
+    lines.extend(pending);
+    lines
+}
+
+/// `text` split before its `#` comment, and the comment after the `#`;
+/// like pip, a `#` only starts one at the beginning or after whitespace,
+/// so URL fragments stay
+fn split_comment(text: &str) -> (&str, Option<&str>) {
+    let mut previous = ' ';
+    for (i, c) in text.char_indices() {
+        if c == '#' && previous.is_whitespace() {
+            return (&text[..i], Some(&text[i + 1..]));
+        }
+        previous = c;
+    }
+    (text, None)
+}
+
+/// An option line split into its name and value: `-r base.txt`,
+/// `-rbase.txt`, `--requirement base.txt` and `--requirement=base.txt`
+/// all give `("-r" or "--requirement", "base.txt")`
+fn split_option(text: &str) -> (&str, &str) {
+    let end = if text.starts_with("--") {
+        text.find(|c: char| c == '=' || c.is_whitespace())
+            .unwrap_or(text.len())
+    } else {
+        text.char_indices().nth(2).map_or(text.len(), |(i, _)| i)
+    };
+    let value = text[end..].trim_start();
+    let value = value.strip_prefix('=').unwrap_or(value).trim();
+    (&text[..end], value)
+}
+
+/// Whether a line that isn't a valid requirement is a bare URL, VCS
+/// reference or path instead
+fn is_location(text: &str) -> bool {
+    text.contains("://")
+        || text.starts_with("file:")
+        || text.starts_with(['.', '/', '~'])
+        || [".whl", ".tar.gz", ".zip"]
+            .iter()
+            .any(|suffix| text.ends_with(suffix))
+}
+
+/// Follow an include or collect an index option from the option line
+/// `text`; other options are reported as ignored
+fn parse_option<F>(
+    text: &str,
+    origin: Origin,
+    load: &mut F,
+    stack: &mut Vec<PathBuf>,
+    parsed: &mut RequirementsFile,
+) -> Result<(), PpmError>
+where
+    F: FnMut(&Path) -> io::Result<String>,
+{
+    let skipped = |reason: &str| SkippedLine {
+        text: text.to_string(),
+        origin: origin.clone(),
+        reason: reason.to_string(),
+    };
+    let (option, value) = split_option(text);
+    match option {
+        "-r" | "--requirement" => {
+            let included = origin.file.parent().unwrap_or(Path::new("")).join(value);
+            parse_into(
+                &paths::normalize(&included),
+                Some(&origin),
+                load,
+                stack,
+                parsed,
+            )?;
+        }
+        "-e" | "--editable" => parsed.manual.push(skipped(
+            "editable install; add it under [packages] as { path = \"...\", editable = true }",
+        )),
+        "-i" | "--index-url" => match &parsed.index_url {
+            None => {
+                parsed.index_url = Some(IndexOption {
+                    url: value.to_string(),
+                    origin,
+                })
+            }
+            Some(first) if first.url == value => {}
+            Some(first) => {
+                let reason = format!("{} already sets the index", first.origin);
+                parsed.ignored.push(skipped(&reason));
+            }
+        },
+        "--extra-index-url" => parsed.extra_index_urls.push(IndexOption {
+            url: value.to_string(),
+            origin,
+        }),
+        "-c" | "--constraint" => parsed
+            .ignored
+            .push(skipped("constraints files aren't imported")),
+        _ => parsed
+            .ignored
+            .push(skipped("pip option ppm doesn't carry over")),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Project;
+    use std::collections::HashMap;
+
+    /// Parse `path` from an in-memory file set
+    fn parse(files: &[(&str, &str)], path: &str) -> Result<RequirementsFile, PpmError> {
+        let files: HashMap<PathBuf, String> = files
+            .iter()
+            .map(|(name, contents)| (PathBuf::from(name), contents.to_string()))
+            .collect();
+        parse_file(Path::new(path), |path| {
+            files
+                .get(path)
+                .cloned()
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+        })
+    }
+
+    #[test]
+    fn test_parse_file_follows_includes() {
+        let parsed = parse(
+            &[
+                (
+                    "requirements.txt",
+                    "-r requirements/dev.txt\nrequests==2.31.0 \\\n    --hash=sha256:abc  # pinned\n",
+                ),
+                (
+                    "requirements/dev.txt",
+                    "--requirement=base.txt\npytest>=8 ; python_version >= \"3.8\"\n",
+                ),
+                ("requirements/base.txt", "# shared\n\nflask==3.0.0\n"),
+            ],
+            "requirements.txt",
+        )
+        .unwrap();
+        let lines: Vec<(String, String)> = parsed
+            .requirements
+            .iter()
+            .map(|line| (line.requirement.to_string(), line.origin.to_string()))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                (
+                    "flask==3.0.0".to_string(),
+                    "requirements/base.txt:3".to_string()
+                ),
+                (
+                    "pytest>=8 ; python_version >= \"3.8\"".to_string(),
+                    "requirements/dev.txt:2".to_string()
+                ),
+                (
+                    "requests==2.31.0".to_string(),
+                    "requirements.txt:2".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            parsed.requirements[1].requirement.marker.as_deref(),
+            Some("python_version >= \"3.8\"")
+        );
+    }
+
+    #[test]
+    fn test_parse_file_detects_cycles() {
+        let err = parse(
+            &[
+                ("a.txt", "flask==3.0.0\n-r sub/b.txt\n"),
+                ("sub/b.txt", "-r ../a.txt\n"),
+            ],
+            "./a.txt",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Requirements files include each other: a.txt -> sub/b.txt -> a.txt"
+        );
+
+        let err = parse(&[("a.txt", "-r missing.txt\n")], "a.txt").unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("Failed to read missing.txt (included at a.txt:1):")
+        );
+        // The same file twice is fine as long as it doesn't include itself
+        let parsed = parse(
+            &[
+                ("a.txt", "-r base.txt\n-r base.txt\n"),
+                ("base.txt", "flask==3.0.0\n"),
+            ],
+            "a.txt",
+        )
+        .unwrap();
+        assert_eq!(parsed.requirements.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_file_collects_index_options() {
+        let parsed = parse(
+            &[
+                (
+                    "requirements.txt",
+                    "-i https://pypi.example.com/simple\n--extra-index-url=https://extra.example.com/simple\n-r other.txt\n--find-links ./wheels\n",
+                ),
+                (
+                    "other.txt",
+                    "--index-url https://pypi.example.com/simple\n--index-url https://elsewhere.example.com/simple\n-c constraints.txt\n",
+                ),
+            ],
+            "requirements.txt",
+        )
+        .unwrap();
+        let index = parsed.index_url.unwrap();
+        assert_eq!(index.url, "https://pypi.example.com/simple");
+        assert_eq!(index.origin.to_string(), "requirements.txt:1");
+        assert_eq!(
+            parsed.extra_index_urls[0].url,
+            "https://extra.example.com/simple"
+        );
+        let ignored: Vec<String> = parsed.ignored.iter().map(|line| line.to_string()).collect();
+        assert_eq!(
+            ignored,
+            [
+                "--index-url https://elsewhere.example.com/simple (other.txt:2): requirements.txt:1 already sets the index",
+                "-c constraints.txt (other.txt:3): constraints files aren't imported",
+                "--find-links ./wheels (requirements.txt:4): pip option ppm doesn't carry over",
+            ]
+        );
+        assert!(parsed.requirements.is_empty());
+    }
+
+    #[test]
+    fn test_parse_file_reports_editable_and_vcs_lines() {
+        let parsed = parse(
+            &[(
+                "requirements.txt",
+                "-e ../shared-lib\ngit+https://github.com/org/lib@v1#egg=lib\n./wheels/tool-1.0-py3-none-any.whl\nlib @ git+https://github.com/org/lib@v1\nother@https://example.com/other-1.0.tar.gz\n",
+            )],
+            "requirements.txt",
+        )
+        .unwrap();
+        let manual: Vec<(&str, usize)> = parsed
+            .manual
+            .iter()
+            .map(|line| (line.text.as_str(), line.origin.line))
+            .collect();
+        assert_eq!(
+            manual,
+            [
+                ("-e ../shared-lib", 1),
+                ("git+https://github.com/org/lib@v1#egg=lib", 2),
+                ("./wheels/tool-1.0-py3-none-any.whl", 3),
+            ]
+        );
+        assert!(parsed.manual[0].reason.contains("editable = true"));
+        assert_eq!(
+            parsed.requirements[0].requirement.url.as_deref(),
+            Some("git+https://github.com/org/lib@v1")
+        );
+        assert_eq!(parsed.requirements[1].requirement.name, "other");
+
+        let err = parse(&[("requirements.txt", "\nflask===\n")], "requirements.txt").unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("requirements.txt:2: Invalid requirement")
+        );
+    }
+
+    #[test]
+    fn test_generate_requirements_sorted() {
+        let mut packages = HashMap::new();
+        packages.insert("requests".to_string(), "2.31.0".into());
+        packages.insert("flask".to_string(), "3.0.0".into());
+        let config = Config::new(
+            Project::new(
+                "demo".to_string(),
+                "0.1.0".to_string(),
+                "".to_string(),
+                "main.py".to_string(),
+                None,
+            ),
+            packages,
+            HashMap::new(),
+        );
+        assert_eq!(
+            generate_requirements(&config, &RenderOptions::default()),
+            "flask==3.0.0\nrequests==2.31.0\n"
+        );
+    }
+
+    #[test]
+    fn test_generate_requirements_one_line_per_package() {
+        let mut packages = HashMap::new();
+        packages.insert("Flask".to_string(), "3.0.0".into());
+        packages.insert("flask".to_string(), "2.0.0".into());
+        packages.insert("typing_extensions".to_string(), "4.9.0".into());
+        packages.insert("Typing-Extensions".to_string(), "4.9.0".into());
+        let config = Config::new(
+            Project::new(
+                "demo".to_string(),
+                "0.1.0".to_string(),
+                "".to_string(),
+                "main.py".to_string(),
+                None,
+            ),
+            packages,
+            HashMap::new(),
+        );
+        assert_eq!(
+            generate_requirements(&config, &RenderOptions::default()),
+            "Flask==3.0.0\nTyping-Extensions==4.9.0\n"
+        );
+    }
+
+    #[test]
+    fn test_render_requirements() {
+        let packages: BTreeMap<String, PackageSpec> = [
+            ("requests", PackageSpec::new("2.31.0", None)),
+            ("Flask", PackageSpec::new("3.0.0", None)),
+            ("Werkzeug", PackageSpec::new("3.0.3", None)),
+            (
+                "pywin32",
+                PackageSpec::new("306", Some("sys_platform == 'win32'")),
+            ),
+            (
+                "lib",
+                PackageSpec::direct("git+https://github.com/org/lib@v1", None),
+            ),
+        ]
+        .into_iter()
+        .map(|(name, spec)| (name.to_string(), spec))
+        .collect();
+        // Sorted by normalized name, so case doesn't move `Werkzeug` first
+        assert_eq!(
+            render_requirements(&packages, &RenderOptions::default()),
+            "Flask==3.0.0\nlib @ git+https://github.com/org/lib@v1\npywin32==306 ; sys_platform == 'win32'\nrequests==2.31.0\nWerkzeug==3.0.3\n"
+        );
+        assert_eq!(
+            render_requirements(&BTreeMap::new(), &RenderOptions::default()),
+            ""
+        );
+
+        let pins: BTreeMap<String, PackageSpec> = [("Django", "5.0.6"), ("asgiref", "3.8.1")]
+            .into_iter()
+            .map(|(name, version)| (name.to_string(), PackageSpec::new(version, None)))
+            .collect();
+        let mut opts = RenderOptions {
+            header: Some("Pinned for deployment".to_string()),
+            hashes: HashMap::from([("django".to_string(), vec!["sha256:aa".to_string()])]),
+            ..RenderOptions::default()
+        };
+        // One package without a hash turns them all off
+        assert_eq!(
+            render_requirements(&pins, &opts),
+            "# Pinned for deployment\nasgiref==3.8.1\nDjango==5.0.6\n"
+        );
+        opts.hashes.insert(
+            "asgiref".to_string(),
+            vec!["sha256:bb".to_string(), "sha256:cc".to_string()],
+        );
+        assert_eq!(
+            render_requirements(&pins, &opts),
+            "# Pinned for deployment\nasgiref==3.8.1 \\\n    --hash=sha256:bb \\\n    --hash=sha256:cc\nDjango==5.0.6 \\\n    --hash=sha256:aa\n"
+        );
+    }
+
+    const LOCK: &str = include_str!("../tests/fixtures/requirements/ppmm.lock");
+
+    /// The project the fixture lock was made for, on httpx and requests
+    fn locked_project() -> Config {
+        let mut packages = HashMap::new();
+        packages.insert("httpx".to_string(), "0.27.0".into());
+        packages.insert("Requests".to_string(), "2.32.3".into());
+        Config::new(
+            Project::new(
+                "demo".to_string(),
+                "0.1.0".to_string(),
+                "".to_string(),
+                "main.py".to_string(),
+                None,
+            ),
+            packages,
+            HashMap::new(),
+        )
+    }
+
+    #[test]
+    fn test_generated_header() {
+        let config = locked_project();
+        assert_eq!(
+            generated_header(&config, "project.toml", Some("2024-05-01T09:30:00Z")),
+            "Generated by ppm from project.toml for demo 0.1.0 at 2024-05-01T09:30:00Z"
+        );
+        assert_eq!(
+            generated_header(&config, "ppmm.lock", None),
+            "Generated by ppm from ppmm.lock for demo 0.1.0"
+        );
+        let from_lock = format!(
+            "# {}\nsix==1.16.0\n",
+            generated_header(&config, "ppmm.lock", None)
+        );
+        assert_eq!(generated_from(&from_lock), Some("ppmm.lock"));
+        assert_eq!(
+            generated_from(
+                "# Generated by ppm from project.toml for demo 0.1.0 at 2024-05-01T09:30:00Z\n"
+            ),
+            Some("project.toml")
+        );
+        assert_eq!(
+            generated_from("# Generated by ppm at 2024-05-01T09:30:00Z\n"),
+            None
+        );
+        assert_eq!(generated_from("six==1.16.0\n"), None);
+        assert_eq!(generated_from(""), None);
+    }
+
+    #[test]
+    fn test_render_locked_golden() {
+        let config = locked_project();
+        let header = || Some(generated_header(&config, "ppmm.lock", None));
+        let rendered = render_locked(&config, LOCK, None, header()).unwrap();
+        assert_eq!(
+            rendered,
+            include_str!("../tests/fixtures/requirements/sections.txt")
+        );
+        // Reproducible: the same lock gives the same file
+        assert_eq!(
+            render_locked(&config, LOCK, None, header()).unwrap(),
+            rendered
+        );
+    }
+
+    #[test]
+    fn test_render_locked_annotated_golden() {
+        let config = locked_project();
+        let packages: Vec<crate::graph::InstalledPackage> =
+            serde_json::from_str(include_str!("../tests/fixtures/requirements/graph.json"))
+                .unwrap();
+        let graph = DependencyGraph::new(packages);
+        assert_eq!(
+            render_locked(
+                &config,
+                LOCK,
+                Some(&graph),
+                Some(generated_header(&config, "ppmm.lock", None))
+            )
+            .unwrap(),
+            include_str!("../tests/fixtures/requirements/annotated.txt")
+        );
+
+        // A package the graph doesn't know gets no annotation
+        let lock = format!("{}six==1.16.0\n", LOCK);
+        let rendered = render_locked(&config, &lock, Some(&graph), None).unwrap();
+        assert!(rendered.starts_with("# Direct dependencies"));
+        assert!(rendered.contains("\nsix==1.16.0\n"));
+        assert!(render_locked(&config, "flask==\n", None, None).is_err());
+    }
+
+    #[test]
+    fn test_parse_requirements() {
+        let parsed = parse_requirements(
+            "# Web stack\n\nflask[async]==3.0.0  # pinned by ops\nrequests>=2.31 ; python_version >= \"3.8\" \\\n    --hash=sha256:aa --hash sha256:bb\n-r base.txt\ngit+https://github.com/org/lib@v1#egg=lib\n",
+        )
+        .unwrap();
+        let lines: Vec<(usize, Option<&str>)> = parsed
+            .lines
+            .iter()
+            .map(|line| (line.line, line.comment.as_deref()))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                (1, Some("Web stack")),
+                (3, Some("pinned by ops")),
+                (4, None),
+                (6, None),
+                (7, None),
+            ]
+        );
+        assert_eq!(parsed.lines[0].entry, Entry::Comment);
+        let Entry::Requirement {
+            requirement,
+            hashes,
+        } = &parsed.lines[1].entry
+        else {
+            panic!("{:?}", parsed.lines[1]);
+        };
+        assert_eq!(requirement.extras, ["async"]);
+        assert!(hashes.is_empty());
+        let Entry::Requirement {
+            requirement,
+            hashes,
+        } = &parsed.lines[2].entry
+        else {
+            panic!("{:?}", parsed.lines[2]);
+        };
+        assert_eq!(
+            requirement.marker.as_deref(),
+            Some("python_version >= \"3.8\"")
+        );
+        assert_eq!(hashes, &["sha256:aa", "sha256:bb"]);
+        assert_eq!(
+            parsed.lines[3].entry,
+            Entry::Option("-r base.txt".to_string())
+        );
+        // The `#` of a URL fragment doesn't start a comment
+        assert_eq!(
+            parsed.lines[4].entry,
+            Entry::Location("git+https://github.com/org/lib@v1#egg=lib".to_string())
+        );
+        let names: Vec<&str> = parsed.requirements().map(|req| req.name.as_str()).collect();
+        assert_eq!(names, ["flask", "requests"]);
+
+        let err = parse_requirements("flask==3.0.0\n\n>=2.0\n").unwrap_err();
+        assert_eq!(err.line, 3);
+        assert!(err.to_string().starts_with("line 3: Invalid requirement"));
+        assert!(parse_requirements("").unwrap().lines.is_empty());
+    }
+
+    #[test]
+    fn test_diff_requirements() {
+        let generated = "flask==3.0.0\nrequests==2.31.0\n";
+        assert!(diff_requirements(generated, generated).is_empty());
+        assert!(diff_requirements("flask==3.0.0\r\nrequests==2.31.0", generated).is_empty());
+        assert!(diff_requirements("flask==3.0.0\n\nrequests==2.31.0\n\n", generated).is_empty());
+        assert!(
+            diff_requirements(
+                "# Generated by ppm at 2024-05-01T09:30:00Z\nflask==3.0.0  # web\nrequests==2.31.0\n",
+                generated
+            )
+            .is_empty()
+        );
+
+        assert_eq!(
+            diff_requirements("flask==2.0.0\nrequests==2.31.0\nsix==1.16.0\n", generated),
+            ["-flask==2.0.0", "+flask==3.0.0", "-six==1.16.0"]
+        );
+        assert_eq!(
+            diff_requirements("", generated),
+            ["+flask==3.0.0", "+requests==2.31.0"]
+        );
+        assert_eq!(
+            diff_requirements("requests==2.31.0\n", ""),
+            ["-requests==2.31.0"]
+        );
+    }
+}
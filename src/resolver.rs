@@ -0,0 +1,454 @@
+//! Full dependency resolution through pip's installation report.
+//!
+//! `pip install --dry-run --ignore-installed --report <file>` resolves the
+//! configured packages against the index without touching the venv and
+//! describes every distribution it would install, transitive ones
+//! included. The report format is documented at
+//! <https://pip.pypa.io/en/stable/reference/installation-report/>.
+
+use crate::context::ProjectContext;
+use crate::error::PpmError;
+use crate::packages::{compare_versions, normalize_pkg_name};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+
+/// Oldest pip with `--report`
+pub const MIN_PIP_VERSION: &str = "22.2";
+
+/// The top level of pip's installation report
+#[derive(Debug, Clone, Deserialize)]
+pub struct InstallationReport {
+    /// Report format version, `"1"` at the time of writing
+    pub version: String,
+    /// pip that wrote the report
+    pub pip_version: String,
+    /// Every distribution pip would install
+    pub install: Vec<InstallationItem>,
+    /// Marker variables of the interpreter the resolution was for
+    #[serde(default)]
+    pub environment: HashMap<String, String>,
+}
+
+/// One distribution in the report
+#[derive(Debug, Clone, Deserialize)]
+pub struct InstallationItem {
+    /// Where the distribution comes from
+    pub download_info: DownloadInfo,
+    /// Installed from a direct reference (URL, path or VCS) rather than
+    /// the index
+    pub is_direct: bool,
+    /// Named on the command line rather than pulled in as a dependency
+    #[serde(default)]
+    pub requested: bool,
+    /// Core metadata of the distribution
+    pub metadata: Metadata,
+}
+
+/// PEP 610 direct URL data for a distribution
+#[derive(Debug, Clone, Deserialize)]
+pub struct DownloadInfo {
+    /// Archive, repository or directory URL
+    pub url: String,
+    /// Set for wheels and sdists
+    pub archive_info: Option<ArchiveInfo>,
+    /// Set for VCS checkouts
+    pub vcs_info: Option<VcsInfo>,
+    /// Set for local directories
+    pub dir_info: Option<DirInfo>,
+}
+
+/// Hashes of a downloaded archive
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ArchiveInfo {
+    /// Algorithm name to hex digest, e.g. `sha256`
+    #[serde(default)]
+    pub hashes: HashMap<String, String>,
+    /// Legacy `<algorithm>=<digest>` form, used when `hashes` is absent
+    pub hash: Option<String>,
+}
+
+/// A VCS checkout
+#[derive(Debug, Clone, Deserialize)]
+pub struct VcsInfo {
+    /// `git`, `hg`, ...
+    pub vcs: String,
+    /// Exact revision that was checked out
+    pub commit_id: String,
+}
+
+/// A local directory
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DirInfo {
+    /// Installed with `-e`
+    #[serde(default)]
+    pub editable: bool,
+}
+
+/// The metadata fields ppm uses
+#[derive(Debug, Clone, Deserialize)]
+pub struct Metadata {
+    /// Distribution name as published
+    pub name: String,
+    /// Exact version
+    pub version: String,
+}
+
+/// One pinned distribution of a resolution
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedPackage {
+    /// Distribution name as published
+    pub name: String,
+    /// Exact version
+    pub version: String,
+    /// Direct reference such as `git+https://...@<commit>`, `None` for
+    /// index packages
+    pub source: Option<String>,
+    /// `algorithm:digest` hashes of the archive, empty when pip recorded
+    /// none (VCS and directory sources)
+    pub hashes: Vec<String>,
+    /// Configured in project.toml rather than pulled in as a dependency
+    pub requested: bool,
+    /// A local directory installed with `-e`
+    pub editable: bool,
+}
+
+impl ResolvedPackage {
+    /// The pip requirement line pinning this distribution, without hashes
+    pub fn requirement(&self) -> String {
+        match &self.source {
+            Some(source) if self.editable => format!("-e {}", source),
+            Some(source) => format!("{} @ {}", self.name, source),
+            None => format!("{}=={}", self.name, self.version),
+        }
+    }
+}
+
+impl From<InstallationItem> for ResolvedPackage {
+    fn from(item: InstallationItem) -> ResolvedPackage {
+        let info = item.download_info;
+        let source = item.is_direct.then(|| match &info.vcs_info {
+            Some(vcs) => format!("{}+{}@{}", vcs.vcs, info.url, vcs.commit_id),
+            None => info.url.clone(),
+        });
+        let mut hashes: Vec<String> = match info.archive_info {
+            Some(archive) if !archive.hashes.is_empty() => archive
+                .hashes
+                .iter()
+                .map(|(algorithm, digest)| format!("{}:{}", algorithm, digest))
+                .collect(),
+            Some(ArchiveInfo {
+                hash: Some(hash), ..
+            }) => hash
+                .split_once('=')
+                .map(|(algorithm, digest)| format!("{}:{}", algorithm, digest))
+                .into_iter()
+                .collect(),
+            _ => vec![],
+        };
+        hashes.sort();
+        ResolvedPackage {
+            name: item.metadata.name,
+            version: item.metadata.version,
+            source,
+            hashes,
+            requested: item.requested,
+            editable: info.dir_info.is_some_and(|dir| dir.editable),
+        }
+    }
+}
+
+/// The pinned distributions in a report, sorted by normalized name
+pub fn parse_report(json: &str) -> Result<Vec<ResolvedPackage>, PpmError> {
+    let report: InstallationReport = serde_json::from_str(json).map_err(|e| {
+        PpmError::Other(format!("Failed to parse pip's installation report: {}", e))
+    })?;
+    let mut resolved: Vec<ResolvedPackage> = report
+        .install
+        .into_iter()
+        .map(ResolvedPackage::from)
+        .collect();
+    resolved.sort_by_key(|pkg| normalize_pkg_name(&pkg.name));
+    Ok(resolved)
+}
+
+/// The version in `pip --version` output, e.g. `23.2.1` from
+/// `pip 23.2.1 from /venv/lib/python3.11/site-packages/pip (python 3.11)`
+pub fn parse_pip_version(output: &str) -> Option<&str> {
+    let version = output
+        .trim()
+        .strip_prefix("pip ")?
+        .split_whitespace()
+        .next()?;
+    version
+        .starts_with(|c: char| c.is_ascii_digit())
+        .then_some(version)
+}
+
+/// Fail with an upgrade hint unless `pip --version` output shows a pip
+/// that supports `--report`
+pub fn check_pip_version(output: &str, python: &Path) -> Result<(), PpmError> {
+    let upgrade = format!("{} -m pip install --upgrade pip", python.display());
+    match parse_pip_version(output) {
+        Some(version) if compare_versions(version, MIN_PIP_VERSION).is_ge() => Ok(()),
+        Some(version) => Err(PpmError::Subprocess(format!(
+            "pip {} in the venv can't resolve dependencies; pip {} or newer is required. Upgrade it with: {}",
+            version, MIN_PIP_VERSION, upgrade
+        ))),
+        None => Err(PpmError::Subprocess(format!(
+            "Could not tell the pip version from '{}'; pip {} or newer is required. Upgrade it with: {}",
+            output.trim(),
+            MIN_PIP_VERSION,
+            upgrade
+        ))),
+    }
+}
+
+/// Resolve every configured package except the project itself with the
+/// venv's pip, held to the `constraints` file when there is one. Markers
+/// are evaluated by pip for the venv interpreter.
+pub fn resolve(ctx: &ProjectContext) -> Result<Vec<ResolvedPackage>, PpmError> {
+    let mut names: Vec<&String> = ctx
+        .config
+        .packages
+        .iter()
+        .filter(|(_, spec)| !spec.is_self())
+        .map(|(name, _)| name)
+        .collect();
+    names.sort();
+    let mut args: Vec<String> = names
+        .into_iter()
+        .flat_map(|name| ctx.config.packages[name].pip_args(name))
+        .collect();
+    if !args.is_empty() {
+        args.extend(ctx.constraint_args()?);
+    }
+    resolve_with(&ctx.venv_pip(), &ctx.venv_python(), &ctx.root, &args)
+}
+
+/// Resolve `args` (as for `pip install`) with the pip at `pip`, run from
+/// `cwd` so relative paths resolve against it
+pub fn resolve_with(
+    pip: &Path,
+    python: &Path,
+    cwd: &Path,
+    args: &[String],
+) -> Result<Vec<ResolvedPackage>, PpmError> {
+    let version = Command::new(pip)
+        .arg("--version")
+        .output()
+        .map_err(|e| PpmError::Subprocess(format!("Failed to execute pip: {}", e)))?;
+    check_pip_version(&String::from_utf8_lossy(&version.stdout), python)?;
+    if args.is_empty() {
+        return Ok(vec![]);
+    }
+
+    // A file of our own under a random name: a predictable path in a
+    // shared temp dir could be a symlink planted by another user
+    let report = tempfile::Builder::new()
+        .prefix("ppm-report-")
+        .suffix(".json")
+        .tempfile()?;
+    let output = Command::new(pip)
+        .args([
+            "install",
+            "--dry-run",
+            "--ignore-installed",
+            "--quiet",
+            "--report",
+        ])
+        .arg(report.path())
+        .args(args)
+        .current_dir(cwd)
+        .output()
+        .map_err(|e| PpmError::Subprocess(format!("Failed to execute pip: {}", e)))?;
+    if !output.status.success() {
+        return Err(PpmError::from_pip(
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+            output.status.code(),
+        ));
+    }
+    parse_report(&std::fs::read_to_string(report.path())?)
+}
+
+/// The lock file `ppm lock` writes, relative to the project root
+pub const LOCK_FILE: &str = "ppmm.lock";
+
+/// ppmm.lock contents for a resolution: one pinned requirement per line,
+/// with `--hash` options when every distribution has a hash (pip checks
+/// all of them or none)
+pub fn render_lock(resolved: &[ResolvedPackage]) -> String {
+    let hashed = !resolved.is_empty() && resolved.iter().all(|pkg| !pkg.hashes.is_empty());
+    let mut out = String::from("# Generated by `ppm lock` from project.toml\n");
+    for pkg in resolved {
+        out.push_str(&pkg.requirement());
+        if hashed {
+            for hash in &pkg.hashes {
+                out.push_str(&format!(" \\\n    --hash={}", hash));
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// How the venv differs from a resolution
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Drift {
+    /// Resolved but not installed
+    Missing {
+        /// Distribution name
+        name: String,
+        /// Resolved version
+        version: String,
+    },
+    /// Installed at another version
+    Mismatch {
+        /// Distribution name
+        name: String,
+        /// Version in the venv
+        installed: String,
+        /// Resolved version
+        resolved: String,
+    },
+}
+
+/// Resolved distributions the venv lacks or has at another version.
+/// `installed` is keyed by normalized name, as from `pip list`.
+pub fn find_drift(resolved: &[ResolvedPackage], installed: &HashMap<String, String>) -> Vec<Drift> {
+    resolved
+        .iter()
+        .filter_map(|pkg| match installed.get(&normalize_pkg_name(&pkg.name)) {
+            None => Some(Drift::Missing {
+                name: pkg.name.clone(),
+                version: pkg.version.clone(),
+            }),
+            Some(version) if compare_versions(version, &pkg.version).is_ne() => {
+                Some(Drift::Mismatch {
+                    name: pkg.name.clone(),
+                    installed: version.clone(),
+                    resolved: pkg.version.clone(),
+                })
+            }
+            Some(_) => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPORT: &str = include_str!("../tests/fixtures/pip-report.json");
+
+    #[test]
+    fn test_parse_report_fixture() {
+        let resolved = parse_report(REPORT).unwrap();
+        let names: Vec<&str> = resolved.iter().map(|pkg| pkg.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "certifi",
+                "charset-normalizer",
+                "idna",
+                "mylib",
+                "requests",
+                "toolz",
+                "urllib3"
+            ]
+        );
+
+        let requests = &resolved[4];
+        assert!(requests.requested);
+        assert_eq!(requests.requirement(), "requests==2.31.0");
+        assert_eq!(
+            requests.hashes,
+            vec!["sha256:58cd2187c01e70e6e26505bca751777aa9f2ee0b7f4300988b709f44e013003f"]
+        );
+        assert!(!resolved[0].requested);
+
+        assert_eq!(
+            resolved[5].requirement(),
+            "toolz @ git+https://github.com/pytoolz/toolz@5dc6d3b1ff7e5dc2b1b7e8a0c3dbbd2d0e0fa6f1"
+        );
+        assert!(resolved[5].hashes.is_empty());
+        assert_eq!(resolved[3].requirement(), "-e file:///home/me/mylib");
+    }
+
+    #[test]
+    fn test_parse_report_rejects_garbage() {
+        assert!(parse_report("{\"version\": \"1\"}").is_err());
+        assert!(parse_report("not json").is_err());
+    }
+
+    #[test]
+    fn test_check_pip_version() {
+        let python = Path::new("venv/bin/python");
+        assert_eq!(
+            parse_pip_version(
+                "pip 23.2.1 from /venv/lib/python3.11/site-packages/pip (python 3.11)"
+            ),
+            Some("23.2.1")
+        );
+        assert!(check_pip_version("pip 22.2 from /x (python 3.10)", python).is_ok());
+        assert!(check_pip_version("pip 24.0 from /x (python 3.12)", python).is_ok());
+
+        let err = check_pip_version("pip 21.3.1 from /x (python 3.9)", python).unwrap_err();
+        assert!(err.to_string().contains("pip 21.3.1"));
+        assert!(
+            err.to_string()
+                .contains("venv/bin/python -m pip install --upgrade pip")
+        );
+        assert!(check_pip_version("", python).is_err());
+    }
+
+    #[test]
+    fn test_render_lock() {
+        let resolved = parse_report(REPORT).unwrap();
+        let lock = render_lock(&resolved);
+        // The git and directory sources have no hash, so none are written
+        assert!(!lock.contains("--hash"));
+        assert!(lock.contains("\nrequests==2.31.0\n"));
+
+        let index_only: Vec<ResolvedPackage> = resolved
+            .into_iter()
+            .filter(|pkg| pkg.source.is_none())
+            .collect();
+        let lock = render_lock(&index_only);
+        assert!(lock.contains(
+            "idna==3.6 \\\n    --hash=sha256:c05567e9c24a6b9faaa835c4821bad0590fbb9d5779e7caa6e1cc4978e7eb24f\n"
+        ));
+    }
+
+    #[test]
+    fn test_find_drift() {
+        let resolved = parse_report(REPORT).unwrap();
+        let installed: HashMap<String, String> = [
+            ("certifi", "2023.11.17"),
+            ("charset-normalizer", "3.3.2"),
+            ("idna", "3.4"),
+            ("mylib", "0.1.0"),
+            ("requests", "2.31.0"),
+            ("toolz", "0.12.0"),
+        ]
+        .iter()
+        .map(|(n, v)| (n.to_string(), v.to_string()))
+        .collect();
+
+        assert_eq!(
+            find_drift(&resolved, &installed),
+            vec![
+                Drift::Mismatch {
+                    name: "idna".to_string(),
+                    installed: "3.4".to_string(),
+                    resolved: "3.6".to_string(),
+                },
+                Drift::Missing {
+                    name: "urllib3".to_string(),
+                    version: "2.1.0".to_string(),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,297 @@
+//! Finishing a `ppm update` that Ctrl+C stopped.
+//!
+//! When an update is interrupted, the versions it already installed are
+//! saved to project.toml and the packages it never got to are written to
+//! [`PENDING_UPDATE`] in `.ppm/`. `ppm update --resume` picks them up: a
+//! plan younger than [`PENDING_MAX_AGE`] is installed as it stands, an
+//! older one is resolved again first.
+
+use crate::error::PpmError;
+use crate::settings::Config;
+use crate::state::StateDir;
+use crate::update::{Rewrite, UpdateMode, UpdateOutcome, UpdateSummary};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The interrupted update's remaining plan, inside the project's
+/// [`StateDir`]
+pub const PENDING_UPDATE: &str = "pending-update.json";
+
+/// How old a plan can get before `--resume` looks the versions up again
+pub const PENDING_MAX_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// A package the interrupted update still has to move
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingPackage {
+    /// Package name as spelled in project.toml
+    pub name: String,
+    /// Version pinned when the update was planned
+    pub from: String,
+    /// Version the update was moving it to
+    pub to: String,
+}
+
+/// What an interrupted update left to do
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingUpdate {
+    /// When the plan was resolved, in seconds since the Unix epoch
+    pub created_at: u64,
+    /// Packages not installed yet, in plan order
+    pub packages: Vec<PendingPackage>,
+    /// The mode the update was planned in
+    #[serde(default)]
+    pub mode: UpdateMode,
+    /// Update policies of pending packages that change with their version
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rewrites: Vec<Rewrite>,
+}
+
+impl PendingUpdate {
+    /// Move the packages named in `names` out of `summary` into a plan
+    /// resolved at `created_at`. Only packages whose outcome has a version
+    /// to install are kept.
+    pub fn take_from(
+        summary: &mut UpdateSummary,
+        names: &[String],
+        created_at: u64,
+    ) -> PendingUpdate {
+        let mut packages = vec![];
+        summary.packages.retain(|p| {
+            if !names.contains(&p.name) {
+                return true;
+            }
+            let (from, to) = match &p.outcome {
+                UpdateOutcome::Updated { from, to } => (from, to),
+                UpdateOutcome::UpToDate { version }
+                | UpdateOutcome::Held { version, .. }
+                | UpdateOutcome::Constrained { version, .. } => (version, version),
+                UpdateOutcome::Failed { .. } => return true,
+            };
+            packages.push(PendingPackage {
+                name: p.name.clone(),
+                from: from.clone(),
+                to: to.clone(),
+            });
+            false
+        });
+        summary.changelogs.retain(|name, _| !names.contains(name));
+        let (rewrites, kept) = summary
+            .rewrites
+            .drain(..)
+            .partition(|rewrite| names.contains(&rewrite.name));
+        summary.rewrites = kept;
+        PendingUpdate {
+            created_at,
+            packages,
+            mode: summary.mode,
+            rewrites,
+        }
+    }
+
+    /// Whether the plan is older than [`PENDING_MAX_AGE`] at `now`
+    pub fn is_stale(&self, now: u64) -> bool {
+        now.saturating_sub(self.created_at) >= PENDING_MAX_AGE.as_secs()
+    }
+
+    /// Names of the pending packages
+    pub fn names(&self) -> Vec<String> {
+        self.packages.iter().map(|p| p.name.clone()).collect()
+    }
+
+    /// The plan as an update summary for `config`, leaving out packages
+    /// project.toml no longer pins at the version the plan started from
+    /// or moved to; their names come back in the second list
+    pub fn summary(&self, config: &Config) -> (UpdateSummary, Vec<String>) {
+        let mut summary = UpdateSummary::new(false);
+        summary.mode = self.mode;
+        summary.rewrites = self.rewrites.clone();
+        let mut changed = vec![];
+        for p in &self.packages {
+            match config.packages.get(&p.name).and_then(|spec| spec.version()) {
+                Some(version) if version == p.from || version == p.to => {
+                    summary.push_resolved(&p.name, version, &p.to)
+                }
+                _ => changed.push(p.name.clone()),
+            }
+        }
+        (summary, changed)
+    }
+
+    /// Write the plan to `state`
+    pub fn store(&self, state: &StateDir) -> io::Result<()> {
+        let json = serde_json::to_vec_pretty(self).map_err(io::Error::other)?;
+        state.write(PENDING_UPDATE, &json).map(|_| ())
+    }
+
+    /// The plan in `state`, if an update left one
+    pub fn load(state: &StateDir) -> Result<Option<PendingUpdate>, PpmError> {
+        let path = state.subpath(PENDING_UPDATE);
+        let contents = match std::fs::read(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        serde_json::from_slice(&contents).map(Some).map_err(|e| {
+            PpmError::Other(format!(
+                "Can't read {}: {}; delete it to drop the interrupted update",
+                path.display(),
+                e
+            ))
+        })
+    }
+
+    /// Remove the plan from `state`
+    pub fn clear(state: &StateDir) -> io::Result<()> {
+        state.remove(PENDING_UPDATE).map(|_| ())
+    }
+}
+
+/// Seconds since the Unix epoch, the clock plans are dated by
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::UpdatePolicy;
+    use crate::update::PinStyle;
+
+    fn summary() -> UpdateSummary {
+        let mut summary = UpdateSummary::new(false);
+        summary.push_resolved("requests", "2.30.0", "2.32.3");
+        summary.push_resolved("idna", "3.6", "3.7");
+        summary.push_resolved("certifi", "2024.2.2", "2024.2.2");
+        summary.fail_update("idna", "boom");
+        summary.push_resolved("flask", "2.3.0", "3.0.3");
+        summary.changelogs.insert(
+            "flask".to_string(),
+            "https://flask.example/changes".to_string(),
+        );
+        summary
+    }
+
+    fn config(packages: &str) -> Config {
+        toml::from_str(&format!(
+            "[project]\nname = \"demo\"\nversion = \"0.1.0\"\ndescription = \"\"\nmain_script = \"./main.py\"\n\n[packages]\n{}\n[scripts]\n",
+            packages
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_take_the_skipped_packages_out_of_the_summary() {
+        let mut summary = summary();
+        let names = ["idna", "certifi", "flask"].map(String::from);
+        let pending = PendingUpdate::take_from(&mut summary, &names, 1_700_000_000);
+
+        assert_eq!(
+            pending.packages,
+            [
+                PendingPackage {
+                    name: "certifi".to_string(),
+                    from: "2024.2.2".to_string(),
+                    to: "2024.2.2".to_string(),
+                },
+                PendingPackage {
+                    name: "flask".to_string(),
+                    from: "2.3.0".to_string(),
+                    to: "3.0.3".to_string(),
+                },
+            ]
+        );
+        // Applying what's left only touches the finished and failed ones
+        let left: Vec<&str> = summary.packages.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(left, ["requests", "idna"]);
+        assert!(summary.changelogs.is_empty());
+        assert_eq!(pending.names(), ["certifi", "flask"]);
+    }
+
+    #[test]
+    fn test_plan_round_trips_through_the_state_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = StateDir::project(dir.path());
+        assert_eq!(PendingUpdate::load(&state).unwrap(), None);
+
+        let pending = PendingUpdate::take_from(&mut summary(), &["flask".to_string()], 42);
+        pending.store(&state).unwrap();
+        let json = std::fs::read_to_string(state.subpath(PENDING_UPDATE)).unwrap();
+        assert!(json.contains(r#""created_at": 42"#));
+        assert_eq!(PendingUpdate::load(&state).unwrap(), Some(pending));
+
+        PendingUpdate::clear(&state).unwrap();
+        assert_eq!(PendingUpdate::load(&state).unwrap(), None);
+
+        std::fs::write(state.subpath(PENDING_UPDATE), "{").unwrap();
+        let err = PendingUpdate::load(&state).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("delete it to drop the interrupted update")
+        );
+    }
+
+    #[test]
+    fn test_old_plans_are_stale() {
+        let pending = PendingUpdate {
+            created_at: 1_000,
+            packages: vec![],
+            mode: UpdateMode::Policy,
+            rewrites: vec![],
+        };
+        assert!(!pending.is_stale(1_000));
+        assert!(!pending.is_stale(1_000 + PENDING_MAX_AGE.as_secs() - 1));
+        assert!(pending.is_stale(1_000 + PENDING_MAX_AGE.as_secs()));
+        // A clock that went backwards doesn't make it stale
+        assert!(!pending.is_stale(10));
+    }
+
+    #[test]
+    fn test_resumed_summary_skips_packages_edited_since() {
+        let pending = PendingUpdate::take_from(
+            &mut summary(),
+            &["certifi", "flask", "requests"].map(String::from),
+            0,
+        );
+        let config = config("requests = \"2.31.0\"\nflask = \"2.3.0\"\ncertifi = \"2024.2.2\"\n");
+        let (resumed, changed) = pending.summary(&config);
+
+        assert_eq!(changed, ["requests"]);
+        let updated: Vec<_> = resumed.updated().collect();
+        assert_eq!(updated, [("flask", "2.3.0", "3.0.3")]);
+        assert_eq!(resumed.up_to_date().count(), 1);
+        assert!(!resumed.dry_run);
+    }
+
+    #[test]
+    fn test_rewrites_of_pending_packages_are_kept() {
+        let mut summary = summary();
+        summary.mode = UpdateMode::Latest {
+            force: false,
+            style: PinStyle::Exact,
+        };
+        summary.rewrites = vec![Rewrite {
+            name: "flask".to_string(),
+            from: UpdatePolicy::Patch,
+            to: UpdatePolicy::Pin,
+        }];
+        let pending = PendingUpdate::take_from(&mut summary, &["flask".to_string()], 0);
+        assert!(summary.rewrites.is_empty());
+
+        let dir = tempfile::tempdir().unwrap();
+        let state = StateDir::project(dir.path());
+        pending.store(&state).unwrap();
+        let loaded = PendingUpdate::load(&state).unwrap().unwrap();
+        assert_eq!(loaded, pending);
+
+        let config = config("flask = { version = \"2.3.0\", update = \"patch\" }\n");
+        let (resumed, _) = loaded.summary(&config);
+        assert_eq!(resumed.mode, summary.mode);
+        assert_eq!(
+            resumed.rewritten().collect::<Vec<_>>(),
+            [("flask", "~=2.3.0".to_string(), "==3.0.3".to_string())]
+        );
+    }
+}
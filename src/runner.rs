@@ -0,0 +1,330 @@
+//! Running python, pip and other programs behind a trait so command
+//! flows can be driven by tests without spawning anything.
+//!
+//! [`SystemRunner`] starts real processes; [`ScriptedRunner`] answers
+//! from canned results and records every invocation.
+
+use crate::interrupt;
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+
+/// How to run a program
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    /// Working directory, the current one when unset
+    pub cwd: Option<PathBuf>,
+    /// Leave stdin, stdout and stderr connected to ppm's instead of
+    /// capturing the output
+    pub inherit_stdio: bool,
+    /// Let the program finish when Ctrl+C is pressed, and stop it on the
+    /// second one, see [`interrupt::shield`]
+    pub shield: bool,
+}
+
+impl RunOptions {
+    /// Capture the output of a program run in `cwd`
+    pub fn in_dir(cwd: &Path) -> RunOptions {
+        RunOptions {
+            cwd: Some(cwd.to_path_buf()),
+            ..RunOptions::default()
+        }
+    }
+}
+
+/// Exit code and output of a finished program; the output is empty when
+/// it was run with [`RunOptions::inherit_stdio`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RunOutput {
+    /// Exit code, `None` when the program was killed by a signal
+    pub code: Option<i32>,
+    /// Everything the program wrote to stdout
+    pub stdout: Vec<u8>,
+    /// Everything the program wrote to stderr
+    pub stderr: Vec<u8>,
+}
+
+impl RunOutput {
+    /// A successful run that printed `stdout`
+    pub fn ok(stdout: &str) -> RunOutput {
+        RunOutput {
+            code: Some(0),
+            stdout: stdout.as_bytes().to_vec(),
+            stderr: vec![],
+        }
+    }
+
+    /// A run that exited with `code` after printing `stderr`
+    pub fn failed(code: i32, stderr: &str) -> RunOutput {
+        RunOutput {
+            code: Some(code),
+            stdout: vec![],
+            stderr: stderr.as_bytes().to_vec(),
+        }
+    }
+
+    /// Whether the program exited with 0
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+
+    /// stdout as text, invalid UTF-8 replaced
+    pub fn stdout_lossy(&self) -> String {
+        String::from_utf8_lossy(&self.stdout).into_owned()
+    }
+
+    /// stderr as text, invalid UTF-8 replaced
+    pub fn stderr_lossy(&self) -> String {
+        String::from_utf8_lossy(&self.stderr).into_owned()
+    }
+}
+
+/// Something that runs programs to completion
+pub trait CommandRunner: std::fmt::Debug + Send + Sync {
+    /// Run `program` with `args`. An `Err` means it couldn't be started;
+    /// a program that ran and failed is an `Ok` with a non-zero code.
+    fn run(
+        &self,
+        program: &OsStr,
+        args: &[OsString],
+        options: &RunOptions,
+    ) -> io::Result<RunOutput>;
+}
+
+/// Runs programs as child processes
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemRunner;
+
+impl CommandRunner for SystemRunner {
+    fn run(
+        &self,
+        program: &OsStr,
+        args: &[OsString],
+        options: &RunOptions,
+    ) -> io::Result<RunOutput> {
+        let mut cmd = Command::new(program);
+        cmd.args(args);
+        if let Some(cwd) = &options.cwd {
+            cmd.current_dir(cwd);
+        }
+        if options.shield {
+            interrupt::shield(&mut cmd);
+        }
+        if options.inherit_stdio {
+            let mut child = cmd.spawn()?;
+            let _tracked = options.shield.then(|| interrupt::track(&child));
+            let status = child.wait()?;
+            return Ok(RunOutput {
+                code: status.code(),
+                ..RunOutput::default()
+            });
+        }
+        let child = cmd
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let _tracked = options.shield.then(|| interrupt::track(&child));
+        let output = child.wait_with_output()?;
+        Ok(RunOutput {
+            code: output.status.code(),
+            stdout: output.stdout,
+            stderr: output.stderr,
+        })
+    }
+}
+
+/// `args` as owned strings, for building a [`CommandRunner::run`] call
+pub fn args<I, S>(args: I) -> Vec<OsString>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    args.into_iter()
+        .map(|arg| arg.as_ref().to_owned())
+        .collect()
+}
+
+/// One program run seen by a [`ScriptedRunner`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Invocation {
+    /// The program as given, usually a full path
+    pub program: PathBuf,
+    /// Its arguments
+    pub args: Vec<OsString>,
+    /// Working directory it was run in
+    pub cwd: Option<PathBuf>,
+}
+
+impl Invocation {
+    /// The program's file name without extension followed by the
+    /// arguments, e.g. `pip install requests==2.31.0`
+    pub fn command_line(&self) -> String {
+        let program = self
+            .program
+            .file_stem()
+            .unwrap_or(self.program.as_os_str())
+            .to_string_lossy();
+        std::iter::once(program.into_owned())
+            .chain(
+                self.args
+                    .iter()
+                    .map(|arg| arg.to_string_lossy().into_owned()),
+            )
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+type Response = Box<dyn Fn(&Invocation) -> io::Result<RunOutput> + Send + Sync>;
+
+/// Runner answering from canned results, for tests. A call is answered
+/// by the first response whose prefix its [`Invocation::command_line`]
+/// starts with; a call nothing matches fails as if the program didn't
+/// exist. Every call is recorded.
+#[derive(Default)]
+pub struct ScriptedRunner {
+    responses: Mutex<Vec<(String, Response)>>,
+    calls: Mutex<Vec<Invocation>>,
+}
+
+impl ScriptedRunner {
+    /// A runner that answers nothing yet
+    pub fn new() -> ScriptedRunner {
+        ScriptedRunner::default()
+    }
+
+    /// Answer calls starting with `prefix` with `output`
+    pub fn respond(&self, prefix: &str, output: RunOutput) -> &ScriptedRunner {
+        self.respond_with(prefix, move |_| Ok(output.clone()))
+    }
+
+    /// Answer calls starting with `prefix` by running `answer`, which can
+    /// also fake the program's effects on disk
+    pub fn respond_with(
+        &self,
+        prefix: &str,
+        answer: impl Fn(&Invocation) -> io::Result<RunOutput> + Send + Sync + 'static,
+    ) -> &ScriptedRunner {
+        if let Ok(mut responses) = self.responses.lock() {
+            responses.push((prefix.to_string(), Box::new(answer)));
+        }
+        self
+    }
+
+    /// Every call so far, in order
+    pub fn calls(&self) -> Vec<Invocation> {
+        self.calls
+            .lock()
+            .map(|calls| calls.clone())
+            .unwrap_or_default()
+    }
+
+    /// The [`Invocation::command_line`] of every call so far
+    pub fn command_lines(&self) -> Vec<String> {
+        self.calls().iter().map(Invocation::command_line).collect()
+    }
+}
+
+impl std::fmt::Debug for ScriptedRunner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ScriptedRunner")
+            .field("calls", &self.command_lines())
+            .finish_non_exhaustive()
+    }
+}
+
+impl CommandRunner for ScriptedRunner {
+    fn run(
+        &self,
+        program: &OsStr,
+        args: &[OsString],
+        options: &RunOptions,
+    ) -> io::Result<RunOutput> {
+        let invocation = Invocation {
+            program: PathBuf::from(program),
+            args: args.to_vec(),
+            cwd: options.cwd.clone(),
+        };
+        if let Ok(mut calls) = self.calls.lock() {
+            calls.push(invocation.clone());
+        }
+        let line = invocation.command_line();
+        let responses = self
+            .responses
+            .lock()
+            .map_err(|_| io::Error::other("scripted runner poisoned"))?;
+        match responses
+            .iter()
+            .find(|(prefix, _)| line.starts_with(prefix.as_str()))
+        {
+            Some((_, answer)) => answer(&invocation),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no scripted response for `{}`", line),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scripted_runner_answers_by_prefix() {
+        let runner = ScriptedRunner::new();
+        runner
+            .respond("pip list", RunOutput::ok("[]"))
+            .respond("pip", RunOutput::failed(1, "ERROR: no"));
+
+        let list = runner
+            .run(
+                OsStr::new("/venv/bin/pip"),
+                &args(["list", "--format=json"]),
+                &RunOptions::in_dir(Path::new("/project")),
+            )
+            .unwrap();
+        assert!(list.success());
+        assert_eq!(list.stdout_lossy(), "[]");
+
+        let install = runner
+            .run(
+                OsStr::new("pip"),
+                &args(["install", "x"]),
+                &RunOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(install.code, Some(1));
+        assert_eq!(install.stderr_lossy(), "ERROR: no");
+
+        let err = runner
+            .run(OsStr::new("python"), &args(["-V"]), &RunOptions::default())
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+        assert_eq!(
+            runner.command_lines(),
+            vec!["pip list --format=json", "pip install x", "python -V"]
+        );
+        assert_eq!(runner.calls()[0].cwd, Some(PathBuf::from("/project")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_system_runner_captures_output() {
+        let output = SystemRunner
+            .run(
+                OsStr::new("sh"),
+                &args(["-c", "echo out; echo err >&2; exit 3"]),
+                &RunOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(output.code, Some(3));
+        assert!(!output.success());
+        assert_eq!(output.stdout_lossy(), "out\n");
+        assert_eq!(output.stderr_lossy(), "err\n");
+    }
+}
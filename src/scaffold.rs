@@ -0,0 +1,73 @@
+//! Starter files `ppm new` writes besides the main script: a README.md
+//! and a smoke test, and the placeholder substitution they share with
+//! the LICENSE texts.
+//!
+//! Templates are embedded in ppm and hold `[key]` placeholders, filled in
+//! by [`fill`].
+
+/// Path of the README, relative to the project root
+pub const README_FILE: &str = "README.md";
+
+/// Path of the smoke test, relative to the project root
+pub const SMOKE_TEST_FILE: &str = "tests/test_smoke.py";
+
+/// The `test` script added along with the smoke test
+pub const TEST_SCRIPT: &str = "python -m unittest discover -s tests";
+
+const README_TEMPLATE: &str = include_str!("templates/README.md");
+const SMOKE_TEST_TEMPLATE: &str = include_str!("templates/test_smoke.py");
+
+/// `template` with each `[key]` of `values` replaced by its value;
+/// brackets around anything else are left alone
+pub fn fill(template: &str, values: &[(&str, &str)]) -> String {
+    values
+        .iter()
+        .fold(template.to_string(), |text, (key, value)| {
+            text.replace(&format!("[{}]", key), value)
+        })
+}
+
+/// The README of the project `name`, described by `description`, whose
+/// `ppm start` runs `main_script`
+pub fn readme(name: &str, description: &str, main_script: &str) -> String {
+    let about = if description.is_empty() {
+        String::new()
+    } else {
+        format!("{}\n\n", description)
+    };
+    fill(
+        README_TEMPLATE,
+        &[
+            ("name", name),
+            ("about", &about),
+            ("main_script", main_script.trim_start_matches("./")),
+        ],
+    )
+}
+
+/// A test that imports `main_script`, relative to the project root
+pub fn smoke_test(main_script: &str) -> String {
+    fill(
+        SMOKE_TEST_TEMPLATE,
+        &[("main_script", main_script.trim_start_matches("./"))],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fill_only_known_placeholders() {
+        assert_eq!(
+            fill("[a] and [b], not [c]", &[("a", "1"), ("b", "[a]")]),
+            "1 and [a], not [c]"
+        );
+
+        let text = readme("demo-app", "A demo.", "./src/main.py");
+        assert!(text.starts_with("# demo-app\n\nA demo.\n\n## Getting started\n"));
+        assert!(text.contains("ppm start       # run src/main.py"));
+        assert!(readme("demo-app", "", "./app.py").starts_with("# demo-app\n\n## Getting"));
+        assert!(smoke_test("./src/main.py").contains("parent.parent / \"src/main.py\""));
+    }
+}
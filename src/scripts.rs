@@ -0,0 +1,419 @@
+//! Picking the `[scripts]` entries `ppm run` runs.
+//!
+//! `ppm run lint` runs one script by name. A name with `*` or `?`, like
+//! `ppm run "test:*"`, or one given with `--filter`, is a glob matched
+//! against every script name, and the matches run one after another in
+//! name order. A bare `ppm run` runs the script `default-script` under
+//! `[project]` names.
+//!
+//! A script given as a table can list the packages it `requires`; before
+//! running it `ppm run` looks them up in the venv's dist-info directories,
+//! see [`missing_requirements`].
+//!
+//! Scripts run in a shell, `sh -c` or on Windows `cmd /C` (PowerShell
+//! with `windows-shell = "powershell"` under `[project]`), so pipes and
+//! globs work. A table with `shell = false` runs its command directly
+//! instead, split into words by [`argv`].
+//!
+//! A table's `windows`, `macos` or `linux` key replaces its `command` on
+//! that OS; a script with no command for this OS can't be selected.
+//!
+//! A name that isn't in `[scripts]` can be one of the project's own
+//! console scripts, see [`crate::entry_points`] and
+//! [`entry_point_script`]; `[scripts]` wins when both have it.
+
+use crate::aliases::split_words;
+use crate::entry_points::{EntryPoint, Source};
+use crate::error::PpmError;
+use crate::packages::normalize_pkg_name;
+use crate::requirement::Requirement;
+use crate::settings::Config;
+use crate::workspace::glob_match;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// A script to run: its name in `[scripts]` and its shell command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Script {
+    /// Name in `[scripts]`
+    pub name: String,
+    /// Shell command
+    pub command: String,
+    /// Requirements that must be installed for it to run
+    pub requires: Vec<String>,
+    /// Whether a shell runs the command
+    pub shell: bool,
+    /// Executable run directly instead of the command, for one of the
+    /// project's entry points
+    pub program: Option<PathBuf>,
+}
+
+/// What `ppm run` was asked to run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    /// The script with this name
+    Name(String),
+    /// Every script whose name matches this glob
+    Pattern(String),
+    /// The project's `default-script`
+    Default,
+}
+
+impl Target {
+    /// The target for `ppm run [NAME] [--filter PATTERN]`: a name with
+    /// `*` or `?` is a pattern too
+    pub fn new(name: Option<&str>, filter: Option<&str>) -> Target {
+        match (name, filter) {
+            (_, Some(pattern)) => Target::Pattern(pattern.to_string()),
+            (Some(name), None) if name.contains(['*', '?']) => Target::Pattern(name.to_string()),
+            (Some(name), None) => Target::Name(name.to_string()),
+            (None, None) => Target::Default,
+        }
+    }
+}
+
+/// The scripts `target` picks from `config`, sorted by name for a
+/// pattern. Fails when nothing matches, listing the scripts there are.
+pub fn select(config: &Config, target: &Target) -> Result<Vec<Script>, PpmError> {
+    let script = |name: &str| -> Option<Result<Script, PpmError>> {
+        config.scripts.get(name).map(|spec| {
+            Ok(Script {
+                name: name.to_string(),
+                command: spec.local_command(name)?.to_string(),
+                requires: spec.requires().to_vec(),
+                shell: spec.shell(),
+                program: None,
+            })
+        })
+    };
+    match target {
+        Target::Name(name) => script(name)
+            .map(|script| Ok(vec![script?]))
+            .unwrap_or_else(|| {
+                Err(PpmError::Other(format!(
+                    "Script with name '{}' does not exist",
+                    name
+                )))
+            }),
+        Target::Pattern(pattern) => {
+            let mut names: Vec<&String> = config
+                .scripts
+                .keys()
+                .filter(|name| glob_match(pattern, name))
+                .collect();
+            if names.is_empty() {
+                return Err(PpmError::Other(format!(
+                    "No scripts match '{}'; {}",
+                    pattern,
+                    available(config)
+                )));
+            }
+            names.sort();
+            names.into_iter().filter_map(|name| script(name)).collect()
+        }
+        Target::Default => {
+            let Some(name) = &config.project.default_script else {
+                return Err(PpmError::Other(format!(
+                    "No script given and no default-script under [project]; {}",
+                    available(config)
+                )));
+            };
+            script(name)
+                .map(|script| Ok(vec![script?]))
+                .unwrap_or_else(|| {
+                    Err(PpmError::Config(format!(
+                        "default-script '{}' under [project] isn't in [scripts]; {}",
+                        name,
+                        available(config)
+                    )))
+                })
+        }
+    }
+}
+
+/// The script running entry point `name` of `entry_points` from its
+/// executable in the venv at `venv_dir`, `None` when there's no such
+/// entry point. Fails when its executable isn't there, as before the
+/// project is installed into the venv.
+pub fn entry_point_script(
+    entry_points: &[EntryPoint],
+    name: &str,
+    venv_dir: &Path,
+) -> Result<Option<Script>, PpmError> {
+    let Some(entry_point) = entry_points.iter().find(|ep| ep.name == name) else {
+        return Ok(None);
+    };
+    let program = entry_point.executable(venv_dir);
+    if entry_point.source == Source::Pyproject || !program.is_file() {
+        return Err(PpmError::Other(format!(
+            "Entry point '{}' ({}) isn't installed in the venv; set install_self = true under [project] and run `ppm install`",
+            name, entry_point.target
+        )));
+    }
+    Ok(Some(Script {
+        name: name.to_string(),
+        command: program.display().to_string(),
+        requires: vec![],
+        shell: false,
+        program: Some(program),
+    }))
+}
+
+/// The entries of `requires` whose distribution isn't in `installed`,
+/// which maps normalized names to versions as
+/// [`crate::preflight::installed_versions`] reads them. Only names are
+/// compared; the versions a requirement asks for aren't checked.
+pub fn missing_requirements(
+    requires: &[String],
+    installed: &HashMap<String, String>,
+) -> Vec<String> {
+    requires
+        .iter()
+        .filter(|requirement| {
+            let name = Requirement::parse(requirement)
+                .map(|req| req.name)
+                .unwrap_or_else(|_| requirement.trim().to_string());
+            !installed.contains_key(&normalize_pkg_name(&name))
+        })
+        .cloned()
+        .collect()
+}
+
+/// The program and arguments of `script`'s command when it runs without
+/// a shell: words split like a shell would, quotes keeping them
+/// together, with `|`, `>` and globs passed on as they are
+pub fn argv(script: &Script) -> Result<Vec<String>, PpmError> {
+    let words = split_words(&script.command).map_err(|e| {
+        PpmError::Config(format!(
+            "Can't split the command of script '{}': {}",
+            script.name, e
+        ))
+    })?;
+    if words.is_empty() {
+        return Err(PpmError::Config(format!(
+            "Script '{}' has an empty command",
+            script.name
+        )));
+    }
+    Ok(words)
+}
+
+/// `available scripts: a, b`, or that there are none
+fn available(config: &Config) -> String {
+    let mut names: Vec<&str> = config.scripts.keys().map(String::as_str).collect();
+    if names.is_empty() {
+        return "[scripts] is empty".to_string();
+    }
+    names.sort();
+    format!("available scripts: {}", names.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROJECT: &str = include_str!("../tests/fixtures/scripts-project.toml");
+
+    fn config() -> Config {
+        toml::from_str(PROJECT).unwrap()
+    }
+
+    fn names(scripts: &[Script]) -> Vec<&str> {
+        scripts.iter().map(|script| script.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_target_from_the_arguments() {
+        assert_eq!(
+            Target::new(Some("lint"), None),
+            Target::Name("lint".to_string())
+        );
+        assert_eq!(
+            Target::new(Some("test:*"), None),
+            Target::Pattern("test:*".to_string())
+        );
+        assert_eq!(
+            Target::new(Some("test:?2e"), None),
+            Target::Pattern("test:?2e".to_string())
+        );
+        assert_eq!(
+            Target::new(None, Some("lint")),
+            Target::Pattern("lint".to_string())
+        );
+        assert_eq!(Target::new(None, None), Target::Default);
+        assert_eq!(config().project.default_script.as_deref(), Some("dev"));
+    }
+
+    #[test]
+    fn test_select_by_name_and_pattern() {
+        let config = config();
+        let lint = select(&config, &Target::Name("lint".to_string())).unwrap();
+        assert_eq!(
+            lint,
+            [Script {
+                name: "lint".to_string(),
+                command: "echo linting".to_string(),
+                requires: vec![],
+                shell: true,
+                program: None,
+            }]
+        );
+        let tests = select(&config, &Target::Pattern("test:*".to_string())).unwrap();
+        assert_eq!(names(&tests), ["test:e2e", "test:integration", "test:unit"]);
+        let docs = select(&config, &Target::Name("docs".to_string())).unwrap();
+        assert_eq!(docs[0].command, "echo docs built");
+        assert_eq!(docs[0].requires, ["mkdocs", "mkdocs-material>=9"]);
+        let one = select(&config, &Target::Pattern("*unit".to_string())).unwrap();
+        assert_eq!(names(&one), ["test:unit"]);
+
+        let err = select(&config, &Target::Name("build".to_string())).unwrap_err();
+        assert_eq!(err.to_string(), "Script with name 'build' does not exist");
+        let err = select(&config, &Target::Pattern("build:*".to_string())).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "No scripts match 'build:*'; available scripts: dev, docs, lint, pipe, test:e2e, test:integration, test:unit"
+        );
+    }
+
+    #[test]
+    fn test_select_the_default_script() {
+        let mut config = config();
+        assert_eq!(names(&select(&config, &Target::Default).unwrap()), ["dev"]);
+
+        config.project.default_script = Some("serve".to_string());
+        assert!(matches!(
+            select(&config, &Target::Default),
+            Err(PpmError::Config(_))
+        ));
+        config.project.default_script = None;
+        let err = select(&config, &Target::Default).unwrap_err();
+        assert!(matches!(err, PpmError::Other(_)));
+        assert!(
+            err.to_string()
+                .contains("available scripts: dev, docs, lint")
+        );
+        config.scripts.clear();
+        assert!(
+            select(&config, &Target::Default)
+                .unwrap_err()
+                .to_string()
+                .ends_with("[scripts] is empty")
+        );
+    }
+
+    #[test]
+    fn test_select_the_command_for_this_os() {
+        let mut config = config();
+        let os = std::env::consts::OS;
+        config.scripts.insert(
+            "clean".to_string(),
+            toml::from_str(&format!(
+                "command = \"echo generic\"\n{} = \"echo {}\"",
+                os, os
+            ))
+            .unwrap(),
+        );
+        let clean = select(&config, &Target::Name("clean".to_string())).unwrap();
+        assert_eq!(clean[0].command, format!("echo {}", os));
+
+        let elsewhere = if os == "windows" { "linux" } else { "windows" };
+        config.scripts.insert(
+            "open".to_string(),
+            toml::from_str(&format!("{} = \"start docs\"", elsewhere)).unwrap(),
+        );
+        let err = select(&config, &Target::Name("open".to_string())).unwrap_err();
+        assert!(matches!(err, PpmError::Config(_)));
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Script 'open' has no command for {}; add `{}` or `command` to its table in [scripts]",
+                os, os
+            )
+        );
+        assert!(select(&config, &Target::Pattern("*".to_string())).is_err());
+        assert!(select(&config, &Target::Pattern("cl*".to_string())).is_ok());
+    }
+
+    #[test]
+    fn test_entry_point_script() {
+        let dir = tempfile::tempdir().unwrap();
+        let venv = dir.path().join("venv");
+        let entry_point = |name: &str, source| EntryPoint {
+            name: name.to_string(),
+            target: format!("myapp.{}:main", name),
+            source,
+        };
+        let entry_points = [
+            entry_point("mycli", Source::Installed),
+            entry_point("serve", Source::Pyproject),
+        ];
+        assert_eq!(
+            entry_point_script(&entry_points, "lint", &venv).unwrap(),
+            None
+        );
+
+        // Declared, but not installed yet
+        let err = entry_point_script(&entry_points, "serve", &venv).unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("Entry point 'serve' (myapp.serve:main) isn't installed")
+        );
+        assert!(entry_point_script(&entry_points, "mycli", &venv).is_err());
+
+        let program = entry_points[0].executable(&venv);
+        std::fs::create_dir_all(program.parent().unwrap()).unwrap();
+        std::fs::write(&program, "").unwrap();
+        let script = entry_point_script(&entry_points, "mycli", &venv)
+            .unwrap()
+            .unwrap();
+        assert_eq!(script.name, "mycli");
+        assert_eq!(script.program, Some(program));
+        assert!(!script.shell);
+    }
+
+    #[test]
+    fn test_missing_requirements_by_name() {
+        let installed: HashMap<String, String> = [("mkdocs", "1.6.0"), ("pyyaml", "6.0.2")]
+            .iter()
+            .map(|(name, version)| (name.to_string(), version.to_string()))
+            .collect();
+        let requires = vec![
+            "MkDocs".to_string(),
+            "mkdocs_material>=9".to_string(),
+            "PyYAML==5.0".to_string(),
+        ];
+        assert_eq!(
+            missing_requirements(&requires, &installed),
+            ["mkdocs_material>=9"]
+        );
+        assert!(missing_requirements(&[], &HashMap::new()).is_empty());
+    }
+
+    #[test]
+    fn test_argv_keeps_quoted_words_and_pipes() {
+        let script = |command: &str| Script {
+            name: "demo".to_string(),
+            command: command.to_string(),
+            requires: vec![],
+            shell: false,
+            program: None,
+        };
+        assert_eq!(
+            argv(&script(r#"python -c "print('a  b')" --flag='x y'"#)).unwrap(),
+            ["python", "-c", "print('a  b')", "--flag=x y"]
+        );
+        assert_eq!(
+            argv(&script("ruff check . | tee lint.log")).unwrap(),
+            ["ruff", "check", ".", "|", "tee", "lint.log"]
+        );
+        assert!(matches!(
+            argv(&script("echo 'oops")),
+            Err(PpmError::Config(_))
+        ));
+        assert!(argv(&script("   ")).is_err());
+
+        let config = config();
+        assert!(config.scripts["lint"].shell());
+        assert!(!config.scripts["pipe"].shell());
+    }
+}
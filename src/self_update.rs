@@ -0,0 +1,530 @@
+//! Newer ppmm releases: noticing them and installing them.
+//!
+//! At most once per [`CHECK_INTERVAL`] the CLI starts a background
+//! `ppm self check-update` that asks crates.io for the newest release and
+//! records the answer in [`UPDATE_CHECK_FILE`] in the global cache
+//! directory. Later runs read that file and mention the newer version;
+//! the command being run never waits for the network.
+//!
+//! `ppm self update` replaces a prebuilt binary with one from GitHub
+//! releases, checked against the release's [`SHASUMS_FILE`]. Copies
+//! managed by cargo or a package manager are left to their installer, see
+//! [`managed_install`].
+
+use crate::error::PpmError;
+use crate::global_config::GlobalConfig;
+use crate::packages::compare_versions;
+use crate::state::StateDir;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Base URL of the crates.io API
+pub const CRATES_IO_API_URL: &str = "https://crates.io/api/v1/crates";
+
+/// The crate ppm is published as
+pub const CRATE_NAME: &str = "ppmm";
+
+/// Minimum time between two checks
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Result of the last check, inside the global [`StateDir`]
+pub const UPDATE_CHECK_FILE: &str = "update-check.json";
+
+/// Disables the check when set to anything but an empty string or `0`
+pub const NO_UPDATE_CHECK_ENV: &str = "PPM_NO_UPDATE_CHECK";
+
+/// Releases of ppmm on the GitHub API
+pub const GITHUB_RELEASES_API_URL: &str = "https://api.github.com/repos/Sumangal44/ppmm/releases";
+
+/// Release asset listing the sha256 of every other asset, as written by
+/// `sha256sum`
+pub const SHASUMS_FILE: &str = "SHASUMS256.txt";
+
+/// How long a check may take before it is given up
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long downloading a release binary may take
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// What the last check found
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CheckState {
+    /// When the check was made, in seconds since the Unix epoch
+    pub checked_at: u64,
+    /// Newest release found, `None` when the check hasn't finished or
+    /// failed
+    pub latest: Option<String>,
+}
+
+impl CheckState {
+    /// The recorded state, `None` if there is none or it can't be read
+    pub fn load(dir: &StateDir) -> Option<CheckState> {
+        let contents = std::fs::read(dir.subpath(UPDATE_CHECK_FILE)).ok()?;
+        serde_json::from_slice(&contents).ok()
+    }
+
+    /// Record the state for the next run
+    pub fn save(&self, dir: &StateDir) -> io::Result<()> {
+        dir.write(UPDATE_CHECK_FILE, &serde_json::to_vec(self)?)?;
+        Ok(())
+    }
+
+    /// Whether another check is due at `now`. A clock set back past the
+    /// last check makes one due too, rather than never again.
+    pub fn is_due(&self, now: u64) -> bool {
+        now < self.checked_at || now - self.checked_at >= CHECK_INTERVAL.as_secs()
+    }
+
+    /// The recorded release, if it is newer than `current`
+    pub fn newer_than(&self, current: &str) -> Option<&str> {
+        self.latest
+            .as_deref()
+            .filter(|latest| is_newer(latest, current))
+    }
+}
+
+/// Whether `latest` is a later release than `current`
+pub fn is_newer(latest: &str, current: &str) -> bool {
+    compare_versions(latest, current) == Ordering::Greater
+}
+
+/// Why automatic checks are off, `None` when they may run. `env` looks up
+/// environment variables.
+pub fn disabled_reason(
+    offline: bool,
+    config: &GlobalConfig,
+    env: impl Fn(&str) -> Option<String>,
+) -> Option<&'static str> {
+    let set = |name: &str| env(name).is_some_and(|value| !value.is_empty() && value != "0");
+    if offline {
+        Some("--offline was passed")
+    } else if set(NO_UPDATE_CHECK_ENV) {
+        Some("PPM_NO_UPDATE_CHECK is set")
+    } else if config.update_check == Some(false) {
+        Some("update-check = false in the global config")
+    } else if set("CI") && env("CI").as_deref() != Some("false") {
+        Some("running in CI")
+    } else {
+        None
+    }
+}
+
+/// Seconds since the Unix epoch
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+/// HTTP client identifying itself as ppmm; crates.io and the GitHub API
+/// reject requests without a user agent
+fn client(timeout: Duration) -> Result<Client, PpmError> {
+    Client::builder()
+        .user_agent(format!("{}/{}", CRATE_NAME, env!("CARGO_PKG_VERSION")))
+        .timeout(timeout)
+        .build()
+        .map_err(|e| PpmError::Network(format!("Failed to create HTTP client: {}", e)))
+}
+
+/// Newest stable release of ppmm on the crates.io API at `api_url`
+pub async fn latest_release(api_url: &str) -> Result<String, PpmError> {
+    let client = client(REQUEST_TIMEOUT)?;
+    let url = format!("{}/{}", api_url, CRATE_NAME);
+    let resp = client
+        .get(&url)
+        .send()
+        .await
+        .map_err(|e| PpmError::Network(format!("Failed to check for updates: {}", e)))?;
+    if resp.status() != StatusCode::OK {
+        return Err(PpmError::Network(format!(
+            "crates.io returned {} for '{}'",
+            resp.status(),
+            CRATE_NAME
+        )));
+    }
+    let json: serde_json::Value = resp
+        .json()
+        .await
+        .map_err(|e| PpmError::Network(format!("Failed to parse crates.io response: {}", e)))?;
+    json["crate"]["max_stable_version"]
+        .as_str()
+        .or_else(|| json["crate"]["max_version"].as_str())
+        .map(String::from)
+        .ok_or_else(|| PpmError::Network("crates.io response has no version".to_string()))
+}
+
+/// A GitHub release of ppmm
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Release {
+    /// The release tag, e.g. `v1.2.0`
+    pub tag: String,
+    /// Asset file name to download URL
+    pub assets: HashMap<String, String>,
+}
+
+impl Release {
+    /// The version the tag names, without its `v`
+    pub fn version(&self) -> &str {
+        self.tag.strip_prefix('v').unwrap_or(&self.tag)
+    }
+}
+
+/// The release tagged `tag` (with or without its leading `v`) on the
+/// GitHub API at `api_url`, or the latest release
+pub async fn fetch_release(api_url: &str, tag: Option<&str>) -> Result<Release, PpmError> {
+    let url = match tag {
+        Some(tag) => format!("{}/tags/v{}", api_url, tag.trim_start_matches('v')),
+        None => format!("{}/latest", api_url),
+    };
+    let resp = client(REQUEST_TIMEOUT)?
+        .get(&url)
+        .send()
+        .await
+        .map_err(|e| PpmError::Network(format!("Failed to fetch the release: {}", e)))?;
+    if resp.status() == StatusCode::NOT_FOUND {
+        return Err(PpmError::Other(match tag {
+            Some(tag) => format!("There is no ppmm release tagged '{}'", tag),
+            None => "ppmm has no published releases".to_string(),
+        }));
+    }
+    if !resp.status().is_success() {
+        return Err(PpmError::Network(format!(
+            "GitHub returned {} for {}",
+            resp.status(),
+            url
+        )));
+    }
+    let json: serde_json::Value = resp
+        .json()
+        .await
+        .map_err(|e| PpmError::Network(format!("Failed to parse the release: {}", e)))?;
+    let tag = json["tag_name"]
+        .as_str()
+        .ok_or_else(|| PpmError::Network("Release has no tag".to_string()))?;
+    let assets = json["assets"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|asset| {
+            Some((
+                asset["name"].as_str()?.to_string(),
+                asset["browser_download_url"].as_str()?.to_string(),
+            ))
+        })
+        .collect();
+    Ok(Release {
+        tag: tag.to_string(),
+        assets,
+    })
+}
+
+/// Download `url` into memory
+pub async fn download(url: &str) -> Result<Vec<u8>, PpmError> {
+    let failed =
+        |e: reqwest::Error| PpmError::Network(format!("Failed to download {}: {}", url, e));
+    let resp = client(DOWNLOAD_TIMEOUT)?
+        .get(url)
+        .send()
+        .await
+        .map_err(failed)?;
+    if !resp.status().is_success() {
+        return Err(PpmError::Network(format!(
+            "Failed to download {}: {}",
+            url,
+            resp.status()
+        )));
+    }
+    Ok(resp.bytes().await.map_err(failed)?.to_vec())
+}
+
+/// File name to sha256 hex digest from a `sha256sum`-style listing
+pub fn parse_shasums(shasums: &str) -> HashMap<String, String> {
+    shasums
+        .lines()
+        .filter_map(|line| {
+            let (digest, name) = line.trim().split_once(char::is_whitespace)?;
+            // `*` marks a file hashed in binary mode
+            let name = name.trim_start().trim_start_matches('*');
+            Some((name.to_string(), digest.to_lowercase()))
+        })
+        .collect()
+}
+
+/// Name of the release asset built for `os` and `arch` (as in
+/// [`std::env::consts`]), `None` when no binary is published for them
+pub fn asset_name(os: &str, arch: &str) -> Option<&'static str> {
+    match (os, arch) {
+        ("linux", "x86_64") => Some("ppmm-linux-x64"),
+        ("macos", "x86_64") => Some("ppmm-macos-x64"),
+        ("windows", "x86_64") => Some("ppmm-windows-x64.exe"),
+        _ => None,
+    }
+}
+
+/// How to upgrade the ppmm at `exe` when something other than a release
+/// download installed it, judging by its path; `None` for a prebuilt
+/// binary `ppm self update` may replace
+pub fn managed_install(exe: &Path) -> Option<&'static str> {
+    let path = exe.to_string_lossy().replace('\\', "/").to_lowercase();
+    if path.contains("/.cargo/bin/")
+        || path.contains("/target/debug/")
+        || path.contains("/target/release/")
+    {
+        Some("it was installed with cargo; run `cargo install ppmm` to upgrade")
+    } else if path.contains("/cellar/")
+        || path.contains("/homebrew/")
+        || path.contains("/linuxbrew/")
+    {
+        Some("it was installed with Homebrew; run `brew upgrade ppmm` to upgrade")
+    } else if path.contains("/scoop/") {
+        Some("it was installed with Scoop; run `scoop update ppmm` to upgrade")
+    } else if path.contains("/winget/") || path.contains("/microsoft/winget") {
+        Some("it was installed with winget; run `winget upgrade ppmm` to upgrade")
+    } else if path.starts_with("/usr/bin/") || path.starts_with("/bin/") {
+        Some("it belongs to a system package; upgrade it with your package manager")
+    } else {
+        None
+    }
+}
+
+/// Where [`replace_exe`] moves the running executable on Windows
+pub fn old_exe_path(exe: &Path) -> PathBuf {
+    let mut name = exe.file_name().unwrap_or_default().to_os_string();
+    name.push(".old");
+    exe.with_file_name(name)
+}
+
+/// Replace the executable at `exe` with `contents`. The new binary is
+/// written next to it and renamed over it, so `exe` is never half
+/// written. Windows can't replace a running executable, only rename it:
+/// the old one is moved to [`old_exe_path`] and deleted by a process
+/// that outlives this one.
+pub fn replace_exe(exe: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut name = exe.file_name().unwrap_or_default().to_os_string();
+    name.push(".new");
+    let new = exe.with_file_name(name);
+    std::fs::write(&new, contents)?;
+    let permissions = std::fs::metadata(exe)?.permissions();
+    std::fs::set_permissions(&new, permissions)?;
+    swap_exe(exe, &new).inspect_err(|_| {
+        let _ = std::fs::remove_file(&new);
+    })
+}
+
+#[cfg(not(windows))]
+fn swap_exe(exe: &Path, new: &Path) -> io::Result<()> {
+    std::fs::rename(new, exe)
+}
+
+#[cfg(windows)]
+fn swap_exe(exe: &Path, new: &Path) -> io::Result<()> {
+    let old = old_exe_path(exe);
+    // Left over from an earlier update whose cleanup didn't run
+    let _ = std::fs::remove_file(&old);
+    std::fs::rename(exe, &old)?;
+    if let Err(e) = std::fs::rename(new, exe) {
+        let _ = std::fs::rename(&old, exe);
+        return Err(e);
+    }
+    // Deleted once this process has exited and released the file
+    let _ = std::process::Command::new("cmd")
+        .arg("/C")
+        .arg(format!(
+            "ping -n 3 127.0.0.1 >NUL & del /F /Q \"{}\"",
+            old.display()
+        ))
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use httpmock::prelude::*;
+
+    /// Answer every request with `status` and `body` on localhost
+    async fn serve(status: u16, body: &str) -> MockServer {
+        let server = MockServer::start_async().await;
+        server.mock(|when, then| {
+            when.method(GET);
+            then.status(status)
+                .header("content-type", "application/json")
+                .body(body);
+        });
+        server
+    }
+
+    #[test]
+    fn test_version_comparison() {
+        assert!(is_newer("1.2.0", "1.1.5"));
+        assert!(is_newer("1.10.0", "1.9.9"));
+        assert!(!is_newer("1.1.5", "1.1.5"));
+        assert!(!is_newer("1.0.9", "1.1.5"));
+
+        let state = CheckState {
+            checked_at: 0,
+            latest: Some("2.0.0".to_string()),
+        };
+        assert_eq!(state.newer_than("1.1.5"), Some("2.0.0"));
+        assert_eq!(state.newer_than("2.0.0"), None);
+        let pending = CheckState {
+            checked_at: 0,
+            latest: None,
+        };
+        assert_eq!(pending.newer_than("1.1.5"), None);
+    }
+
+    #[test]
+    fn test_checks_are_throttled_to_one_per_interval() {
+        let day = CHECK_INTERVAL.as_secs();
+        let state = CheckState {
+            checked_at: 10 * day,
+            latest: None,
+        };
+        assert!(!state.is_due(10 * day));
+        assert!(!state.is_due(11 * day - 1));
+        assert!(state.is_due(11 * day));
+        // The clock went backwards
+        assert!(state.is_due(9 * day));
+
+        let dir = tempfile::tempdir().unwrap();
+        let global = StateDir::project(dir.path());
+        assert_eq!(CheckState::load(&global), None);
+        state.save(&global).unwrap();
+        assert_eq!(CheckState::load(&global), Some(state));
+    }
+
+    #[test]
+    fn test_disabled_reason() {
+        let env = |vars: &[(&str, &str)]| {
+            let vars: HashMap<String, String> = vars
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            move |name: &str| vars.get(name).cloned()
+        };
+        let config = GlobalConfig::default();
+        assert_eq!(disabled_reason(false, &config, env(&[])), None);
+        assert!(disabled_reason(true, &config, env(&[])).is_some());
+        assert!(disabled_reason(false, &config, env(&[("PPM_NO_UPDATE_CHECK", "1")])).is_some());
+        assert_eq!(
+            disabled_reason(false, &config, env(&[("PPM_NO_UPDATE_CHECK", "0")])),
+            None
+        );
+        assert_eq!(
+            disabled_reason(false, &config, env(&[("CI", "true")])),
+            Some("running in CI")
+        );
+        assert_eq!(
+            disabled_reason(false, &config, env(&[("CI", "false")])),
+            None
+        );
+        let off = GlobalConfig {
+            update_check: Some(false),
+            ..GlobalConfig::default()
+        };
+        assert!(disabled_reason(false, &off, env(&[])).is_some());
+    }
+
+    #[test]
+    fn test_assets_and_checksums() {
+        assert_eq!(asset_name("linux", "x86_64"), Some("ppmm-linux-x64"));
+        assert_eq!(
+            asset_name("windows", "x86_64"),
+            Some("ppmm-windows-x64.exe")
+        );
+        assert_eq!(asset_name("linux", "aarch64"), None);
+
+        let shasums = "\
+ABC123  ppmm-linux-x64
+def456 *ppmm-windows-x64.exe
+
+";
+        let digests = parse_shasums(shasums);
+        assert_eq!(digests.len(), 2);
+        assert_eq!(digests["ppmm-linux-x64"], "abc123");
+        assert_eq!(digests["ppmm-windows-x64.exe"], "def456");
+    }
+
+    #[test]
+    fn test_managed_installs_are_detected() {
+        let managed = |path: &str| managed_install(Path::new(path)).is_some();
+        assert!(managed("/home/me/.cargo/bin/ppmm"));
+        assert!(managed("C:\\Users\\me\\.cargo\\bin\\ppmm.exe"));
+        assert!(managed("/opt/homebrew/bin/ppmm"));
+        assert!(managed("/usr/local/Cellar/ppmm/1.1.5/bin/ppmm"));
+        assert!(managed(
+            "C:\\Users\\me\\scoop\\apps\\ppmm\\current\\ppmm.exe"
+        ));
+        assert!(managed("/usr/bin/ppmm"));
+        assert!(!managed("/usr/local/bin/ppmm"));
+        assert!(!managed("/home/me/bin/ppmm"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_replace_exe_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let exe = dir.path().join("ppmm");
+        std::fs::write(&exe, "old").unwrap();
+        std::fs::set_permissions(&exe, std::fs::Permissions::from_mode(0o755)).unwrap();
+        replace_exe(&exe, b"new").unwrap();
+        assert_eq!(std::fs::read_to_string(&exe).unwrap(), "new");
+        let mode = std::fs::metadata(&exe).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o755);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_release() {
+        let server = serve(
+            200,
+            r#"{"tag_name": "v1.2.0", "assets": [{"name": "ppmm-linux-x64", "browser_download_url": "https://example.com/ppmm-linux-x64"}, {"name": "broken"}]}"#,
+        )
+        .await;
+        let release = fetch_release(&server.base_url(), Some("1.2.0"))
+            .await
+            .unwrap();
+        assert_eq!(release.version(), "1.2.0");
+        assert_eq!(
+            release.assets,
+            HashMap::from([(
+                "ppmm-linux-x64".to_string(),
+                "https://example.com/ppmm-linux-x64".to_string()
+            )])
+        );
+
+        let server = serve(404, r#"{"message": "Not Found"}"#).await;
+        assert!(matches!(
+            fetch_release(&server.base_url(), Some("v9.9.9")).await,
+            Err(PpmError::Other(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_latest_release() {
+        let server = serve(
+            200,
+            r#"{"crate": {"name": "ppmm", "max_version": "1.3.0-beta.1", "max_stable_version": "1.2.0"}}"#,
+        )
+        .await;
+        assert_eq!(latest_release(&server.base_url()).await.unwrap(), "1.2.0");
+
+        let server = serve(404, r#"{"errors": []}"#).await;
+        assert!(matches!(
+            latest_release(&server.base_url()).await,
+            Err(PpmError::Network(_))
+        ));
+    }
+}
@@ -1,17 +1,115 @@
+//! The `project.toml` schema and its (de)serialization.
+
+use crate::compat;
+use crate::error::PpmError;
+use crate::foreign;
+use crate::marker::Marker;
+use crate::packages;
+use crate::paths;
+use crate::state;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::{fs, io::Error};
+use std::collections::{BTreeMap, HashMap};
+use std::{fmt, fs, io, path::Path};
+
+/// File name of the project configuration, relative to the project root
+pub const PROJECT_CONFIG_FILE: &str = "project.toml";
 
-#[derive(Deserialize, Serialize, Debug)]
+/// The `[project]` table
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct Project {
+    /// Project name
     pub name: String,
+    /// Semantic version, bumped by `ppm bump`
     pub version: String,
+    /// Free-form description
     pub description: String,
+    /// SPDX identifier of the project's license, like `MIT`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    /// Script run by `ppm start`
     pub main_script: String,
+    /// Virtual environment directory; `venv` when unset
     pub venv: Option<String>,
+    /// Permit a `venv` outside the project root
+    #[serde(
+        rename = "allow-external-venv",
+        default,
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    pub allow_external_venv: bool,
+    /// `false` runs scripts with the system interpreter instead of a venv
+    /// and refuses to install packages; a venv when unset
+    #[serde(rename = "use-venv", default, skip_serializing_if = "Option::is_none")]
+    pub use_venv: Option<bool>,
+    /// Install the project itself with `pip install -e .` after its packages
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub install_self: bool,
+    /// Concurrent pip processes for `ppm install` and `ppm update`; one
+    /// per core (at most 4) when unset or 0, a single pip run when 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jobs: Option<usize>,
+    /// Simple (PEP 503) index pip installs from; PyPI when unset, or the
+    /// workspace's when the project is a workspace member
+    #[serde(rename = "index-url", default, skip_serializing_if = "Option::is_none")]
+    pub index_url: Option<String>,
+    /// pip constraints file passed to every `pip install` as `-c`: a path
+    /// relative to the project root or an http(s) URL, see
+    /// [`crate::constraints`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub constraints: Option<String>,
+    /// Interpreter the venv is created with: a command like `python3.11`
+    /// or just a version like `3.11`; `python` when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub python: Option<String>,
+    /// Total coverage percentage `ppm test --coverage` must reach
+    #[serde(
+        rename = "coverage-min",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub coverage_min: Option<f64>,
+    /// File whose `__version__` `ppm bump --sync-source` rewrites; the
+    /// main script or the package's `__init__.py` when unset
+    #[serde(
+        rename = "version-file",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub version_file: Option<String>,
+    /// ppm releases that handle this file, like `>=1.2`; any when
+    /// unset, see [`crate::compat`]
+    #[serde(
+        rename = "ppm-version",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub ppm_version: Option<String>,
+    /// Shell `[scripts]` commands run in on Windows; `cmd` when unset
+    #[serde(
+        rename = "windows-shell",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub windows_shell: Option<WindowsShell>,
+    /// Script a bare `ppm run` runs, see [`crate::scripts`]
+    #[serde(
+        rename = "default-script",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub default_script: Option<String>,
+    /// Regenerate the requirements file after every command that changes
+    /// the packages, see [`RequirementsSettings`]
+    #[serde(
+        rename = "sync-requirements",
+        default,
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    pub sync_requirements: bool,
 }
 
 impl Project {
+    /// Build a `[project]` table from its fields
     pub fn new(
         name: String,
         version: String,
@@ -23,43 +121,740 @@ impl Project {
             name,
             version,
             description,
+            license: None,
             main_script,
             venv,
+            allow_external_venv: false,
+            use_venv: None,
+            install_self: false,
+            jobs: None,
+            index_url: None,
+            constraints: None,
+            python: None,
+            coverage_min: None,
+            version_file: None,
+            ppm_version: None,
+            windows_shell: None,
+            default_script: None,
+            sync_requirements: false,
+        }
+    }
+}
+
+/// A `[packages]` value: a pinned version from the package index, or a
+/// direct reference to a git repository, local path or URL. Index and
+/// git/URL entries can be limited to some environments by a PEP 508 marker.
+///
+/// ```toml
+/// requests = "2.31.0"
+/// pywin32 = "306; sys_platform == 'win32'"
+/// uvloop = { version = "0.19.0", marker = "sys_platform == 'linux'" }
+/// django = { version = "4.2.1", update = "minor" }
+/// internal = { git = "https://github.com/org/lib", rev = "abc123" }
+/// shared = { path = "../shared-lib", editable = true }
+/// wheel = { url = "https://example.com/wheel-1.0-py3-none-any.whl" }
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum PackageSpec {
+    /// `"2.31.0"` or `"306; sys_platform == 'win32'"`
+    Version(String),
+    /// `{ version = "...", marker = "..." }`
+    Table(PackageTable),
+    /// `{ git = "...", rev = "..." }`
+    Git(GitSource),
+    /// `{ path = "...", editable = true }`
+    Path(PathSource),
+    /// `{ url = "..." }`
+    Url(UrlSource),
+}
+
+/// How far `ppm update` may move an index package, set with `update` in
+/// the table form: `django = { version = "4.2.1", update = "minor" }`
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdatePolicy {
+    /// Never updated automatically
+    Pin,
+    /// Only to newer releases with the same major and minor version
+    Patch,
+    /// Only to newer releases with the same major version
+    Minor,
+    /// To the newest release
+    #[default]
+    Latest,
+}
+
+impl fmt::Display for UpdatePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            UpdatePolicy::Pin => "pin",
+            UpdatePolicy::Patch => "patch",
+            UpdatePolicy::Minor => "minor",
+            UpdatePolicy::Latest => "latest",
+        })
+    }
+}
+
+/// The shell `[scripts]` commands run in on Windows, set with
+/// `windows-shell` under `[project]`
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum WindowsShell {
+    /// `cmd /C`
+    #[default]
+    Cmd,
+    /// `powershell -NoProfile -Command`
+    Powershell,
+}
+
+/// The table form of an index [`PackageSpec`]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct PackageTable {
+    /// Pinned version
+    pub version: String,
+    /// Environment marker; the package is skipped where it is false
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub marker: Option<String>,
+    /// Update policy; [`UpdatePolicy::Latest`] when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub update: Option<UpdatePolicy>,
+}
+
+/// A package installed from a git repository
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct GitSource {
+    /// Repository URL, e.g. `https://github.com/org/lib`
+    pub git: String,
+    /// Commit, tag or branch to install; the default branch when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rev: Option<String>,
+    /// Branch followed by `ppm update --refresh-git`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    /// Tag followed by `ppm update --refresh-git`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    /// Environment marker; the package is skipped where it is false
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub marker: Option<String>,
+}
+
+/// A package installed from a local directory, relative to the project root
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct PathSource {
+    /// Directory containing the package's setup.py or pyproject.toml
+    pub path: String,
+    /// Install with `pip install -e`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub editable: bool,
+}
+
+/// A package installed from a URL, e.g. a wheel or sdist
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct UrlSource {
+    /// Archive URL
+    pub url: String,
+    /// Environment marker; the package is skipped where it is false
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub marker: Option<String>,
+}
+
+impl GitSource {
+    /// The ref pip checks out: `rev`, else `tag`, else `branch`
+    pub fn reference(&self) -> Option<&str> {
+        self.rev
+            .as_deref()
+            .or(self.tag.as_deref())
+            .or(self.branch.as_deref())
+    }
+
+    /// The branch or tag `ppm update --refresh-git` resolves to a new `rev`
+    pub fn tracked_ref(&self) -> Option<&str> {
+        self.branch.as_deref().or(self.tag.as_deref())
+    }
+
+    /// The `git+` URL pip installs, e.g. `git+https://github.com/org/lib@abc123`
+    pub fn pip_url(&self) -> String {
+        match self.reference() {
+            Some(reference) => format!("git+{}@{}", self.git, reference),
+            None => format!("git+{}", self.git),
         }
     }
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+impl PackageSpec {
+    /// A pinned version, with `marker` written inline after `;`
+    pub fn new(version: &str, marker: Option<&str>) -> PackageSpec {
+        match marker {
+            Some(marker) => PackageSpec::Version(format!("{}; {}", version, marker)),
+            None => PackageSpec::Version(version.to_string()),
+        }
+    }
+
+    /// A direct reference from a requirement's `name @ url`: `git+` URLs
+    /// become git entries (an `@ref` suffix becomes `rev`), others URL entries
+    pub fn direct(url: &str, marker: Option<&str>) -> PackageSpec {
+        let marker = marker.map(str::to_string);
+        match url.strip_prefix("git+") {
+            Some(repo) => {
+                // `@` after the last `/` separates the ref; earlier ones are credentials
+                let (git, rev) = match repo.rsplit_once('@') {
+                    Some((git, rev)) if !rev.contains('/') => (git, Some(rev.to_string())),
+                    _ => (repo, None),
+                };
+                PackageSpec::Git(GitSource {
+                    git: git.to_string(),
+                    rev,
+                    branch: None,
+                    tag: None,
+                    marker,
+                })
+            }
+            None => PackageSpec::Url(UrlSource {
+                url: url.to_string(),
+                marker,
+            }),
+        }
+    }
+
+    /// Whether this is the project itself, a path package pointing at `.`
+    pub fn is_self(&self) -> bool {
+        match self {
+            PackageSpec::Path(path) => paths::normalize(Path::new(&path.path))
+                .as_os_str()
+                .is_empty(),
+            _ => false,
+        }
+    }
+
+    /// Whether the package comes from the package index, so it has a
+    /// version that can be looked up and updated
+    pub fn is_index(&self) -> bool {
+        matches!(self, PackageSpec::Version(_) | PackageSpec::Table(_))
+    }
+
+    /// The pinned version, without any marker; `None` for direct references
+    pub fn version(&self) -> Option<&str> {
+        match self {
+            PackageSpec::Version(value) => value.split(';').next().map(str::trim),
+            PackageSpec::Table(table) => Some(&table.version),
+            _ => None,
+        }
+    }
+
+    /// The environment marker, if any
+    pub fn marker(&self) -> Option<&str> {
+        let marker = match self {
+            PackageSpec::Version(value) => value.split_once(';').map(|(_, marker)| marker),
+            PackageSpec::Table(table) => table.marker.as_deref(),
+            PackageSpec::Git(git) => git.marker.as_deref(),
+            PackageSpec::Url(url) => url.marker.as_deref(),
+            PackageSpec::Path(_) => None,
+        };
+        marker.map(str::trim).filter(|m| !m.is_empty())
+    }
+
+    /// How far `ppm update` may move the package; only the table form of
+    /// an index package can set anything but [`UpdatePolicy::Latest`]
+    pub fn update_policy(&self) -> UpdatePolicy {
+        match self {
+            PackageSpec::Table(table) => table.update.unwrap_or_default(),
+            _ => UpdatePolicy::Latest,
+        }
+    }
+
+    /// The same entry pinned to `version`, keeping its marker, policy and
+    /// form.
+    /// For a git entry the version is the commit `rev`; path and URL
+    /// entries are returned unchanged.
+    pub fn with_version(&self, version: &str) -> PackageSpec {
+        match self {
+            PackageSpec::Version(_) => PackageSpec::new(version, self.marker()),
+            PackageSpec::Table(table) => PackageSpec::Table(PackageTable {
+                version: version.to_string(),
+                ..table.clone()
+            }),
+            PackageSpec::Git(git) => PackageSpec::Git(GitSource {
+                rev: Some(version.to_string()),
+                ..git.clone()
+            }),
+            PackageSpec::Path(_) | PackageSpec::Url(_) => self.clone(),
+        }
+    }
+
+    /// The same index entry with update policy `policy`, switching a
+    /// plain version to the table form when it needs one; other entries
+    /// are returned unchanged
+    pub fn with_update_policy(&self, policy: UpdatePolicy) -> PackageSpec {
+        let update = (policy != UpdatePolicy::Latest).then_some(policy);
+        match self {
+            PackageSpec::Version(_) if update.is_none() => self.clone(),
+            PackageSpec::Version(_) => PackageSpec::Table(PackageTable {
+                version: self.version().unwrap_or_default().to_string(),
+                marker: self.marker().map(str::to_string),
+                update,
+            }),
+            PackageSpec::Table(table) => PackageSpec::Table(PackageTable {
+                update,
+                ..table.clone()
+            }),
+            _ => self.clone(),
+        }
+    }
+
+    /// The requirements.txt line for `name`, e.g.
+    /// `pywin32==306 ; sys_platform == 'win32'`, `lib @ git+https://...@rev`
+    /// or `-e ../shared-lib`
+    pub fn requirement(&self, name: &str) -> String {
+        let line = match self {
+            PackageSpec::Version(_) | PackageSpec::Table(_) => {
+                format!("{}=={}", name, self.version().unwrap_or_default())
+            }
+            PackageSpec::Git(git) => format!("{} @ {}", name, git.pip_url()),
+            PackageSpec::Url(url) => format!("{} @ {}", name, url.url),
+            PackageSpec::Path(path) if path.editable => format!("-e {}", path.path),
+            PackageSpec::Path(path) => path.path.clone(),
+        };
+        match self.marker() {
+            Some(marker) => format!("{} ; {}", line, marker),
+            None => line,
+        }
+    }
+
+    /// Arguments for `pip install`, with paths relative to the project root
+    pub fn pip_args(&self, name: &str) -> Vec<String> {
+        match self {
+            PackageSpec::Path(path) if path.editable => vec!["-e".to_string(), path.path.clone()],
+            _ => vec![self.requirement(name)],
+        }
+    }
+}
+
+/// The version for index packages, otherwise where the package comes from
+impl fmt::Display for PackageSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PackageSpec::Version(_) | PackageSpec::Table(_) => {
+                write!(f, "{}", self.version().unwrap_or_default())
+            }
+            PackageSpec::Git(git) => write!(f, "{}", git.pip_url()),
+            PackageSpec::Url(url) => write!(f, "{}", url.url),
+            PackageSpec::Path(path) if path.editable => write!(f, "{} (editable)", path.path),
+            PackageSpec::Path(path) => write!(f, "{}", path.path),
+        }
+    }
+}
+
+impl From<&str> for PackageSpec {
+    fn from(version: &str) -> PackageSpec {
+        PackageSpec::Version(version.to_string())
+    }
+}
+
+impl From<String> for PackageSpec {
+    fn from(version: String) -> PackageSpec {
+        PackageSpec::Version(version)
+    }
+}
+
+/// A `[scripts]` value: a shell command, or a table adding the packages
+/// the script needs in the venv, which `ppm run` checks before running it
+///
+/// ```toml
+/// test = "pytest"
+/// docs = { command = "mkdocs build", requires = ["mkdocs", "mkdocs-material"] }
+/// serve = { command = "python -m http.server 8000", shell = false }
+/// clean = { command = "rm -rf build", windows = "rmdir /s /q build" }
+/// ```
+///
+/// A table's `windows`, `macos` and `linux` keys replace `command` on
+/// that OS, see [`ScriptSpec::command_for`]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum ScriptSpec {
+    /// `"pytest"`
+    Command(String),
+    /// `{ command = "...", requires = [...] }`
+    Table(ScriptTable),
+}
+
+/// The table form of a [`ScriptSpec`]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ScriptTable {
+    /// Shell command, for every OS without its own key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// Command on Windows
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub windows: Option<String>,
+    /// Command on macOS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub macos: Option<String>,
+    /// Command on Linux
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub linux: Option<String>,
+    /// Requirements like `mkdocs` or `mkdocs-material>=9` that must be
+    /// installed for the script to run
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requires: Vec<String>,
+    /// Whether a shell runs the command; `false` runs it directly, split
+    /// into words like a shell would. A shell when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shell: Option<bool>,
+}
+
+impl ScriptTable {
+    /// The command under the key for `os`, a [`std::env::consts::OS`]
+    /// value
+    fn os_command(&self, os: &str) -> Option<&str> {
+        match os {
+            "windows" => self.windows.as_deref(),
+            "macos" => self.macos.as_deref(),
+            "linux" => self.linux.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+impl ScriptSpec {
+    /// The shell command the script runs on `os`, a
+    /// [`std::env::consts::OS`] value: the table's key for that OS, else
+    /// its `command`. `None` when the table has neither.
+    pub fn command_for(&self, os: &str) -> Option<&str> {
+        match self {
+            ScriptSpec::Command(command) => Some(command),
+            ScriptSpec::Table(table) => table.os_command(os).or(table.command.as_deref()),
+        }
+    }
+
+    /// Whether the command for `os` comes from the table's key for that
+    /// OS rather than `command`
+    pub fn is_os_variant(&self, os: &str) -> bool {
+        match self {
+            ScriptSpec::Command(_) => false,
+            ScriptSpec::Table(table) => table.os_command(os).is_some(),
+        }
+    }
+
+    /// The shell command the script runs on this OS
+    pub fn command(&self) -> Option<&str> {
+        self.command_for(std::env::consts::OS)
+    }
+
+    /// [`ScriptSpec::command`], failing when script `name` has no command
+    /// for this OS
+    pub fn local_command(&self, name: &str) -> Result<&str, PpmError> {
+        self.command().ok_or_else(|| {
+            PpmError::Config(format!(
+                "Script '{}' has no command for {}; add `{}` or `command` to its table in [scripts]",
+                name,
+                std::env::consts::OS,
+                std::env::consts::OS
+            ))
+        })
+    }
+
+    /// Requirements that must be installed for the script to run
+    pub fn requires(&self) -> &[String] {
+        match self {
+            ScriptSpec::Command(_) => &[],
+            ScriptSpec::Table(table) => &table.requires,
+        }
+    }
+
+    /// Whether a shell runs the command, as it does unless the table says
+    /// `shell = false`
+    pub fn shell(&self) -> bool {
+        match self {
+            ScriptSpec::Command(_) => true,
+            ScriptSpec::Table(table) => table.shell.unwrap_or(true),
+        }
+    }
+}
+
+impl From<&str> for ScriptSpec {
+    fn from(command: &str) -> ScriptSpec {
+        ScriptSpec::Command(command.to_string())
+    }
+}
+
+impl From<String> for ScriptSpec {
+    fn from(command: String) -> ScriptSpec {
+        ScriptSpec::Command(command)
+    }
+}
+
+/// The `[workspace]` table of a root project.toml that manages several
+/// member projects, each with its own project.toml and venv.
+///
+/// ```toml
+/// [workspace]
+/// members = ["services/*", "libs/shared"]
+/// index-url = "https://pypi.example.com/simple"
+/// python = "3.11"
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct Workspace {
+    /// Member directories relative to the workspace root; `*` and `?`
+    /// match within one path component
+    #[serde(default)]
+    pub members: Vec<String>,
+    /// `index-url` for members that don't set their own
+    #[serde(rename = "index-url", default, skip_serializing_if = "Option::is_none")]
+    pub index_url: Option<String>,
+    /// `python` for members that don't set their own
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub python: Option<String>,
+}
+
+impl Workspace {
+    /// The `[workspace]` table of the project.toml at `path`, `None` when
+    /// the file is missing, unreadable or has no such table
+    pub fn load_from_file(path: &Path) -> Result<Option<Workspace>, PpmError> {
+        let Ok(toml_string) = fs::read_to_string(path) else {
+            return Ok(None);
+        };
+        let mut table: toml::Table =
+            toml::from_str(&toml_string).map_err(|source| PpmError::ConfigParse {
+                path: path.to_string_lossy().into_owned(),
+                source,
+            })?;
+        let Some(workspace) = table.remove("workspace") else {
+            return Ok(None);
+        };
+        workspace
+            .try_into()
+            .map(Some)
+            .map_err(|source| PpmError::ConfigParse {
+                path: path.to_string_lossy().into_owned(),
+                source,
+            })
+    }
+}
+
+/// The `[requirements]` table: how `ppm gen` writes the requirements file
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct RequirementsSettings {
+    /// The file, relative to the project root; requirements.txt when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+}
+
+impl RequirementsSettings {
+    /// Whether nothing is set, so the table can be left out
+    pub fn is_default(&self) -> bool {
+        self == &RequirementsSettings::default()
+    }
+}
+
+/// A whole `project.toml`
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct Config {
+    /// Project metadata
     pub project: Project,
-    pub packages: HashMap<String, String>,
-    pub scripts: HashMap<String, String>,
+    /// Package name to pinned version
+    pub packages: HashMap<String, PackageSpec>,
+    /// Script name to shell command, run by `ppm run`
+    pub scripts: HashMap<String, ScriptSpec>,
+    /// Member projects, when this project.toml is also a workspace root
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspace: Option<Workspace>,
+    /// Alias name to the ppm command line it stands for, see
+    /// [`crate::aliases`]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, String>,
+    /// Git hook name to the ppm command or script it runs, see
+    /// [`crate::hooks`]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub hooks: BTreeMap<String, String>,
+    /// How the requirements file is generated
+    #[serde(default, skip_serializing_if = "RequirementsSettings::is_default")]
+    pub requirements: RequirementsSettings,
 }
 
 impl Config {
+    /// Build a config from its tables
     pub fn new(
         project: Project,
-        packages: HashMap<String, String>,
-        scripts: HashMap<String, String>,
+        packages: HashMap<String, PackageSpec>,
+        scripts: HashMap<String, ScriptSpec>,
     ) -> Config {
         Config {
             project,
             packages,
             scripts,
+            workspace: None,
+            aliases: BTreeMap::new(),
+            hooks: BTreeMap::new(),
+            requirements: RequirementsSettings::default(),
         }
     }
 
-    pub fn write_to_file(&self, path: &str) -> Result<(), Error> {
-        let toml_string = toml::to_string(&self)
-            .map_err(|e| Error::new(std::io::ErrorKind::InvalidData, e))?;
-        fs::write(path, toml_string)
+    /// Serialize to TOML and atomically replace `path` with it
+    pub fn write_to_file(&self, path: &str) -> Result<(), PpmError> {
+        let toml_string =
+            toml::to_string(&self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        state::write_atomic(Path::new(path), toml_string.as_bytes()).map_err(|e| {
+            io::Error::new(e.kind(), format!("Failed to write {}: {}", path, e)).into()
+        })
+    }
+
+    /// The requirements file `ppm gen` writes, relative to the project
+    /// root
+    pub fn requirements_file(&self) -> &str {
+        self.requirements
+            .output
+            .as_deref()
+            .unwrap_or(crate::requirements::REQUIREMENTS_FILE)
+    }
+
+    /// Whether `ppm install` installs the project itself, via
+    /// `install_self` or a `{ path = "." }` package
+    pub fn installs_self(&self) -> bool {
+        self.project.install_self || self.packages.values().any(PackageSpec::is_self)
     }
 
-    pub fn load_from_file(path: &str) -> Result<Config, Error> {
-        let toml_string = fs::read_to_string(path)?;
-        let config: Config = toml::from_str(&toml_string)
-            .map_err(|e| Error::new(std::io::ErrorKind::InvalidData, e))?;
-        Ok(config)
+    /// Fail if two `[packages]` keys name the same package, e.g. `Flask`
+    /// and `flask`
+    pub fn check_duplicates(&self) -> Result<(), PpmError> {
+        let versions: HashMap<String, String> = self
+            .packages
+            .iter()
+            .map(|(name, spec)| (name.clone(), spec.to_string()))
+            .collect();
+        let duplicates = packages::find_duplicates(&versions);
+        if duplicates.is_empty() {
+            return Ok(());
+        }
+        let listed: Vec<String> = duplicates.iter().map(|d| d.to_string()).collect();
+        Err(PpmError::Config(format!(
+            "Duplicate packages in [packages]: {}; keep one entry per package",
+            listed.join("; ")
+        )))
+    }
+
+    /// Fail if a `[packages]` marker can't be parsed
+    pub fn check_markers(&self) -> Result<(), PpmError> {
+        let mut names: Vec<&String> = self.packages.keys().collect();
+        names.sort();
+        for name in names {
+            if let Some(marker) = self.packages[name].marker() {
+                Marker::parse(marker).map_err(|e| {
+                    PpmError::Config(format!(
+                        "Invalid marker for '{}' in [packages]: {}",
+                        name, e
+                    ))
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Fail if a `[scripts]` table has no command for any OS
+    pub fn check_scripts(&self) -> Result<(), PpmError> {
+        let mut names: Vec<&String> = self.scripts.keys().collect();
+        names.sort();
+        for name in names {
+            if let ScriptSpec::Table(table) = &self.scripts[name]
+                && table.command.is_none()
+                && table.windows.is_none()
+                && table.macos.is_none()
+                && table.linux.is_none()
+            {
+                return Err(PpmError::Config(format!(
+                    "Script '{}' in [scripts] needs a `command`, or a `windows`, `macos` or `linux` one",
+                    name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the paths in `[project]` stay inside `root`, the
+    /// directory holding project.toml.
+    ///
+    /// A `venv` outside the root is an error unless `allow-external-venv`
+    /// is set, and so is a `version-file` outside it, since `ppm bump`
+    /// writes to it. A `main_script` outside the root is allowed but
+    /// returned as a warning.
+    pub fn check_paths(&self, root: &Path) -> Result<Vec<String>, PpmError> {
+        if let Some(venv) = &self.project.venv
+            && !self.project.allow_external_venv
+            && !paths::is_within(root, Path::new(venv))
+        {
+            return Err(PpmError::Config(format!(
+                "venv '{}' is outside the project root; set allow-external-venv = true under [project] to use it",
+                venv
+            )));
+        }
+
+        if let Some(file) = &self.project.version_file
+            && !paths::is_within(root, Path::new(file))
+        {
+            return Err(PpmError::Config(format!(
+                "version-file '{}' is outside the project root",
+                file
+            )));
+        }
+
+        let mut warnings = vec![];
+        if !paths::is_within(root, Path::new(&self.project.main_script)) {
+            warnings.push(format!(
+                "main_script '{}' is outside the project root",
+                self.project.main_script
+            ));
+        }
+        Ok(warnings)
+    }
+
+    /// The config as a TOML table, e.g. for merging
+    pub fn to_table(&self) -> Result<toml::Table, PpmError> {
+        toml::Table::try_from(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e).into())
+    }
+
+    /// Rebuild a config from a table produced by [`to_table`](Self::to_table);
+    /// `path` is only used in the error
+    pub fn from_table(table: toml::Table, path: &str) -> Result<Config, PpmError> {
+        toml::Value::Table(table)
+            .try_into()
+            .map_err(|source| PpmError::ConfigParse {
+                path: path.to_string(),
+                source,
+            })
+    }
+
+    /// Read and parse `path`; a missing file is [`PpmError::ConfigNotFound`]
+    pub fn load_from_file(path: &str) -> Result<Config, PpmError> {
+        if !Path::new(path).exists() {
+            return Err(PpmError::ConfigNotFound(path.to_string()));
+        }
+        let toml_string = fs::read_to_string(path)
+            .map_err(|e| io::Error::new(e.kind(), format!("Failed to read {}: {}", path, e)))?;
+        let parse_error = |source| PpmError::ConfigParse {
+            path: path.to_string(),
+            source,
+        };
+        // Checked first: the rest may use constructs this ppm doesn't know
+        let table: toml::Table = toml::from_str(&toml_string).map_err(parse_error)?;
+        if let Some(requirement) = table
+            .get("project")
+            .and_then(|project| project.get("ppm-version"))
+            .and_then(toml::Value::as_str)
+        {
+            compat::check(requirement).map_err(PpmError::Config)?;
+        }
+        toml::from_str(&toml_string).map_err(|source| match foreign::detect(&table) {
+            Some(format) => PpmError::Config(foreign::describe(path, format)),
+            None => parse_error(source),
+        })
     }
 }
 
@@ -94,20 +889,471 @@ mod tests {
             None,
         );
         let mut packages = HashMap::new();
-        packages.insert("requests".to_string(), "2.0.0".to_string());
-        
+        packages.insert("requests".to_string(), "2.0.0".into());
+
         let config = Config::new(project, packages, HashMap::new());
-        
+
         // Write to temp file
         let file = NamedTempFile::new().expect("Failed to create temp file");
         let path = file.path().to_str().unwrap();
-        
+
         config.write_to_file(path).expect("Failed to write config");
-        
+
         // Load back
         let loaded = Config::load_from_file(path).expect("Failed to load config");
-        
+
         assert_eq!(loaded.project.name, "test");
-        assert_eq!(loaded.packages.get("requests"), Some(&"2.0.0".to_string()));
+        assert_eq!(loaded.packages["requests"].version(), Some("2.0.0"));
+    }
+
+    #[test]
+    fn test_load_missing_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("project.toml");
+        let err = Config::load_from_file(path.to_str().unwrap()).unwrap_err();
+        assert!(matches!(err, PpmError::ConfigNotFound(_)));
+        assert_eq!(err.exit_code(), 2);
+    }
+
+    #[test]
+    fn test_load_malformed_config() {
+        let file = NamedTempFile::new().unwrap();
+        fs::write(file.path(), "[project\nname = ").unwrap();
+        let path = file.path().to_str().unwrap();
+        match Config::load_from_file(path).unwrap_err() {
+            PpmError::ConfigParse { path: p, .. } => assert_eq!(p, path),
+            other => panic!("expected ConfigParse, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_load_foreign_manifest() {
+        let file = NamedTempFile::new().unwrap();
+        fs::write(
+            file.path(),
+            "[tool.poetry]\nname = \"demo\"\nversion = \"0.1.0\"\n\n[tool.poetry.dependencies]\npython = \"^3.11\"\n",
+        )
+        .unwrap();
+        let path = file.path().to_str().unwrap();
+        match Config::load_from_file(path).unwrap_err() {
+            PpmError::Config(message) => {
+                assert!(message.starts_with(&format!("{} looks like a Poetry manifest", path)));
+                assert!(message.contains("`ppm init`"));
+            }
+            other => panic!("expected Config, got {:?}", other),
+        }
+    }
+
+    fn config_with(venv: Option<&str>, main_script: &str) -> Config {
+        Config::new(
+            Project::new(
+                "test".to_string(),
+                "1.0.0".to_string(),
+                "".to_string(),
+                main_script.to_string(),
+                venv.map(|v| v.to_string()),
+            ),
+            HashMap::new(),
+            HashMap::new(),
+        )
+    }
+
+    #[test]
+    fn test_check_paths_rejects_external_venv() {
+        let dir = tempfile::tempdir().unwrap();
+        for venv in ["../../other", "/usr", "venv/../../escape"] {
+            let err = config_with(Some(venv), "main.py")
+                .check_paths(dir.path())
+                .unwrap_err();
+            assert!(matches!(err, PpmError::Config(_)));
+            assert!(err.to_string().contains(venv));
+        }
+
+        let mut config = config_with(Some("/usr"), "main.py");
+        config.project.allow_external_venv = true;
+        assert!(config.check_paths(dir.path()).unwrap().is_empty());
+
+        assert!(
+            config_with(Some(".venv"), "./src/main.py")
+                .check_paths(dir.path())
+                .unwrap()
+                .is_empty()
+        );
+        assert!(
+            config_with(None, "main.py")
+                .check_paths(dir.path())
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_check_paths_rejects_external_version_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = config_with(None, "main.py");
+        config.project.version_file = Some("../other/__init__.py".to_string());
+        let err = config.check_paths(dir.path()).unwrap_err();
+        assert!(err.to_string().contains("version-file"));
+
+        config.project.version_file = Some("src/demo/__init__.py".to_string());
+        assert!(config.check_paths(dir.path()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_check_paths_warns_on_external_main_script() {
+        let dir = tempfile::tempdir().unwrap();
+        let warnings = config_with(None, "../shared/main.py")
+            .check_paths(dir.path())
+            .unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("../shared/main.py"));
+    }
+
+    #[test]
+    fn test_check_duplicates() {
+        let mut config = config_with(None, "main.py");
+        config.packages.insert("Flask".to_string(), "2.0".into());
+        assert!(config.check_duplicates().is_ok());
+
+        config.packages.insert("flask".to_string(), "3.0".into());
+        let err = config.check_duplicates().unwrap_err();
+        assert_eq!(err.exit_code(), 2);
+        assert!(err.to_string().contains("Flask==2.0 and flask==3.0"));
+    }
+
+    #[test]
+    fn test_allow_external_venv_round_trip() {
+        let parsed: Config = toml::from_str(
+            r#"
+[project]
+name = "demo"
+version = "0.1.0"
+description = ""
+main_script = "main.py"
+venv = "/opt/venvs/demo"
+allow-external-venv = true
+
+[packages]
+
+[scripts]
+"#,
+        )
+        .unwrap();
+        assert!(parsed.project.allow_external_venv);
+        assert!(
+            toml::to_string(&parsed)
+                .unwrap()
+                .contains("allow-external-venv = true")
+        );
+        assert!(
+            !toml::to_string(&config_with(None, "main.py"))
+                .unwrap()
+                .contains("allow-external-venv")
+        );
+    }
+
+    #[test]
+    fn test_package_spec_forms() {
+        let parsed: Config = toml::from_str(
+            r#"
+[project]
+name = "demo"
+version = "0.1.0"
+description = ""
+main_script = "main.py"
+
+[packages]
+requests = "2.31.0"
+pywin32 = "306; sys_platform == 'win32'"
+uvloop = { version = "0.19.0", marker = "sys_platform == 'linux'" }
+django = { version = "4.2.1", update = "minor" }
+
+[scripts]
+"#,
+        )
+        .unwrap();
+        let requests = &parsed.packages["requests"];
+        assert_eq!(
+            (requests.version(), requests.marker()),
+            (Some("2.31.0"), None)
+        );
+        let pywin32 = &parsed.packages["pywin32"];
+        assert_eq!(pywin32.version(), Some("306"));
+        assert_eq!(pywin32.marker(), Some("sys_platform == 'win32'"));
+        let uvloop = &parsed.packages["uvloop"];
+        assert_eq!(uvloop.version(), Some("0.19.0"));
+        assert_eq!(
+            uvloop.requirement("uvloop"),
+            "uvloop==0.19.0 ; sys_platform == 'linux'"
+        );
+        assert!(parsed.check_markers().is_ok());
+        assert_eq!(
+            parsed.packages["django"].update_policy(),
+            UpdatePolicy::Minor
+        );
+        assert_eq!(requests.update_policy(), UpdatePolicy::Latest);
+
+        // Each entry keeps its form through a round trip and a version change
+        let reparsed: Config = toml::from_str(&toml::to_string(&parsed).unwrap()).unwrap();
+        assert_eq!(reparsed, parsed);
+        assert_eq!(
+            uvloop.with_version("0.20.0"),
+            PackageSpec::Table(PackageTable {
+                version: "0.20.0".to_string(),
+                marker: Some("sys_platform == 'linux'".to_string()),
+                update: None,
+            })
+        );
+        assert_eq!(
+            pywin32.with_version("307"),
+            PackageSpec::from("307; sys_platform == 'win32'")
+        );
+
+        // A policy needs the table form; dropping it keeps the table
+        assert_eq!(
+            pywin32.with_update_policy(UpdatePolicy::Pin),
+            PackageSpec::Table(PackageTable {
+                version: "306".to_string(),
+                marker: Some("sys_platform == 'win32'".to_string()),
+                update: Some(UpdatePolicy::Pin),
+            })
+        );
+        assert_eq!(requests.with_update_policy(UpdatePolicy::Latest), *requests);
+        assert_eq!(
+            parsed.packages["django"]
+                .with_update_policy(UpdatePolicy::Latest)
+                .update_policy(),
+            UpdatePolicy::Latest
+        );
+    }
+
+    #[test]
+    fn test_direct_reference_round_trip() {
+        let packages: HashMap<String, PackageSpec> = toml::from_str(
+            r#"
+internal = { git = "https://github.com/org/lib", rev = "abc123" }
+tracked = { git = "https://github.com/org/tracked", branch = "main" }
+shared = { path = "../shared-lib", editable = true }
+vendored = { path = "vendor/pkg" }
+wheel = { url = "https://example.com/wheel-1.0-py3-none-any.whl", marker = "os_name == 'nt'" }
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            packages["internal"],
+            PackageSpec::Git(GitSource {
+                git: "https://github.com/org/lib".to_string(),
+                rev: Some("abc123".to_string()),
+                branch: None,
+                tag: None,
+                marker: None,
+            })
+        );
+        assert_eq!(
+            packages["shared"],
+            PackageSpec::Path(PathSource {
+                path: "../shared-lib".to_string(),
+                editable: true,
+            })
+        );
+        assert!(packages.values().all(|spec| !spec.is_index()));
+        assert!(packages.values().all(|spec| spec.version().is_none()));
+
+        let written = toml::to_string(&packages).unwrap();
+        assert!(!written.contains("editable = false"), "{}", written);
+        let reparsed: HashMap<String, PackageSpec> = toml::from_str(&written).unwrap();
+        assert_eq!(reparsed, packages);
+    }
+
+    #[test]
+    fn test_pip_args_and_requirement_lines() {
+        let packages: HashMap<String, PackageSpec> = toml::from_str(
+            r#"
+requests = "2.31.0"
+internal = { git = "https://github.com/org/lib", rev = "abc123" }
+tracked = { git = "https://github.com/org/tracked", branch = "main" }
+shared = { path = "../shared-lib", editable = true }
+vendored = { path = "vendor/pkg" }
+wheel = { url = "https://example.com/wheel-1.0-py3-none-any.whl", marker = "os_name == 'nt'" }
+"#,
+        )
+        .unwrap();
+        let args = |name: &str| packages[name].pip_args(name);
+
+        assert_eq!(args("requests"), vec!["requests==2.31.0"]);
+        assert_eq!(
+            args("internal"),
+            vec!["internal @ git+https://github.com/org/lib@abc123"]
+        );
+        assert_eq!(
+            args("tracked"),
+            vec!["tracked @ git+https://github.com/org/tracked@main"]
+        );
+        assert_eq!(args("shared"), vec!["-e", "../shared-lib"]);
+        assert_eq!(args("vendored"), vec!["vendor/pkg"]);
+        assert_eq!(
+            args("wheel"),
+            vec!["wheel @ https://example.com/wheel-1.0-py3-none-any.whl ; os_name == 'nt'"]
+        );
+        assert_eq!(packages["shared"].requirement("shared"), "-e ../shared-lib");
+    }
+
+    #[test]
+    fn test_direct_from_requirement_url() {
+        assert_eq!(
+            PackageSpec::direct("git+https://user@github.com/org/lib@v1.2", None),
+            PackageSpec::Git(GitSource {
+                git: "https://user@github.com/org/lib".to_string(),
+                rev: Some("v1.2".to_string()),
+                branch: None,
+                tag: None,
+                marker: None,
+            })
+        );
+        match PackageSpec::direct("git+https://user@github.com/org/lib", None) {
+            PackageSpec::Git(git) => assert_eq!(git.rev, None),
+            other => panic!("expected a git package, got {:?}", other),
+        }
+        assert_eq!(
+            PackageSpec::direct("https://example.com/pkg.whl", Some("os_name == 'nt'")),
+            PackageSpec::Url(UrlSource {
+                url: "https://example.com/pkg.whl".to_string(),
+                marker: Some("os_name == 'nt'".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn test_installs_self() {
+        let mut config = config_with(None, "main.py");
+        assert!(!config.installs_self());
+        config.packages.insert(
+            "mypkg".to_string(),
+            PackageSpec::Path(PathSource {
+                path: "./".to_string(),
+                editable: true,
+            }),
+        );
+        assert!(config.packages["mypkg"].is_self());
+        assert!(config.installs_self());
+
+        let mut config = config_with(None, "main.py");
+        config.project.install_self = true;
+        assert!(config.installs_self());
+        assert!(
+            toml::to_string(&config)
+                .unwrap()
+                .contains("install_self = true")
+        );
+    }
+
+    #[test]
+    fn test_package_table_rejects_unknown_keys() {
+        let err = toml::from_str::<HashMap<String, PackageSpec>>(
+            "uvloop = { version = \"0.19.0\", markr = \"sys_platform == 'linux'\" }",
+        );
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn test_check_markers() {
+        let mut config = config_with(None, "main.py");
+        config.packages.insert(
+            "pywin32".to_string(),
+            PackageSpec::new("306", Some("sys_platfrom == 'win32'")),
+        );
+        let err = config.check_markers().unwrap_err();
+        assert_eq!(err.exit_code(), 2);
+        assert!(err.to_string().contains("'pywin32'"));
+        assert!(err.to_string().contains("sys_platfrom"));
+    }
+
+    #[test]
+    fn test_script_os_variants() {
+        let scripts: HashMap<String, ScriptSpec> = toml::from_str(
+            r#"
+plain = "make"
+generic = { command = "rm -rf build" }
+clean = { command = "rm -rf build", windows = "rmdir /s /q build" }
+open = { macos = "open docs", windows = "start docs" }
+each = { command = "true", windows = "w", macos = "m", linux = "l" }
+"#,
+        )
+        .unwrap();
+
+        for os in ["linux", "macos", "windows", "freebsd"] {
+            assert_eq!(scripts["plain"].command_for(os), Some("make"));
+            assert!(!scripts["plain"].is_os_variant(os));
+            assert_eq!(scripts["generic"].command_for(os), Some("rm -rf build"));
+            assert!(!scripts["generic"].is_os_variant(os));
+        }
+        // An OS key wins on its OS; elsewhere `command` applies
+        assert_eq!(
+            scripts["clean"].command_for("windows"),
+            Some("rmdir /s /q build")
+        );
+        assert!(scripts["clean"].is_os_variant("windows"));
+        assert_eq!(scripts["clean"].command_for("linux"), Some("rm -rf build"));
+        assert!(!scripts["clean"].is_os_variant("linux"));
+        // Without `command` an OS lacking its key has nothing to run
+        assert_eq!(scripts["open"].command_for("macos"), Some("open docs"));
+        assert_eq!(scripts["open"].command_for("windows"), Some("start docs"));
+        assert_eq!(scripts["open"].command_for("linux"), None);
+        assert_eq!(scripts["open"].command_for("freebsd"), None);
+        assert_eq!(scripts["each"].command_for("linux"), Some("l"));
+        assert_eq!(scripts["each"].command_for("macos"), Some("m"));
+        assert_eq!(scripts["each"].command_for("windows"), Some("w"));
+        assert_eq!(scripts["each"].command_for("freebsd"), Some("true"));
+
+        // Keys that aren't set aren't written back
+        let written = toml::to_string(&scripts["open"]).unwrap();
+        assert!(!written.contains("command"));
+        assert!(!written.contains("linux"));
+        assert_eq!(
+            toml::from_str::<ScriptSpec>(&written).unwrap(),
+            scripts["open"]
+        );
+        assert!(
+            toml::from_str::<HashMap<String, ScriptSpec>>("x = { command = \"a\", win = \"b\" }")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_check_scripts() {
+        let mut config = config_with(None, "main.py");
+        config.scripts.insert(
+            "open".to_string(),
+            toml::from_str("macos = \"open docs\"").unwrap(),
+        );
+        assert!(config.check_scripts().is_ok());
+        config.scripts.insert(
+            "empty".to_string(),
+            toml::from_str("requires = [\"x\"]").unwrap(),
+        );
+        let err = config.check_scripts().unwrap_err();
+        assert_eq!(err.exit_code(), 2);
+        assert!(err.to_string().contains("Script 'empty'"));
+    }
+
+    #[test]
+    fn test_write_to_missing_dir_is_io_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("missing").join("project.toml");
+        let config = Config::new(
+            Project::new(
+                "test".to_string(),
+                "1.0.0".to_string(),
+                "".to_string(),
+                "main.py".to_string(),
+                None,
+            ),
+            HashMap::new(),
+            HashMap::new(),
+        );
+        let err = config.write_to_file(path.to_str().unwrap()).unwrap_err();
+        assert!(matches!(err, PpmError::Io(_)));
+        assert!(err.to_string().starts_with("Failed to write"));
     }
 }
@@ -0,0 +1,274 @@
+//! Where ppm keeps its own files.
+//!
+//! Per-project state (caches, logs, backups) lives in `.ppm/` next to
+//! project.toml; data shared between projects, like index responses,
+//! lives in a global cache directory. Neither is created until something
+//! is written, so read-only commands leave no empty directories behind.
+//! Files are replaced by writing a temporary file and renaming it over
+//! the old one, so a concurrent reader never sees half a file.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Per-project state directory, relative to the project root
+pub const STATE_DIR: &str = ".ppm";
+
+/// Subdirectory of a [`StateDir`] for data that can be rebuilt at any
+/// time, removed by `ppm clean`
+pub const CACHE: &str = "cache";
+
+/// Overrides the global cache directory
+pub const CACHE_DIR_ENV: &str = "PPM_CACHE_DIR";
+
+/// A directory of ppm-managed files
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateDir {
+    root: PathBuf,
+}
+
+impl StateDir {
+    /// `.ppm/` in the project at `project_root`
+    pub fn project(project_root: &Path) -> StateDir {
+        StateDir {
+            root: project_root.join(STATE_DIR),
+        }
+    }
+
+    /// The cross-project cache directory, see [`global_cache_dir`]
+    pub fn global() -> Option<StateDir> {
+        global_cache_dir().map(|root| StateDir { root })
+    }
+
+    /// The directory itself, which may not exist yet
+    pub fn path(&self) -> &Path {
+        &self.root
+    }
+
+    /// Whether anything has been written here yet
+    pub fn exists(&self) -> bool {
+        self.root.is_dir()
+    }
+
+    /// Path of `name` (e.g. `cache/pypi`) inside the directory, without
+    /// creating anything
+    pub fn subpath(&self, name: &str) -> PathBuf {
+        self.root.join(name)
+    }
+
+    /// Like [`StateDir::subpath`], creating the directory `name` and its
+    /// parents first
+    pub fn ensure_dir(&self, name: &str) -> io::Result<PathBuf> {
+        self.create_root()?;
+        let dir = self.subpath(name);
+        fs::create_dir_all(&dir)?;
+        Ok(dir)
+    }
+
+    /// Atomically replace the file `name` with `contents`, creating its
+    /// directory when needed
+    pub fn write(&self, name: &str, contents: &[u8]) -> io::Result<PathBuf> {
+        self.create_root()?;
+        let path = self.subpath(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        write_atomic(&path, contents)?;
+        Ok(path)
+    }
+
+    /// Append `contents` to the file `name`, creating it and its directory
+    /// when needed. Meant for small records: each call is a single write,
+    /// so lines from concurrent processes don't interleave.
+    pub fn append(&self, name: &str, contents: &[u8]) -> io::Result<PathBuf> {
+        self.create_root()?;
+        let path = self.subpath(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)?
+            .write_all(contents)?;
+        Ok(path)
+    }
+
+    /// Move the file `from` to `to`, replacing `to`
+    pub fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        fs::rename(self.subpath(from), self.subpath(to))
+    }
+
+    /// Total size in bytes of everything inside, 0 when it doesn't exist
+    pub fn size(&self) -> u64 {
+        dir_size(&self.root)
+    }
+
+    /// Remove the entry `name` (a file or directory), returning the bytes
+    /// freed
+    pub fn remove(&self, name: &str) -> io::Result<u64> {
+        remove(&self.subpath(name))
+    }
+
+    /// Remove the whole directory, returning the bytes freed
+    pub fn clear(&self) -> io::Result<u64> {
+        remove(&self.root)
+    }
+
+    /// The project directory ignores itself, so it stays out of git even
+    /// in projects created before the template listed it
+    fn create_root(&self) -> io::Result<()> {
+        if self.root.is_dir() {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.root)?;
+        if self.root.ends_with(STATE_DIR) {
+            write_atomic(&self.root.join(".gitignore"), b"*\n")?;
+        }
+        Ok(())
+    }
+}
+
+/// Cache directory shared by all projects: `$PPM_CACHE_DIR` when set,
+/// otherwise `ppm` in the platform cache directory (`$XDG_CACHE_HOME` or
+/// `~/.cache` on Linux, `~/Library/Caches` on macOS, `%LOCALAPPDATA%` on
+/// Windows)
+pub fn global_cache_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os(CACHE_DIR_ENV).filter(|dir| !dir.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    platform_cache_dir().map(|dir| dir.join("ppm"))
+}
+
+#[cfg(target_os = "windows")]
+pub(crate) fn platform_cache_dir() -> Option<PathBuf> {
+    std::env::var_os("LOCALAPPDATA").map(PathBuf::from)
+}
+
+#[cfg(target_os = "macos")]
+pub(crate) fn platform_cache_dir() -> Option<PathBuf> {
+    std::env::home_dir().map(|home| home.join("Library").join("Caches"))
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+pub(crate) fn platform_cache_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::home_dir().map(|home| home.join(".cache")))
+}
+
+/// Replace `path` with `contents` by writing a temporary file next to it
+/// and renaming it into place
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let file_name = path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Not a file path: {}", path.display()),
+        )
+    })?;
+    let tmp = path.with_file_name(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+    let written = fs::File::create(&tmp).and_then(|mut file| {
+        file.write_all(contents)?;
+        file.sync_all()
+    });
+    match written.and_then(|()| fs::rename(&tmp, path)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = fs::remove_file(&tmp);
+            Err(e)
+        }
+    }
+}
+
+/// Total size in bytes of the files under `path`; symlinks are not
+/// followed
+pub fn dir_size(path: &Path) -> u64 {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    fs::read_dir(path)
+        .map(|entries| entries.flatten().map(|entry| dir_size(&entry.path())).sum())
+        .unwrap_or(0)
+}
+
+fn remove(path: &Path) -> io::Result<u64> {
+    let size = dir_size(path);
+    let result = if path.is_dir() {
+        crate::paths::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    };
+    match result {
+        Ok(()) => Ok(size),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(e),
+    }
+}
+
+/// `1.5 MiB`-style size for humans
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_project_state_is_created_lazily() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = StateDir::project(dir.path());
+        assert_eq!(state.subpath("logs"), dir.path().join(".ppm").join("logs"));
+        assert!(!state.exists());
+        assert_eq!(state.size(), 0);
+
+        let path = state.write("cache/pypi/requests.json", b"{}").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{}");
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join(".ppm").join(".gitignore")).unwrap(),
+            "*\n"
+        );
+        state
+            .write("cache/pypi/requests.json", b"{\"v\": 1}")
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{\"v\": 1}");
+        // The temporary file was renamed into place
+        assert_eq!(
+            std::fs::read_dir(path.parent().unwrap()).unwrap().count(),
+            1
+        );
+        assert_eq!(state.size(), 10);
+
+        assert_eq!(state.remove(CACHE).unwrap(), 8);
+        assert_eq!(state.remove(CACHE).unwrap(), 0);
+        assert!(state.exists());
+        assert_eq!(state.clear().unwrap(), 2);
+        assert!(!state.exists());
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024), "5.0 MiB");
+    }
+}
@@ -0,0 +1,235 @@
+//! `ppm stats`: how much disk space each installed distribution takes.
+//!
+//! A distribution's files are those its dist-info `RECORD` lists, sized
+//! as they are on disk now. A distribution without `RECORD` is sized by
+//! walking the top-level packages it names in `top_level.txt` (or the
+//! directory named after it) plus its dist-info.
+
+use crate::context::ProjectContext;
+use crate::error::PpmError;
+use crate::packages::normalize_pkg_name;
+use crate::paths::site_packages_dirs;
+use crate::settings::Config;
+use crate::state::dir_size;
+use crate::verify::parse_record;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// Disk usage of one installed distribution
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageSize {
+    /// Distribution name as its metadata spells it
+    pub name: String,
+    /// Installed version
+    pub version: String,
+    /// Bytes on disk
+    pub size: u64,
+    /// Whether project.toml lists the package, as opposed to it being
+    /// installed as a dependency
+    pub direct: bool,
+}
+
+/// Disk usage of a venv
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VenvStats {
+    /// Installed distributions, largest first
+    pub packages: Vec<PackageSize>,
+    /// Size of the whole venv directory, interpreter links included
+    pub total: u64,
+}
+
+impl VenvStats {
+    /// Fraction of the venv taken by the `count` largest packages
+    pub fn top_share(&self, count: usize) -> f64 {
+        let top: u64 = self.packages.iter().take(count).map(|p| p.size).sum();
+        share(top, self.total)
+    }
+}
+
+/// `part` as a fraction of `total`, 0 for an empty total
+pub fn share(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 / total as f64
+    }
+}
+
+/// Bytes taken by the distribution whose metadata is in `dist_info`,
+/// inside `site_packages`
+pub fn dist_size(site_packages: &Path, dist_info: &Path) -> u64 {
+    if let Ok(record) = fs::read_to_string(dist_info.join("RECORD")) {
+        let mut seen = HashSet::new();
+        return parse_record(&record)
+            .iter()
+            .filter(|entry| seen.insert(entry.path.clone()))
+            .filter_map(|entry| fs::symlink_metadata(site_packages.join(&entry.path)).ok())
+            .map(|metadata| metadata.len())
+            .sum();
+    }
+
+    let mut roots: Vec<String> = fs::read_to_string(dist_info.join("top_level.txt"))
+        .map(|top_level| {
+            top_level
+                .lines()
+                .map(|line| line.trim().to_string())
+                .filter(|line| !line.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+    if roots.is_empty() {
+        let stem = dist_info.file_stem().unwrap_or_default().to_string_lossy();
+        let name = stem.split('-').next().unwrap_or_default();
+        roots.push(name.replace(['-', '.'], "_").to_lowercase());
+    }
+    let modules: u64 = roots
+        .iter()
+        .map(|root| {
+            dir_size(&site_packages.join(root))
+                + dir_size(&site_packages.join(format!("{}.py", root)))
+        })
+        .sum();
+    modules + dir_size(dist_info)
+}
+
+/// Sizes of every distribution installed in `venv_dir`, marking those
+/// `config` lists
+pub fn venv_stats(venv_dir: &Path, config: &Config) -> VenvStats {
+    let declared: HashSet<String> = config
+        .packages
+        .keys()
+        .map(|name| normalize_pkg_name(name))
+        .collect();
+    let mut packages = vec![];
+    for site_packages in site_packages_dirs(venv_dir) {
+        let Ok(entries) = fs::read_dir(&site_packages) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            let Some(stem) = file_name.strip_suffix(".dist-info") else {
+                continue;
+            };
+            let Some((dist_name, version)) = stem.split_once('-') else {
+                continue;
+            };
+            let dist_info = entry.path();
+            let name = fs::read_to_string(dist_info.join("METADATA"))
+                .ok()
+                .and_then(|metadata| {
+                    metadata
+                        .lines()
+                        .find_map(|line| line.strip_prefix("Name:").map(|n| n.trim().to_string()))
+                })
+                .unwrap_or_else(|| dist_name.to_string());
+            packages.push(PackageSize {
+                direct: declared.contains(&normalize_pkg_name(&name)),
+                name,
+                version: version.to_string(),
+                size: dist_size(&site_packages, &dist_info),
+            });
+        }
+    }
+    packages.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+    VenvStats {
+        packages,
+        total: dir_size(venv_dir),
+    }
+}
+
+/// Disk usage of the project's venv
+pub fn stats(ctx: &ProjectContext) -> Result<VenvStats, PpmError> {
+    ctx.check_venv_in_use()?;
+    let venv_dir = ctx.venv_dir();
+    if !venv_dir.is_dir() {
+        return Err(PpmError::Venv(
+            "Virtual Environment Not Found; run `ppm install` to create it".to_string(),
+        ));
+    }
+    Ok(venv_stats(&venv_dir, &ctx.config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::{PackageSpec, Project};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_sizes_from_record_and_package_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let venv = dir.path().join("venv");
+        let site = venv.join("lib").join("python3.12").join("site-packages");
+
+        // numpy: sized by RECORD, including a script outside site-packages
+        let numpy = site.join("numpy-2.0.0.dist-info");
+        fs::create_dir_all(site.join("numpy").join("core")).unwrap();
+        fs::create_dir_all(&numpy).unwrap();
+        fs::create_dir_all(venv.join("bin")).unwrap();
+        fs::write(site.join("numpy").join("__init__.py"), vec![b'x'; 1000]).unwrap();
+        fs::write(
+            site.join("numpy").join("core").join("_core.so"),
+            vec![b'x'; 5000],
+        )
+        .unwrap();
+        fs::write(venv.join("bin").join("f2py"), vec![b'x'; 100]).unwrap();
+        fs::write(numpy.join("METADATA"), "Name: numpy\nVersion: 2.0.0\n").unwrap();
+        let record = "numpy/__init__.py,sha256=a,1000\n\
+                      numpy/core/_core.so,sha256=b,5000\n\
+                      numpy/core/_core.so,sha256=b,5000\n\
+                      ../../../bin/f2py,sha256=c,100\n\
+                      numpy/removed.py,sha256=d,700\n\
+                      numpy-2.0.0.dist-info/METADATA,,\n\
+                      numpy-2.0.0.dist-info/RECORD,,\n";
+        fs::write(numpy.join("RECORD"), record).unwrap();
+        let metadata_size = "Name: numpy\nVersion: 2.0.0\n".len() as u64;
+        let record_size = record.len() as u64;
+
+        // PyYAML: no RECORD, modules named by top_level.txt
+        let yaml = site.join("PyYAML-6.0.1.dist-info");
+        fs::create_dir_all(site.join("yaml")).unwrap();
+        fs::create_dir_all(&yaml).unwrap();
+        fs::write(site.join("yaml").join("__init__.py"), vec![b'x'; 300]).unwrap();
+        fs::write(site.join("_yaml.py"), vec![b'x'; 20]).unwrap();
+        fs::write(yaml.join("top_level.txt"), "_yaml\nyaml\n").unwrap();
+
+        fs::write(venv.join("pyvenv.cfg"), "home = /usr/bin\n").unwrap();
+        // six: neither, found by its name
+        let six = site.join("six-1.16.0.dist-info");
+        fs::create_dir_all(&six).unwrap();
+        fs::write(site.join("six.py"), vec![b'x'; 40]).unwrap();
+
+        let config = Config::new(
+            Project::new(
+                "demo".to_string(),
+                "0.1.0".to_string(),
+                String::new(),
+                "./main.py".to_string(),
+                None,
+            ),
+            HashMap::from([("NumPy".to_string(), PackageSpec::from("2.0.0"))]),
+            HashMap::new(),
+        );
+        let stats = venv_stats(&venv, &config);
+        let sizes: Vec<(&str, u64, bool)> = stats
+            .packages
+            .iter()
+            .map(|p| (p.name.as_str(), p.size, p.direct))
+            .collect();
+        assert_eq!(
+            sizes,
+            vec![
+                ("numpy", 6100 + metadata_size + record_size, true),
+                ("PyYAML", 320 + 11, false),
+                ("six", 40, false),
+            ]
+        );
+        assert_eq!(stats.packages[1].version, "6.0.1");
+        assert_eq!(stats.total, dir_size(&venv));
+        let packages: u64 = stats.packages.iter().map(|p| p.size).sum();
+        assert_eq!(stats.total, packages + "home = /usr/bin\n".len() as u64);
+        assert_eq!(stats.top_share(3), packages as f64 / stats.total as f64);
+        assert_eq!(share(1, 0), 0.0);
+    }
+}
@@ -0,0 +1,283 @@
+//! Standard-library module names, per Python version.
+//!
+//! Generated from `sys.stdlib_module_names` of CPython 3.10 to 3.13
+//! (public modules only), with the modules added and removed since 3.8.
+
+/// `(major, minor)`, e.g. `(3, 12)`
+pub type PythonVersion = (u32, u32);
+
+/// Top-level modules shipped with every Python from 3.8 to 3.13
+const COMMON: &[&str] = &[
+    "__future__",
+    "_thread",
+    "abc",
+    "antigravity",
+    "argparse",
+    "array",
+    "ast",
+    "asyncio",
+    "atexit",
+    "base64",
+    "bdb",
+    "binascii",
+    "bisect",
+    "builtins",
+    "bz2",
+    "cProfile",
+    "calendar",
+    "cmath",
+    "cmd",
+    "code",
+    "codecs",
+    "codeop",
+    "collections",
+    "colorsys",
+    "compileall",
+    "concurrent",
+    "configparser",
+    "contextlib",
+    "contextvars",
+    "copy",
+    "copyreg",
+    "csv",
+    "ctypes",
+    "curses",
+    "dataclasses",
+    "datetime",
+    "dbm",
+    "decimal",
+    "difflib",
+    "dis",
+    "doctest",
+    "email",
+    "encodings",
+    "ensurepip",
+    "enum",
+    "errno",
+    "faulthandler",
+    "fcntl",
+    "filecmp",
+    "fileinput",
+    "fnmatch",
+    "fractions",
+    "ftplib",
+    "functools",
+    "gc",
+    "genericpath",
+    "getopt",
+    "getpass",
+    "gettext",
+    "glob",
+    "grp",
+    "gzip",
+    "hashlib",
+    "heapq",
+    "hmac",
+    "html",
+    "http",
+    "idlelib",
+    "imaplib",
+    "importlib",
+    "inspect",
+    "io",
+    "ipaddress",
+    "itertools",
+    "json",
+    "keyword",
+    "linecache",
+    "locale",
+    "logging",
+    "lzma",
+    "mailbox",
+    "marshal",
+    "math",
+    "mimetypes",
+    "mmap",
+    "modulefinder",
+    "msvcrt",
+    "multiprocessing",
+    "netrc",
+    "nt",
+    "ntpath",
+    "nturl2path",
+    "numbers",
+    "opcode",
+    "operator",
+    "optparse",
+    "os",
+    "pathlib",
+    "pdb",
+    "pickle",
+    "pickletools",
+    "pkgutil",
+    "platform",
+    "plistlib",
+    "poplib",
+    "posix",
+    "posixpath",
+    "pprint",
+    "profile",
+    "pstats",
+    "pty",
+    "pwd",
+    "py_compile",
+    "pyclbr",
+    "pydoc",
+    "pydoc_data",
+    "pyexpat",
+    "queue",
+    "quopri",
+    "random",
+    "re",
+    "readline",
+    "reprlib",
+    "resource",
+    "rlcompleter",
+    "runpy",
+    "sched",
+    "secrets",
+    "select",
+    "selectors",
+    "shelve",
+    "shlex",
+    "shutil",
+    "signal",
+    "site",
+    "smtplib",
+    "socket",
+    "socketserver",
+    "sqlite3",
+    "sre_compile",
+    "sre_constants",
+    "sre_parse",
+    "ssl",
+    "stat",
+    "statistics",
+    "string",
+    "stringprep",
+    "struct",
+    "subprocess",
+    "symtable",
+    "sys",
+    "sysconfig",
+    "syslog",
+    "tabnanny",
+    "tarfile",
+    "tempfile",
+    "termios",
+    "textwrap",
+    "this",
+    "threading",
+    "time",
+    "timeit",
+    "tkinter",
+    "token",
+    "tokenize",
+    "trace",
+    "traceback",
+    "tracemalloc",
+    "tty",
+    "turtle",
+    "turtledemo",
+    "types",
+    "typing",
+    "unicodedata",
+    "unittest",
+    "urllib",
+    "uuid",
+    "venv",
+    "warnings",
+    "wave",
+    "weakref",
+    "webbrowser",
+    "winreg",
+    "winsound",
+    "wsgiref",
+    "xml",
+    "xmlrpc",
+    "zipapp",
+    "zipfile",
+    "zipimport",
+    "zlib",
+];
+
+/// Modules only in some versions: the first version with the module and
+/// the first one without it
+const VERSIONED: &[(&str, PythonVersion, Option<PythonVersion>)] = &[
+    ("graphlib", (3, 9), None),
+    ("zoneinfo", (3, 9), None),
+    ("tomllib", (3, 11), None),
+    ("dummy_threading", (3, 0), Some((3, 9))),
+    ("formatter", (3, 0), Some((3, 10))),
+    ("parser", (3, 0), Some((3, 10))),
+    ("symbol", (3, 0), Some((3, 10))),
+    ("binhex", (3, 0), Some((3, 11))),
+    ("asynchat", (3, 0), Some((3, 12))),
+    ("asyncore", (3, 0), Some((3, 12))),
+    ("distutils", (3, 0), Some((3, 12))),
+    ("imp", (3, 0), Some((3, 12))),
+    ("smtpd", (3, 0), Some((3, 12))),
+    ("aifc", (3, 0), Some((3, 13))),
+    ("audioop", (3, 0), Some((3, 13))),
+    ("cgi", (3, 0), Some((3, 13))),
+    ("cgitb", (3, 0), Some((3, 13))),
+    ("chunk", (3, 0), Some((3, 13))),
+    ("crypt", (3, 0), Some((3, 13))),
+    ("imghdr", (3, 0), Some((3, 13))),
+    ("lib2to3", (3, 0), Some((3, 13))),
+    ("mailcap", (3, 0), Some((3, 13))),
+    ("msilib", (3, 0), Some((3, 13))),
+    ("nis", (3, 0), Some((3, 13))),
+    ("nntplib", (3, 0), Some((3, 13))),
+    ("ossaudiodev", (3, 0), Some((3, 13))),
+    ("pipes", (3, 0), Some((3, 13))),
+    ("sndhdr", (3, 0), Some((3, 13))),
+    ("spwd", (3, 0), Some((3, 13))),
+    ("sunau", (3, 0), Some((3, 13))),
+    ("telnetlib", (3, 0), Some((3, 13))),
+    ("uu", (3, 0), Some((3, 13))),
+    ("xdrlib", (3, 0), Some((3, 13))),
+];
+
+/// Whether `module` (a top-level name such as `json`) is part of the
+/// standard library of Python `version`
+pub fn is_stdlib(module: &str, version: PythonVersion) -> bool {
+    COMMON.contains(&module)
+        || VERSIONED.iter().any(|(name, added, removed)| {
+            *name == module && version >= *added && removed.is_none_or(|removed| version < removed)
+        })
+}
+
+/// `(major, minor)` from a version such as `3.11` or `3.11.7`
+pub fn parse_python_version(version: &str) -> Option<PythonVersion> {
+    let mut parts = version.trim().split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_stdlib_by_version() {
+        assert!(is_stdlib("json", (3, 8)));
+        assert!(is_stdlib("os", (3, 13)));
+        assert!(!is_stdlib("requests", (3, 12)));
+
+        assert!(!is_stdlib("tomllib", (3, 10)));
+        assert!(is_stdlib("tomllib", (3, 11)));
+        assert!(is_stdlib("distutils", (3, 11)));
+        assert!(!is_stdlib("distutils", (3, 12)));
+        assert!(is_stdlib("cgi", (3, 12)));
+        assert!(!is_stdlib("cgi", (3, 13)));
+    }
+
+    #[test]
+    fn test_parse_python_version() {
+        assert_eq!(parse_python_version("3.11"), Some((3, 11)));
+        assert_eq!(parse_python_version("3.12.1"), Some((3, 12)));
+        assert_eq!(parse_python_version("three"), None);
+    }
+}
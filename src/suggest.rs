@@ -0,0 +1,283 @@
+//! Close matches for a package name the index doesn't have, so `ppm add
+//! request` can offer `requests` instead of dead-ending on a 404.
+//!
+//! Candidates come from a list of the most downloaded PyPI projects,
+//! cached in the global cache directory for [`POPULAR_MAX_AGE`], and from
+//! PyPI's search. [`rank`] keeps those within a few edits of the name,
+//! closest first. Both sources are best effort: when one can't be
+//! reached it adds no candidates.
+
+use crate::index;
+use crate::packages::normalize_pkg_name;
+use crate::state::StateDir;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The most downloaded PyPI projects of the last 30 days, most first
+pub const POPULAR_URL: &str =
+    "https://hugovk.github.io/top-pypi-packages/top-pypi-packages-30-days.min.json";
+
+
+/// The cached popular list, inside the global [`StateDir`]
+pub const POPULAR_FILE: &str = "cache/popular-packages.json";
+
+/// How long the cached popular list is used before it's fetched again
+pub const POPULAR_MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// How many matches are offered
+pub const MAX_SUGGESTIONS: usize = 5;
+
+/// Edits between `a` and `b`: insertions, deletions, substitutions and
+/// swaps of neighbouring characters
+pub fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // Three rows of the optimal string alignment table
+    let mut before: Vec<usize> = vec![0; b.len() + 1];
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut current = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(before[j - 2] + 1);
+            }
+        }
+        before = std::mem::replace(&mut previous, current);
+    }
+    previous[b.len()]
+}
+
+/// Up to `limit` of `candidates` close to `name`, closest first; among
+/// equally close ones the earlier candidate wins, so candidates are best
+/// given most popular first. Case and `-`, `_`, `.` don't count, and
+/// `name` itself is never suggested.
+pub fn rank(name: &str, candidates: &[String], limit: usize) -> Vec<String> {
+    let wanted = normalize_pkg_name(name);
+    let max_edits = (wanted.chars().count() / 3).clamp(1, 3);
+    let mut seen = HashSet::new();
+    let mut close: Vec<(usize, usize, &String)> = candidates
+        .iter()
+        .enumerate()
+        .filter_map(|(order, candidate)| {
+            let normalized = normalize_pkg_name(candidate);
+            if normalized == wanted || !seen.insert(normalized.clone()) {
+                return None;
+            }
+            let edits = distance(&wanted, &normalized);
+            (edits <= max_edits).then_some((edits, order, candidate))
+        })
+        .collect();
+    close.sort();
+    close
+        .into_iter()
+        .take(limit)
+        .map(|(_, _, candidate)| candidate.clone())
+        .collect()
+}
+
+/// Project names in the popular list's JSON, in its order
+pub fn parse_popular(json: &str) -> Vec<String> {
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(json) else {
+        return vec![];
+    };
+    value["rows"]
+        .as_array()
+        .map(|rows| {
+            rows.iter()
+                .filter_map(|row| row["project"].as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Project names on a PyPI search results page, in its order
+pub fn parse_search(html: &str) -> Vec<String> {
+    const NAME_START: &str = "package-snippet__name\">";
+    html.split(NAME_START)
+        .skip(1)
+        .filter_map(|rest| rest.split('<').next())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// The popular list as cached, with when it was fetched
+#[derive(Serialize, Deserialize, Debug)]
+struct CachedPopular {
+    fetched_at: u64,
+    names: Vec<String>,
+}
+
+/// The popular list from `cache` while it's fresh, fetched from `url` and
+/// cached otherwise. A stale copy is used when the fetch fails.
+pub async fn popular(client: &Client, url: &str, cache: Option<&StateDir>) -> Vec<String> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let cached: Option<CachedPopular> = cache
+        .and_then(|dir| std::fs::read(dir.subpath(POPULAR_FILE)).ok())
+        .and_then(|contents| serde_json::from_slice(&contents).ok());
+    if let Some(cached) = &cached
+        && now.saturating_sub(cached.fetched_at) < POPULAR_MAX_AGE.as_secs()
+    {
+        return cached.names.clone();
+    }
+    match index::fetch_text(client, url)
+        .await
+        .map(|json| parse_popular(&json))
+    {
+        Ok(names) if !names.is_empty() => {
+            if let Some(dir) = cache {
+                let fresh = CachedPopular {
+                    fetched_at: now,
+                    names: names.clone(),
+                };
+                if let Ok(contents) = serde_json::to_vec(&fresh) {
+                    let _ = dir.write(POPULAR_FILE, &contents);
+                }
+            }
+            names
+        }
+        _ => cached.map(|cached| cached.names).unwrap_or_default(),
+    }
+}
+
+/// PyPI's search page, for PyPI at `pypi_url` (see
+/// [`crate::index::pypi_url`])
+pub fn search_url(pypi_url: &str) -> String {
+    format!("{}/search/", pypi_url)
+}
+
+/// Names PyPI's search at `search_url` finds for `name`, empty when it
+/// can't be reached
+pub async fn search(client: &Client, search_url: &str, name: &str) -> Vec<String> {
+    let url = match reqwest::Url::parse_with_params(search_url, &[("q", name)]) {
+        Ok(url) => url,
+        Err(_) => return vec![],
+    };
+    index::fetch_text(client, url.as_str())
+        .await
+        .map(|html| parse_search(&html))
+        .unwrap_or_default()
+}
+
+/// Up to [`MAX_SUGGESTIONS`] close matches for `name` from the popular
+/// list and PyPI's search at `search_url`
+pub async fn suggestions(
+    client: &Client,
+    search_url: &str,
+    name: &str,
+    cache: Option<&StateDir>,
+) -> Vec<String> {
+    let (mut candidates, found) = futures::join!(
+        popular(client, POPULAR_URL, cache),
+        search(client, search_url, name)
+    );
+    candidates.extend(found);
+    rank(name, &candidates, MAX_SUGGESTIONS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_distance() {
+        assert_eq!(distance("request", "requests"), 1);
+        assert_eq!(distance("reqeusts", "requests"), 1);
+        assert_eq!(distance("numpy", "numpy"), 0);
+        assert_eq!(distance("", "abc"), 3);
+        assert_eq!(distance("flask", "django"), 5);
+    }
+
+    #[test]
+    fn test_rank_closest_then_most_popular() {
+        let popular = names(&[
+            "boto3",
+            "requests",
+            "urllib3",
+            "request",
+            "requests-oauthlib",
+            "Requests_Mock",
+            "pytest",
+            "requestes",
+        ]);
+        assert_eq!(rank("request", &popular, 5), vec!["requests", "requestes"]);
+        assert_eq!(
+            rank("reqeusts", &popular, 5),
+            vec!["requests", "request", "requestes"]
+        );
+        assert_eq!(rank("requests-mok", &popular, 5), vec!["Requests_Mock"]);
+        assert_eq!(rank("pytset", &popular, 1), vec!["pytest"]);
+        // Short names only allow one edit; nothing close is nothing
+        assert!(rank("bot", &popular, 5).is_empty());
+        assert!(rank("tensorflow", &popular, 5).is_empty());
+
+        // Duplicates from both sources are offered once
+        let doubled = names(&["requests", "Requests", "requests"]);
+        assert_eq!(rank("request", &doubled, 5), vec!["requests"]);
+    }
+
+    #[test]
+    fn test_parse_popular_and_search() {
+        let json = r#"{"last_update": "2026-10-01", "rows": [
+            {"download_count": 10, "project": "boto3"},
+            {"download_count": 5, "project": "requests"}
+        ]}"#;
+        assert_eq!(parse_popular(json), vec!["boto3", "requests"]);
+        assert!(parse_popular("not json").is_empty());
+
+        let html = r#"<a class="package-snippet" href="/project/requests/">
+            <span class="package-snippet__name">requests</span>
+            <span class="package-snippet__version">2.32.3</span></a>
+            <span class="package-snippet__name">requests-toolbelt</span>"#;
+        assert_eq!(parse_search(html), vec!["requests", "requests-toolbelt"]);
+    }
+
+    #[tokio::test]
+    async fn test_popular_list_is_cached() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = StateDir::project(dir.path());
+        let client = Client::new();
+        // Nothing cached and nothing reachable: no candidates
+        let unreachable = "http://127.0.0.1:9/top.json";
+        assert!(popular(&client, unreachable, Some(&cache)).await.is_empty());
+
+        // A fresh copy is used without fetching; a stale one when the fetch fails
+        let cached = CachedPopular {
+            fetched_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            names: names(&["requests"]),
+        };
+        cache
+            .write(POPULAR_FILE, &serde_json::to_vec(&cached).unwrap())
+            .unwrap();
+        assert_eq!(
+            popular(&client, unreachable, Some(&cache)).await,
+            vec!["requests"]
+        );
+        let stale = CachedPopular {
+            fetched_at: 0,
+            ..cached
+        };
+        cache
+            .write(POPULAR_FILE, &serde_json::to_vec(&stale).unwrap())
+            .unwrap();
+        assert_eq!(
+            popular(&client, unreachable, Some(&cache)).await,
+            vec!["requests"]
+        );
+    }
+}
@@ -0,0 +1,197 @@
+use crate::utils::theme;
+use colored::{ColoredString, Colorize};
+
+/// Color applied to a cell after padding, so ANSI codes never skew
+/// alignment; the colors themselves come from the [`theme`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Plain,
+    Bold,
+    Dim,
+    Info,
+    Warn,
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct Cell {
+    pub text: String,
+    pub style: Style,
+    /// Shown dimmed after the text, e.g. a package's marker
+    pub note: Option<String>,
+}
+
+impl Cell {
+    pub fn new(text: impl Into<String>, style: Style) -> Cell {
+        Cell {
+            text: text.into(),
+            style,
+            note: None,
+        }
+    }
+
+    pub fn with_note(mut self, note: Option<String>) -> Cell {
+        self.note = note;
+        self
+    }
+
+    fn width(&self) -> usize {
+        let note = self.note.as_ref().map_or(0, |n| n.chars().count() + 1);
+        self.text.chars().count() + note
+    }
+
+    pub fn plain(text: impl Into<String>) -> Cell {
+        Cell::new(text, Style::Plain)
+    }
+
+    fn styled(&self, text: &str) -> ColoredString {
+        let theme = theme();
+        match self.style {
+            Style::Plain => text.normal(),
+            Style::Bold => theme.strong(text.normal()),
+            Style::Dim => text.dimmed(),
+            Style::Info => theme.info(text),
+            Style::Warn => theme.warn(text),
+            Style::Error => theme.error(text),
+        }
+    }
+
+    /// The styled cell padded to `width`, or unpadded when `None`
+    fn render(&self, width: Option<usize>) -> String {
+        let mut out = self.styled(&self.text).to_string();
+        if let Some(note) = &self.note {
+            out.push_str(&format!(" {}", note.dimmed()));
+        }
+        if let Some(width) = width {
+            out.push_str(&" ".repeat(width.saturating_sub(self.width())));
+        }
+        out
+    }
+}
+
+/// Width-aligned table used by list/outdated style commands
+#[derive(Debug, Default)]
+pub struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<Cell>>,
+}
+
+impl Table {
+    pub fn new(headers: &[&str]) -> Table {
+        Table {
+            headers: headers.iter().map(|h| h.to_string()).collect(),
+            rows: vec![],
+        }
+    }
+
+    pub fn add_row(&mut self, row: Vec<Cell>) {
+        self.rows.push(row);
+    }
+
+    fn widths(&self) -> Vec<usize> {
+        let mut widths: Vec<usize> = self.headers.iter().map(|h| h.chars().count()).collect();
+        for row in &self.rows {
+            for (i, cell) in row.iter().enumerate() {
+                let len = cell.width();
+                match widths.get_mut(i) {
+                    Some(w) => *w = (*w).max(len),
+                    None => widths.push(len),
+                }
+            }
+        }
+        widths
+    }
+
+    /// Render the table; the last column is never padded so lines have no
+    /// trailing whitespace
+    pub fn render(&self) -> String {
+        let widths = self.widths();
+        let mut out = String::new();
+
+        let header: Vec<Cell> = self
+            .headers
+            .iter()
+            .map(|h| Cell::new(h.clone(), Style::Bold))
+            .collect();
+        let separator: Vec<Cell> = widths.iter().map(|w| Cell::plain("-".repeat(*w))).collect();
+
+        for row in std::iter::once(&header)
+            .chain(std::iter::once(&separator))
+            .chain(self.rows.iter())
+        {
+            let last = row.len().saturating_sub(1);
+            let line: Vec<String> = row
+                .iter()
+                .enumerate()
+                .map(|(i, cell)| cell.render((i != last).then(|| widths[i])))
+                .collect();
+            out.push_str(&line.join("  "));
+            out.push('\n');
+        }
+        out
+    }
+
+    pub fn print(&self) {
+        print!("{}", self.render());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_aligns_columns() {
+        colored::control::set_override(false);
+        let mut table = Table::new(&["Package", "Configured", "Installed"]);
+        table.add_row(vec![
+            Cell::plain("requests"),
+            Cell::plain("2.31.0"),
+            Cell::new("2.30.0", Style::Warn),
+        ]);
+        table.add_row(vec![
+            Cell::plain("numpy"),
+            Cell::plain("1.26.4"),
+            Cell::new("-", Style::Error),
+        ]);
+
+        let expected = "\
+Package   Configured  Installed
+--------  ----------  ---------
+requests  2.31.0      2.30.0
+numpy     1.26.4      -
+";
+        assert_eq!(table.render(), expected);
+    }
+
+    #[test]
+    fn test_render_notes_count_toward_width() {
+        colored::control::set_override(false);
+        let mut table = Table::new(&["Package", "Configured", "Installed"]);
+        table.add_row(vec![
+            Cell::plain("pywin32"),
+            Cell::plain("306").with_note(Some("; sys_platform == 'win32'".to_string())),
+            Cell::new("-", Style::Dim),
+        ]);
+        table.add_row(vec![
+            Cell::plain("requests"),
+            Cell::plain("2.31.0").with_note(None),
+            Cell::plain("2.31.0"),
+        ]);
+
+        let expected = "\
+Package   Configured                     Installed
+--------  -----------------------------  ---------
+pywin32   306 ; sys_platform == 'win32'  -
+requests  2.31.0                         2.31.0
+";
+        assert_eq!(table.render(), expected);
+    }
+
+    #[test]
+    fn test_render_header_only() {
+        colored::control::set_override(false);
+        let table = Table::new(&["Package"]);
+        assert_eq!(table.render(), "Package\n-------\n");
+    }
+}
@@ -0,0 +1,18 @@
+"""Smoke test: the main script imports without errors."""
+
+import importlib.util
+import pathlib
+import unittest
+
+MAIN_SCRIPT = pathlib.Path(__file__).resolve().parent.parent / "[main_script]"
+
+
+class SmokeTest(unittest.TestCase):
+    def test_main_script_imports(self):
+        spec = importlib.util.spec_from_file_location("main", MAIN_SCRIPT)
+        module = importlib.util.module_from_spec(spec)
+        spec.loader.exec_module(module)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,343 @@
+//! `ppm test`: find the project's test framework and make sense of what
+//! it printed.
+//!
+//! pytest is used when the project configures it (`pytest.ini`,
+//! `[tool.pytest.ini_options]` in pyproject.toml, `[tool:pytest]` in
+//! setup.cfg, `[pytest]` in tox.ini, a top-level `conftest.py`) or has it
+//! installed in the venv. Otherwise a project with tests to discover runs
+//! `python -m unittest discover`.
+
+use crate::verify::inspect_installed;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// A test framework `ppm test` knows how to run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framework {
+    /// `python -m pytest`
+    Pytest,
+    /// `python -m unittest discover`
+    Unittest,
+}
+
+impl Framework {
+    /// Arguments to the venv's python that run the tests, followed by
+    /// `extra` from the command line
+    pub fn args(&self, extra: &[String]) -> Vec<String> {
+        let mut args: Vec<String> = match self {
+            Framework::Pytest => vec!["-m".into(), "pytest".into()],
+            Framework::Unittest => vec!["-m".into(), "unittest".into(), "discover".into()],
+        };
+        args.extend(extra.iter().cloned());
+        args
+    }
+}
+
+impl fmt::Display for Framework {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Framework::Pytest => "pytest",
+            Framework::Unittest => "unittest",
+        })
+    }
+}
+
+/// Config files and the section in them that configures pytest; an empty
+/// section means the file alone does
+const PYTEST_CONFIGS: &[(&str, &str)] = &[
+    ("pytest.ini", ""),
+    ("conftest.py", ""),
+    ("pyproject.toml", "[tool.pytest.ini_options]"),
+    ("setup.cfg", "[tool:pytest]"),
+    ("tox.ini", "[pytest]"),
+];
+
+/// Whether the project at `root` configures pytest
+pub fn pytest_configured(root: &Path) -> bool {
+    PYTEST_CONFIGS.iter().any(|(file, section)| {
+        let path = root.join(file);
+        if section.is_empty() {
+            return path.is_file();
+        }
+        fs::read_to_string(path)
+            .map(|contents| contents.lines().any(|line| line.trim() == *section))
+            .unwrap_or(false)
+    })
+}
+
+/// Whether `root` has anything for `unittest discover` to find: a
+/// `tests` or `test` directory, or `test*.py` files at the top
+fn has_tests(root: &Path) -> bool {
+    if root.join("tests").is_dir() || root.join("test").is_dir() {
+        return true;
+    }
+    fs::read_dir(root)
+        .map(|entries| {
+            entries.flatten().any(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                name.starts_with("test") && name.ends_with(".py")
+            })
+        })
+        .unwrap_or(false)
+}
+
+/// The framework for the project at `root` with its venv at `venv_dir`,
+/// `None` when there is neither pytest nor anything to discover
+pub fn detect(root: &Path, venv_dir: &Path) -> Option<Framework> {
+    if pytest_configured(root) || inspect_installed(venv_dir, "pytest").is_some() {
+        Some(Framework::Pytest)
+    } else if has_tests(root) {
+        Some(Framework::Unittest)
+    } else {
+        None
+    }
+}
+
+/// `args` quoted for the shell a `[scripts]` command runs in, to append
+/// to it
+pub fn quote_args(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| quote_arg(arg))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(not(target_os = "windows"))]
+fn quote_arg(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_=./:,@%+".contains(c));
+    if plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn quote_arg(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"', '&', '|', '<', '>', '^']) {
+        arg.to_string()
+    } else {
+        format!("\"{}\"", arg.replace('"', "\"\""))
+    }
+}
+
+/// Counts from a test run's final summary
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Summary {
+    /// Tests that passed
+    pub passed: u32,
+    /// Tests whose assertions failed
+    pub failed: u32,
+    /// Tests that raised an error, or failed to be collected
+    pub errors: u32,
+    /// Tests that were skipped
+    pub skipped: u32,
+    /// How long the run took as the framework printed it, like `0.12s`
+    pub duration: Option<String>,
+}
+
+impl Summary {
+    /// Whether nothing failed
+    pub fn success(&self) -> bool {
+        self.failed == 0 && self.errors == 0
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = vec![format!("{} passed", self.passed)];
+        for (count, label) in [
+            (self.failed, "failed"),
+            (self.errors, "errors"),
+            (self.skipped, "skipped"),
+        ] {
+            if count > 0 {
+                parts.push(format!("{} {}", count, label));
+            }
+        }
+        write!(f, "{}", parts.join(", "))?;
+        if let Some(duration) = &self.duration {
+            write!(f, " in {}", duration)?;
+        }
+        Ok(())
+    }
+}
+
+/// The summary at the end of pytest or unittest `output`, `None` when
+/// there is none
+pub fn parse_summary(output: &str) -> Option<Summary> {
+    let lines: Vec<&str> = output.lines().map(str::trim).collect();
+    lines
+        .iter()
+        .rev()
+        .find_map(|line| parse_pytest_line(line))
+        .or_else(|| parse_unittest(&lines))
+}
+
+/// `==== 3 passed, 1 failed, 2 skipped in 0.12s ====`
+fn parse_pytest_line(line: &str) -> Option<Summary> {
+    let line = line.trim_matches(|c: char| c == '=' || c.is_whitespace());
+    let (counts, duration) = line.rsplit_once(" in ")?;
+    let duration = duration.split_whitespace().next()?;
+    if !duration.ends_with('s') {
+        return None;
+    }
+    let mut summary = Summary {
+        duration: Some(duration.to_string()),
+        ..Summary::default()
+    };
+    let mut any = false;
+    for part in counts.split(", ") {
+        let (count, label) = part.trim().split_once(' ')?;
+        let count: u32 = count.parse().ok()?;
+        match label {
+            "passed" => summary.passed = count,
+            "failed" => summary.failed = count,
+            "error" | "errors" => summary.errors = count,
+            "skipped" => summary.skipped = count,
+            _ => {}
+        }
+        any = true;
+    }
+    any.then_some(summary)
+}
+
+/// `Ran 5 tests in 0.001s` followed by `OK` or `FAILED (failures=1)`
+fn parse_unittest(lines: &[&str]) -> Option<Summary> {
+    let ran_at = lines.iter().rposition(|line| line.starts_with("Ran "))?;
+    let (ran, duration) = lines[ran_at]
+        .strip_prefix("Ran ")?
+        .split_once(" in ")
+        .map(|(tests, duration)| (tests, Some(duration.to_string())))?;
+    let ran: u32 = ran.split_whitespace().next()?.parse().ok()?;
+    let verdict = lines[ran_at + 1..].iter().find(|line| !line.is_empty())?;
+    if !verdict.starts_with("OK") && !verdict.starts_with("FAILED") {
+        return None;
+    }
+    let mut summary = Summary {
+        duration,
+        ..Summary::default()
+    };
+    if let Some(details) = verdict
+        .split_once('(')
+        .and_then(|(_, rest)| rest.strip_suffix(')'))
+    {
+        for detail in details.split(", ") {
+            let Some((key, count)) = detail.split_once('=') else {
+                continue;
+            };
+            let count: u32 = count.parse().unwrap_or(0);
+            match key {
+                "failures" => summary.failed = count,
+                "errors" => summary.errors = count,
+                "skipped" => summary.skipped = count,
+                _ => {}
+            }
+        }
+    }
+    summary.passed = ran.saturating_sub(summary.failed + summary.errors + summary.skipped);
+    Some(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_framework() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let venv = root.join("venv");
+        assert_eq!(detect(root, &venv), None);
+
+        fs::create_dir_all(root.join("tests")).unwrap();
+        assert_eq!(detect(root, &venv), Some(Framework::Unittest));
+
+        fs::write(root.join("pyproject.toml"), "[project]\nname = \"demo\"\n").unwrap();
+        assert_eq!(detect(root, &venv), Some(Framework::Unittest));
+        fs::write(
+            root.join("pyproject.toml"),
+            "[project]\nname = \"demo\"\n\n[tool.pytest.ini_options]\naddopts = \"-q\"\n",
+        )
+        .unwrap();
+        assert_eq!(detect(root, &venv), Some(Framework::Pytest));
+
+        // Installed in the venv, without any configuration
+        fs::remove_file(root.join("pyproject.toml")).unwrap();
+        let site = venv.join("lib").join("python3.12").join("site-packages");
+        fs::create_dir_all(site.join("pytest-8.2.0.dist-info")).unwrap();
+        assert_eq!(detect(root, &venv), Some(Framework::Pytest));
+
+        assert_eq!(
+            Framework::Unittest.args(&["-v".to_string()]),
+            vec!["-m", "unittest", "discover", "-v"]
+        );
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_quote_args() {
+        let args: Vec<String> = ["-k", "slow and not db", "--maxfail=2", "it's", ""]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        assert_eq!(
+            quote_args(&args),
+            r#"-k 'slow and not db' --maxfail=2 'it'\''s' ''"#
+        );
+    }
+
+    #[test]
+    fn test_parse_pytest_summary() {
+        let output = "tests/test_a.py ..F.s\n\
+                      FAILED tests/test_a.py::test_c - assert 1 == 2\n\
+                      ========= 1 failed, 3 passed, 1 skipped, 2 warnings in 0.12s =========\n";
+        let summary = parse_summary(output).unwrap();
+        assert_eq!(
+            summary,
+            Summary {
+                passed: 3,
+                failed: 1,
+                errors: 0,
+                skipped: 1,
+                duration: Some("0.12s".to_string()),
+            }
+        );
+        assert!(!summary.success());
+        assert_eq!(
+            summary.to_string(),
+            "3 passed, 1 failed, 1 skipped in 0.12s"
+        );
+
+        let summary = parse_summary("=== 1 passed, 2 errors in 1.50s (0:00:01) ===").unwrap();
+        assert_eq!(summary.errors, 2);
+        assert_eq!(
+            parse_summary("collected 0 items\nno tests ran in 0.01s"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_unittest_summary() {
+        let output = "..F\n\
+                      ----------------------------------------------------------------------\n\
+                      Ran 4 tests in 0.003s\n\
+                      \n\
+                      FAILED (failures=1, skipped=1)\n";
+        let summary = parse_summary(output).unwrap();
+        assert_eq!((summary.passed, summary.failed, summary.skipped), (2, 1, 1));
+        assert_eq!(
+            summary.to_string(),
+            "2 passed, 1 failed, 1 skipped in 0.003s"
+        );
+
+        let summary = parse_summary("Ran 2 tests in 0.000s\n\nOK\n").unwrap();
+        assert!(summary.success());
+        assert_eq!(summary.passed, 2);
+        assert_eq!(parse_summary("Ran 2 tests in 0.000s\n"), None);
+    }
+}
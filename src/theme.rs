@@ -0,0 +1,290 @@
+//! Colors and symbols for ppm's output.
+//!
+//! Every styling decision of the output helpers, tables and progress bars
+//! comes from a [`Theme`], picked with `theme` in the global config:
+//!
+//! ```toml
+//! theme = "high-contrast"
+//! ```
+//!
+//! or adjusted key by key on top of a preset:
+//!
+//! ```toml
+//! [theme]
+//! preset = "ascii"
+//! info = "cyan"
+//! bold = false
+//! ```
+//!
+//! Colors are names `colored` knows, like `"bright magenta"`, or `"#rrggbb"`.
+
+use colored::{Color, ColoredString, Colorize};
+use serde::{Deserialize, Serialize};
+
+/// Names of the built-in themes, the default first
+pub const PRESETS: &[&str] = &["default", "high-contrast", "ascii"];
+
+/// How output is styled
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    /// Errors and failures
+    pub error: Color,
+    /// Warnings and things out of date
+    pub warn: Color,
+    /// Progress messages and things in order
+    pub info: Color,
+    /// Spinners and progress bars
+    pub accent: Color,
+    /// Marks an info message
+    pub bullet: String,
+    /// Marks something in order
+    pub check: String,
+    /// Marks something missing or failed
+    pub cross: String,
+    /// Separates the old and new side of a change
+    pub arrow: String,
+    /// Whether labels and headers are bold
+    pub bold: bool,
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme {
+            error: Color::BrightRed,
+            warn: Color::BrightYellow,
+            info: Color::BrightGreen,
+            accent: Color::Green,
+            bullet: "•".to_string(),
+            check: "✓".to_string(),
+            cross: "✗".to_string(),
+            arrow: "→".to_string(),
+            bold: true,
+        }
+    }
+}
+
+impl Theme {
+    /// The built-in theme called `name`, one of [`PRESETS`]
+    pub fn preset(name: &str) -> Option<Theme> {
+        match name {
+            "default" => Some(Theme::default()),
+            // Avoids telling states apart by red and green alone
+            "high-contrast" => Some(Theme {
+                error: Color::BrightMagenta,
+                warn: Color::BrightYellow,
+                info: Color::BrightCyan,
+                accent: Color::BrightWhite,
+                ..Theme::default()
+            }),
+            "ascii" => Some(Theme {
+                bullet: "*".to_string(),
+                check: "ok".to_string(),
+                cross: "x".to_string(),
+                arrow: "->".to_string(),
+                ..Theme::default()
+            }),
+            _ => None,
+        }
+    }
+
+    /// Whether every symbol is ASCII, as in the `ascii` preset
+    pub fn is_ascii(&self) -> bool {
+        [&self.bullet, &self.check, &self.cross, &self.arrow]
+            .iter()
+            .all(|symbol| symbol.is_ascii())
+    }
+
+    /// `text` in the error color
+    pub fn error(&self, text: &str) -> ColoredString {
+        text.color(self.error)
+    }
+
+    /// `text` in the warning color
+    pub fn warn(&self, text: &str) -> ColoredString {
+        text.color(self.warn)
+    }
+
+    /// `text` in the info color
+    pub fn info(&self, text: &str) -> ColoredString {
+        text.color(self.info)
+    }
+
+    /// `text` in bold, unless the theme turns bold off
+    pub fn strong(&self, text: ColoredString) -> ColoredString {
+        if self.bold { text.bold() } else { text }
+    }
+}
+
+/// `theme` in the global config: a preset's name or a table of overrides
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum ThemeConfig {
+    /// One of [`PRESETS`]
+    Preset(String),
+    /// A preset with some of its keys replaced
+    Custom(CustomTheme),
+}
+
+/// The `[theme]` table; unset keys come from `preset`
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct CustomTheme {
+    /// The preset to start from, `default` when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+    /// Color of errors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Color of warnings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warn: Option<String>,
+    /// Color of info messages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub info: Option<String>,
+    /// Color of spinners and progress bars
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accent: Option<String>,
+    /// Symbol before info messages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bullet: Option<String>,
+    /// Symbol for something in order
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub check: Option<String>,
+    /// Symbol for something missing or failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cross: Option<String>,
+    /// Symbol between the two sides of a change
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arrow: Option<String>,
+    /// Whether labels and headers are bold
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bold: Option<bool>,
+}
+
+impl ThemeConfig {
+    /// The theme this describes, or why it describes none
+    pub fn resolve(&self) -> Result<Theme, String> {
+        match self {
+            ThemeConfig::Preset(name) => preset_or_err(name),
+            ThemeConfig::Custom(custom) => {
+                let mut theme = preset_or_err(custom.preset.as_deref().unwrap_or("default"))?;
+                for (color, value) in [
+                    (&mut theme.error, &custom.error),
+                    (&mut theme.warn, &custom.warn),
+                    (&mut theme.info, &custom.info),
+                    (&mut theme.accent, &custom.accent),
+                ] {
+                    if let Some(value) = value {
+                        *color = value
+                            .parse()
+                            .map_err(|_| format!("Unknown color '{}' in theme", value))?;
+                    }
+                }
+                for (symbol, value) in [
+                    (&mut theme.bullet, &custom.bullet),
+                    (&mut theme.check, &custom.check),
+                    (&mut theme.cross, &custom.cross),
+                    (&mut theme.arrow, &custom.arrow),
+                ] {
+                    if let Some(value) = value {
+                        symbol.clone_from(value);
+                    }
+                }
+                if let Some(bold) = custom.bold {
+                    theme.bold = bold;
+                }
+                Ok(theme)
+            }
+        }
+    }
+}
+
+fn preset_or_err(name: &str) -> Result<Theme, String> {
+    Theme::preset(name).ok_or_else(|| {
+        format!(
+            "Unknown theme '{}'; the presets are {}",
+            name,
+            PRESETS.join(", ")
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use colored::Styles;
+
+    #[test]
+    fn test_presets() {
+        for name in PRESETS {
+            assert!(Theme::preset(name).is_some(), "{}", name);
+        }
+        assert_eq!(Theme::preset("default"), Some(Theme::default()));
+        assert_eq!(Theme::preset("solarized"), None);
+
+        assert!(Theme::preset("ascii").unwrap().is_ascii());
+        assert!(!Theme::default().is_ascii());
+
+        let high = Theme::preset("high-contrast").unwrap();
+        assert_ne!(high.error, Color::BrightRed);
+        assert_ne!(high.info, Color::BrightGreen);
+    }
+
+    #[test]
+    fn test_styling() {
+        let theme = Theme::default();
+        let error = theme.strong(theme.error("error:"));
+        assert_eq!(error.fgcolor, Some(Color::BrightRed));
+        assert!(error.style.contains(Styles::Bold));
+
+        let plain = Theme {
+            bold: false,
+            ..Theme::default()
+        };
+        let info = plain.strong(plain.info("done"));
+        assert_eq!(info.fgcolor, Some(Color::BrightGreen));
+        assert!(!info.style.contains(Styles::Bold));
+    }
+
+    #[test]
+    fn test_resolve_config() {
+        #[derive(Deserialize)]
+        struct Config {
+            theme: ThemeConfig,
+        }
+        let theme = |toml: &str| toml::from_str::<Config>(toml).unwrap().theme.resolve();
+
+        assert_eq!(
+            theme("theme = \"ascii\"").unwrap(),
+            Theme::preset("ascii").unwrap()
+        );
+        assert!(
+            theme("theme = \"neon\"")
+                .unwrap_err()
+                .contains("default, high-contrast, ascii")
+        );
+
+        let custom = theme(
+            "[theme]\npreset = \"ascii\"\ninfo = \"bright magenta\"\nerror = \"#ff8800\"\nbold = false\n",
+        )
+        .unwrap();
+        assert_eq!(custom.info, Color::BrightMagenta);
+        assert_eq!(
+            custom.error,
+            Color::TrueColor {
+                r: 0xff,
+                g: 0x88,
+                b: 0x00
+            }
+        );
+        assert_eq!(custom.bullet, "*");
+        assert!(!custom.bold);
+
+        assert!(
+            theme("[theme]\nwarn = \"plaid\"\n")
+                .unwrap_err()
+                .contains("plaid")
+        );
+        assert!(toml::from_str::<Config>("[theme]\nsparkle = true\n").is_err());
+    }
+}
@@ -0,0 +1,1652 @@
+//! Planning `ppm update`: which packages move to which version.
+
+use crate::age;
+use crate::constraints::Constraints;
+use crate::context::ProjectContext;
+use crate::error::PpmError;
+use crate::index::Releases;
+use crate::listing::MARKDOWN_HEADERS;
+use crate::markdown;
+use crate::packages::{compare_versions, is_prerelease, normalize_pkg_name, release_prefix};
+use crate::settings::{Config, GitSource, PackageSpec, UpdatePolicy};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+
+/// How `ppm update` runs, from its command-line flags
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UpdateOptions {
+    /// Resolve and print the plan without installing anything
+    pub dry_run: bool,
+    /// Move git packages that follow a branch or tag to its latest commit
+    pub refresh_git: bool,
+    /// How many pip processes to run at once
+    pub jobs: Option<usize>,
+    /// Apply the plan without asking
+    pub yes: bool,
+    /// Reinstall packages the venv already has at their version
+    pub force_reinstall: bool,
+    /// Fail when a pin the update leaves in place is on a yanked release
+    pub deny_yanked: bool,
+    /// Finish the update Ctrl+C stopped, see [`crate::resume`]
+    pub resume: bool,
+    /// Print the summary as a Markdown table instead
+    pub markdown: bool,
+    /// Which versions packages may move to
+    pub mode: UpdateMode,
+}
+
+/// How `ppm update --latest` writes back a package it moved past its
+/// update policy
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PinStyle {
+    /// Pin the new version exactly, with `update = "pin"`
+    Exact,
+    /// Keep the policy, so its range moves along: `~=1.4` becomes `~=2.0`
+    #[default]
+    Compatible,
+}
+
+impl fmt::Display for PinStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PinStyle::Exact => "exact",
+            PinStyle::Compatible => "compatible",
+        })
+    }
+}
+
+/// Which versions `ppm update` moves packages to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateMode {
+    /// The newest release each package's update policy allows
+    #[default]
+    Policy,
+    /// The newest release, past `patch` and `minor` policies, and past
+    /// `pin` too when `force` is set
+    Latest {
+        /// Move pinned packages as well
+        force: bool,
+        /// How the packages moved past their policy are written back
+        style: PinStyle,
+    },
+}
+
+impl UpdateMode {
+    /// The update policy `spec` is planned with in this mode
+    pub fn policy(&self, spec: &PackageSpec) -> UpdatePolicy {
+        match (self, spec.update_policy()) {
+            (UpdateMode::Policy, policy) => policy,
+            (UpdateMode::Latest { force: false, .. }, UpdatePolicy::Pin) => UpdatePolicy::Pin,
+            (UpdateMode::Latest { .. }, _) => UpdatePolicy::Latest,
+        }
+    }
+
+    /// `config` with each index package's update policy replaced by the
+    /// one this mode plans it with, for the `plan_*` functions
+    pub fn planning_config(&self, config: &Config) -> Config {
+        let mut planning = config.clone();
+        for spec in planning.packages.values_mut() {
+            if spec.is_index() {
+                *spec = spec.with_update_policy(self.policy(spec));
+            }
+        }
+        planning
+    }
+
+    /// What the mode does, for the first line of the summary
+    pub fn describe(&self) -> String {
+        match self {
+            UpdateMode::Policy => "within each package's update policy".to_string(),
+            UpdateMode::Latest { force, style } => format!(
+                "latest, past {} (--pin-style {})",
+                if *force {
+                    "every update policy including pin"
+                } else {
+                    "patch and minor update policies"
+                },
+                style
+            ),
+        }
+    }
+}
+
+/// Whether `policy` lets a package pinned at `current` be at `version`
+pub fn policy_allows(policy: UpdatePolicy, current: &str, version: &str) -> bool {
+    // Leading release segments that must stay the same
+    let fixed = match policy {
+        UpdatePolicy::Pin => return compare_versions(version, current) == Ordering::Equal,
+        UpdatePolicy::Patch => 2,
+        UpdatePolicy::Minor => 1,
+        UpdatePolicy::Latest => 0,
+    };
+    compare_versions(version, current) != Ordering::Less
+        && release_prefix(version, fixed) == release_prefix(current, fixed)
+}
+
+/// The versions `policy` allows from `version`, as a PEP 440 specifier:
+/// `==4.2.1` for `pin`, `~=4.2.1` for `patch`, `~=4.2` for `minor` and
+/// `>=4.2.1` for `latest`
+pub fn specifier(policy: UpdatePolicy, version: &str) -> String {
+    let prefix = |count| {
+        release_prefix(version, count)
+            .iter()
+            .map(u64::to_string)
+            .collect::<Vec<_>>()
+            .join(".")
+    };
+    match policy {
+        UpdatePolicy::Pin => format!("=={}", version),
+        UpdatePolicy::Patch => format!("~={}", prefix(3)),
+        UpdatePolicy::Minor => format!("~={}", prefix(2)),
+        UpdatePolicy::Latest => format!(">={}", version),
+    }
+}
+
+/// An entry `ppm update --latest` moves past its update policy, which is
+/// rewritten along with its version
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rewrite {
+    /// Package name as spelled in project.toml
+    pub name: String,
+    /// The update policy it had
+    pub from: UpdatePolicy,
+    /// The update policy it gets
+    pub to: UpdatePolicy,
+}
+
+/// Whether pip has to run for a package pinned at `configured` and
+/// resolved to `latest`, which the venv has at `installed` (`None` when
+/// it's missing there)
+pub fn needs_install(configured: &str, installed: Option<&str>, latest: &str) -> bool {
+    configured != latest
+        || installed.is_none_or(|installed| compare_versions(installed, latest) != Ordering::Equal)
+}
+
+/// What happened (or, in a dry run, would happen) to one package
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpdateOutcome {
+    /// A newer version exists
+    Updated {
+        /// Version pinned in project.toml
+        from: String,
+        /// Latest version on the index
+        to: String,
+    },
+    /// The pinned version is the latest
+    UpToDate {
+        /// The pinned (and latest) version
+        version: String,
+    },
+    /// The package's update policy keeps it from a newer version
+    Held {
+        /// The pinned version, unchanged
+        version: String,
+        /// Latest version on the index; not looked up for pinned packages
+        latest: Option<String>,
+        /// The policy that holds it
+        policy: UpdatePolicy,
+    },
+    /// The project's constraints file keeps it from a newer version
+    Constrained {
+        /// The pinned version, unchanged
+        version: String,
+        /// The version it would otherwise have moved to
+        latest: String,
+        /// The constraint that holds it, like `django<5.0`
+        constraint: String,
+    },
+    /// Resolving or installing the package failed
+    Failed {
+        /// Human-readable cause
+        reason: String,
+    },
+}
+
+/// Outcome for one package
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageUpdate {
+    /// Package name as spelled in project.toml
+    pub name: String,
+    /// What happened to it
+    pub outcome: UpdateOutcome,
+}
+
+/// Result of an update run, collected before anything is printed so the
+/// same data can feed the summary table, `--dry-run` and `--json`
+#[derive(Debug, Default)]
+pub struct UpdateSummary {
+    /// Nothing was (or will be) installed
+    pub dry_run: bool,
+    /// Per-package outcomes in resolution order
+    pub packages: Vec<PackageUpdate>,
+    /// Changelog links of updated packages by name, where one was found
+    pub changelogs: HashMap<String, String>,
+    /// Age in days of the release each updated package moves away from,
+    /// only looked up for Markdown output
+    pub ages: HashMap<String, u64>,
+    /// The mode the update was planned in
+    pub mode: UpdateMode,
+    /// Entries whose update policy changes with their version, see
+    /// [`plan_rewrites`](UpdateSummary::plan_rewrites)
+    pub rewrites: Vec<Rewrite>,
+}
+
+impl UpdateSummary {
+    /// An empty summary
+    pub fn new(dry_run: bool) -> UpdateSummary {
+        UpdateSummary {
+            dry_run,
+            packages: vec![],
+            changelogs: HashMap::new(),
+            ages: HashMap::new(),
+            mode: UpdateMode::Policy,
+            rewrites: vec![],
+        }
+    }
+
+    /// Record an outcome for `name`
+    pub fn push(&mut self, name: &str, outcome: UpdateOutcome) {
+        self.packages.push(PackageUpdate {
+            name: name.to_string(),
+            outcome,
+        });
+    }
+
+    /// Resolve the outcome for `name` from its configured and latest version
+    pub fn push_resolved(&mut self, name: &str, configured: &str, latest: &str) {
+        let outcome = if configured == latest {
+            UpdateOutcome::UpToDate {
+                version: latest.to_string(),
+            }
+        } else {
+            UpdateOutcome::Updated {
+                from: configured.to_string(),
+                to: latest.to_string(),
+            }
+        };
+        self.push(name, outcome);
+    }
+
+    /// Resolve the outcome for `name`, held by `policy`, from its
+    /// configured version and the index's releases
+    pub fn push_with_policy(
+        &mut self,
+        name: &str,
+        configured: &str,
+        policy: UpdatePolicy,
+        releases: &Releases,
+    ) {
+        if let Some(newest) = newest_allowed(policy, configured, &releases.versions) {
+            return self.push_resolved(name, configured, &newest);
+        }
+        let outcome = if compare_versions(&releases.latest, configured) == Ordering::Greater {
+            UpdateOutcome::Held {
+                version: configured.to_string(),
+                latest: Some(releases.latest.clone()),
+                policy,
+            }
+        } else {
+            UpdateOutcome::UpToDate {
+                version: configured.to_string(),
+            }
+        };
+        self.push(name, outcome);
+    }
+
+    /// Turn every planned update into a failure, e.g. when the pip batch fails
+    pub fn fail_updates(&mut self, reason: &str) {
+        for pkg in self.packages.iter_mut() {
+            if let UpdateOutcome::Updated { .. } = pkg.outcome {
+                pkg.outcome = UpdateOutcome::Failed {
+                    reason: reason.to_string(),
+                };
+            }
+        }
+    }
+
+    /// Turn the planned update of `name` into a failure, e.g. when its pip
+    /// run fails while the others succeed
+    pub fn fail_update(&mut self, name: &str, reason: &str) {
+        for pkg in self.packages.iter_mut().filter(|pkg| pkg.name == name) {
+            if let UpdateOutcome::Updated { .. } = pkg.outcome {
+                pkg.outcome = UpdateOutcome::Failed {
+                    reason: reason.to_string(),
+                };
+            }
+        }
+    }
+
+    /// Names of the updated index packages, whose changelogs
+    /// [`set_changelogs`](UpdateSummary::set_changelogs) takes
+    pub fn updated_index_packages(&self, config: &Config) -> Vec<String> {
+        self.updated()
+            .filter(|(name, _, _)| {
+                config
+                    .packages
+                    .get(*name)
+                    .is_some_and(PackageSpec::is_index)
+            })
+            .map(|(name, _, _)| name.to_string())
+            .collect()
+    }
+
+    /// Record the changelog links of `names`, in the same order
+    pub fn set_changelogs(&mut self, names: &[String], urls: Vec<Option<String>>) {
+        for (name, url) in names.iter().zip(urls) {
+            if let Some(url) = url {
+                self.changelogs.insert(name.clone(), url);
+            }
+        }
+    }
+
+    /// Record, for an update planned in [`UpdateMode::Latest`], the index
+    /// packages whose new version is outside what their update policy in
+    /// `config` allows; the mode's [`PinStyle`] decides the policy they
+    /// get
+    pub fn plan_rewrites(&mut self, config: &Config) {
+        let UpdateMode::Latest { style, .. } = self.mode else {
+            return;
+        };
+        self.rewrites = self
+            .updated()
+            .filter_map(|(name, from, to)| {
+                let policy = config
+                    .packages
+                    .get(name)
+                    .filter(|spec| spec.is_index())?
+                    .update_policy();
+                if policy_allows(policy, from, to) {
+                    return None;
+                }
+                Some(Rewrite {
+                    name: name.to_string(),
+                    from: policy,
+                    to: match style {
+                        PinStyle::Exact => UpdatePolicy::Pin,
+                        PinStyle::Compatible => policy,
+                    },
+                })
+            })
+            .collect();
+    }
+
+    /// `(name, old specifier, new specifier)` for each updated package
+    /// whose update policy is rewritten, see [`specifier`]
+    pub fn rewritten(&self) -> impl Iterator<Item = (&str, String, String)> {
+        self.updated().filter_map(|(name, from, to)| {
+            let rewrite = self.rewrites.iter().find(|rewrite| rewrite.name == name)?;
+            Some((
+                name,
+                specifier(rewrite.from, from),
+                specifier(rewrite.to, to),
+            ))
+        })
+    }
+
+    /// `(name, from, to)` for each package with a newer version
+    pub fn updated(&self) -> impl Iterator<Item = (&str, &str, &str)> {
+        self.packages.iter().filter_map(|p| match &p.outcome {
+            UpdateOutcome::Updated { from, to } => {
+                Some((p.name.as_str(), from.as_str(), to.as_str()))
+            }
+            _ => None,
+        })
+    }
+
+    /// `(name, version)` for each package already current
+    pub fn up_to_date(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.packages.iter().filter_map(|p| match &p.outcome {
+            UpdateOutcome::UpToDate { version } => Some((p.name.as_str(), version.as_str())),
+            _ => None,
+        })
+    }
+
+    /// Each package its policy held back
+    pub fn held(&self) -> impl Iterator<Item = (&str, &str, Option<&str>, UpdatePolicy)> {
+        self.packages.iter().filter_map(|p| match &p.outcome {
+            UpdateOutcome::Held {
+                version,
+                latest,
+                policy,
+            } => Some((
+                p.name.as_str(),
+                version.as_str(),
+                latest.as_deref(),
+                *policy,
+            )),
+            _ => None,
+        })
+    }
+
+    /// `(name, version, latest, constraint)` for each package the
+    /// constraints file held back
+    pub fn constrained(&self) -> impl Iterator<Item = (&str, &str, &str, &str)> {
+        self.packages.iter().filter_map(|p| match &p.outcome {
+            UpdateOutcome::Constrained {
+                version,
+                latest,
+                constraint,
+            } => Some((
+                p.name.as_str(),
+                version.as_str(),
+                latest.as_str(),
+                constraint.as_str(),
+            )),
+            _ => None,
+        })
+    }
+
+    /// `(name, reason)` for each package that failed
+    pub fn failed(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.packages.iter().filter_map(|p| match &p.outcome {
+            UpdateOutcome::Failed { reason } => Some((p.name.as_str(), reason.as_str())),
+            _ => None,
+        })
+    }
+
+    /// Requirement specs to hand to pip, e.g. `name==version` or, for a
+    /// refreshed git package, `name @ git+url@rev`. Packages that keep
+    /// their version are left out when the venv already has it, going by
+    /// `installed` (normalized name to version, `None` when unknown);
+    /// `force` includes them all, for pip to reinstall with
+    /// `--force-reinstall` so a broken install gets repaired.
+    pub fn install_specs(
+        &self,
+        config: &Config,
+        installed: Option<&HashMap<String, String>>,
+        force: bool,
+    ) -> Vec<String> {
+        self.packages
+            .iter()
+            .filter_map(|p| {
+                let (configured, version) = match &p.outcome {
+                    UpdateOutcome::Updated { from, to } => (from, to),
+                    UpdateOutcome::UpToDate { version }
+                    | UpdateOutcome::Held { version, .. }
+                    | UpdateOutcome::Constrained { version, .. } => (version, version),
+                    UpdateOutcome::Failed { .. } => return None,
+                };
+                let spec = config.packages.get(&p.name)?;
+                let in_venv = match installed {
+                    // Without the venv's packages only version changes count
+                    None => Some(version.as_str()),
+                    Some(installed) => match installed.get(&normalize_pkg_name(&p.name)) {
+                        // pip reports a git package's version, not its commit
+                        Some(_) if !spec.is_index() => Some(version.as_str()),
+                        other => other.map(String::as_str),
+                    },
+                };
+                if !force && !needs_install(configured, in_venv, version) {
+                    return None;
+                }
+                Some(spec.with_version(version).requirement(&p.name))
+            })
+            .collect()
+    }
+
+    /// Write the new versions of updated packages into `config`, and
+    /// the new update policies of those [`rewritten`](UpdateSummary::rewritten)
+    pub fn apply(&self, config: &mut Config) {
+        for (name, _, to) in self.updated() {
+            if let Some(spec) = config.packages.get_mut(name) {
+                *spec = spec.with_version(to);
+                if let Some(rewrite) = self.rewrites.iter().find(|rewrite| rewrite.name == name) {
+                    *spec = spec.with_update_policy(rewrite.to);
+                }
+            }
+        }
+    }
+
+    /// Grouped JSON object: `{"dry_run", "mode", "updated", "rewritten",
+    /// "up_to_date", "held", "constrained", "failed"}`, with a
+    /// `changelog_url` (or `null`) for each updated package
+    pub fn to_json(&self) -> serde_json::Value {
+        let mode = match self.mode {
+            UpdateMode::Policy => serde_json::json!({"name": "policy"}),
+            UpdateMode::Latest { force, style } => serde_json::json!({
+                "name": "latest",
+                "force": force,
+                "pin_style": style.to_string(),
+            }),
+        };
+        serde_json::json!({
+            "dry_run": self.dry_run,
+            "mode": mode,
+            "updated": self
+                .updated()
+                .map(|(name, from, to)| {
+                    serde_json::json!({
+                        "name": name,
+                        "from": from,
+                        "to": to,
+                        "changelog_url": self.changelogs.get(name),
+                    })
+                })
+                .collect::<Vec<_>>(),
+            "rewritten": self
+                .rewritten()
+                .map(|(name, from, to)| serde_json::json!({"name": name, "from": from, "to": to}))
+                .collect::<Vec<_>>(),
+            "up_to_date": self
+                .up_to_date()
+                .map(|(name, version)| serde_json::json!({"name": name, "version": version}))
+                .collect::<Vec<_>>(),
+            "held": self
+                .held()
+                .map(|(name, version, latest, policy)| {
+                    serde_json::json!({
+                        "name": name,
+                        "version": version,
+                        "latest": latest,
+                        "policy": policy.to_string(),
+                    })
+                })
+                .collect::<Vec<_>>(),
+            "constrained": self
+                .constrained()
+                .map(|(name, version, latest, constraint)| {
+                    serde_json::json!({
+                        "name": name,
+                        "version": version,
+                        "latest": latest,
+                        "constraint": constraint,
+                    })
+                })
+                .collect::<Vec<_>>(),
+            "failed": self
+                .failed()
+                .map(|(name, reason)| serde_json::json!({"name": name, "reason": reason}))
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    /// The updated packages as a Markdown table of [`MARKDOWN_HEADERS`],
+    /// for a pull request description; a line saying so when there are
+    /// none
+    pub fn to_markdown(&self) -> String {
+        let rows: Vec<Vec<String>> = self
+            .updated()
+            .map(|(name, from, to)| {
+                vec![
+                    name.to_string(),
+                    from.to_string(),
+                    to.to_string(),
+                    self.ages
+                        .get(name)
+                        .map_or_else(|| "?".to_string(), |days| age::format_age(*days)),
+                    self.changelogs
+                        .get(name)
+                        .map(|url| markdown::link("changelog", url))
+                        .unwrap_or_default(),
+                ]
+            })
+            .collect();
+        if rows.is_empty() {
+            return "No updates\n".to_string();
+        }
+        markdown::table(MARKDOWN_HEADERS, &rows, &[4])
+    }
+
+    /// Human-readable summary: the mode, `old -> new` per package with
+    /// its changelog link dimmed on the next line, the rewritten
+    /// specifiers, then the packages already current, those held back by
+    /// their policy or by the constraints file, then failures with a
+    /// one-line reason
+    pub fn render(&self) -> String {
+        let width = self
+            .packages
+            .iter()
+            .map(|p| p.name.chars().count())
+            .max()
+            .unwrap_or(0);
+        let mut out = format!("{} {}\n", "Mode:".bold(), self.mode.describe());
+
+        let updated: Vec<_> = self.updated().collect();
+        if !updated.is_empty() {
+            let title = if self.dry_run {
+                "Would update"
+            } else {
+                "Updated"
+            };
+            out.push_str(&format!("{} ({}):\n", title.bold(), updated.len()));
+            for (name, from, to) in updated {
+                out.push_str(&format!(
+                    "  {:<width$}  {} -> {}\n",
+                    name,
+                    from.red(),
+                    to.green(),
+                    width = width
+                ));
+                if let Some(url) = self.changelogs.get(name) {
+                    out.push_str(&format!(
+                        "  {:<width$}  {}\n",
+                        "",
+                        url.dimmed(),
+                        width = width
+                    ));
+                }
+            }
+        }
+
+        let rewritten: Vec<_> = self.rewritten().collect();
+        if !rewritten.is_empty() {
+            out.push_str(&format!(
+                "{} ({}):\n",
+                "Specifiers rewritten".bold(),
+                rewritten.len()
+            ));
+            for (name, from, to) in rewritten {
+                out.push_str(&format!(
+                    "  {:<width$}  {} -> {}\n",
+                    name,
+                    from,
+                    to,
+                    width = width
+                ));
+            }
+        }
+
+        let current: Vec<_> = self.up_to_date().collect();
+        if !current.is_empty() {
+            out.push_str(&format!(
+                "{} ({}):\n",
+                "Already up to date".bold(),
+                current.len()
+            ));
+            for (name, version) in current {
+                out.push_str(&format!(
+                    "  {:<width$}  {}\n",
+                    name,
+                    version.dimmed(),
+                    width = width
+                ));
+            }
+        }
+
+        let held: Vec<_> = self.held().collect();
+        if !held.is_empty() {
+            out.push_str(&format!(
+                "{} ({}):\n",
+                "Held back by policy".bold(),
+                held.len()
+            ));
+            for (name, version, latest, policy) in held {
+                let detail = match latest {
+                    Some(latest) => format!("latest {}, update = \"{}\"", latest, policy),
+                    None => format!("update = \"{}\"", policy),
+                };
+                out.push_str(&format!(
+                    "  {:<width$}  {} {}\n",
+                    name,
+                    version,
+                    format!("({})", detail).dimmed(),
+                    width = width
+                ));
+            }
+        }
+
+        let constrained: Vec<_> = self.constrained().collect();
+        if !constrained.is_empty() {
+            out.push_str(&format!(
+                "{} ({}):\n",
+                "Held back by constraints".bold(),
+                constrained.len()
+            ));
+            for (name, version, latest, constraint) in constrained {
+                out.push_str(&format!(
+                    "  {:<width$}  {} {}\n",
+                    name,
+                    version,
+                    format!("(latest {}, constraint {})", latest, constraint).dimmed(),
+                    width = width
+                ));
+            }
+        }
+
+        let failed: Vec<_> = self.failed().collect();
+        if !failed.is_empty() {
+            out.push_str(&format!("{} ({}):\n", "Failed".bold(), failed.len()));
+            for (name, reason) in failed {
+                let reason = reason.lines().next().unwrap_or_default();
+                out.push_str(&format!(
+                    "  {:<width$}  {}\n",
+                    name,
+                    reason.bright_red(),
+                    width = width
+                ));
+            }
+        }
+        out
+    }
+}
+
+/// Packages selected for an update
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct UpdateTargets {
+    /// Configured packages to resolve, sorted by name
+    pub targets: Vec<String>,
+    /// Requested names that are not in project.toml
+    pub unknown: Vec<String>,
+}
+
+/// Select the packages to update: every configured package when
+/// `pkg_names` is empty, otherwise the requested ones. The project itself
+/// (a `{ path = "." }` package) is never selected.
+///
+/// Fails when names were requested but none of them is configured.
+pub fn update_targets(config: &Config, pkg_names: &[String]) -> Result<UpdateTargets, PpmError> {
+    let mut selected = UpdateTargets::default();
+    if pkg_names.is_empty() {
+        selected.targets = config
+            .packages
+            .iter()
+            .filter(|(_, spec)| !spec.is_self())
+            .map(|(name, _)| name.clone())
+            .collect();
+        selected.targets.sort();
+        return Ok(selected);
+    }
+
+    for name in pkg_names {
+        if config
+            .packages
+            .get(name)
+            .is_some_and(|spec| !spec.is_self())
+        {
+            selected.targets.push(name.clone());
+        } else {
+            selected.unknown.push(name.clone());
+        }
+    }
+    if selected.targets.is_empty() {
+        return Err(PpmError::Other(
+            "No valid packages specified to update".to_owned(),
+        ));
+    }
+    Ok(selected)
+}
+
+/// Resolve `targets` with `resolve` (name to latest version) and classify
+/// each against the version pinned in `config`. Resolution failures are
+/// recorded in the summary rather than aborting the plan.
+pub fn plan_updates_with(
+    config: &Config,
+    targets: &[String],
+    dry_run: bool,
+    mut resolve: impl FnMut(&str) -> Result<String, PpmError>,
+) -> UpdateSummary {
+    let mut summary = UpdateSummary::new(dry_run);
+    for name in targets {
+        // Git, path and URL packages have no index version to resolve, and
+        // packages with a policy are planned by `plan_policy_updates_with`
+        let configured = match config.packages.get(name) {
+            Some(spec) if spec.update_policy() == UpdatePolicy::Latest => match spec.version() {
+                Some(version) => version,
+                None => continue,
+            },
+            _ => continue,
+        };
+        match resolve(name) {
+            Ok(latest) => summary.push_resolved(name, configured, &latest),
+            Err(e) => summary.push(
+                name,
+                UpdateOutcome::Failed {
+                    reason: e.to_string(),
+                },
+            ),
+        }
+    }
+    summary
+}
+
+/// The newest of `versions` that `policy` lets a package move to from
+/// `current`, `None` when there is none newer. Pre-releases are never
+/// picked.
+pub fn newest_allowed(policy: UpdatePolicy, current: &str, versions: &[String]) -> Option<String> {
+    versions
+        .iter()
+        .filter(|version| !is_prerelease(version))
+        .filter(|version| compare_versions(version, current) == Ordering::Greater)
+        .filter(|version| policy_allows(policy, current, version))
+        .max_by(|a, b| compare_versions(a, b))
+        .cloned()
+}
+
+/// Plan the index packages in `targets` that have an update policy other
+/// than `latest`, with `releases` looking up a package's releases. Pinned
+/// packages aren't looked up at all.
+pub fn plan_policy_updates_with(
+    config: &Config,
+    targets: &[String],
+    summary: &mut UpdateSummary,
+    mut releases: impl FnMut(&str) -> Result<Releases, PpmError>,
+) {
+    for name in targets {
+        let Some(spec) = config.packages.get(name) else {
+            continue;
+        };
+        let (policy, Some(configured)) = (spec.update_policy(), spec.version()) else {
+            continue;
+        };
+        match policy {
+            UpdatePolicy::Latest => {}
+            UpdatePolicy::Pin => summary.push(
+                name,
+                UpdateOutcome::Held {
+                    version: configured.to_string(),
+                    latest: None,
+                    policy,
+                },
+            ),
+            _ => match releases(name) {
+                Ok(releases) => summary.push_with_policy(name, configured, policy, &releases),
+                Err(e) => summary.push(
+                    name,
+                    UpdateOutcome::Failed {
+                        reason: e.to_string(),
+                    },
+                ),
+            },
+        }
+    }
+}
+
+/// Hold the planned index packages to `constraints`, with `releases`
+/// looking up a package's releases. A package whose target version the
+/// constraint rules out moves to the newest release its policy and the
+/// constraint allow instead, or is held back when none is newer than its
+/// pin. A pin the constraint rules out moves to the newest release the
+/// constraint allows, even an older one, since pip would refuse to
+/// install it. Only packages needing another version are looked up.
+pub fn constrain_updates_with(
+    config: &Config,
+    summary: &mut UpdateSummary,
+    constraints: &Constraints,
+    mut releases: impl FnMut(&str) -> Result<Releases, PpmError>,
+) {
+    if constraints.is_empty() {
+        return;
+    }
+    for pkg in summary.packages.iter_mut() {
+        let Some(spec) = config
+            .packages
+            .get(&pkg.name)
+            .filter(|spec| spec.is_index())
+        else {
+            continue;
+        };
+        let (Some(configured), Some(constraint)) = (spec.version(), constraints.get(&pkg.name))
+        else {
+            continue;
+        };
+        let target = match &pkg.outcome {
+            UpdateOutcome::Updated { to, .. } => to.clone(),
+            UpdateOutcome::UpToDate { version } => version.clone(),
+            UpdateOutcome::Held { version, .. } if spec.update_policy() != UpdatePolicy::Pin => {
+                version.clone()
+            }
+            _ => continue,
+        };
+        if constraint.allows(&target) && constraint.allows(configured) {
+            continue;
+        }
+        let allowed: Vec<String> = match releases(&pkg.name) {
+            Ok(releases) => releases
+                .versions
+                .into_iter()
+                .filter(|version| !is_prerelease(version) && constraint.allows(version))
+                .collect(),
+            Err(e) => {
+                pkg.outcome = UpdateOutcome::Failed {
+                    reason: e.to_string(),
+                };
+                continue;
+            }
+        };
+        let newest = newest_allowed(spec.update_policy(), configured, &allowed);
+        pkg.outcome = match newest {
+            Some(to) => UpdateOutcome::Updated {
+                from: configured.to_string(),
+                to,
+            },
+            None if constraint.allows(configured) => UpdateOutcome::Constrained {
+                version: configured.to_string(),
+                latest: target,
+                constraint: constraint.to_string(),
+            },
+            None => match allowed.iter().max_by(|a, b| compare_versions(a, b)) {
+                Some(to) => UpdateOutcome::Updated {
+                    from: configured.to_string(),
+                    to: to.clone(),
+                },
+                None => UpdateOutcome::Failed {
+                    reason: format!("no release satisfies the constraint {}", constraint),
+                },
+            },
+        };
+    }
+}
+
+/// Re-resolve the branch or tag of each git package in `targets` with
+/// `resolve` (repository and ref to commit), recording the new commit as
+/// an update of its `rev`. Git packages without a branch or tag are left
+/// out; see [`GitSource::tracked_ref`].
+pub fn plan_git_refresh_with(
+    config: &Config,
+    targets: &[String],
+    summary: &mut UpdateSummary,
+    mut resolve: impl FnMut(&GitSource, &str) -> Result<String, PpmError>,
+) {
+    for name in targets {
+        let git = match config.packages.get(name) {
+            Some(PackageSpec::Git(git)) => git,
+            _ => continue,
+        };
+        let Some(reference) = git.tracked_ref() else {
+            continue;
+        };
+        match resolve(git, reference) {
+            Ok(commit) => summary.push_resolved(name, git.rev.as_deref().unwrap_or("-"), &commit),
+            Err(e) => summary.push(
+                name,
+                UpdateOutcome::Failed {
+                    reason: e.to_string(),
+                },
+            ),
+        }
+    }
+}
+
+/// Plan an update of `pkg_names` (all packages when empty) against the
+/// package index. Unlike the CLI, unknown package names are an error.
+pub fn plan_updates(
+    ctx: &ProjectContext,
+    pkg_names: &[String],
+    dry_run: bool,
+) -> Result<UpdateSummary, PpmError> {
+    let selected = update_targets(&ctx.config, pkg_names)?;
+    if let Some(name) = selected.unknown.first() {
+        return Err(PpmError::Other(format!(
+            "Package '{}' not found in project.toml",
+            name
+        )));
+    }
+    let mut latest = lookup_latest(ctx, &ctx.config, &selected.targets, |_, _| {})?;
+    let mut summary = plan_updates_with(&ctx.config, &selected.targets, dry_run, |name| {
+        latest
+            .remove(name)
+            .unwrap_or_else(|| ctx.latest_version(name))
+    });
+    plan_policy_updates_with(&ctx.config, &selected.targets, &mut summary, |name| {
+        ctx.releases(name)
+    });
+    constrain_updates_with(&ctx.config, &mut summary, &ctx.constraints()?, |name| {
+        ctx.releases(name)
+    });
+    Ok(summary)
+}
+
+/// Latest versions of the index packages among `targets` without an
+/// update policy in `config`, looked up concurrently; `on_done` sees
+/// each result as it arrives
+pub fn lookup_latest(
+    ctx: &ProjectContext,
+    config: &Config,
+    targets: &[String],
+    on_done: impl FnMut(&str, &Result<String, PpmError>),
+) -> Result<HashMap<String, Result<String, PpmError>>, PpmError> {
+    let index: Vec<String> = targets
+        .iter()
+        .filter(|name| {
+            config.packages.get(*name).is_some_and(|spec| {
+                spec.version().is_some() && spec.update_policy() == UpdatePolicy::Latest
+            })
+        })
+        .cloned()
+        .collect();
+    let results = ctx.latest_versions(&index, on_done)?;
+    Ok(index.into_iter().zip(results).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> UpdateSummary {
+        let mut summary = UpdateSummary::new(false);
+        summary.push_resolved("requests", "2.30.0", "2.31.0");
+        summary.push_resolved("numpy", "1.26.4", "1.26.4");
+        summary.push(
+            "nope",
+            UpdateOutcome::Failed {
+                reason: "404 Not Found\nmore details".to_string(),
+            },
+        );
+        summary
+    }
+
+    #[test]
+    fn test_push_resolved_classifies() {
+        let summary = sample();
+        assert_eq!(
+            summary.updated().collect::<Vec<_>>(),
+            vec![("requests", "2.30.0", "2.31.0")]
+        );
+        assert_eq!(
+            summary.up_to_date().collect::<Vec<_>>(),
+            vec![("numpy", "1.26.4")]
+        );
+        assert_eq!(summary.failed().count(), 1);
+    }
+
+    #[test]
+    fn test_fail_updates_keeps_up_to_date() {
+        let mut summary = sample();
+        summary.fail_updates("pip exploded");
+        assert_eq!(summary.updated().count(), 0);
+        assert_eq!(summary.up_to_date().count(), 1);
+        assert_eq!(summary.failed().count(), 2);
+    }
+
+    #[test]
+    fn test_fail_update_of_one_package() {
+        let mut summary = sample();
+        summary.fail_update("requests", "pip exploded");
+        summary.fail_update("numpy", "pip exploded");
+        assert_eq!(summary.updated().count(), 0);
+        assert_eq!(summary.up_to_date().count(), 1);
+        assert_eq!(summary.failed().count(), 2);
+    }
+
+    #[test]
+    fn test_render_sections() {
+        colored::control::set_override(false);
+        let expected = "\
+Mode: within each package's update policy
+Updated (1):
+  requests  2.30.0 -> 2.31.0
+Already up to date (1):
+  numpy     1.26.4
+Failed (1):
+  nope      404 Not Found
+";
+        assert_eq!(sample().render(), expected);
+
+        let mut dry = UpdateSummary::new(true);
+        dry.push_resolved("requests", "2.30.0", "2.31.0");
+        assert!(
+            dry.render()
+                .starts_with("Mode: within each package's update policy\nWould update (1):")
+        );
+    }
+
+    fn config(packages: &[(&str, &str)]) -> Config {
+        let project = crate::settings::Project::new(
+            "demo".to_string(),
+            "0.1.0".to_string(),
+            "".to_string(),
+            "main.py".to_string(),
+            None,
+        );
+        let packages = packages
+            .iter()
+            .map(|(n, v)| (n.to_string(), PackageSpec::from(*v)))
+            .collect();
+        Config::new(project, packages, Default::default())
+    }
+
+    #[test]
+    fn test_update_targets() {
+        let conf = config(&[("requests", "2.30.0"), ("flask", "3.0.0")]);
+        let all = update_targets(&conf, &[]).unwrap();
+        assert_eq!(all.targets, vec!["flask", "requests"]);
+
+        let some = update_targets(&conf, &["requests".to_string(), "nope".to_string()]).unwrap();
+        assert_eq!(some.targets, vec!["requests"]);
+        assert_eq!(some.unknown, vec!["nope"]);
+
+        assert!(update_targets(&conf, &["nope".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_plan_updates_with_fake_resolver() {
+        let mut conf = config(&[("requests", "2.30.0"), ("flask", "3.0.0"), ("gone", "1.0")]);
+        let targets = update_targets(&conf, &[]).unwrap().targets;
+        let summary = plan_updates_with(&conf, &targets, false, |name| match name {
+            "requests" => Ok("2.31.0".to_string()),
+            "flask" => Ok("3.0.0".to_string()),
+            _ => Err(PpmError::PackageNotFound(name.to_string())),
+        });
+
+        assert_eq!(
+            summary.updated().collect::<Vec<_>>(),
+            vec![("requests", "2.30.0", "2.31.0")]
+        );
+        assert_eq!(
+            summary.up_to_date().collect::<Vec<_>>(),
+            vec![("flask", "3.0.0")]
+        );
+        assert_eq!(summary.failed().count(), 1);
+        assert_eq!(
+            summary.install_specs(&conf, None, true),
+            vec!["flask==3.0.0", "requests==2.31.0"]
+        );
+        assert_eq!(
+            summary.install_specs(&conf, None, false),
+            vec!["requests==2.31.0"]
+        );
+        let installed = HashMap::from([("requests".to_string(), "2.30.0".to_string())]);
+        assert_eq!(
+            summary.install_specs(&conf, Some(&installed), false),
+            vec!["flask==3.0.0", "requests==2.31.0"]
+        );
+
+        summary.apply(&mut conf);
+        assert_eq!(conf.packages["requests"].version(), Some("2.31.0"));
+        assert_eq!(conf.packages["gone"].version(), Some("1.0"));
+    }
+
+    #[test]
+    fn test_needs_install() {
+        // Already there at the latest version
+        assert!(!needs_install("2.31.0", Some("2.31.0"), "2.31.0"));
+        assert!(!needs_install("2.31", Some("2.31.0"), "2.31"));
+        // A newer version, or the venv has another one or none
+        assert!(needs_install("2.30.0", Some("2.30.0"), "2.31.0"));
+        assert!(needs_install("2.31.0", Some("2.30.0"), "2.31.0"));
+        assert!(needs_install("2.31.0", None, "2.31.0"));
+    }
+
+    #[test]
+    fn test_apply_keeps_markers() {
+        let mut conf = config(&[("pywin32", "305; sys_platform == 'win32'")]);
+        let mut summary = UpdateSummary::new(false);
+        summary.push_resolved("pywin32", "305", "306");
+        summary.apply(&mut conf);
+        assert_eq!(
+            conf.packages["pywin32"],
+            PackageSpec::from("306; sys_platform == 'win32'")
+        );
+    }
+
+    #[test]
+    fn test_direct_references_are_not_resolved() {
+        let mut conf = config(&[("requests", "2.30.0")]);
+        conf.packages.insert(
+            "lib".to_string(),
+            PackageSpec::direct("git+https://github.com/org/lib@abc123", None),
+        );
+        conf.packages.insert(
+            "tracked".to_string(),
+            PackageSpec::Git(GitSource {
+                git: "https://github.com/org/tracked".to_string(),
+                rev: Some("1111111".to_string()),
+                branch: Some("main".to_string()),
+                tag: None,
+                marker: None,
+            }),
+        );
+        let targets = update_targets(&conf, &[]).unwrap().targets;
+
+        let mut summary = plan_updates_with(&conf, &targets, false, |name| {
+            assert_eq!(name, "requests", "only index packages are looked up");
+            Ok("2.31.0".to_string())
+        });
+        plan_git_refresh_with(&conf, &targets, &mut summary, |git, reference| {
+            assert_eq!(
+                (git.git.as_str(), reference),
+                ("https://github.com/org/tracked", "main")
+            );
+            Ok("2222222".to_string())
+        });
+        assert_eq!(
+            summary.updated().collect::<Vec<_>>(),
+            vec![
+                ("requests", "2.30.0", "2.31.0"),
+                ("tracked", "1111111", "2222222")
+            ]
+        );
+        assert_eq!(
+            summary.install_specs(&conf, None, false),
+            vec![
+                "requests==2.31.0",
+                "tracked @ git+https://github.com/org/tracked@2222222"
+            ]
+        );
+
+        summary.apply(&mut conf);
+        match &conf.packages["tracked"] {
+            PackageSpec::Git(git) => {
+                assert_eq!(git.rev.as_deref(), Some("2222222"));
+                assert_eq!(git.branch.as_deref(), Some("main"));
+            }
+            other => panic!("expected a git package, got {:?}", other),
+        }
+    }
+
+    fn with_policy(conf: &mut Config, name: &str, version: &str, policy: UpdatePolicy) {
+        conf.packages.insert(
+            name.to_string(),
+            PackageSpec::Table(crate::settings::PackageTable {
+                version: version.to_string(),
+                marker: None,
+                update: Some(policy),
+            }),
+        );
+    }
+
+    #[test]
+    fn test_newest_allowed_by_policy() {
+        let versions: Vec<String> = [
+            "4.1.13",
+            "4.2.0",
+            "4.2.1",
+            "4.2.11",
+            "4.2.11.post1",
+            "4.2.12rc1",
+            "5.0",
+            "5.0.6",
+            "5.0.6.post2",
+            "5.1a1",
+        ]
+        .iter()
+        .map(|v| v.to_string())
+        .collect();
+        let newest = |policy, current| newest_allowed(policy, current, &versions);
+        assert_eq!(newest(UpdatePolicy::Pin, "4.2.1"), None);
+        // Post-releases are final and newer than the release they follow
+        assert_eq!(
+            newest(UpdatePolicy::Patch, "4.2.1").as_deref(),
+            Some("4.2.11.post1")
+        );
+        assert_eq!(
+            newest(UpdatePolicy::Minor, "4.1.13").as_deref(),
+            Some("4.2.11.post1")
+        );
+        assert_eq!(
+            newest(UpdatePolicy::Latest, "4.2.1").as_deref(),
+            Some("5.0.6.post2")
+        );
+        assert_eq!(
+            newest(UpdatePolicy::Patch, "4.2.11").as_deref(),
+            Some("4.2.11.post1")
+        );
+        assert_eq!(newest(UpdatePolicy::Patch, "4.2.11.post1"), None);
+        assert_eq!(newest(UpdatePolicy::Latest, "5.0.6.post2"), None);
+    }
+
+    #[test]
+    fn test_plan_policy_updates_with_canned_releases() {
+        let mut conf = config(&[("requests", "2.30.0")]);
+        with_policy(&mut conf, "django", "4.2.1", UpdatePolicy::Minor);
+        with_policy(&mut conf, "celery", "5.3.0", UpdatePolicy::Patch);
+        with_policy(&mut conf, "numpy", "1.26.4", UpdatePolicy::Pin);
+        let targets = update_targets(&conf, &[]).unwrap().targets;
+
+        let mut summary = plan_updates_with(&conf, &targets, false, |name| {
+            assert_eq!(
+                name, "requests",
+                "packages with a policy are planned separately"
+            );
+            Ok("2.31.0".to_string())
+        });
+        plan_policy_updates_with(&conf, &targets, &mut summary, |name| {
+            let (latest, versions): (&str, &[&str]) = match name {
+                "django" => ("5.0.6", &["4.2.1", "4.2.11", "5.0.6"]),
+                "celery" => ("5.4.0", &["5.3.0", "5.4.0"]),
+                _ => panic!("{} shouldn't be looked up", name),
+            };
+            Ok(Releases {
+                latest: latest.to_string(),
+                versions: versions.iter().map(|v| v.to_string()).collect(),
+            })
+        });
+
+        assert_eq!(
+            summary.updated().collect::<Vec<_>>(),
+            vec![
+                ("requests", "2.30.0", "2.31.0"),
+                ("django", "4.2.1", "4.2.11")
+            ]
+        );
+        assert_eq!(summary.up_to_date().count(), 0);
+        assert_eq!(
+            summary.held().collect::<Vec<_>>(),
+            vec![
+                ("celery", "5.3.0", Some("5.4.0"), UpdatePolicy::Patch),
+                ("numpy", "1.26.4", None, UpdatePolicy::Pin),
+            ]
+        );
+        assert_eq!(summary.to_json()["held"][0]["policy"], "patch");
+        assert!(summary.render().contains("Held back by policy (2)"));
+
+        summary.apply(&mut conf);
+        assert_eq!(conf.packages["django"].version(), Some("4.2.11"));
+        assert_eq!(conf.packages["django"].update_policy(), UpdatePolicy::Minor);
+    }
+
+    #[test]
+    fn test_specifiers_of_policies() {
+        assert_eq!(specifier(UpdatePolicy::Pin, "4.2.1"), "==4.2.1");
+        assert_eq!(specifier(UpdatePolicy::Patch, "4.2.1"), "~=4.2.1");
+        assert_eq!(specifier(UpdatePolicy::Patch, "4.2"), "~=4.2.0");
+        assert_eq!(specifier(UpdatePolicy::Minor, "1.4.2"), "~=1.4");
+        assert_eq!(specifier(UpdatePolicy::Minor, "2.0rc1"), "~=2.0");
+        assert_eq!(specifier(UpdatePolicy::Minor, "7"), "~=7.0");
+        assert_eq!(specifier(UpdatePolicy::Latest, "2.31.0"), ">=2.31.0");
+
+        assert!(policy_allows(UpdatePolicy::Pin, "1.0", "1.0.0"));
+        assert!(!policy_allows(UpdatePolicy::Pin, "1.0", "1.0.1"));
+        assert!(policy_allows(UpdatePolicy::Patch, "4.2.1", "4.2.11"));
+        assert!(!policy_allows(UpdatePolicy::Patch, "4.2.1", "4.3.0"));
+        assert!(policy_allows(UpdatePolicy::Minor, "1.4.2", "1.9.0"));
+        assert!(!policy_allows(UpdatePolicy::Minor, "1.4.2", "2.0.0"));
+        assert!(!policy_allows(UpdatePolicy::Minor, "1.4.2", "1.4.1"));
+        assert!(policy_allows(UpdatePolicy::Latest, "1.4.2", "9.0"));
+    }
+
+    fn plan_latest(conf: &Config, mode: UpdateMode) -> UpdateSummary {
+        let planning = mode.planning_config(conf);
+        let targets = update_targets(conf, &[]).unwrap().targets;
+        let mut summary = plan_updates_with(&planning, &targets, false, |name| match name {
+            "requests" => Ok("2.32.3".to_string()),
+            "celery" => Ok("5.4.0".to_string()),
+            "django" => Ok("5.0.6".to_string()),
+            "numpy" => Ok("2.0.0".to_string()),
+            "urllib3" => Ok("1.26.19".to_string()),
+            _ => panic!("{} shouldn't be looked up", name),
+        });
+        plan_policy_updates_with(&planning, &targets, &mut summary, |name| {
+            panic!("{} is planned as latest", name)
+        });
+        summary.mode = mode;
+        summary.plan_rewrites(conf);
+        summary
+    }
+
+    fn latest_config() -> Config {
+        let mut conf = config(&[("requests", "2.31.0")]);
+        with_policy(&mut conf, "django", "4.2.1", UpdatePolicy::Minor);
+        with_policy(&mut conf, "celery", "5.3.0", UpdatePolicy::Patch);
+        with_policy(&mut conf, "numpy", "1.26.4", UpdatePolicy::Pin);
+        // Already at the newest release its policy allows
+        with_policy(&mut conf, "urllib3", "1.26.18", UpdatePolicy::Minor);
+        conf
+    }
+
+    #[test]
+    fn test_latest_mode_compatible_moves_the_range() {
+        let mut conf = latest_config();
+        let mode = UpdateMode::Latest {
+            force: false,
+            style: PinStyle::Compatible,
+        };
+        let summary = plan_latest(&conf, mode);
+
+        assert_eq!(
+            summary.updated().collect::<Vec<_>>(),
+            vec![
+                ("celery", "5.3.0", "5.4.0"),
+                ("django", "4.2.1", "5.0.6"),
+                ("requests", "2.31.0", "2.32.3"),
+                ("urllib3", "1.26.18", "1.26.19"),
+            ]
+        );
+        // A pin stays put without --force
+        assert_eq!(
+            summary.held().collect::<Vec<_>>(),
+            vec![("numpy", "1.26.4", None, UpdatePolicy::Pin)]
+        );
+        // Only the packages that left their range are rewritten
+        assert_eq!(
+            summary.rewritten().collect::<Vec<_>>(),
+            vec![
+                ("celery", "~=5.3.0".to_string(), "~=5.4.0".to_string()),
+                ("django", "~=4.2".to_string(), "~=5.0".to_string()),
+            ]
+        );
+
+        colored::control::set_override(false);
+        let rendered = summary.render();
+        assert!(rendered.starts_with(
+            "Mode: latest, past patch and minor update policies (--pin-style compatible)\n"
+        ));
+        assert!(rendered.contains(
+            "Specifiers rewritten (2):\n  celery    ~=5.3.0 -> ~=5.4.0\n  django    ~=4.2 -> ~=5.0\n"
+        ));
+        let json = summary.to_json();
+        assert_eq!(json["mode"]["name"], "latest");
+        assert_eq!(json["mode"]["pin_style"], "compatible");
+        assert_eq!(json["rewritten"][1]["to"], "~=5.0");
+
+        summary.apply(&mut conf);
+        assert_eq!(conf.packages["django"].version(), Some("5.0.6"));
+        assert_eq!(conf.packages["django"].update_policy(), UpdatePolicy::Minor);
+        assert_eq!(conf.packages["celery"].update_policy(), UpdatePolicy::Patch);
+        assert_eq!(conf.packages["requests"], PackageSpec::from("2.32.3"));
+    }
+
+    #[test]
+    fn test_latest_mode_exact_pins_and_force_moves_pins() {
+        let mut conf = latest_config();
+        let mode = UpdateMode::Latest {
+            force: true,
+            style: PinStyle::Exact,
+        };
+        let mut summary = plan_latest(&conf, mode);
+
+        assert_eq!(summary.held().count(), 0);
+        assert_eq!(
+            summary.rewritten().collect::<Vec<_>>(),
+            vec![
+                ("celery", "~=5.3.0".to_string(), "==5.4.0".to_string()),
+                ("django", "~=4.2".to_string(), "==5.0.6".to_string()),
+                ("numpy", "==1.26.4".to_string(), "==2.0.0".to_string()),
+            ]
+        );
+        colored::control::set_override(false);
+        assert!(summary.render().starts_with(
+            "Mode: latest, past every update policy including pin (--pin-style exact)\n"
+        ));
+
+        // A package whose install failed keeps its version and policy
+        summary.fail_update("celery", "pip exploded");
+        assert_eq!(summary.rewritten().count(), 2);
+        summary.apply(&mut conf);
+        assert_eq!(conf.packages["celery"].version(), Some("5.3.0"));
+        assert_eq!(conf.packages["celery"].update_policy(), UpdatePolicy::Patch);
+        assert_eq!(conf.packages["django"].version(), Some("5.0.6"));
+        assert_eq!(conf.packages["django"].update_policy(), UpdatePolicy::Pin);
+        assert_eq!(conf.packages["numpy"].version(), Some("2.0.0"));
+        assert_eq!(conf.packages["numpy"].update_policy(), UpdatePolicy::Pin);
+        // Within its range, a minor policy is left alone
+        assert_eq!(
+            conf.packages["urllib3"].update_policy(),
+            UpdatePolicy::Minor
+        );
+        // Latest-policy entries have no range to rewrite
+        assert_eq!(conf.packages["requests"], PackageSpec::from("2.32.3"));
+    }
+
+    #[test]
+    fn test_policy_mode_rewrites_nothing() {
+        let conf = latest_config();
+        let planning = UpdateMode::Policy.planning_config(&conf);
+        assert_eq!(planning, conf);
+        let mut summary = UpdateSummary::new(false);
+        summary.push_resolved("django", "4.2.1", "5.0.6");
+        summary.plan_rewrites(&conf);
+        assert!(summary.rewrites.is_empty());
+    }
+
+    #[test]
+    fn test_to_json() {
+        let json = sample().to_json();
+        assert_eq!(json["dry_run"], false);
+        assert_eq!(json["mode"]["name"], "policy");
+        assert_eq!(json["updated"][0]["name"], "requests");
+        assert_eq!(json["updated"][0]["to"], "2.31.0");
+        assert_eq!(json["up_to_date"][0]["version"], "1.26.4");
+        assert_eq!(json["failed"][0]["reason"], "404 Not Found\nmore details");
+    }
+
+    #[test]
+    fn test_changelog_links() {
+        let conf = config(&[("requests", "2.30.0"), ("numpy", "1.26.4")]);
+        let mut summary = sample();
+        summary.push_resolved("nope", "1.0", "2.0");
+        let names = summary.updated_index_packages(&conf);
+        assert_eq!(names, ["requests"]);
+        summary.set_changelogs(
+            &names,
+            vec![Some("https://github.com/psf/requests/releases".to_string())],
+        );
+
+        colored::control::set_override(false);
+        assert!(summary.render().starts_with(
+            "Mode: within each package's update policy\nUpdated (2):\n  requests  2.30.0 -> 2.31.0\n            https://github.com/psf/requests/releases\n  nope      1.0 -> 2.0\n"
+        ));
+        let json = summary.to_json();
+        assert_eq!(
+            json["updated"][0]["changelog_url"],
+            "https://github.com/psf/requests/releases"
+        );
+        assert!(json["updated"][1]["changelog_url"].is_null());
+    }
+
+    #[test]
+    fn test_to_markdown_matches_golden_file() {
+        let mut summary = sample();
+        summary.push_resolved("django", "4.2.1", "5.0.6");
+        summary.set_changelogs(
+            &["requests".to_string(), "django".to_string()],
+            vec![
+                Some("https://github.com/psf/requests/releases".to_string()),
+                Some("https://docs.djangoproject.com/en/5.0/releases/ (notes)".to_string()),
+            ],
+        );
+        summary.ages.insert("requests".to_string(), 400);
+        assert_eq!(
+            summary.to_markdown(),
+            include_str!("../tests/fixtures/markdown/update.md")
+        );
+        assert_eq!(UpdateSummary::new(true).to_markdown(), "No updates\n");
+    }
+
+    #[test]
+    fn test_constrain_updates_with_parsed_constraints() {
+        let mut conf = config(&[
+            ("django", "4.2.1"),
+            ("requests", "2.31.0"),
+            ("numpy", "1.26.4"),
+            ("urllib3", "2.2.0"),
+            ("idna", "3.6"),
+        ]);
+        with_policy(&mut conf, "celery", "5.3.0", UpdatePolicy::Minor);
+        with_policy(&mut conf, "click", "8.1.7", UpdatePolicy::Pin);
+        let constraints = Constraints::parse(
+            "\
+Django<5.0
+requests<=2.31.0
+celery~=5.3.0
+click<8.0
+urllib3<2
+idna>=3.6,!=3.7
+",
+        )
+        .unwrap();
+
+        let mut summary = UpdateSummary::new(true);
+        summary.push_resolved("django", "4.2.1", "5.0.6");
+        summary.push_resolved("requests", "2.31.0", "2.32.3");
+        summary.push_resolved("numpy", "1.26.4", "2.0.0");
+        summary.push_resolved("urllib3", "2.2.0", "2.2.0");
+        summary.push_resolved("idna", "3.6", "3.7");
+        summary.push(
+            "celery",
+            UpdateOutcome::Held {
+                version: "5.3.0".to_string(),
+                latest: Some("5.4.0".to_string()),
+                policy: UpdatePolicy::Minor,
+            },
+        );
+        summary.push(
+            "click",
+            UpdateOutcome::Held {
+                version: "8.1.7".to_string(),
+                latest: None,
+                policy: UpdatePolicy::Pin,
+            },
+        );
+
+        let mut looked_up = vec![];
+        constrain_updates_with(&conf, &mut summary, &constraints, |name| {
+            looked_up.push(name.to_string());
+            let versions: &[&str] = match name {
+                "django" => &["4.2.1", "4.2.11", "5.0.6", "5.1a1"],
+                "requests" => &["2.30.0", "2.31.0", "2.32.3"],
+                "urllib3" => &["1.26.18", "1.26.19rc1", "2.2.0"],
+                "idna" => &["3.6", "3.7"],
+                _ => panic!("{} shouldn't be looked up", name),
+            };
+            Ok(Releases {
+                latest: versions.last().unwrap().to_string(),
+                versions: versions.iter().map(|v| v.to_string()).collect(),
+            })
+        });
+        // Unconstrained packages, targets the constraint allows and pins
+        // aren't looked up
+        assert_eq!(looked_up, ["django", "requests", "urllib3", "idna"]);
+
+        assert_eq!(
+            summary.updated().collect::<Vec<_>>(),
+            vec![
+                ("django", "4.2.1", "4.2.11"),
+                ("numpy", "1.26.4", "2.0.0"),
+                ("urllib3", "2.2.0", "1.26.18"),
+            ]
+        );
+        assert_eq!(
+            summary.constrained().collect::<Vec<_>>(),
+            vec![
+                ("requests", "2.31.0", "2.32.3", "requests<=2.31.0"),
+                ("idna", "3.6", "3.7", "idna>=3.6,!=3.7"),
+            ]
+        );
+        assert_eq!(summary.up_to_date().count(), 0);
+        assert_eq!(summary.held().count(), 2);
+
+        colored::control::set_override(false);
+        assert!(summary.render().contains(
+            "Held back by constraints (2):\n  requests  2.31.0 (latest 2.32.3, constraint requests<=2.31.0)\n"
+        ));
+        let json = summary.to_json();
+        assert_eq!(json["constrained"][0]["constraint"], "requests<=2.31.0");
+        assert_eq!(json["constrained"][1]["latest"], "3.7");
+
+        // Nothing newer to move to, and a failing lookup
+        let constraints = Constraints::parse("django>=9\nrequests<2\n").unwrap();
+        let mut summary = UpdateSummary::new(true);
+        summary.push_resolved("django", "4.2.1", "5.0.6");
+        summary.push_resolved("requests", "2.31.0", "2.31.0");
+        constrain_updates_with(&conf, &mut summary, &constraints, |name| match name {
+            "django" => Ok(Releases {
+                latest: "5.0.6".to_string(),
+                versions: vec!["4.2.1".to_string(), "5.0.6".to_string()],
+            }),
+            _ => Err(PpmError::Network("offline".to_string())),
+        });
+        assert_eq!(
+            summary.failed().collect::<Vec<_>>(),
+            vec![
+                ("django", "no release satisfies the constraint django>=9"),
+                ("requests", "offline"),
+            ]
+        );
+    }
+}
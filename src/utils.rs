@@ -1,69 +1,257 @@
+use crate::progress::{Phase, Progress};
+use colored::Colorize;
+use ppmm::ProjectContext;
+use ppmm::activate::VenvEnv;
+use ppmm::error::PpmError;
+use ppmm::graph::{self, DependencyGraph};
+use ppmm::history::{self, Entry};
+use ppmm::install::{self, InstallEvent, InstallReport, InstallTask};
+use ppmm::interrupt;
+use ppmm::lock::{self, ProjectLock};
+use ppmm::packages::normalize_pkg_name;
+use ppmm::paths;
+use ppmm::pip_config::{Key, Setting, Source};
+use ppmm::prompt::{LinePrompter, Prompter};
+use ppmm::requirement::Requirement;
+use ppmm::resolver::LOCK_FILE;
+use ppmm::runner::{self, CommandRunner, RunOptions};
+use ppmm::scripts::{self, Script};
+use ppmm::settings::{PROJECT_CONFIG_FILE, PackageSpec, WindowsShell};
+use ppmm::state::StateDir;
+use ppmm::theme::Theme;
+use ppmm::venv::{self, InterpreterCheck, VenvRecord};
 use std::{
+    collections::HashMap,
+    ffi::OsString,
     io::{self, Write, IsTerminal},
-    path::Path,
+    path::{Path, PathBuf},
     process::Command,
+    sync::OnceLock,
+    time::Duration,
+    sync::atomic::{AtomicBool, Ordering},
 };
 
 
 // Constants
-const PROJECT_CONFIG_FILE: &str = "project.toml";
-const REQUIREMENTS_FILE: &str = "requirements.txt";
-const PYPI_API_URL: &str = "https://pypi.org/pypi";
+pub fn get_project_config_file() -> &'static str {
+    PROJECT_CONFIG_FILE
+}
 
-// Cross-platform path helpers
-#[cfg(target_os = "windows")]
-const PYTHON_EXE: &str = "python.exe";
-#[cfg(not(target_os = "windows"))]
-const PYTHON_EXE: &str = "python";
+pub fn get_lock_file() -> &'static str {
+    LOCK_FILE
+}
 
-#[cfg(target_os = "windows")]
-const PIP_EXE: &str = "pip.exe";
-#[cfg(not(target_os = "windows"))]
-const PIP_EXE: &str = "pip";
+/// Lock the project against other mutating ppm processes, waiting for
+/// the current holder if there is one
+pub fn lock_project(ctx: &ProjectContext) -> Result<ProjectLock, PpmError> {
+    let timeout = lock::lock_timeout(std::env::var(lock::LOCK_TIMEOUT_ENV).ok());
+    let lock = ProjectLock::acquire_timeout(&ctx.root, timeout, |holder| match holder {
+        Some(pid) => iprint(format!(
+            "Waiting for other ppm process (pid {}) in this project to finish...",
+            pid
+        )),
+        None => iprint("Waiting for another ppm process in this project to finish...".to_string()),
+    })?;
+    if let Some(pid) = lock.stale_pid() {
+        wprint(format!(
+            "Broke a stale lock left by ppm process (pid {}), which is no longer running; check project.toml and the venv for a half-finished change",
+            pid
+        ));
+    }
+    Ok(lock)
+}
 
-#[cfg(target_os = "windows")]
-const VENV_BIN_DIR: &str = "Scripts";
-#[cfg(not(target_os = "windows"))]
-const VENV_BIN_DIR: &str = "bin";
+/// Load the project around the current directory and show its warnings
+pub fn load_project() -> Result<ProjectContext, PpmError> {
+    let mut ctx = ProjectContext::current_dir()?;
+    apply_global_flags(&mut ctx);
+    for warning in &ctx.warnings {
+        wprint(warning.clone());
+    }
+    Ok(ctx)
+}
 
-pub fn get_venv_python_path(venv_root: &str) -> String {
-    format!("./{}/{}/{}", venv_root, VENV_BIN_DIR, PYTHON_EXE)
+/// Carry `--no-probe`, `--offline` and `--timeout` over to `ctx`
+pub fn apply_global_flags(ctx: &mut ProjectContext) {
+    ctx.probe_python = !NO_PROBE.load(Ordering::Relaxed);
+    ctx.offline = OFFLINE.load(Ordering::Relaxed);
+    ctx.apply_network_flags(&network_flags());
 }
 
-pub fn get_venv_pip_path(venv_root: &str) -> String {
-    format!("./{}/{}/{}", venv_root, VENV_BIN_DIR, PIP_EXE)
+/// Save project.toml, telling the user when the command's changes raised
+/// its `ppm-version`; `prompter` settles conflicts with outside edits
+pub fn save_project(
+    ctx: &mut ProjectContext,
+    prompter: Option<&dyn Prompter>,
+) -> Result<(), PpmError> {
+    if let Some(raise) = ctx.raise_ppm_version() {
+        iprint(raise.to_string());
+    }
+    match prompter {
+        Some(prompter) => ctx.save_with_prompter(prompter),
+        None => ctx.save(),
+    }
 }
 
-pub fn get_venv_bin_dir(venv_root: &str) -> String {
-    format!("./{}/{}/", venv_root, VENV_BIN_DIR)
+/// The project as it was before a command, to log what the command changed
+pub struct HistoryRecorder {
+    root: PathBuf,
+    before: Option<ProjectContext>,
+    venv_existed: bool,
 }
 
-pub fn get_project_config_file() -> &'static str {
-    PROJECT_CONFIG_FILE
+impl HistoryRecorder {
+    /// Snapshot the project at `root`, which may not exist yet
+    pub fn start(root: PathBuf) -> HistoryRecorder {
+        let before = ProjectContext::load(&root).ok();
+        let venv_existed = before.as_ref().is_some_and(check_venv_dir_exists);
+        HistoryRecorder {
+            root,
+            before,
+            venv_existed,
+        }
+    }
+
+    /// Append the command's entry to the project's history. A failure to
+    /// write it is only a warning: the command itself already happened.
+    pub fn finish(self, result: &Result<(), PpmError>) {
+        // Nothing to log when the command didn't leave a project behind
+        let Ok(after) = ProjectContext::load(&self.root) else {
+            return;
+        };
+        let command: Vec<String> = std::env::args().skip(1).collect();
+        let changes = history::diff(self.before.as_ref().map(|ctx| &ctx.config), &after.config);
+        let mut entry = Entry::new(
+            &command.join(" "),
+            changes,
+            result.as_ref().err().map(|e| e.to_string()),
+        );
+        entry.venv_created = !self.venv_existed && check_venv_dir_exists(&after);
+        if let Err(e) = history::append(&after.state(), &entry) {
+            wprint(format!("Could not write to the history log: {}", e));
+        }
+    }
+}
+
+static NO_PROBE: AtomicBool = AtomicBool::new(false);
+
+/// Never run the venv interpreter just to learn its version (`--no-probe`)
+pub fn set_no_probe(enabled: bool) {
+    NO_PROBE.store(enabled, Ordering::Relaxed);
+}
+
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Answer release-history checks from the cache only (`--offline`)
+pub fn set_offline(enabled: bool) {
+    OFFLINE.store(enabled, Ordering::Relaxed);
+}
+
+static TIMEOUT: OnceLock<Duration> = OnceLock::new();
+
+/// Time each request to the package index may take (`--timeout`)
+pub fn set_timeout(timeout: Option<Duration>) {
+    if let Some(timeout) = timeout {
+        let _ = TIMEOUT.set(timeout);
+    }
+}
+
+/// The network settings given as flags, like `--timeout`, for
+/// [`NetworkSettings::with_flags`](ppmm::pip_config::NetworkSettings::with_flags)
+pub fn network_flags() -> Vec<(Key, Setting)> {
+    TIMEOUT
+        .get()
+        .map(|timeout| {
+            (
+                Key::Timeout,
+                Setting {
+                    value: timeout.as_secs_f64().to_string(),
+                    source: Source::Flag("--timeout".to_string()),
+                },
+            )
+        })
+        .into_iter()
+        .collect()
+}
+
+static SYNC_REQUIREMENTS: AtomicBool = AtomicBool::new(false);
+
+/// Regenerate the requirements file after package changes even when the
+/// project doesn't set `sync-requirements` (`--sync-requirements`)
+pub fn set_sync_requirements(enabled: bool) {
+    SYNC_REQUIREMENTS.store(enabled, Ordering::Relaxed);
 }
 
-pub fn get_requirements_file() -> &'static str {
-    REQUIREMENTS_FILE
+pub fn sync_requirements_forced() -> bool {
+    SYNC_REQUIREMENTS.load(Ordering::Relaxed)
+}
+
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// In JSON mode stdout is reserved for data, so messages move to stderr
+pub fn set_json_output(enabled: bool) {
+    JSON_OUTPUT.store(enabled, Ordering::Relaxed);
+}
+
+pub fn json_output() -> bool {
+    JSON_OUTPUT.load(Ordering::Relaxed)
+}
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// Style output with `theme` for the rest of the process; only the first
+/// call counts. Called from `main` with the global config's theme.
+pub fn set_theme(theme: Theme) {
+    let _ = THEME.set(theme);
+}
+
+/// The theme output is styled with, the default one until [`set_theme`]
+pub fn theme() -> &'static Theme {
+    THEME.get_or_init(Theme::default)
+}
+
+static STDOUT_RESERVED: AtomicBool = AtomicBool::new(false);
+
+/// Output meant to be piped, like `--format markdown`, keeps stdout to
+/// itself the way JSON mode does
+pub fn reserve_stdout() {
+    STDOUT_RESERVED.store(true, Ordering::Relaxed);
+}
+
+/// Print human-oriented text (messages, pip output) honoring JSON mode
+pub fn print_human(text: impl std::fmt::Display) {
+    if json_output() || STDOUT_RESERVED.load(Ordering::Relaxed) {
+        eprintln!("{}", text);
+    } else {
+        println!("{}", text);
+    }
 }
 
 pub fn eprint(msg: String) {
-    println!("{} {}", "error:".bright_red().bold(), msg.bright_red());
+    let theme = theme();
+    print_human(format!(
+        "{} {}",
+        theme.strong(theme.error("error:")),
+        theme.error(&msg)
+    ));
 }
 
 pub fn wprint(msg: String) {
-    println!(
+    let theme = theme();
+    print_human(format!(
         "{} {}",
-        "warning:".bright_yellow().bold(),
-        msg.bright_yellow()
-    );
+        theme.strong(theme.warn("warning:")),
+        theme.warn(&msg)
+    ));
 }
 
 pub fn iprint(msg: String) {
-    println!(
+    let theme = theme();
+    print_human(format!(
         "{} {}",
-        "•".bright_green().bold(),
-        msg.bright_green().bold()
-    );
+        theme.strong(theme.info(&theme.bullet)),
+        theme.strong(theme.info(&msg))
+    ));
 }
 
 pub fn project_exists(name: &String, is_init: bool) -> bool {
@@ -71,164 +259,448 @@ pub fn project_exists(name: &String, is_init: bool) -> bool {
         Path::new(get_project_config_file()).exists()
     } else {
         Path::new(name).exists()
-        && Path::new(&format!("{}/{}", name, get_project_config_file())).exists()
+        && Path::new(name).join(get_project_config_file()).exists()
     }
 }
 
-pub fn check_venv_dir_exists(venv_root: &str) -> bool {
-    Path::new(&get_venv_bin_dir(venv_root)).exists()
+/// Whether the venv exists and is in use, see
+/// [`ProjectContext::system_python`]
+pub fn check_venv_dir_exists(ctx: &ProjectContext) -> bool {
+    !ctx.system_python && ctx.venv_bin_dir().exists()
 }
 
-pub fn get_pkg_version(pkg: &str) -> Result<String, String> {
-    let url = format!("{}/{}/json", PYPI_API_URL, pkg);
-    let resp = reqwest::blocking::get(&url)
-        .map_err(|e| format!("Failed to retrieve package version: {}", e))?;
+/// Parse `pip list --format=json` output into names and versions, as pip
+/// spells them
+pub fn parse_pip_list_entries(json: &str) -> Result<Vec<(String, String)>, String> {
+    let entries: Vec<serde_json::Value> =
+        serde_json::from_str(json).map_err(|e| format!("Failed to parse pip list output: {}", e))?;
+
+    Ok(entries
+        .iter()
+        .filter_map(|entry| {
+            let name = entry["name"].as_str()?;
+            let version = entry["version"].as_str()?;
+            Some((name.to_string(), version.to_string()))
+        })
+        .collect())
+}
 
-    let json: serde_json::Value = resp
-        .json()
-        .map_err(|e| format!("Failed to parse JSON response: {}", e))?;
+/// Parse `pip list --format=json` output into normalized name -> version
+pub fn parse_pip_list(json: &str) -> Result<HashMap<String, String>, String> {
+    Ok(parse_pip_list_entries(json)?
+        .into_iter()
+        .map(|(name, version)| (normalize_pkg_name(&name), version))
+        .collect())
+}
 
-    let version = json["info"]["version"]
-        .as_str()
-        .ok_or_else(|| "Version field not found in response".to_string())?;
+/// Distributions installed in the venv, keyed by normalized name
+pub fn get_installed_packages(ctx: &ProjectContext) -> Result<HashMap<String, String>, String> {
+    parse_pip_list(&pip_list(ctx)?)
+}
 
-    Ok(version.to_string())
+/// Distributions installed in the venv, names and versions as pip lists
+/// them
+pub fn get_installed_distributions(ctx: &ProjectContext) -> Result<Vec<(String, String)>, String> {
+    parse_pip_list_entries(&pip_list(ctx)?)
 }
 
-pub fn setup_venv(venv_path: String) -> Result<(), String> {
-    iprint("Setting Up Virtual Environment...".to_string());
-    let venv = Command::new("python")
-        .arg("-m")
-        .arg("venv")
-        .arg(&venv_path)
-        .output()
-        .map_err(|e| format!("Failed to execute python command: {}", e))?;
-
-    if !venv.status.success() {
+/// The output of `pip list --format=json` in the venv
+fn pip_list(ctx: &ProjectContext) -> Result<String, String> {
+    ctx.require_venv().map_err(|e| e.to_string())?;
+
+    let output = ctx
+        .runner
+        .run(
+            ctx.venv_pip().as_os_str(),
+            &runner::args(["list", "--format=json"]),
+            &RunOptions::default(),
+        )
+        .map_err(|e| format!("Failed to execute pip: {}", e))?;
+
+    if !output.success() {
         return Err(format!(
-            "Virtual environment creation failed: {}",
-            String::from_utf8_lossy(&venv.stderr)
+            "Failed to list installed packages: {}",
+            output.stderr_lossy()
         ));
     }
+
+    Ok(output.stdout_lossy())
+}
+
+/// Create a venv at `venv_path` with the interpreter `python` from
+/// project.toml, see [`venv::interpreter_command`]
+pub fn setup_venv(
+    runner: &dyn CommandRunner,
+    venv_path: &Path,
+    python: Option<&str>,
+) -> Result<(), PpmError> {
+    iprint("Setting Up Virtual Environment...".to_string());
+    let spinner = Progress::spinner(Phase::Venv, "Creating virtual environment...");
+    let python = venv::interpreter_command(python);
+    let mut args = runner::args(&python[1..]);
+    args.extend(runner::args(["-m", "venv"]));
+    args.push(venv_path.into());
+    let venv = match runner.run(python[0].as_ref(), &args, &RunOptions::default()) {
+        Ok(venv) => venv,
+        Err(e) => {
+            let msg = format!("Failed to execute {}: {}", python.join(" "), e);
+            spinner.abandon(&msg);
+            return Err(PpmError::Venv(msg));
+        }
+    };
+
+    if !venv.success() {
+        let msg = format!(
+            "Virtual environment creation failed: {}",
+            venv.stderr_lossy().trim()
+        );
+        spinner.abandon(&msg);
+        return Err(PpmError::Venv(msg));
+    }
+    spinner.finish();
     Ok(())
 }
 
-pub fn ask_if_create_venv() -> bool {
-    // Check if stdin is a terminal
-    if !std::io::stdin().is_terminal() {
-        wprint("Non-interactive environment detected. Defaulting to 'yes' for venv creation.".to_string());
-        return true;
+/// [`setup_venv`] for the venv `venv_root` of the project at
+/// `project_root`, also recording the interpreter it was created from so
+/// later commands notice when that changes
+pub fn setup_project_venv(
+    runner: &dyn CommandRunner,
+    project_root: &Path,
+    venv_root: &str,
+    python: Option<&str>,
+) -> Result<(), PpmError> {
+    setup_venv(
+        runner,
+        &paths::get_venv_dir(project_root, venv_root),
+        python,
+    )?;
+    let project_root = paths::normalize(&std::path::absolute(project_root)?);
+    let record = VenvRecord::of(
+        &paths::get_venv_dir(&project_root, venv_root),
+        &paths::get_venv_python_path(&project_root, venv_root),
+    );
+    if let Some(record) = record
+        && let Err(e) = record.store(&StateDir::project(&project_root))
+    {
+        wprint(format!("Could not record the venv's interpreter: {}", e));
     }
+    Ok(())
+}
 
-    loop {
-        let mut answer = String::new();
-        print!(
-            "{}",
-            "[?] Do you want to create a virtual environment? (y/n): "
-                .green()
-                .bold()
-        );
-        
-        // Handle potential flush error safely
-        if let Err(e) = io::stdout().flush() {
-            eprint(format!("Failed to flush stdout: {}", e));
-            return false; // Default safe behavior
-        }
+/// Warn when the interpreter the venv was created from is gone or now
+/// another Python version, see [`venv::check_drift`]
+pub fn warn_interpreter_drift(ctx: &ProjectContext) {
+    let check = venv::check_drift(&ctx.state(), &ctx.venv_dir(), &ctx.venv_python(), |base| {
+        ctx.probe_python
+            .then(|| venv::probe_python_version(ctx.runner.as_ref(), base))
+            .flatten()
+    });
+    if let InterpreterCheck::Drifted(drift) = check {
+        wprint(format!("{}; run `ppm venv recreate`", drift));
+    }
+}
 
-        // Handle read_line error safely
-        if let Err(e) = io::stdin().read_line(&mut answer) {
-            eprint(format!("Failed to read line: {}", e));
-            return false;
-        }
+/// Variables activating the venv for a script, none when the project
+/// runs on the system Python
+fn venv_vars(ctx: &ProjectContext) -> Vec<(&'static str, OsString)> {
+    if ctx.system_python {
+        return vec![];
+    }
+    let current_path = std::env::var_os("PATH").unwrap_or_default();
+    VenvEnv::new(ctx).vars(&current_path)
+}
 
-        match answer.trim().to_lowercase().as_str() {
-            "y" | "yes" => return true,
-            "n" | "no" => return false,
-            _ => {
-                println!("{}", "Invalid option, please type 'y' or 'n'".bright_red());
+/// A shell running `script` in the project root, with the venv's
+/// executables first on `PATH` (`cmd /C` on Windows, or PowerShell with
+/// `windows-shell = "powershell"`, and `sh -c` elsewhere)
+pub fn shell_command(ctx: &ProjectContext, script: &str) -> Result<Command, PpmError> {
+    let mut cmd = if cfg!(target_os = "windows") {
+        match ctx.config.project.windows_shell.unwrap_or_default() {
+            WindowsShell::Cmd => {
+                let mut c = Command::new("cmd");
+                c.arg("/C");
+                c
+            }
+            WindowsShell::Powershell => {
+                let mut c = Command::new("powershell");
+                c.args(["-NoProfile", "-Command"]);
+                c
             }
         }
+    } else if cfg!(target_os = "linux") || cfg!(target_os = "macos") {
+        let mut c = Command::new("sh");
+        c.arg("-c");
+        c
+    } else {
+        return Err(PpmError::Other("Unsupported OS".to_owned()));
+    };
+
+    cmd.envs(venv_vars(ctx));
+    cmd.arg(script).current_dir(&ctx.root);
+    Ok(cmd)
+}
+
+/// `script` in the project root: its entry point executable, else
+/// [`shell_command`] when it runs in a shell, otherwise its command split
+/// into words by [`scripts::argv`] and run directly, with the same
+/// environment
+pub fn script_command(ctx: &ProjectContext, script: &Script) -> Result<Command, PpmError> {
+    if let Some(program) = &script.program {
+        let mut cmd = Command::new(program);
+        cmd.envs(venv_vars(ctx));
+        cmd.current_dir(&ctx.root);
+        return Ok(cmd);
+    }
+    if script.shell {
+        return shell_command(ctx, &script.command);
     }
+    let argv = scripts::argv(script)?;
+    let mut cmd = Command::new(&argv[0]);
+    cmd.envs(venv_vars(ctx));
+    cmd.args(&argv[1..]).current_dir(&ctx.root);
+    Ok(cmd)
 }
 
-pub fn parse_version(pkg: &str) -> (String, Option<String>) {
-    if let Some((name, version)) = pkg.split_once("==") {
-        (name.to_string(), Some(version.to_string()))
-    } else {
-        (pkg.to_string(), None)
+/// Prompts on the terminal. Questions go to stderr in JSON mode; without
+/// a terminal on stdin every question takes its default.
+#[derive(Debug)]
+pub struct TerminalPrompter;
+
+impl TerminalPrompter {
+    fn line_prompter(&self, default: &str) -> Option<LinePrompter<io::StdinLock<'static>, Box<dyn Write>>> {
+        if !io::stdin().is_terminal() {
+            wprint(format!(
+                "Non-interactive environment detected. Defaulting to '{}'.",
+                default
+            ));
+            return None;
+        }
+        let output: Box<dyn Write> = if json_output() {
+            Box::new(io::stderr())
+        } else {
+            Box::new(io::stdout())
+        };
+        Some(LinePrompter::new(io::stdin().lock(), output))
     }
 }
 
-fn validate_package_name(pkg: &str) -> Result<(), String> {
-    if pkg.is_empty() {
-        return Err("Package name cannot be empty".to_string());
+fn styled_question(question: &str) -> String {
+    format!("[?] {}", question).green().bold().to_string()
+}
+
+impl Prompter for TerminalPrompter {
+    fn ask_yes_no(&self, question: &str, default: bool) -> bool {
+        match self.line_prompter(if default { "yes" } else { "no" }) {
+            Some(prompter) => prompter.ask_yes_no(&styled_question(question), default),
+            None => default,
+        }
     }
-    if pkg
-        .chars()
-        .any(|c| !c.is_alphanumeric() && !"._-=<>~!".contains(c))
-    {
-        return Err(format!("Invalid package name: {}", pkg));
+
+    fn ask_string(&self, question: &str, default: Option<&str>) -> Option<String> {
+        match self.line_prompter(default.unwrap_or("")) {
+            Some(prompter) => prompter.ask_string(&styled_question(question), default),
+            None => default.map(|d| d.to_string()),
+        }
+    }
+
+    fn select(&self, question: &str, options: &[&str]) -> Option<usize> {
+        self.line_prompter("none")?
+            .select(&styled_question(question), options)
+    }
+
+    fn is_interactive(&self) -> bool {
+        io::stdin().is_terminal()
     }
-    Ok(())
 }
 
+/// Make sure the venv exists, offering to create it when missing.
+/// `Ok(false)` means the user declined. The venv's pip config is kept
+/// pointing at the configured `index-url`. Fails without asking when
+/// the project runs on the system Python.
+pub fn ensure_venv(ctx: &ProjectContext, prompter: &dyn Prompter) -> Result<bool, PpmError> {
+    ctx.check_venv_in_use()?;
+    if !check_venv_dir_exists(ctx) {
+        wprint(format!("Could not find '{}' directory", ctx.venv_root()));
+        if !prompter.ask_yes_no("Do you want to create a virtual environment?", true) {
+            return Ok(false);
+        }
+        setup_project_venv(
+            ctx.runner.as_ref(),
+            &ctx.root,
+            ctx.venv_root(),
+            ctx.shared.python.as_deref(),
+        )?;
+    } else {
+        warn_interpreter_drift(ctx);
+    }
+    venv::write_pip_config(&ctx.venv_dir(), ctx.shared.index_url.as_deref())?;
+    Ok(true)
+}
 
-pub fn install_packages_batch(pkgs: &[String], venv_root: &str) -> Result<(), String> {
-    if !check_venv_dir_exists(venv_root) {
-        return Err("Virtual Environment Not Found".to_string());
+/// Whether the configured package `name` applies to the venv; a package
+/// whose marker is false here is reported as skipped
+pub fn matches_environment(
+    ctx: &ProjectContext,
+    name: &str,
+    spec: &PackageSpec,
+) -> Result<bool, PpmError> {
+    let matches = ctx.marker_matches(spec)?;
+    if !matches {
+        iprint(format!(
+            "Skipping '{}': marker `{}` does not match this environment",
+            name,
+            spec.marker().unwrap_or_default()
+        ));
     }
+    Ok(matches)
+}
+
+/// Parse user- or file-supplied specs, failing on the first invalid one
+pub fn parse_requirements<S: AsRef<str>>(specs: &[S]) -> Result<Vec<Requirement>, PpmError> {
+    specs
+        .iter()
+        .map(|spec| Requirement::parse(spec.as_ref()).map_err(PpmError::Other))
+        .collect()
+}
+
+
+pub fn install_packages_batch(pkgs: &[Requirement], ctx: &ProjectContext) -> Result<(), PpmError> {
+    let args: Vec<Vec<String>> = pkgs.iter().map(|pkg| vec![pkg.to_string()]).collect();
+    pip_install(&args, ctx)
+}
+
+/// Install packages given as `pip install` arguments, one group per
+/// package (e.g. `["-e", "../lib"]`), from the project root so relative
+/// paths resolve against it
+pub fn pip_install(pkgs: &[Vec<String>], ctx: &ProjectContext) -> Result<(), PpmError> {
+    ctx.require_venv()?;
 
     if pkgs.is_empty() {
         return Ok(());
     }
 
-    for pkg in pkgs {
-        validate_package_name(pkg)?;
-    }
-
+    let args = pkgs.concat();
+    let constraints = ctx.constraint_args()?;
     iprint(format!("Installing {} packages...", pkgs.len()));
-    let output = Command::new(get_venv_pip_path(venv_root))
-        .arg("install")
-        .args(pkgs)
-        .output()
-        .map_err(|e| format!("Failed to execute pip: {}", e))?;
+    let spinner = Progress::spinner(Phase::Install, &format!("pip install {}", args.join(" ")));
+    let mut pip_args = runner::args(["install"]);
+    pip_args.extend(runner::args(&constraints));
+    pip_args.extend(runner::args(&args));
+    let options = RunOptions {
+        shield: true,
+        ..RunOptions::in_dir(&ctx.root)
+    };
+    let output = match ctx
+        .runner
+        .run(ctx.venv_pip().as_os_str(), &pip_args, &options)
+    {
+        Ok(output) => output,
+        Err(e) => {
+            let msg = format!("Failed to execute pip: {}", e);
+            spinner.abandon(&msg);
+            return Err(PpmError::Subprocess(msg));
+        }
+    };
 
-    if !output.status.success() {
-        return Err(format!(
-            "Failed to install packages: {}",
-            String::from_utf8_lossy(&output.stderr)
-        ));
+    if !output.success() {
+        let err = PpmError::from_pip(output.stderr_lossy(), output.code);
+        spinner.abandon(&err.to_string());
+        return Err(err);
     }
+    spinner.finish();
 
-    println!("{}", String::from_utf8_lossy(&output.stdout));
+    print_human(output.stdout_lossy());
     Ok(())
 }
 
-pub fn generate_lock_file(venv_root: &str) -> Result<(), String> {
-    if !check_venv_dir_exists(venv_root) {
-        return Err("Virtual Environment Not Found".to_string());
+/// Install `tasks` with a single pip run, or with up to `jobs` pip
+/// processes when `jobs` is more than 1. Output of concurrent runs is
+/// prefixed with the package it belongs to. Ctrl+C from here on lets the
+/// running pip processes finish, see [`interrupt`].
+pub fn install_tasks(tasks: &[InstallTask], ctx: &ProjectContext, jobs: usize) -> InstallReport {
+    interrupt::handle_ctrl_c();
+    if jobs <= 1 || tasks.len() < 2 {
+        let args: Vec<Vec<String>> = tasks.iter().map(|task| task.args.clone()).collect();
+        return match pip_install(&args, ctx) {
+            Ok(()) => InstallReport::default(),
+            Err(e) => InstallReport::all_failed(tasks, e),
+        };
+    }
+    if let Err(err) = ctx.require_venv() {
+        return InstallReport::all_failed(tasks, err);
     }
 
+    iprint(format!(
+        "Installing {} packages with up to {} pip processes...",
+        tasks.len(),
+        jobs
+    ));
+    let options = match ctx.constraint_args() {
+        Ok(options) => options,
+        Err(e) => return InstallReport::all_failed(tasks, e),
+    };
+    let progress = Progress::bar(Phase::Install, tasks.len(), "Installing");
+    let report = install::install_parallel(
+        &ctx.venv_pip(),
+        &ctx.root,
+        &options,
+        tasks,
+        jobs,
+        &interrupt::interrupted,
+        |event| match event {
+            InstallEvent::Started(name) => progress.start_concurrent_item(name),
+            InstallEvent::Output(name, line) => progress.println(format!(
+                "{} {}",
+                format!("[{}]", name.unwrap_or("dependencies")).dimmed(),
+                line
+            )),
+            InstallEvent::Finished(name, reason) => progress.finish_concurrent_item(name, reason),
+        },
+    );
+    progress.finish();
+    report
+}
+
+/// `pip install -e .` in the project root
+pub fn install_self(ctx: &ProjectContext) -> Result<(), PpmError> {
+    iprint(format!(
+        "Installing '{}' in editable mode",
+        ctx.config.project.name
+    ));
+    pip_install(&[vec!["-e".to_string(), ".".to_string()]], ctx)
+}
+
+pub fn generate_lock_file(ctx: &ProjectContext) -> Result<(), String> {
+    ctx.require_venv().map_err(|e| e.to_string())?;
+
     iprint("Generating ppmm.lock...".to_string());
-    let output = Command::new(get_venv_pip_path(venv_root))
-        .arg("freeze")
-        .output()
+    let output = ctx
+        .runner
+        .run(
+            ctx.venv_pip().as_os_str(),
+            &runner::args(["freeze"]),
+            &RunOptions::default(),
+        )
         .map_err(|e| format!("Failed to execute pip freeze: {}", e))?;
 
-    if !output.status.success() {
+    if !output.success() {
         return Err(format!(
             "Failed to generate lock file: {}",
-            String::from_utf8_lossy(&output.stderr)
+            output.stderr_lossy()
         ));
     }
 
-    let lock_content = String::from_utf8_lossy(&output.stdout);
-    let mut file = std::fs::File::create("ppmm.lock")
+    let lock_content = output.stdout_lossy();
+    let mut file = std::fs::File::create(ctx.root.join(get_lock_file()))
         .map_err(|e| format!("Failed to create ppmm.lock: {}", e))?;
     
     file.write_all(lock_content.as_bytes())
         .map_err(|e| format!("Failed to write to ppmm.lock: {}", e))?;
 
+    // Keeps `ppm rm`'s dependents check working without the venv
+    let graph = DependencyGraph::from_venv(&ctx.venv_dir(), &ctx.config);
+    let _ = graph::store(&ctx.state(), &graph);
     Ok(())
 }
 
@@ -237,40 +709,70 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_validate_package_name() {
-        assert!(validate_package_name("requests").is_ok());
-        assert!(validate_package_name("my-package").is_ok());
-        assert!(validate_package_name("my_package").is_ok());
-        assert!(validate_package_name("package123").is_ok());
-        
-        // Invalid names
-        assert!(validate_package_name("").is_err());
-        assert!(validate_package_name("pkg with spaces").is_err());
-        assert!(validate_package_name("pkg/slash").is_err());
+    fn test_setup_venv_failure_is_venv_error() {
+        let err = setup_venv(&runner::SystemRunner, Path::new("/dev/null/venv"), None).unwrap_err();
+        assert!(matches!(err, PpmError::Venv(_)));
     }
 
     #[test]
-    fn test_parse_version() {
-        assert_eq!(parse_version("requests==2.26.0"), ("requests".to_string(), Some("2.26.0".to_string())));
-        assert_eq!(parse_version("numpy"), ("numpy".to_string(), None));
+    fn test_install_without_venv_is_venv_error() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join(PROJECT_CONFIG_FILE),
+            "[project]\nname = \"demo\"\nversion = \"0.1.0\"\ndescription = \"\"\nmain_script = \"./main.py\"\n\n[packages]\n\n[scripts]\n",
+        )
+        .unwrap();
+        let ctx = ProjectContext::load(dir.path()).unwrap();
+        let reqs = parse_requirements(&["requests"]).unwrap();
+        let err = install_packages_batch(&reqs, &ctx).unwrap_err();
+        assert!(matches!(err, PpmError::Venv(_)));
     }
 
     #[test]
-    fn test_get_venv_paths() {
-        let venv_root = "test_venv";
-        
-        #[cfg(target_os = "windows")]
-        {
-            assert_eq!(get_venv_python_path(venv_root), "./test_venv/Scripts/python.exe");
-            assert_eq!(get_venv_pip_path(venv_root), "./test_venv/Scripts/pip.exe");
-            assert_eq!(get_venv_bin_dir(venv_root), "./test_venv/Scripts/");
-        }
+    fn test_system_python_refuses_to_install() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join(PROJECT_CONFIG_FILE),
+            "[project]\nname = \"demo\"\nversion = \"0.1.0\"\ndescription = \"\"\nmain_script = \"./main.py\"\nuse-venv = false\n\n[packages]\n\n[scripts]\n",
+        )
+        .unwrap();
+        let ctx = ProjectContext::load(dir.path()).unwrap();
+        assert!(ctx.system_python);
+        let none = ppmm::prompt::ScriptedPrompter::new(&[]);
+        let err = ensure_venv(&ctx, &none).unwrap_err();
+        assert!(matches!(err, PpmError::Venv(_)));
+        assert!(err.to_string().contains("use-venv = false under [project]"));
+        assert_eq!(none.transcript(), "");
+
+        let reqs = parse_requirements(&["requests"]).unwrap();
+        let err = install_packages_batch(&reqs, &ctx).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("ppm never installs packages into the system Python")
+        );
+        assert!(!dir.path().join("venv").exists());
+    }
 
-        #[cfg(not(target_os = "windows"))]
-        {
-            assert_eq!(get_venv_python_path(venv_root), "./test_venv/bin/python");
-            assert_eq!(get_venv_pip_path(venv_root), "./test_venv/bin/pip");
-            assert_eq!(get_venv_bin_dir(venv_root), "./test_venv/bin/");
-        }
+    #[test]
+    fn test_parse_requirements() {
+        let reqs = parse_requirements(&["requests==2.26.0", "numpy"]).unwrap();
+        assert_eq!(reqs[0].pinned_version(), Some("2.26.0"));
+        assert_eq!(reqs[1].name, "numpy");
+
+        let err = parse_requirements(&["requests", "--index-url=http://evil"]).unwrap_err();
+        assert!(matches!(err, PpmError::Other(_)));
+    }
+
+    #[test]
+    fn test_parse_pip_list() {
+        let json = r#"[{"name": "Flask", "version": "3.0.0"}, {"name": "typing_extensions", "version": "4.9.0"}]"#;
+        let installed = parse_pip_list(json).unwrap();
+        assert_eq!(installed.get("flask"), Some(&"3.0.0".to_string()));
+        assert_eq!(installed.get("typing-extensions"), Some(&"4.9.0".to_string()));
+        assert!(parse_pip_list("not json").is_err());
+        assert_eq!(
+            parse_pip_list_entries(json).unwrap()[1],
+            ("typing_extensions".to_string(), "4.9.0".to_string())
+        );
     }
 }
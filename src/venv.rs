@@ -0,0 +1,703 @@
+//! Health checks for an existing virtual environment.
+//!
+//! Besides checking that the venv's interpreter runs, ppm remembers the
+//! interpreter it created the venv from in `.ppm/venv.json`. An OS
+//! upgrade that removes that interpreter, or turns `python3` into another
+//! minor version, leaves a venv that keeps running old binaries until
+//! something breaks; [`check_drift`] notices it first.
+
+use crate::paths;
+use crate::runner::{self, CommandRunner, RunOptions};
+use crate::state::StateDir;
+use serde::{Deserialize, Serialize};
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Why a venv's interpreter can't be used
+#[derive(Debug, PartialEq)]
+pub enum VenvProblem {
+    /// The base Python recorded in pyvenv.cfg no longer exists
+    DeadBasePython(PathBuf),
+    /// The interpreter file is missing (or a dangling symlink)
+    MissingInterpreter(PathBuf),
+    /// The interpreter exists but can't be executed
+    NotExecutable(PathBuf),
+}
+
+impl std::fmt::Display for VenvProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VenvProblem::DeadBasePython(home) => write!(
+                f,
+                "the Python it was created from ('{}', per pyvenv.cfg) no longer exists",
+                home.display()
+            ),
+            VenvProblem::MissingInterpreter(python) => {
+                write!(f, "'{}' does not exist", python.display())
+            }
+            VenvProblem::NotExecutable(python) => {
+                write!(f, "'{}' is not executable", python.display())
+            }
+        }
+    }
+}
+
+/// The value of `key` in `venv_dir/pyvenv.cfg`
+fn cfg_value(venv_dir: &Path, key: &str) -> Option<String> {
+    let cfg = fs::read_to_string(venv_dir.join("pyvenv.cfg")).ok()?;
+    text_value(&cfg, key)
+}
+
+/// The value of `key` in the text of a pyvenv.cfg
+fn text_value(cfg: &str, key: &str) -> Option<String> {
+    cfg.lines().find_map(|line| {
+        let (name, value) = line.split_once('=')?;
+        (name.trim() == key).then(|| value.trim().to_string())
+    })
+}
+
+/// The `home` entry of `venv_dir/pyvenv.cfg`: the directory of the base
+/// interpreter the venv was created from
+pub fn base_python_home(venv_dir: &Path) -> Option<PathBuf> {
+    cfg_value(venv_dir, "home").map(PathBuf::from)
+}
+
+/// The interpreter version recorded in `venv_dir/pyvenv.cfg`, e.g.
+/// `3.11.4`. `venv` writes it as `version`, virtualenv and uv as
+/// `version_info` (`3.11.4.final.0`).
+pub fn cfg_python_version(venv_dir: &Path) -> Option<String> {
+    ["version", "version_info"]
+        .iter()
+        .find_map(|key| cfg_value(venv_dir, key).and_then(|value| release_version(&value)))
+}
+
+/// Version of `python` from `python --version`, which prints
+/// `Python 3.11.4`
+pub fn probe_python_version(runner: &dyn CommandRunner, python: &Path) -> Option<String> {
+    probe_version(runner, python.as_os_str(), runner::args(["--version"]))
+}
+
+/// Version of the interpreter a venv would be created with, run as
+/// `command` from [`interpreter_command`]
+pub fn probe_interpreter_version(runner: &dyn CommandRunner, command: &[String]) -> Option<String> {
+    let (program, args) = command.split_first()?;
+    let mut args = runner::args(args);
+    args.push("--version".into());
+    probe_version(runner, program.as_ref(), args)
+}
+
+fn probe_version(
+    runner: &dyn CommandRunner,
+    program: &OsStr,
+    args: Vec<OsString>,
+) -> Option<String> {
+    let output = runner.run(program, &args, &RunOptions::default()).ok()?;
+    if !output.success() {
+        return None;
+    }
+    // Python 2 printed its version to stderr
+    let text = match String::from_utf8_lossy(&output.stdout).trim() {
+        "" => String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        stdout => stdout.to_string(),
+    };
+    release_version(text.strip_prefix("Python ")?)
+}
+
+/// The leading `major.minor[.micro]` of a version, `None` when it doesn't
+/// start with at least two numbers
+fn release_version(version: &str) -> Option<String> {
+    let parts: Vec<&str> = version
+        .trim()
+        .split('.')
+        .take(3)
+        .take_while(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+        .collect();
+    (parts.len() >= 2).then(|| parts.join("."))
+}
+
+/// Check that `python`, the interpreter inside `venv_dir`, can be run
+pub fn check_interpreter(venv_dir: &Path, python: &Path) -> Result<(), VenvProblem> {
+    if let Some(home) = base_python_home(venv_dir)
+        && !home.exists()
+    {
+        return Err(VenvProblem::DeadBasePython(home));
+    }
+    if !python.is_file() {
+        return Err(VenvProblem::MissingInterpreter(python.to_path_buf()));
+    }
+    if !paths::is_executable(python) {
+        return Err(VenvProblem::NotExecutable(python.to_path_buf()));
+    }
+    Ok(())
+}
+
+/// The command line that runs the interpreter named by `python` in
+/// project.toml: `python` when unset, `python3.11` (`py -3.11` on
+/// Windows) for a bare version, otherwise the value itself
+pub fn interpreter_command(python: Option<&str>) -> Vec<String> {
+    let Some(python) = python.map(str::trim).filter(|python| !python.is_empty()) else {
+        return vec!["python".to_string()];
+    };
+    if release_version(python).as_deref() != Some(python) {
+        return vec![python.to_string()];
+    }
+    if cfg!(target_os = "windows") {
+        vec!["py".to_string(), format!("-{}", python)]
+    } else {
+        vec![format!("python{}", python)]
+    }
+}
+
+/// File in the project's state directory recording the interpreter the
+/// venv was created from
+pub const VENV_RECORD_FILE: &str = "venv.json";
+
+/// What a pyvenv.cfg says about the interpreter that made the venv
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PyvenvCfg {
+    /// `home`: directory of the base interpreter
+    pub home: Option<PathBuf>,
+    /// `executable`: the base interpreter itself, written by Python 3.11
+    /// and later
+    pub executable: Option<PathBuf>,
+    /// `version` (or virtualenv's `version_info`), like `3.11.4`
+    pub version: Option<String>,
+}
+
+impl PyvenvCfg {
+    /// Parse the text of a pyvenv.cfg
+    pub fn parse(text: &str) -> PyvenvCfg {
+        PyvenvCfg {
+            home: text_value(text, "home").map(PathBuf::from),
+            executable: text_value(text, "executable").map(PathBuf::from),
+            version: ["version", "version_info"]
+                .iter()
+                .find_map(|key| text_value(text, key).and_then(|value| release_version(&value))),
+        }
+    }
+
+    /// `venv_dir/pyvenv.cfg`, `None` when it can't be read
+    pub fn read(venv_dir: &Path) -> Option<PyvenvCfg> {
+        let text = fs::read_to_string(venv_dir.join("pyvenv.cfg")).ok()?;
+        Some(PyvenvCfg::parse(&text))
+    }
+}
+
+/// The interpreter a venv was created from, as ppm saw it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VenvRecord {
+    /// The venv directory
+    pub venv: PathBuf,
+    /// Version of the venv's interpreter, like `3.11.4`
+    #[serde(default)]
+    pub python_version: Option<String>,
+    /// The base interpreter, like `/usr/bin/python3`
+    pub base_python: PathBuf,
+    /// `base_python` with symlinks resolved, like `/usr/bin/python3.11`;
+    /// `None` before ppm has checked it against the venv
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolved: Option<PathBuf>,
+}
+
+impl VenvRecord {
+    /// The venv at `venv_dir`, whose interpreter is `python`, as it is
+    /// now. The base interpreter is pyvenv.cfg's `executable`, else where
+    /// the venv's interpreter links to outside the venv; `None` when
+    /// neither tells.
+    pub fn of(venv_dir: &Path, python: &Path) -> Option<VenvRecord> {
+        let cfg = PyvenvCfg::read(venv_dir)?;
+        let base_python = cfg
+            .executable
+            .clone()
+            .or_else(|| linked_interpreter(venv_dir, python))?;
+        Some(VenvRecord {
+            venv: venv_dir.to_path_buf(),
+            python_version: cfg.version,
+            resolved: fs::canonicalize(&base_python).ok(),
+            base_python,
+        })
+    }
+
+    /// The record in `state`, `None` when there is none or it can't be read
+    pub fn load(state: &StateDir) -> Option<VenvRecord> {
+        let text = fs::read_to_string(state.subpath(VENV_RECORD_FILE)).ok()?;
+        serde_json::from_str(&text).ok()
+    }
+
+    /// Write the record to `state`
+    pub fn store(&self, state: &StateDir) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(std::io::Error::other)?;
+        state.write(VENV_RECORD_FILE, json.as_bytes()).map(|_| ())
+    }
+}
+
+/// The first file outside `venv_dir` that the symlink `python` leads to
+fn linked_interpreter(venv_dir: &Path, python: &Path) -> Option<PathBuf> {
+    let mut path = python.to_path_buf();
+    // Bounded, in case of a symlink loop
+    for _ in 0..8 {
+        let target = fs::read_link(&path).ok()?;
+        path = path.parent()?.join(target);
+        if !path.starts_with(venv_dir) {
+            return Some(path);
+        }
+    }
+    None
+}
+
+/// How a venv's base interpreter has changed since the venv was created
+#[derive(Debug, Clone, PartialEq)]
+pub enum Drift {
+    /// The base interpreter no longer exists
+    BaseMissing(PathBuf),
+    /// The base interpreter is now another minor version of Python
+    VersionChanged {
+        /// The base interpreter
+        base: PathBuf,
+        /// Version the venv was created with
+        venv: String,
+        /// Version the base interpreter is now
+        now: String,
+    },
+}
+
+impl std::fmt::Display for Drift {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Drift::BaseMissing(base) => write!(
+                f,
+                "The venv was created from {}, which no longer exists",
+                base.display()
+            ),
+            Drift::VersionChanged { base, venv, now } => write!(
+                f,
+                "The venv was created with Python {} from {}, which is now Python {}",
+                venv,
+                base.display(),
+                now
+            ),
+        }
+    }
+}
+
+/// Result of comparing a venv with its base interpreter
+#[derive(Debug, Clone, PartialEq)]
+pub enum InterpreterCheck {
+    /// The base interpreter is the one the venv was created from
+    Same,
+    /// The base interpreter changed under the venv
+    Drifted(Drift),
+    /// The versions couldn't be compared
+    Unknown,
+}
+
+/// Compare the venv described by `record` and its pyvenv.cfg `cfg` with
+/// its base interpreter now. `resolve` resolves the symlinks of a path,
+/// `None` when it doesn't exist; `probe` asks an interpreter its version,
+/// and is only called when the resolved interpreter isn't the recorded
+/// one. Only the minor version counts: a patch release installed in
+/// place doesn't break a venv.
+pub fn interpreter_drift(
+    record: &VenvRecord,
+    cfg: &PyvenvCfg,
+    resolve: impl Fn(&Path) -> Option<PathBuf>,
+    probe: impl FnOnce(&Path) -> Option<String>,
+) -> InterpreterCheck {
+    let Some(resolved) = resolve(&record.base_python) else {
+        return InterpreterCheck::Drifted(Drift::BaseMissing(record.base_python.clone()));
+    };
+    if record.resolved.as_ref() == Some(&resolved) {
+        return InterpreterCheck::Same;
+    }
+    let (Some(venv), Some(now)) = (
+        cfg.version.as_ref().or(record.python_version.as_ref()),
+        probe(&record.base_python),
+    ) else {
+        return InterpreterCheck::Unknown;
+    };
+    let minor = |version: &str| version.split('.').take(2).collect::<Vec<_>>().join(".");
+    if minor(venv) == minor(&now) {
+        InterpreterCheck::Same
+    } else {
+        InterpreterCheck::Drifted(Drift::VersionChanged {
+            base: record.base_python.clone(),
+            venv: venv.clone(),
+            now,
+        })
+    }
+}
+
+/// Check the venv at `venv_dir` against the interpreter it was created
+/// from, see [`interpreter_drift`]. A venv whose base interpreter checks
+/// out is recorded in `state` (again, when it now resolves elsewhere), so
+/// later checks only look at the file system.
+pub fn check_drift(
+    state: &StateDir,
+    venv_dir: &Path,
+    python: &Path,
+    probe: impl FnOnce(&Path) -> Option<String>,
+) -> InterpreterCheck {
+    let Some(cfg) = PyvenvCfg::read(venv_dir) else {
+        return InterpreterCheck::Unknown;
+    };
+    let stored = VenvRecord::load(state)
+        .filter(|record| paths::normalize(&record.venv) == paths::normalize(venv_dir));
+    let record = match &stored {
+        Some(record) => record.clone(),
+        // Not created by ppm, or before it kept records: compare the
+        // versions once
+        None => match VenvRecord::of(venv_dir, python) {
+            Some(record) => VenvRecord {
+                resolved: None,
+                ..record
+            },
+            None => return InterpreterCheck::Unknown,
+        },
+    };
+    let check = interpreter_drift(&record, &cfg, |path| fs::canonicalize(path).ok(), probe);
+    if check == InterpreterCheck::Same {
+        let resolved = fs::canonicalize(&record.base_python).ok();
+        if stored.as_ref().map(|stored| &stored.resolved) != Some(&resolved) {
+            // Only spares the next check, so a failed write doesn't matter
+            let _ = VenvRecord {
+                python_version: cfg.version.or(record.python_version),
+                resolved,
+                ..record
+            }
+            .store(state);
+        }
+    }
+    check
+}
+
+/// First line of the pip config ppm writes, to recognize it later
+const PIP_CONFIG_HEADER: &str = "# Written by ppm from index-url in project.toml";
+
+/// pip's config file inside `venv_dir`, read by every pip run from the
+/// venv
+pub fn pip_config_path(venv_dir: &Path) -> PathBuf {
+    venv_dir.join(if cfg!(target_os = "windows") {
+        "pip.ini"
+    } else {
+        "pip.conf"
+    })
+}
+
+/// Point pip in `venv_dir` at `index_url`, or remove the config written
+/// for an earlier one when it is `None`. A pip config ppm didn't write
+/// is left alone.
+pub fn write_pip_config(venv_dir: &Path, index_url: Option<&str>) -> std::io::Result<()> {
+    let path = pip_config_path(venv_dir);
+    let existing = fs::read_to_string(&path).ok();
+    if existing
+        .as_deref()
+        .is_some_and(|text| !text.starts_with(PIP_CONFIG_HEADER))
+    {
+        return Ok(());
+    }
+    match index_url {
+        Some(index_url) => {
+            let text = format!(
+                "{}\n[global]\nindex-url = {}\n",
+                PIP_CONFIG_HEADER, index_url
+            );
+            if existing.as_deref() != Some(text.as_str()) {
+                fs::write(&path, text)?;
+            }
+            Ok(())
+        }
+        None if existing.is_some() => fs::remove_file(&path),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::RunOutput;
+
+    #[test]
+    fn test_missing_interpreter() {
+        let dir = tempfile::tempdir().unwrap();
+        let python = dir.path().join("bin").join("python");
+        assert_eq!(
+            check_interpreter(dir.path(), &python),
+            Err(VenvProblem::MissingInterpreter(python))
+        );
+    }
+
+    #[test]
+    fn test_dead_base_python() {
+        let dir = tempfile::tempdir().unwrap();
+        let home = dir.path().join("uninstalled-python").join("bin");
+        fs::write(
+            dir.path().join("pyvenv.cfg"),
+            format!(
+                "home = {}\ninclude-system-site-packages = false\nversion = 3.11.4\n",
+                home.display()
+            ),
+        )
+        .unwrap();
+        assert_eq!(base_python_home(dir.path()), Some(home.clone()));
+
+        let err = check_interpreter(dir.path(), &dir.path().join("python")).unwrap_err();
+        assert_eq!(err, VenvProblem::DeadBasePython(home));
+        assert!(err.to_string().contains("pyvenv.cfg"));
+    }
+
+    #[test]
+    fn test_cfg_python_version() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(cfg_python_version(dir.path()), None);
+
+        let cfg = dir.path().join("pyvenv.cfg");
+        fs::write(&cfg, "home = /usr/bin\nversion = 3.11.4\n").unwrap();
+        assert_eq!(cfg_python_version(dir.path()).as_deref(), Some("3.11.4"));
+
+        fs::write(&cfg, "home = /usr/bin\nversion_info = 3.12.1.final.0\n").unwrap();
+        assert_eq!(cfg_python_version(dir.path()).as_deref(), Some("3.12.1"));
+
+        fs::write(&cfg, "home = /usr/bin\nversion = unknown\n").unwrap();
+        assert_eq!(cfg_python_version(dir.path()), None);
+    }
+
+    const VENV_CFG: &str = "home = /usr/bin\ninclude-system-site-packages = false\nversion = 3.11.4\nexecutable = /usr/bin/python3.11\ncommand = /usr/bin/python3.11 -m venv /work/demo/venv\n";
+
+    fn record(resolved: Option<&str>) -> VenvRecord {
+        VenvRecord {
+            venv: PathBuf::from("/work/demo/venv"),
+            python_version: Some("3.11.4".to_string()),
+            base_python: PathBuf::from("/usr/bin/python3"),
+            resolved: resolved.map(PathBuf::from),
+        }
+    }
+
+    #[test]
+    fn test_parse_pyvenv_cfg() {
+        assert_eq!(
+            PyvenvCfg::parse(VENV_CFG),
+            PyvenvCfg {
+                home: Some(PathBuf::from("/usr/bin")),
+                executable: Some(PathBuf::from("/usr/bin/python3.11")),
+                version: Some("3.11.4".to_string()),
+            }
+        );
+        // virtualenv, and venv before Python 3.11
+        let virtualenv = PyvenvCfg::parse("home = /opt/py/bin\nversion_info = 3.9.18.final.0\n");
+        assert_eq!(virtualenv.executable, None);
+        assert_eq!(virtualenv.version.as_deref(), Some("3.9.18"));
+        assert_eq!(PyvenvCfg::parse(""), PyvenvCfg::default());
+    }
+
+    #[test]
+    fn test_interpreter_drift() {
+        let cfg = PyvenvCfg::parse(VENV_CFG);
+        let resolve_to = |target: &'static str| move |_: &Path| Some(PathBuf::from(target));
+        let no_probe = |_: &Path| -> Option<String> { panic!("probed an unchanged interpreter") };
+
+        // Still the same file: nothing is run
+        assert_eq!(
+            interpreter_drift(
+                &record(Some("/usr/bin/python3.11")),
+                &cfg,
+                resolve_to("/usr/bin/python3.11"),
+                no_probe
+            ),
+            InterpreterCheck::Same
+        );
+        assert_eq!(
+            interpreter_drift(
+                &record(Some("/usr/bin/python3.11")),
+                &cfg,
+                |_| None,
+                no_probe
+            ),
+            InterpreterCheck::Drifted(Drift::BaseMissing(PathBuf::from("/usr/bin/python3")))
+        );
+
+        // python3 now points at 3.12
+        let drift = interpreter_drift(
+            &record(Some("/usr/bin/python3.11")),
+            &cfg,
+            resolve_to("/usr/bin/python3.12"),
+            |_| Some("3.12.1".to_string()),
+        );
+        assert_eq!(
+            drift,
+            InterpreterCheck::Drifted(Drift::VersionChanged {
+                base: PathBuf::from("/usr/bin/python3"),
+                venv: "3.11.4".to_string(),
+                now: "3.12.1".to_string(),
+            })
+        );
+        let InterpreterCheck::Drifted(drift) = drift else {
+            unreachable!()
+        };
+        assert_eq!(
+            drift.to_string(),
+            "The venv was created with Python 3.11.4 from /usr/bin/python3, which is now Python 3.12.1"
+        );
+
+        // A patch release elsewhere, or a venv not recorded yet
+        assert_eq!(
+            interpreter_drift(
+                &record(Some("/usr/bin/python3.11")),
+                &cfg,
+                resolve_to("/opt/python/3.11.9/bin/python3.11"),
+                |_| Some("3.11.9".to_string())
+            ),
+            InterpreterCheck::Same
+        );
+        assert_eq!(
+            interpreter_drift(
+                &record(None),
+                &cfg,
+                resolve_to("/usr/bin/python3.11"),
+                |_| None
+            ),
+            InterpreterCheck::Unknown
+        );
+        // The record's version stands in for a pyvenv.cfg without one
+        assert_eq!(
+            interpreter_drift(
+                &record(None),
+                &PyvenvCfg::default(),
+                resolve_to("/usr/bin/python3.13"),
+                |_| Some("3.13.0".to_string())
+            ),
+            InterpreterCheck::Drifted(Drift::VersionChanged {
+                base: PathBuf::from("/usr/bin/python3"),
+                venv: "3.11.4".to_string(),
+                now: "3.13.0".to_string(),
+            })
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_check_drift_records_the_venv() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = StateDir::project(dir.path());
+        let base_dir = dir.path().join("system");
+        fs::create_dir_all(&base_dir).unwrap();
+        fs::write(base_dir.join("python3.11"), "").unwrap();
+        fs::write(base_dir.join("python3.12"), "").unwrap();
+        let base = base_dir.join("python3");
+        std::os::unix::fs::symlink(base_dir.join("python3.11"), &base).unwrap();
+
+        let venv_dir = dir.path().join("venv");
+        let python = venv_dir.join("bin").join("python");
+        fs::create_dir_all(python.parent().unwrap()).unwrap();
+        std::os::unix::fs::symlink(&base, &python).unwrap();
+        fs::write(venv_dir.join("pyvenv.cfg"), "home = /x\nversion = 3.11.4\n").unwrap();
+
+        // Adopted after one look at the version, then never run again
+        assert_eq!(
+            check_drift(&state, &venv_dir, &python, |_| Some("3.11.4".to_string())),
+            InterpreterCheck::Same
+        );
+        let stored = VenvRecord::load(&state).unwrap();
+        assert_eq!(stored.base_python, base);
+        assert_eq!(
+            stored.resolved,
+            Some(fs::canonicalize(base_dir.join("python3.11")).unwrap())
+        );
+        assert_eq!(
+            check_drift(&state, &venv_dir, &python, |_| panic!("probed")),
+            InterpreterCheck::Same
+        );
+
+        fs::remove_file(&base).unwrap();
+        std::os::unix::fs::symlink(base_dir.join("python3.12"), &base).unwrap();
+        assert!(matches!(
+            check_drift(&state, &venv_dir, &python, |_| Some("3.12.1".to_string())),
+            InterpreterCheck::Drifted(Drift::VersionChanged { .. })
+        ));
+        // A drifted venv keeps its record
+        assert_eq!(VenvRecord::load(&state).unwrap(), stored);
+
+        fs::remove_file(&base).unwrap();
+        assert_eq!(
+            check_drift(&state, &venv_dir, &python, |_| None),
+            InterpreterCheck::Drifted(Drift::BaseMissing(base))
+        );
+    }
+
+    #[test]
+    fn test_probe_interpreter_version() {
+        let runner = runner::ScriptedRunner::new();
+        runner
+            .respond("py -3.11 --version", RunOutput::ok("Python 3.11.9\n"))
+            .respond(
+                "python2",
+                RunOutput {
+                    code: Some(0),
+                    stdout: vec![],
+                    stderr: b"Python 2.7.18\n".to_vec(),
+                },
+            );
+        let command = ["py".to_string(), "-3.11".to_string()];
+        assert_eq!(
+            probe_interpreter_version(&runner, &command).as_deref(),
+            Some("3.11.9")
+        );
+        assert_eq!(
+            probe_interpreter_version(&runner, &["python2".to_string()]).as_deref(),
+            Some("2.7.18")
+        );
+        assert_eq!(
+            probe_interpreter_version(&runner, &["python3.99".to_string()]),
+            None
+        );
+        assert_eq!(probe_interpreter_version(&runner, &[]), None);
+    }
+
+    #[test]
+    fn test_pip_config_follows_index_url() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = pip_config_path(dir.path());
+        write_pip_config(dir.path(), Some("https://pypi.example.com/simple")).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.contains("index-url = https://pypi.example.com/simple\n"));
+
+        write_pip_config(dir.path(), None).unwrap();
+        assert!(!path.exists());
+
+        // The user's own config stays
+        fs::write(&path, "[global]\ntimeout = 60\n").unwrap();
+        write_pip_config(dir.path(), Some("https://pypi.example.com/simple")).unwrap();
+        write_pip_config(dir.path(), None).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "[global]\ntimeout = 60\n"
+        );
+
+        assert_eq!(interpreter_command(None), vec!["python"]);
+        assert_eq!(
+            interpreter_command(Some("/opt/py/bin/python3")),
+            vec!["/opt/py/bin/python3"]
+        );
+        if !cfg!(target_os = "windows") {
+            assert_eq!(interpreter_command(Some("3.11")), vec!["python3.11"]);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_not_executable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let python = dir.path().join("python");
+        fs::write(&python, "").unwrap();
+        fs::set_permissions(&python, fs::Permissions::from_mode(0o644)).unwrap();
+        assert_eq!(
+            check_interpreter(dir.path(), &python),
+            Err(VenvProblem::NotExecutable(python.clone()))
+        );
+
+        fs::set_permissions(&python, fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(check_interpreter(dir.path(), &python), Ok(()));
+    }
+}
@@ -0,0 +1,530 @@
+//! `ppm verify`: check installed distributions against what the index
+//! published.
+//!
+//! For each configured package the venv's dist-info is inspected:
+//!
+//! - every file listed in `RECORD` must still have its recorded hash
+//! - `WHEEL` tags identify which published wheel was installed; a
+//!   version or wheel the index doesn't have means an unknown source
+//! - an archive hash recorded in `direct_url.json` must equal the
+//!   sha256 digest the index publishes for that file
+//!
+//! Git and local path packages have nothing published to compare with and
+//! are reported as unverifiable instead.
+
+use crate::context::ProjectContext;
+use crate::error::PpmError;
+use crate::index::ReleaseFile;
+use crate::packages::normalize_pkg_name;
+use crate::paths::site_packages_dirs;
+use crate::settings::{Config, PackageSpec};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Verdict for one package
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyStatus {
+    /// Files are intact and match a published artifact
+    Ok(String),
+    /// Installed files or the recorded archive hash differ from what was
+    /// published
+    Mismatch(String),
+    /// Not installed, or installed from something the index doesn't have
+    Unknown(String),
+    /// A git or local path package, with nothing published to compare with
+    Unverifiable(String),
+}
+
+impl VerifyStatus {
+    /// `OK`, `MISMATCH`, `UNKNOWN` or `UNVERIFIABLE`
+    pub fn label(&self) -> &'static str {
+        match self {
+            VerifyStatus::Ok(_) => "OK",
+            VerifyStatus::Mismatch(_) => "MISMATCH",
+            VerifyStatus::Unknown(_) => "UNKNOWN",
+            VerifyStatus::Unverifiable(_) => "UNVERIFIABLE",
+        }
+    }
+
+    /// Why the status was given
+    pub fn detail(&self) -> &str {
+        match self {
+            VerifyStatus::Ok(detail)
+            | VerifyStatus::Mismatch(detail)
+            | VerifyStatus::Unknown(detail)
+            | VerifyStatus::Unverifiable(detail) => detail,
+        }
+    }
+
+    /// Whether `ppm verify` should fail because of this package
+    pub fn is_failure(&self) -> bool {
+        matches!(self, VerifyStatus::Mismatch(_) | VerifyStatus::Unknown(_))
+    }
+}
+
+impl fmt::Display for VerifyStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.label(), self.detail())
+    }
+}
+
+/// Verification result for one configured package
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageVerification {
+    /// Package name as spelled in project.toml
+    pub name: String,
+    /// Installed version, `None` when not installed
+    pub version: Option<String>,
+    /// The verdict
+    pub status: VerifyStatus,
+}
+
+/// One line of a dist-info `RECORD`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordEntry {
+    /// Path relative to site-packages
+    pub path: String,
+    /// `algorithm=urlsafe-base64-digest`, absent for RECORD itself and
+    /// bytecode
+    pub hash: Option<String>,
+    /// Size in bytes
+    pub size: Option<u64>,
+}
+
+/// Parse `RECORD`, a CSV file of `path,hash,size` rows. Paths containing
+/// commas are quoted.
+pub fn parse_record(record: &str) -> Vec<RecordEntry> {
+    record
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| {
+            let fields = split_csv(line);
+            let path = fields.first()?.clone();
+            let field = |i: usize| fields.get(i).filter(|f| !f.is_empty()).cloned();
+            Some(RecordEntry {
+                path,
+                hash: field(1),
+                size: field(2).and_then(|size| size.parse().ok()),
+            })
+        })
+        .collect()
+}
+
+fn split_csv(line: &str) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+/// `sha256=<digest>` in RECORD's urlsafe base64 form
+pub fn record_hash(data: &[u8]) -> String {
+    format!("sha256={}", URL_SAFE_NO_PAD.encode(Sha256::digest(data)))
+}
+
+/// Hex sha256 digest, the form the index publishes
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Whether `data` has the RECORD hash `expected`; `None` for algorithms
+/// other than sha256
+pub fn record_hash_matches(expected: &str, data: &[u8]) -> Option<bool> {
+    let (algorithm, digest) = expected.split_once('=')?;
+    if algorithm != "sha256" {
+        return None;
+    }
+    // Some tools pad the base64 digest
+    Some(digest.trim_end_matches('=') == URL_SAFE_NO_PAD.encode(Sha256::digest(data)))
+}
+
+/// RECORD entries under `site_packages` whose file is missing or no longer
+/// has its recorded hash
+pub fn changed_files(site_packages: &Path, record: &[RecordEntry]) -> Vec<String> {
+    record
+        .iter()
+        .filter(|entry| {
+            let Some(hash) = &entry.hash else {
+                return false;
+            };
+            match fs::read(site_packages.join(&entry.path)) {
+                Ok(data) => record_hash_matches(hash, &data) == Some(false),
+                Err(_) => true,
+            }
+        })
+        .map(|entry| entry.path.clone())
+        .collect()
+}
+
+/// The `Tag:` lines of a dist-info `WHEEL` file, e.g. `py3-none-any`
+pub fn wheel_tags(wheel: &str) -> Vec<String> {
+    wheel
+        .lines()
+        .filter_map(|line| line.strip_prefix("Tag:"))
+        .map(|tag| tag.trim().to_string())
+        .collect()
+}
+
+/// Expanded tags of a wheel file name: `six-1.16.0-py2.py3-none-any.whl`
+/// has `py2-none-any` and `py3-none-any`. `None` unless `filename` is a
+/// wheel of `name` at `version`.
+pub fn wheel_file_tags(filename: &str, name: &str, version: &str) -> Option<Vec<String>> {
+    let stem = filename.strip_suffix(".whl")?;
+    let parts: Vec<&str> = stem.split('-').collect();
+    if parts.len() < 5
+        || normalize_pkg_name(parts[0]) != normalize_pkg_name(name)
+        || parts[1] != version
+    {
+        return None;
+    }
+    let [python, abi, platform] = parts[parts.len() - 3..] else {
+        return None;
+    };
+    let mut tags = vec![];
+    for python in python.split('.') {
+        for abi in abi.split('.') {
+            for platform in platform.split('.') {
+                tags.push(format!("{}-{}-{}", python, abi, platform));
+            }
+        }
+    }
+    Some(tags)
+}
+
+/// What the venv's dist-info says about one installed distribution
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InstalledDist {
+    /// Installed version
+    pub version: String,
+    /// `WHEEL` tags
+    pub tags: Vec<String>,
+    /// RECORD entries whose file is missing or changed
+    pub changed: Vec<String>,
+    /// Parsed `direct_url.json`, present for non-index installs
+    pub direct_url: Option<serde_json::Value>,
+}
+
+/// Compare an installed distribution with the files the index published
+/// for its version
+pub fn verdict(dist: &InstalledDist, published: &[ReleaseFile], name: &str) -> VerifyStatus {
+    if let Some(direct) = &dist.direct_url
+        && (direct.get("vcs_info").is_some() || direct.get("dir_info").is_some())
+    {
+        let url = direct["url"].as_str().unwrap_or("a local or VCS source");
+        return VerifyStatus::Unverifiable(format!("installed from {}", url));
+    }
+    if !dist.changed.is_empty() {
+        let mut shown: Vec<&str> = dist.changed.iter().take(3).map(String::as_str).collect();
+        if dist.changed.len() > 3 {
+            shown.push("...");
+        }
+        return VerifyStatus::Mismatch(format!(
+            "{} installed file(s) differ from RECORD: {}",
+            dist.changed.len(),
+            shown.join(", ")
+        ));
+    }
+    if published.is_empty() {
+        return VerifyStatus::Unknown(format!(
+            "version {} is not published on the index",
+            dist.version
+        ));
+    }
+
+    if let Some(direct) = &dist.direct_url {
+        let url = direct["url"].as_str().unwrap_or_default();
+        let archive = &direct["archive_info"];
+        let recorded = archive["hashes"]["sha256"]
+            .as_str()
+            .map(str::to_string)
+            .or_else(|| {
+                archive["hash"]
+                    .as_str()
+                    .and_then(|hash| hash.strip_prefix("sha256="))
+                    .map(str::to_string)
+            });
+        let filename = url.rsplit('/').next().unwrap_or_default();
+        let same_name = published.iter().find(|file| file.filename == filename);
+        return match (recorded, same_name) {
+            (Some(hash), _) if published.iter().any(|file| file.sha256 == hash) => {
+                VerifyStatus::Ok(format!("{} (sha256 matches the index)", filename))
+            }
+            (Some(hash), Some(file)) => VerifyStatus::Mismatch(format!(
+                "{} has sha256 {} but the index publishes {}",
+                filename, hash, file.sha256
+            )),
+            _ => VerifyStatus::Unknown(format!("installed from {}", url)),
+        };
+    }
+
+    let wheel = published.iter().find(|file| {
+        wheel_file_tags(&file.filename, name, &dist.version)
+            .is_some_and(|tags| tags.iter().any(|tag| dist.tags.contains(tag)))
+    });
+    match wheel {
+        Some(file) => VerifyStatus::Ok(file.filename.clone()),
+        None => VerifyStatus::Unknown(format!(
+            "no wheel published for {} matches the installed tags ({})",
+            dist.version,
+            dist.tags.join(", ")
+        )),
+    }
+}
+
+/// Read the dist-info of `name` from the venv's site-packages, `None`
+/// when it isn't installed
+pub fn inspect_installed(venv_dir: &Path, name: &str) -> Option<InstalledDist> {
+    let wanted = normalize_pkg_name(name);
+    for site_packages in site_packages_dirs(venv_dir) {
+        let Ok(entries) = fs::read_dir(&site_packages) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            let Some(stem) = file_name.strip_suffix(".dist-info") else {
+                continue;
+            };
+            let Some((dist_name, version)) = stem.split_once('-') else {
+                continue;
+            };
+            if normalize_pkg_name(dist_name) != wanted {
+                continue;
+            }
+            let dist_info: PathBuf = entry.path();
+            let read = |file: &str| fs::read_to_string(dist_info.join(file)).unwrap_or_default();
+            return Some(InstalledDist {
+                version: version.to_string(),
+                tags: wheel_tags(&read("WHEEL")),
+                changed: changed_files(&site_packages, &parse_record(&read("RECORD"))),
+                direct_url: serde_json::from_str(&read("direct_url.json")).ok(),
+            });
+        }
+    }
+    None
+}
+
+/// Verify every configured package, looking up published files with
+/// `published(name, version)`. Sorted by name.
+pub fn verify_with<F>(
+    config: &Config,
+    venv_dir: &Path,
+    mut published: F,
+) -> Result<Vec<PackageVerification>, PpmError>
+where
+    F: FnMut(&str, &str) -> Result<Vec<ReleaseFile>, PpmError>,
+{
+    let mut names: Vec<&String> = config.packages.keys().collect();
+    names.sort();
+
+    let mut results = vec![];
+    for name in names {
+        let spec = &config.packages[name];
+        let installed = inspect_installed(venv_dir, name);
+        let version = installed.as_ref().map(|dist| dist.version.clone());
+        let status = match (spec, &installed) {
+            (PackageSpec::Git(_), _) => VerifyStatus::Unverifiable("git package".to_string()),
+            (PackageSpec::Path(_), _) => {
+                VerifyStatus::Unverifiable("local path package".to_string())
+            }
+            (_, None) => VerifyStatus::Unknown("not installed".to_string()),
+            (_, Some(dist)) => verdict(dist, &published(name, &dist.version)?, name),
+        };
+        results.push(PackageVerification {
+            name: name.clone(),
+            version,
+            status,
+        });
+    }
+    Ok(results)
+}
+
+/// Verify every configured package against the project's package index
+pub fn verify(ctx: &ProjectContext) -> Result<Vec<PackageVerification>, PpmError> {
+    verify_with(&ctx.config, &ctx.venv_dir(), |name, version| {
+        ctx.release_files(name, version)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIX_RECORD: &str = "\
+six-1.16.0.dist-info/RECORD,,
+six.py,sha256=TOOfQi7nFGjMjL3cMDWRiNWdHIGQL4eUUBNclTOyk3Q,34549
+\"odd,name.py\",sha256=47DEQpj8HBSa-_TImW-5JCeuQeRkm5NMpJWZG3hSuFU,0
+__pycache__/six.cpython-311.pyc,,
+";
+
+    fn six(changed: Vec<String>, direct_url: Option<&str>) -> InstalledDist {
+        InstalledDist {
+            version: "1.16.0".to_string(),
+            tags: vec!["py2-none-any".to_string(), "py3-none-any".to_string()],
+            changed,
+            direct_url: direct_url.map(|json| serde_json::from_str(json).unwrap()),
+        }
+    }
+
+    fn published() -> Vec<ReleaseFile> {
+        vec![
+            ReleaseFile {
+                filename: "six-1.16.0-py2.py3-none-any.whl".to_string(),
+                sha256: "8abb2f1d86890a2dfb989f9a77cfcfd3e47c2a354b01111771326f8aa26e0254"
+                    .to_string(),
+            },
+            ReleaseFile {
+                filename: "six-1.16.0.tar.gz".to_string(),
+                sha256: "1e61c37477a1626458e36f7b1d82aa5c9b094fa4802892072e49de9c60c4c926"
+                    .to_string(),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_parse_record() {
+        let entries = parse_record(SIX_RECORD);
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[0].hash, None);
+        assert_eq!(entries[1].path, "six.py");
+        assert_eq!(entries[1].size, Some(34549));
+        assert_eq!(entries[2].path, "odd,name.py");
+        assert_eq!(entries[3].hash, None);
+    }
+
+    #[test]
+    fn test_digests() {
+        // sha256 of the empty string
+        assert_eq!(
+            sha256_hex(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            record_hash(b""),
+            "sha256=47DEQpj8HBSa-_TImW-5JCeuQeRkm5NMpJWZG3hSuFU"
+        );
+        let entries = parse_record(SIX_RECORD);
+        assert_eq!(
+            record_hash_matches(entries[2].hash.as_deref().unwrap(), b""),
+            Some(true)
+        );
+        assert_eq!(
+            record_hash_matches(entries[2].hash.as_deref().unwrap(), b"tampered"),
+            Some(false)
+        );
+        assert_eq!(record_hash_matches("md5=abc", b""), None);
+    }
+
+    #[test]
+    fn test_changed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("odd,name.py"), "").unwrap();
+        fs::write(dir.path().join("six.py"), "# edited\n").unwrap();
+        assert_eq!(
+            changed_files(dir.path(), &parse_record(SIX_RECORD)),
+            vec!["six.py"]
+        );
+    }
+
+    #[test]
+    fn test_wheel_file_tags() {
+        assert_eq!(
+            wheel_file_tags("six-1.16.0-py2.py3-none-any.whl", "six", "1.16.0"),
+            Some(vec!["py2-none-any".to_string(), "py3-none-any".to_string()])
+        );
+        assert_eq!(
+            wheel_file_tags(
+                "typing_extensions-4.9.0-1-py3-none-any.whl",
+                "typing-extensions",
+                "4.9.0"
+            ),
+            Some(vec!["py3-none-any".to_string()])
+        );
+        assert_eq!(wheel_file_tags("six-1.16.0.tar.gz", "six", "1.16.0"), None);
+        assert_eq!(
+            wheel_file_tags("six-1.15.0-py2.py3-none-any.whl", "six", "1.16.0"),
+            None
+        );
+        assert_eq!(
+            wheel_tags("Wheel-Version: 1.0\nTag: py2-none-any\nTag: py3-none-any\n"),
+            vec!["py2-none-any", "py3-none-any"]
+        );
+    }
+
+    #[test]
+    fn test_verdict() {
+        let ok = verdict(&six(vec![], None), &published(), "six");
+        assert_eq!(
+            ok,
+            VerifyStatus::Ok("six-1.16.0-py2.py3-none-any.whl".to_string())
+        );
+
+        let tampered = verdict(&six(vec!["six.py".to_string()], None), &published(), "six");
+        assert_eq!(tampered.label(), "MISMATCH");
+        assert!(tampered.detail().contains("six.py"));
+
+        assert_eq!(verdict(&six(vec![], None), &[], "six").label(), "UNKNOWN");
+
+        let local_build = InstalledDist {
+            tags: vec!["cp311-cp311-linux_x86_64".to_string()],
+            ..six(vec![], None)
+        };
+        assert_eq!(
+            verdict(&local_build, &published(), "six").label(),
+            "UNKNOWN"
+        );
+
+        let git = six(
+            vec![],
+            Some(
+                r#"{"url": "https://github.com/benjaminp/six", "vcs_info": {"vcs": "git", "commit_id": "abc"}}"#,
+            ),
+        );
+        assert_eq!(verdict(&git, &published(), "six").label(), "UNVERIFIABLE");
+    }
+
+    #[test]
+    fn test_verdict_compares_archive_digests() {
+        let url = "https://files.pythonhosted.org/packages/d9/5a/six-1.16.0-py2.py3-none-any.whl";
+        let direct = |hash: &str| {
+            format!(
+                r#"{{"url": "{}", "archive_info": {{"hashes": {{"sha256": "{}"}}}}}}"#,
+                url, hash
+            )
+        };
+
+        let matching = direct("8abb2f1d86890a2dfb989f9a77cfcfd3e47c2a354b01111771326f8aa26e0254");
+        let status = verdict(&six(vec![], Some(&matching)), &published(), "six");
+        assert_eq!(status.label(), "OK");
+
+        let other = direct("0000000000000000000000000000000000000000000000000000000000000000");
+        let status = verdict(&six(vec![], Some(&other)), &published(), "six");
+        assert_eq!(status.label(), "MISMATCH");
+
+        let elsewhere = r#"{"url": "https://mirror.example/six.whl", "archive_info": {}}"#;
+        let status = verdict(&six(vec![], Some(elsewhere)), &published(), "six");
+        assert_eq!(
+            status,
+            VerifyStatus::Unknown("installed from https://mirror.example/six.whl".to_string())
+        );
+    }
+}
@@ -0,0 +1,122 @@
+//! The release list `ppm add --version-pick` offers: the newest
+//! [`PICK_LIMIT`] releases with their upload days, yanked ones marked.
+//!
+//! Pre-releases are left out unless asked for. Yanked releases stay in
+//! the list, since pip still installs them when pinned exactly.
+
+use crate::index::ReleaseEntry;
+use crate::packages::{compare_versions, is_prerelease};
+
+/// How many releases are offered
+pub const PICK_LIMIT: usize = 15;
+
+/// The releases to offer from `history`, newest first, at most
+/// [`PICK_LIMIT`]; pre-releases only with `pre`
+pub fn pickable(history: &[ReleaseEntry], pre: bool) -> Vec<&ReleaseEntry> {
+    let mut releases: Vec<&ReleaseEntry> = history
+        .iter()
+        .filter(|release| pre || !is_prerelease(&release.version))
+        .collect();
+    releases.sort_by(|a, b| compare_versions(&b.version, &a.version));
+    releases.truncate(PICK_LIMIT);
+    releases
+}
+
+/// One line per release, like `2.32.0   2024-05-20  (yanked)`, with the
+/// versions padded to a common width
+pub fn describe(releases: &[&ReleaseEntry]) -> Vec<String> {
+    let width = releases
+        .iter()
+        .map(|release| release.version.len())
+        .max()
+        .unwrap_or(0);
+    releases
+        .iter()
+        .map(|release| {
+            let mut line = format!(
+                "{:<width$}  {}",
+                release.version,
+                release.uploaded.as_deref().unwrap_or("unknown date"),
+                width = width
+            );
+            if release.yanked {
+                line.push_str("  (yanked)");
+            }
+            line
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::release_entries;
+
+    const RELEASES: &str = include_str!("../tests/fixtures/pypi-releases.json");
+
+    fn history() -> Vec<ReleaseEntry> {
+        release_entries(&serde_json::from_str(RELEASES).unwrap())
+    }
+
+    #[test]
+    fn test_release_entries_from_api_data() {
+        let history = history();
+        // 1.0.5 has no files and can't be installed
+        assert_eq!(history.len(), 6);
+        let yanked = history.iter().find(|r| r.version == "2.32.0").unwrap();
+        assert!(yanked.yanked);
+        assert_eq!(yanked.uploaded.as_deref(), Some("2024-05-20"));
+        // The earliest file's day, whatever order the files are listed in
+        let latest = history.iter().find(|r| r.version == "2.32.3").unwrap();
+        assert!(!latest.yanked);
+        assert_eq!(latest.uploaded.as_deref(), Some("2024-05-29"));
+    }
+
+    #[test]
+    fn test_pickable_newest_first_without_prereleases() {
+        let history = history();
+        let versions: Vec<&str> = pickable(&history, false)
+            .iter()
+            .map(|r| r.version.as_str())
+            .collect();
+        assert_eq!(versions, ["2.32.3", "2.32.1", "2.32.0", "2.31.0", "2.0.0"]);
+        assert_eq!(pickable(&history, true)[0].version, "3.0.0b1");
+    }
+
+    #[test]
+    fn test_pickable_keeps_the_newest() {
+        let history: Vec<ReleaseEntry> = (0..40)
+            .map(|minor| ReleaseEntry {
+                version: format!("1.{}", minor),
+                uploaded: None,
+                yanked: false,
+                yanked_reason: None,
+            })
+            .collect();
+        let picked = pickable(&history, false);
+        assert_eq!(picked.len(), PICK_LIMIT);
+        assert_eq!(picked[0].version, "1.39");
+        assert_eq!(picked[PICK_LIMIT - 1].version, "1.25");
+    }
+
+    #[test]
+    fn test_describe_dates_and_yanked() {
+        let history = history();
+        assert_eq!(
+            describe(&pickable(&history, true)[..4]),
+            [
+                "3.0.0b1  2024-08-01",
+                "2.32.3   2024-05-29",
+                "2.32.1   2024-05-20",
+                "2.32.0   2024-05-20  (yanked)",
+            ]
+        );
+        let undated = ReleaseEntry {
+            version: "0.1".to_string(),
+            uploaded: None,
+            yanked: false,
+            yanked_reason: None,
+        };
+        assert_eq!(describe(&[&undated]), ["0.1  unknown date"]);
+    }
+}
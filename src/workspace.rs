@@ -0,0 +1,340 @@
+//! Workspaces: a root project.toml with a `[workspace]` table managing
+//! several member projects, each with its own project.toml and venv.
+//!
+//! Members are the directories matched by the `members` patterns, taken
+//! relative to the workspace root, that contain a project.toml. `*` and
+//! `?` match within one path component. The root may be a project itself
+//! or hold nothing but the `[workspace]` table.
+//!
+//! The workspace's `index-url` and `python` apply to every member whose
+//! `[project]` doesn't set them, see [`Shared::resolve`].
+
+use crate::error::PpmError;
+use crate::settings::{PROJECT_CONFIG_FILE, Project, Workspace};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A member project of a workspace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Member {
+    /// Path relative to the workspace root, with `/` separators, like
+    /// `services/api`
+    pub name: String,
+    /// Directory containing the member's project.toml
+    pub root: PathBuf,
+}
+
+impl Member {
+    /// The last component of [`name`](Self::name), like `api`
+    pub fn short_name(&self) -> &str {
+        self.name.rsplit('/').next().unwrap_or(&self.name)
+    }
+}
+
+/// The `[workspace]` table of `root/project.toml`, `None` when there is
+/// none
+pub fn load_workspace(root: &Path) -> Result<Option<Workspace>, PpmError> {
+    Workspace::load_from_file(&root.join(PROJECT_CONFIG_FILE))
+}
+
+/// Nearest directory at or above `start` whose project.toml has a
+/// `[workspace]` table, with that table
+pub fn find_workspace_root(start: &Path) -> Option<(PathBuf, Workspace)> {
+    start.ancestors().find_map(|dir| {
+        let workspace = load_workspace(dir).ok()??;
+        Some((dir.to_path_buf(), workspace))
+    })
+}
+
+/// Whether `name` matches the single path component `pattern`, where `*`
+/// matches any run of characters and `?` any one character
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Where the last `*` was, and how much of `name` it has taken
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Directories under `root` matching the `/`-separated `pattern`
+fn expand_pattern(root: &Path, pattern: &str) -> Vec<PathBuf> {
+    let mut dirs = vec![root.to_path_buf()];
+    for component in pattern.split('/').filter(|c| !c.is_empty() && *c != ".") {
+        if !component.contains(['*', '?']) {
+            dirs = dirs
+                .into_iter()
+                .map(|dir| dir.join(component))
+                .filter(|dir| dir.is_dir())
+                .collect();
+            continue;
+        }
+        let mut matched = vec![];
+        for dir in dirs {
+            let Ok(entries) = fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                // Like shell globs, wildcards skip hidden directories
+                if !name.starts_with('.') && glob_match(component, &name) && entry.path().is_dir() {
+                    matched.push(entry.path());
+                }
+            }
+        }
+        dirs = matched;
+    }
+    dirs
+}
+
+/// The members of the workspace at `root`, ordered by name. A pattern
+/// without wildcards must name a project; wildcards skip directories
+/// without a project.toml.
+pub fn members(root: &Path, workspace: &Workspace) -> Result<Vec<Member>, PpmError> {
+    let mut members: Vec<Member> = vec![];
+    for pattern in &workspace.members {
+        let pattern = pattern.replace('\\', "/");
+        if pattern.split('/').any(|c| c == "..") {
+            return Err(PpmError::Config(format!(
+                "Workspace member '{}' is outside the workspace root",
+                pattern
+            )));
+        }
+        let dirs = expand_pattern(root, &pattern);
+        if !pattern.contains(['*', '?'])
+            && !dirs
+                .iter()
+                .any(|dir| dir.join(PROJECT_CONFIG_FILE).is_file())
+        {
+            return Err(PpmError::Config(format!(
+                "Workspace member '{}' has no {}",
+                pattern, PROJECT_CONFIG_FILE
+            )));
+        }
+        for dir in dirs {
+            if dir == root || !dir.join(PROJECT_CONFIG_FILE).is_file() {
+                continue;
+            }
+            let name = dir
+                .strip_prefix(root)
+                .unwrap_or(&dir)
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+                .join("/");
+            if !members.iter().any(|member| member.name == name) {
+                members.push(Member { name, root: dir });
+            }
+        }
+    }
+    members.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(members)
+}
+
+/// The member called `name`: its path relative to the workspace root, or
+/// its last component when no other member shares it
+pub fn select<'a>(members: &'a [Member], name: &str) -> Result<&'a Member, PpmError> {
+    let name = name.trim_end_matches('/').replace('\\', "/");
+    if let Some(member) = members.iter().find(|member| member.name == name) {
+        return Ok(member);
+    }
+    let matches: Vec<&Member> = members
+        .iter()
+        .filter(|member| member.short_name() == name)
+        .collect();
+    match matches.as_slice() {
+        [member] => Ok(member),
+        [] => Err(PpmError::Config(format!(
+            "No workspace member '{}'; members: {}",
+            name,
+            names(members)
+        ))),
+        _ => Err(PpmError::Config(format!(
+            "'{}' is ambiguous; use one of {}",
+            name,
+            names(&matches.into_iter().cloned().collect::<Vec<_>>())
+        ))),
+    }
+}
+
+/// Member names joined for messages
+pub fn names(members: &[Member]) -> String {
+    if members.is_empty() {
+        return "none".to_string();
+    }
+    members
+        .iter()
+        .map(|member| member.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The workspace `project_root` belongs to: the nearest one above it that
+/// lists it as a member, or its own `[workspace]`
+pub fn enclosing_workspace(project_root: &Path) -> Option<(PathBuf, Workspace)> {
+    project_root.ancestors().find_map(|dir| {
+        let workspace = load_workspace(dir).ok()??;
+        let is_member = dir == project_root
+            || members(dir, &workspace)
+                .map(|members| members.iter().any(|member| member.root == project_root))
+                .unwrap_or(false);
+        is_member.then(|| (dir.to_path_buf(), workspace))
+    })
+}
+
+/// Settings a member inherits from its workspace
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Shared {
+    /// Simple index pip installs from
+    pub index_url: Option<String>,
+    /// Interpreter the venv is created with
+    pub python: Option<String>,
+}
+
+impl Shared {
+    /// Each setting from `project`, or from `workspace` when the project
+    /// doesn't set it
+    pub fn resolve(project: &Project, workspace: Option<&Workspace>) -> Shared {
+        let inherit = |own: &Option<String>, shared: fn(&Workspace) -> &Option<String>| {
+            own.clone()
+                .or_else(|| workspace.and_then(|ws| shared(ws).clone()))
+        };
+        Shared {
+            index_url: inherit(&project.index_url, |ws| &ws.index_url),
+            python: inherit(&project.python, |ws| &ws.python),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_project(dir: &Path) {
+        fs::create_dir_all(dir).unwrap();
+        fs::write(dir.join(PROJECT_CONFIG_FILE), "").unwrap();
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*", "api"));
+        assert!(glob_match("svc-*", "svc-api"));
+        assert!(glob_match("svc-?", "svc-a"));
+        assert!(glob_match("*-*-x", "a-b-c-x"));
+        assert!(!glob_match("svc-?", "svc-ab"));
+        assert!(!glob_match("svc-*", "lib-api"));
+        assert!(!glob_match("api", "api2"));
+    }
+
+    #[test]
+    fn test_members_from_patterns() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write_project(&root.join("services/api"));
+        write_project(&root.join("services/worker"));
+        fs::create_dir_all(root.join("services/docs")).unwrap();
+        write_project(&root.join("services/.hidden"));
+        write_project(&root.join("libs/shared"));
+
+        let workspace = Workspace {
+            members: vec!["services/*".to_string(), "libs/shared".to_string()],
+            ..Workspace::default()
+        };
+        let found = members(root, &workspace).unwrap();
+        let names: Vec<&str> = found.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["libs/shared", "services/api", "services/worker"]
+        );
+        assert_eq!(found[1].root, root.join("services/api"));
+
+        assert_eq!(select(&found, "worker").unwrap().name, "services/worker");
+        assert_eq!(select(&found, "libs/shared/").unwrap().name, "libs/shared");
+        assert!(select(&found, "docs").is_err());
+
+        let missing = Workspace {
+            members: vec!["services/docs".to_string()],
+            ..Workspace::default()
+        };
+        assert!(members(root, &missing).is_err());
+        let outside = Workspace {
+            members: vec!["../elsewhere".to_string()],
+            ..Workspace::default()
+        };
+        assert!(members(root, &outside).is_err());
+    }
+
+    #[test]
+    fn test_enclosing_workspace() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::write(
+            root.join(PROJECT_CONFIG_FILE),
+            "[workspace]\nmembers = [\"services/*\"]\n",
+        )
+        .unwrap();
+        write_project(&root.join("services/api"));
+        write_project(&root.join("tools/lint"));
+
+        let (found, workspace) = enclosing_workspace(&root.join("services/api")).unwrap();
+        assert_eq!(found, root);
+        assert_eq!(workspace.members, vec!["services/*"]);
+        assert_eq!(enclosing_workspace(&root.join("tools/lint")), None);
+        assert_eq!(
+            find_workspace_root(&root.join("tools/lint")).map(|(dir, _)| dir),
+            Some(root.to_path_buf())
+        );
+    }
+
+    #[test]
+    fn test_members_inherit_unset_settings() {
+        let workspace = Workspace {
+            members: vec![],
+            index_url: Some("https://pypi.example.com/simple".to_string()),
+            python: Some("3.11".to_string()),
+        };
+        let mut project = Project::new(
+            "api".to_string(),
+            "0.1.0".to_string(),
+            String::new(),
+            "main.py".to_string(),
+            None,
+        );
+        assert_eq!(
+            Shared::resolve(&project, Some(&workspace)),
+            Shared {
+                index_url: Some("https://pypi.example.com/simple".to_string()),
+                python: Some("3.11".to_string()),
+            }
+        );
+
+        project.python = Some("python3.12".to_string());
+        let shared = Shared::resolve(&project, Some(&workspace));
+        assert_eq!(shared.python.as_deref(), Some("python3.12"));
+        assert_eq!(
+            shared.index_url.as_deref(),
+            Some("https://pypi.example.com/simple")
+        );
+        assert_eq!(Shared::resolve(&project, None).index_url, None);
+    }
+}
@@ -0,0 +1,218 @@
+//! Pins on releases the index has yanked.
+//!
+//! pip still installs a yanked release when it is pinned exactly, so a
+//! project can keep shipping a version its maintainer pulled. [`check_pin`]
+//! spots those pins in a package's release history and suggests the
+//! nearest release that isn't yanked. Release histories from PyPI are
+//! cached under [`RELEASES_CACHE`] in the global cache directory, so
+//! `--offline` checks still warn; [`crate::metadata_cache`] keeps that
+//! within its budget.
+
+use crate::index::ReleaseEntry;
+use crate::metadata_cache;
+use crate::packages::{compare_versions, is_prerelease, normalize_pkg_name};
+use crate::settings::Config;
+use crate::state::StateDir;
+use std::cmp::Ordering;
+use std::fmt;
+use std::io;
+
+/// Cached release histories, one file per package, inside the global
+/// [`StateDir`]
+pub const RELEASES_CACHE: &str = "cache/pypi";
+
+/// A configured pin on a yanked release
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct YankedPin {
+    /// Package name as configured
+    pub name: String,
+    /// The pinned version
+    pub version: String,
+    /// Why the maintainer yanked it, when they said
+    pub reason: Option<String>,
+    /// The nearest release that isn't yanked
+    pub replacement: Option<String>,
+}
+
+impl fmt::Display for YankedPin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} has been yanked from the index",
+            self.name, self.version
+        )?;
+        if let Some(reason) = &self.reason {
+            write!(f, " ({})", reason)?;
+        }
+        match &self.replacement {
+            Some(replacement) => write!(f, "; consider {}=={}", self.name, replacement),
+            None => write!(f, "; no other release is available"),
+        }
+    }
+}
+
+impl YankedPin {
+    /// `{"name", "version", "reason", "replacement"}` for JSON output
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "name": self.name,
+            "version": self.version,
+            "reason": self.reason,
+            "replacement": self.replacement,
+        })
+    }
+}
+
+/// Index packages pinned to a version in `config`, sorted by name
+pub fn pins(config: &Config) -> Vec<(String, String)> {
+    let mut pins: Vec<(String, String)> = config
+        .packages
+        .iter()
+        .filter(|(_, spec)| spec.is_index())
+        .filter_map(|(name, spec)| Some((name.clone(), spec.version()?.to_string())))
+        .collect();
+    pins.sort();
+    pins
+}
+
+/// The yanked pin `name==version` is, according to `history`; `None` when
+/// the release isn't yanked or isn't listed
+pub fn check_pin(name: &str, version: &str, history: &[ReleaseEntry]) -> Option<YankedPin> {
+    let release = history
+        .iter()
+        .find(|release| compare_versions(&release.version, version) == Ordering::Equal)?;
+    if !release.yanked {
+        return None;
+    }
+    Some(YankedPin {
+        name: name.to_string(),
+        version: version.to_string(),
+        reason: release.yanked_reason.clone(),
+        replacement: nearest_unyanked(version, history),
+    })
+}
+
+/// The release closest to `version` that isn't yanked: the oldest newer
+/// one, or failing that the newest older one. Pre-releases are only
+/// considered when `version` is one.
+pub fn nearest_unyanked(version: &str, history: &[ReleaseEntry]) -> Option<String> {
+    let pre = is_prerelease(version);
+    let candidates: Vec<&str> = history
+        .iter()
+        .filter(|release| !release.yanked && (pre || !is_prerelease(&release.version)))
+        .map(|release| release.version.as_str())
+        .collect();
+    let newer = candidates
+        .iter()
+        .filter(|candidate| compare_versions(candidate, version) == Ordering::Greater)
+        .min_by(|a, b| compare_versions(a, b));
+    let older = candidates
+        .iter()
+        .filter(|candidate| compare_versions(candidate, version) == Ordering::Less)
+        .max_by(|a, b| compare_versions(a, b));
+    newer.or(older).map(|candidate| candidate.to_string())
+}
+
+fn cache_file(name: &str) -> String {
+    format!("{}/{}.json", RELEASES_CACHE, normalize_pkg_name(name))
+}
+
+/// Keep `history` of `name` in `cache` for offline checks, evicting the
+/// least recently used histories beyond `max_size` bytes
+pub fn store(
+    cache: &StateDir,
+    name: &str,
+    history: &[ReleaseEntry],
+    max_size: u64,
+) -> io::Result<()> {
+    let contents = serde_json::to_vec(history).map_err(io::Error::other)?;
+    let path = cache.write(&cache_file(name), &contents)?;
+    metadata_cache::evict(cache, max_size, Some(&path)).map(|_| ())
+}
+
+/// The history of `name` last stored in `cache`, if any
+pub fn load(cache: &StateDir, name: &str) -> Option<Vec<ReleaseEntry>> {
+    let path = cache.subpath(&cache_file(name));
+    let contents = std::fs::read(&path).ok()?;
+    let history = serde_json::from_slice(&contents).ok()?;
+    let _ = metadata_cache::touch(&path);
+    Some(history)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::release_entries;
+
+    const RELEASES: &str = include_str!("../tests/fixtures/pypi-releases.json");
+
+    fn history() -> Vec<ReleaseEntry> {
+        release_entries(&serde_json::from_str(RELEASES).unwrap())
+    }
+
+    #[test]
+    fn test_check_pin_on_a_yanked_release() {
+        let pin = check_pin("requests", "2.32.0", &history()).unwrap();
+        assert_eq!(
+            pin,
+            YankedPin {
+                name: "requests".to_string(),
+                version: "2.32.0".to_string(),
+                reason: Some("Yanked due to conflicts with CVE-2024-35195 mitigation".to_string()),
+                replacement: Some("2.32.1".to_string()),
+            }
+        );
+        assert_eq!(
+            pin.to_string(),
+            "requests 2.32.0 has been yanked from the index (Yanked due to conflicts with CVE-2024-35195 mitigation); consider requests==2.32.1"
+        );
+        assert_eq!(check_pin("requests", "2.31.0", &history()), None);
+        assert_eq!(check_pin("requests", "9.9.9", &history()), None);
+    }
+
+    #[test]
+    fn test_nearest_unyanked_falls_back_to_older() {
+        let entry = |version: &str, yanked: bool| ReleaseEntry {
+            version: version.to_string(),
+            uploaded: None,
+            yanked,
+            yanked_reason: None,
+        };
+        let history = vec![
+            entry("1.0", false),
+            entry("1.1", false),
+            entry("1.2", true),
+            entry("2.0rc1", false),
+        ];
+        assert_eq!(nearest_unyanked("1.2", &history).as_deref(), Some("1.1"));
+        assert_eq!(nearest_unyanked("1.0.1", &history).as_deref(), Some("1.1"));
+        assert_eq!(
+            nearest_unyanked("2.0b1", &history).as_deref(),
+            Some("2.0rc1")
+        );
+
+        let pin = check_pin("demo", "1.2", &history).unwrap();
+        assert_eq!(pin.reason, None);
+        assert_eq!(
+            pin.to_string(),
+            "demo 1.2 has been yanked from the index; consider demo==1.1"
+        );
+    }
+
+    #[test]
+    fn test_cache_keeps_the_yanked_flag() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = StateDir::project(dir.path());
+        assert_eq!(load(&cache, "requests"), None);
+        store(
+            &cache,
+            "Requests",
+            &history(),
+            metadata_cache::DEFAULT_MAX_SIZE,
+        )
+        .unwrap();
+        let cached = load(&cache, "requests").unwrap();
+        assert_eq!(cached, history());
+        assert!(check_pin("requests", "2.32.0", &cached).is_some());
+    }
+}
@@ -0,0 +1,255 @@
+//! End-to-end tests of the commands that look packages up, and of the
+//! library's lookups against a slow index, on the fake PyPI in
+//! [`support`]. They need neither the network nor Python.
+
+mod support;
+
+use assert_cmd::cargo::cargo_bin_cmd;
+use ppmm::PpmError;
+use ppmm::index::{self, Throttle};
+use predicates::prelude::*;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use support::{FakePypi, MISSING, write_project};
+
+/// The popular-packages list suggestions start from, cached in `cache` as
+/// fetched just now so it isn't downloaded
+fn seed_popular_packages(cache: &Path, names: &[&str]) {
+    let fetched_at = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let cached = serde_json::json!({"fetched_at": fetched_at, "names": names});
+    std::fs::create_dir_all(cache.join("cache")).unwrap();
+    std::fs::write(
+        cache.join("cache").join("popular-packages.json"),
+        cached.to_string(),
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_outdated_against_fake_index() {
+    let pypi = FakePypi::start();
+    let dir = tempfile::tempdir().unwrap();
+    write_project(
+        dir.path(),
+        Some(&pypi.simple_url()),
+        "requests = \"2.31.0\"\nnightly = \"1.0.0a1\"\n",
+    );
+    let output = cargo_bin_cmd!("ppmm")
+        .current_dir(dir.path())
+        .env("PPM_CACHE_DIR", dir.path().join("cache"))
+        .args(["--json", "list", "--outdated"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let listed: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let latest = |name: &str| {
+        listed["packages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|package| package["name"] == name)
+            .map(|package| package["latest"].clone())
+    };
+    // The yanked 2.32.0 and the 3.0.0b1 pre-release aren't latest
+    assert_eq!(latest("requests"), Some("2.32.3".into()));
+    assert_eq!(latest("nightly"), Some("1.0.0b2".into()));
+}
+
+#[test]
+fn test_update_dry_run_against_fake_index() {
+    let pypi = FakePypi::start();
+    let dir = tempfile::tempdir().unwrap();
+    write_project(
+        dir.path(),
+        Some(&pypi.simple_url()),
+        "requests = \"2.31.0\"\nnightly = \"1.0.0a1\"\n",
+    );
+    let before = std::fs::read_to_string(dir.path().join("project.toml")).unwrap();
+
+    cargo_bin_cmd!("ppmm")
+        .current_dir(dir.path())
+        .env("PPM_CACHE_DIR", dir.path().join("cache"))
+        .args(["update", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("2.31.0 -> 2.32.3"))
+        .stdout(predicate::str::contains("1.0.0a1 -> 1.0.0b2"));
+
+    let after = std::fs::read_to_string(dir.path().join("project.toml")).unwrap();
+    assert_eq!(before, after);
+    assert!(!dir.path().join("venv").exists());
+}
+
+#[test]
+fn test_add_suggests_close_matches_for_a_missing_package() {
+    let pypi = FakePypi::start();
+    let dir = tempfile::tempdir().unwrap();
+    write_project(dir.path(), None, "");
+    let cache = dir.path().join("cache");
+    seed_popular_packages(&cache, &["boto3", "requests", "urllib3"]);
+
+    // Without a terminal the matches are only printed
+    cargo_bin_cmd!("ppmm")
+        .current_dir(dir.path())
+        .env("PPM_PYPI_URL", pypi.url())
+        .env("PPM_CACHE_DIR", &cache)
+        .args(["add", MISSING])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains(format!(
+            "Package '{}' was not found; did you mean requests?",
+            MISSING
+        )));
+
+    let config = std::fs::read_to_string(dir.path().join("project.toml")).unwrap();
+    assert!(!config.contains("requests"), "{}", config);
+}
+
+#[test]
+fn test_server_errors_are_retried_then_reported() {
+    let pypi = FakePypi::start();
+    let flaky = pypi.fail("flaky", 500);
+    let dir = tempfile::tempdir().unwrap();
+    write_project(dir.path(), Some(&pypi.simple_url()), "flaky = \"1.0.0\"\n");
+
+    cargo_bin_cmd!("ppmm")
+        .current_dir(dir.path())
+        .env("PPM_CACHE_DIR", dir.path().join("cache"))
+        .args(["update", "--dry-run"])
+        .assert()
+        .code(3)
+        .stdout(predicate::str::contains("Could not resolve 1 package(s)"));
+    // The version lookup and the yanked-release check, each tried once
+    // and retried twice
+    flaky.assert_calls(2 * 3);
+}
+
+#[test]
+fn test_timeout_fails_a_stalled_package_without_retrying() {
+    let pypi = FakePypi::start();
+    let stalled = pypi.stall("stalled", std::time::Duration::from_secs(10));
+    let dir = tempfile::tempdir().unwrap();
+    write_project(
+        dir.path(),
+        Some(&pypi.simple_url()),
+        "requests = \"2.31.0\"\nstalled = \"1.0.0\"\n",
+    );
+
+    let start = std::time::Instant::now();
+    cargo_bin_cmd!("ppmm")
+        .current_dir(dir.path())
+        .env("PPM_CACHE_DIR", dir.path().join("cache"))
+        .args(["--timeout", "0.3", "update", "--dry-run"])
+        .assert()
+        .code(3)
+        .stdout(predicate::str::contains("requests  2.31.0 -> 2.32.3"))
+        .stdout(predicate::str::contains(
+            "stalled   Looking up 'stalled' on the package index timed out",
+        ));
+    assert!(start.elapsed() < std::time::Duration::from_secs(10));
+    // The version lookup and the yanked-release check, once each: a
+    // timed-out request already took its whole timeout, so it isn't
+    // tried again
+    stalled.assert_calls(2);
+
+    cargo_bin_cmd!("ppmm")
+        .args(["update", "--timeout", "0"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "The timeout must be more than 0 seconds",
+        ));
+}
+
+/// The fake's JSON API, for the library's index lookups
+fn api_url(pypi: &FakePypi) -> String {
+    index::json_api_url(&pypi.simple_url()).unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_slow_request_times_out() {
+    let pypi = FakePypi::start();
+    let stalled = pypi.stall("stalled", Duration::from_secs(5));
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_millis(100))
+        .build()
+        .unwrap();
+    let err = index::latest_version(&client, &api_url(&pypi), "stalled")
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&err, PpmError::Timeout { timed_out, .. } if timed_out == &["stalled"]),
+        "{:?}",
+        err
+    );
+    assert_eq!(err.exit_code(), ppmm::error::EXIT_NETWORK);
+    stalled.assert_calls(1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_phase_budget_cancels_slow_lookups() {
+    let pypi = FakePypi::start();
+    pypi.stall("stalled", Duration::from_secs(10));
+    let url = api_url(&pypi);
+    let pkgs: Vec<String> = ["requests", "stalled", "nightly"]
+        .iter()
+        .map(|pkg| pkg.to_string())
+        .collect();
+    let client = reqwest::Client::new();
+    let mut done = vec![];
+    let start = Instant::now();
+    let err = index::latest_versions(
+        &client,
+        &url,
+        &pkgs,
+        &Throttle::new(Duration::from_millis(1)),
+        Some(Duration::from_millis(500)),
+        |pkg, result| done.push((pkg.to_string(), result.is_ok())),
+    )
+    .await
+    .unwrap_err();
+    // Gave up at the budget rather than waiting for the slow answer
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    match err {
+        PpmError::Timeout {
+            what,
+            limit,
+            resolved,
+            timed_out,
+        } => {
+            assert_eq!(what, "Looking up 3 packages");
+            assert_eq!(limit, Some(Duration::from_millis(500)));
+            assert_eq!(resolved, ["requests", "nightly"]);
+            assert_eq!(timed_out, ["stalled"]);
+        }
+        err => panic!("{:?}", err),
+    }
+    // Every package was reported, the slow one as failed
+    done.sort();
+    assert_eq!(
+        done,
+        [
+            ("nightly".to_string(), true),
+            ("requests".to_string(), true),
+            ("stalled".to_string(), false)
+        ]
+    );
+
+    // Within the budget nothing changes
+    let results = index::latest_versions(
+        &client,
+        &url,
+        &pkgs[..1],
+        &Throttle::new(Duration::from_millis(1)),
+        Some(Duration::from_secs(5)),
+        |_, _| {},
+    )
+    .await
+    .unwrap();
+    assert_eq!(results[0].as_deref().ok(), Some("2.32.3"));
+}
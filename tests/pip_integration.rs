@@ -0,0 +1,127 @@
+//! Resolution against a real pip and PyPI. Needs `python3` and network
+//! access, so it only runs with `cargo test --features pip-integration`.
+#![cfg(feature = "pip-integration")]
+
+use assert_cmd::cargo::cargo_bin_cmd;
+use ppmm::resolver::resolve_with;
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn test_resolve_with_real_pip() {
+    let dir = tempfile::tempdir().unwrap();
+    let venv = dir.path().join("venv");
+    let status = Command::new("python3")
+        .args(["-m", "venv"])
+        .arg(&venv)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let bin = if cfg!(windows) { "Scripts" } else { "bin" };
+    let python = venv.join(bin).join("python");
+    let status = Command::new(&python)
+        .args(["-m", "pip", "install", "--quiet", "--upgrade", "pip"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let resolved = resolve_with(
+        &venv.join(bin).join("pip"),
+        &python,
+        dir.path(),
+        &["requests==2.31.0".to_string()],
+    )
+    .unwrap();
+    let requests = resolved.iter().find(|pkg| pkg.name == "requests").unwrap();
+    assert_eq!(requests.version, "2.31.0");
+    assert!(requests.requested);
+    assert!(!requests.hashes.is_empty());
+    // Transitive dependencies are part of the resolution
+    assert!(
+        resolved
+            .iter()
+            .any(|pkg| pkg.name == "urllib3" && !pkg.requested)
+    );
+}
+
+fn copy_dir(from: &Path, to: &Path) {
+    std::fs::create_dir_all(to).unwrap();
+    for entry in std::fs::read_dir(from).unwrap() {
+        let entry = entry.unwrap();
+        if entry.file_type().unwrap().is_dir() {
+            copy_dir(&entry.path(), &to.join(entry.file_name()));
+        } else {
+            std::fs::copy(entry.path(), to.join(entry.file_name())).unwrap();
+        }
+    }
+}
+
+#[test]
+fn test_build_trivial_pyproject() {
+    let dir = tempfile::tempdir().unwrap();
+    copy_dir(
+        &Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/build"),
+        dir.path(),
+    );
+
+    let output = cargo_bin_cmd!("ppmm")
+        .current_dir(dir.path())
+        .args(["build", "--json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let paths: Vec<&str> = report["artifacts"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|artifact| artifact["path"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        paths,
+        vec!["dist/demo-0.1.0-py3-none-any.whl", "dist/demo-0.1.0.tar.gz"]
+    );
+    // `build` went into its own environment, not the project's
+    assert!(dir.path().join(".ppm/cache/build-env").is_dir());
+    assert!(!dir.path().join("venv").exists());
+}
+
+#[test]
+fn test_bundle_round_trip_with_real_pip() {
+    let dir = tempfile::tempdir().unwrap();
+    let source = dir.path().join("source");
+    std::fs::create_dir_all(&source).unwrap();
+    std::fs::write(
+        source.join("project.toml"),
+        "[project]\nname = \"demo\"\nversion = \"0.1.0\"\ndescription = \"\"\nmain_script = \"./main.py\"\npython = \"python3\"\n\n[packages]\nsix = \"1.16.0\"\n\n[scripts]\n",
+    )
+    .unwrap();
+    let status = Command::new("python3")
+        .args(["-m", "venv"])
+        .arg(source.join("venv"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+    for args in [&["lock"][..], &["bundle", "create", "../demo.tar.gz"]] {
+        cargo_bin_cmd!("ppmm")
+            .current_dir(&source)
+            .args(args)
+            .assert()
+            .success();
+    }
+
+    // Installing needs no index
+    let target = dir.path().join("target");
+    cargo_bin_cmd!("ppmm")
+        .current_dir(dir.path())
+        .args(["bundle", "install", "demo.tar.gz", "--dir", "target"])
+        .env("PIP_INDEX_URL", "http://127.0.0.1:9/simple")
+        .assert()
+        .success();
+    let bin = if cfg!(windows) { "Scripts" } else { "bin" };
+    let output = Command::new(target.join("venv").join(bin).join("python"))
+        .args(["-c", "import six; print(six.__version__)"])
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "1.16.0");
+}
@@ -0,0 +1,139 @@
+//! A fake PyPI for integration tests: canned JSON API responses and a
+//! search page served on localhost, so commands that look packages up
+//! run without the network.
+//!
+//! Point a project at it with [`FakePypi::simple_url`] as its
+//! `index-url`, or put it in PyPI's place with [`FakePypi::url`] in
+//! `PPM_PYPI_URL`.
+
+// Each test crate uses its own part of this
+#![allow(dead_code)]
+
+use httpmock::Mock;
+use httpmock::prelude::*;
+use std::path::Path;
+use std::time::Duration;
+
+/// requests' releases: 2.32.3 is latest, 2.32.0 is yanked and 3.0.0b1 is
+/// a pre-release
+pub const REQUESTS: &str = include_str!("../fixtures/pypi-releases.json");
+
+/// A package with nothing but pre-releases
+pub const NIGHTLY: &str = r#"{
+    "info": {"name": "nightly", "version": "1.0.0b2"},
+    "releases": {
+        "1.0.0a1": [{"filename": "nightly-1.0.0a1.tar.gz", "yanked": false, "upload_time": "2024-03-01T09:00:00"}],
+        "1.0.0b2": [{"filename": "nightly-1.0.0b2.tar.gz", "yanked": false, "upload_time": "2024-04-01T09:00:00"}]
+    }
+}"#;
+
+/// The name the fake index answers 404 for
+pub const MISSING: &str = "requestss";
+
+/// PyPI's search results for [`MISSING`]
+const SEARCH_RESULTS: &str = r#"<ul>
+<li><span class="package-snippet__name">requests</span></li>
+<li><span class="package-snippet__name">requests-oauthlib</span></li>
+</ul>"#;
+
+/// A running fake PyPI, stopped when dropped
+pub struct FakePypi {
+    server: MockServer,
+}
+
+impl FakePypi {
+    /// Serve `requests` ([`REQUESTS`]), `nightly` ([`NIGHTLY`]), a 404 for
+    /// [`MISSING`] and a search page, see [`FakePypi::package`]
+    pub fn start() -> FakePypi {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path(format!("/pypi/{}/json", MISSING));
+            then.status(404)
+                .header("content-type", "application/json")
+                .body(r#"{"message": "Not Found"}"#);
+        });
+        server.mock(|when, then| {
+            when.method(GET).path("/search/");
+            then.status(200)
+                .header("content-type", "text/html")
+                .body(SEARCH_RESULTS);
+        });
+        let pypi = FakePypi { server };
+        pypi.package("requests", REQUESTS);
+        pypi.package("nightly", NIGHTLY);
+        pypi
+    }
+
+    /// Serve `body` as the JSON of `name`, and of each of its releases
+    pub fn package(&self, name: &str, body: &str) -> Mock<'_> {
+        self.server.mock(|when, then| {
+            when.method(GET)
+                .path_matches(format!("^/pypi/{}/([^/]+/)?json$", name).as_str());
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(body);
+        })
+    }
+
+    /// Answer every lookup of `name` with `status` and return the mock,
+    /// to count how often it was asked
+    pub fn fail(&self, name: &str, status: u16) -> Mock<'_> {
+        self.server.mock(|when, then| {
+            when.method(GET).path(format!("/pypi/{}/json", name));
+            then.status(status);
+        })
+    }
+
+    /// Have `version` of `name` on the index, as `ppm publish` checks
+    /// before uploading; any other version is a 404
+    pub fn publish(&self, name: &str, version: &str) -> Mock<'_> {
+        self.server.mock(|when, then| {
+            when.method(GET)
+                .path(format!("/pypi/{}/{}/json", name, version));
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(format!(
+                    r#"{{"info": {{"name": "{}", "version": "{}"}}}}"#,
+                    name, version
+                ));
+        })
+    }
+
+    /// Answer every lookup of `name` only after `delay`, like a proxy
+    /// that accepts connections and then hangs
+    pub fn stall(&self, name: &str, delay: Duration) -> Mock<'_> {
+        self.server.mock(|when, then| {
+            when.method(GET).path(format!("/pypi/{}/json", name));
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(NIGHTLY)
+                .delay(delay);
+        })
+    }
+
+    /// Address for `PPM_PYPI_URL`
+    pub fn url(&self) -> String {
+        self.server.base_url()
+    }
+
+    /// Simple index address for a project's `index-url`
+    pub fn simple_url(&self) -> String {
+        self.server.url("/simple")
+    }
+}
+
+/// Write a project.toml to `dir` with the `[packages]` lines `packages`,
+/// looking packages up on `index_url` when given
+pub fn write_project(dir: &Path, index_url: Option<&str>, packages: &str) {
+    let index = index_url
+        .map(|url| format!("index-url = \"{}\"\n", url))
+        .unwrap_or_default();
+    std::fs::write(
+        dir.join("project.toml"),
+        format!(
+            "[project]\nname = \"demo\"\nversion = \"0.1.0\"\ndescription = \"\"\nmain_script = \"./main.py\"\n{}\n[packages]\n{}\n[scripts]\n",
+            index, packages
+        ),
+    )
+    .unwrap();
+}